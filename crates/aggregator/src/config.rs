@@ -0,0 +1,54 @@
+//! Environment configuration shared by the workers.
+
+use std::str::FromStr;
+
+use nostr_sdk::Keys;
+use thiserror::Error;
+
+/// Errors from reading a worker's configuration.
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("invalid {name}: {value:?}")]
+    Invalid { name: &'static str, value: String },
+}
+
+/// Parse `name` from the environment, or `default` when it is unset.
+pub(crate) fn env_or<T>(name: &'static str, default: T) -> Result<T, ConfigError>
+where
+    T: FromStr,
+{
+    match std::env::var(name) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map_err(|_| ConfigError::Invalid { name, value }),
+        Err(_) => Ok(default),
+    }
+}
+
+/// Comma-separated values of `name`, trimmed, or none when it is unset.
+pub(crate) fn env_list(name: &str) -> Vec<String> {
+    std::env::var(name)
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Signing keys from `NOSTR_SECRET_KEY` (hex or `nsec`), or `None` when it is unset.
+pub(crate) fn nostr_keys() -> Result<Option<Keys>, ConfigError> {
+    match std::env::var("NOSTR_SECRET_KEY") {
+        Ok(value) if !value.trim().is_empty() => {
+            Keys::parse(value.trim())
+                .map(Some)
+                .map_err(|_| ConfigError::Invalid {
+                    name: "NOSTR_SECRET_KEY",
+                    // Never echo the key itself
+                    value: "<redacted>".to_string(),
+                })
+        }
+        _ => Ok(None),
+    }
+}
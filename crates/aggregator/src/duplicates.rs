@@ -0,0 +1,227 @@
+//! Duplicate-video detection.
+//!
+//! Re-uploads of the same video by other accounts split its engagement and crowd
+//! trending. Each run compares recent videos and flags every video whose file
+//! SHA-256 (from its `imeta` tag) or title matches an earlier video by a different
+//! pubkey, recording which video it duplicates in a `video_duplicates` snapshot.
+//! Titles match when they are equal ignoring case, punctuation, and spacing, and
+//! only titles long enough not to be generic ("video", "untitled") are compared.
+//! Matches by the same pubkey are left alone: those are usually edits or the same
+//! video in both kinds.
+
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::time::Duration;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use funnel_clickhouse::{ClickHouseError, DuplicateCandidate, DuplicateQueries, VideoDuplicate};
+
+use crate::config::{ConfigError, env_or};
+
+/// How often duplicates are recomputed by default.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Duplicate detection parameters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicateConfig {
+    /// Time between runs.
+    pub interval: Duration,
+    /// Only videos created this recently are compared.
+    pub window: Duration,
+    /// Shortest normalized title compared, in characters.
+    pub min_title_chars: usize,
+}
+
+impl Default for DuplicateConfig {
+    fn default() -> Self {
+        Self {
+            interval: DEFAULT_INTERVAL,
+            window: Duration::from_secs(90 * 24 * 60 * 60),
+            min_title_chars: 12,
+        }
+    }
+}
+
+impl DuplicateConfig {
+    /// Defaults overridden by `DUPLICATES_*` environment variables.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let defaults = Self::default();
+
+        Ok(Self {
+            interval: Duration::from_secs(env_or(
+                "DUPLICATES_INTERVAL_SECS",
+                defaults.interval.as_secs(),
+            )?),
+            window: Duration::from_secs(
+                env_or("DUPLICATES_WINDOW_DAYS", defaults.window.as_secs() / 86_400)? * 86_400,
+            ),
+            min_title_chars: env_or("DUPLICATES_MIN_TITLE_CHARS", defaults.min_title_chars)?,
+        })
+    }
+
+    /// Find the re-uploads among `videos` as of `computed_at`, in the order the
+    /// re-uploads were created.
+    ///
+    /// The earliest video with a given file or title is the original; later ones by
+    /// other pubkeys duplicate it. A file match wins over a title match.
+    pub fn find(
+        &self,
+        videos: &[DuplicateCandidate],
+        computed_at: DateTime<Utc>,
+    ) -> Vec<VideoDuplicate> {
+        let mut videos: Vec<&DuplicateCandidate> = videos.iter().collect();
+        videos.sort_by(|a, b| (a.created_at, &a.id).cmp(&(b.created_at, &b.id)));
+
+        let mut by_sha256: HashMap<&str, &DuplicateCandidate> = HashMap::new();
+        let mut by_title: HashMap<String, &DuplicateCandidate> = HashMap::new();
+        let mut duplicates = Vec::new();
+        for video in videos {
+            let sha256 = is_sha256(&video.sha256)
+                .then(|| original(by_sha256.entry(video.sha256.as_str()), video))
+                .flatten()
+                .map(|original| (original, VideoDuplicate::REASON_SHA256));
+            let title = normalize_title(&video.title);
+            let title = (title.chars().count() >= self.min_title_chars)
+                .then(|| original(by_title.entry(title), video))
+                .flatten()
+                .map(|original| (original, VideoDuplicate::REASON_TITLE));
+
+            if let Some((original, reason)) = sha256.or(title) {
+                duplicates.push(VideoDuplicate {
+                    computed_at,
+                    event_id: video.id.clone(),
+                    duplicate_of: original.id.clone(),
+                    reason: reason.to_string(),
+                });
+            }
+        }
+        duplicates
+    }
+}
+
+/// The original that `video` duplicates under `entry`, if it's by another pubkey.
+/// The first video under a key becomes its original.
+fn original<'a, K>(
+    entry: Entry<'_, K, &'a DuplicateCandidate>,
+    video: &'a DuplicateCandidate,
+) -> Option<&'a DuplicateCandidate> {
+    let original = *entry.or_insert(video);
+    (original.pubkey != video.pubkey).then_some(original)
+}
+
+/// Whether `value` is a lowercase hex SHA-256.
+fn is_sha256(value: &str) -> bool {
+    value.len() == 64
+        && value
+            .bytes()
+            .all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// `title` lowercased, with runs of anything but letters and digits replaced by a
+/// single space.
+pub fn normalize_title(title: &str) -> String {
+    title
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Compute and store a snapshot as of `now`, returning how many duplicates it holds.
+pub async fn run_once<S>(
+    storage: &S,
+    config: &DuplicateConfig,
+    now: DateTime<Utc>,
+) -> Result<usize, ClickHouseError>
+where
+    S: DuplicateQueries,
+{
+    let created_after = now - TimeDelta::from_std(config.window).unwrap_or(TimeDelta::MAX);
+    let videos = storage.get_duplicate_candidates(created_after).await?;
+    let duplicates = config.find(&videos, now);
+    storage.insert_video_duplicates(&duplicates).await?;
+    Ok(duplicates.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn video(
+        id: &str,
+        pubkey: &str,
+        minutes: i64,
+        title: &str,
+        sha256: &str,
+    ) -> DuplicateCandidate {
+        DuplicateCandidate {
+            id: id.to_string(),
+            pubkey: pubkey.to_string(),
+            created_at: now() + TimeDelta::minutes(minutes),
+            title: title.to_string(),
+            sha256: sha256.to_string(),
+        }
+    }
+
+    fn now() -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000, 0).unwrap()
+    }
+
+    fn found(duplicates: &[VideoDuplicate]) -> Vec<(&str, &str, &str)> {
+        duplicates
+            .iter()
+            .map(|d| {
+                (
+                    d.event_id.as_str(),
+                    d.duplicate_of.as_str(),
+                    d.reason.as_str(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn normalize_title_ignores_case_and_punctuation() {
+        assert_eq!(
+            normalize_title("  My Trip to Lisbon!! (4K) "),
+            "my trip to lisbon 4k"
+        );
+        assert_eq!(
+            normalize_title("my-trip_to.lisbon 4k"),
+            "my trip to lisbon 4k"
+        );
+        assert_eq!(normalize_title("!!!"), "");
+    }
+
+    #[test]
+    fn find_flags_later_uploads_by_other_pubkeys() {
+        let sha = "a".repeat(64);
+        let videos = [
+            video("copy", "thief", 10, "Something else", &sha),
+            video("orig", "alice", 0, "Sunset over the harbour", &sha),
+            video("retitled", "bob", 20, "sunset, over the HARBOUR", ""),
+            video("edit", "alice", 30, "Sunset over the harbour", &sha),
+        ];
+
+        let duplicates = DuplicateConfig::default().find(&videos, now());
+
+        assert_eq!(
+            found(&duplicates),
+            [("copy", "orig", "sha256"), ("retitled", "orig", "title")]
+        );
+        assert!(duplicates.iter().all(|d| d.computed_at == now()));
+    }
+
+    #[test]
+    fn find_skips_short_titles_and_malformed_hashes() {
+        let videos = [
+            video("a", "alice", 0, "Untitled", "not-a-hash"),
+            video("b", "bob", 1, "untitled", "not-a-hash"),
+            video("c", "carol", 2, "", &"A".repeat(64)),
+            video("d", "dave", 3, "", &"A".repeat(64)),
+        ];
+
+        assert!(DuplicateConfig::default().find(&videos, now()).is_empty());
+    }
+}
@@ -0,0 +1,470 @@
+//! NIP-90 data vending machine for video discovery.
+//!
+//! Listens on the configured relays for content discovery job requests
+//! (kind 5300) and answers each with a job result (kind 6300) whose content is
+//! the JSON array of `a`/`e` tags of the videos found, the form NIP-90 discovery
+//! clients render as a feed. Requests addressed to another provider with a `p` tag
+//! are ignored, as are encrypted ones.
+//!
+//! A request picks its results with:
+//!
+//! - an `i` input of type `text`: `#tag` searches a hashtag, anything else searches
+//!   titles
+//! - `param sort trending|recent` without an input (default `trending`)
+//! - `param max_results <n>` (default and cap set in configuration)
+//! - `param min_trust <0..1>` for trending and recent results
+//!
+//! With `DVM_PRICE_MSATS` set, results carry an `amount` tag so clients can zap the
+//! result; results are delivered either way. On startup the service announces
+//! itself with a NIP-89 handler event (kind 31990) so clients can find it.
+
+use std::time::Duration;
+
+use funnel_clickhouse::{ClickHouseError, VideoQueries};
+use funnel_observability::aggregator;
+use metrics::counter;
+use nostr_sdk::prelude::*;
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::config::{ConfigError, env_list, env_or, nostr_keys};
+use crate::publish::video_tag;
+
+/// Content discovery job request.
+pub const KIND_DISCOVERY_REQUEST: u16 = 5300;
+/// Content discovery job result.
+pub const KIND_DISCOVERY_RESULT: u16 = 6300;
+/// Job feedback.
+pub const KIND_JOB_FEEDBACK: u16 = 7000;
+/// Handler information (NIP-89).
+pub const KIND_HANDLER_INFO: u16 = 31990;
+
+/// DVM parameters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DvmConfig {
+    /// Signing keys, set when the DVM is enabled.
+    pub keys: Option<Keys>,
+    /// Relays to listen for requests on and publish results to.
+    pub relays: Vec<String>,
+    /// Results when a request doesn't ask for a number.
+    pub default_results: u32,
+    /// Most results returned for one request.
+    pub max_results: u32,
+    /// Price asked for each result set, in millisatoshis.
+    pub price_msats: Option<u64>,
+    /// Name shown in the handler announcement.
+    pub name: String,
+    /// Description shown in the handler announcement.
+    pub about: String,
+}
+
+impl Default for DvmConfig {
+    fn default() -> Self {
+        Self {
+            keys: None,
+            relays: Vec::new(),
+            default_results: 20,
+            max_results: 100,
+            price_msats: None,
+            name: "Funnel".to_string(),
+            about: "Trending, recent, and searched Nostr videos".to_string(),
+        }
+    }
+}
+
+impl DvmConfig {
+    /// Defaults overridden by `DVM_*` environment variables, signing with
+    /// `NOSTR_SECRET_KEY` when `DVM_ENABLED` is set.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let defaults = Self::default();
+
+        let keys = if env_or("DVM_ENABLED", false)? {
+            Some(nostr_keys()?.ok_or(ConfigError::Invalid {
+                name: "NOSTR_SECRET_KEY",
+                value: String::new(),
+            })?)
+        } else {
+            None
+        };
+        let relays = env_list("DVM_RELAYS");
+        if keys.is_some() && relays.is_empty() {
+            return Err(ConfigError::Invalid {
+                name: "DVM_RELAYS",
+                value: String::new(),
+            });
+        }
+
+        let price_msats = env_or("DVM_PRICE_MSATS", 0)?;
+        Ok(Self {
+            keys,
+            relays,
+            default_results: env_or("DVM_DEFAULT_RESULTS", defaults.default_results)?,
+            max_results: env_or("DVM_MAX_RESULTS", defaults.max_results)?,
+            price_msats: (price_msats > 0).then_some(price_msats),
+            name: env_or("DVM_NAME", defaults.name)?,
+            about: env_or("DVM_ABOUT", defaults.about)?,
+        })
+    }
+
+    /// Whether the DVM runs.
+    pub fn enabled(&self) -> bool {
+        self.keys.is_some()
+    }
+
+    /// Read the job from a request, or `Ok(None)` when it isn't for this provider.
+    pub fn parse_job(&self, request: &Event, provider: &PublicKey) -> Result<Option<Job>, String> {
+        let tags: Vec<&[String]> = request.tags.iter().map(Tag::as_slice).collect();
+        let providers: Vec<&str> = tags
+            .iter()
+            .filter(|tag| tag.first().map(String::as_str) == Some("p"))
+            .filter_map(|tag| tag.get(1).map(String::as_str))
+            .collect();
+        if !providers.is_empty() && !providers.contains(&provider.to_hex().as_str()) {
+            return Ok(None);
+        }
+        if tags
+            .iter()
+            .any(|tag| tag.first().map(String::as_str) == Some("encrypted"))
+        {
+            return Ok(None);
+        }
+
+        let param = |name: &str| {
+            tags.iter()
+                .find(|tag| {
+                    tag.first().map(String::as_str) == Some("param")
+                        && tag.get(1).map(String::as_str) == Some(name)
+                })
+                .and_then(|tag| tag.get(2))
+                .map(|value| value.trim())
+        };
+
+        let limit = match param("max_results") {
+            Some(value) => value
+                .parse::<u32>()
+                .map_err(|_| format!("invalid max_results: {value:?}"))?,
+            None => self.default_results,
+        }
+        .clamp(1, self.max_results.max(1));
+
+        let min_trust = match param("min_trust") {
+            Some(value) => Some(
+                value
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|min| (0.0..=1.0).contains(min))
+                    .ok_or_else(|| format!("invalid min_trust: {value:?}"))?,
+            ),
+            None => None,
+        };
+
+        let input = tags
+            .iter()
+            .find(|tag| {
+                tag.first().map(String::as_str) == Some("i")
+                    && tag.get(2).is_none_or(|kind| kind == "text")
+            })
+            .and_then(|tag| tag.get(1))
+            .map(|input| input.trim())
+            .filter(|input| !input.is_empty());
+
+        let source = match (input, param("sort")) {
+            (Some(input), _) => match input.strip_prefix('#') {
+                Some(hashtag) => Source::Hashtag(hashtag.to_lowercase()),
+                None => Source::Text(input.to_string()),
+            },
+            (None, None | Some("trending")) => Source::Trending,
+            (None, Some("recent")) => Source::Recent,
+            (None, Some(sort)) => return Err(format!("unsupported sort: {sort:?}")),
+        };
+
+        Ok(Some(Job {
+            source,
+            limit,
+            min_trust,
+        }))
+    }
+
+    /// Job result answering `request` with `videos` (tags from [`Job::run`]).
+    pub fn result(&self, request: &Event, videos: &[Vec<String>]) -> EventBuilder {
+        let content = serde_json::to_string(videos).unwrap_or_else(|_| "[]".to_string());
+        let mut tags = vec![
+            Tag::custom(TagKind::custom("request"), [request.as_json()]),
+            Tag::event(request.id),
+            Tag::public_key(request.pubkey),
+        ];
+        tags.extend(
+            request
+                .tags
+                .iter()
+                .filter(|tag| tag.as_slice().first().map(String::as_str) == Some("i"))
+                .cloned(),
+        );
+        if let Some(price) = self.price_msats {
+            tags.push(Tag::custom(TagKind::custom("amount"), [price.to_string()]));
+        }
+        EventBuilder::new(Kind::from(KIND_DISCOVERY_RESULT), content).tags(tags)
+    }
+
+    /// Feedback telling the requester their job failed.
+    pub fn error(&self, request: &Event, message: &str) -> EventBuilder {
+        EventBuilder::new(Kind::from(KIND_JOB_FEEDBACK), "").tags([
+            Tag::custom(TagKind::custom("status"), ["error", message]),
+            Tag::event(request.id),
+            Tag::public_key(request.pubkey),
+        ])
+    }
+
+    /// NIP-89 announcement of this DVM.
+    pub fn announcement(&self) -> EventBuilder {
+        let content = serde_json::json!({ "name": self.name, "about": self.about });
+        EventBuilder::new(Kind::from(KIND_HANDLER_INFO), content.to_string()).tags([
+            Tag::identifier("funnel-video-discovery"),
+            Tag::custom(TagKind::k(), [KIND_DISCOVERY_REQUEST.to_string()]),
+        ])
+    }
+}
+
+/// Where a job's results come from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    Trending,
+    Recent,
+    Hashtag(String),
+    Text(String),
+}
+
+/// A content discovery job.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Job {
+    pub source: Source,
+    pub limit: u32,
+    pub min_trust: Option<f64>,
+}
+
+impl Job {
+    /// Find the job's videos, as `a`/`e` tags in result order.
+    pub async fn run<S>(&self, storage: &S) -> Result<Vec<Vec<String>>, ClickHouseError>
+    where
+        S: VideoQueries,
+    {
+        let tags = match &self.source {
+            Source::Trending => storage
+                .get_trending_videos(self.min_trust, self.limit)
+                .await?
+                .iter()
+                .map(|v| video_tag(&v.id, v.kind, &v.pubkey, &v.d_tag))
+                .collect(),
+            Source::Recent => storage
+                .get_recent_videos(None, self.min_trust, self.limit)
+                .await?
+                .iter()
+                .map(|v| video_tag(&v.id, v.kind, &v.pubkey, &v.d_tag))
+                .collect(),
+            Source::Hashtag(hashtag) => storage
+                .search_by_hashtag(hashtag, None, self.limit)
+                .await?
+                .iter()
+                .map(|v| video_tag(&v.event_id, v.kind, &v.pubkey, &v.d_tag))
+                .collect(),
+            Source::Text(query) => storage
+                .search_by_text(query, None, self.limit)
+                .await?
+                .iter()
+                .map(|v| video_tag(&v.id, v.kind, &v.pubkey, &v.d_tag))
+                .collect(),
+        };
+        Ok(tags)
+    }
+}
+
+/// Connect a client that signs with `keys` and reads and writes `relays`.
+pub async fn connect(keys: Keys, relays: &[String]) -> Result<Client, nostr_sdk::client::Error> {
+    let client = Client::builder().signer(keys).build();
+    for relay in relays {
+        client.add_relay(relay.as_str()).await?;
+    }
+    client.connect().await;
+    Ok(client)
+}
+
+/// Announce the DVM, then answer job requests until the client shuts down.
+pub async fn serve<S>(
+    storage: &S,
+    client: &Client,
+    provider: PublicKey,
+    config: &DvmConfig,
+) -> Result<(), nostr_sdk::client::Error>
+where
+    S: VideoQueries,
+{
+    if let Err(e) = client.send_event_builder(config.announcement()).await {
+        tracing::warn!(error = %e, "Failed to announce DVM");
+    }
+
+    let mut notifications = client.notifications();
+    let filter = Filter::new()
+        .kind(Kind::from(KIND_DISCOVERY_REQUEST))
+        .since(Timestamp::now());
+    client.subscribe(filter, None).await?;
+
+    loop {
+        match notifications.recv().await {
+            Ok(RelayPoolNotification::Event { event, .. })
+                if event.kind == Kind::from(KIND_DISCOVERY_REQUEST) =>
+            {
+                handle(storage, client, &provider, config, &event).await;
+            }
+            Ok(RelayPoolNotification::Shutdown) | Err(RecvError::Closed) => return Ok(()),
+            Ok(_) => {}
+            Err(RecvError::Lagged(skipped)) => {
+                tracing::warn!(skipped, "DVM fell behind; skipped job requests");
+            }
+        }
+    }
+}
+
+/// Answer one job request.
+async fn handle<S>(
+    storage: &S,
+    client: &Client,
+    provider: &PublicKey,
+    config: &DvmConfig,
+    request: &Event,
+) where
+    S: VideoQueries,
+{
+    let reply = match config.parse_job(request, provider) {
+        Ok(None) => return,
+        Ok(Some(job)) => match job.run(storage).await {
+            Ok(videos) => {
+                counter!(aggregator::DVM_JOBS, "status" => "ok").increment(1);
+                config.result(request, &videos)
+            }
+            Err(e) => {
+                counter!(aggregator::DVM_JOBS, "status" => "error").increment(1);
+                tracing::error!(code = %e.code(), error = %e, job = %request.id, "DVM job failed");
+                config.error(request, "Internal error")
+            }
+        },
+        Err(message) => {
+            counter!(aggregator::DVM_JOBS, "status" => "invalid").increment(1);
+            config.error(request, &message)
+        }
+    };
+
+    // Don't let a slow relay hold up the next request
+    match tokio::time::timeout(Duration::from_secs(10), client.send_event_builder(reply)).await {
+        Ok(Ok(_)) => {}
+        Ok(Err(e)) => tracing::warn!(error = %e, job = %request.id, "Failed to send DVM reply"),
+        Err(_) => tracing::warn!(job = %request.id, "Timed out sending DVM reply"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(keys: &Keys, tags: &[&[&str]]) -> Event {
+        EventBuilder::new(Kind::from(KIND_DISCOVERY_REQUEST), "")
+            .tags(
+                tags.iter()
+                    .map(|tag| Tag::parse(tag.iter().copied()).unwrap()),
+            )
+            .sign_with_keys(keys)
+            .unwrap()
+    }
+
+    fn config() -> DvmConfig {
+        DvmConfig {
+            default_results: 20,
+            max_results: 50,
+            ..DvmConfig::default()
+        }
+    }
+
+    #[test]
+    fn parse_job_reads_inputs_and_params() {
+        let customer = Keys::generate();
+        let provider = Keys::generate().public_key();
+        let job = |tags: &[&[&str]]| config().parse_job(&request(&customer, tags), &provider);
+
+        assert_eq!(
+            job(&[]).unwrap(),
+            Some(Job {
+                source: Source::Trending,
+                limit: 20,
+                min_trust: None,
+            })
+        );
+        assert_eq!(
+            job(&[
+                &["param", "sort", "recent"],
+                &["param", "max_results", "500"],
+                &["param", "min_trust", "0.5"],
+            ])
+            .unwrap(),
+            Some(Job {
+                source: Source::Recent,
+                limit: 50,
+                min_trust: Some(0.5),
+            })
+        );
+        assert_eq!(
+            job(&[&["i", "#Nostr", "text"]]).unwrap().unwrap().source,
+            Source::Hashtag("nostr".to_string())
+        );
+        assert_eq!(
+            job(&[&["i", "cat videos", "text"]])
+                .unwrap()
+                .unwrap()
+                .source,
+            Source::Text("cat videos".to_string())
+        );
+
+        assert!(job(&[&["param", "sort", "oldest"]]).is_err());
+        assert!(job(&[&["param", "min_trust", "2"]]).is_err());
+    }
+
+    #[test]
+    fn parse_job_skips_requests_for_other_providers() {
+        let customer = Keys::generate();
+        let provider = Keys::generate().public_key();
+        let other = Keys::generate().public_key().to_hex();
+        let mine = provider.to_hex();
+
+        let for_other = request(&customer, &[&["p", &other]]);
+        assert_eq!(config().parse_job(&for_other, &provider), Ok(None));
+
+        let for_me = request(&customer, &[&["p", &mine]]);
+        assert!(config().parse_job(&for_me, &provider).unwrap().is_some());
+
+        let encrypted = request(&customer, &[&["encrypted"]]);
+        assert_eq!(config().parse_job(&encrypted, &provider), Ok(None));
+    }
+
+    #[test]
+    fn result_references_request_and_lists_videos() {
+        let customer = Keys::generate();
+        let request = request(&customer, &[&["i", "#nostr", "text"]]);
+        let videos = vec![vec!["e".to_string(), "ab".repeat(32)]];
+        let config = DvmConfig {
+            price_msats: Some(1000),
+            ..config()
+        };
+
+        let result = config
+            .result(&request, &videos)
+            .sign_with_keys(&Keys::generate())
+            .unwrap();
+
+        assert_eq!(result.kind, Kind::from(KIND_DISCOVERY_RESULT));
+        let listed: Vec<Vec<String>> = serde_json::from_str(&result.content).unwrap();
+        assert_eq!(listed, videos);
+        let tags: Vec<&[String]> = result.tags.iter().map(Tag::as_slice).collect();
+        assert!(tags.contains(&&["e".to_string(), request.id.to_hex()][..]));
+        assert!(tags.contains(&&["p".to_string(), customer.public_key().to_hex()][..]));
+        assert!(tags.contains(&&["amount".to_string(), "1000".to_string()][..]));
+        assert!(tags.iter().any(|tag| tag[0] == "i" && tag[1] == "#nostr"));
+        assert!(tags.iter().any(|tag| tag[0] == "request"));
+    }
+}
@@ -0,0 +1,19 @@
+//! Funnel aggregation workers.
+//!
+//! Derived tables that are too costly or too opinionated to compute on every read
+//! are rebuilt here on a schedule, with their parameters set in code and
+//! configuration rather than buried in SQL views.
+
+mod config;
+pub mod duplicates;
+pub mod dvm;
+pub mod media;
+pub mod probe;
+pub mod profiles;
+pub mod publish;
+pub mod service;
+pub mod trending;
+pub mod verify;
+pub mod wot;
+
+pub use self::config::ConfigError;
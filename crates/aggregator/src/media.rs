@@ -0,0 +1,330 @@
+//! Media URL health checks.
+//!
+//! Each run probes the video and thumbnail URLs of recent videos that haven't been
+//! checked lately (or ever) and records the outcome in `media_health`. A URL is
+//! available when it answers 2xx with something other than an HTML page, which is
+//! what most hosts serve in place of a deleted file. After
+//! [`DEAD_AFTER_FAILURES`] failed checks in a row a video's media counts as dead,
+//! and the API can hide the video from listings.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use funnel_clickhouse::{
+    ClickHouseError, DEAD_AFTER_FAILURES, MediaHealth, MediaQueries, MediaTarget,
+};
+use funnel_observability::aggregator;
+use metrics::counter;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use crate::config::{ConfigError, env_or};
+use crate::probe::{Probe, Probed};
+
+/// How often media is checked by default.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Media checker parameters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MediaCheckConfig {
+    /// Whether the checker runs at all.
+    pub enabled: bool,
+    /// Time between runs.
+    pub interval: Duration,
+    /// Only media of videos created this recently is checked.
+    pub window: Duration,
+    /// Minimum time between checks of the same URL.
+    pub recheck: Duration,
+    /// URLs checked per run.
+    pub limit: u32,
+    /// Timeout for each probe.
+    pub timeout: Duration,
+    /// Probes in flight at once.
+    pub concurrency: usize,
+}
+
+impl Default for MediaCheckConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            interval: DEFAULT_INTERVAL,
+            window: Duration::from_secs(7 * 24 * 60 * 60),
+            recheck: Duration::from_secs(6 * 60 * 60),
+            limit: 500,
+            timeout: Duration::from_secs(10),
+            concurrency: 16,
+        }
+    }
+}
+
+impl MediaCheckConfig {
+    /// Defaults overridden by `MEDIA_CHECK_*` environment variables.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let defaults = Self::default();
+
+        let concurrency = env_or("MEDIA_CHECK_CONCURRENCY", defaults.concurrency)?;
+        if concurrency == 0 {
+            return Err(ConfigError::Invalid {
+                name: "MEDIA_CHECK_CONCURRENCY",
+                value: concurrency.to_string(),
+            });
+        }
+
+        Ok(Self {
+            enabled: env_or("MEDIA_CHECK_ENABLED", defaults.enabled)?,
+            interval: Duration::from_secs(env_or(
+                "MEDIA_CHECK_INTERVAL_SECS",
+                defaults.interval.as_secs(),
+            )?),
+            window: Duration::from_secs(
+                env_or(
+                    "MEDIA_CHECK_WINDOW_DAYS",
+                    defaults.window.as_secs() / 86_400,
+                )? * 86_400,
+            ),
+            recheck: Duration::from_secs(
+                env_or(
+                    "MEDIA_CHECK_RECHECK_HOURS",
+                    defaults.recheck.as_secs() / 3600,
+                )? * 3600,
+            ),
+            limit: env_or("MEDIA_CHECK_LIMIT", defaults.limit)?,
+            timeout: Duration::from_secs(env_or(
+                "MEDIA_CHECK_TIMEOUT_SECS",
+                defaults.timeout.as_secs(),
+            )?),
+            concurrency,
+        })
+    }
+}
+
+/// Health of `target` given what the probe found at `checked_at`.
+pub fn assess(target: MediaTarget, probed: Probed, checked_at: DateTime<Utc>) -> MediaHealth {
+    let html = probed
+        .content_type
+        .trim_start()
+        .to_ascii_lowercase()
+        .starts_with("text/html");
+    let available = (200..300).contains(&probed.status) && !html;
+
+    MediaHealth {
+        failures: if available {
+            0
+        } else {
+            target.failures.saturating_add(1)
+        },
+        event_id: target.event_id,
+        url: target.url,
+        media_type: target.media_type,
+        available,
+        status: probed.status,
+        content_type: probed.content_type,
+        checked_at,
+    }
+}
+
+/// Check the media due as of `now` and store the results, returning how many URLs
+/// were checked.
+pub async fn run_once<S, P>(
+    storage: &S,
+    probe: &P,
+    config: &MediaCheckConfig,
+    now: DateTime<Utc>,
+) -> Result<usize, ClickHouseError>
+where
+    S: MediaQueries,
+    P: Probe + Clone + 'static,
+{
+    let created_after = now - TimeDelta::from_std(config.window).unwrap_or(TimeDelta::MAX);
+    let checked_before = now - TimeDelta::from_std(config.recheck).unwrap_or(TimeDelta::MAX);
+    let targets = storage
+        .get_media_targets(created_after, checked_before, config.limit)
+        .await?;
+
+    let permits = Arc::new(Semaphore::new(config.concurrency.max(1)));
+    let mut probes = JoinSet::new();
+    for target in targets {
+        let probe = probe.clone();
+        let permits = permits.clone();
+        probes.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            let probed = probe.probe(&target.url).await;
+            assess(target, probed, Utc::now())
+        });
+    }
+
+    let mut checks = Vec::with_capacity(probes.len());
+    while let Some(result) = probes.join_next().await {
+        match result {
+            Ok(check) => {
+                let result = match (check.available, check.failures >= DEAD_AFTER_FAILURES) {
+                    (true, _) => "available",
+                    (false, false) => "failed",
+                    (false, true) => "dead",
+                };
+                counter!(
+                    aggregator::MEDIA_CHECKS,
+                    "media_type" => check.media_type.clone(),
+                    "result" => result
+                )
+                .increment(1);
+                checks.push(check);
+            }
+            Err(e) => tracing::warn!(error = %e, "Media probe task failed"),
+        }
+    }
+
+    if !checks.is_empty() {
+        storage.insert_media_health(&checks).await?;
+    }
+    Ok(checks.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    use funnel_clickhouse::{MediaVerification, VerifyTarget};
+
+    use super::*;
+
+    fn target(url: &str, failures: u32) -> MediaTarget {
+        MediaTarget {
+            event_id: "e1".to_string(),
+            url: url.to_string(),
+            media_type: MediaHealth::MEDIA_VIDEO.to_string(),
+            failures,
+        }
+    }
+
+    fn probed(status: u16, content_type: &str) -> Probed {
+        Probed {
+            status,
+            content_type: content_type.to_string(),
+        }
+    }
+
+    fn now() -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000, 0).unwrap()
+    }
+
+    #[derive(Clone, Default)]
+    struct MockProbe {
+        responses: Arc<HashMap<String, Probed>>,
+    }
+
+    impl Probe for MockProbe {
+        async fn probe(&self, url: &str) -> Probed {
+            self.responses
+                .get(url)
+                .cloned()
+                .unwrap_or_else(Probed::failed)
+        }
+    }
+
+    #[derive(Default)]
+    struct MockStorage {
+        targets: Vec<MediaTarget>,
+        window: Mutex<Option<(DateTime<Utc>, DateTime<Utc>)>>,
+        written: Mutex<Vec<MediaHealth>>,
+    }
+
+    impl MediaQueries for MockStorage {
+        async fn get_media_targets(
+            &self,
+            created_after: DateTime<Utc>,
+            checked_before: DateTime<Utc>,
+            _limit: u32,
+        ) -> Result<Vec<MediaTarget>, ClickHouseError> {
+            *self.window.lock().unwrap() = Some((created_after, checked_before));
+            Ok(self.targets.clone())
+        }
+
+        async fn insert_media_health(&self, checks: &[MediaHealth]) -> Result<(), ClickHouseError> {
+            self.written.lock().unwrap().extend_from_slice(checks);
+            Ok(())
+        }
+
+        async fn get_verify_targets(
+            &self,
+            _created_after: DateTime<Utc>,
+            _checked_before: DateTime<Utc>,
+            _limit: u32,
+        ) -> Result<Vec<VerifyTarget>, ClickHouseError> {
+            Ok(Vec::new())
+        }
+
+        async fn insert_media_verifications(
+            &self,
+            _verifications: &[MediaVerification],
+        ) -> Result<(), ClickHouseError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn assess_counts_consecutive_failures() {
+        let ok = assess(target("a", 2), probed(200, "video/mp4"), now());
+        assert!(ok.available);
+        assert_eq!(ok.failures, 0);
+
+        let missing = assess(target("a", 2), probed(404, ""), now());
+        assert!(!missing.available);
+        assert_eq!(missing.failures, 3);
+        assert!(missing.is_dead());
+
+        let unreachable = assess(target("a", 0), Probed::failed(), now());
+        assert_eq!(unreachable.status, 0);
+        assert_eq!(unreachable.failures, 1);
+        assert!(!unreachable.is_dead());
+    }
+
+    #[test]
+    fn assess_treats_html_pages_as_unavailable() {
+        let page = assess(
+            target("a", 0),
+            probed(200, "Text/HTML; charset=utf-8"),
+            now(),
+        );
+        assert!(!page.available);
+        assert_eq!(page.failures, 1);
+    }
+
+    #[tokio::test]
+    async fn run_once_checks_due_targets() {
+        let storage = MockStorage {
+            targets: vec![target("https://ok", 1), target("https://gone", 0)],
+            ..MockStorage::default()
+        };
+        let probe = MockProbe {
+            responses: Arc::new(HashMap::from([(
+                "https://ok".to_string(),
+                probed(206, "video/mp4"),
+            )])),
+        };
+        let config = MediaCheckConfig {
+            window: Duration::from_secs(86_400),
+            recheck: Duration::from_secs(3600),
+            ..MediaCheckConfig::default()
+        };
+
+        assert_eq!(run_once(&storage, &probe, &config, now()).await.unwrap(), 2);
+        assert_eq!(
+            *storage.window.lock().unwrap(),
+            Some((now() - TimeDelta::days(1), now() - TimeDelta::hours(1)))
+        );
+        let written = storage.written.lock().unwrap();
+        let ok = written
+            .iter()
+            .find(|check| check.url == "https://ok")
+            .unwrap();
+        let gone = written
+            .iter()
+            .find(|check| check.url == "https://gone")
+            .unwrap();
+        assert!(ok.available && ok.failures == 0);
+        assert!(!gone.available && gone.failures == 1);
+    }
+}
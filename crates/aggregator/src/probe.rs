@@ -0,0 +1,263 @@
+//! HTTP probes and downloads of media URLs.
+//!
+//! Media URLs come from arbitrary event authors, so requests only reach `http` and
+//! `https` URLs whose host isn't `localhost` or a loopback, private, or link-local
+//! address literal, and redirects are only followed to URLs passing the same check.
+//! Hostnames that resolve to internal addresses are not caught; run the checker
+//! where it can't reach internal services if that matters.
+
+use std::future::Future;
+use std::net::IpAddr;
+use std::time::Duration;
+
+use reqwest::header::{CONTENT_TYPE, RANGE};
+use reqwest::redirect::Policy;
+use reqwest::{StatusCode, Url};
+use sha2::{Digest, Sha256};
+
+/// Most redirects followed for one request, as with reqwest's default policy.
+const MAX_REDIRECTS: usize = 10;
+
+/// What a probe found at a URL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Probed {
+    /// HTTP status, or 0 when the request failed or was refused.
+    pub status: u16,
+    pub content_type: String,
+}
+
+impl Probed {
+    /// A request that failed or was never made.
+    pub fn failed() -> Self {
+        Self {
+            status: 0,
+            content_type: String::new(),
+        }
+    }
+}
+
+/// Something that can check whether a URL is reachable.
+pub trait Probe: Send + Sync {
+    fn probe(&self, url: &str) -> impl Future<Output = Probed> + Send;
+}
+
+/// What downloading a file to hash it produced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fetched {
+    /// SHA-256 of the whole file (lowercase hex).
+    Hashed(String),
+    /// The file is larger than the download limit.
+    TooLarge,
+    /// The request failed, was refused, or didn't return 2xx.
+    Failed,
+}
+
+/// Something that can download a file and hash it.
+pub trait Fetch: Send + Sync {
+    /// Download `url`, giving up on files over `max_bytes`.
+    fn sha256(&self, url: &str, max_bytes: u64) -> impl Future<Output = Fetched> + Send;
+}
+
+/// Probe that sends `HEAD`, falling back to a one-byte ranged `GET` for servers that
+/// don't allow `HEAD`.
+#[derive(Debug, Clone)]
+pub struct HttpProbe {
+    client: reqwest::Client,
+}
+
+impl HttpProbe {
+    pub fn new(timeout: Duration) -> Result<Self, reqwest::Error> {
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .user_agent(concat!("funnel-media-check/", env!("CARGO_PKG_VERSION")))
+            .redirect(Policy::custom(|attempt| {
+                match refuse_redirect(attempt.url(), attempt.previous().len()) {
+                    Some(reason) => attempt.error(reason),
+                    None => attempt.follow(),
+                }
+            }))
+            .build()?;
+        Ok(Self { client })
+    }
+}
+
+impl Probe for HttpProbe {
+    async fn probe(&self, url: &str) -> Probed {
+        let Some(parsed) = Url::parse(url).ok().filter(is_public) else {
+            return Probed::failed();
+        };
+
+        let response = match self.client.head(parsed.clone()).send().await {
+            Ok(response)
+                if matches!(
+                    response.status(),
+                    StatusCode::METHOD_NOT_ALLOWED | StatusCode::NOT_IMPLEMENTED
+                ) =>
+            {
+                self.client
+                    .get(parsed)
+                    .header(RANGE, "bytes=0-0")
+                    .send()
+                    .await
+            }
+            response => response,
+        };
+
+        match response {
+            Ok(response) => Probed {
+                status: response.status().as_u16(),
+                content_type: response
+                    .headers()
+                    .get(CONTENT_TYPE)
+                    .and_then(|value| value.to_str().ok())
+                    .unwrap_or_default()
+                    .to_string(),
+            },
+            Err(e) => {
+                tracing::debug!(url, error = %e, "Media probe failed");
+                Probed::failed()
+            }
+        }
+    }
+}
+
+impl Fetch for HttpProbe {
+    async fn sha256(&self, url: &str, max_bytes: u64) -> Fetched {
+        let Some(parsed) = Url::parse(url).ok().filter(is_public) else {
+            return Fetched::Failed;
+        };
+
+        let mut response = match self.client.get(parsed).send().await {
+            Ok(response) if response.status().is_success() => response,
+            Ok(_) => return Fetched::Failed,
+            Err(e) => {
+                tracing::debug!(url, error = %e, "Media download failed");
+                return Fetched::Failed;
+            }
+        };
+        if response.content_length().is_some_and(|len| len > max_bytes) {
+            return Fetched::TooLarge;
+        }
+
+        let mut hasher = Sha256::new();
+        let mut read = 0u64;
+        loop {
+            match response.chunk().await {
+                Ok(Some(chunk)) => {
+                    read += chunk.len() as u64;
+                    if read > max_bytes {
+                        return Fetched::TooLarge;
+                    }
+                    hasher.update(&chunk);
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    tracing::debug!(url, error = %e, "Media download failed");
+                    return Fetched::Failed;
+                }
+            }
+        }
+        Fetched::Hashed(
+            hasher
+                .finalize()
+                .iter()
+                .map(|b| format!("{b:02x}"))
+                .collect(),
+        )
+    }
+}
+
+/// Whether `url` may be requested.
+fn is_public(url: &Url) -> bool {
+    if !matches!(url.scheme(), "http" | "https") {
+        return false;
+    }
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    // IPv6 hosts keep their brackets in `host_str`
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    match host.parse::<IpAddr>() {
+        Ok(ip) => is_public_ip(ip),
+        Err(_) => {
+            let host = host.trim_end_matches('.');
+            !(host.eq_ignore_ascii_case("localhost")
+                || host.to_ascii_lowercase().ends_with(".localhost"))
+        }
+    }
+}
+
+/// Why a redirect to `url`, after `previous` URLs were requested, isn't followed.
+fn refuse_redirect(url: &Url, previous: usize) -> Option<&'static str> {
+    if previous >= MAX_REDIRECTS {
+        Some("too many redirects")
+    } else if !is_public(url) {
+        Some("redirect to a non-public URL")
+    } else {
+        None
+    }
+}
+
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast())
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_ip(IpAddr::V4(ip)),
+            None => {
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local())
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_public_http_urls_are_probed() {
+        let public = |url: &str| is_public(&Url::parse(url).unwrap());
+
+        assert!(public("https://cdn.example.com/video.mp4"));
+        assert!(public("http://93.184.216.34/video.mp4"));
+
+        assert!(!public("ftp://cdn.example.com/video.mp4"));
+        assert!(!public("http://localhost:8123/"));
+        assert!(!public("http://api.localhost/"));
+        assert!(!public("http://127.0.0.1/"));
+        assert!(!public("http://10.0.0.5/"));
+        assert!(!public("http://192.168.1.1/"));
+        assert!(!public("http://169.254.169.254/latest/meta-data"));
+        assert!(!public("http://[::1]/"));
+        assert!(!public("http://[fd00::1]/"));
+        assert!(!public("http://[::ffff:127.0.0.1]/"));
+    }
+
+    #[test]
+    fn redirects_are_only_followed_to_public_urls() {
+        let refused = |url: &str, previous| refuse_redirect(&Url::parse(url).unwrap(), previous);
+
+        assert_eq!(refused("https://cdn.example.com/video.mp4", 1), None);
+        assert_eq!(
+            refused("http://169.254.169.254/latest/meta-data", 1),
+            Some("redirect to a non-public URL")
+        );
+        assert_eq!(
+            refused("http://localhost:8123/", 1),
+            Some("redirect to a non-public URL")
+        );
+        assert_eq!(
+            refused("https://cdn.example.com/video.mp4", MAX_REDIRECTS),
+            Some("too many redirects")
+        );
+    }
+}
@@ -0,0 +1,235 @@
+//! Profile enrichment.
+//!
+//! Live ingestion only stores the profiles (kind 0) and contact lists (kind 3) that
+//! relays happen to send, so many video authors never get one and can't be shown by
+//! name. Each run finds authors of recent videos whose profile is missing or hasn't
+//! been stored for a while, asks relays for their kind 0 and kind 3 events, and
+//! inserts whatever comes back into `events_local` like any ingested event. Every
+//! attempt is recorded in `profile_fetches`, so authors the relays know nothing
+//! about aren't asked again until their profile would be stale anyway.
+
+use std::time::Duration;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use funnel_clickhouse::{ClickHouseError, EventRow, EventWriter, ProfileFetch, ProfileQueries};
+use funnel_observability::aggregator;
+use funnel_proto::ParsedEvent;
+use metrics::counter;
+use nostr_sdk::prelude::*;
+use thiserror::Error;
+
+use crate::config::{ConfigError, env_list, env_or};
+
+/// How often profiles are refreshed by default.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Authors asked for in one relay request.
+const AUTHORS_PER_REQUEST: usize = 100;
+
+/// Errors from a profile enrichment run.
+#[derive(Debug, Error)]
+pub enum ProfileError {
+    #[error(transparent)]
+    ClickHouse(#[from] ClickHouseError),
+    #[error(transparent)]
+    Client(#[from] nostr_sdk::client::Error),
+}
+
+/// Profile enrichment parameters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfileConfig {
+    /// Whether the worker runs at all (it also needs relays).
+    pub enabled: bool,
+    /// Relays profiles are fetched from.
+    pub relays: Vec<String>,
+    /// Time between runs.
+    pub interval: Duration,
+    /// Only authors of videos created this recently are looked up.
+    pub window: Duration,
+    /// Age after which a stored profile, or a fetch that found nothing, is refreshed.
+    pub stale: Duration,
+    /// Authors fetched per run.
+    pub limit: u32,
+    /// Timeout for each relay request.
+    pub timeout: Duration,
+}
+
+impl Default for ProfileConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            relays: Vec::new(),
+            interval: DEFAULT_INTERVAL,
+            window: Duration::from_secs(30 * 24 * 60 * 60),
+            stale: Duration::from_secs(7 * 24 * 60 * 60),
+            limit: 500,
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+impl ProfileConfig {
+    /// Defaults overridden by `PROFILE_*` environment variables. Profiles are fetched
+    /// from `PROFILE_RELAYS`, or the ingester's `RELAY_URL` when that is unset.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let defaults = Self::default();
+
+        let mut relays = env_list("PROFILE_RELAYS");
+        if relays.is_empty() {
+            relays = env_list("RELAY_URL");
+        }
+
+        Ok(Self {
+            enabled: env_or("PROFILE_ENRICH_ENABLED", defaults.enabled)?,
+            relays,
+            interval: Duration::from_secs(env_or(
+                "PROFILE_INTERVAL_SECS",
+                defaults.interval.as_secs(),
+            )?),
+            window: Duration::from_secs(
+                env_or("PROFILE_WINDOW_DAYS", defaults.window.as_secs() / 86_400)? * 86_400,
+            ),
+            stale: Duration::from_secs(
+                env_or("PROFILE_STALE_DAYS", defaults.stale.as_secs() / 86_400)? * 86_400,
+            ),
+            limit: env_or("PROFILE_LIMIT", defaults.limit)?,
+            timeout: Duration::from_secs(env_or(
+                "PROFILE_TIMEOUT_SECS",
+                defaults.timeout.as_secs(),
+            )?),
+        })
+    }
+
+    /// Whether profiles are fetched: enabled, with relays to fetch from.
+    pub fn active(&self) -> bool {
+        self.enabled && !self.relays.is_empty()
+    }
+}
+
+/// Connect a client that reads from `relays`.
+pub async fn connect(relays: &[String]) -> Result<Client, nostr_sdk::client::Error> {
+    let client = Client::builder().build();
+    for relay in relays {
+        client.add_read_relay(relay.as_str()).await?;
+    }
+    client.connect().await;
+    Ok(client)
+}
+
+/// Event rows to insert for `events` fetched for `pubkeys` at `fetched_at`, and a
+/// fetch record for each pubkey counting the events found for it.
+pub fn fetched_rows(
+    pubkeys: &[String],
+    events: impl IntoIterator<Item = Event>,
+    fetched_at: DateTime<Utc>,
+) -> (Vec<EventRow>, Vec<ProfileFetch>) {
+    let rows: Vec<EventRow> = events
+        .into_iter()
+        .filter_map(|event| ParsedEvent::from_json(&event.as_json()).ok())
+        .map(|event| EventRow::from_parsed(&event.with_received_at(fetched_at), ""))
+        .collect();
+
+    let fetches = pubkeys
+        .iter()
+        .map(|pubkey| ProfileFetch {
+            pubkey: pubkey.clone(),
+            events: rows.iter().filter(|row| &row.pubkey == pubkey).count() as u32,
+            fetched_at,
+        })
+        .collect();
+    (rows, fetches)
+}
+
+/// Fetch the profiles due as of `now` and store them, returning how many authors
+/// were looked up.
+pub async fn run_once<S>(
+    storage: &S,
+    client: &Client,
+    config: &ProfileConfig,
+    now: DateTime<Utc>,
+) -> Result<usize, ProfileError>
+where
+    S: ProfileQueries + EventWriter,
+{
+    let created_after = now - TimeDelta::from_std(config.window).unwrap_or(TimeDelta::MAX);
+    let stale_before = now - TimeDelta::from_std(config.stale).unwrap_or(TimeDelta::MAX);
+    let pubkeys = storage
+        .get_profile_targets(created_after, stale_before, config.limit)
+        .await?;
+
+    for chunk in pubkeys.chunks(AUTHORS_PER_REQUEST) {
+        // Malformed pubkeys can't be asked for, but are still recorded as fetched
+        let authors = chunk
+            .iter()
+            .filter_map(|pubkey| PublicKey::from_hex(pubkey).ok());
+        let filter = Filter::new()
+            .authors(authors)
+            .kinds([Kind::Metadata, Kind::ContactList]);
+        let events = client.fetch_events(filter, config.timeout).await?;
+
+        let (rows, fetches) = fetched_rows(chunk, events, Utc::now());
+        if !rows.is_empty() {
+            storage.insert_events(&rows).await?;
+        }
+        counter!(aggregator::PROFILE_EVENTS).increment(rows.len() as u64);
+        storage.insert_profile_fetches(&fetches).await?;
+    }
+    Ok(pubkeys.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(keys: &Keys, kind: Kind, content: &str) -> Event {
+        EventBuilder::new(kind, content)
+            .sign_with_keys(keys)
+            .unwrap()
+    }
+
+    fn now() -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000, 0).unwrap()
+    }
+
+    #[test]
+    fn fetched_rows_counts_events_per_author() {
+        let alice = Keys::generate();
+        let bob = Keys::generate();
+        let pubkeys = vec![alice.public_key().to_hex(), bob.public_key().to_hex()];
+        let events = vec![
+            event(&alice, Kind::Metadata, r#"{"name":"alice"}"#),
+            event(&alice, Kind::ContactList, ""),
+        ];
+
+        let (rows, fetches) = fetched_rows(&pubkeys, events, now());
+
+        assert_eq!(rows.len(), 2);
+        assert!(rows.iter().all(|row| row.pubkey == pubkeys[0]));
+        assert!(rows.iter().all(|row| row.ingested_at == Some(now())));
+        assert_eq!(
+            fetches
+                .iter()
+                .map(|fetch| (fetch.pubkey.as_str(), fetch.events))
+                .collect::<Vec<_>>(),
+            [(pubkeys[0].as_str(), 2), (pubkeys[1].as_str(), 0)]
+        );
+        assert!(fetches.iter().all(|fetch| fetch.fetched_at == now()));
+    }
+
+    #[test]
+    fn active_needs_relays() {
+        assert!(!ProfileConfig::default().active());
+        let config = ProfileConfig {
+            relays: vec!["wss://relay.example.com".to_string()],
+            ..ProfileConfig::default()
+        };
+        assert!(config.active());
+        assert!(
+            !ProfileConfig {
+                enabled: false,
+                ..config
+            }
+            .active()
+        );
+    }
+}
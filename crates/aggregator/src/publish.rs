@@ -0,0 +1,243 @@
+//! Trending digests published to Nostr.
+//!
+//! Each run signs a NIP-51 video set (kind 30005) listing the current top trending
+//! videos and publishes it to the configured relays. The set keeps the same `d` tag
+//! across runs, so each run replaces the last and clients can follow it at one
+//! `naddr`. Addressable videos are listed by `a` tag, so the set resolves to their
+//! latest version; others by `e` tag.
+
+use std::time::Duration;
+
+use funnel_clickhouse::{ClickHouseError, TrendingVideo, VideoQueries};
+use funnel_proto::KIND_VIDEO_SET;
+use nostr_sdk::prelude::*;
+use thiserror::Error;
+
+use crate::config::{ConfigError, env_list, env_or, nostr_keys};
+
+/// How often the digest is published by default.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Errors from publishing a digest.
+#[derive(Debug, Error)]
+pub enum PublishError {
+    #[error(transparent)]
+    ClickHouse(#[from] ClickHouseError),
+    #[error("invalid tag: {0}")]
+    Tag(#[from] nostr_sdk::nostr::event::tag::Error),
+    #[error(transparent)]
+    Client(#[from] nostr_sdk::client::Error),
+    #[error("no relay accepted the event: {0}")]
+    Rejected(String),
+}
+
+/// Digest publisher parameters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PublishConfig {
+    /// Signing keys, set when there are relays to publish to.
+    pub keys: Option<Keys>,
+    /// Relays the digest is published to.
+    pub relays: Vec<String>,
+    /// Time between runs.
+    pub interval: Duration,
+    /// Videos listed per digest.
+    pub limit: u32,
+    /// `d` tag of the video set.
+    pub identifier: String,
+    pub title: String,
+}
+
+impl Default for PublishConfig {
+    fn default() -> Self {
+        Self {
+            keys: None,
+            relays: Vec::new(),
+            interval: DEFAULT_INTERVAL,
+            limit: 20,
+            identifier: "funnel-trending".to_string(),
+            title: "Trending videos".to_string(),
+        }
+    }
+}
+
+impl PublishConfig {
+    /// Defaults overridden by `PUBLISH_*` environment variables, signing with
+    /// `NOSTR_SECRET_KEY` when `PUBLISH_RELAYS` is set.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let defaults = Self::default();
+
+        let relays = env_list("PUBLISH_RELAYS");
+        let keys = if relays.is_empty() {
+            None
+        } else {
+            Some(nostr_keys()?.ok_or(ConfigError::Invalid {
+                name: "NOSTR_SECRET_KEY",
+                value: String::new(),
+            })?)
+        };
+
+        Ok(Self {
+            keys,
+            relays,
+            interval: Duration::from_secs(env_or(
+                "PUBLISH_INTERVAL_SECS",
+                defaults.interval.as_secs(),
+            )?),
+            limit: env_or("PUBLISH_LIMIT", defaults.limit)?,
+            identifier: env_or("PUBLISH_IDENTIFIER", defaults.identifier)?,
+            title: env_or("PUBLISH_TITLE", defaults.title)?,
+        })
+    }
+
+    /// Whether digests are published.
+    pub fn enabled(&self) -> bool {
+        self.keys.is_some()
+    }
+
+    /// Tags of the video set listing `videos` in order.
+    pub fn video_set_tags(&self, videos: &[TrendingVideo]) -> Vec<Vec<String>> {
+        let mut tags = vec![
+            vec!["d".to_string(), self.identifier.clone()],
+            vec!["title".to_string(), self.title.clone()],
+        ];
+        if let Some(image) = videos
+            .iter()
+            .map(|video| &video.thumbnail)
+            .find(|t| !t.is_empty())
+        {
+            tags.push(vec!["image".to_string(), image.clone()]);
+        }
+        tags.extend(
+            videos
+                .iter()
+                .map(|video| video_tag(&video.id, video.kind, &video.pubkey, &video.d_tag)),
+        );
+        tags
+    }
+}
+
+/// Tag referencing a video: `a` for addressable videos, so it resolves to their
+/// latest version, otherwise `e`.
+pub(crate) fn video_tag(id: &str, kind: u16, pubkey: &str, d_tag: &str) -> Vec<String> {
+    if d_tag.is_empty() {
+        vec!["e".to_string(), id.to_string()]
+    } else {
+        vec!["a".to_string(), format!("{kind}:{pubkey}:{d_tag}")]
+    }
+}
+
+/// Connect a client that signs with `keys` and publishes to `relays`.
+pub async fn connect(keys: Keys, relays: &[String]) -> Result<Client, nostr_sdk::client::Error> {
+    let client = Client::builder().signer(keys).build();
+    for relay in relays {
+        client.add_write_relay(relay.as_str()).await?;
+    }
+    client.connect().await;
+    Ok(client)
+}
+
+/// Publish a digest of the current trending videos, returning how many it lists.
+///
+/// Nothing is published while there are no trending videos, so an empty snapshot
+/// can't blank out the last digest.
+pub async fn run_once<S>(
+    storage: &S,
+    client: &Client,
+    config: &PublishConfig,
+) -> Result<usize, PublishError>
+where
+    S: VideoQueries,
+{
+    let videos = storage.get_trending_videos(None, config.limit).await?;
+    if videos.is_empty() {
+        return Ok(0);
+    }
+
+    let tags = config
+        .video_set_tags(&videos)
+        .into_iter()
+        .map(Tag::parse)
+        .collect::<Result<Vec<_>, _>>()?;
+    let builder = EventBuilder::new(Kind::from(KIND_VIDEO_SET), "").tags(tags);
+    let output = client.send_event_builder(builder).await?;
+
+    for (relay, error) in &output.failed {
+        tracing::warn!(%relay, error, "Relay rejected digest");
+    }
+    if output.success.is_empty() {
+        let errors: Vec<_> = output
+            .failed
+            .iter()
+            .map(|(relay, error)| format!("{relay}: {error}"))
+            .collect();
+        return Err(PublishError::Rejected(errors.join("; ")));
+    }
+    tracing::info!(
+        event_id = %output.val,
+        relays = output.success.len(),
+        videos = videos.len(),
+        "Published trending digest"
+    );
+    Ok(videos.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+    use funnel_proto::{EventAddress, VideoRef, VideoSet};
+
+    use super::*;
+
+    const PUBKEY: &str = "6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93";
+    const EVENT_ID: &str = "b2d670de53b27691c0c3400225b65c35a26d06093bcc41f48ffc71e0907f9d4a";
+
+    fn video(id: &str, d_tag: &str, thumbnail: &str) -> TrendingVideo {
+        TrendingVideo {
+            id: id.to_string(),
+            pubkey: PUBKEY.to_string(),
+            created_at: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+            kind: 34235,
+            d_tag: d_tag.to_string(),
+            title: String::new(),
+            thumbnail: thumbnail.to_string(),
+            reactions: 0,
+            comments: 0,
+            reposts: 0,
+            engagement_score: 0,
+            trending_score: 0.0,
+        }
+    }
+
+    #[test]
+    fn video_set_lists_videos_in_order() {
+        let tags = PublishConfig::default().video_set_tags(&[
+            video("v1", "my-video", ""),
+            video(EVENT_ID, "", "https://cdn.example.com/thumb.jpg"),
+        ]);
+
+        let set = VideoSet::from_tags(&tags);
+        assert_eq!(set.title.as_deref(), Some("Trending videos"));
+        assert_eq!(
+            set.image.as_deref(),
+            Some("https://cdn.example.com/thumb.jpg")
+        );
+        assert_eq!(
+            set.videos,
+            [
+                VideoRef::Address(EventAddress {
+                    kind: 34235,
+                    pubkey: PUBKEY.to_string(),
+                    identifier: "my-video".to_string(),
+                }),
+                VideoRef::Event(EVENT_ID.to_string()),
+            ]
+        );
+        assert_eq!(tags[0], ["d", "funnel-trending"]);
+    }
+
+    #[test]
+    fn video_set_tags_are_valid_nostr_tags() {
+        let tags = PublishConfig::default().video_set_tags(&[video("v1", "my-video", "")]);
+        assert!(tags.into_iter().all(|tag| Tag::parse(tag).is_ok()));
+    }
+}
@@ -0,0 +1,306 @@
+//! Aggregator service.
+//!
+//! Periodically recomputes derived tables in ClickHouse: trending scores into
+//! `trending_videos` (see [`trending`]), web-of-trust scores into `pubkey_trust` (see
+//! [`wot`]), re-uploaded videos into `video_duplicates` (see [`duplicates`]), media URL
+//! health into `media_health` (see [`media`]), and media hash checks into
+//! `media_verification` (see [`verify`]), each on its own schedule. It
+//! also fetches missing or stale author profiles from relays (see [`profiles`]). With
+//! a Nostr key configured, it publishes a trending digest to relays (see [`publish`])
+//! and can answer NIP-90 content discovery jobs (see [`dvm`]).
+
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use funnel_clickhouse::{ClickHouseClient, ClickHouseConfig};
+use funnel_observability::heartbeat::{self, CheckFailure, Heartbeat};
+use funnel_observability::{PrometheusConfig, aggregator};
+use funnel_proto::ErrorCode;
+use metrics::{counter, gauge, histogram};
+use tokio::time::MissedTickBehavior;
+
+use crate::duplicates::{self, DuplicateConfig};
+use crate::dvm::{self, DvmConfig};
+use crate::media::{self, MediaCheckConfig};
+use crate::probe::HttpProbe;
+use crate::profiles::{self, ProfileConfig};
+use crate::publish::{self, PublishConfig};
+use crate::trending::{self, TrendingConfig};
+use crate::verify::{self, VerifyConfig};
+use crate::wot::{self, WotConfig};
+
+/// Upper bound on the self-check ClickHouse ping
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A job's last successful run, and how old it may get before the service is unhealthy.
+#[derive(Clone)]
+struct Freshness {
+    check: &'static str,
+    last_success: Arc<Mutex<Instant>>,
+    max_age: Duration,
+}
+
+impl Freshness {
+    /// Unhealthy once runs have been failing for three intervals.
+    fn new(check: &'static str, interval: Duration) -> Self {
+        Self {
+            check,
+            last_success: Arc::new(Mutex::new(Instant::now())),
+            max_age: interval * 3,
+        }
+    }
+}
+
+/// Run every enabled job until the process exits.
+pub async fn run() -> anyhow::Result<()> {
+    let ch_config = ClickHouseConfig::from_env()?;
+    let trending_config = TrendingConfig::from_env()?;
+    let wot_config = WotConfig::from_env()?;
+    let duplicate_config = DuplicateConfig::from_env()?;
+    let media_config = MediaCheckConfig::from_env()?;
+    let verify_config = VerifyConfig::from_env()?;
+    let profile_config = ProfileConfig::from_env()?;
+    let publish_config = PublishConfig::from_env()?;
+    let dvm_config = DvmConfig::from_env()?;
+
+    tracing::info!(
+        clickhouse_url = %ch_config.safe_url(),
+        database = %ch_config.database,
+        interval_secs = trending_config.interval.as_secs(),
+        window_days = trending_config.window.as_secs() / 86_400,
+        decay_hours = trending_config.decay_hours,
+        weights = ?trending_config.weights,
+        limit = trending_config.limit,
+        wot_interval_secs = wot_config.interval.as_secs(),
+        wot_anchors = wot_config.anchors.len(),
+        duplicates_interval_secs = duplicate_config.interval.as_secs(),
+        media_check_enabled = media_config.enabled,
+        media_check_interval_secs = media_config.interval.as_secs(),
+        media_verify_enabled = verify_config.enabled,
+        media_verify_servers = verify_config.servers.len(),
+        profile_enrich_enabled = profile_config.active(),
+        profile_relays = profile_config.relays.len(),
+        publish_enabled = publish_config.enabled(),
+        publish_relays = publish_config.relays.len(),
+        dvm_enabled = dvm_config.enabled(),
+        dvm_relays = dvm_config.relays.len(),
+        "Starting aggregator"
+    );
+
+    let _metrics = funnel_observability::init_metrics(
+        PrometheusConfig::from_env().with_global_label("service", "funnel-aggregator"),
+    );
+
+    let clickhouse = ClickHouseClient::connect(&ch_config).await?;
+
+    let trending_fresh = Freshness::new("trending_fresh", trending_config.interval);
+    let wot_fresh = Freshness::new("wot_fresh", wot_config.interval);
+    let duplicates_fresh = Freshness::new("duplicates_fresh", duplicate_config.interval);
+    let media_fresh = Freshness::new("media_fresh", media_config.interval);
+    let verify_fresh = Freshness::new("verify_fresh", verify_config.interval);
+    let probe = HttpProbe::new(media_config.timeout)?;
+    let fetcher = HttpProbe::new(verify_config.timeout)?;
+    let profile_fresh = Freshness::new("profile_fresh", profile_config.interval);
+    let profile_client = if profile_config.active() {
+        Some(profiles::connect(&profile_config.relays).await?)
+    } else {
+        None
+    };
+    let publish_fresh = Freshness::new("publish_fresh", publish_config.interval);
+    let publisher = match &publish_config.keys {
+        Some(keys) => {
+            let client = publish::connect(keys.clone(), &publish_config.relays).await?;
+            tracing::info!(pubkey = %keys.public_key(), "Publishing trending digests");
+            Some(client)
+        }
+        None => None,
+    };
+    let dvm = match &dvm_config.keys {
+        Some(keys) => Some((
+            dvm::connect(keys.clone(), &dvm_config.relays).await?,
+            keys.public_key(),
+        )),
+        None => None,
+    };
+    Heartbeat::spawn(heartbeat::DEFAULT_INTERVAL, {
+        let clickhouse = clickhouse.clone();
+        let mut jobs = vec![
+            trending_fresh.clone(),
+            wot_fresh.clone(),
+            duplicates_fresh.clone(),
+        ];
+        if media_config.enabled {
+            jobs.push(media_fresh.clone());
+        }
+        if verify_config.enabled {
+            jobs.push(verify_fresh.clone());
+        }
+        if profile_client.is_some() {
+            jobs.push(profile_fresh.clone());
+        }
+        if publisher.is_some() {
+            jobs.push(publish_fresh.clone());
+        }
+        move || {
+            let clickhouse = clickhouse.clone();
+            let jobs = jobs.clone();
+            async move { self_check(&clickhouse, &jobs).await }
+        }
+    });
+
+    tokio::join!(
+        run_every(
+            "trending",
+            trending_config.interval,
+            aggregator::TRENDING_VIDEOS,
+            trending_fresh,
+            || trending::run_once(&clickhouse, &trending_config, chrono::Utc::now()),
+        ),
+        run_every(
+            "wot",
+            wot_config.interval,
+            aggregator::TRUSTED_PUBKEYS,
+            wot_fresh,
+            || wot::run_once(&clickhouse, &wot_config, chrono::Utc::now()),
+        ),
+        run_every(
+            "duplicates",
+            duplicate_config.interval,
+            aggregator::DUPLICATE_VIDEOS,
+            duplicates_fresh,
+            || duplicates::run_once(&clickhouse, &duplicate_config, chrono::Utc::now()),
+        ),
+        async {
+            if media_config.enabled {
+                run_every(
+                    "media",
+                    media_config.interval,
+                    aggregator::MEDIA_CHECKED,
+                    media_fresh,
+                    || media::run_once(&clickhouse, &probe, &media_config, chrono::Utc::now()),
+                )
+                .await;
+            }
+        },
+        async {
+            if verify_config.enabled {
+                run_every(
+                    "verify",
+                    verify_config.interval,
+                    aggregator::MEDIA_VERIFIED,
+                    verify_fresh,
+                    || verify::run_once(&clickhouse, &fetcher, &verify_config, chrono::Utc::now()),
+                )
+                .await;
+            }
+        },
+        async {
+            if let Some(client) = &profile_client {
+                run_every(
+                    "profiles",
+                    profile_config.interval,
+                    aggregator::PROFILES_FETCHED,
+                    profile_fresh,
+                    || profiles::run_once(&clickhouse, client, &profile_config, chrono::Utc::now()),
+                )
+                .await;
+            }
+        },
+        async {
+            if let Some(client) = &publisher {
+                run_every(
+                    "publish",
+                    publish_config.interval,
+                    aggregator::PUBLISHED_VIDEOS,
+                    publish_fresh,
+                    || publish::run_once(&clickhouse, client, &publish_config),
+                )
+                .await;
+            }
+        },
+        async {
+            if let Some((client, provider)) = &dvm {
+                tracing::info!(pubkey = %provider, "Serving discovery jobs");
+                if let Err(e) = dvm::serve(&clickhouse, client, *provider, &dvm_config).await {
+                    tracing::error!(error = %e, "DVM stopped");
+                }
+            }
+        },
+    );
+    Ok(())
+}
+
+/// Run `job` every `interval`, recording each run's outcome and the row count it
+/// reports in `rows_gauge`.
+async fn run_every<F, Fut, E>(
+    job: &'static str,
+    interval: Duration,
+    rows_gauge: &'static str,
+    fresh: Freshness,
+    mut run: F,
+) where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<usize, E>>,
+    E: std::fmt::Display,
+{
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+
+        let start = Instant::now();
+        let result = run().await;
+        histogram!(aggregator::RUN_DURATION, "job" => job).record(start.elapsed().as_secs_f64());
+
+        match result {
+            Ok(count) => {
+                counter!(aggregator::RUNS, "job" => job, "status" => "ok").increment(1);
+                gauge!(rows_gauge).set(count as f64);
+                *fresh.last_success.lock().unwrap_or_else(|e| e.into_inner()) = Instant::now();
+                tracing::info!(
+                    job,
+                    rows = count,
+                    elapsed_ms = start.elapsed().as_millis() as u64,
+                    "Aggregation run finished"
+                );
+            }
+            Err(e) => {
+                counter!(aggregator::RUNS, "job" => job, "status" => "error").increment(1);
+                tracing::error!(
+                    code = %ErrorCode::AggregatorRunFailed,
+                    job,
+                    error = %e,
+                    "Aggregation run failed"
+                );
+            }
+        }
+    }
+}
+
+/// Check that ClickHouse is reachable and every job's output is being refreshed.
+async fn self_check(clickhouse: &ClickHouseClient, jobs: &[Freshness]) -> Vec<CheckFailure> {
+    let mut failures = Vec::new();
+
+    match tokio::time::timeout(CHECK_TIMEOUT, clickhouse.ping()).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => failures.push(CheckFailure::new("clickhouse_reachable", e.to_string())),
+        Err(_) => failures.push(CheckFailure::new("clickhouse_reachable", "ping timed out")),
+    }
+
+    for job in jobs {
+        let age = job
+            .last_success
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .elapsed();
+        if age > job.max_age {
+            failures.push(CheckFailure::new(
+                job.check,
+                format!("last successful run {}s ago", age.as_secs()),
+            ));
+        }
+    }
+
+    failures
+}
@@ -0,0 +1,279 @@
+//! Trending scores.
+//!
+//! Each run scores every video created within the window by its weighted
+//! engagement, decayed exponentially with age:
+//!
+//! ```text
+//! engagement = reactions × w_reactions + comments × w_comments
+//!            + reposts × w_reposts + zaps × w_zaps
+//! score      = engagement × exp(−age_hours / decay_hours)
+//! ```
+//!
+//! and writes the highest-scoring videos to `trending_videos` as a new snapshot.
+//! The defaults match the `trending_videos` view this replaces, with zaps added.
+
+use std::time::Duration;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use funnel_clickhouse::{ClickHouseError, TrendingCandidate, TrendingQueries, TrendingScore};
+
+use crate::config::{ConfigError, env_or};
+
+/// How often scores are recomputed by default.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Weight of each kind of engagement in a video's score.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EngagementWeights {
+    pub reactions: u64,
+    pub comments: u64,
+    pub reposts: u64,
+    pub zaps: u64,
+}
+
+impl Default for EngagementWeights {
+    fn default() -> Self {
+        Self {
+            reactions: 1,
+            comments: 2,
+            reposts: 3,
+            zaps: 5,
+        }
+    }
+}
+
+impl EngagementWeights {
+    /// Weighted engagement of `video`.
+    pub fn engagement(&self, video: &TrendingCandidate) -> u64 {
+        [
+            (video.reactions, self.reactions),
+            (video.comments, self.comments),
+            (video.reposts, self.reposts),
+            (video.zaps, self.zaps),
+        ]
+        .into_iter()
+        .fold(0u64, |total, (count, weight)| {
+            total.saturating_add(count.saturating_mul(weight))
+        })
+    }
+}
+
+/// Trending algorithm parameters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrendingConfig {
+    /// Time between runs.
+    pub interval: Duration,
+    /// Only videos created this recently are scored.
+    pub window: Duration,
+    /// Age at which a video's score has decayed to 1/e of its engagement.
+    pub decay_hours: f64,
+    pub weights: EngagementWeights,
+    /// Videos kept per snapshot.
+    pub limit: usize,
+}
+
+impl Default for TrendingConfig {
+    fn default() -> Self {
+        Self {
+            interval: DEFAULT_INTERVAL,
+            window: Duration::from_secs(30 * 24 * 60 * 60),
+            decay_hours: 168.0,
+            weights: EngagementWeights::default(),
+            limit: 1000,
+        }
+    }
+}
+
+impl TrendingConfig {
+    /// Defaults overridden by `TRENDING_*` environment variables.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let defaults = Self::default();
+        let weights = defaults.weights;
+
+        let decay_hours = env_or("TRENDING_DECAY_HOURS", defaults.decay_hours)?;
+        if !(decay_hours.is_finite() && decay_hours > 0.0) {
+            return Err(ConfigError::Invalid {
+                name: "TRENDING_DECAY_HOURS",
+                value: decay_hours.to_string(),
+            });
+        }
+
+        Ok(Self {
+            interval: Duration::from_secs(env_or(
+                "TRENDING_INTERVAL_SECS",
+                defaults.interval.as_secs(),
+            )?),
+            window: Duration::from_secs(
+                env_or("TRENDING_WINDOW_DAYS", defaults.window.as_secs() / 86_400)? * 86_400,
+            ),
+            decay_hours,
+            weights: EngagementWeights {
+                reactions: env_or("TRENDING_WEIGHT_REACTIONS", weights.reactions)?,
+                comments: env_or("TRENDING_WEIGHT_COMMENTS", weights.comments)?,
+                reposts: env_or("TRENDING_WEIGHT_REPOSTS", weights.reposts)?,
+                zaps: env_or("TRENDING_WEIGHT_ZAPS", weights.zaps)?,
+            },
+            limit: env_or("TRENDING_LIMIT", defaults.limit)?,
+        })
+    }
+
+    /// Score `video` as of `computed_at`.
+    pub fn score(&self, video: TrendingCandidate, computed_at: DateTime<Utc>) -> TrendingScore {
+        let engagement = self.weights.engagement(&video);
+        let age_hours = (computed_at - video.created_at).num_seconds().max(0) as f64 / 3600.0;
+
+        TrendingScore {
+            computed_at,
+            engagement_score: engagement,
+            trending_score: engagement as f64 * (-age_hours / self.decay_hours).exp(),
+            id: video.id,
+            pubkey: video.pubkey,
+            created_at: video.created_at,
+            kind: video.kind,
+            d_tag: video.d_tag,
+            title: video.title,
+            thumbnail: video.thumbnail,
+            reactions: video.reactions,
+            comments: video.comments,
+            reposts: video.reposts,
+            zaps: video.zaps,
+        }
+    }
+
+    /// Score `videos` and keep the top [`limit`](Self::limit), highest first.
+    pub fn rank(
+        &self,
+        videos: Vec<TrendingCandidate>,
+        computed_at: DateTime<Utc>,
+    ) -> Vec<TrendingScore> {
+        let mut scores: Vec<_> = videos
+            .into_iter()
+            .map(|video| self.score(video, computed_at))
+            .collect();
+        scores.sort_by(|a, b| b.trending_score.total_cmp(&a.trending_score));
+        scores.truncate(self.limit);
+        scores
+    }
+}
+
+/// Compute and store a snapshot as of `now`, returning how many videos it holds.
+pub async fn run_once<S>(
+    storage: &S,
+    config: &TrendingConfig,
+    now: DateTime<Utc>,
+) -> Result<usize, ClickHouseError>
+where
+    S: TrendingQueries,
+{
+    let since = now - TimeDelta::from_std(config.window).unwrap_or(TimeDelta::MAX);
+    let videos = storage.get_trending_candidates(since).await?;
+    let scores = config.rank(videos, now);
+    storage.insert_trending_scores(&scores).await?;
+    Ok(scores.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    fn video(id: &str, age_hours: i64, reactions: u64, zaps: u64) -> TrendingCandidate {
+        TrendingCandidate {
+            id: id.to_string(),
+            pubkey: "pk".to_string(),
+            created_at: now() - TimeDelta::hours(age_hours),
+            kind: 34235,
+            d_tag: String::new(),
+            title: String::new(),
+            thumbnail: String::new(),
+            reactions,
+            comments: 0,
+            reposts: 0,
+            zaps,
+        }
+    }
+
+    fn now() -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000, 0).unwrap()
+    }
+
+    #[derive(Default)]
+    struct MockStorage {
+        videos: Vec<TrendingCandidate>,
+        since: Mutex<Option<DateTime<Utc>>>,
+        written: Mutex<Vec<TrendingScore>>,
+    }
+
+    impl TrendingQueries for MockStorage {
+        async fn get_trending_candidates(
+            &self,
+            since: DateTime<Utc>,
+        ) -> Result<Vec<TrendingCandidate>, ClickHouseError> {
+            *self.since.lock().unwrap() = Some(since);
+            Ok(self.videos.clone())
+        }
+
+        async fn insert_trending_scores(
+            &self,
+            scores: &[TrendingScore],
+        ) -> Result<(), ClickHouseError> {
+            self.written.lock().unwrap().extend_from_slice(scores);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn score_decays_weighted_engagement() {
+        let config = TrendingConfig::default();
+
+        let fresh = config.score(video("a", 0, 10, 2), now());
+        assert_eq!(fresh.engagement_score, 20);
+        assert_eq!(fresh.trending_score, 20.0);
+
+        let week_old = config.score(video("b", 168, 10, 2), now());
+        assert_eq!(week_old.engagement_score, 20);
+        assert!((week_old.trending_score - 20.0 / std::f64::consts::E).abs() < 1e-9);
+    }
+
+    #[test]
+    fn rank_orders_by_score_and_applies_limit() {
+        let config = TrendingConfig {
+            limit: 2,
+            ..TrendingConfig::default()
+        };
+        let ranked = config.rank(
+            vec![
+                video("old", 500, 100, 0),
+                video("new", 1, 10, 0),
+                video("zapped", 1, 0, 4),
+            ],
+            now(),
+        );
+
+        let ids: Vec<_> = ranked.iter().map(|score| score.id.as_str()).collect();
+        assert_eq!(ids, ["zapped", "new"]);
+        assert!(ranked.iter().all(|score| score.computed_at == now()));
+    }
+
+    #[tokio::test]
+    async fn run_once_writes_snapshot_for_window() {
+        let storage = MockStorage {
+            videos: vec![video("a", 2, 1, 0), video("b", 1, 3, 0)],
+            ..MockStorage::default()
+        };
+        let config = TrendingConfig {
+            window: Duration::from_secs(86_400),
+            ..TrendingConfig::default()
+        };
+
+        assert_eq!(run_once(&storage, &config, now()).await.unwrap(), 2);
+        assert_eq!(
+            *storage.since.lock().unwrap(),
+            Some(now() - TimeDelta::days(1))
+        );
+        let written = storage.written.lock().unwrap();
+        assert_eq!(written[0].id, "b");
+        assert_eq!(written[1].id, "a");
+    }
+}
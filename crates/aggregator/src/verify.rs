@@ -0,0 +1,413 @@
+//! Media hash verification.
+//!
+//! Videos can declare their file's SHA-256 in an `x` entry of an `imeta` tag
+//! (NIP-92), which is also the file's address on Blossom servers. Each run downloads
+//! the files of recent videos that aren't verified yet, from the event's URL and
+//! then from each configured Blossom server at `/<sha256>`, and records whether any
+//! copy hashes to the declared value in `media_verification`. A SHA-256 covers the
+//! whole file, so files are downloaded in full, up to a size limit.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use funnel_clickhouse::{ClickHouseError, MediaQueries, MediaVerification, VerifyTarget};
+use funnel_observability::aggregator;
+use metrics::counter;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use crate::config::{ConfigError, env_or};
+use crate::probe::{Fetch, Fetched};
+
+/// How often files are verified by default.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(30 * 60);
+
+/// Hash verifier parameters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifyConfig {
+    /// Whether the verifier runs at all.
+    pub enabled: bool,
+    /// Time between runs.
+    pub interval: Duration,
+    /// Only files of videos created this recently are verified.
+    pub window: Duration,
+    /// Minimum time before a file that failed verification is tried again.
+    pub recheck: Duration,
+    /// Files verified per run.
+    pub limit: u32,
+    /// Timeout for each download.
+    pub timeout: Duration,
+    /// Largest file downloaded.
+    pub max_bytes: u64,
+    /// Downloads in flight at once.
+    pub concurrency: usize,
+    /// Blossom server base URLs (no trailing slash) tried after the event's URL.
+    pub servers: Vec<String>,
+}
+
+impl Default for VerifyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval: DEFAULT_INTERVAL,
+            window: Duration::from_secs(7 * 24 * 60 * 60),
+            recheck: Duration::from_secs(24 * 60 * 60),
+            limit: 50,
+            timeout: Duration::from_secs(300),
+            max_bytes: 512 * 1024 * 1024,
+            concurrency: 4,
+            servers: Vec::new(),
+        }
+    }
+}
+
+impl VerifyConfig {
+    /// Defaults overridden by `MEDIA_VERIFY_*` environment variables.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let defaults = Self::default();
+
+        let concurrency = env_or("MEDIA_VERIFY_CONCURRENCY", defaults.concurrency)?;
+        if concurrency == 0 {
+            return Err(ConfigError::Invalid {
+                name: "MEDIA_VERIFY_CONCURRENCY",
+                value: concurrency.to_string(),
+            });
+        }
+
+        let servers = match std::env::var("MEDIA_VERIFY_SERVERS") {
+            Ok(value) => value
+                .split(',')
+                .map(str::trim)
+                .filter(|server| !server.is_empty())
+                .map(|server| {
+                    if server.starts_with("https://") || server.starts_with("http://") {
+                        Ok(server.trim_end_matches('/').to_string())
+                    } else {
+                        Err(ConfigError::Invalid {
+                            name: "MEDIA_VERIFY_SERVERS",
+                            value: server.to_string(),
+                        })
+                    }
+                })
+                .collect::<Result<_, _>>()?,
+            Err(_) => defaults.servers,
+        };
+
+        Ok(Self {
+            enabled: env_or("MEDIA_VERIFY_ENABLED", defaults.enabled)?,
+            interval: Duration::from_secs(env_or(
+                "MEDIA_VERIFY_INTERVAL_SECS",
+                defaults.interval.as_secs(),
+            )?),
+            window: Duration::from_secs(
+                env_or(
+                    "MEDIA_VERIFY_WINDOW_DAYS",
+                    defaults.window.as_secs() / 86_400,
+                )? * 86_400,
+            ),
+            recheck: Duration::from_secs(
+                env_or(
+                    "MEDIA_VERIFY_RECHECK_HOURS",
+                    defaults.recheck.as_secs() / 3600,
+                )? * 3600,
+            ),
+            limit: env_or("MEDIA_VERIFY_LIMIT", defaults.limit)?,
+            timeout: Duration::from_secs(env_or(
+                "MEDIA_VERIFY_TIMEOUT_SECS",
+                defaults.timeout.as_secs(),
+            )?),
+            max_bytes: env_or("MEDIA_VERIFY_MAX_MB", defaults.max_bytes / (1024 * 1024))?
+                * 1024
+                * 1024,
+            concurrency,
+            servers,
+        })
+    }
+
+    /// URLs to fetch `target` from, in order: the event's URL, then each server.
+    pub fn sources(&self, target: &VerifyTarget) -> Vec<String> {
+        let mut sources = vec![target.url.clone()];
+        for server in &self.servers {
+            let url = format!("{server}/{}", target.sha256);
+            if !sources.contains(&url) {
+                sources.push(url);
+            }
+        }
+        sources
+    }
+}
+
+/// Verify `target` by fetching it from each source until a copy matches.
+///
+/// Without a match, a copy with another hash makes it a mismatch, ahead of copies
+/// too large to download, ahead of no copy at all.
+pub async fn verify<F>(
+    fetcher: &F,
+    config: &VerifyConfig,
+    target: VerifyTarget,
+) -> MediaVerification
+where
+    F: Fetch,
+{
+    let mut status = MediaVerification::STATUS_UNAVAILABLE;
+    let mut source = String::new();
+    for url in config.sources(&target) {
+        match fetcher.sha256(&url, config.max_bytes).await {
+            Fetched::Hashed(sha256) if sha256 == target.sha256 => {
+                status = MediaVerification::STATUS_VERIFIED;
+                source = url;
+                break;
+            }
+            Fetched::Hashed(_) => status = MediaVerification::STATUS_MISMATCH,
+            Fetched::TooLarge if status == MediaVerification::STATUS_UNAVAILABLE => {
+                status = MediaVerification::STATUS_TOO_LARGE;
+            }
+            Fetched::TooLarge | Fetched::Failed => {}
+        }
+    }
+
+    MediaVerification {
+        event_id: target.event_id,
+        sha256: target.sha256,
+        url: target.url,
+        status: status.to_string(),
+        source,
+        checked_at: Utc::now(),
+    }
+}
+
+/// Verify the files due as of `now` and store the results, returning how many
+/// were verified.
+pub async fn run_once<S, F>(
+    storage: &S,
+    fetcher: &F,
+    config: &VerifyConfig,
+    now: DateTime<Utc>,
+) -> Result<usize, ClickHouseError>
+where
+    S: MediaQueries,
+    F: Fetch + Clone + 'static,
+{
+    let created_after = now - TimeDelta::from_std(config.window).unwrap_or(TimeDelta::MAX);
+    let checked_before = now - TimeDelta::from_std(config.recheck).unwrap_or(TimeDelta::MAX);
+    let targets = storage
+        .get_verify_targets(created_after, checked_before, config.limit)
+        .await?;
+
+    let config = Arc::new(config.clone());
+    let permits = Arc::new(Semaphore::new(config.concurrency.max(1)));
+    let mut downloads = JoinSet::new();
+    for target in targets {
+        let fetcher = fetcher.clone();
+        let config = config.clone();
+        let permits = permits.clone();
+        downloads.spawn(async move {
+            let _permit = permits.acquire_owned().await;
+            verify(&fetcher, &config, target).await
+        });
+    }
+
+    let mut verifications = Vec::with_capacity(downloads.len());
+    while let Some(result) = downloads.join_next().await {
+        match result {
+            Ok(verification) => {
+                counter!(aggregator::MEDIA_VERIFICATIONS, "status" => verification.status.clone())
+                    .increment(1);
+                verifications.push(verification);
+            }
+            Err(e) => tracing::warn!(error = %e, "Media verification task failed"),
+        }
+    }
+
+    storage.insert_media_verifications(&verifications).await?;
+    Ok(verifications
+        .iter()
+        .filter(|verification| verification.is_verified())
+        .count())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    use funnel_clickhouse::{MediaHealth, MediaTarget};
+
+    use super::*;
+
+    const SHA256: &str = "0f343b0931126a20f133d67c2b018a3b5e4b9ae3c1ba4d4bd6e5a1c8f3e0f5e1";
+
+    fn target(url: &str) -> VerifyTarget {
+        VerifyTarget {
+            event_id: "e1".to_string(),
+            url: url.to_string(),
+            sha256: SHA256.to_string(),
+        }
+    }
+
+    fn config(servers: &[&str]) -> VerifyConfig {
+        VerifyConfig {
+            servers: servers.iter().map(|server| server.to_string()).collect(),
+            ..VerifyConfig::default()
+        }
+    }
+
+    #[derive(Clone, Default)]
+    struct MockFetcher {
+        files: Arc<HashMap<String, Fetched>>,
+        fetched: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl MockFetcher {
+        fn new(files: &[(&str, Fetched)]) -> Self {
+            Self {
+                files: Arc::new(
+                    files
+                        .iter()
+                        .map(|(url, fetched)| (url.to_string(), fetched.clone()))
+                        .collect(),
+                ),
+                ..Self::default()
+            }
+        }
+    }
+
+    impl Fetch for MockFetcher {
+        async fn sha256(&self, url: &str, _max_bytes: u64) -> Fetched {
+            self.fetched.lock().unwrap().push(url.to_string());
+            self.files.get(url).cloned().unwrap_or(Fetched::Failed)
+        }
+    }
+
+    #[derive(Default)]
+    struct MockStorage {
+        targets: Vec<VerifyTarget>,
+        written: Mutex<Vec<MediaVerification>>,
+    }
+
+    impl MediaQueries for MockStorage {
+        async fn get_media_targets(
+            &self,
+            _created_after: DateTime<Utc>,
+            _checked_before: DateTime<Utc>,
+            _limit: u32,
+        ) -> Result<Vec<MediaTarget>, ClickHouseError> {
+            Ok(Vec::new())
+        }
+
+        async fn insert_media_health(
+            &self,
+            _checks: &[MediaHealth],
+        ) -> Result<(), ClickHouseError> {
+            Ok(())
+        }
+
+        async fn get_verify_targets(
+            &self,
+            _created_after: DateTime<Utc>,
+            _checked_before: DateTime<Utc>,
+            _limit: u32,
+        ) -> Result<Vec<VerifyTarget>, ClickHouseError> {
+            Ok(self.targets.clone())
+        }
+
+        async fn insert_media_verifications(
+            &self,
+            verifications: &[MediaVerification],
+        ) -> Result<(), ClickHouseError> {
+            self.written
+                .lock()
+                .unwrap()
+                .extend_from_slice(verifications);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn verify_falls_back_to_blossom_servers() {
+        let blossom = format!("https://blossom.example.com/{SHA256}");
+        let fetcher = MockFetcher::new(&[
+            ("https://cdn.example.com/v.mp4", Fetched::Failed),
+            (&blossom, Fetched::Hashed(SHA256.to_string())),
+        ]);
+        let config = config(&["https://blossom.example.com", "https://other.example.com"]);
+
+        let verification = verify(&fetcher, &config, target("https://cdn.example.com/v.mp4")).await;
+
+        assert!(verification.is_verified());
+        assert_eq!(verification.source, blossom);
+        assert_eq!(verification.url, "https://cdn.example.com/v.mp4");
+        // Stops at the first match
+        assert_eq!(fetcher.fetched.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn verify_reports_mismatch_over_other_failures() {
+        let fetcher = MockFetcher::new(&[
+            ("https://cdn.example.com/v.mp4", Fetched::TooLarge),
+            (
+                &format!("https://blossom.example.com/{SHA256}"),
+                Fetched::Hashed("ff".repeat(32)),
+            ),
+        ]);
+
+        let mismatch = verify(
+            &fetcher,
+            &config(&["https://blossom.example.com"]),
+            target("https://cdn.example.com/v.mp4"),
+        )
+        .await;
+        assert_eq!(mismatch.status, MediaVerification::STATUS_MISMATCH);
+        assert!(mismatch.source.is_empty());
+
+        let too_large = verify(
+            &fetcher,
+            &config(&[]),
+            target("https://cdn.example.com/v.mp4"),
+        )
+        .await;
+        assert_eq!(too_large.status, MediaVerification::STATUS_TOO_LARGE);
+
+        let unavailable = verify(
+            &fetcher,
+            &config(&[]),
+            target("https://gone.example.com/v.mp4"),
+        )
+        .await;
+        assert_eq!(unavailable.status, MediaVerification::STATUS_UNAVAILABLE);
+    }
+
+    #[test]
+    fn sources_skip_duplicate_blossom_url() {
+        let blossom = format!("https://blossom.example.com/{SHA256}");
+        assert_eq!(
+            config(&["https://blossom.example.com"]).sources(&target(&blossom)),
+            [blossom]
+        );
+    }
+
+    #[tokio::test]
+    async fn run_once_records_every_result() {
+        let storage = MockStorage {
+            targets: vec![
+                target("https://cdn.example.com/ok.mp4"),
+                target("https://cdn.example.com/gone.mp4"),
+            ],
+            ..MockStorage::default()
+        };
+        let fetcher = MockFetcher::new(&[(
+            "https://cdn.example.com/ok.mp4",
+            Fetched::Hashed(SHA256.to_string()),
+        )]);
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+
+        assert_eq!(
+            run_once(&storage, &fetcher, &config(&[]), now)
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(storage.written.lock().unwrap().len(), 2);
+    }
+}
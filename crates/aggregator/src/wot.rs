@@ -0,0 +1,316 @@
+//! Web-of-trust scores.
+//!
+//! Each run builds the follow graph from every pubkey's latest contact list
+//! (kind 3) and ranks pubkeys with PageRank. Random jumps land on the configured
+//! anchor pubkeys, or on any pubkey when none of them appear in the graph, so with
+//! anchors, rank only reaches accounts inside the anchors' network.
+//!
+//! A pubkey's trust is the rank its followers pass on to it, scaled so the most
+//! trusted pubkey has 1; anchors always have 1. Accounts that no ranked pubkey
+//! follows have no trust and are left out of the `pubkey_trust` snapshot, which is
+//! what lets `min_trust` drop spam accounts with no social proof.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use funnel_clickhouse::{ClickHouseError, FollowEdge, PubkeyTrust, TrustQueries};
+use funnel_proto::normalize_pubkey;
+
+use crate::config::{ConfigError, env_or};
+
+/// How often scores are recomputed by default.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Web-of-trust parameters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WotConfig {
+    /// Time between runs.
+    pub interval: Duration,
+    /// Pubkeys (lowercase hex) that trust starts from; empty trusts every pubkey
+    /// equally to begin with.
+    pub anchors: Vec<String>,
+    /// Probability of following a link rather than jumping to an anchor.
+    pub damping: f64,
+    /// PageRank iterations per run.
+    pub iterations: usize,
+}
+
+impl Default for WotConfig {
+    fn default() -> Self {
+        Self {
+            interval: DEFAULT_INTERVAL,
+            anchors: Vec::new(),
+            damping: 0.85,
+            iterations: 20,
+        }
+    }
+}
+
+impl WotConfig {
+    /// Defaults overridden by `WOT_*` environment variables.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        let defaults = Self::default();
+
+        let damping = env_or("WOT_DAMPING", defaults.damping)?;
+        if !(0.0..1.0).contains(&damping) {
+            return Err(ConfigError::Invalid {
+                name: "WOT_DAMPING",
+                value: damping.to_string(),
+            });
+        }
+
+        let anchors = match std::env::var("WOT_ANCHORS") {
+            Ok(value) => value
+                .split(',')
+                .map(str::trim)
+                .filter(|anchor| !anchor.is_empty())
+                .map(|anchor| {
+                    normalize_pubkey(anchor).ok_or_else(|| ConfigError::Invalid {
+                        name: "WOT_ANCHORS",
+                        value: anchor.to_string(),
+                    })
+                })
+                .collect::<Result<_, _>>()?,
+            Err(_) => defaults.anchors,
+        };
+
+        Ok(Self {
+            interval: Duration::from_secs(env_or(
+                "WOT_INTERVAL_SECS",
+                defaults.interval.as_secs(),
+            )?),
+            anchors,
+            damping,
+            iterations: env_or("WOT_ITERATIONS", defaults.iterations)?,
+        })
+    }
+
+    /// Score every pubkey in the follow graph as of `computed_at`, highest first.
+    ///
+    /// Pubkeys without trust are left out.
+    pub fn score(&self, edges: &[FollowEdge], computed_at: DateTime<Utc>) -> Vec<PubkeyTrust> {
+        let graph = FollowGraph::new(edges);
+        let n = graph.pubkeys.len();
+
+        let anchors: Vec<usize> = self
+            .anchors
+            .iter()
+            .filter_map(|anchor| graph.index.get(anchor.as_str()).copied())
+            .collect();
+        let mut jump = vec![0.0; n];
+        if anchors.is_empty() {
+            jump.fill(1.0 / n as f64);
+        } else {
+            for &anchor in &anchors {
+                jump[anchor] = 1.0 / anchors.len() as f64;
+            }
+        }
+
+        let mut rank = jump.clone();
+        for _ in 0..self.iterations {
+            // Rank held by pubkeys that follow nobody is spread like a jump
+            let dangling: f64 = (0..n)
+                .filter(|&u| graph.following[u] == 0)
+                .map(|u| rank[u])
+                .sum();
+            let jumped = 1.0 - self.damping + self.damping * dangling;
+            let passed = graph.pass(&rank);
+            for ((rank, passed), jump) in rank.iter_mut().zip(passed).zip(&jump) {
+                *rank = self.damping * passed + jumped * jump;
+            }
+        }
+
+        let mut trust = graph.pass(&rank);
+        let max = trust.iter().copied().fold(0.0, f64::max);
+        for value in &mut trust {
+            *value = if max > 0.0 { *value / max } else { 0.0 };
+        }
+        for &anchor in &anchors {
+            trust[anchor] = 1.0;
+        }
+
+        let mut scores: Vec<_> = (0..n)
+            .filter(|&v| trust[v] > 0.0)
+            .map(|v| PubkeyTrust {
+                computed_at,
+                pubkey: graph.pubkeys[v].to_string(),
+                trust: trust[v],
+                followers: graph.followers[v],
+            })
+            .collect();
+        scores.sort_by(|a, b| b.trust.total_cmp(&a.trust));
+        scores
+    }
+}
+
+/// Follow graph with pubkeys replaced by indexes.
+struct FollowGraph<'a> {
+    pubkeys: Vec<&'a str>,
+    index: HashMap<&'a str, usize>,
+    /// `(follower, followed)` pairs.
+    links: Vec<(usize, usize)>,
+    following: Vec<u64>,
+    followers: Vec<u64>,
+}
+
+impl<'a> FollowGraph<'a> {
+    fn new(edges: &'a [FollowEdge]) -> Self {
+        let mut graph = Self {
+            pubkeys: Vec::new(),
+            index: HashMap::new(),
+            links: Vec::with_capacity(edges.len()),
+            following: Vec::new(),
+            followers: Vec::new(),
+        };
+        for edge in edges {
+            let follower = graph.intern(&edge.follower);
+            let followed = graph.intern(&edge.followed);
+            graph.links.push((follower, followed));
+            graph.following[follower] += 1;
+            graph.followers[followed] += 1;
+        }
+        graph
+    }
+
+    fn intern(&mut self, pubkey: &'a str) -> usize {
+        *self.index.entry(pubkey).or_insert_with(|| {
+            self.pubkeys.push(pubkey);
+            self.following.push(0);
+            self.followers.push(0);
+            self.pubkeys.len() - 1
+        })
+    }
+
+    /// Rank each pubkey receives from its followers, each splitting theirs evenly
+    /// across everyone they follow.
+    fn pass(&self, rank: &[f64]) -> Vec<f64> {
+        let mut passed = vec![0.0; rank.len()];
+        for &(follower, followed) in &self.links {
+            passed[followed] += rank[follower] / self.following[follower] as f64;
+        }
+        passed
+    }
+}
+
+/// Compute and store a snapshot as of `now`, returning how many pubkeys it holds.
+pub async fn run_once<S>(
+    storage: &S,
+    config: &WotConfig,
+    now: DateTime<Utc>,
+) -> Result<usize, ClickHouseError>
+where
+    S: TrustQueries,
+{
+    let edges = storage.get_follow_edges().await?;
+    let scores = config.score(&edges, now);
+    storage.insert_pubkey_trust(&scores).await?;
+    Ok(scores.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    fn edges(pairs: &[(&str, &str)]) -> Vec<FollowEdge> {
+        pairs
+            .iter()
+            .map(|(follower, followed)| FollowEdge {
+                follower: follower.to_string(),
+                followed: followed.to_string(),
+            })
+            .collect()
+    }
+
+    fn now() -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000, 0).unwrap()
+    }
+
+    fn trust_of<'a>(scores: &'a [PubkeyTrust], pubkey: &str) -> Option<&'a PubkeyTrust> {
+        scores.iter().find(|score| score.pubkey == pubkey)
+    }
+
+    #[derive(Default)]
+    struct MockStorage {
+        edges: Vec<FollowEdge>,
+        written: Mutex<Vec<PubkeyTrust>>,
+    }
+
+    impl TrustQueries for MockStorage {
+        async fn get_follow_edges(&self) -> Result<Vec<FollowEdge>, ClickHouseError> {
+            Ok(self.edges.clone())
+        }
+
+        async fn insert_pubkey_trust(&self, scores: &[PubkeyTrust]) -> Result<(), ClickHouseError> {
+            self.written.lock().unwrap().extend_from_slice(scores);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn anchors_limit_trust_to_their_network() {
+        let config = WotConfig {
+            anchors: vec!["anchor".to_string()],
+            ..WotConfig::default()
+        };
+        let scores = config.score(
+            &edges(&[
+                ("anchor", "friend"),
+                ("friend", "friend-of-friend"),
+                ("spammer", "sybil"),
+                ("sybil", "spammer"),
+            ]),
+            now(),
+        );
+
+        assert_eq!(trust_of(&scores, "anchor").unwrap().trust, 1.0);
+        let friend = trust_of(&scores, "friend").unwrap();
+        let friend_of_friend = trust_of(&scores, "friend-of-friend").unwrap();
+        assert_eq!(friend.trust, 1.0);
+        assert!(friend_of_friend.trust > 0.0 && friend_of_friend.trust < friend.trust);
+        assert_eq!(friend.followers, 1);
+        assert!(trust_of(&scores, "spammer").is_none());
+        assert!(trust_of(&scores, "sybil").is_none());
+        assert!(scores.iter().all(|score| score.computed_at == now()));
+    }
+
+    #[test]
+    fn without_anchors_unfollowed_pubkeys_have_no_trust() {
+        let scores = WotConfig::default().score(
+            &edges(&[
+                ("a", "popular"),
+                ("b", "popular"),
+                ("c", "popular"),
+                ("a", "niche"),
+                ("lurker", "a"),
+            ]),
+            now(),
+        );
+
+        assert_eq!(scores[0].pubkey, "popular");
+        assert_eq!(scores[0].trust, 1.0);
+        assert_eq!(scores[0].followers, 3);
+        assert!(trust_of(&scores, "niche").unwrap().trust < 1.0);
+        assert!(trust_of(&scores, "lurker").is_none());
+        assert!(trust_of(&scores, "b").is_none());
+    }
+
+    #[tokio::test]
+    async fn run_once_writes_snapshot() {
+        let storage = MockStorage {
+            edges: edges(&[("a", "b"), ("b", "c")]),
+            ..MockStorage::default()
+        };
+
+        assert_eq!(
+            run_once(&storage, &WotConfig::default(), now())
+                .await
+                .unwrap(),
+            2
+        );
+        let written = storage.written.lock().unwrap();
+        assert!(written.iter().all(|score| score.pubkey != "a"));
+    }
+}
@@ -0,0 +1,447 @@
+//! Admin API handlers for operational actions.
+//!
+//! These routes are mounted under `/admin` only when `ADMIN_TOKEN` is configured,
+//! and always require that token regardless of the `/api` auth setting.
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use axum::{
+    Extension, Json,
+    extract::{Query, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use funnel_clickhouse::{
+    AdminQueries, BackfillRequest, EventDeletion, StatsQueries, VideoModeration, VideoQueries,
+};
+use funnel_observability::{LogFilterError, api, log_filter, set_log_filter};
+use funnel_proto::ErrorCode;
+use metrics::{counter, histogram};
+use serde::Deserialize;
+
+use crate::auth::TokenId;
+use crate::cache::ResponseCache;
+use crate::config::ApiConfig;
+use crate::handlers::AppState;
+use crate::params::EventIdParam;
+
+/// Request body for `POST /admin/backfill`.
+#[derive(Debug, Deserialize)]
+pub struct BackfillBody {
+    /// Window start (Unix seconds, inclusive).
+    pub since: i64,
+    /// Window end (Unix seconds, exclusive).
+    pub until: i64,
+    /// Event kinds to fetch; omitted or empty means all kinds.
+    #[serde(default)]
+    pub kinds: Vec<u16>,
+}
+
+/// Request body for `POST /admin/events/{id}/tombstone`.
+#[derive(Debug, Default, Deserialize)]
+pub struct TombstoneBody {
+    #[serde(default)]
+    pub reason: String,
+}
+
+/// Request body for `POST /admin/videos/{id}/hide` and `/unhide`.
+#[derive(Debug, Default, Deserialize)]
+pub struct ModerationBody {
+    #[serde(default)]
+    pub reason: String,
+}
+
+/// Query parameters for `GET /admin/reports`.
+#[derive(Debug, Deserialize)]
+pub struct ReportsQuery {
+    pub limit: Option<u32>,
+}
+
+/// Request body for `POST /admin/cache/purge`.
+#[derive(Debug, Default, Deserialize)]
+pub struct PurgeCacheBody {
+    /// Only purge responses whose path starts with this prefix.
+    #[serde(default)]
+    pub prefix: Option<String>,
+}
+
+/// Request body for `PUT /admin/log-level`.
+#[derive(Debug, Deserialize)]
+pub struct LogLevelBody {
+    /// Filter directives in `RUST_LOG` syntax, e.g. `info,funnel_api=debug`.
+    pub filter: String,
+}
+
+/// Queue a backfill of a time window for the ingestion service.
+pub async fn trigger_backfill<S>(
+    State(state): State<AppState<S>>,
+    Json(body): Json<BackfillBody>,
+) -> Response
+where
+    S: VideoQueries + StatsQueries + AdminQueries + Clone + Send + Sync + 'static,
+{
+    counter!(api::REQUESTS, "endpoint" => "admin_backfill").increment(1);
+
+    let window = DateTime::<Utc>::from_timestamp(body.since, 0)
+        .zip(DateTime::<Utc>::from_timestamp(body.until, 0))
+        .filter(|(since, until)| since < until);
+    let Some((since, until)) = window else {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            ErrorCode::ApiInvalidParameter,
+            "'since' must be a valid timestamp before 'until'",
+        );
+    };
+
+    let request = BackfillRequest::new(since, until, body.kinds);
+    match state.storage.upsert_backfill_request(&request).await {
+        Ok(()) => {
+            tracing::info!(
+                id = %request.id,
+                since = %request.since,
+                until = %request.until,
+                kinds = ?request.kinds,
+                "Queued backfill request"
+            );
+            (
+                StatusCode::ACCEPTED,
+                [(header::CACHE_CONTROL, "no-store")],
+                Json(serde_json::json!({
+                    "id": request.id,
+                    "status": request.status,
+                })),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            tracing::error!(code = %e.code(), error = %e, "Failed to queue backfill request");
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                e.code(),
+                "Internal server error",
+            )
+        }
+    }
+}
+
+/// Tombstone an event so it is hidden from video queries.
+///
+/// Purges the response cache so the event disappears immediately.
+pub async fn tombstone_event<S>(
+    State(state): State<AppState<S>>,
+    Extension(cache): Extension<Arc<ResponseCache>>,
+    EventIdParam(id): EventIdParam,
+    Json(body): Json<TombstoneBody>,
+) -> Response
+where
+    S: VideoQueries + StatsQueries + AdminQueries + Clone + Send + Sync + 'static,
+{
+    counter!(api::REQUESTS, "endpoint" => "admin_tombstone").increment(1);
+
+    let deletion = EventDeletion {
+        event_id: id,
+        deleted_at: Utc::now(),
+        deleted_by: "admin".to_string(),
+        deletion_event_id: String::new(),
+        reason: body.reason,
+    };
+
+    match state.storage.insert_deletion(&deletion).await {
+        Ok(()) => {
+            cache.purge(None).await;
+            (
+                StatusCode::OK,
+                [(header::CACHE_CONTROL, "no-store")],
+                Json(serde_json::json!({
+                    "event_id": deletion.event_id,
+                    "tombstoned": true,
+                })),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            tracing::error!(code = %e.code(), error = %e, "Failed to tombstone event");
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                e.code(),
+                "Internal server error",
+            )
+        }
+    }
+}
+
+/// Hide a video from listings, regardless of reports.
+///
+/// Purges the response cache so the video disappears immediately.
+pub async fn hide_video<S>(
+    State(state): State<AppState<S>>,
+    Extension(cache): Extension<Arc<ResponseCache>>,
+    token: Option<Extension<TokenId>>,
+    EventIdParam(id): EventIdParam,
+    body: Option<Json<ModerationBody>>,
+) -> Response
+where
+    S: VideoQueries + StatsQueries + AdminQueries + Clone + Send + Sync + 'static,
+{
+    counter!(api::REQUESTS, "endpoint" => "admin_hide").increment(1);
+    moderate(
+        &state,
+        &cache,
+        token,
+        id,
+        VideoModeration::STATUS_HIDDEN,
+        body,
+    )
+    .await
+}
+
+/// List a video again, and keep it listed however many reports it gets.
+///
+/// Purges the response cache so the video reappears immediately.
+pub async fn unhide_video<S>(
+    State(state): State<AppState<S>>,
+    Extension(cache): Extension<Arc<ResponseCache>>,
+    token: Option<Extension<TokenId>>,
+    EventIdParam(id): EventIdParam,
+    body: Option<Json<ModerationBody>>,
+) -> Response
+where
+    S: VideoQueries + StatsQueries + AdminQueries + Clone + Send + Sync + 'static,
+{
+    counter!(api::REQUESTS, "endpoint" => "admin_unhide").increment(1);
+    moderate(
+        &state,
+        &cache,
+        token,
+        id,
+        VideoModeration::STATUS_APPROVED,
+        body,
+    )
+    .await
+}
+
+async fn moderate<S>(
+    state: &AppState<S>,
+    cache: &ResponseCache,
+    token: Option<Extension<TokenId>>,
+    event_id: String,
+    status: &str,
+    body: Option<Json<ModerationBody>>,
+) -> Response
+where
+    S: VideoQueries + StatsQueries + AdminQueries + Clone + Send + Sync + 'static,
+{
+    let body = body.map(|Json(body)| body).unwrap_or_default();
+    let updated_by = token
+        .map(|Extension(token)| token.as_str().to_string())
+        .unwrap_or_default();
+    let moderation = VideoModeration::new(event_id, status, body.reason, updated_by);
+
+    match state.storage.upsert_video_moderation(&moderation).await {
+        Ok(()) => {
+            cache.purge(None).await;
+            (
+                StatusCode::OK,
+                [(header::CACHE_CONTROL, "no-store")],
+                Json(serde_json::json!({
+                    "event_id": moderation.event_id,
+                    "status": moderation.status,
+                })),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            tracing::error!(code = %e.code(), error = %e, "Failed to record video moderation");
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                e.code(),
+                "Internal server error",
+            )
+        }
+    }
+}
+
+/// Get reported videos, most distinct reporters first.
+pub async fn get_reports<S>(
+    State(state): State<AppState<S>>,
+    Query(params): Query<ReportsQuery>,
+) -> Response
+where
+    S: VideoQueries + StatsQueries + AdminQueries + Clone + Send + Sync + 'static,
+{
+    let start = Instant::now();
+    counter!(api::REQUESTS, "endpoint" => "admin_reports").increment(1);
+
+    let limit = params.limit.unwrap_or(50).min(500);
+    match state.storage.get_reported_videos(limit).await {
+        Ok(reports) => {
+            histogram!(api::QUERY_DURATION, "endpoint" => "admin_reports")
+                .record(start.elapsed().as_secs_f64());
+            (
+                StatusCode::OK,
+                [(header::CACHE_CONTROL, "no-store")],
+                Json(serde_json::json!({ "reports": reports })),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            tracing::error!(code = %e.code(), error = %e, "Failed to get reported videos");
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                e.code(),
+                "Internal server error",
+            )
+        }
+    }
+}
+
+/// Re-read file-backed API and admin tokens.
+pub async fn reload_config(Extension(config): Extension<ApiConfig>) -> Response {
+    counter!(api::REQUESTS, "endpoint" => "admin_reload").increment(1);
+
+    match config.reload() {
+        Ok(changed) => {
+            tracing::info!(changed, "Reloaded API tokens");
+            (
+                StatusCode::OK,
+                [(header::CACHE_CONTROL, "no-store")],
+                Json(serde_json::json!({ "reloaded": changed })),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            tracing::error!(
+                code = %ErrorCode::ApiInternal,
+                error = %e,
+                "Failed to reload API tokens"
+            );
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorCode::ApiInternal,
+                "Failed to reload tokens",
+            )
+        }
+    }
+}
+
+/// Drop cached responses, optionally only those under a path prefix.
+pub async fn purge_cache(
+    Extension(cache): Extension<Arc<ResponseCache>>,
+    body: Option<Json<PurgeCacheBody>>,
+) -> Response {
+    counter!(api::REQUESTS, "endpoint" => "admin_cache_purge").increment(1);
+
+    let body = body.map(|Json(body)| body).unwrap_or_default();
+    let purged = cache.purge(body.prefix.as_deref()).await;
+    tracing::info!(purged, prefix = ?body.prefix, "Purged response cache");
+    (
+        StatusCode::OK,
+        [(header::CACHE_CONTROL, "no-store")],
+        Json(serde_json::json!({ "purged": purged })),
+    )
+        .into_response()
+}
+
+/// Get the current log filter.
+pub async fn get_log_level() -> Response {
+    counter!(api::REQUESTS, "endpoint" => "admin_log_level").increment(1);
+
+    match log_filter() {
+        Some(filter) => (
+            StatusCode::OK,
+            [(header::CACHE_CONTROL, "no-store")],
+            Json(serde_json::json!({ "filter": filter })),
+        )
+            .into_response(),
+        None => error_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            ErrorCode::ApiInternal,
+            "Log filter is not reloadable",
+        ),
+    }
+}
+
+/// Replace the log filter without restarting.
+///
+/// The change lasts until the next restart, which goes back to `RUST_LOG`.
+pub async fn set_log_level(Json(body): Json<LogLevelBody>) -> Response {
+    counter!(api::REQUESTS, "endpoint" => "admin_log_level").increment(1);
+
+    let previous = log_filter();
+    match set_log_filter(&body.filter) {
+        Ok(()) => {
+            tracing::warn!(
+                filter = %body.filter,
+                previous = ?previous,
+                "Changed log filter"
+            );
+            (
+                StatusCode::OK,
+                [(header::CACHE_CONTROL, "no-store")],
+                Json(serde_json::json!({
+                    "filter": log_filter(),
+                    "previous": previous,
+                })),
+            )
+                .into_response()
+        }
+        Err(LogFilterError::Invalid(e)) => error_response(
+            StatusCode::BAD_REQUEST,
+            ErrorCode::ApiInvalidParameter,
+            &format!("Invalid filter: {e}"),
+        ),
+        Err(e) => {
+            tracing::error!(
+                code = %ErrorCode::ApiInternal,
+                error = %e,
+                "Failed to change log filter"
+            );
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                ErrorCode::ApiInternal,
+                "Log filter is not reloadable",
+            )
+        }
+    }
+}
+
+/// Get ingestion progress per relay source.
+pub async fn get_checkpoints<S>(State(state): State<AppState<S>>) -> Response
+where
+    S: VideoQueries + StatsQueries + AdminQueries + Clone + Send + Sync + 'static,
+{
+    let start = Instant::now();
+    counter!(api::REQUESTS, "endpoint" => "admin_checkpoints").increment(1);
+
+    match state.storage.get_ingestion_checkpoints().await {
+        Ok(checkpoints) => {
+            histogram!(api::QUERY_DURATION, "endpoint" => "admin_checkpoints")
+                .record(start.elapsed().as_secs_f64());
+            (
+                StatusCode::OK,
+                [(header::CACHE_CONTROL, "no-store")],
+                Json(serde_json::json!({ "checkpoints": checkpoints })),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            tracing::error!(code = %e.code(), error = %e, "Failed to get ingestion checkpoints");
+            error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                e.code(),
+                "Internal server error",
+            )
+        }
+    }
+}
+
+fn error_response(status: StatusCode, code: ErrorCode, message: &str) -> Response {
+    (
+        status,
+        [(header::CACHE_CONTROL, "no-store")],
+        Json(serde_json::json!({ "error": message, "code": code })),
+    )
+        .into_response()
+}
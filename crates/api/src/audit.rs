@@ -0,0 +1,136 @@
+//! Audit records for admin actions.
+//!
+//! Every admin request that changes something (anything but `GET`/`HEAD`) is written
+//! to the audit stream (see [`funnel_observability::audit`]) once it completes: who
+//! made it (by [`TokenId`]), the route and its parameters, and the outcome. Requests
+//! rejected before reaching the handler, such as by rate limits, are recorded too.
+
+use std::time::Instant;
+
+use axum::{
+    Json,
+    body::{Body, to_bytes},
+    extract::{MatchedPath, Request},
+    http::{Method, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use funnel_observability::audit::audit;
+use funnel_proto::ErrorCode;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::auth::TokenId;
+
+/// Largest request body buffered for an audit record.
+///
+/// Admin request bodies are small JSON documents; larger ones are rejected with `413`.
+pub const MAX_AUDITED_BODY_BYTES: usize = 64 * 1024;
+
+/// One audited request.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AuditRecord {
+    /// Caller, or `None` if auth didn't identify one.
+    pub token_id: Option<String>,
+    pub method: String,
+    /// Route pattern, e.g. `/admin/events/{id}/tombstone`.
+    pub route: String,
+    pub path: String,
+    pub query: Option<String>,
+    /// JSON request body, if any.
+    pub params: Option<Value>,
+    pub status: u16,
+    pub outcome: Outcome,
+    pub duration_ms: u64,
+}
+
+/// Whether an audited request succeeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Success,
+    Failure,
+}
+
+impl Outcome {
+    fn from_status(status: StatusCode) -> Self {
+        if status.is_success() || status.is_redirection() {
+            Self::Success
+        } else {
+            Self::Failure
+        }
+    }
+}
+
+/// Middleware writing an [`AuditRecord`] for each state-changing request.
+///
+/// Must run inside [`require_auth`](crate::auth::require_auth) so the caller's
+/// [`TokenId`] is known.
+pub async fn audit_log(request: Request, next: Next) -> Response {
+    if matches!(*request.method(), Method::GET | Method::HEAD) {
+        return next.run(request).await;
+    }
+
+    let start = Instant::now();
+    let (parts, body) = request.into_parts();
+    let Ok(body) = to_bytes(body, MAX_AUDITED_BODY_BYTES).await else {
+        return (
+            StatusCode::PAYLOAD_TOO_LARGE,
+            [(header::CACHE_CONTROL, "no-store")],
+            Json(serde_json::json!({
+                "error": "Request body too large",
+                "code": ErrorCode::ApiPayloadTooLarge,
+            })),
+        )
+            .into_response();
+    };
+
+    let token_id = parts
+        .extensions
+        .get::<TokenId>()
+        .map(|id| id.as_str().to_string());
+    let route = parts
+        .extensions
+        .get::<MatchedPath>()
+        .map_or_else(|| parts.uri.path(), MatchedPath::as_str)
+        .to_string();
+    let method = parts.method.to_string();
+    let path = parts.uri.path().to_string();
+    let query = parts.uri.query().map(str::to_string);
+    let params = serde_json::from_slice(&body).ok();
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+
+    let status = response.status();
+    audit(&AuditRecord {
+        token_id,
+        method,
+        route,
+        path,
+        query,
+        params,
+        status: status.as_u16(),
+        outcome: Outcome::from_status(status),
+        duration_ms: start.elapsed().as_millis() as u64,
+    });
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outcome_follows_status() {
+        assert_eq!(Outcome::from_status(StatusCode::OK), Outcome::Success);
+        assert_eq!(Outcome::from_status(StatusCode::ACCEPTED), Outcome::Success);
+        assert_eq!(
+            Outcome::from_status(StatusCode::TOO_MANY_REQUESTS),
+            Outcome::Failure
+        );
+        assert_eq!(
+            Outcome::from_status(StatusCode::INTERNAL_SERVER_ERROR),
+            Outcome::Failure
+        );
+    }
+}
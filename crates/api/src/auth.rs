@@ -1,4 +1,14 @@
 //! Bearer token authentication middleware.
+//!
+//! Requests authenticate with a static token or, when configured, a JWT issued by an
+//! identity provider (see [`crate::jwt`]).
+//!
+//! Tokens can belong to a tenant (see [`crate::tenant`]): a static token through its
+//! line in the token file, a JWT through its tenant claim. An auth config accepts
+//! only the tokens of its own tenant, or only tokens without one.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
 
 use axum::{
     Json,
@@ -8,45 +18,234 @@ use axum::{
     middleware::Next,
     response::{IntoResponse, Response},
 };
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use funnel_proto::ErrorCode;
+use sha2::{Digest, Sha256};
 use subtle::ConstantTimeEq;
 
+use crate::jwt::{JwtError, JwtIdentity, JwtVerifier};
+
 /// Configuration for bearer token authentication.
+///
+/// Clones share the same tokens, so a [`reload`](Self::reload) is visible to every
+/// router layer holding this config.
 #[derive(Clone)]
 pub struct AuthConfig {
-    /// The expected bearer token value.
-    token: String,
+    /// Static bearer tokens, including other tenants'.
+    tokens: Arc<RwLock<Vec<ApiToken>>>,
+    /// File the tokens were read from, re-read on reload.
+    token_file: Option<PathBuf>,
+    /// JWTs accepted in addition to the static tokens.
+    jwt: Option<JwtAuth>,
+    /// Tenant whose tokens are accepted; `None` accepts tokens without a tenant.
+    tenant: Option<String>,
+}
+
+/// A static token and the tenant it belongs to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ApiToken {
+    value: String,
+    tenant: Option<String>,
+}
+
+impl ApiToken {
+    /// Parse a token file line: the token, optionally followed by its tenant.
+    fn parse(line: &str) -> Self {
+        let mut fields = line.split_whitespace();
+        Self {
+            value: fields.next().unwrap_or_default().to_string(),
+            tenant: fields.next().map(str::to_string),
+        }
+    }
+}
+
+/// JWT acceptance for one auth config.
+#[derive(Clone)]
+struct JwtAuth {
+    verifier: Arc<JwtVerifier>,
+    /// Scope the token must carry, if any.
+    scope: Option<String>,
 }
 
 impl AuthConfig {
     /// Create a new auth config with the given token.
     pub fn new(token: impl Into<String>) -> Self {
+        Self::with_tokens(vec![token.into()])
+    }
+
+    /// Create a new auth config accepting any of the given tokens.
+    pub fn with_tokens(tokens: Vec<String>) -> Self {
+        let tokens = tokens
+            .into_iter()
+            .map(|value| ApiToken {
+                value,
+                tenant: None,
+            })
+            .collect();
         Self {
-            token: token.into(),
+            tokens: Arc::new(RwLock::new(tokens)),
+            token_file: None,
+            jwt: None,
+            tenant: None,
         }
     }
 
-    /// Create auth config from the API_TOKEN environment variable.
+    /// Create an auth config that accepts only JWTs.
+    pub fn jwt_only(verifier: Arc<JwtVerifier>, scope: Option<String>) -> Self {
+        Self::with_tokens(Vec::new()).with_jwt(verifier, scope)
+    }
+
+    /// Also accept JWTs validated by `verifier`, requiring `scope` when given.
+    pub fn with_jwt(mut self, verifier: Arc<JwtVerifier>, scope: Option<String>) -> Self {
+        self.jwt = Some(JwtAuth { verifier, scope });
+        self
+    }
+
+    /// This config, accepting only the tokens of `tenant`.
+    ///
+    /// The tokens stay shared, so a reload is visible to every tenant.
+    pub fn for_tenant(&self, tenant: impl Into<String>) -> Self {
+        Self {
+            tenant: Some(tenant.into()),
+            ..self.clone()
+        }
+    }
+
+    /// Create auth config from the API_TOKEN (or API_TOKEN_FILE) environment variable.
     ///
     /// Returns `None` if the environment variable is not set or is empty.
     pub fn from_env() -> Option<Self> {
-        std::env::var("API_TOKEN")
-            .ok()
-            .filter(|s| !s.is_empty())
-            .map(Self::new)
+        Self::from_env_var("API_TOKEN")
+    }
+
+    /// Create auth config from `{var}`, falling back to a token file named by `{var}_FILE`.
+    ///
+    /// The file holds one token per line, so each client can get its own token; blank
+    /// lines and `#` comments are skipped. A token followed by a tenant ID (separated
+    /// by whitespace) belongs to that tenant. Returns `None` if neither is set or
+    /// there are no tokens.
+    pub fn from_env_var(var: &str) -> Option<Self> {
+        if let Some(token) = std::env::var(var).ok().filter(|s| !s.is_empty()) {
+            return Some(Self::new(token));
+        }
+
+        let path = PathBuf::from(std::env::var(format!("{}_FILE", var)).ok()?);
+        match read_token_file(&path) {
+            Ok(tokens) if !tokens.is_empty() => Some(Self {
+                tokens: Arc::new(RwLock::new(parse_tokens(&tokens))),
+                token_file: Some(path),
+                jwt: None,
+                tenant: None,
+            }),
+            Ok(_) => None,
+            Err(e) => {
+                tracing::error!(error = %e, path = %path.display(), "Failed to read token file");
+                None
+            }
+        }
     }
 
-    /// Validate a bearer token against the configured token.
+    /// Re-read the tokens from their file.
+    ///
+    /// Returns `Ok(true)` if the tokens changed, and `Ok(false)` when unchanged or
+    /// when the tokens were not loaded from a file.
+    pub fn reload(&self) -> std::io::Result<bool> {
+        let Some(path) = &self.token_file else {
+            return Ok(false);
+        };
+
+        let tokens = parse_tokens(&read_token_file(path)?);
+        if tokens.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "token file is empty",
+            ));
+        }
+
+        let mut current = self.tokens.write().unwrap_or_else(|e| e.into_inner());
+        if *current == tokens {
+            return Ok(false);
+        }
+        *current = tokens;
+        Ok(true)
+    }
+
+    /// Validate a bearer token against the configured tokens of this config's tenant.
     pub fn validate(&self, token: &str) -> bool {
-        // Use constant-time comparison to prevent timing attacks
+        self.find(token)
+            .is_some_and(|token| token.tenant == self.tenant)
+    }
+
+    /// The tenant a token belongs to, whichever tenant this config accepts.
+    ///
+    /// JWTs aren't verified here, so this only decides which tenant's routes see the
+    /// request; those routes then validate the token.
+    pub fn tenant_of(&self, token: &str) -> Option<String> {
+        match self.find(token) {
+            Some(token) => token.tenant,
+            None => self
+                .jwt
+                .as_ref()
+                .and_then(|jwt| jwt.verifier.unverified_tenant(token)),
+        }
+    }
+
+    /// The static token matching `token`.
+    fn find(&self, token: &str) -> Option<ApiToken> {
+        let expected = self.tokens.read().unwrap_or_else(|e| e.into_inner());
+
+        // Use constant-time comparison to prevent timing attacks, and check every
+        // token so the position of a match isn't revealed either
         let a = token.as_bytes();
-        let b = self.token.as_bytes();
+        expected.iter().fold(None, |found, expected| {
+            let b = expected.value.as_bytes();
+            // Length check is not constant-time, but that's acceptable for tokens
+            // since the expected token length is not secret
+            let equal = a.len() == b.len() && bool::from(a.ct_eq(b));
+            if equal { Some(expected.clone()) } else { found }
+        })
+    }
+}
+
+/// Stable, non-secret identifier for an authenticated token.
+///
+/// Derived from a SHA-256 of the token, so usage can be tracked and reported per
+/// token without keeping the token itself. Inserted into request extensions by
+/// [`require_auth`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TokenId(String);
+
+impl TokenId {
+    pub fn from_token(token: &str) -> Self {
+        let digest = Sha256::digest(token.as_bytes());
+        Self(digest[..8].iter().map(|b| format!("{b:02x}")).collect())
+    }
+
+    /// Identifier for a JWT subject, so usage is tracked per client across tokens.
+    pub fn from_identity(identity: &JwtIdentity) -> Self {
+        let issuer = identity.issuer.as_deref().unwrap_or_default();
+        Self::from_token(&format!("jwt:{issuer}:{}", identity.subject))
+    }
 
-        // Length check is not constant-time, but that's acceptable for tokens
-        // since the expected token length is not secret
-        a.len() == b.len() && a.ct_eq(b).into()
+    pub fn as_str(&self) -> &str {
+        &self.0
     }
 }
 
+fn parse_tokens(lines: &[String]) -> Vec<ApiToken> {
+    lines.iter().map(|line| ApiToken::parse(line)).collect()
+}
+
+fn read_token_file(path: &Path) -> std::io::Result<Vec<String>> {
+    Ok(std::fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
 /// Extract bearer token from Authorization header.
 fn extract_bearer_token(headers: &HeaderMap) -> Option<&str> {
     headers
@@ -55,29 +254,95 @@ fn extract_bearer_token(headers: &HeaderMap) -> Option<&str> {
         .and_then(|value| value.strip_prefix("Bearer "))
 }
 
+/// Extract the token from a `Bearer` header, or the password of a `Basic` header.
+///
+/// Basic auth lets browsers open token-protected pages such as `/dashboard`; the
+/// username is ignored.
+pub(crate) fn extract_token(headers: &HeaderMap) -> Option<String> {
+    if let Some(token) = extract_bearer_token(headers) {
+        return Some(token.to_string());
+    }
+
+    let encoded = headers
+        .get("authorization")?
+        .to_str()
+        .ok()?
+        .strip_prefix("Basic ")?;
+    let decoded = String::from_utf8(BASE64.decode(encoded.trim()).ok()?).ok()?;
+    let (_, password) = decoded.split_once(':')?;
+    Some(password.to_string())
+}
+
 /// Authentication middleware that validates bearer tokens.
 ///
 /// This middleware checks for a valid `Authorization: Bearer <token>` header
-/// and rejects requests that don't have a valid token.
-pub async fn require_auth(headers: HeaderMap, request: Request<Body>, next: Next) -> Response {
+/// (or Basic credentials with the token as password) and rejects requests that
+/// don't have a valid token. When JWTs are accepted, a token that isn't one of the
+/// static tokens is verified as a JWT, and its [`JwtIdentity`] is added to the
+/// request extensions.
+pub async fn require_auth(headers: HeaderMap, mut request: Request<Body>, next: Next) -> Response {
     // Get auth config from request extensions
     let auth_config = request
         .extensions()
         .get::<AuthConfig>()
+        .cloned()
         .expect("AuthConfig not found in request extensions");
 
-    match extract_bearer_token(&headers) {
-        Some(token) if auth_config.validate(token) => next.run(request).await,
-        Some(_) => unauthorized_response("Invalid token"),
-        None => unauthorized_response("Missing authorization header"),
+    let Some(token) = extract_token(&headers) else {
+        return unauthorized_response("Missing authorization header");
+    };
+    if auth_config.validate(&token) {
+        request.extensions_mut().insert(TokenId::from_token(&token));
+        return next.run(request).await;
+    }
+
+    let Some(jwt) = auth_config.jwt else {
+        return unauthorized_response("Invalid token");
+    };
+    match jwt.verifier.verify(&token).await {
+        Ok(identity) if identity.tenant != auth_config.tenant => {
+            unauthorized_response("Invalid token")
+        }
+        Ok(identity) if jwt.scope.as_ref().is_none_or(|s| identity.has_scope(s)) => {
+            request
+                .extensions_mut()
+                .insert(TokenId::from_identity(&identity));
+            request.extensions_mut().insert(identity);
+            next.run(request).await
+        }
+        Ok(_) => error_response(
+            StatusCode::FORBIDDEN,
+            ErrorCode::ApiForbidden,
+            "Insufficient scope",
+        ),
+        Err(JwtError::Jwks(e)) => {
+            tracing::error!(code = %ErrorCode::ApiUnavailable, error = %e, "JWKS unavailable");
+            error_response(
+                StatusCode::SERVICE_UNAVAILABLE,
+                ErrorCode::ApiUnavailable,
+                "Token verification unavailable",
+            )
+        }
+        Err(e) => {
+            tracing::debug!(error = %e, "Rejected JWT");
+            unauthorized_response("Invalid token")
+        }
     }
 }
 
 /// Generate a 401 Unauthorized response with a JSON error body.
 fn unauthorized_response(message: &str) -> Response {
-    (
+    error_response(
         StatusCode::UNAUTHORIZED,
-        Json(serde_json::json!({ "error": message })),
+        ErrorCode::ApiUnauthorized,
+        message,
+    )
+}
+
+fn error_response(status: StatusCode, code: ErrorCode, message: &str) -> Response {
+    (
+        status,
+        Json(serde_json::json!({ "error": message, "code": code })),
     )
         .into_response()
 }
@@ -131,6 +396,109 @@ mod tests {
         assert_eq!(extract_bearer_token(&headers), None);
     }
 
+    #[test]
+    fn extract_token_accepts_basic_password() {
+        let mut headers = HeaderMap::new();
+        // "admin:my-token"
+        headers.insert(
+            "authorization",
+            "Basic YWRtaW46bXktdG9rZW4=".parse().unwrap(),
+        );
+        assert_eq!(extract_token(&headers), Some("my-token".to_string()));
+    }
+
+    #[test]
+    fn extract_token_prefers_bearer() {
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", "Bearer my-token".parse().unwrap());
+        assert_eq!(extract_token(&headers), Some("my-token".to_string()));
+    }
+
+    #[test]
+    fn extract_token_rejects_malformed_basic() {
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", "Basic not-base64!".parse().unwrap());
+        assert_eq!(extract_token(&headers), None);
+    }
+
+    #[test]
+    fn auth_config_accepts_any_configured_token() {
+        let config = AuthConfig::with_tokens(vec!["client-a".to_string(), "client-b".to_string()]);
+        assert!(config.validate("client-a"));
+        assert!(config.validate("client-b"));
+        assert!(!config.validate("client-c"));
+    }
+
+    #[test]
+    fn token_id_is_stable_and_hides_token() {
+        let id = TokenId::from_token("secret-token-123");
+        assert_eq!(id, TokenId::from_token("secret-token-123"));
+        assert_ne!(id, TokenId::from_token("secret-token-456"));
+        assert_eq!(id.as_str().len(), 16);
+        assert!(!id.as_str().contains("secret"));
+    }
+
+    #[test]
+    fn read_token_file_skips_blank_lines_and_comments() {
+        let path = std::env::temp_dir().join(format!("funnel-tokens-{}", std::process::id()));
+        std::fs::write(&path, "# partner tokens\nclient-a\n\n  client-b  \n").unwrap();
+
+        assert_eq!(read_token_file(&path).unwrap(), ["client-a", "client-b"]);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn tenant_tokens_are_only_accepted_for_their_tenant() {
+        let config = AuthConfig::new("shared-token");
+        config
+            .tokens
+            .write()
+            .unwrap()
+            .push(ApiToken::parse("acme-token  acme"));
+        let acme = config.for_tenant("acme");
+
+        assert!(config.validate("shared-token"));
+        assert!(!config.validate("acme-token"));
+        assert!(acme.validate("acme-token"));
+        assert!(!acme.validate("shared-token"));
+        assert_eq!(config.tenant_of("acme-token").as_deref(), Some("acme"));
+        assert_eq!(acme.tenant_of("shared-token"), None);
+        assert_eq!(acme.tenant_of("unknown-token"), None);
+    }
+
+    #[test]
+    fn reload_without_token_file_is_noop() {
+        let config = AuthConfig::new("secret-token-123");
+        assert!(!config.reload().unwrap());
+        assert!(config.validate("secret-token-123"));
+    }
+
+    #[test]
+    fn reload_picks_up_new_token_from_file() {
+        let path = std::env::temp_dir().join(format!("funnel-token-{}", std::process::id()));
+        std::fs::write(&path, "first-token\n").unwrap();
+
+        // SAFETY: This test runs single-threaded (--test-threads=1)
+        unsafe {
+            std::env::set_var("RELOAD_TEST_TOKEN_FILE", &path);
+        }
+        let config = AuthConfig::from_env_var("RELOAD_TEST_TOKEN").unwrap();
+        let clone = config.clone();
+        assert!(config.validate("first-token"));
+
+        std::fs::write(&path, "second-token").unwrap();
+        assert!(config.reload().unwrap());
+        assert!(clone.validate("second-token"));
+        assert!(!clone.validate("first-token"));
+
+        // Clean up
+        unsafe {
+            std::env::remove_var("RELOAD_TEST_TOKEN_FILE");
+        }
+        let _ = std::fs::remove_file(&path);
+    }
+
     #[test]
     fn from_env_returns_none_for_empty_string() {
         // Simulate empty API_TOKEN (as set by docker-compose with ${API_TOKEN:-})
@@ -0,0 +1,632 @@
+//! Response cache for GET endpoints.
+//!
+//! `HEAD` requests share entries with `GET`, so uptime checks and CDN probes never
+//! reach the database for a cached path. Only `200` responses the handler marks
+//! `public, max-age=N` are cached, so each route keeps its own TTL. Entries are
+//! keyed by path, sorted query string, and negotiated response format.
+//!
+//! Once an entry expires it is still served for `stale_while_revalidate` while a
+//! single background request refreshes it. If the upstream request fails (a 5xx or
+//! 408, including circuit breaker and load-shedding rejections), an expired entry
+//! is served for up to `stale_if_error` instead of the error.
+//!
+//! Entries live in process memory by default. With the `redis` feature, replicas can
+//! share a Redis instance instead, so a response cached (or purged) by one pod is
+//! seen by all of them.
+
+mod memory;
+#[cfg(feature = "redis")]
+mod redis;
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use axum::{
+    Json,
+    body::{Body, Bytes, HttpBody},
+    extract::{Request, State},
+    http::{HeaderMap, HeaderValue, Method, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use funnel_observability::api;
+use funnel_proto::ErrorCode;
+use metrics::counter;
+
+use crate::format::ResponseFormat;
+
+use self::memory::MemoryStore;
+#[cfg(feature = "redis")]
+use self::redis::RedisStore;
+
+/// Header reporting whether a response came from the cache.
+pub const X_CACHE: &str = "x-cache";
+
+/// Default maximum number of cached responses (in-memory backend).
+pub const DEFAULT_MAX_ENTRIES: usize = 10_000;
+
+/// Default size limit for a single cached response body (1 MiB).
+pub const DEFAULT_MAX_ENTRY_BYTES: usize = 1024 * 1024;
+
+/// Default time an expired entry is served while it is refreshed.
+pub const DEFAULT_STALE_WHILE_REVALIDATE: Duration = Duration::from_secs(60);
+
+/// Default time an expired entry may be served when the upstream request fails.
+pub const DEFAULT_STALE_IF_ERROR: Duration = Duration::from_secs(300);
+
+/// Where cached responses are stored.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub enum CacheBackend {
+    /// Per-process memory.
+    #[default]
+    Memory,
+    /// A Redis instance shared by all replicas. Requires the `redis` feature.
+    Redis { url: String },
+}
+
+/// Response cache settings.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheConfig {
+    /// Whether the cache is active.
+    pub enabled: bool,
+    /// Storage backend.
+    pub backend: CacheBackend,
+    /// Maximum number of cached responses in memory. `0` disables the cache.
+    pub max_entries: usize,
+    /// Responses with larger bodies are never cached.
+    pub max_entry_bytes: usize,
+    /// How long past its TTL an entry is served while being refreshed.
+    pub stale_while_revalidate: Duration,
+    /// How long past its TTL an entry is served in place of an upstream error.
+    pub stale_if_error: Duration,
+    /// Tenant whose responses are cached, keeping its entries apart from other
+    /// tenants' in a shared backend.
+    pub tenant: Option<String>,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            backend: CacheBackend::Memory,
+            max_entries: DEFAULT_MAX_ENTRIES,
+            max_entry_bytes: DEFAULT_MAX_ENTRY_BYTES,
+            stale_while_revalidate: DEFAULT_STALE_WHILE_REVALIDATE,
+            stale_if_error: DEFAULT_STALE_IF_ERROR,
+            tenant: None,
+        }
+    }
+}
+
+impl CacheConfig {
+    /// Load from `RESPONSE_CACHE_ENABLED`, `RESPONSE_CACHE_REDIS_URL`,
+    /// `RESPONSE_CACHE_MAX_ENTRIES`, `RESPONSE_CACHE_MAX_ENTRY_BYTES`,
+    /// `RESPONSE_CACHE_SWR_SECS`, and `RESPONSE_CACHE_STALE_IF_ERROR_SECS`.
+    ///
+    /// Setting `RESPONSE_CACHE_REDIS_URL` selects the Redis backend.
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            enabled: std::env::var("RESPONSE_CACHE_ENABLED")
+                .map(|v| !matches!(v.to_ascii_lowercase().as_str(), "0" | "false" | "no"))
+                .unwrap_or(defaults.enabled),
+            backend: match std::env::var("RESPONSE_CACHE_REDIS_URL") {
+                Ok(url) if !url.is_empty() => CacheBackend::Redis { url },
+                _ => defaults.backend,
+            },
+            max_entries: env_parse("RESPONSE_CACHE_MAX_ENTRIES").unwrap_or(defaults.max_entries),
+            max_entry_bytes: env_parse("RESPONSE_CACHE_MAX_ENTRY_BYTES")
+                .unwrap_or(defaults.max_entry_bytes),
+            stale_while_revalidate: env_parse("RESPONSE_CACHE_SWR_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.stale_while_revalidate),
+            stale_if_error: env_parse("RESPONSE_CACHE_STALE_IF_ERROR_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.stale_if_error),
+            tenant: defaults.tenant,
+        }
+    }
+
+    fn is_active(&self) -> bool {
+        self.enabled && self.max_entries > 0
+    }
+
+    /// How long an entry stays useful past its TTL.
+    fn retention(&self) -> Duration {
+        self.stale_while_revalidate.max(self.stale_if_error)
+    }
+}
+
+/// Normalized cache key for a request.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    path: String,
+    /// Query pairs, sorted so parameter order doesn't matter.
+    query: String,
+    format: ResponseFormat,
+}
+
+impl CacheKey {
+    fn from_request(request: &Request) -> Self {
+        let mut pairs: Vec<&str> = request
+            .uri()
+            .query()
+            .unwrap_or("")
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .collect();
+        pairs.sort_unstable();
+
+        let format_param = pairs.iter().find_map(|pair| pair.strip_prefix("format="));
+        let format = ResponseFormat::negotiate(request.headers(), format_param);
+
+        Self {
+            path: request.uri().path().to_string(),
+            query: pairs.join("&"),
+            format,
+        }
+    }
+}
+
+impl fmt::Display for CacheKey {
+    /// `path?query#format`; `#` never appears in a request path or query, and
+    /// keeping the path first lets backends purge by path prefix.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let format = match self.format {
+            ResponseFormat::Json => "json",
+            ResponseFormat::Csv => "csv",
+        };
+        write!(f, "{}?{}#{}", self.path, self.query, format)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CachedResponse {
+    headers: HeaderMap,
+    body: Bytes,
+    /// Wall-clock time, so entries shared between replicas age consistently.
+    stored_at: SystemTime,
+    ttl: Duration,
+}
+
+impl CachedResponse {
+    fn age(&self, now: SystemTime) -> Duration {
+        now.duration_since(self.stored_at).unwrap_or_default()
+    }
+
+    fn is_fresh(&self, now: SystemTime) -> bool {
+        self.age(now) < self.ttl
+    }
+
+    /// Whether the entry is still within `window` past its TTL.
+    fn is_usable_within(&self, now: SystemTime, window: Duration) -> bool {
+        self.age(now) < self.ttl + window
+    }
+
+    fn to_response(&self, cache_status: &'static str, now: SystemTime) -> Response {
+        let mut response = Response::new(Body::from(self.body.clone()));
+        *response.headers_mut() = self.headers.clone();
+        response
+            .headers_mut()
+            .insert(header::AGE, HeaderValue::from(self.age(now).as_secs()));
+        response
+            .headers_mut()
+            .insert(X_CACHE, HeaderValue::from_static(cache_status));
+        response
+    }
+}
+
+enum Lookup {
+    Fresh(CachedResponse),
+    /// Expired but within the stale-while-revalidate window. `refresh` is set for
+    /// the one request that should revalidate it.
+    Stale {
+        entry: CachedResponse,
+        refresh: bool,
+    },
+    Miss,
+}
+
+enum Store {
+    Memory(MemoryStore),
+    #[cfg(feature = "redis")]
+    Redis(RedisStore),
+}
+
+/// Shared response cache.
+///
+/// Backend errors are logged and treated as misses, so an unavailable Redis never
+/// fails a request.
+pub struct ResponseCache {
+    config: CacheConfig,
+    store: Store,
+}
+
+impl ResponseCache {
+    pub fn new(config: CacheConfig) -> Self {
+        let memory = || Store::Memory(MemoryStore::new(config.max_entries, config.retention()));
+        let store = match &config.backend {
+            CacheBackend::Memory => memory(),
+            #[cfg(feature = "redis")]
+            CacheBackend::Redis { url } => {
+                match RedisStore::new(url, config.retention(), config.tenant.as_deref()) {
+                    Ok(store) => Store::Redis(store),
+                    Err(e) => {
+                        tracing::error!(error = %e, "Invalid Redis URL, using in-memory cache");
+                        memory()
+                    }
+                }
+            }
+            #[cfg(not(feature = "redis"))]
+            CacheBackend::Redis { .. } => {
+                tracing::warn!("Built without the redis feature, using in-memory cache");
+                memory()
+            }
+        };
+
+        Self { config, store }
+    }
+
+    /// Remove cached responses whose path starts with `prefix`, or all of them.
+    ///
+    /// Returns the number of entries removed.
+    pub async fn purge(&self, prefix: Option<&str>) -> usize {
+        match &self.store {
+            Store::Memory(store) => store.purge(prefix),
+            #[cfg(feature = "redis")]
+            Store::Redis(store) => store.purge(prefix).await.unwrap_or_else(|e| {
+                backend_error("purge", e);
+                0
+            }),
+        }
+    }
+
+    async fn get(&self, key: &CacheKey) -> Option<CachedResponse> {
+        match &self.store {
+            Store::Memory(store) => store.get(key),
+            #[cfg(feature = "redis")]
+            Store::Redis(store) => store.get(key).await.unwrap_or_else(|e| {
+                backend_error("get", e);
+                None
+            }),
+        }
+    }
+
+    async fn put(&self, key: &CacheKey, entry: CachedResponse) {
+        match &self.store {
+            Store::Memory(store) => store.put(key, entry),
+            #[cfg(feature = "redis")]
+            Store::Redis(store) => {
+                if let Err(e) = store.put(key, &entry).await {
+                    backend_error("put", e);
+                }
+            }
+        }
+    }
+
+    /// Claim the refresh of a stale entry. Returns `false` if someone else has it.
+    async fn begin_refresh(&self, key: &CacheKey) -> bool {
+        match &self.store {
+            Store::Memory(store) => store.begin_refresh(key),
+            #[cfg(feature = "redis")]
+            Store::Redis(store) => store.begin_refresh(key).await.unwrap_or_else(|e| {
+                backend_error("begin_refresh", e);
+                false
+            }),
+        }
+    }
+
+    async fn finish_refresh(&self, key: &CacheKey) {
+        match &self.store {
+            Store::Memory(store) => store.finish_refresh(key),
+            #[cfg(feature = "redis")]
+            Store::Redis(store) => {
+                if let Err(e) = store.finish_refresh(key).await {
+                    backend_error("finish_refresh", e);
+                }
+            }
+        }
+    }
+
+    async fn lookup(&self, key: &CacheKey, now: SystemTime) -> Lookup {
+        let Some(entry) = self.get(key).await else {
+            return Lookup::Miss;
+        };
+
+        if entry.is_fresh(now) {
+            return Lookup::Fresh(entry);
+        }
+        if !entry.is_usable_within(now, self.config.stale_while_revalidate) {
+            return Lookup::Miss;
+        }
+
+        let refresh = self.begin_refresh(key).await;
+        Lookup::Stale { entry, refresh }
+    }
+
+    async fn stale_if_error(&self, key: &CacheKey, now: SystemTime) -> Option<CachedResponse> {
+        self.get(key)
+            .await
+            .filter(|entry| entry.is_usable_within(now, self.config.stale_if_error))
+    }
+}
+
+#[cfg(feature = "redis")]
+fn backend_error<E>(operation: &'static str, error: E)
+where
+    E: fmt::Display,
+{
+    counter!(api::CACHE_ERRORS, "operation" => operation).increment(1);
+    tracing::warn!(operation, error = %error, "Response cache backend error");
+}
+
+/// Middleware serving cached responses for GET and HEAD requests.
+pub async fn response_cache(
+    State(cache): State<Arc<ResponseCache>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !cache.config.is_active() || !matches!(*request.method(), Method::GET | Method::HEAD) {
+        return next.run(request).await;
+    }
+
+    let key = CacheKey::from_request(&request);
+    let now = SystemTime::now();
+
+    match cache.lookup(&key, now).await {
+        Lookup::Fresh(entry) => {
+            counter!(api::CACHE_REQUESTS, "result" => "hit").increment(1);
+            return entry.to_response("HIT", now);
+        }
+        Lookup::Stale { entry, refresh } => {
+            counter!(api::CACHE_REQUESTS, "result" => "stale").increment(1);
+            if refresh {
+                tokio::spawn(revalidate(cache.clone(), key, request, next));
+            }
+            return entry.to_response("STALE", now);
+        }
+        Lookup::Miss => {}
+    }
+
+    counter!(api::CACHE_REQUESTS, "result" => "miss").increment(1);
+    let response = next.run(request).await;
+
+    if is_upstream_failure(response.status()) {
+        let now = SystemTime::now();
+        if let Some(entry) = cache.stale_if_error(&key, now).await {
+            tracing::debug!(key = %key, status = %response.status(), "Serving stale response");
+            return entry.to_response("STALE", now);
+        }
+        return response;
+    }
+
+    store(&cache, &key, response).await
+}
+
+/// Refresh a stale entry in the background.
+async fn revalidate(cache: Arc<ResponseCache>, key: CacheKey, request: Request, next: Next) {
+    let response = next.run(request).await;
+    if response.status() == StatusCode::OK {
+        store(&cache, &key, response).await;
+    } else {
+        tracing::debug!(
+            key = %key,
+            status = %response.status(),
+            "Cache revalidation failed, keeping stale entry"
+        );
+    }
+    cache.finish_refresh(&key).await;
+}
+
+/// Cache the response if it's cacheable and return it to the caller, marked as a miss.
+async fn store(cache: &ResponseCache, key: &CacheKey, response: Response) -> Response {
+    let Some(ttl) = cacheable_ttl(&response) else {
+        return response;
+    };
+    let size = response.body().size_hint().exact();
+    if size.is_none_or(|size| size > cache.config.max_entry_bytes as u64) {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = match axum::body::to_bytes(body, cache.config.max_entry_bytes).await {
+        Ok(body) => body,
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to buffer response for caching");
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                [(header::CACHE_CONTROL, "no-store")],
+                Json(serde_json::json!({
+                    "error": "Internal server error",
+                    "code": ErrorCode::ApiInternal,
+                })),
+            )
+                .into_response();
+        }
+    };
+
+    cache
+        .put(
+            key,
+            CachedResponse {
+                headers: parts.headers.clone(),
+                body: body.clone(),
+                stored_at: SystemTime::now(),
+                ttl,
+            },
+        )
+        .await;
+
+    let mut response = Response::from_parts(parts, Body::from(body));
+    response
+        .headers_mut()
+        .insert(X_CACHE, HeaderValue::from_static("MISS"));
+    response
+}
+
+/// TTL for a `200` response marked `public, max-age=N`, if it may be cached.
+pub(crate) fn cacheable_ttl(response: &Response) -> Option<Duration> {
+    if response.status() != StatusCode::OK {
+        return None;
+    }
+
+    let cache_control = response
+        .headers()
+        .get(header::CACHE_CONTROL)?
+        .to_str()
+        .ok()?;
+    let mut public = false;
+    let mut max_age = None;
+    for directive in cache_control.split(',').map(str::trim) {
+        match directive.to_ascii_lowercase().as_str() {
+            "public" => public = true,
+            "private" | "no-store" | "no-cache" => return None,
+            other => {
+                if let Some(secs) = other.strip_prefix("max-age=") {
+                    max_age = secs.parse::<u64>().ok();
+                }
+            }
+        }
+    }
+
+    match (public, max_age) {
+        (true, Some(secs)) if secs > 0 => Some(Duration::from_secs(secs)),
+        _ => None,
+    }
+}
+
+fn is_upstream_failure(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::REQUEST_TIMEOUT
+}
+
+fn env_parse<T>(name: &str) -> Option<T>
+where
+    T: std::str::FromStr,
+{
+    std::env::var(name).ok().and_then(|s| s.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(uri: &str) -> Request {
+        Request::builder().uri(uri).body(Body::empty()).unwrap()
+    }
+
+    pub(super) fn entry(ttl: Duration, stored_at: SystemTime) -> CachedResponse {
+        CachedResponse {
+            headers: HeaderMap::new(),
+            body: Bytes::from_static(b"{}"),
+            stored_at,
+            ttl,
+        }
+    }
+
+    pub(super) fn key(path: &str) -> CacheKey {
+        CacheKey::from_request(&request(path))
+    }
+
+    fn response_with_cache_control(value: &'static str) -> Response {
+        ([(header::CACHE_CONTROL, value)], "body").into_response()
+    }
+
+    #[test]
+    fn key_ignores_query_order() {
+        assert_eq!(
+            key("/api/videos?sort=trending&limit=10"),
+            key("/api/videos?limit=10&sort=trending&")
+        );
+        assert_ne!(key("/api/videos?limit=10"), key("/api/videos?limit=20"));
+    }
+
+    #[test]
+    fn key_includes_format() {
+        let mut csv = request("/api/videos");
+        csv.headers_mut()
+            .insert(header::ACCEPT, HeaderValue::from_static("text/csv"));
+        assert_ne!(CacheKey::from_request(&csv), key("/api/videos"));
+        assert_eq!(key("/api/videos?format=csv").format, ResponseFormat::Csv);
+    }
+
+    #[test]
+    fn key_display_starts_with_path() {
+        assert_eq!(
+            key("/api/videos?sort=recent").to_string(),
+            "/api/videos?sort=recent#json"
+        );
+    }
+
+    #[test]
+    fn ttl_comes_from_public_max_age() {
+        assert_eq!(
+            cacheable_ttl(&response_with_cache_control("public, max-age=60")),
+            Some(Duration::from_secs(60))
+        );
+        assert_eq!(
+            cacheable_ttl(&response_with_cache_control("no-store")),
+            None
+        );
+        assert_eq!(
+            cacheable_ttl(&response_with_cache_control("private, max-age=60")),
+            None
+        );
+        assert_eq!(
+            cacheable_ttl(&response_with_cache_control("public, max-age=0")),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn lookup_serves_stale_and_refreshes_once() {
+        let cache = ResponseCache::new(CacheConfig::default());
+        let now = SystemTime::now();
+        let stored_at = now - Duration::from_secs(90);
+        cache
+            .put(
+                &key("/api/stats"),
+                entry(Duration::from_secs(60), stored_at),
+            )
+            .await;
+
+        assert!(matches!(
+            cache.lookup(&key("/api/stats"), now).await,
+            Lookup::Stale { refresh: true, .. }
+        ));
+        assert!(matches!(
+            cache.lookup(&key("/api/stats"), now).await,
+            Lookup::Stale { refresh: false, .. }
+        ));
+
+        cache.finish_refresh(&key("/api/stats")).await;
+        assert!(matches!(
+            cache.lookup(&key("/api/stats"), now).await,
+            Lookup::Stale { refresh: true, .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn lookup_misses_past_stale_window() {
+        let cache = ResponseCache::new(CacheConfig::default());
+        let now = SystemTime::now();
+        let stored_at = now - Duration::from_secs(200);
+        cache
+            .put(
+                &key("/api/stats"),
+                entry(Duration::from_secs(60), stored_at),
+            )
+            .await;
+
+        assert!(matches!(
+            cache.lookup(&key("/api/stats"), now).await,
+            Lookup::Miss
+        ));
+        assert!(
+            cache
+                .stale_if_error(&key("/api/stats"), now)
+                .await
+                .is_some()
+        );
+    }
+}
@@ -0,0 +1,138 @@
+//! In-process cache backend.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, SystemTime};
+
+use funnel_observability::api;
+use metrics::gauge;
+
+use super::{CacheKey, CachedResponse};
+
+#[derive(Debug, Default)]
+struct State {
+    entries: HashMap<CacheKey, CachedResponse>,
+    /// Keys with a background refresh in flight.
+    refreshing: HashSet<CacheKey>,
+}
+
+/// Bounded map of cached responses.
+#[derive(Debug)]
+pub struct MemoryStore {
+    max_entries: usize,
+    /// How long entries are kept past their TTL before eviction.
+    retention: Duration,
+    state: Mutex<State>,
+}
+
+impl MemoryStore {
+    pub fn new(max_entries: usize, retention: Duration) -> Self {
+        Self {
+            max_entries,
+            retention,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    pub fn get(&self, key: &CacheKey) -> Option<CachedResponse> {
+        self.lock().entries.get(key).cloned()
+    }
+
+    pub fn put(&self, key: &CacheKey, entry: CachedResponse) {
+        let mut state = self.lock();
+        if state.entries.len() >= self.max_entries && !state.entries.contains_key(key) {
+            self.evict(&mut state, SystemTime::now());
+        }
+        state.entries.insert(key.clone(), entry);
+        gauge!(api::CACHE_ENTRIES).set(state.entries.len() as f64);
+    }
+
+    pub fn begin_refresh(&self, key: &CacheKey) -> bool {
+        self.lock().refreshing.insert(key.clone())
+    }
+
+    pub fn finish_refresh(&self, key: &CacheKey) {
+        self.lock().refreshing.remove(key);
+    }
+
+    pub fn purge(&self, prefix: Option<&str>) -> usize {
+        let mut state = self.lock();
+        let before = state.entries.len();
+        match prefix {
+            Some(prefix) => state.entries.retain(|key, _| !key.path.starts_with(prefix)),
+            None => state.entries.clear(),
+        }
+        gauge!(api::CACHE_ENTRIES).set(state.entries.len() as f64);
+        before - state.entries.len()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Make room for one entry: drop everything past its retention, then the oldest
+    /// entry if that wasn't enough.
+    fn evict(&self, state: &mut State, now: SystemTime) {
+        state
+            .entries
+            .retain(|_, entry| entry.is_usable_within(now, self.retention));
+
+        if state.entries.len() >= self.max_entries {
+            let oldest = state
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.stored_at)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                state.entries.remove(&oldest);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{entry, key};
+    use super::*;
+
+    #[test]
+    fn put_evicts_oldest_when_full() {
+        let store = MemoryStore::new(2, Duration::from_secs(300));
+        let now = SystemTime::now();
+        let ttl = Duration::from_secs(60);
+        store.put(&key("/a"), entry(ttl, now - Duration::from_secs(2)));
+        store.put(&key("/b"), entry(ttl, now - Duration::from_secs(1)));
+        store.put(&key("/c"), entry(ttl, now));
+
+        assert!(store.get(&key("/a")).is_none());
+        assert!(store.get(&key("/b")).is_some());
+        assert!(store.get(&key("/c")).is_some());
+    }
+
+    #[test]
+    fn put_evicts_expired_entries_first() {
+        let store = MemoryStore::new(2, Duration::from_secs(60));
+        let now = SystemTime::now();
+        let ttl = Duration::from_secs(60);
+        store.put(&key("/expired"), entry(ttl, now - Duration::from_secs(600)));
+        store.put(&key("/a"), entry(ttl, now - Duration::from_secs(1)));
+        store.put(&key("/b"), entry(ttl, now));
+
+        assert!(store.get(&key("/expired")).is_none());
+        assert!(store.get(&key("/a")).is_some());
+    }
+
+    #[test]
+    fn purge_by_prefix() {
+        let store = MemoryStore::new(10, Duration::from_secs(300));
+        let now = SystemTime::now();
+        let ttl = Duration::from_secs(60);
+        store.put(&key("/api/videos"), entry(ttl, now));
+        store.put(&key("/api/videos?sort=trending"), entry(ttl, now));
+        store.put(&key("/api/stats"), entry(ttl, now));
+
+        assert_eq!(store.purge(Some("/api/videos")), 2);
+        assert_eq!(store.purge(None), 1);
+        assert!(store.get(&key("/api/stats")).is_none());
+    }
+}
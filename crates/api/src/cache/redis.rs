@@ -0,0 +1,316 @@
+//! Redis cache backend, shared by all API replicas.
+//!
+//! Each entry is a hash under `funnel:cache:<key>` holding the response headers,
+//! body, store time, and TTL, and expires once it is past its stale windows.
+//! Refresh claims are separate `SET NX` keys so only one replica revalidates a
+//! stale entry at a time. A tenant's keys carry its ID
+//! (`funnel:cache:<tenant>:<key>`), so tenants sharing a Redis instance never see or
+//! purge each other's entries.
+
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use axum::{
+    body::Bytes,
+    http::{HeaderMap, HeaderName, HeaderValue},
+};
+use redis::{Client, RedisResult, aio::ConnectionManager};
+use tokio::sync::OnceCell;
+
+use super::{CacheKey, CachedResponse};
+
+/// Namespace for cached responses.
+const ENTRY_PREFIX: &str = "funnel:cache:";
+
+/// Namespace for refresh claims, kept apart so purges never match them.
+const REFRESH_PREFIX: &str = "funnel:cache-refresh:";
+
+/// How long a refresh claim is held if the replica holding it never releases it.
+const REFRESH_CLAIM_TTL: Duration = Duration::from_secs(30);
+
+/// Keys fetched per `SCAN` call when purging.
+const SCAN_COUNT: usize = 500;
+
+/// Cache entries stored in Redis.
+pub struct RedisStore {
+    client: Client,
+    /// Start of every entry key, including the tenant if there is one.
+    entries: String,
+    /// Start of every refresh claim key, including the tenant if there is one.
+    refreshes: String,
+    /// Connected on first use, so startup doesn't depend on Redis being up.
+    connection: OnceCell<ConnectionManager>,
+    /// How long entries are kept past their TTL.
+    retention: Duration,
+}
+
+impl RedisStore {
+    pub fn new(url: &str, retention: Duration, tenant: Option<&str>) -> RedisResult<Self> {
+        let tenant = tenant
+            .map(|tenant| format!("{tenant}:"))
+            .unwrap_or_default();
+        Ok(Self {
+            client: Client::open(url)?,
+            entries: format!("{ENTRY_PREFIX}{tenant}"),
+            refreshes: format!("{REFRESH_PREFIX}{tenant}"),
+            connection: OnceCell::new(),
+            retention,
+        })
+    }
+
+    pub async fn get(&self, key: &CacheKey) -> RedisResult<Option<CachedResponse>> {
+        let mut conn = self.connection().await?;
+        let fields: HashMap<String, Vec<u8>> = redis::cmd("HGETALL")
+            .arg(self.entry_key(key))
+            .query_async(&mut conn)
+            .await?;
+        Ok(decode_entry(fields))
+    }
+
+    pub async fn put(&self, key: &CacheKey, entry: &CachedResponse) -> RedisResult<()> {
+        let mut conn = self.connection().await?;
+        let entry_key = self.entry_key(key);
+        let expire_ms = (entry.ttl + self.retention).as_millis() as u64;
+
+        let _: () = redis::pipe()
+            .atomic()
+            .cmd("DEL")
+            .arg(&entry_key)
+            .ignore()
+            .cmd("HSET")
+            .arg(&entry_key)
+            .arg("headers")
+            .arg(encode_headers(&entry.headers))
+            .arg("body")
+            .arg(entry.body.as_ref())
+            .arg("stored_at_ms")
+            .arg(unix_millis(entry.stored_at))
+            .arg("ttl_ms")
+            .arg(entry.ttl.as_millis() as u64)
+            .ignore()
+            .cmd("PEXPIRE")
+            .arg(&entry_key)
+            .arg(expire_ms)
+            .ignore()
+            .query_async(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn begin_refresh(&self, key: &CacheKey) -> RedisResult<bool> {
+        let mut conn = self.connection().await?;
+        let claimed: Option<String> = redis::cmd("SET")
+            .arg(self.refresh_key(key))
+            .arg(1)
+            .arg("NX")
+            .arg("PX")
+            .arg(REFRESH_CLAIM_TTL.as_millis() as u64)
+            .query_async(&mut conn)
+            .await?;
+        Ok(claimed.is_some())
+    }
+
+    pub async fn finish_refresh(&self, key: &CacheKey) -> RedisResult<()> {
+        let mut conn = self.connection().await?;
+        let _: () = redis::cmd("DEL")
+            .arg(self.refresh_key(key))
+            .query_async(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn purge(&self, prefix: Option<&str>) -> RedisResult<usize> {
+        let Some(pattern) = self.purge_pattern(prefix) else {
+            return Ok(0);
+        };
+        let mut conn = self.connection().await?;
+
+        let mut purged = 0;
+        let mut cursor = 0u64;
+        loop {
+            let (next, keys): (u64, Vec<String>) = redis::cmd("SCAN")
+                .arg(cursor)
+                .arg("MATCH")
+                .arg(&pattern)
+                .arg("COUNT")
+                .arg(SCAN_COUNT)
+                .query_async(&mut conn)
+                .await?;
+            if !keys.is_empty() {
+                let deleted: usize = redis::cmd("DEL").arg(&keys).query_async(&mut conn).await?;
+                purged += deleted;
+            }
+            if next == 0 {
+                return Ok(purged);
+            }
+            cursor = next;
+        }
+    }
+
+    fn entry_key(&self, key: &CacheKey) -> String {
+        format!("{}{key}", self.entries)
+    }
+
+    fn refresh_key(&self, key: &CacheKey) -> String {
+        format!("{}{key}", self.refreshes)
+    }
+
+    /// `SCAN` pattern for this store's entries whose path starts with `prefix`, or
+    /// `None` if no path can. Keys start with the path, which always starts with
+    /// `/`, so the pattern without a tenant never matches a tenant's keys.
+    fn purge_pattern(&self, prefix: Option<&str>) -> Option<String> {
+        let path = match prefix.unwrap_or_default() {
+            "" => "/",
+            prefix if prefix.starts_with('/') => prefix,
+            _ => return None,
+        };
+        Some(format!(
+            "{}{}*",
+            escape_glob(&self.entries),
+            escape_glob(path)
+        ))
+    }
+
+    async fn connection(&self) -> RedisResult<ConnectionManager> {
+        self.connection
+            .get_or_try_init(|| self.client.get_connection_manager())
+            .await
+            .cloned()
+    }
+}
+
+/// Escape Redis glob metacharacters so a path prefix matches literally.
+fn escape_glob(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn encode_headers(headers: &HeaderMap) -> String {
+    let pairs: Vec<(&str, &str)> = headers
+        .iter()
+        .filter_map(|(name, value)| Some((name.as_str(), value.to_str().ok()?)))
+        .collect();
+    serde_json::to_string(&pairs).unwrap_or_default()
+}
+
+fn decode_headers(bytes: &[u8]) -> Option<HeaderMap> {
+    let pairs: Vec<(String, String)> = serde_json::from_slice(bytes).ok()?;
+    let mut headers = HeaderMap::with_capacity(pairs.len());
+    for (name, value) in pairs {
+        headers.append(
+            HeaderName::from_bytes(name.as_bytes()).ok()?,
+            HeaderValue::from_str(&value).ok()?,
+        );
+    }
+    Some(headers)
+}
+
+/// Rebuild an entry from `HGETALL` output; missing or malformed entries are misses.
+fn decode_entry(mut fields: HashMap<String, Vec<u8>>) -> Option<CachedResponse> {
+    let number = |bytes: Vec<u8>| String::from_utf8(bytes).ok()?.parse::<u64>().ok();
+
+    let headers = decode_headers(&fields.remove("headers")?)?;
+    let body = Bytes::from(fields.remove("body")?);
+    let stored_at_ms = number(fields.remove("stored_at_ms")?)?;
+    let ttl_ms = number(fields.remove("ttl_ms")?)?;
+
+    Some(CachedResponse {
+        headers,
+        body,
+        stored_at: UNIX_EPOCH + Duration::from_millis(stored_at_ms),
+        ttl: Duration::from_millis(ttl_ms),
+    })
+}
+
+fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::header;
+
+    use super::super::tests::key;
+    use super::*;
+
+    #[test]
+    fn escape_glob_escapes_metacharacters() {
+        assert_eq!(escape_glob("/api/videos"), "/api/videos");
+        assert_eq!(escape_glob("/a*b?[c]\\"), "/a\\*b\\?\\[c\\]\\\\");
+    }
+
+    fn store(tenant: Option<&str>) -> RedisStore {
+        RedisStore::new("redis://localhost:6379", Duration::from_secs(60), tenant).unwrap()
+    }
+
+    #[test]
+    fn entry_key_is_namespaced() {
+        assert_eq!(
+            store(None).entry_key(&key("/api/stats")),
+            "funnel:cache:/api/stats?#json"
+        );
+        assert_eq!(
+            store(Some("acme")).entry_key(&key("/api/stats")),
+            "funnel:cache:acme:/api/stats?#json"
+        );
+        assert_eq!(
+            store(Some("acme")).refresh_key(&key("/api/stats")),
+            "funnel:cache-refresh:acme:/api/stats?#json"
+        );
+    }
+
+    #[test]
+    fn purge_pattern_stays_within_the_tenant() {
+        assert_eq!(store(None).purge_pattern(None).unwrap(), "funnel:cache:/*");
+        assert_eq!(
+            store(Some("acme"))
+                .purge_pattern(Some("/api/videos"))
+                .unwrap(),
+            "funnel:cache:acme:/api/videos*"
+        );
+        assert_eq!(
+            store(Some("a*")).purge_pattern(None).unwrap(),
+            "funnel:cache:a\\*:/*"
+        );
+        assert!(store(None).purge_pattern(Some("acme:")).is_none());
+    }
+
+    #[test]
+    fn entry_round_trips() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::CACHE_CONTROL,
+            HeaderValue::from_static("public, max-age=60"),
+        );
+        let stored_at = UNIX_EPOCH + Duration::from_millis(1_700_000_000_123);
+
+        let fields = HashMap::from([
+            ("headers".to_string(), encode_headers(&headers).into_bytes()),
+            ("body".to_string(), b"{\"ok\":true}".to_vec()),
+            (
+                "stored_at_ms".to_string(),
+                unix_millis(stored_at).to_string().into_bytes(),
+            ),
+            ("ttl_ms".to_string(), b"60000".to_vec()),
+        ]);
+        let entry = decode_entry(fields).unwrap();
+
+        assert_eq!(entry.headers, headers);
+        assert_eq!(entry.body, Bytes::from_static(b"{\"ok\":true}"));
+        assert_eq!(entry.stored_at, stored_at);
+        assert_eq!(entry.ttl, Duration::from_secs(60));
+    }
+
+    #[test]
+    fn decode_entry_rejects_missing_fields() {
+        assert!(decode_entry(HashMap::new()).is_none());
+    }
+}
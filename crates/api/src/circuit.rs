@@ -0,0 +1,301 @@
+//! Circuit breaker for storage-backed routes.
+//!
+//! Handlers turn ClickHouse errors into 500, 502, 503, or 504 responses and slow
+//! queries hit the request timeout (408), so the breaker counts consecutive responses
+//! of those statuses. Other 5xx, such as the 501 oEmbed answers for unsupported
+//! formats, say nothing about storage and don't count. Each route group has its own
+//! breaker. Once `failure_threshold` is reached it opens and
+//! rejects requests with `503` and `Retry-After` for `open_duration`, then lets
+//! requests through again (half-open): the first success closes it, the first
+//! failure re-opens it.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use axum::{
+    Json,
+    extract::{Request, State},
+    http::{HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use funnel_observability::api;
+use funnel_proto::ErrorCode;
+use metrics::{counter, gauge};
+
+/// Default consecutive failures before the breaker opens.
+pub const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+
+/// Default time the breaker stays open before allowing a trial request.
+pub const DEFAULT_OPEN_DURATION: Duration = Duration::from_secs(30);
+
+/// Circuit breaker settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CircuitBreakerConfig {
+    /// Consecutive failures before opening. `0` disables the breaker.
+    pub failure_threshold: u32,
+    /// How long to reject requests once open.
+    pub open_duration: Duration,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            open_duration: DEFAULT_OPEN_DURATION,
+        }
+    }
+}
+
+impl CircuitBreakerConfig {
+    /// Load from `CIRCUIT_BREAKER_THRESHOLD` and `CIRCUIT_BREAKER_OPEN_SECS`.
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            failure_threshold: std::env::var("CIRCUIT_BREAKER_THRESHOLD")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(defaults.failure_threshold),
+            open_duration: std::env::var("CIRCUIT_BREAKER_OPEN_SECS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.open_duration),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BreakerState {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    HalfOpen,
+}
+
+/// Shared circuit breaker state.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    /// Route group the breaker guards, for metrics and logs.
+    group: &'static str,
+    config: CircuitBreakerConfig,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    pub fn new(group: &'static str, config: CircuitBreakerConfig) -> Self {
+        Self {
+            group,
+            config,
+            state: Mutex::new(BreakerState::Closed { failures: 0 }),
+        }
+    }
+
+    /// Check whether a request may proceed.
+    ///
+    /// Returns the remaining open time when the breaker is rejecting requests.
+    pub fn check(&self) -> Result<(), Duration> {
+        if self.config.failure_threshold == 0 {
+            return Ok(());
+        }
+
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        match *state {
+            BreakerState::Open { until } => {
+                let now = Instant::now();
+                if now < until {
+                    return Err(until - now);
+                }
+                tracing::info!(
+                    group = self.group,
+                    "Circuit breaker half-open, allowing trial requests"
+                );
+                *state = BreakerState::HalfOpen;
+                Ok(())
+            }
+            BreakerState::Closed { .. } | BreakerState::HalfOpen => Ok(()),
+        }
+    }
+
+    /// Record a successful storage-backed request.
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        if *state == BreakerState::HalfOpen {
+            tracing::info!(group = self.group, "Circuit breaker closed");
+            gauge!(api::CIRCUIT_BREAKER_OPEN, "group" => self.group).set(0.0);
+        }
+        *state = BreakerState::Closed { failures: 0 };
+    }
+
+    /// Record a failed storage-backed request.
+    pub fn record_failure(&self) {
+        if self.config.failure_threshold == 0 {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let failures = match *state {
+            BreakerState::Closed { failures } => failures + 1,
+            BreakerState::HalfOpen => self.config.failure_threshold,
+            // Requests admitted before the breaker opened; keep the current window
+            BreakerState::Open { .. } => return,
+        };
+
+        if failures >= self.config.failure_threshold {
+            tracing::warn!(
+                group = self.group,
+                failures,
+                open_secs = self.config.open_duration.as_secs(),
+                "Circuit breaker opened"
+            );
+            gauge!(api::CIRCUIT_BREAKER_OPEN, "group" => self.group).set(1.0);
+            *state = BreakerState::Open {
+                until: Instant::now() + self.config.open_duration,
+            };
+        } else {
+            *state = BreakerState::Closed { failures };
+        }
+    }
+}
+
+/// Middleware that short-circuits requests while the breaker is open.
+pub async fn circuit_breaker(
+    State(breaker): State<Arc<CircuitBreaker>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if let Err(retry_after) = breaker.check() {
+        counter!(api::CIRCUIT_BREAKER_REJECTIONS, "group" => breaker.group).increment(1);
+        return unavailable_response(retry_after);
+    }
+
+    let response = next.run(request).await;
+    if is_storage_failure(response.status()) {
+        breaker.record_failure();
+    } else {
+        breaker.record_success();
+    }
+    response
+}
+
+/// Whether a response status means the storage behind it failed or timed out.
+fn is_storage_failure(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::INTERNAL_SERVER_ERROR
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+            | StatusCode::REQUEST_TIMEOUT
+    )
+}
+
+fn unavailable_response(retry_after: Duration) -> Response {
+    // Round up so clients never retry before the breaker half-opens
+    let secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+
+    let mut response = (
+        StatusCode::SERVICE_UNAVAILABLE,
+        [(header::CACHE_CONTROL, "no-store")],
+        Json(serde_json::json!({
+            "error": "Service temporarily unavailable",
+            "code": ErrorCode::ApiUnavailable,
+        })),
+    )
+        .into_response();
+    response
+        .headers_mut()
+        .insert(header::RETRY_AFTER, HeaderValue::from(secs.max(1)));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker(threshold: u32, open_duration: Duration) -> CircuitBreaker {
+        CircuitBreaker::new(
+            "api",
+            CircuitBreakerConfig {
+                failure_threshold: threshold,
+                open_duration,
+            },
+        )
+    }
+
+    #[test]
+    fn opens_after_threshold_failures() {
+        let breaker = breaker(3, Duration::from_secs(30));
+
+        breaker.record_failure();
+        breaker.record_failure();
+        assert!(breaker.check().is_ok());
+
+        breaker.record_failure();
+        assert!(breaker.check().is_err());
+    }
+
+    #[test]
+    fn success_resets_failure_count() {
+        let breaker = breaker(2, Duration::from_secs(30));
+
+        breaker.record_failure();
+        breaker.record_success();
+        breaker.record_failure();
+        assert!(breaker.check().is_ok());
+    }
+
+    #[test]
+    fn half_open_after_duration_and_closes_on_success() {
+        let breaker = breaker(1, Duration::ZERO);
+
+        breaker.record_failure();
+        assert!(breaker.check().is_ok());
+        breaker.record_success();
+        assert_eq!(
+            *breaker.state.lock().unwrap(),
+            BreakerState::Closed { failures: 0 }
+        );
+    }
+
+    #[test]
+    fn half_open_failure_reopens() {
+        let breaker = breaker(3, Duration::ZERO);
+
+        for _ in 0..3 {
+            breaker.record_failure();
+        }
+        assert!(breaker.check().is_ok());
+        breaker.record_failure();
+        assert!(matches!(
+            *breaker.state.lock().unwrap(),
+            BreakerState::Open { .. }
+        ));
+    }
+
+    #[test]
+    fn zero_threshold_disables_breaker() {
+        let breaker = breaker(0, Duration::from_secs(30));
+
+        for _ in 0..10 {
+            breaker.record_failure();
+        }
+        assert!(breaker.check().is_ok());
+    }
+
+    #[test]
+    fn only_storage_statuses_are_failures() {
+        for status in [500, 502, 503, 504, 408] {
+            assert!(is_storage_failure(StatusCode::from_u16(status).unwrap()));
+        }
+        for status in [200, 304, 400, 404, 429, 501, 505] {
+            assert!(!is_storage_failure(StatusCode::from_u16(status).unwrap()));
+        }
+    }
+
+    #[test]
+    fn retry_after_rounds_up() {
+        let response = unavailable_response(Duration::from_millis(1500));
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "2");
+    }
+}
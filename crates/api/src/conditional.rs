@@ -0,0 +1,120 @@
+//! Entity tags and conditional requests.
+//!
+//! Cacheable responses (`200` marked `public, max-age=N`) get a strong `ETag` derived
+//! from the body, and a matching `If-None-Match` turns them into `304 Not Modified`.
+//! This runs outside the response cache, so cache hits are validated too.
+//!
+//! `HEAD` requests run the `GET` handler; axum drops the body only after all
+//! middleware, so `HEAD` responses carry the same `ETag` and `Content-Length` as the
+//! matching `GET`.
+
+use axum::{
+    Json,
+    body::{Body, Bytes, HttpBody},
+    extract::Request,
+    http::{HeaderValue, Method, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use funnel_proto::ErrorCode;
+use sha2::{Digest, Sha256};
+
+use crate::cache::cacheable_ttl;
+
+/// Largest body hashed for an `ETag`; bigger responses go out without one.
+pub const MAX_ETAG_BODY_BYTES: usize = 4 * 1024 * 1024;
+
+/// Middleware adding `ETag`s to cacheable responses and answering `If-None-Match`.
+pub async fn conditional_get(request: Request, next: Next) -> Response {
+    if !matches!(*request.method(), Method::GET | Method::HEAD) {
+        return next.run(request).await;
+    }
+
+    let if_none_match = request.headers().get(header::IF_NONE_MATCH).cloned();
+    let response = next.run(request).await;
+
+    if cacheable_ttl(&response).is_none() {
+        return response;
+    }
+    let size = response.body().size_hint().exact();
+    if size.is_none_or(|size| size > MAX_ETAG_BODY_BYTES as u64) {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let body = match axum::body::to_bytes(body, MAX_ETAG_BODY_BYTES).await {
+        Ok(body) => body,
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to buffer response for ETag");
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                [(header::CACHE_CONTROL, "no-store")],
+                Json(serde_json::json!({
+                    "error": "Internal server error",
+                    "code": ErrorCode::ApiInternal,
+                })),
+            )
+                .into_response();
+        }
+    };
+
+    let etag = entity_tag(&body);
+    parts.headers.insert(header::ETAG, etag.clone());
+
+    let not_modified = if_none_match
+        .as_ref()
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| etag_matches(value, &etag));
+    if not_modified {
+        parts.status = StatusCode::NOT_MODIFIED;
+        parts.headers.remove(header::CONTENT_TYPE);
+        parts.headers.remove(header::CONTENT_LENGTH);
+        return Response::from_parts(parts, Body::empty());
+    }
+
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// Strong entity tag for a response body: the first 128 bits of its SHA-256.
+fn entity_tag(body: &Bytes) -> HeaderValue {
+    let digest = Sha256::digest(body);
+    let hex: String = digest[..16].iter().map(|b| format!("{b:02x}")).collect();
+    HeaderValue::from_str(&format!("\"{hex}\"")).expect("hex is a valid header value")
+}
+
+/// Whether an `If-None-Match` value matches `etag`, using weak comparison as RFC 9110
+/// requires for `If-None-Match`.
+fn etag_matches(if_none_match: &str, etag: &HeaderValue) -> bool {
+    let etag = etag.to_str().unwrap_or_default();
+    if_none_match
+        .split(',')
+        .map(str::trim)
+        .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entity_tag_is_quoted_and_stable() {
+        let a = entity_tag(&Bytes::from_static(b"{\"ok\":true}"));
+        let b = entity_tag(&Bytes::from_static(b"{\"ok\":true}"));
+        let c = entity_tag(&Bytes::from_static(b"{\"ok\":false}"));
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        let value = a.to_str().unwrap();
+        assert!(value.starts_with('"') && value.ends_with('"'));
+        assert_eq!(value.len(), 34);
+    }
+
+    #[test]
+    fn etag_matches_lists_weak_tags_and_wildcard() {
+        let etag = HeaderValue::from_static("\"abc\"");
+        assert!(etag_matches("\"abc\"", &etag));
+        assert!(etag_matches("\"xyz\", W/\"abc\"", &etag));
+        assert!(etag_matches("*", &etag));
+        assert!(!etag_matches("\"xyz\"", &etag));
+    }
+}
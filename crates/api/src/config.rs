@@ -0,0 +1,154 @@
+//! Runtime configuration for the API router.
+
+use std::sync::Arc;
+
+use crate::auth::AuthConfig;
+use crate::cache::CacheConfig;
+use crate::circuit::CircuitBreakerConfig;
+use crate::cors::CorsConfig;
+use crate::jwt::{JwtConfig, JwtVerifier};
+use crate::limits::RequestLimits;
+use crate::prometheus::MetricsConfig;
+use crate::usage::UsageConfig;
+
+/// Router-level configuration loaded at startup.
+#[derive(Clone, Default)]
+pub struct ApiConfig {
+    /// Bearer token for `/api/*` routes. `None` leaves them public.
+    pub auth: Option<AuthConfig>,
+    /// Bearer token for `/admin/*` routes. `None` disables the admin API entirely.
+    pub admin_auth: Option<AuthConfig>,
+    /// Circuit breaker around storage-backed routes.
+    pub circuit_breaker: CircuitBreakerConfig,
+    /// Cross-origin request policy.
+    pub cors: CorsConfig,
+    /// Timeout, body size, and concurrency limits for each route group.
+    pub limits: RequestLimits,
+    /// In-memory response cache for GET endpoints.
+    pub cache: CacheConfig,
+    /// Access to the `/metrics` endpoint.
+    pub metrics: MetricsConfig,
+    /// Monthly per-token quotas for `/api/*` routes.
+    pub usage: UsageConfig,
+}
+
+impl ApiConfig {
+    /// Load configuration from the environment.
+    ///
+    /// Reads `API_TOKEN`/`API_TOKEN_FILE`, `ADMIN_TOKEN`/`ADMIN_TOKEN_FILE`, and the
+    /// `JWT_*`, `CIRCUIT_BREAKER_*`, `CORS_*`, `RESPONSE_CACHE_*`, `METRICS_*`,
+    /// `USAGE_*`, and request limit settings.
+    pub fn from_env() -> Self {
+        let mut auth = AuthConfig::from_env();
+        let mut admin_auth = AuthConfig::from_env_var("ADMIN_TOKEN");
+        if let Some(jwt) = JwtConfig::from_env() {
+            let api_scope = jwt.api_scope.clone();
+            let admin_scope = jwt.admin_scope.clone();
+            let verifier = Arc::new(JwtVerifier::new(jwt));
+            auth = Some(with_jwt(auth, verifier.clone(), api_scope));
+            // JWTs only reach the admin API when a scope is set aside for it
+            if let Some(scope) = admin_scope {
+                admin_auth = Some(with_jwt(admin_auth, verifier, Some(scope)));
+            }
+        }
+
+        Self {
+            metrics: MetricsConfig::from_env(auth.as_ref()),
+            auth,
+            admin_auth,
+            circuit_breaker: CircuitBreakerConfig::from_env(),
+            cors: CorsConfig::from_env(),
+            limits: RequestLimits::from_env(),
+            cache: CacheConfig::from_env(),
+            usage: UsageConfig::from_env(),
+        }
+    }
+
+    /// Set the `/api/*` auth config.
+    pub fn with_auth(mut self, auth: AuthConfig) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+
+    /// Set the `/admin/*` auth config.
+    pub fn with_admin_auth(mut self, admin_auth: AuthConfig) -> Self {
+        self.admin_auth = Some(admin_auth);
+        self
+    }
+
+    /// Set the circuit breaker configuration.
+    pub fn with_circuit_breaker(mut self, circuit_breaker: CircuitBreakerConfig) -> Self {
+        self.circuit_breaker = circuit_breaker;
+        self
+    }
+
+    /// Set the CORS policy.
+    pub fn with_cors(mut self, cors: CorsConfig) -> Self {
+        self.cors = cors;
+        self
+    }
+
+    /// Set the per-route-group request limits.
+    pub fn with_limits(mut self, limits: RequestLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Set the response cache configuration.
+    pub fn with_cache(mut self, cache: CacheConfig) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Set the `/metrics` access configuration.
+    pub fn with_metrics(mut self, metrics: MetricsConfig) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Set the per-token usage quotas.
+    pub fn with_usage(mut self, usage: UsageConfig) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    /// This config for one tenant's routes, accepting only that tenant's tokens and
+    /// caching under its own keys.
+    pub fn for_tenant(&self, tenant: &str) -> Self {
+        Self {
+            auth: self.auth.as_ref().map(|auth| auth.for_tenant(tenant)),
+            admin_auth: self.admin_auth.as_ref().map(|auth| auth.for_tenant(tenant)),
+            cache: CacheConfig {
+                tenant: Some(tenant.to_string()),
+                ..self.cache.clone()
+            },
+            ..self.clone()
+        }
+    }
+
+    /// Re-read any file-backed tokens.
+    ///
+    /// Returns the number of tokens that changed.
+    pub fn reload(&self) -> std::io::Result<usize> {
+        let mut changed = 0;
+        let tokens = [&self.auth, &self.admin_auth, &self.metrics.auth];
+        for auth in tokens.into_iter().flatten() {
+            if auth.reload()? {
+                changed += 1;
+            }
+        }
+        Ok(changed)
+    }
+}
+
+/// Add JWT acceptance to `auth`, or accept only JWTs when there are no static tokens.
+fn with_jwt(
+    auth: Option<AuthConfig>,
+    verifier: Arc<JwtVerifier>,
+    scope: Option<String>,
+) -> AuthConfig {
+    match auth {
+        Some(auth) => auth.with_jwt(verifier, scope),
+        None => AuthConfig::jwt_only(verifier, scope),
+    }
+}
@@ -0,0 +1,195 @@
+//! CORS policy configuration.
+//!
+//! The default policy lets any origin make read-only requests with a bearer token,
+//! which suits a public read API. The layer wraps every route, so preflight
+//! (`OPTIONS`) requests are answered from this policy before auth or routing.
+//! Deployments serving a known set of frontends should list them in
+//! `CORS_ALLOWED_ORIGINS`.
+
+use std::time::Duration;
+
+use axum::http::{HeaderName, HeaderValue, Method, header};
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+
+use crate::cache::X_CACHE;
+
+/// How long browsers may cache preflight responses.
+const PREFLIGHT_MAX_AGE: Duration = Duration::from_secs(600);
+
+/// Allowed origins for cross-origin requests.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub enum CorsOrigins {
+    /// Any origin (`Access-Control-Allow-Origin: *`).
+    #[default]
+    Any,
+    /// Only these exact origins, e.g. `https://app.example.com`.
+    List(Vec<String>),
+}
+
+/// CORS policy settings.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CorsConfig {
+    pub allowed_origins: CorsOrigins,
+    pub allowed_methods: Vec<Method>,
+    pub allowed_headers: Vec<HeaderName>,
+    /// Allow cookies/credentials. Requires an explicit origin list.
+    pub allow_credentials: bool,
+    /// Mirror any request back as allowed (development only).
+    pub permissive: bool,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: CorsOrigins::Any,
+            allowed_methods: vec![Method::GET, Method::HEAD, Method::OPTIONS],
+            allowed_headers: vec![
+                header::AUTHORIZATION,
+                header::CONTENT_TYPE,
+                header::ACCEPT,
+                header::IF_NONE_MATCH,
+            ],
+            allow_credentials: false,
+            permissive: false,
+        }
+    }
+}
+
+impl CorsConfig {
+    /// Load from `CORS_ALLOWED_ORIGINS`, `CORS_ALLOWED_METHODS`, `CORS_ALLOWED_HEADERS`,
+    /// `CORS_ALLOW_CREDENTIALS`, and `CORS_PERMISSIVE`.
+    ///
+    /// List values are comma-separated; unset variables keep the defaults.
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+
+        let allowed_origins = match std::env::var("CORS_ALLOWED_ORIGINS") {
+            Ok(value) => parse_origins(&value),
+            Err(_) => defaults.allowed_origins,
+        };
+        let allowed_methods = match std::env::var("CORS_ALLOWED_METHODS") {
+            Ok(value) => parse_list(&value, |s| {
+                Method::from_bytes(s.to_ascii_uppercase().as_bytes()).ok()
+            }),
+            Err(_) => defaults.allowed_methods,
+        };
+        let allowed_headers = match std::env::var("CORS_ALLOWED_HEADERS") {
+            Ok(value) => parse_list(&value, |s| HeaderName::from_bytes(s.as_bytes()).ok()),
+            Err(_) => defaults.allowed_headers,
+        };
+
+        let mut config = Self {
+            allowed_origins,
+            allowed_methods,
+            allowed_headers,
+            allow_credentials: env_flag("CORS_ALLOW_CREDENTIALS"),
+            permissive: env_flag("CORS_PERMISSIVE"),
+        };
+
+        if config.allow_credentials && config.allowed_origins == CorsOrigins::Any {
+            tracing::warn!(
+                "CORS_ALLOW_CREDENTIALS requires CORS_ALLOWED_ORIGINS to list origins; ignoring"
+            );
+            config.allow_credentials = false;
+        }
+
+        config
+    }
+
+    /// Build the tower-http layer for this policy.
+    pub fn layer(&self) -> CorsLayer {
+        if self.permissive {
+            return CorsLayer::permissive();
+        }
+
+        let origin = match &self.allowed_origins {
+            CorsOrigins::Any => AllowOrigin::from(Any),
+            CorsOrigins::List(origins) => AllowOrigin::list(
+                origins
+                    .iter()
+                    .filter_map(|origin| HeaderValue::from_str(origin).ok()),
+            ),
+        };
+
+        CorsLayer::new()
+            .allow_origin(origin)
+            .allow_methods(self.allowed_methods.clone())
+            .allow_headers(self.allowed_headers.clone())
+            // Wildcard origins can't be combined with credentials
+            .allow_credentials(
+                self.allow_credentials && self.allowed_origins != CorsOrigins::Any,
+            )
+            .expose_headers([
+                header::RETRY_AFTER,
+                header::ETAG,
+                header::AGE,
+                HeaderName::from_static(X_CACHE),
+            ])
+            .max_age(PREFLIGHT_MAX_AGE)
+    }
+}
+
+fn parse_origins(value: &str) -> CorsOrigins {
+    if value.split(',').any(|origin| origin.trim() == "*") {
+        return CorsOrigins::Any;
+    }
+
+    CorsOrigins::List(parse_list(value, |origin| {
+        let origin = origin.trim_end_matches('/');
+        match HeaderValue::from_str(origin) {
+            Ok(_) => Some(origin.to_string()),
+            Err(_) => {
+                tracing::warn!(origin, "Ignoring invalid CORS origin");
+                None
+            }
+        }
+    }))
+}
+
+fn parse_list<T, F>(value: &str, parse: F) -> Vec<T>
+where
+    F: Fn(&str) -> Option<T>,
+{
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .filter_map(parse)
+        .collect()
+}
+
+fn env_flag(name: &str) -> bool {
+    std::env::var(name)
+        .map(|v| matches!(v.to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_origins_handles_wildcard() {
+        assert_eq!(parse_origins("*"), CorsOrigins::Any);
+        assert_eq!(parse_origins("https://a.example, *"), CorsOrigins::Any);
+    }
+
+    #[test]
+    fn parse_origins_trims_and_drops_trailing_slash() {
+        assert_eq!(
+            parse_origins(" https://a.example/ ,https://b.example,,"),
+            CorsOrigins::List(vec![
+                "https://a.example".to_string(),
+                "https://b.example".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn parse_list_skips_invalid_entries() {
+        let methods = parse_list("get, post, not a method", |s| {
+            Method::from_bytes(s.to_ascii_uppercase().as_bytes()).ok()
+        });
+        assert_eq!(methods, vec![Method::GET, Method::POST]);
+    }
+}
@@ -0,0 +1,170 @@
+//! Embedded operational dashboard.
+//!
+//! `GET /dashboard` serves a single static HTML page that polls the existing JSON
+//! endpoints (`/admin/checkpoints`, `/readyz`, `/api/stats`, `/api/videos`, and
+//! `/admin/errors`) from the browser. It is mounted with the other admin routes, whose
+//! `401`s carry a Basic challenge so browsers prompt for the admin token.
+//!
+//! Recent errors are kept in memory by [`record_errors`], which watches the embed and
+//! API routes for `5xx` and `408` responses.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use axum::{
+    Extension, Json,
+    extract::{Request, State},
+    http::{HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::{Html, IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use funnel_observability::api;
+use metrics::counter;
+use serde::Serialize;
+
+/// The dashboard page; all data is fetched client-side.
+const DASHBOARD_HTML: &str = include_str!("dashboard.html");
+
+/// Challenge sent with `401` responses so browsers show a login prompt.
+const BASIC_CHALLENGE: &str = r#"Basic realm="Funnel admin", charset="UTF-8""#;
+
+/// Default number of errors kept for the dashboard.
+pub const DEFAULT_RECENT_ERRORS: usize = 100;
+
+/// A failed request seen by [`record_errors`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ErrorEntry {
+    pub at: DateTime<Utc>,
+    pub method: String,
+    pub path: String,
+    pub status: u16,
+}
+
+/// Bounded, newest-first log of failed requests.
+#[derive(Debug)]
+pub struct RecentErrors {
+    capacity: usize,
+    entries: Mutex<VecDeque<ErrorEntry>>,
+}
+
+impl Default for RecentErrors {
+    fn default() -> Self {
+        Self::new(DEFAULT_RECENT_ERRORS)
+    }
+}
+
+impl RecentErrors {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    /// Record an error, dropping the oldest one when full.
+    pub fn push(&self, entry: ErrorEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() == self.capacity {
+            entries.pop_back();
+        }
+        entries.push_front(entry);
+    }
+
+    /// Recorded errors, newest first.
+    pub fn snapshot(&self) -> Vec<ErrorEntry> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.iter().cloned().collect()
+    }
+}
+
+/// Middleware recording server errors and timeouts in [`RecentErrors`].
+///
+/// Only the path is kept, not the query string, so tokens passed as parameters
+/// never reach the dashboard.
+pub async fn record_errors(
+    State(errors): State<Arc<RecentErrors>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+
+    let response = next.run(request).await;
+    let status = response.status();
+    if status.is_server_error() || status == StatusCode::REQUEST_TIMEOUT {
+        errors.push(ErrorEntry {
+            at: Utc::now(),
+            method,
+            path,
+            status: status.as_u16(),
+        });
+    }
+    response
+}
+
+/// Middleware adding a Basic challenge to `401` responses.
+pub async fn basic_challenge(request: Request, next: Next) -> Response {
+    let mut response = next.run(request).await;
+    if response.status() == StatusCode::UNAUTHORIZED {
+        response.headers_mut().insert(
+            header::WWW_AUTHENTICATE,
+            HeaderValue::from_static(BASIC_CHALLENGE),
+        );
+    }
+    response
+}
+
+/// Serve the dashboard page.
+pub async fn dashboard() -> Response {
+    counter!(api::REQUESTS, "endpoint" => "admin_dashboard").increment(1);
+
+    ([(header::CACHE_CONTROL, "no-store")], Html(DASHBOARD_HTML)).into_response()
+}
+
+/// List recent failed requests, newest first.
+pub async fn get_recent_errors(Extension(errors): Extension<Arc<RecentErrors>>) -> Response {
+    counter!(api::REQUESTS, "endpoint" => "admin_errors").increment(1);
+
+    (
+        StatusCode::OK,
+        [(header::CACHE_CONTROL, "no-store")],
+        Json(serde_json::json!({ "errors": errors.snapshot() })),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(path: &str) -> ErrorEntry {
+        ErrorEntry {
+            at: Utc::now(),
+            method: "GET".to_string(),
+            path: path.to_string(),
+            status: 500,
+        }
+    }
+
+    #[test]
+    fn recent_errors_keeps_newest_first() {
+        let errors = RecentErrors::new(2);
+        errors.push(error("/a"));
+        errors.push(error("/b"));
+        errors.push(error("/c"));
+
+        let paths: Vec<_> = errors.snapshot().into_iter().map(|e| e.path).collect();
+        assert_eq!(paths, ["/c", "/b"]);
+    }
+
+    #[test]
+    fn recent_errors_with_zero_capacity_keeps_nothing() {
+        let errors = RecentErrors::new(0);
+        errors.push(error("/a"));
+        assert!(errors.snapshot().is_empty());
+    }
+}
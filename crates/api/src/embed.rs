@@ -0,0 +1,290 @@
+//! oEmbed responses and embeddable player pages.
+//!
+//! `GET /api/oembed?url=...` returns oEmbed JSON for a Funnel video URL and
+//! `GET /api/videos/{id}/embed` serves a small HTML player page carrying
+//! OpenGraph and Twitter card tags for link previews.
+
+use std::fmt::Write;
+
+use funnel_clickhouse::VideoDetails;
+use serde::Serialize;
+
+use crate::feed::{encode_query_value, escape_xml, guess_media_type};
+
+/// Provider name reported in oEmbed responses.
+pub const PROVIDER_NAME: &str = "Funnel";
+
+/// Default player dimensions (16:9).
+pub const DEFAULT_WIDTH: u32 = 640;
+pub const DEFAULT_HEIGHT: u32 = 360;
+
+/// oEmbed "video" response.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OEmbedResponse {
+    pub version: &'static str,
+    #[serde(rename = "type")]
+    pub embed_type: &'static str,
+    pub provider_name: &'static str,
+    pub provider_url: String,
+    pub title: String,
+    pub author_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thumbnail_url: Option<String>,
+    pub html: String,
+    pub width: u32,
+    pub height: u32,
+    /// Video duration in seconds (non-standard extension).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration: Option<u64>,
+}
+
+impl OEmbedResponse {
+    /// Build an oEmbed response for a video, constrained to the requested size.
+    pub fn new(
+        video: &VideoDetails,
+        public_url: &str,
+        max_width: Option<u32>,
+        max_height: Option<u32>,
+    ) -> Self {
+        let (width, height) = fit_dimensions(max_width, max_height);
+        let html = format!(
+            r#"<iframe src="{}" width="{}" height="{}" frameborder="0" allow="autoplay; fullscreen" allowfullscreen></iframe>"#,
+            escape_xml(&embed_url(public_url, &video.id)),
+            width,
+            height
+        );
+
+        Self {
+            version: "1.0",
+            embed_type: "video",
+            provider_name: PROVIDER_NAME,
+            provider_url: public_url.to_string(),
+            title: display_title(video).to_string(),
+            author_name: video.pubkey.clone(),
+            thumbnail_url: non_empty(&video.thumbnail),
+            html,
+            width,
+            height,
+            duration: parse_duration(&video.duration),
+        }
+    }
+}
+
+/// Extract a hex event ID from a Funnel video URL or a bare ID.
+///
+/// Accepts URLs such as `.../api/videos/{id}/embed` or `.../api/videos/{id}/stats`,
+/// returning the last path segment that looks like a 64-character hex ID.
+pub fn extract_event_id(url: &str) -> Option<String> {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+
+    path.rsplit('/')
+        .find(|segment| segment.len() == 64 && segment.chars().all(|c| c.is_ascii_hexdigit()))
+        .map(|segment| segment.to_ascii_lowercase())
+}
+
+/// URL of the embeddable player page for a video.
+pub fn embed_url(public_url: &str, event_id: &str) -> String {
+    format!("{}/api/videos/{}/embed", public_url, event_id)
+}
+
+/// Render the HTML player page with OpenGraph and Twitter card metadata.
+pub fn render_embed_page(video: &VideoDetails, public_url: &str) -> String {
+    let title = escape_xml(display_title(video));
+    let page_url = embed_url(public_url, &video.id);
+    let oembed_url = format!(
+        "{}/api/oembed?url={}",
+        public_url,
+        encode_query_value(&page_url)
+    );
+
+    let mut out = String::new();
+    out.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    let _ = writeln!(out, "<title>{}</title>", title);
+    let _ = writeln!(
+        out,
+        r#"<link rel="alternate" type="application/json+oembed" href="{}" title="{}">"#,
+        escape_xml(&oembed_url),
+        title
+    );
+    meta(&mut out, "og:type", "video.other");
+    meta(&mut out, "og:site_name", PROVIDER_NAME);
+    meta(&mut out, "og:title", display_title(video));
+    meta(&mut out, "og:url", &page_url);
+    if !video.content.is_empty() {
+        meta(&mut out, "og:description", &video.content);
+    }
+    if !video.thumbnail.is_empty() {
+        meta(&mut out, "og:image", &video.thumbnail);
+    }
+    if !video.video_url.is_empty() {
+        meta(&mut out, "og:video", &video.video_url);
+        meta(
+            &mut out,
+            "og:video:type",
+            guess_media_type(&video.video_url),
+        );
+        meta(&mut out, "og:video:width", &DEFAULT_WIDTH.to_string());
+        meta(&mut out, "og:video:height", &DEFAULT_HEIGHT.to_string());
+    }
+    if let Some(duration) = parse_duration(&video.duration) {
+        meta(&mut out, "video:duration", &duration.to_string());
+    }
+    meta(&mut out, "article:author", &video.pubkey);
+    meta(&mut out, "twitter:card", "player");
+    meta(&mut out, "twitter:title", display_title(video));
+    meta(&mut out, "twitter:player", &page_url);
+    meta(&mut out, "twitter:player:width", &DEFAULT_WIDTH.to_string());
+    meta(
+        &mut out,
+        "twitter:player:height",
+        &DEFAULT_HEIGHT.to_string(),
+    );
+    out.push_str(
+        "<style>html,body{margin:0;height:100%;background:#000}video{width:100%;height:100%}</style>\n",
+    );
+    out.push_str("</head>\n<body>\n");
+
+    out.push_str(r#"<video controls playsinline preload="metadata""#);
+    if !video.thumbnail.is_empty() {
+        let _ = write!(out, r#" poster="{}""#, escape_xml(&video.thumbnail));
+    }
+    out.push('>');
+    if !video.video_url.is_empty() {
+        let _ = write!(
+            out,
+            r#"<source src="{}" type="{}">"#,
+            escape_xml(&video.video_url),
+            guess_media_type(&video.video_url)
+        );
+    }
+    out.push_str("</video>\n</body>\n</html>\n");
+    out
+}
+
+fn meta(out: &mut String, property: &str, content: &str) {
+    let _ = writeln!(
+        out,
+        r#"<meta property="{}" content="{}">"#,
+        property,
+        escape_xml(content)
+    );
+}
+
+fn display_title(video: &VideoDetails) -> &str {
+    if video.title.is_empty() {
+        video.d_tag.as_str()
+    } else {
+        video.title.as_str()
+    }
+}
+
+fn non_empty(s: &str) -> Option<String> {
+    if s.is_empty() {
+        None
+    } else {
+        Some(s.to_string())
+    }
+}
+
+/// Parse a `duration` tag value (seconds, possibly fractional).
+fn parse_duration(raw: &str) -> Option<u64> {
+    raw.trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|d| d.is_finite() && *d >= 0.0)
+        .map(|d| d.round() as u64)
+}
+
+/// Fit the default 16:9 player into the requested maximum size.
+fn fit_dimensions(max_width: Option<u32>, max_height: Option<u32>) -> (u32, u32) {
+    let mut width = DEFAULT_WIDTH;
+    let mut height = DEFAULT_HEIGHT;
+
+    if let Some(max) = max_width.filter(|m| *m > 0 && *m < width) {
+        width = max;
+        height = width * DEFAULT_HEIGHT / DEFAULT_WIDTH;
+    }
+    if let Some(max) = max_height.filter(|m| *m > 0 && *m < height) {
+        height = max;
+        width = height * DEFAULT_WIDTH / DEFAULT_HEIGHT;
+    }
+
+    (width, height)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Utc};
+
+    use super::*;
+
+    const ID: &str = "4376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65";
+
+    fn make_video() -> VideoDetails {
+        VideoDetails {
+            id: ID.to_string(),
+            pubkey: "pubkey1".to_string(),
+            created_at: DateTime::<Utc>::from_timestamp(1700000000, 0).unwrap(),
+            kind: 34235,
+            d_tag: "my-video".to_string(),
+            title: "A \"quoted\" title".to_string(),
+            thumbnail: "https://example.com/thumb.jpg".to_string(),
+            video_url: "https://example.com/video.mp4".to_string(),
+            duration: "42.6".to_string(),
+            content: String::new(),
+        }
+    }
+
+    #[test]
+    fn extract_event_id_from_embed_url() {
+        let url = format!("https://funnel.example.com/api/videos/{}/embed", ID);
+        assert_eq!(extract_event_id(&url), Some(ID.to_string()));
+    }
+
+    #[test]
+    fn extract_event_id_from_bare_id_and_query() {
+        assert_eq!(extract_event_id(ID), Some(ID.to_string()));
+        let url = format!("https://x/{}?t=10", ID.to_uppercase());
+        assert_eq!(extract_event_id(&url), Some(ID.to_string()));
+    }
+
+    #[test]
+    fn extract_event_id_rejects_non_hex() {
+        assert_eq!(extract_event_id("https://x/api/videos/abc/embed"), None);
+    }
+
+    #[test]
+    fn oembed_response_includes_iframe_and_duration() {
+        let response = OEmbedResponse::new(&make_video(), "https://f.example", None, None);
+
+        assert_eq!(response.embed_type, "video");
+        assert_eq!(response.width, 640);
+        assert_eq!(response.height, 360);
+        assert_eq!(response.duration, Some(43));
+        assert!(
+            response
+                .html
+                .contains(&format!("https://f.example/api/videos/{}/embed", ID))
+        );
+    }
+
+    #[test]
+    fn oembed_response_respects_max_width() {
+        let response = OEmbedResponse::new(&make_video(), "https://f.example", Some(320), None);
+        assert_eq!((response.width, response.height), (320, 180));
+    }
+
+    #[test]
+    fn embed_page_contains_og_tags() {
+        let html = render_embed_page(&make_video(), "https://f.example");
+
+        assert!(
+            html.contains(r#"<meta property="og:title" content="A &quot;quoted&quot; title">"#)
+        );
+        assert!(
+            html.contains(r#"<meta property="og:image" content="https://example.com/thumb.jpg">"#)
+        );
+        assert!(html.contains(r#"<meta property="video:duration" content="43">"#));
+        assert!(html.contains("application/json+oembed"));
+    }
+}
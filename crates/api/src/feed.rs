@@ -0,0 +1,223 @@
+//! RSS feed rendering for video listings.
+//!
+//! Produces RSS 2.0 documents with an `<enclosure>` per video so podcast and
+//! feed readers can follow a hashtag or creator.
+
+use std::fmt::Write;
+
+use funnel_clickhouse::VideoDetails;
+
+/// Content type used for RSS responses.
+pub const RSS_CONTENT_TYPE: &str = "application/rss+xml; charset=utf-8";
+
+/// Channel-level metadata for a feed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FeedChannel {
+    pub title: String,
+    pub description: String,
+    /// Canonical URL of the feed itself.
+    pub link: String,
+}
+
+/// Render videos as an RSS 2.0 document.
+pub fn render_rss(channel: &FeedChannel, videos: &[VideoDetails]) -> String {
+    let mut out = String::new();
+
+    out.push_str(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    out.push('\n');
+    out.push_str(
+        r#"<rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom" xmlns:media="http://search.yahoo.com/mrss/">"#,
+    );
+    out.push_str("\n<channel>\n");
+    let _ = writeln!(out, "<title>{}</title>", escape_xml(&channel.title));
+    let _ = writeln!(out, "<link>{}</link>", escape_xml(&channel.link));
+    let _ = writeln!(
+        out,
+        "<description>{}</description>",
+        escape_xml(&channel.description)
+    );
+    let _ = writeln!(
+        out,
+        r#"<atom:link href="{}" rel="self" type="application/rss+xml"/>"#,
+        escape_xml(&channel.link)
+    );
+    if let Some(latest) = videos.iter().map(|v| v.created_at).max() {
+        let _ = writeln!(
+            out,
+            "<lastBuildDate>{}</lastBuildDate>",
+            latest.to_rfc2822()
+        );
+    }
+
+    for video in videos {
+        render_item(&mut out, video);
+    }
+
+    out.push_str("</channel>\n</rss>\n");
+    out
+}
+
+fn render_item(out: &mut String, video: &VideoDetails) {
+    let title = if video.title.is_empty() {
+        video.d_tag.as_str()
+    } else {
+        video.title.as_str()
+    };
+
+    out.push_str("<item>\n");
+    let _ = writeln!(out, "<title>{}</title>", escape_xml(title));
+    let _ = writeln!(out, r#"<guid isPermaLink="false">{}</guid>"#, video.id);
+    let _ = writeln!(out, "<pubDate>{}</pubDate>", video.created_at.to_rfc2822());
+    if !video.content.is_empty() {
+        let _ = writeln!(
+            out,
+            "<description>{}</description>",
+            escape_xml(&video.content)
+        );
+    }
+    if !video.video_url.is_empty() {
+        let _ = writeln!(out, "<link>{}</link>", escape_xml(&video.video_url));
+        let _ = writeln!(
+            out,
+            r#"<enclosure url="{}" type="{}" length="0"/>"#,
+            escape_xml(&video.video_url),
+            guess_media_type(&video.video_url)
+        );
+    }
+    if !video.thumbnail.is_empty() {
+        let _ = writeln!(
+            out,
+            r#"<media:thumbnail url="{}"/>"#,
+            escape_xml(&video.thumbnail)
+        );
+    }
+    out.push_str("</item>\n");
+}
+
+/// Guess a video MIME type from the URL's file extension.
+pub fn guess_media_type(url: &str) -> &'static str {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let extension = path
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase());
+
+    match extension.as_deref() {
+        Some("webm") => "video/webm",
+        Some("mov") => "video/quicktime",
+        Some("m3u8") => "application/vnd.apple.mpegurl",
+        Some("mkv") => "video/x-matroska",
+        _ => "video/mp4",
+    }
+}
+
+/// Percent-encode a value for use in a URL query string.
+pub fn encode_query_value(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => {
+                let _ = write!(encoded, "%{:02X}", byte);
+            }
+        }
+    }
+    encoded
+}
+
+/// Escape text for inclusion in XML content or attribute values.
+pub fn escape_xml(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Utc};
+
+    use super::*;
+
+    fn make_video(id: &str, title: &str, url: &str) -> VideoDetails {
+        VideoDetails {
+            id: id.to_string(),
+            pubkey: "pubkey1".to_string(),
+            created_at: DateTime::<Utc>::from_timestamp(1700000000, 0).unwrap(),
+            kind: 34235,
+            d_tag: format!("d-{}", id),
+            title: title.to_string(),
+            thumbnail: String::new(),
+            video_url: url.to_string(),
+            duration: String::new(),
+            content: String::new(),
+        }
+    }
+
+    fn channel() -> FeedChannel {
+        FeedChannel {
+            title: "Funnel: #nostr".to_string(),
+            description: "Recent videos".to_string(),
+            link: "http://localhost:8080/api/feeds/rss?tag=nostr".to_string(),
+        }
+    }
+
+    #[test]
+    fn render_rss_includes_enclosure() {
+        let videos = vec![make_video(
+            "video1",
+            "Hello",
+            "https://cdn.example.com/a.webm",
+        )];
+        let xml = render_rss(&channel(), &videos);
+
+        assert!(xml.contains(
+            r#"<enclosure url="https://cdn.example.com/a.webm" type="video/webm" length="0"/>"#
+        ));
+        assert!(xml.contains("<guid isPermaLink=\"false\">video1</guid>"));
+        assert!(xml.contains("<pubDate>Tue, 14 Nov 2023 22:13:20 +0000</pubDate>"));
+    }
+
+    #[test]
+    fn render_rss_escapes_titles() {
+        let videos = vec![make_video("video1", "Tom & <Jerry>", "")];
+        let xml = render_rss(&channel(), &videos);
+
+        assert!(xml.contains("<title>Tom &amp; &lt;Jerry&gt;</title>"));
+        assert!(!xml.contains("<enclosure"));
+    }
+
+    #[test]
+    fn render_rss_falls_back_to_d_tag_for_untitled_videos() {
+        let videos = vec![make_video("video1", "", "https://cdn.example.com/a.mp4")];
+        let xml = render_rss(&channel(), &videos);
+
+        assert!(xml.contains("<title>d-video1</title>"));
+    }
+
+    #[test]
+    fn encode_query_value_escapes_reserved_characters() {
+        assert_eq!(encode_query_value("nostr"), "nostr");
+        assert_eq!(encode_query_value("a b&c"), "a%20b%26c");
+    }
+
+    #[test]
+    fn guess_media_type_uses_extension() {
+        assert_eq!(guess_media_type("https://x/a.MP4"), "video/mp4");
+        assert_eq!(guess_media_type("https://x/a.mov?sig=1"), "video/quicktime");
+        assert_eq!(
+            guess_media_type("https://x/stream.m3u8"),
+            "application/vnd.apple.mpegurl"
+        );
+        assert_eq!(guess_media_type("https://x/blob"), "video/mp4");
+    }
+}
@@ -0,0 +1,306 @@
+//! Response format negotiation and CSV rendering.
+//!
+//! List endpoints return JSON by default. Clients can ask for CSV either with
+//! `?format=csv` or an `Accept: text/csv` header; the query parameter wins when
+//! both are present. Without the parameter, responses carry `Vary: Accept`, so
+//! shared caches keep a URL's JSON and CSV apart.
+
+use axum::{
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use funnel_clickhouse::{TrendingVideo, VideoHashtag, VideoStats};
+
+/// Content type used for CSV responses.
+pub const CSV_CONTENT_TYPE: &str = "text/csv; charset=utf-8";
+
+/// Output format requested by the client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ResponseFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+impl ResponseFormat {
+    /// Pick a format from the `format` query parameter and the `Accept` header.
+    pub fn negotiate(headers: &HeaderMap, format: Option<&str>) -> Self {
+        if let Some(format) = format {
+            return match format.to_ascii_lowercase().as_str() {
+                "csv" => Self::Csv,
+                _ => Self::Json,
+            };
+        }
+
+        let accepts_csv = headers
+            .get(header::ACCEPT)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|accept| {
+                accept
+                    .split(',')
+                    .any(|media| media.trim().starts_with("text/csv"))
+            });
+
+        if accepts_csv { Self::Csv } else { Self::Json }
+    }
+}
+
+/// A row type that can be written as a CSV record.
+pub trait CsvRecord {
+    /// Column names, written as the first line of the output.
+    const HEADER: &'static [&'static str];
+
+    /// Append this record's fields, in `HEADER` order.
+    fn write_fields(&self, fields: &mut Vec<String>);
+}
+
+/// Render rows as CSV, including a header line.
+pub fn to_csv<T>(rows: &[T]) -> String
+where
+    T: CsvRecord,
+{
+    let mut out = String::new();
+    write_line(&mut out, T::HEADER.iter().map(|s| s.to_string()));
+
+    let mut fields = Vec::with_capacity(T::HEADER.len());
+    for row in rows {
+        fields.clear();
+        row.write_fields(&mut fields);
+        write_line(&mut out, fields.drain(..));
+    }
+
+    out
+}
+
+/// Build a CSV response with the given `Cache-Control` value.
+pub fn csv_response<T>(rows: &[T], cache_control: &'static str) -> Response
+where
+    T: CsvRecord,
+{
+    (
+        StatusCode::OK,
+        [
+            (
+                header::CONTENT_TYPE,
+                HeaderValue::from_static(CSV_CONTENT_TYPE),
+            ),
+            (
+                header::CACHE_CONTROL,
+                HeaderValue::from_static(cache_control),
+            ),
+        ],
+        to_csv(rows),
+    )
+        .into_response()
+}
+
+/// Add `Vary: Accept` to `response` unless the `format` query parameter picked its
+/// representation.
+pub fn vary_on_accept(format: Option<&str>, mut response: Response) -> Response {
+    if format.is_none() {
+        response
+            .headers_mut()
+            .append(header::VARY, HeaderValue::from_static("Accept"));
+    }
+    response
+}
+
+fn write_line<I>(out: &mut String, fields: I)
+where
+    I: Iterator<Item = String>,
+{
+    for (i, field) in fields.enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        out.push_str(&escape_field(&field));
+    }
+    out.push_str("\r\n");
+}
+
+/// Quote a field per RFC 4180 and neutralize spreadsheet formula prefixes.
+///
+/// Titles are user-supplied, so a value like `=HYPERLINK(...)` would otherwise be
+/// evaluated when the export is opened in a spreadsheet.
+fn escape_field(field: &str) -> String {
+    let field = if field.starts_with(['=', '+', '-', '@']) {
+        format!("'{}", field)
+    } else {
+        field.to_string()
+    };
+
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field
+    }
+}
+
+fn format_timestamp(ts: &DateTime<Utc>) -> String {
+    ts.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+impl CsvRecord for VideoStats {
+    const HEADER: &'static [&'static str] = &[
+        "id",
+        "pubkey",
+        "created_at",
+        "kind",
+        "d_tag",
+        "title",
+        "thumbnail",
+        "reactions",
+        "comments",
+        "reposts",
+        "engagement_score",
+    ];
+
+    fn write_fields(&self, fields: &mut Vec<String>) {
+        fields.extend([
+            self.id.clone(),
+            self.pubkey.clone(),
+            format_timestamp(&self.created_at),
+            self.kind.to_string(),
+            self.d_tag.clone(),
+            self.title.clone(),
+            self.thumbnail.clone(),
+            self.reactions.to_string(),
+            self.comments.to_string(),
+            self.reposts.to_string(),
+            self.engagement_score.to_string(),
+        ]);
+    }
+}
+
+impl CsvRecord for TrendingVideo {
+    const HEADER: &'static [&'static str] = &[
+        "id",
+        "pubkey",
+        "created_at",
+        "kind",
+        "d_tag",
+        "title",
+        "thumbnail",
+        "reactions",
+        "comments",
+        "reposts",
+        "engagement_score",
+        "trending_score",
+    ];
+
+    fn write_fields(&self, fields: &mut Vec<String>) {
+        fields.extend([
+            self.id.clone(),
+            self.pubkey.clone(),
+            format_timestamp(&self.created_at),
+            self.kind.to_string(),
+            self.d_tag.clone(),
+            self.title.clone(),
+            self.thumbnail.clone(),
+            self.reactions.to_string(),
+            self.comments.to_string(),
+            self.reposts.to_string(),
+            self.engagement_score.to_string(),
+            self.trending_score.to_string(),
+        ]);
+    }
+}
+
+impl CsvRecord for VideoHashtag {
+    const HEADER: &'static [&'static str] = &[
+        "event_id",
+        "hashtag",
+        "created_at",
+        "pubkey",
+        "kind",
+        "title",
+        "thumbnail",
+        "d_tag",
+    ];
+
+    fn write_fields(&self, fields: &mut Vec<String>) {
+        fields.extend([
+            self.event_id.clone(),
+            self.hashtag.clone(),
+            format_timestamp(&self.created_at),
+            self.pubkey.clone(),
+            self.kind.to_string(),
+            self.title.clone(),
+            self.thumbnail.clone(),
+            self.d_tag.clone(),
+        ]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negotiate_defaults_to_json() {
+        let headers = HeaderMap::new();
+        assert_eq!(
+            ResponseFormat::negotiate(&headers, None),
+            ResponseFormat::Json
+        );
+    }
+
+    #[test]
+    fn negotiate_uses_query_parameter() {
+        let headers = HeaderMap::new();
+        assert_eq!(
+            ResponseFormat::negotiate(&headers, Some("CSV")),
+            ResponseFormat::Csv
+        );
+    }
+
+    #[test]
+    fn negotiate_uses_accept_header() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::ACCEPT,
+            "application/json;q=0.5, text/csv".parse().unwrap(),
+        );
+        assert_eq!(
+            ResponseFormat::negotiate(&headers, None),
+            ResponseFormat::Csv
+        );
+    }
+
+    #[test]
+    fn negotiate_query_parameter_overrides_accept_header() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, "text/csv".parse().unwrap());
+        assert_eq!(
+            ResponseFormat::negotiate(&headers, Some("json")),
+            ResponseFormat::Json
+        );
+    }
+
+    #[test]
+    fn vary_on_accept_only_without_query_parameter() {
+        let vary = |format| {
+            vary_on_accept(format, StatusCode::OK.into_response())
+                .headers()
+                .get(header::VARY)
+                .cloned()
+        };
+        assert_eq!(vary(None), Some(HeaderValue::from_static("Accept")));
+        assert_eq!(vary(Some("csv")), None);
+    }
+
+    #[test]
+    fn escape_field_quotes_special_characters() {
+        assert_eq!(escape_field("plain"), "plain");
+        assert_eq!(escape_field("a,b"), "\"a,b\"");
+        assert_eq!(escape_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(escape_field("line\nbreak"), "\"line\nbreak\"");
+    }
+
+    #[test]
+    fn escape_field_neutralizes_formulas() {
+        assert_eq!(escape_field("=1+1"), "'=1+1");
+        assert_eq!(escape_field("@cmd"), "'@cmd");
+    }
+}
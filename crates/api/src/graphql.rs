@@ -0,0 +1,448 @@
+//! GraphQL endpoint over the storage traits.
+//!
+//! `POST /api/graphql` exposes the same data as the REST endpoints, but lets a
+//! client follow relations in one request, e.g. a video, its author, and the
+//! author's other videos.
+//!
+//! The schema is built once without a storage type; each request carries the
+//! storage backend as request data behind the object-safe [`GraphStorage`] trait.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Instant;
+
+use async_graphql::{
+    ComplexObject, Context, EmptyMutation, EmptySubscription, Enum, ErrorExtensions, Object,
+    Schema, SimpleObject,
+};
+use axum::{
+    Extension, Json,
+    extract::State,
+    http::header,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use funnel_clickhouse::{
+    ClickHouseError, StatsQueries, TrendingVideo, VideoHashtag, VideoQueries, VideoStats,
+};
+use funnel_observability::api;
+use funnel_proto::ErrorCode;
+use metrics::{counter, histogram};
+
+use crate::handlers::AppState;
+
+/// Default number of items returned by list fields.
+const DEFAULT_LIMIT: u32 = 20;
+
+/// Maximum number of items returned by list fields.
+const MAX_LIMIT: u32 = 100;
+
+/// Maximum nesting depth of a query.
+const MAX_DEPTH: usize = 8;
+
+/// Maximum complexity (roughly, number of fields resolved) of a query. List fields
+/// count their children once per item they can return.
+const MAX_COMPLEXITY: usize = 10_000;
+
+/// The API's GraphQL schema.
+pub type FunnelSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// Build the GraphQL schema.
+pub fn build_schema() -> FunnelSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish()
+}
+
+/// Execute a GraphQL request.
+pub async fn graphql_handler<S>(
+    State(state): State<AppState<S>>,
+    Extension(schema): Extension<FunnelSchema>,
+    Json(request): Json<async_graphql::Request>,
+) -> Response
+where
+    S: VideoQueries + StatsQueries + Clone + Send + Sync + 'static,
+{
+    let start = Instant::now();
+    counter!(api::REQUESTS, "endpoint" => "graphql").increment(1);
+
+    let storage: Arc<dyn GraphStorage> = state.storage.clone();
+    let response = schema.execute(request.data(storage)).await;
+
+    histogram!(api::QUERY_DURATION, "endpoint" => "graphql").record(start.elapsed().as_secs_f64());
+
+    ([(header::CACHE_CONTROL, "no-store")], Json(response)).into_response()
+}
+
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, ClickHouseError>> + Send + 'a>>;
+
+/// Object-safe view of the storage traits used by resolvers.
+pub trait GraphStorage: Send + Sync {
+    fn video_stats<'a>(&'a self, event_id: &'a str) -> BoxFuture<'a, Option<VideoStats>>;
+    fn videos_by_author<'a>(
+        &'a self,
+        pubkey: &'a str,
+        limit: u32,
+    ) -> BoxFuture<'a, Vec<VideoStats>>;
+    fn trending_videos(
+        &self,
+        min_trust: Option<f64>,
+        limit: u32,
+    ) -> BoxFuture<'_, Vec<TrendingVideo>>;
+    fn recent_videos(
+        &self,
+        kind: Option<u16>,
+        min_trust: Option<f64>,
+        limit: u32,
+    ) -> BoxFuture<'_, Vec<VideoStats>>;
+    fn hashtag_videos<'a>(&'a self, tag: &'a str, limit: u32) -> BoxFuture<'a, Vec<VideoHashtag>>;
+    fn text_search<'a>(&'a self, query: &'a str, limit: u32) -> BoxFuture<'a, Vec<VideoStats>>;
+    fn event_count(&self) -> BoxFuture<'_, u64>;
+    fn video_count(&self) -> BoxFuture<'_, u64>;
+}
+
+impl<S> GraphStorage for S
+where
+    S: VideoQueries + StatsQueries,
+{
+    fn video_stats<'a>(&'a self, event_id: &'a str) -> BoxFuture<'a, Option<VideoStats>> {
+        Box::pin(self.get_video_stats(event_id))
+    }
+
+    fn videos_by_author<'a>(
+        &'a self,
+        pubkey: &'a str,
+        limit: u32,
+    ) -> BoxFuture<'a, Vec<VideoStats>> {
+        Box::pin(self.get_videos_by_author(pubkey, limit))
+    }
+
+    fn trending_videos(
+        &self,
+        min_trust: Option<f64>,
+        limit: u32,
+    ) -> BoxFuture<'_, Vec<TrendingVideo>> {
+        Box::pin(self.get_trending_videos(min_trust, limit))
+    }
+
+    fn recent_videos(
+        &self,
+        kind: Option<u16>,
+        min_trust: Option<f64>,
+        limit: u32,
+    ) -> BoxFuture<'_, Vec<VideoStats>> {
+        Box::pin(self.get_recent_videos(kind, min_trust, limit))
+    }
+
+    fn hashtag_videos<'a>(&'a self, tag: &'a str, limit: u32) -> BoxFuture<'a, Vec<VideoHashtag>> {
+        Box::pin(self.search_by_hashtag(tag, None, limit))
+    }
+
+    fn text_search<'a>(&'a self, query: &'a str, limit: u32) -> BoxFuture<'a, Vec<VideoStats>> {
+        Box::pin(self.search_by_text(query, None, limit))
+    }
+
+    fn event_count(&self) -> BoxFuture<'_, u64> {
+        Box::pin(self.get_event_count())
+    }
+
+    fn video_count(&self) -> BoxFuture<'_, u64> {
+        Box::pin(self.get_video_count())
+    }
+}
+
+/// Video ordering for the `videos` field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Enum)]
+pub enum VideoSort {
+    #[default]
+    Recent,
+    Trending,
+}
+
+/// A video with engagement counts.
+#[derive(Debug, Clone, SimpleObject)]
+#[graphql(complex)]
+pub struct Video {
+    pub id: String,
+    pub pubkey: String,
+    pub created_at: DateTime<Utc>,
+    pub kind: u16,
+    pub d_tag: String,
+    pub title: String,
+    pub thumbnail: String,
+    pub reactions: u64,
+    pub comments: u64,
+    pub reposts: u64,
+    pub engagement_score: u64,
+    /// Only set when listing trending videos.
+    pub trending_score: Option<f64>,
+}
+
+#[ComplexObject]
+impl Video {
+    /// The video's author.
+    async fn author(&self) -> Creator {
+        Creator {
+            pubkey: self.pubkey.clone(),
+        }
+    }
+}
+
+impl From<VideoStats> for Video {
+    fn from(v: VideoStats) -> Self {
+        Self {
+            id: v.id,
+            pubkey: v.pubkey,
+            created_at: v.created_at,
+            kind: v.kind,
+            d_tag: v.d_tag,
+            title: v.title,
+            thumbnail: v.thumbnail,
+            reactions: v.reactions,
+            comments: v.comments,
+            reposts: v.reposts,
+            engagement_score: v.engagement_score,
+            trending_score: None,
+        }
+    }
+}
+
+impl From<TrendingVideo> for Video {
+    fn from(v: TrendingVideo) -> Self {
+        Self {
+            id: v.id,
+            pubkey: v.pubkey,
+            created_at: v.created_at,
+            kind: v.kind,
+            d_tag: v.d_tag,
+            title: v.title,
+            thumbnail: v.thumbnail,
+            reactions: v.reactions,
+            comments: v.comments,
+            reposts: v.reposts,
+            engagement_score: v.engagement_score,
+            trending_score: Some(v.trending_score),
+        }
+    }
+}
+
+/// A video matching a hashtag search.
+#[derive(Debug, Clone, SimpleObject)]
+#[graphql(complex)]
+pub struct HashtagVideo {
+    pub event_id: String,
+    pub hashtag: String,
+    pub created_at: DateTime<Utc>,
+    pub pubkey: String,
+    pub kind: u16,
+    pub title: String,
+    pub thumbnail: String,
+    pub d_tag: String,
+}
+
+#[ComplexObject]
+impl HashtagVideo {
+    /// The video with engagement counts.
+    async fn video(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Video>> {
+        let stats = storage(ctx)?
+            .video_stats(&self.event_id)
+            .await
+            .map_err(storage_error)?;
+        Ok(stats.map(Video::from))
+    }
+
+    /// The video's author.
+    async fn author(&self) -> Creator {
+        Creator {
+            pubkey: self.pubkey.clone(),
+        }
+    }
+}
+
+impl From<VideoHashtag> for HashtagVideo {
+    fn from(v: VideoHashtag) -> Self {
+        Self {
+            event_id: v.event_id,
+            hashtag: v.hashtag,
+            created_at: v.created_at,
+            pubkey: v.pubkey,
+            kind: v.kind,
+            title: v.title,
+            thumbnail: v.thumbnail,
+            d_tag: v.d_tag,
+        }
+    }
+}
+
+/// A video creator, identified by pubkey.
+#[derive(Debug, Clone)]
+pub struct Creator {
+    pubkey: String,
+}
+
+#[Object]
+impl Creator {
+    async fn pubkey(&self) -> &str {
+        &self.pubkey
+    }
+
+    /// The creator's most recent videos.
+    #[graphql(complexity = "clamp_limit(limit) as usize * child_complexity")]
+    async fn videos(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default_with = "DEFAULT_LIMIT")] limit: u32,
+    ) -> async_graphql::Result<Vec<Video>> {
+        let videos = storage(ctx)?
+            .videos_by_author(&self.pubkey, clamp_limit(limit))
+            .await
+            .map_err(storage_error)?;
+        Ok(videos.into_iter().map(Video::from).collect())
+    }
+}
+
+/// Global totals.
+#[derive(Debug, Clone, SimpleObject)]
+pub struct Stats {
+    pub total_events: u64,
+    pub total_videos: u64,
+}
+
+/// Root query type.
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// List recent or trending videos, optionally only from creators with at least
+    /// `minTrust` (0 to 1) web-of-trust score.
+    #[graphql(complexity = "clamp_limit(limit) as usize * child_complexity")]
+    async fn videos(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default)] sort: VideoSort,
+        kind: Option<u16>,
+        #[graphql(default_with = "DEFAULT_LIMIT")] limit: u32,
+        min_trust: Option<f64>,
+    ) -> async_graphql::Result<Vec<Video>> {
+        let storage = storage(ctx)?;
+        let limit = clamp_limit(limit);
+        let min_trust = match min_trust {
+            Some(min) if !(0.0..=1.0).contains(&min) => {
+                return Err(
+                    async_graphql::Error::new("minTrust must be between 0 and 1").extend_with(
+                        |_, extensions| {
+                            extensions.set("code", ErrorCode::ApiInvalidParameter.as_str())
+                        },
+                    ),
+                );
+            }
+            min_trust => min_trust.filter(|min| *min > 0.0),
+        };
+        let videos = match sort {
+            VideoSort::Recent => storage
+                .recent_videos(kind, min_trust, limit)
+                .await
+                .map(|v| v.into_iter().map(Video::from).collect()),
+            VideoSort::Trending => storage
+                .trending_videos(min_trust, limit)
+                .await
+                .map(|v| v.into_iter().map(Video::from).collect()),
+        };
+        videos.map_err(storage_error)
+    }
+
+    /// Look up a video by event ID.
+    async fn video(&self, ctx: &Context<'_>, id: String) -> async_graphql::Result<Option<Video>> {
+        let stats = storage(ctx)?
+            .video_stats(&id)
+            .await
+            .map_err(storage_error)?;
+        Ok(stats.map(Video::from))
+    }
+
+    /// Look up a creator by pubkey.
+    async fn creator(&self, pubkey: String) -> Creator {
+        Creator { pubkey }
+    }
+
+    /// Full-text search over video titles.
+    #[graphql(complexity = "clamp_limit(limit) as usize * child_complexity")]
+    async fn search(
+        &self,
+        ctx: &Context<'_>,
+        query: String,
+        #[graphql(default_with = "DEFAULT_LIMIT")] limit: u32,
+    ) -> async_graphql::Result<Vec<Video>> {
+        let videos = storage(ctx)?
+            .text_search(&query, clamp_limit(limit))
+            .await
+            .map_err(storage_error)?;
+        Ok(videos.into_iter().map(Video::from).collect())
+    }
+
+    /// Videos tagged with a hashtag.
+    #[graphql(complexity = "clamp_limit(limit) as usize * child_complexity")]
+    async fn hashtag(
+        &self,
+        ctx: &Context<'_>,
+        tag: String,
+        #[graphql(default_with = "DEFAULT_LIMIT")] limit: u32,
+    ) -> async_graphql::Result<Vec<HashtagVideo>> {
+        let videos = storage(ctx)?
+            .hashtag_videos(&tag, clamp_limit(limit))
+            .await
+            .map_err(storage_error)?;
+        Ok(videos.into_iter().map(HashtagVideo::from).collect())
+    }
+
+    /// Global event and video totals.
+    async fn stats(&self, ctx: &Context<'_>) -> async_graphql::Result<Stats> {
+        let storage = storage(ctx)?;
+        let total_events = storage.event_count().await.map_err(storage_error)?;
+        let total_videos = storage.video_count().await.map_err(storage_error)?;
+        Ok(Stats {
+            total_events,
+            total_videos,
+        })
+    }
+}
+
+fn storage<'a>(ctx: &Context<'a>) -> async_graphql::Result<&'a Arc<dyn GraphStorage>> {
+    ctx.data::<Arc<dyn GraphStorage>>()
+}
+
+fn clamp_limit(limit: u32) -> u32 {
+    limit.clamp(1, MAX_LIMIT)
+}
+
+/// Log storage errors and hide their details from clients, as the REST handlers do.
+fn storage_error(e: ClickHouseError) -> async_graphql::Error {
+    tracing::error!(code = %e.code(), error = %e, "GraphQL storage query failed");
+    let code = e.code();
+    async_graphql::Error::new("Internal server error")
+        .extend_with(|_, extensions| extensions.set("code", code.as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clamp_limit_bounds() {
+        assert_eq!(clamp_limit(0), 1);
+        assert_eq!(clamp_limit(DEFAULT_LIMIT), DEFAULT_LIMIT);
+        assert_eq!(clamp_limit(1000), MAX_LIMIT);
+    }
+
+    #[test]
+    fn schema_exposes_query_fields() {
+        let sdl = build_schema().sdl();
+        for field in [
+            "videos(", "video(", "creator(", "search(", "hashtag(", "stats:",
+        ] {
+            assert!(sdl.contains(field), "missing {field} in schema");
+        }
+    }
+}
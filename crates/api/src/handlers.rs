@@ -4,19 +4,39 @@
 //! with mock implementations.
 
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use axum::{
     Json,
     extract::{Path, Query, State},
-    http::{StatusCode, header},
-    response::IntoResponse,
+    http::{HeaderMap, StatusCode, header},
+    response::{Html, IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use funnel_clickhouse::{
+    EventDeletion, FileMetadataEvent, IndexedVideo, IngestLatency, KindCount, MediaHealth,
+    MediaVerification, RelaySummary, StatsQueries, VideoDuplicate, VideoQueries, VideoStats,
 };
-use funnel_clickhouse::{StatsQueries, VideoQueries};
 use funnel_observability::api;
+use funnel_observability::heartbeat::Heartbeat;
+use funnel_proto::{
+    ErrorCode, EventAddress, FileMeta, KIND_VIDEO_SET, VideoSet, encode_nevent, encode_npub,
+    normalize_pubkey,
+};
 use metrics::{counter, histogram};
 use serde::{Deserialize, Serialize};
 
+use crate::embed::{OEmbedResponse, extract_event_id, render_embed_page};
+use crate::feed::{FeedChannel, RSS_CONTENT_TYPE, encode_query_value, render_rss};
+use crate::format::{ResponseFormat, csv_response, vary_on_accept};
+use crate::params::{EventIdParam, PubkeyParam};
+use crate::playlist::{Playlist, VideoIndex, video_refs};
+use crate::search::{SearchCursor, SearchResponse, TOTAL_ESTIMATE_CAP};
+use crate::updates::{VideoCursor, VideoUpdates, parse_wait};
+
+/// Default public base URL used when building absolute links.
+pub const DEFAULT_PUBLIC_URL: &str = "http://localhost:8080";
+
 /// Application state containing the storage backend.
 #[derive(Clone)]
 pub struct AppState<S>
@@ -24,6 +44,14 @@ where
     S: VideoQueries + StatsQueries + Clone + Send + Sync + 'static,
 {
     pub storage: Arc<S>,
+    /// Externally visible base URL (no trailing slash), used for feed links.
+    pub public_url: String,
+    /// Replication delay (seconds) above which `/readyz` reports not ready.
+    pub max_replication_lag: Option<u64>,
+    /// Broadcast of newly indexed videos, shared by long-poll requests.
+    pub updates: Arc<VideoUpdates>,
+    /// Background self-checks, reported by `/readyz` when running.
+    pub heartbeat: Option<Heartbeat>,
 }
 
 impl<S> AppState<S>
@@ -33,28 +61,105 @@ where
     pub fn new(storage: S) -> Self {
         Self {
             storage: Arc::new(storage),
+            public_url: DEFAULT_PUBLIC_URL.to_string(),
+            max_replication_lag: None,
+            updates: Arc::new(VideoUpdates::default()),
+            heartbeat: None,
         }
     }
+
+    /// Set the externally visible base URL.
+    pub fn with_public_url(mut self, public_url: impl Into<String>) -> Self {
+        self.public_url = public_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Fail readiness when replication lags by more than `seconds`.
+    pub fn with_max_replication_lag(mut self, seconds: u64) -> Self {
+        self.max_replication_lag = Some(seconds);
+        self
+    }
+
+    /// Check for newly indexed videos every `interval` while long polls are waiting.
+    pub fn with_update_interval(mut self, interval: Duration) -> Self {
+        self.updates = Arc::new(VideoUpdates::new(interval));
+        self
+    }
+
+    /// Report `heartbeat`'s self-checks from `/readyz`.
+    pub fn with_heartbeat(mut self, heartbeat: Heartbeat) -> Self {
+        self.heartbeat = Some(heartbeat);
+        self
+    }
+}
+
+/// Body of every JSON error response.
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorResponse {
+    pub error: &'static str,
+    /// Stable code for grouping failures, e.g. `FNL-API-003`.
+    pub code: ErrorCode,
+}
+
+impl ErrorResponse {
+    pub fn new(code: ErrorCode, error: &'static str) -> Self {
+        Self { error, code }
+    }
+}
+
+/// Health check response body.
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthResponse {
+    pub status: &'static str,
 }
 
 /// Health check response.
 pub async fn health() -> impl IntoResponse {
     (
         [(header::CACHE_CONTROL, "no-store")],
-        Json(serde_json::json!({ "status": "ok" })),
+        Json(HealthResponse { status: "ok" }),
     )
 }
 
-/// Video stats path parameters.
-#[derive(Debug, Deserialize)]
-pub struct VideoStatsPath {
-    pub id: String,
+/// Response of `GET /api/videos/{id}/stats`: the video's stats with NIP-19 forms of
+/// its identifiers, for clients to link to, and the metadata of its file.
+#[derive(Debug, Serialize)]
+pub struct VideoStatsResponse {
+    #[serde(flatten)]
+    pub stats: VideoStats,
+    /// `nevent` of the video, with its author.
+    pub nevent: Option<String>,
+    /// `naddr` of the video, which resolves to its latest version.
+    pub naddr: Option<String>,
+    /// `npub` of the video's author.
+    pub npub: Option<String>,
+    /// Metadata (size, blurhash, ...) from the file metadata event for the video's
+    /// file, if one was published.
+    pub file: Option<FileMeta>,
+}
+
+impl VideoStatsResponse {
+    fn new(stats: VideoStats, file: Option<FileMetadataEvent>) -> Self {
+        let naddr = EventAddress {
+            kind: stats.kind,
+            pubkey: stats.pubkey.clone(),
+            identifier: stats.d_tag.clone(),
+        }
+        .to_naddr();
+        Self {
+            nevent: encode_nevent(&stats.id, Some(&stats.pubkey), &[]),
+            naddr,
+            npub: encode_npub(&stats.pubkey),
+            file: file.map(|event| FileMeta::from_tags(&event.tags)),
+            stats,
+        }
+    }
 }
 
 /// Get stats for a specific video.
 pub async fn get_video_stats<S>(
     State(state): State<AppState<S>>,
-    Path(params): Path<VideoStatsPath>,
+    EventIdParam(id): EventIdParam,
 ) -> impl IntoResponse
 where
     S: VideoQueries + StatsQueries + Clone + Send + Sync + 'static,
@@ -62,62 +167,347 @@ where
     let start = Instant::now();
     counter!(api::REQUESTS, "endpoint" => "video_stats").increment(1);
 
-    match state.storage.get_video_stats(&params.id).await {
-        Ok(Some(stats)) => {
+    match tokio::try_join!(
+        state.storage.get_video_stats(&id),
+        state.storage.get_file_metadata(&id),
+    ) {
+        Ok((Some(stats), file)) => {
             histogram!(api::QUERY_DURATION, "endpoint" => "video_stats")
                 .record(start.elapsed().as_secs_f64());
             (
                 StatusCode::OK,
                 [(header::CACHE_CONTROL, "public, max-age=30")],
-                Json(serde_json::to_value(stats).unwrap()),
+                Json(VideoStatsResponse::new(stats, file)),
             )
                 .into_response()
         }
-        Ok(None) => (
-            StatusCode::NOT_FOUND,
-            [(header::CACHE_CONTROL, "no-store")],
-            Json(serde_json::json!({ "error": "Video not found" })),
+        Ok((None, _)) => video_not_found(state.storage.as_ref(), &id).await,
+        Err(e) => {
+            tracing::error!(code = %e.code(), error = %e, "Failed to get video stats");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                [(header::CACHE_CONTROL, "no-store")],
+                Json(ErrorResponse::new(e.code(), "Internal server error")),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Latest health check of one of a video's media URLs.
+#[derive(Debug, Serialize)]
+pub struct MediaStatus {
+    pub url: String,
+    /// `video` or `thumbnail`.
+    pub media_type: String,
+    pub available: bool,
+    /// Whether the URL failed enough checks in a row to count as dead.
+    pub dead: bool,
+    /// HTTP status of the last check, or 0 when the request failed.
+    pub status: u16,
+    pub content_type: String,
+    pub checked_at: DateTime<Utc>,
+}
+
+impl From<MediaHealth> for MediaStatus {
+    fn from(health: MediaHealth) -> Self {
+        Self {
+            dead: health.is_dead(),
+            url: health.url,
+            media_type: health.media_type,
+            available: health.available,
+            status: health.status,
+            content_type: health.content_type,
+            checked_at: health.checked_at,
+        }
+    }
+}
+
+/// Latest check of a video file against the SHA-256 declared in its `imeta` tag.
+#[derive(Debug, Serialize)]
+pub struct MediaIntegrity {
+    pub sha256: String,
+    /// `verified`, `mismatch`, `unavailable`, or `too_large`.
+    pub status: String,
+    pub verified: bool,
+    /// URL the matching file was fetched from, when verified.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    pub checked_at: DateTime<Utc>,
+}
+
+impl From<MediaVerification> for MediaIntegrity {
+    fn from(verification: MediaVerification) -> Self {
+        Self {
+            verified: verification.is_verified(),
+            sha256: verification.sha256,
+            status: verification.status,
+            source: (!verification.source.is_empty()).then_some(verification.source),
+            checked_at: verification.checked_at,
+        }
+    }
+}
+
+/// Response of `GET /api/videos/{id}/media`.
+#[derive(Debug, Serialize)]
+pub struct MediaHealthResponse {
+    pub event_id: String,
+    /// Empty until the media checker has visited the video.
+    pub media: Vec<MediaStatus>,
+    /// `null` until the hash verifier has checked the video file, or when the video
+    /// declares no SHA-256.
+    pub integrity: Option<MediaIntegrity>,
+}
+
+/// Get the health of a video's media URLs and the integrity of its video file.
+pub async fn get_video_media<S>(
+    State(state): State<AppState<S>>,
+    EventIdParam(id): EventIdParam,
+) -> impl IntoResponse
+where
+    S: VideoQueries + StatsQueries + Clone + Send + Sync + 'static,
+{
+    let start = Instant::now();
+    counter!(api::REQUESTS, "endpoint" => "video_media").increment(1);
+
+    match tokio::try_join!(
+        state.storage.get_media_health(&id),
+        state.storage.get_media_verification(&id),
+    ) {
+        Ok((health, verification)) => {
+            histogram!(api::QUERY_DURATION, "endpoint" => "video_media")
+                .record(start.elapsed().as_secs_f64());
+            (
+                StatusCode::OK,
+                [(header::CACHE_CONTROL, "public, max-age=60")],
+                Json(MediaHealthResponse {
+                    event_id: id,
+                    media: health.into_iter().map(MediaStatus::from).collect(),
+                    integrity: verification.map(MediaIntegrity::from),
+                }),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            tracing::error!(code = %e.code(), error = %e, "Failed to get media health");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                [(header::CACHE_CONTROL, "no-store")],
+                Json(ErrorResponse::new(e.code(), "Internal server error")),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// A video related to another as a re-upload.
+#[derive(Debug, Serialize)]
+pub struct DuplicateLink {
+    pub event_id: String,
+    /// `sha256` (same file) or `title` (same title ignoring case and punctuation).
+    pub reason: String,
+}
+
+/// Response of `GET /api/videos/{id}/duplicates`.
+#[derive(Debug, Serialize)]
+pub struct DuplicatesResponse {
+    pub event_id: String,
+    /// The earlier video by another pubkey this one re-uploads, if any.
+    pub duplicate_of: Option<DuplicateLink>,
+    /// Later videos by other pubkeys that re-upload this one.
+    pub duplicates: Vec<DuplicateLink>,
+}
+
+impl DuplicatesResponse {
+    fn new(event_id: String, rows: Vec<VideoDuplicate>) -> Self {
+        let (own, others): (Vec<_>, Vec<_>) =
+            rows.into_iter().partition(|row| row.event_id == event_id);
+        Self {
+            duplicate_of: own.into_iter().next().map(|row| DuplicateLink {
+                event_id: row.duplicate_of,
+                reason: row.reason,
+            }),
+            duplicates: others
+                .into_iter()
+                .map(|row| DuplicateLink {
+                    event_id: row.event_id,
+                    reason: row.reason,
+                })
+                .collect(),
+            event_id,
+        }
+    }
+}
+
+/// Get which video a video re-uploads, and which videos re-upload it.
+pub async fn get_video_duplicates<S>(
+    State(state): State<AppState<S>>,
+    EventIdParam(id): EventIdParam,
+) -> impl IntoResponse
+where
+    S: VideoQueries + StatsQueries + Clone + Send + Sync + 'static,
+{
+    let start = Instant::now();
+    counter!(api::REQUESTS, "endpoint" => "video_duplicates").increment(1);
+
+    let result = state.storage.get_video_duplicates(&id).await;
+
+    histogram!(api::QUERY_DURATION, "endpoint" => "video_duplicates")
+        .record(start.elapsed().as_secs_f64());
+
+    match result {
+        Ok(rows) => (
+            StatusCode::OK,
+            [(header::CACHE_CONTROL, "public, max-age=60")],
+            Json(DuplicatesResponse::new(id, rows)),
         )
             .into_response(),
         Err(e) => {
-            tracing::error!(error = %e, "Failed to get video stats");
+            tracing::error!(code = %e.code(), error = %e, "Failed to get video duplicates");
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 [(header::CACHE_CONTROL, "no-store")],
-                Json(serde_json::json!({ "error": "Internal server error" })),
+                Json(ErrorResponse::new(e.code(), "Internal server error")),
             )
                 .into_response()
         }
     }
 }
 
+/// Body of the `410 Gone` returned for a deleted video.
+#[derive(Debug, Serialize)]
+pub struct VideoGone {
+    pub error: &'static str,
+    pub code: ErrorCode,
+    pub deleted_at: DateTime<Utc>,
+    /// `author` for NIP-09 deletion requests, `operator` for admin tombstones.
+    pub deleted_by: &'static str,
+    /// ID of the kind 5 deletion request, for author deletions.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deletion_event_id: Option<String>,
+}
+
+impl VideoGone {
+    pub fn new(deletion: EventDeletion) -> Self {
+        let by_author = !deletion.deletion_event_id.is_empty();
+        Self {
+            error: "Video deleted",
+            code: ErrorCode::ApiGone,
+            deleted_at: deletion.deleted_at,
+            deleted_by: if by_author { "author" } else { "operator" },
+            deletion_event_id: by_author.then_some(deletion.deletion_event_id),
+        }
+    }
+}
+
+/// Tombstone of a video that wasn't found, if it was deleted.
+///
+/// A failed lookup is logged and treated as no tombstone, so it can't turn a `404`
+/// into a `500`.
+async fn find_deletion<S>(storage: &S, event_id: &str) -> Option<EventDeletion>
+where
+    S: VideoQueries,
+{
+    storage
+        .get_video_deletion(event_id)
+        .await
+        .unwrap_or_else(|e| {
+            tracing::warn!(
+                code = %e.code(),
+                error = %e,
+                event_id,
+                "Failed to check video tombstone"
+            );
+            None
+        })
+}
+
+/// `410 Gone` for a deleted video, `404 Not Found` for one that never existed.
+async fn video_not_found<S>(storage: &S, event_id: &str) -> Response
+where
+    S: VideoQueries,
+{
+    match find_deletion(storage, event_id).await {
+        Some(deletion) => (
+            StatusCode::GONE,
+            [(header::CACHE_CONTROL, "no-store")],
+            Json(VideoGone::new(deletion)),
+        )
+            .into_response(),
+        None => (
+            StatusCode::NOT_FOUND,
+            [(header::CACHE_CONTROL, "no-store")],
+            Json(ErrorResponse::new(
+                ErrorCode::ApiNotFound,
+                "Video not found",
+            )),
+        )
+            .into_response(),
+    }
+}
+
 /// List videos query parameters.
 #[derive(Debug, Deserialize)]
 pub struct ListVideosQuery {
     pub sort: Option<String>,
     pub kind: Option<u16>,
     pub limit: Option<u32>,
+    pub format: Option<String>,
+    /// Return videos indexed after this cursor instead of a sorted list.
+    pub since_cursor: Option<String>,
+    /// With `since_cursor`, how long to hold the request until new videos arrive.
+    pub wait: Option<String>,
+    /// Only list videos from creators with at least this web-of-trust score.
+    pub min_trust: Option<f64>,
+}
+
+/// Videos indexed after a cursor, returned by `GET /api/videos?since_cursor=...`.
+#[derive(Debug, Serialize)]
+pub struct VideoUpdatesResponse {
+    pub videos: Vec<IndexedVideo>,
+    /// Cursor to pass as `since_cursor` on the next request.
+    pub next_cursor: String,
 }
 
 /// List videos with optional sorting.
+///
+/// With `since_cursor`, returns videos indexed after the cursor instead, optionally
+/// long-polling for new ones.
 pub async fn list_videos<S>(
     State(state): State<AppState<S>>,
     Query(params): Query<ListVideosQuery>,
+    headers: HeaderMap,
 ) -> impl IntoResponse
 where
     S: VideoQueries + StatsQueries + Clone + Send + Sync + 'static,
 {
+    if params.since_cursor.is_some() {
+        return follow_videos(state, params).await;
+    }
+
     let start = Instant::now();
     counter!(api::REQUESTS, "endpoint" => "list_videos").increment(1);
 
+    let Some(min_trust) = min_trust_filter(params.min_trust) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            [(header::CACHE_CONTROL, "no-store")],
+            Json(ErrorResponse::new(
+                ErrorCode::ApiInvalidParameter,
+                "'min_trust' must be between 0 and 1",
+            )),
+        )
+            .into_response();
+    };
     let limit = params.limit.unwrap_or(50).min(100);
     let sort = params.sort.as_deref().unwrap_or("recent");
+    let format = ResponseFormat::negotiate(&headers, params.format.as_deref());
 
     let result = match sort {
-        "popular" | "trending" => state.storage.get_trending_videos(limit).await,
+        "popular" | "trending" => state.storage.get_trending_videos(min_trust, limit).await,
         _ => state
             .storage
-            .get_recent_videos(params.kind, limit)
+            .get_recent_videos(params.kind, min_trust, limit)
             .await
             .map(|v| {
                 v.into_iter()
@@ -143,40 +533,139 @@ where
         .record(start.elapsed().as_secs_f64());
 
     match result {
-        Ok(videos) => (
-            [(header::CACHE_CONTROL, "public, max-age=60")],
-            Json(serde_json::to_value(videos).unwrap()),
-        )
-            .into_response(),
+        Ok(videos) if format == ResponseFormat::Csv => vary_on_accept(
+            params.format.as_deref(),
+            csv_response(&videos, "public, max-age=60"),
+        ),
+        Ok(videos) => vary_on_accept(
+            params.format.as_deref(),
+            (
+                [(header::CACHE_CONTROL, "public, max-age=60")],
+                Json(videos),
+            )
+                .into_response(),
+        ),
         Err(e) => {
-            tracing::error!(error = %e, "Failed to list videos");
+            tracing::error!(code = %e.code(), error = %e, "Failed to list videos");
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 [(header::CACHE_CONTROL, "no-store")],
-                Json(serde_json::json!({ "error": "Internal server error" })),
+                Json(ErrorResponse::new(e.code(), "Internal server error")),
             )
                 .into_response()
         }
     }
 }
 
-/// User videos path parameters.
-#[derive(Debug, Deserialize)]
-pub struct UserVideosPath {
-    pub pubkey: String,
+/// Storage filter for a `min_trust` parameter, or `None` if it is out of range.
+///
+/// Trust scores are in `[0, 1]`; `0` filters nothing.
+fn min_trust_filter(min_trust: Option<f64>) -> Option<Option<f64>> {
+    match min_trust {
+        Some(min) if !(0.0..=1.0).contains(&min) => None,
+        min_trust => Some(min_trust.filter(|min| *min > 0.0)),
+    }
+}
+
+/// Return videos indexed after `since_cursor`, oldest first.
+///
+/// With `wait`, an empty result holds the request until new videos are indexed or
+/// the wait runs out, for clients that can't keep a stream open. The subscription is
+/// taken before the first query, so videos indexed in between still wake it.
+async fn follow_videos<S>(state: AppState<S>, params: ListVideosQuery) -> Response
+where
+    S: VideoQueries + StatsQueries + Clone + Send + Sync + 'static,
+{
+    counter!(api::REQUESTS, "endpoint" => "follow_videos").increment(1);
+
+    let Some(cursor) = params.since_cursor.as_deref().and_then(VideoCursor::parse) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            [(header::CACHE_CONTROL, "no-store")],
+            Json(ErrorResponse::new(
+                ErrorCode::ApiInvalidParameter,
+                "Invalid since_cursor",
+            )),
+        )
+            .into_response();
+    };
+    let wait = match params.wait.as_deref().map(parse_wait) {
+        None => Duration::ZERO,
+        Some(Some(wait)) => wait,
+        Some(None) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                [(header::CACHE_CONTROL, "no-store")],
+                Json(ErrorResponse::new(
+                    ErrorCode::ApiInvalidParameter,
+                    "Invalid wait",
+                )),
+            )
+                .into_response();
+        }
+    };
+    let limit = params.limit.unwrap_or(50).min(100);
+
+    let deadline = tokio::time::Instant::now() + wait;
+    let mut updates = (!wait.is_zero()).then(|| state.updates.subscribe(state.storage.clone()));
+    let result = loop {
+        let start = Instant::now();
+        let result = state
+            .storage
+            .get_videos_indexed_after(cursor.indexed_at, &cursor.id, params.kind, limit)
+            .await;
+        histogram!(api::QUERY_DURATION, "endpoint" => "follow_videos")
+            .record(start.elapsed().as_secs_f64());
+
+        let Some(updates) = updates.as_mut() else {
+            break result;
+        };
+        if !matches!(&result, Ok(videos) if videos.is_empty()) {
+            break result;
+        }
+        match tokio::time::timeout_at(deadline, updates.changed()).await {
+            Ok(Ok(())) => continue,
+            _ => break result,
+        }
+    };
+
+    match result {
+        Ok(videos) => {
+            let next_cursor = videos.last().map_or(cursor, VideoCursor::after);
+            (
+                [(header::CACHE_CONTROL, "no-store")],
+                Json(VideoUpdatesResponse {
+                    videos,
+                    next_cursor: next_cursor.to_string(),
+                }),
+            )
+                .into_response()
+        }
+        Err(e) => {
+            tracing::error!(code = %e.code(), error = %e, "Failed to list newly indexed videos");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                [(header::CACHE_CONTROL, "no-store")],
+                Json(ErrorResponse::new(e.code(), "Internal server error")),
+            )
+                .into_response()
+        }
+    }
 }
 
 /// User videos query parameters.
 #[derive(Debug, Deserialize)]
 pub struct UserVideosQuery {
     pub limit: Option<u32>,
+    pub format: Option<String>,
 }
 
 /// Get videos by a specific user.
 pub async fn get_user_videos<S>(
     State(state): State<AppState<S>>,
-    Path(params): Path<UserVideosPath>,
+    PubkeyParam(pubkey): PubkeyParam,
     Query(query): Query<UserVideosQuery>,
+    headers: HeaderMap,
 ) -> impl IntoResponse
 where
     S: VideoQueries + StatsQueries + Clone + Send + Sync + 'static,
@@ -185,27 +674,171 @@ where
     counter!(api::REQUESTS, "endpoint" => "user_videos").increment(1);
 
     let limit = query.limit.unwrap_or(50).min(100);
+    let format = ResponseFormat::negotiate(&headers, query.format.as_deref());
 
-    match state
-        .storage
-        .get_videos_by_author(&params.pubkey, limit)
-        .await
-    {
+    match state.storage.get_videos_by_author(&pubkey, limit).await {
         Ok(videos) => {
             histogram!(api::QUERY_DURATION, "endpoint" => "user_videos")
                 .record(start.elapsed().as_secs_f64());
+            let response = if format == ResponseFormat::Csv {
+                csv_response(&videos, "public, max-age=60")
+            } else {
+                (
+                    [(header::CACHE_CONTROL, "public, max-age=60")],
+                    Json(videos),
+                )
+                    .into_response()
+            };
+            vary_on_accept(query.format.as_deref(), response)
+        }
+        Err(e) => {
+            tracing::error!(code = %e.code(), error = %e, "Failed to get user videos");
             (
-                [(header::CACHE_CONTROL, "public, max-age=60")],
-                Json(serde_json::to_value(videos).unwrap()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                [(header::CACHE_CONTROL, "no-store")],
+                Json(ErrorResponse::new(e.code(), "Internal server error")),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Playlist path parameters.
+#[derive(Debug, Deserialize)]
+pub struct PlaylistPath {
+    pub naddr: String,
+}
+
+/// Get a playlist (NIP-51 video set) by its `naddr` or `30005:<pubkey>:<d-tag>` address.
+pub async fn get_playlist<S>(
+    State(state): State<AppState<S>>,
+    Path(params): Path<PlaylistPath>,
+) -> impl IntoResponse
+where
+    S: VideoQueries + StatsQueries + Clone + Send + Sync + 'static,
+{
+    let start = Instant::now();
+    counter!(api::REQUESTS, "endpoint" => "playlist").increment(1);
+
+    let Some(address) = EventAddress::parse(&params.naddr).filter(|a| a.kind == KIND_VIDEO_SET)
+    else {
+        return (
+            StatusCode::BAD_REQUEST,
+            [(header::CACHE_CONTROL, "no-store")],
+            Json(ErrorResponse::new(
+                ErrorCode::ApiInvalidParameter,
+                "Invalid playlist address",
+            )),
+        )
+            .into_response();
+    };
+
+    let result = match state
+        .storage
+        .get_playlist(&address.pubkey, &address.identifier)
+        .await
+    {
+        Ok(Some(event)) => {
+            let set = VideoSet::from_tags(&event.tags);
+            let (event_ids, addresses) = video_refs([&set]);
+            state
+                .storage
+                .get_videos_by_refs(&event_ids, &addresses)
+                .await
+                .map(|videos| Some(Playlist::new(event, set, &VideoIndex::new(videos))))
+        }
+        Ok(None) => Ok(None),
+        Err(e) => Err(e),
+    };
+
+    histogram!(api::QUERY_DURATION, "endpoint" => "playlist").record(start.elapsed().as_secs_f64());
+
+    match result {
+        Ok(Some(playlist)) => (
+            [(header::CACHE_CONTROL, "public, max-age=60")],
+            Json(playlist),
+        )
+            .into_response(),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            [(header::CACHE_CONTROL, "no-store")],
+            Json(ErrorResponse::new(
+                ErrorCode::ApiNotFound,
+                "Playlist not found",
+            )),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!(code = %e.code(), error = %e, "Failed to get playlist");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                [(header::CACHE_CONTROL, "no-store")],
+                Json(ErrorResponse::new(e.code(), "Internal server error")),
             )
                 .into_response()
         }
+    }
+}
+
+/// User playlists query parameters.
+#[derive(Debug, Deserialize)]
+pub struct UserPlaylistsQuery {
+    pub limit: Option<u32>,
+}
+
+/// Get a user's playlists, newest first, with their videos resolved.
+pub async fn get_user_playlists<S>(
+    State(state): State<AppState<S>>,
+    PubkeyParam(pubkey): PubkeyParam,
+    Query(query): Query<UserPlaylistsQuery>,
+) -> impl IntoResponse
+where
+    S: VideoQueries + StatsQueries + Clone + Send + Sync + 'static,
+{
+    let start = Instant::now();
+    counter!(api::REQUESTS, "endpoint" => "user_playlists").increment(1);
+
+    let limit = query.limit.unwrap_or(20).min(100);
+
+    let result = match state.storage.get_playlists_by_author(&pubkey, limit).await {
+        Ok(events) => {
+            let sets: Vec<_> = events
+                .iter()
+                .map(|event| VideoSet::from_tags(&event.tags))
+                .collect();
+            // One lookup for the videos of every playlist
+            let (event_ids, addresses) = video_refs(&sets);
+            state
+                .storage
+                .get_videos_by_refs(&event_ids, &addresses)
+                .await
+                .map(|videos| {
+                    let index = VideoIndex::new(videos);
+                    events
+                        .into_iter()
+                        .zip(sets)
+                        .map(|(event, set)| Playlist::new(event, set, &index))
+                        .collect::<Vec<_>>()
+                })
+        }
+        Err(e) => Err(e),
+    };
+
+    histogram!(api::QUERY_DURATION, "endpoint" => "user_playlists")
+        .record(start.elapsed().as_secs_f64());
+
+    match result {
+        Ok(playlists) => (
+            [(header::CACHE_CONTROL, "public, max-age=60")],
+            Json(playlists),
+        )
+            .into_response(),
         Err(e) => {
-            tracing::error!(error = %e, "Failed to get user videos");
+            tracing::error!(code = %e.code(), error = %e, "Failed to get user playlists");
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 [(header::CACHE_CONTROL, "no-store")],
-                Json(serde_json::json!({ "error": "Internal server error" })),
+                Json(ErrorResponse::new(e.code(), "Internal server error")),
             )
                 .into_response()
         }
@@ -218,12 +851,16 @@ pub struct SearchQuery {
     pub tag: Option<String>,
     pub q: Option<String>,
     pub limit: Option<u32>,
+    pub format: Option<String>,
+    /// `next_cursor` from the previous page.
+    pub cursor: Option<String>,
 }
 
-/// Search videos by hashtag or text.
+/// Search videos by hashtag or text, one page at a time.
 pub async fn search_videos<S>(
     State(state): State<AppState<S>>,
     Query(params): Query<SearchQuery>,
+    headers: HeaderMap,
 ) -> impl IntoResponse
 where
     S: VideoQueries + StatsQueries + Clone + Send + Sync + 'static,
@@ -232,25 +869,64 @@ where
     counter!(api::REQUESTS, "endpoint" => "search").increment(1);
 
     let limit = params.limit.unwrap_or(50).min(100);
+    let format = ResponseFormat::negotiate(&headers, params.format.as_deref());
+    let cursor = match params.cursor.as_deref().map(SearchCursor::parse) {
+        None => None,
+        Some(Some(cursor)) => Some(cursor),
+        Some(None) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                [(header::CACHE_CONTROL, "no-store")],
+                Json(ErrorResponse::new(
+                    ErrorCode::ApiInvalidParameter,
+                    "Invalid cursor",
+                )),
+            )
+                .into_response();
+        }
+    };
+    let before = cursor.as_ref().map(|c| (c.created_at, c.id.as_str()));
 
     // Search by hashtag if provided
     if let Some(tag) = params.tag {
-        match state.storage.search_by_hashtag(&tag, limit).await {
+        let (videos, total) = tokio::join!(
+            state.storage.search_by_hashtag(&tag, before, limit),
+            state.storage.count_by_hashtag(&tag, TOTAL_ESTIMATE_CAP),
+        );
+        match videos {
             Ok(videos) => {
                 histogram!(api::QUERY_DURATION, "endpoint" => "search")
                     .record(start.elapsed().as_secs_f64());
-                return (
-                    [(header::CACHE_CONTROL, "public, max-age=60")],
-                    Json(serde_json::to_value(videos).unwrap()),
-                )
-                    .into_response();
+                if format == ResponseFormat::Csv {
+                    return vary_on_accept(
+                        params.format.as_deref(),
+                        csv_response(&videos, "public, max-age=60"),
+                    );
+                }
+                let total = total
+                    .inspect_err(|e| {
+                        tracing::warn!(
+                            code = %e.code(),
+                            error = %e,
+                            "Failed to count hashtag results"
+                        )
+                    })
+                    .ok();
+                return vary_on_accept(
+                    params.format.as_deref(),
+                    (
+                        [(header::CACHE_CONTROL, "public, max-age=60")],
+                        Json(SearchResponse::new(videos, total, limit)),
+                    )
+                        .into_response(),
+                );
             }
             Err(e) => {
-                tracing::error!(error = %e, "Failed to search by hashtag");
+                tracing::error!(code = %e.code(), error = %e, "Failed to search by hashtag");
                 return (
                     StatusCode::INTERNAL_SERVER_ERROR,
                     [(header::CACHE_CONTROL, "no-store")],
-                    Json(serde_json::json!({ "error": "Internal server error" })),
+                    Json(ErrorResponse::new(e.code(), "Internal server error")),
                 )
                     .into_response();
             }
@@ -259,22 +935,49 @@ where
 
     // Full-text search by query string
     if let Some(q) = params.q {
-        match state.storage.search_by_text(&q, limit).await {
+        let (videos, total) = tokio::join!(
+            state.storage.search_by_text(&q, before, limit),
+            state.storage.count_by_text(&q, TOTAL_ESTIMATE_CAP),
+        );
+        match videos {
             Ok(videos) => {
                 histogram!(api::QUERY_DURATION, "endpoint" => "search")
                     .record(start.elapsed().as_secs_f64());
-                return (
-                    [(header::CACHE_CONTROL, "public, max-age=60")],
-                    Json(serde_json::to_value(videos).unwrap()),
-                )
-                    .into_response();
+                if format == ResponseFormat::Csv {
+                    return vary_on_accept(
+                        params.format.as_deref(),
+                        csv_response(&videos, "public, max-age=60"),
+                    );
+                }
+                let total = total
+                    .inspect_err(|e| {
+                        tracing::warn!(
+                            code = %e.code(),
+                            error = %e,
+                            "Failed to count text results"
+                        )
+                    })
+                    .ok();
+                return vary_on_accept(
+                    params.format.as_deref(),
+                    (
+                        [(header::CACHE_CONTROL, "public, max-age=60")],
+                        Json(SearchResponse::new(videos, total, limit)),
+                    )
+                        .into_response(),
+                );
             }
             Err(e) => {
-                tracing::error!(error = %e, query = %q, "Failed to search by text");
+                tracing::error!(
+                    code = %e.code(),
+                    error = %e,
+                    query = %q,
+                    "Failed to search by text"
+                );
                 return (
                     StatusCode::INTERNAL_SERVER_ERROR,
                     [(header::CACHE_CONTROL, "no-store")],
-                    Json(serde_json::json!({ "error": "Internal server error" })),
+                    Json(ErrorResponse::new(e.code(), "Internal server error")),
                 )
                     .into_response();
             }
@@ -284,19 +987,325 @@ where
     (
         StatusCode::BAD_REQUEST,
         [(header::CACHE_CONTROL, "no-store")],
-        Json(serde_json::json!({ "error": "Search requires 'tag' or 'q' parameter" })),
+        Json(ErrorResponse::new(
+            ErrorCode::ApiInvalidParameter,
+            "Search requires 'tag' or 'q' parameter",
+        )),
     )
         .into_response()
 }
 
+/// RSS feed query parameters.
+#[derive(Debug, Deserialize)]
+pub struct RssFeedQuery {
+    pub tag: Option<String>,
+    pub pubkey: Option<String>,
+    pub limit: Option<u32>,
+    /// With `tag`, only include videos from creators with at least this
+    /// web-of-trust score.
+    pub min_trust: Option<f64>,
+}
+
+/// Render recent videos for a hashtag or creator as an RSS feed.
+pub async fn get_rss_feed<S>(
+    State(state): State<AppState<S>>,
+    Query(params): Query<RssFeedQuery>,
+) -> impl IntoResponse
+where
+    S: VideoQueries + StatsQueries + Clone + Send + Sync + 'static,
+{
+    let start = Instant::now();
+    counter!(api::REQUESTS, "endpoint" => "rss_feed").increment(1);
+
+    let Some(min_trust) = min_trust_filter(params.min_trust) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            [(header::CACHE_CONTROL, "no-store")],
+            Json(ErrorResponse::new(
+                ErrorCode::ApiInvalidParameter,
+                "'min_trust' must be between 0 and 1",
+            )),
+        )
+            .into_response();
+    };
+    let limit = params.limit.unwrap_or(50).min(100);
+
+    // Accept the same pubkey forms as the path extractors
+    let pubkey = match params.pubkey.as_deref().map(normalize_pubkey) {
+        None => None,
+        Some(Some(pubkey)) => Some(pubkey),
+        Some(None) => {
+            return (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                [(header::CACHE_CONTROL, "no-store")],
+                Json(ErrorResponse::new(
+                    ErrorCode::ApiInvalidIdentifier,
+                    "Invalid pubkey",
+                )),
+            )
+                .into_response();
+        }
+    };
+
+    let (channel, result) = match (params.tag, pubkey) {
+        (Some(tag), None) => (
+            FeedChannel {
+                title: format!("Funnel: #{}", tag),
+                description: format!("Recent Nostr videos tagged #{}", tag),
+                link: format!(
+                    "{}/api/feeds/rss?tag={}",
+                    state.public_url,
+                    encode_query_value(&tag)
+                ),
+            },
+            state
+                .storage
+                .get_video_details_by_hashtag(&tag, min_trust, limit)
+                .await,
+        ),
+        (None, Some(pubkey)) => (
+            FeedChannel {
+                title: format!("Funnel: videos by {}", pubkey),
+                description: format!("Recent Nostr videos published by {}", pubkey),
+                link: format!(
+                    "{}/api/feeds/rss?pubkey={}",
+                    state.public_url,
+                    encode_query_value(&pubkey)
+                ),
+            },
+            state
+                .storage
+                .get_video_details_by_author(&pubkey, limit)
+                .await,
+        ),
+        _ => {
+            return (
+                StatusCode::BAD_REQUEST,
+                [(header::CACHE_CONTROL, "no-store")],
+                Json(ErrorResponse::new(
+                    ErrorCode::ApiInvalidParameter,
+                    "Feed requires exactly one of 'tag' or 'pubkey'",
+                )),
+            )
+                .into_response();
+        }
+    };
+
+    histogram!(api::QUERY_DURATION, "endpoint" => "rss_feed").record(start.elapsed().as_secs_f64());
+
+    match result {
+        Ok(videos) => (
+            [
+                (header::CONTENT_TYPE, RSS_CONTENT_TYPE),
+                (header::CACHE_CONTROL, "public, max-age=300"),
+            ],
+            render_rss(&channel, &videos),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!(code = %e.code(), error = %e, "Failed to build RSS feed");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                [(header::CACHE_CONTROL, "no-store")],
+                Json(ErrorResponse::new(e.code(), "Internal server error")),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// oEmbed query parameters.
+#[derive(Debug, Deserialize)]
+pub struct OEmbedQuery {
+    pub url: String,
+    pub maxwidth: Option<u32>,
+    pub maxheight: Option<u32>,
+    pub format: Option<String>,
+}
+
+/// oEmbed endpoint for Funnel video URLs.
+pub async fn get_oembed<S>(
+    State(state): State<AppState<S>>,
+    Query(params): Query<OEmbedQuery>,
+) -> impl IntoResponse
+where
+    S: VideoQueries + StatsQueries + Clone + Send + Sync + 'static,
+{
+    let start = Instant::now();
+    counter!(api::REQUESTS, "endpoint" => "oembed").increment(1);
+
+    // oEmbed providers must answer 501 for formats they don't support
+    if params.format.as_deref().is_some_and(|f| f != "json") {
+        return (
+            StatusCode::NOT_IMPLEMENTED,
+            [(header::CACHE_CONTROL, "no-store")],
+            Json(ErrorResponse::new(
+                ErrorCode::ApiInvalidParameter,
+                "Only the json format is supported",
+            )),
+        )
+            .into_response();
+    }
+
+    let Some(event_id) = extract_event_id(&params.url) else {
+        return (
+            StatusCode::NOT_FOUND,
+            [(header::CACHE_CONTROL, "no-store")],
+            Json(ErrorResponse::new(
+                ErrorCode::ApiNotFound,
+                "URL does not reference a video",
+            )),
+        )
+            .into_response();
+    };
+
+    match state.storage.get_video_details(&event_id).await {
+        Ok(Some(video)) => {
+            histogram!(api::QUERY_DURATION, "endpoint" => "oembed")
+                .record(start.elapsed().as_secs_f64());
+            (
+                [(header::CACHE_CONTROL, "public, max-age=300")],
+                Json(OEmbedResponse::new(
+                    &video,
+                    &state.public_url,
+                    params.maxwidth,
+                    params.maxheight,
+                )),
+            )
+                .into_response()
+        }
+        Ok(None) => video_not_found(state.storage.as_ref(), &event_id).await,
+        Err(e) => {
+            tracing::error!(code = %e.code(), error = %e, "Failed to build oEmbed response");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                [(header::CACHE_CONTROL, "no-store")],
+                Json(ErrorResponse::new(e.code(), "Internal server error")),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Embeddable player page with OpenGraph metadata.
+pub async fn get_video_embed<S>(
+    State(state): State<AppState<S>>,
+    EventIdParam(id): EventIdParam,
+) -> impl IntoResponse
+where
+    S: VideoQueries + StatsQueries + Clone + Send + Sync + 'static,
+{
+    let start = Instant::now();
+    counter!(api::REQUESTS, "endpoint" => "video_embed").increment(1);
+
+    match state.storage.get_video_details(&id).await {
+        Ok(Some(video)) => {
+            histogram!(api::QUERY_DURATION, "endpoint" => "video_embed")
+                .record(start.elapsed().as_secs_f64());
+            (
+                [(header::CACHE_CONTROL, "public, max-age=300")],
+                Html(render_embed_page(&video, &state.public_url)),
+            )
+                .into_response()
+        }
+        Ok(None) => match find_deletion(state.storage.as_ref(), &id).await {
+            Some(_) => (
+                StatusCode::GONE,
+                [(header::CACHE_CONTROL, "no-store")],
+                Html("<!DOCTYPE html><title>Video deleted</title>".to_string()),
+            )
+                .into_response(),
+            None => (
+                StatusCode::NOT_FOUND,
+                [(header::CACHE_CONTROL, "no-store")],
+                Html("<!DOCTYPE html><title>Video not found</title>".to_string()),
+            )
+                .into_response(),
+        },
+        Err(e) => {
+            tracing::error!(code = %e.code(), error = %e, "Failed to render video embed");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                [(header::CACHE_CONTROL, "no-store")],
+                Html("<!DOCTYPE html><title>Internal server error</title>".to_string()),
+            )
+                .into_response()
+        }
+    }
+}
+
 /// Stats response.
 #[derive(Debug, Serialize)]
 pub struct Stats {
     pub total_events: u64,
     pub total_videos: u64,
+    /// Events inserted in the last hour.
+    pub events_last_hour: u64,
+    /// Events inserted in the last 24 hours.
+    pub events_last_day: u64,
+    /// Video events inserted since midnight UTC.
+    pub videos_today: u64,
+    /// Newest event `created_at`, absent when nothing has been ingested.
+    pub latest_event_at: Option<DateTime<Utc>>,
+    /// Seconds since `latest_event_at`. Backdated events inflate this; see
+    /// `ingest_latency` for the pipeline's own delay.
+    pub ingest_lag_seconds: Option<u64>,
+    /// Latency percentiles of events inserted in the last hour, absent without any
+    /// that carry a receive time.
+    pub ingest_latency: Option<IngestLatencyStats>,
+    /// Stored event counts per kind, largest first.
+    pub kinds: Vec<KindCount>,
+}
+
+/// Pipeline latency of recently inserted events.
+#[derive(Debug, Serialize)]
+pub struct IngestLatencyStats {
+    /// Events the percentiles cover.
+    pub events: u64,
+    /// Seconds from `created_at` until a relay received the event.
+    pub created_to_received: Percentiles,
+    /// Seconds from receipt until the event was inserted.
+    pub received_to_inserted: Percentiles,
+}
+
+impl IngestLatencyStats {
+    /// The stats, or `None` when no events were measured.
+    fn from_latency(latency: IngestLatency) -> Option<Self> {
+        if latency.events == 0 {
+            return None;
+        }
+        Some(Self {
+            events: latency.events,
+            created_to_received: Percentiles::from_quantiles(&latency.created_to_received)?,
+            received_to_inserted: Percentiles::from_quantiles(&latency.received_to_inserted)?,
+        })
+    }
+}
+
+/// Latency percentiles, in seconds.
+#[derive(Debug, Serialize)]
+pub struct Percentiles {
+    pub p50: f64,
+    pub p90: f64,
+    pub p99: f64,
+}
+
+impl Percentiles {
+    /// From ClickHouse `quantiles(0.5, 0.9, 0.99)`, or `None` if they aren't all numbers.
+    fn from_quantiles(quantiles: &[f64]) -> Option<Self> {
+        match *quantiles {
+            [p50, p90, p99] if quantiles.iter().all(|q| q.is_finite()) => {
+                Some(Self { p50, p90, p99 })
+            }
+            _ => None,
+        }
+    }
 }
 
 /// Get overall stats.
+///
+/// Each query degrades independently: a failure reports zero (or omits the field)
+/// rather than failing the whole response.
 pub async fn get_stats<S>(State(state): State<AppState<S>>) -> impl IntoResponse
 where
     S: VideoQueries + StatsQueries + Clone + Send + Sync + 'static,
@@ -304,16 +1313,141 @@ where
     let start = Instant::now();
     counter!(api::REQUESTS, "endpoint" => "stats").increment(1);
 
-    let events = state.storage.get_event_count().await.unwrap_or(0);
-    let videos = state.storage.get_video_count().await.unwrap_or(0);
+    let (events, videos, kinds, activity, latency) = tokio::join!(
+        state.storage.get_event_count(),
+        state.storage.get_video_count(),
+        state.storage.get_kind_counts(),
+        state.storage.get_ingest_activity(),
+        state.storage.get_ingest_latency(),
+    );
 
     histogram!(api::QUERY_DURATION, "endpoint" => "stats").record(start.elapsed().as_secs_f64());
 
+    let activity = activity
+        .inspect_err(
+            |e| tracing::warn!(code = %e.code(), error = %e, "Failed to get ingest activity"),
+        )
+        .ok();
+    let latest_event_at = activity
+        .as_ref()
+        .map(|a| a.latest_event_at)
+        .filter(|at| at.timestamp() > 0);
+    let ingest_lag_seconds =
+        latest_event_at.map(|at| (Utc::now() - at).num_seconds().max(0) as u64);
+    let ingest_latency = latency
+        .inspect_err(
+            |e| tracing::warn!(code = %e.code(), error = %e, "Failed to get ingest latency"),
+        )
+        .ok()
+        .and_then(IngestLatencyStats::from_latency);
+
     (
         [(header::CACHE_CONTROL, "public, max-age=60")],
         Json(Stats {
-            total_events: events,
-            total_videos: videos,
+            total_events: events.unwrap_or(0),
+            total_videos: videos.unwrap_or(0),
+            events_last_hour: activity.as_ref().map_or(0, |a| a.events_last_hour),
+            events_last_day: activity.as_ref().map_or(0, |a| a.events_last_day),
+            videos_today: activity.as_ref().map_or(0, |a| a.videos_today),
+            latest_event_at,
+            ingest_lag_seconds,
+            ingest_latency,
+            kinds: kinds.unwrap_or_default(),
         }),
     )
 }
+
+/// Longest window `GET /api/relays` sums, the retention of `relay_stats`.
+const MAX_RELAY_STATS_HOURS: u32 = 30 * 24;
+
+/// Relay stats query parameters.
+#[derive(Debug, Deserialize)]
+pub struct RelaysQuery {
+    /// Hours of counters to sum (default 24).
+    pub hours: Option<u32>,
+}
+
+/// Relay stats response.
+#[derive(Debug, Serialize)]
+pub struct RelaysResponse {
+    /// Hours of counters summed.
+    pub hours: u32,
+    /// Relays contributing the most unique videos first.
+    pub relays: Vec<RelayActivity>,
+}
+
+/// What one relay contributed to ingestion over the window.
+#[derive(Debug, Serialize)]
+pub struct RelayActivity {
+    pub relay_url: String,
+    /// Every event the relay sent, duplicates included.
+    pub events_received: u64,
+    /// Events no relay had sent before.
+    pub unique_events: u64,
+    /// Unique events that are videos.
+    pub unique_videos: u64,
+    /// Events already received from this relay or another.
+    pub duplicates: u64,
+    /// Unique events that couldn't be parsed.
+    pub parse_failures: u64,
+    /// Whether the relay was connected at the last report.
+    pub connected: bool,
+    /// Seconds the relay had been connected at the last report.
+    pub uptime_seconds: u64,
+    /// Time of the last report.
+    pub last_reported_at: DateTime<Utc>,
+}
+
+impl From<RelaySummary> for RelayActivity {
+    fn from(summary: RelaySummary) -> Self {
+        Self {
+            relay_url: summary.relay_url,
+            events_received: summary.events_received,
+            unique_events: summary.unique_events,
+            unique_videos: summary.unique_videos,
+            duplicates: summary.duplicates,
+            parse_failures: summary.parse_failures,
+            connected: summary.connected,
+            uptime_seconds: summary.uptime_secs,
+            last_reported_at: summary.last_recorded_at,
+        }
+    }
+}
+
+/// Get what each relay contributed to live ingestion over the last `hours`.
+pub async fn get_relays<S>(
+    State(state): State<AppState<S>>,
+    Query(query): Query<RelaysQuery>,
+) -> impl IntoResponse
+where
+    S: VideoQueries + StatsQueries + Clone + Send + Sync + 'static,
+{
+    let start = Instant::now();
+    counter!(api::REQUESTS, "endpoint" => "relays").increment(1);
+
+    let hours = query.hours.unwrap_or(24).clamp(1, MAX_RELAY_STATS_HOURS);
+    let since = Utc::now() - chrono::Duration::hours(hours.into());
+    let result = state.storage.get_relay_stats(since).await;
+
+    histogram!(api::QUERY_DURATION, "endpoint" => "relays").record(start.elapsed().as_secs_f64());
+
+    match result {
+        Ok(relays) => (
+            [(header::CACHE_CONTROL, "public, max-age=60")],
+            Json(RelaysResponse {
+                hours,
+                relays: relays.into_iter().map(RelayActivity::from).collect(),
+            }),
+        )
+            .into_response(),
+        Err(e) => {
+            tracing::error!(code = %e.code(), error = %e, "Failed to get relay stats");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                [(header::CACHE_CONTROL, "no-store")],
+                Json(ErrorResponse::new(e.code(), "Internal server error")),
+            )
+                .into_response()
+        }
+    }
+}
@@ -2,13 +2,38 @@
 //!
 //! Provides handlers and router configuration for the video analytics API.
 
+pub mod admin;
+pub mod audit;
 pub mod auth;
+pub mod cache;
+pub mod circuit;
+pub mod conditional;
+pub mod config;
+pub mod cors;
+pub mod dashboard;
+pub mod embed;
+pub mod feed;
+pub mod format;
+pub mod graphql;
 pub mod handlers;
+pub mod jwt;
+pub mod limits;
+pub mod params;
+pub mod playlist;
+pub mod probes;
+pub mod prometheus;
 pub mod router;
+pub mod search;
+pub mod server;
+pub mod shutdown;
+pub mod tenant;
+pub mod updates;
+pub mod usage;
 
 #[cfg(test)]
 mod tests;
 
 pub use self::auth::AuthConfig;
+pub use self::config::ApiConfig;
 pub use self::handlers::*;
 pub use self::router::create_router;
@@ -0,0 +1,266 @@
+//! Per-route-group request limits.
+//!
+//! Each route group gets its own [`RouteLimiter`], which enforces:
+//! - a request timeout (`408`), longer for CSV/RSS exports and extended by the
+//!   requested wait for long polls,
+//! - a request body size limit (`413`),
+//! - a cap on concurrent in-flight requests (`503` with `Retry-After`).
+//!
+//! The timeout runs inside the circuit breaker so slow queries count as storage
+//! failures; the body and concurrency checks run outside it so shedding load
+//! never trips the breaker.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    Json,
+    extract::{Request, State},
+    http::{HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use funnel_observability::api;
+use funnel_proto::ErrorCode;
+use metrics::counter;
+use tokio::sync::Semaphore;
+
+use crate::format::ResponseFormat;
+use crate::updates::parse_wait;
+
+/// Default timeout for regular requests.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Default timeout for CSV and RSS exports.
+pub const DEFAULT_EXPORT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Default request body limit (64 KiB); the API only accepts small JSON bodies.
+pub const DEFAULT_MAX_BODY_BYTES: usize = 64 * 1024;
+
+/// Default cap on concurrent requests per route group.
+pub const DEFAULT_MAX_CONCURRENT: usize = 256;
+
+/// Limits applied to a route group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RequestLimits {
+    pub timeout: Duration,
+    pub export_timeout: Duration,
+    pub max_body_bytes: usize,
+    pub max_concurrent: usize,
+}
+
+impl Default for RequestLimits {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_TIMEOUT,
+            export_timeout: DEFAULT_EXPORT_TIMEOUT,
+            max_body_bytes: DEFAULT_MAX_BODY_BYTES,
+            max_concurrent: DEFAULT_MAX_CONCURRENT,
+        }
+    }
+}
+
+impl RequestLimits {
+    /// Load from `REQUEST_TIMEOUT_SECS`, `EXPORT_TIMEOUT_SECS`, `MAX_BODY_BYTES`,
+    /// and `MAX_CONCURRENT_REQUESTS`.
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            timeout: env_parse("REQUEST_TIMEOUT_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.timeout),
+            export_timeout: env_parse("EXPORT_TIMEOUT_SECS")
+                .map(Duration::from_secs)
+                .unwrap_or(defaults.export_timeout),
+            max_body_bytes: env_parse("MAX_BODY_BYTES").unwrap_or(defaults.max_body_bytes),
+            max_concurrent: env_parse("MAX_CONCURRENT_REQUESTS").unwrap_or(defaults.max_concurrent),
+        }
+    }
+}
+
+/// Limits plus the concurrency permits for one route group.
+#[derive(Debug, Clone)]
+pub struct RouteLimiter {
+    group: &'static str,
+    limits: RequestLimits,
+    permits: Arc<Semaphore>,
+}
+
+impl RouteLimiter {
+    pub fn new(group: &'static str, limits: RequestLimits) -> Self {
+        Self {
+            group,
+            limits,
+            permits: Arc::new(Semaphore::new(limits.max_concurrent)),
+        }
+    }
+
+    /// Configured limits.
+    pub fn limits(&self) -> RequestLimits {
+        self.limits
+    }
+}
+
+/// Middleware enforcing the body size limit and concurrency cap.
+pub async fn request_limits(
+    State(limiter): State<RouteLimiter>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let content_length = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<usize>().ok());
+    if content_length.is_some_and(|len| len > limiter.limits.max_body_bytes) {
+        counter!(api::REQUESTS_REJECTED, "group" => limiter.group, "reason" => "body_too_large")
+            .increment(1);
+        return error_response(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            ErrorCode::ApiPayloadTooLarge,
+            "Request body too large",
+        );
+    }
+
+    let Ok(_permit) = limiter.permits.clone().try_acquire_owned() else {
+        counter!(api::REQUESTS_REJECTED, "group" => limiter.group, "reason" => "concurrency")
+            .increment(1);
+        let mut response = error_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            ErrorCode::ApiUnavailable,
+            "Too many concurrent requests",
+        );
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, HeaderValue::from_static("1"));
+        return response;
+    };
+
+    next.run(request).await
+}
+
+/// Middleware enforcing the request timeout.
+pub async fn request_timeout(
+    State(limiter): State<RouteLimiter>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let timeout = if is_export(&request) {
+        limiter.limits.export_timeout
+    } else {
+        limiter.limits.timeout + long_poll_wait(&request)
+    };
+
+    match tokio::time::timeout(timeout, next.run(request)).await {
+        Ok(response) => response,
+        Err(_) => {
+            counter!(api::REQUEST_TIMEOUTS, "group" => limiter.group).increment(1);
+            tracing::warn!(
+                group = limiter.group,
+                timeout_secs = timeout.as_secs_f64(),
+                "Request timed out"
+            );
+            error_response(
+                StatusCode::REQUEST_TIMEOUT,
+                ErrorCode::ApiTimeout,
+                "Request timed out",
+            )
+        }
+    }
+}
+
+/// Whether a request asks for a CSV or RSS export.
+fn is_export(request: &Request) -> bool {
+    if request.uri().path().starts_with("/api/feeds/") {
+        return true;
+    }
+
+    let format = request.uri().query().and_then(|query| {
+        query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("format="))
+    });
+    ResponseFormat::negotiate(request.headers(), format) == ResponseFormat::Csv
+}
+
+/// Time a long-poll request on `/api/videos` may spend waiting for new videos.
+///
+/// Zero for everything else, including invalid `wait` values the handler rejects.
+fn long_poll_wait(request: &Request) -> Duration {
+    if request.uri().path() != "/api/videos" {
+        return Duration::ZERO;
+    }
+
+    let query = request.uri().query().unwrap_or_default();
+    if !query
+        .split('&')
+        .any(|pair| pair.starts_with("since_cursor="))
+    {
+        return Duration::ZERO;
+    }
+    query
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("wait="))
+        .and_then(parse_wait)
+        .unwrap_or_default()
+}
+
+fn error_response(status: StatusCode, code: ErrorCode, message: &str) -> Response {
+    (
+        status,
+        [(header::CACHE_CONTROL, "no-store")],
+        Json(serde_json::json!({ "error": message, "code": code })),
+    )
+        .into_response()
+}
+
+fn env_parse<T>(name: &str) -> Option<T>
+where
+    T: std::str::FromStr,
+{
+    std::env::var(name).ok().and_then(|s| s.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::Body;
+
+    use super::*;
+
+    fn request(uri: &str) -> Request {
+        Request::builder().uri(uri).body(Body::empty()).unwrap()
+    }
+
+    #[test]
+    fn is_export_detects_csv_and_feeds() {
+        assert!(is_export(&request("/api/videos?sort=recent&format=csv")));
+        assert!(is_export(&request("/api/feeds/rss?tag=nostr")));
+        assert!(!is_export(&request("/api/videos?format=json")));
+        assert!(!is_export(&request("/api/videos")));
+    }
+
+    #[test]
+    fn is_export_detects_accept_header() {
+        let mut request = request("/api/search?tag=nostr");
+        request
+            .headers_mut()
+            .insert(header::ACCEPT, HeaderValue::from_static("text/csv"));
+        assert!(is_export(&request));
+    }
+
+    #[test]
+    fn long_poll_wait_extends_only_following_requests() {
+        assert_eq!(
+            long_poll_wait(&request("/api/videos?since_cursor=1700000000&wait=20s")),
+            Duration::from_secs(20)
+        );
+        assert_eq!(
+            long_poll_wait(&request("/api/videos?wait=20s")),
+            Duration::ZERO
+        );
+        assert_eq!(
+            long_poll_wait(&request("/api/search?since_cursor=1&wait=20s")),
+            Duration::ZERO
+        );
+    }
+}
@@ -0,0 +1,66 @@
+//! Path parameter extractors for event IDs and pubkeys.
+//!
+//! Both accept 64-character hex in any case, NIP-19 bech32 (`note`/`nevent` for
+//! events, `npub`/`nprofile` for pubkeys), or a `nostr:` URI, and hand handlers
+//! lowercase hex. Anything else is rejected with `422` before storage is queried.
+//!
+//! Each extractor reads the route's only path parameter.
+
+use axum::{
+    Json,
+    extract::{FromRequestParts, Path},
+    http::{StatusCode, header, request::Parts},
+    response::{IntoResponse, Response},
+};
+use funnel_proto::{ErrorCode, normalize_event_id, normalize_pubkey};
+
+use crate::handlers::ErrorResponse;
+
+/// An event ID path parameter, normalized to lowercase hex.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EventIdParam(pub String);
+
+/// A pubkey path parameter, normalized to lowercase hex.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PubkeyParam(pub String);
+
+impl<S> FromRequestParts<S> for EventIdParam
+where
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Path(value) = Path::<String>::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        normalize_event_id(&value)
+            .map(Self)
+            .ok_or_else(|| invalid("Invalid event ID"))
+    }
+}
+
+impl<S> FromRequestParts<S> for PubkeyParam
+where
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Path(value) = Path::<String>::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        normalize_pubkey(&value)
+            .map(Self)
+            .ok_or_else(|| invalid("Invalid pubkey"))
+    }
+}
+
+fn invalid(message: &'static str) -> Response {
+    (
+        StatusCode::UNPROCESSABLE_ENTITY,
+        [(header::CACHE_CONTROL, "no-store")],
+        Json(ErrorResponse::new(ErrorCode::ApiInvalidIdentifier, message)),
+    )
+        .into_response()
+}
@@ -0,0 +1,210 @@
+//! Playlist responses built from NIP-51 video sets (kind 30005).
+//!
+//! A video set lists videos by event ID (`e` tags) or by address (`a` tags, resolving
+//! to the latest version). Entries are looked up in one batched query and returned in
+//! list order with their stats; entries that aren't indexed are counted as missing.
+
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use funnel_clickhouse::{PlaylistEvent, VideoStats};
+use funnel_proto::{EventAddress, KIND_VIDEO_SET, VideoRef, VideoSet};
+use serde::Serialize;
+
+/// A playlist with its videos resolved.
+#[derive(Debug, Clone, Serialize)]
+pub struct Playlist {
+    pub id: String,
+    pub pubkey: String,
+    pub d_tag: String,
+    /// Address of this playlist, as accepted by `GET /api/playlists/{naddr}`.
+    pub naddr: Option<String>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image: Option<String>,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub created_at: DateTime<Utc>,
+    /// Listed videos in playlist order.
+    pub videos: Vec<VideoStats>,
+    /// Entries whose video isn't indexed or has been deleted.
+    pub missing: usize,
+}
+
+impl Playlist {
+    /// Build a playlist from its event, resolving entries against `videos`.
+    ///
+    /// A video listed more than once appears only at its first position.
+    pub fn new(event: PlaylistEvent, set: VideoSet, videos: &VideoIndex) -> Self {
+        let mut seen = HashSet::new();
+        let mut resolved = Vec::new();
+        let mut missing = 0;
+        for entry in &set.videos {
+            match videos.get(entry) {
+                Some(video) if seen.insert(video.id.clone()) => resolved.push(video.clone()),
+                Some(_) => {}
+                None => missing += 1,
+            }
+        }
+
+        let naddr = EventAddress {
+            kind: KIND_VIDEO_SET,
+            pubkey: event.pubkey.clone(),
+            identifier: event.d_tag.clone(),
+        }
+        .to_naddr();
+
+        Self {
+            id: event.id,
+            pubkey: event.pubkey,
+            d_tag: event.d_tag,
+            naddr,
+            title: set.title,
+            description: set.description,
+            image: set.image,
+            created_at: event.created_at,
+            videos: resolved,
+            missing,
+        }
+    }
+}
+
+/// Event IDs and `kind:pubkey:d-tag` addresses listed across `sets`, deduplicated,
+/// for a single lookup.
+pub fn video_refs<'a>(sets: impl IntoIterator<Item = &'a VideoSet>) -> (Vec<String>, Vec<String>) {
+    let mut event_ids = HashSet::new();
+    let mut addresses = HashSet::new();
+    for entry in sets.into_iter().flat_map(|set| &set.videos) {
+        match entry {
+            VideoRef::Event(id) => event_ids.insert(id.clone()),
+            VideoRef::Address(address) => addresses.insert(address.to_string()),
+        };
+    }
+    (
+        event_ids.into_iter().collect(),
+        addresses.into_iter().collect(),
+    )
+}
+
+/// Looked-up videos, indexed by event ID and by address.
+#[derive(Debug, Default)]
+pub struct VideoIndex {
+    by_id: HashMap<String, VideoStats>,
+    /// Latest version of each addressed video.
+    by_address: HashMap<String, VideoStats>,
+}
+
+impl VideoIndex {
+    pub fn new(videos: Vec<VideoStats>) -> Self {
+        let mut index = Self::default();
+        for video in videos {
+            let address = format!("{}:{}:{}", video.kind, video.pubkey, video.d_tag);
+            let newer = index
+                .by_address
+                .get(&address)
+                .is_none_or(|current| video.created_at > current.created_at);
+            if newer {
+                index.by_address.insert(address, video.clone());
+            }
+            index.by_id.insert(video.id.clone(), video);
+        }
+        index
+    }
+
+    fn get(&self, entry: &VideoRef) -> Option<&VideoStats> {
+        match entry {
+            VideoRef::Event(id) => self.by_id.get(id),
+            VideoRef::Address(address) => self.by_address.get(&address.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    const PUBKEY: &str = "6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93";
+
+    fn video(id: &str, d_tag: &str, created_at: i64) -> VideoStats {
+        VideoStats {
+            id: id.to_string(),
+            pubkey: PUBKEY.to_string(),
+            created_at: Utc.timestamp_opt(created_at, 0).unwrap(),
+            kind: 34235,
+            d_tag: d_tag.to_string(),
+            title: format!("Video {id}"),
+            thumbnail: String::new(),
+            reactions: 0,
+            comments: 0,
+            reposts: 0,
+            engagement_score: 0,
+        }
+    }
+
+    fn address(d_tag: &str) -> VideoRef {
+        VideoRef::Address(EventAddress {
+            kind: 34235,
+            pubkey: PUBKEY.to_string(),
+            identifier: d_tag.to_string(),
+        })
+    }
+
+    fn event() -> PlaylistEvent {
+        PlaylistEvent {
+            id: "playlist-1".to_string(),
+            pubkey: PUBKEY.to_string(),
+            created_at: Utc.timestamp_opt(1_700_000_000, 0).unwrap(),
+            d_tag: "faves".to_string(),
+            tags: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn playlist_keeps_list_order_and_counts_missing() {
+        let set = VideoSet {
+            title: Some("Favourites".to_string()),
+            description: None,
+            image: None,
+            videos: vec![
+                VideoRef::Event("v2".to_string()),
+                address("first"),
+                VideoRef::Event("gone".to_string()),
+                // Duplicate of the addressed video
+                VideoRef::Event("v1".to_string()),
+            ],
+        };
+        let index = VideoIndex::new(vec![video("v1", "first", 100), video("v2", "second", 200)]);
+
+        let playlist = Playlist::new(event(), set, &index);
+        let ids: Vec<_> = playlist.videos.iter().map(|v| v.id.as_str()).collect();
+        assert_eq!(ids, ["v2", "v1"]);
+        assert_eq!(playlist.missing, 1);
+        assert!(playlist.naddr.unwrap().starts_with("naddr1"));
+    }
+
+    #[test]
+    fn addressed_entries_resolve_to_latest_version() {
+        let index = VideoIndex::new(vec![video("old", "clip", 100), video("new", "clip", 200)]);
+
+        assert_eq!(index.get(&address("clip")).unwrap().id, "new");
+        assert_eq!(
+            index.get(&VideoRef::Event("old".to_string())).unwrap().id,
+            "old"
+        );
+    }
+
+    #[test]
+    fn video_refs_deduplicates_across_sets() {
+        let set = VideoSet {
+            title: None,
+            description: None,
+            image: None,
+            videos: vec![VideoRef::Event("v1".to_string()), address("clip")],
+        };
+
+        let (ids, addresses) = video_refs([&set, &set]);
+        assert_eq!(ids, ["v1"]);
+        assert_eq!(addresses, [format!("34235:{PUBKEY}:clip")]);
+    }
+}
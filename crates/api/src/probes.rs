@@ -0,0 +1,208 @@
+//! Kubernetes-style liveness and readiness probes.
+//!
+//! `/livez` only reports that the process is serving requests. `/readyz` checks
+//! ClickHouse connectivity, that the schema is applied, and (optionally) that
+//! replication lag is within bounds, returning 503 when any check fails so the
+//! pod is taken out of rotation. When the server runs a self-check
+//! [`Heartbeat`], `/readyz` also reports its latest results and fails while it is
+//! unhealthy.
+
+use std::future::Future;
+use std::time::Duration;
+
+use axum::{
+    Json,
+    extract::State,
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use funnel_clickhouse::{ClickHouseError, HealthQueries, StatsQueries, VideoQueries};
+use funnel_observability::heartbeat::{CheckFailure, HealthReport, Heartbeat};
+use serde::Serialize;
+
+use crate::handlers::{AppState, HealthResponse};
+
+/// Upper bound on each dependency check, so a hung database fails the probe
+/// instead of stalling it.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Outcome of a single readiness check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Ok,
+    Failed,
+    Skipped,
+}
+
+/// Per-dependency readiness results.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReadinessChecks {
+    pub clickhouse: CheckStatus,
+    pub schema: CheckStatus,
+    pub replication_lag: CheckStatus,
+    pub heartbeat: CheckStatus,
+}
+
+/// Body returned by `/readyz`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReadinessResponse {
+    pub status: &'static str,
+    pub checks: ReadinessChecks,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replication_lag_seconds: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub heartbeat: Option<HealthReport>,
+}
+
+impl ReadinessResponse {
+    fn is_ready(&self) -> bool {
+        [
+            self.checks.clickhouse,
+            self.checks.schema,
+            self.checks.replication_lag,
+            self.checks.heartbeat,
+        ]
+        .iter()
+        .all(|status| *status != CheckStatus::Failed)
+    }
+}
+
+/// Liveness probe: the process is up and serving requests.
+pub async fn livez() -> impl IntoResponse {
+    (
+        [(header::CACHE_CONTROL, "no-store")],
+        Json(HealthResponse { status: "ok" }),
+    )
+}
+
+/// Readiness probe: dependencies are reachable and healthy.
+pub async fn readyz<S>(State(state): State<AppState<S>>) -> Response
+where
+    S: VideoQueries + StatsQueries + HealthQueries + Clone + Send + Sync + 'static,
+{
+    let mut response = ReadinessResponse {
+        status: "ready",
+        checks: ReadinessChecks {
+            clickhouse: CheckStatus::Failed,
+            schema: CheckStatus::Skipped,
+            replication_lag: CheckStatus::Skipped,
+            heartbeat: CheckStatus::Skipped,
+        },
+        replication_lag_seconds: None,
+        heartbeat: state.heartbeat.as_ref().and_then(Heartbeat::report),
+    };
+
+    if let Some(report) = &response.heartbeat {
+        response.checks.heartbeat = if report.healthy {
+            CheckStatus::Ok
+        } else {
+            CheckStatus::Failed
+        };
+    }
+
+    if run_check("clickhouse", state.storage.ping())
+        .await
+        .is_some()
+    {
+        response.checks.clickhouse = CheckStatus::Ok;
+
+        response.checks.schema = match run_check("schema", state.storage.check_schema()).await {
+            Some(true) => CheckStatus::Ok,
+            Some(false) => {
+                tracing::warn!("Readiness check failed: schema not applied");
+                CheckStatus::Failed
+            }
+            None => CheckStatus::Failed,
+        };
+
+        if let Some(max_lag) = state.max_replication_lag {
+            let lag = run_check("replication_lag", state.storage.get_replication_lag()).await;
+            response.replication_lag_seconds = lag;
+            response.checks.replication_lag = match lag {
+                Some(lag) if lag <= max_lag => CheckStatus::Ok,
+                Some(lag) => {
+                    tracing::warn!(lag, max_lag, "Readiness check failed: replication lag");
+                    CheckStatus::Failed
+                }
+                None => CheckStatus::Failed,
+            };
+        }
+    }
+
+    let status = if response.is_ready() {
+        StatusCode::OK
+    } else {
+        response.status = "not_ready";
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (
+        status,
+        [(header::CACHE_CONTROL, "no-store")],
+        Json(response),
+    )
+        .into_response()
+}
+
+/// Self-checks for the API's [`Heartbeat`]: ClickHouse is reachable, the schema is
+/// applied, and replication lag is within `max_replication_lag` when set.
+pub async fn self_check<S>(storage: &S, max_replication_lag: Option<u64>) -> Vec<CheckFailure>
+where
+    S: HealthQueries,
+{
+    let mut failures = Vec::new();
+
+    if let Err(reason) = check_within_timeout(storage.ping()).await {
+        failures.push(CheckFailure::new("clickhouse_reachable", reason));
+        return failures;
+    }
+
+    match check_within_timeout(storage.check_schema()).await {
+        Ok(true) => {}
+        Ok(false) => failures.push(CheckFailure::new("schema_applied", "schema not applied")),
+        Err(reason) => failures.push(CheckFailure::new("schema_applied", reason)),
+    }
+
+    if let Some(max_lag) = max_replication_lag {
+        match check_within_timeout(storage.get_replication_lag()).await {
+            Ok(lag) if lag <= max_lag => {}
+            Ok(lag) => failures.push(CheckFailure::new(
+                "replication_lag",
+                format!("{lag}s behind, limit {max_lag}s"),
+            )),
+            Err(reason) => failures.push(CheckFailure::new("replication_lag", reason)),
+        }
+    }
+
+    failures
+}
+
+/// Run a check with [`CHECK_TIMEOUT`], describing any failure.
+async fn check_within_timeout<T, F>(check: F) -> Result<T, String>
+where
+    F: Future<Output = Result<T, ClickHouseError>>,
+{
+    match tokio::time::timeout(CHECK_TIMEOUT, check).await {
+        Ok(result) => result.map_err(|e| e.to_string()),
+        Err(_) => Err("timed out".to_string()),
+    }
+}
+
+/// Run a check with [`CHECK_TIMEOUT`], logging and discarding any error.
+async fn run_check<T, F>(name: &str, check: F) -> Option<T>
+where
+    F: Future<Output = Result<T, ClickHouseError>>,
+{
+    match tokio::time::timeout(CHECK_TIMEOUT, check).await {
+        Ok(Ok(value)) => Some(value),
+        Ok(Err(e)) => {
+            tracing::warn!(check = name, error = %e, "Readiness check failed");
+            None
+        }
+        Err(_) => {
+            tracing::warn!(check = name, "Readiness check timed out");
+            None
+        }
+    }
+}
@@ -0,0 +1,73 @@
+//! Prometheus scrape endpoint.
+//!
+//! `/metrics` is public on the API port by default. It can require a token
+//! (a dedicated `METRICS_TOKEN`, or the regular `API_TOKEN`), and it can move to a
+//! separate internal listener with `METRICS_BIND_ADDR` so it is never exposed on the
+//! public port at all.
+
+use axum::{Extension, Router, http::header, middleware, routing::get};
+use metrics_exporter_prometheus::PrometheusHandle;
+
+use crate::auth::{AuthConfig, require_auth};
+
+/// Access settings for `/metrics`.
+#[derive(Clone, Default)]
+pub struct MetricsConfig {
+    /// Token required to scrape. `None` leaves `/metrics` public.
+    pub auth: Option<AuthConfig>,
+    /// Serve `/metrics` on this address instead of the API listener.
+    pub bind_addr: Option<String>,
+}
+
+impl MetricsConfig {
+    /// Load from `METRICS_TOKEN`/`METRICS_TOKEN_FILE`, `METRICS_REQUIRE_API_TOKEN`, and
+    /// `METRICS_BIND_ADDR`.
+    ///
+    /// A dedicated metrics token wins; otherwise `METRICS_REQUIRE_API_TOKEN=true`
+    /// reuses `api_auth`.
+    pub fn from_env(api_auth: Option<&AuthConfig>) -> Self {
+        let require_api_token = std::env::var("METRICS_REQUIRE_API_TOKEN")
+            .ok()
+            .and_then(|s| s.parse::<bool>().ok())
+            .unwrap_or(false);
+        let auth = AuthConfig::from_env_var("METRICS_TOKEN")
+            .or_else(|| api_auth.filter(|_| require_api_token).cloned());
+
+        Self {
+            auth,
+            bind_addr: std::env::var("METRICS_BIND_ADDR")
+                .ok()
+                .filter(|s| !s.is_empty()),
+        }
+    }
+
+    /// Require a token to scrape.
+    pub fn with_auth(mut self, auth: AuthConfig) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+
+    /// Serve `/metrics` on a separate listener.
+    pub fn with_bind_addr(mut self, bind_addr: impl Into<String>) -> Self {
+        self.bind_addr = Some(bind_addr.into());
+        self
+    }
+}
+
+/// Router serving `/metrics`, behind `auth` when given.
+pub fn metrics_router<T>(handle: PrometheusHandle, auth: Option<AuthConfig>) -> Router<T>
+where
+    T: Clone + Send + Sync + 'static,
+{
+    let routes = Router::new().route(
+        "/metrics",
+        get(move || async move { ([(header::CACHE_CONTROL, "no-store")], handle.render()) }),
+    );
+
+    match auth {
+        Some(auth) => routes
+            .layer(middleware::from_fn(require_auth))
+            .layer(Extension(auth)),
+        None => routes,
+    }
+}
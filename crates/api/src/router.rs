@@ -1,88 +1,266 @@
 //! Router configuration for the API.
 
-use axum::{Extension, Router, http::header, middleware, routing::get};
-use funnel_clickhouse::{StatsQueries, VideoQueries};
+use std::sync::Arc;
+
+use axum::{
+    Extension, Router,
+    body::Body,
+    extract::DefaultBodyLimit,
+    middleware,
+    routing::{get, post},
+};
+use funnel_clickhouse::{AdminQueries, HealthQueries, StatsQueries, VideoQueries};
+use funnel_observability::http_request_span;
 use metrics_exporter_prometheus::PrometheusHandle;
-use tower_http::cors::CorsLayer;
 use tower_http::trace::TraceLayer;
 
+use crate::admin::{
+    get_checkpoints, get_log_level, get_reports, hide_video, purge_cache, reload_config,
+    set_log_level, tombstone_event, trigger_backfill, unhide_video,
+};
+use crate::audit::audit_log;
 use crate::auth::{AuthConfig, require_auth};
+use crate::cache::{ResponseCache, response_cache};
+use crate::circuit::{CircuitBreaker, circuit_breaker};
+use crate::conditional::conditional_get;
+use crate::config::ApiConfig;
+use crate::dashboard::{
+    RecentErrors, basic_challenge, dashboard, get_recent_errors, record_errors,
+};
+use crate::graphql::{build_schema, graphql_handler};
 use crate::handlers::{
-    AppState, get_stats, get_user_videos, get_video_stats, health, list_videos, search_videos,
+    AppState, get_oembed, get_playlist, get_relays, get_rss_feed, get_stats, get_user_playlists,
+    get_user_videos, get_video_duplicates, get_video_embed, get_video_media, get_video_stats,
+    health, list_videos, search_videos,
 };
+use crate::limits::{RequestLimits, RouteLimiter, request_limits, request_timeout};
+use crate::probes::{livez, readyz};
+use crate::prometheus::metrics_router;
+use crate::usage::{UsageTracker, get_usage, track_usage};
 
 /// Create the API router with the given storage backend and metrics handle.
 ///
-/// If `auth_config` is `Some`, bearer token authentication will be required for
-/// all `/api/*` endpoints. The `/health` and `/metrics` endpoints remain public
-/// for monitoring purposes.
+/// If `config.auth` is `Some`, bearer token authentication will be required for
+/// all `/api/*` endpoints. The health probes stay public for monitoring, and the
+/// oEmbed/embed endpoints stay public so link preview crawlers and iframes can load
+/// them. `/metrics` follows `config.metrics`: public by default, optionally behind a
+/// token, and left out entirely when it has its own listener (see
+/// [`metrics_router`]). The `/admin/*` endpoints are only mounted when
+/// `config.admin_auth` is `Some`.
 pub fn create_router<S>(
     state: AppState<S>,
     metrics_handle: PrometheusHandle,
-    auth_config: Option<AuthConfig>,
+    config: ApiConfig,
 ) -> Router
 where
-    S: VideoQueries + StatsQueries + Clone + Send + Sync + 'static,
+    S: VideoQueries + StatsQueries + AdminQueries + HealthQueries + Clone + Send + Sync + 'static,
 {
-    // Public routes (no auth required)
-    let public_routes = Router::new().route("/health", get(health)).route(
-        "/metrics",
-        get(move || async move {
-            (
-                [(header::CACHE_CONTROL, "no-store")],
-                metrics_handle.render(),
-            )
-        }),
-    );
-
-    // Protected API routes
-    let api_routes = Router::new()
-        .route("/api/videos/{id}/stats", get(get_video_stats::<S>))
-        .route("/api/videos", get(list_videos::<S>))
-        .route("/api/users/{pubkey}/videos", get(get_user_videos::<S>))
-        .route("/api/search", get(search_videos::<S>))
-        .route("/api/stats", get(get_stats::<S>));
-
-    // Apply auth middleware only if auth is configured
-    let api_routes = if let Some(config) = auth_config {
-        api_routes
-            .layer(middleware::from_fn(require_auth))
-            .layer(Extension(config))
-    } else {
-        api_routes
+    let cors = config.cors.layer();
+    let metrics_routes = match config.metrics.bind_addr {
+        Some(_) => Router::new(),
+        None => metrics_router(metrics_handle, config.metrics.auth.clone()),
     };
 
-    public_routes
-        .merge(api_routes)
-        .layer(TraceLayer::new_for_http())
-        .layer(CorsLayer::permissive())
+    metrics_routes
+        .merge(app_routes(config))
+        .layer(TraceLayer::new_for_http().make_span_with(http_request_span::<Body>))
+        .layer(cors)
         .with_state(state)
 }
 
-/// Create a router for testing without metrics endpoint.
+/// Create a router for testing without metrics endpoint or tracing.
 ///
-/// If `auth_config` is `Some`, authentication will be required for API routes.
+/// Auth, admin routes, and CORS follow `config` as in [`create_router`].
 #[cfg(test)]
-pub fn create_test_router<S>(state: AppState<S>, auth_config: Option<AuthConfig>) -> Router
+pub fn create_test_router<S>(state: AppState<S>, config: ApiConfig) -> Router
+where
+    S: VideoQueries + StatsQueries + AdminQueries + HealthQueries + Clone + Send + Sync + 'static,
+{
+    let cors = config.cors.layer();
+
+    app_routes(config).layer(cors).with_state(state)
+}
+
+/// All routes except `/metrics`.
+///
+/// Embed and API routes share one response cache, since they hit the same database,
+/// but each route group has its own circuit breaker and request limits, so anonymous
+/// embed traffic can't open the breaker of the authenticated API. Their failed
+/// requests are recorded for the admin dashboard.
+fn app_routes<S>(config: ApiConfig) -> Router<AppState<S>>
+where
+    S: VideoQueries + StatsQueries + AdminQueries + HealthQueries + Clone + Send + Sync + 'static,
+{
+    let embed_breaker = Arc::new(CircuitBreaker::new("embed", config.circuit_breaker));
+    let api_breaker = Arc::new(CircuitBreaker::new("api", config.circuit_breaker));
+    let cache = Arc::new(ResponseCache::new(config.cache.clone()));
+    let errors = Arc::new(RecentErrors::default());
+    let usage = Arc::new(UsageTracker::new(config.usage));
+
+    let public_routes = embed_routes(embed_breaker, cache.clone(), config.limits)
+        .merge(protected_routes(
+            config.auth.clone(),
+            api_breaker,
+            cache.clone(),
+            usage,
+            config.limits,
+        ))
+        .layer(middleware::from_fn_with_state(
+            errors.clone(),
+            record_errors,
+        ));
+
+    probe_routes()
+        .merge(public_routes)
+        .merge(admin_routes(config, cache, errors))
+}
+
+/// Apply a route group's request limits.
+///
+/// The timeout sits inside the circuit breaker (when given) so slow queries count as
+/// failures; the body and concurrency checks sit outside it.
+fn with_limits<S>(
+    routes: Router<AppState<S>>,
+    limiter: RouteLimiter,
+    breaker: Option<Arc<CircuitBreaker>>,
+) -> Router<AppState<S>>
+where
+    S: VideoQueries + StatsQueries + Clone + Send + Sync + 'static,
+{
+    let max_body_bytes = limiter.limits().max_body_bytes;
+    let routes = routes.layer(middleware::from_fn_with_state(
+        limiter.clone(),
+        request_timeout,
+    ));
+    let routes = match breaker {
+        Some(breaker) => routes.layer(middleware::from_fn_with_state(breaker, circuit_breaker)),
+        None => routes,
+    };
+
+    routes
+        .layer(middleware::from_fn_with_state(limiter, request_limits))
+        .layer(DefaultBodyLimit::max(max_body_bytes))
+}
+
+/// Health and probe endpoints, which never require auth.
+fn probe_routes<S>() -> Router<AppState<S>>
+where
+    S: VideoQueries + StatsQueries + HealthQueries + Clone + Send + Sync + 'static,
+{
+    Router::new()
+        .route("/health", get(health))
+        .route("/livez", get(livez))
+        .route("/readyz", get(readyz::<S>))
+}
+
+/// Embed endpoints, which never require auth.
+fn embed_routes<S>(
+    breaker: Arc<CircuitBreaker>,
+    cache: Arc<ResponseCache>,
+    limits: RequestLimits,
+) -> Router<AppState<S>>
 where
     S: VideoQueries + StatsQueries + Clone + Send + Sync + 'static,
 {
-    let public_routes = Router::new().route("/health", get(health));
+    let routes = Router::new()
+        .route("/api/oembed", get(get_oembed::<S>))
+        .route("/api/videos/{id}/embed", get(get_video_embed::<S>));
 
+    with_limits(routes, RouteLimiter::new("embed", limits), Some(breaker))
+        .layer(middleware::from_fn_with_state(cache, response_cache))
+        .layer(middleware::from_fn(conditional_get))
+}
+
+/// API routes, wrapped in the auth middleware when auth is configured.
+///
+/// Auth runs before the circuit breaker, so unauthenticated requests get 401 rather
+/// than 503 and never count as storage failures. The response cache sits between
+/// them: cached responses still require auth, but skip the limits and breaker.
+/// `ETag`s are added outside the cache, so cache hits can be answered with `304`.
+/// Usage is counted per token just inside auth, so cache hits count too.
+fn protected_routes<S>(
+    auth_config: Option<AuthConfig>,
+    breaker: Arc<CircuitBreaker>,
+    cache: Arc<ResponseCache>,
+    usage: Arc<UsageTracker>,
+    limits: RequestLimits,
+) -> Router<AppState<S>>
+where
+    S: VideoQueries + StatsQueries + Clone + Send + Sync + 'static,
+{
     let api_routes = Router::new()
         .route("/api/videos/{id}/stats", get(get_video_stats::<S>))
+        .route("/api/videos/{id}/media", get(get_video_media::<S>))
+        .route(
+            "/api/videos/{id}/duplicates",
+            get(get_video_duplicates::<S>),
+        )
         .route("/api/videos", get(list_videos::<S>))
         .route("/api/users/{pubkey}/videos", get(get_user_videos::<S>))
+        .route(
+            "/api/users/{pubkey}/playlists",
+            get(get_user_playlists::<S>),
+        )
+        .route("/api/playlists/{naddr}", get(get_playlist::<S>))
         .route("/api/search", get(search_videos::<S>))
-        .route("/api/stats", get(get_stats::<S>));
+        .route("/api/feeds/rss", get(get_rss_feed::<S>))
+        .route("/api/stats", get(get_stats::<S>))
+        .route("/api/relays", get(get_relays::<S>))
+        .route("/api/graphql", post(graphql_handler::<S>))
+        .route("/api/usage", get(get_usage))
+        .layer(Extension(build_schema()))
+        .layer(Extension(usage.clone()));
+    let api_routes = with_limits(api_routes, RouteLimiter::new("api", limits), Some(breaker))
+        .layer(middleware::from_fn_with_state(cache, response_cache))
+        .layer(middleware::from_fn(conditional_get));
 
-    let api_routes = if let Some(config) = auth_config {
+    // Apply auth middleware only if auth is configured
+    if let Some(config) = auth_config {
         api_routes
+            .layer(middleware::from_fn_with_state(usage, track_usage))
             .layer(middleware::from_fn(require_auth))
             .layer(Extension(config))
     } else {
         api_routes
+    }
+}
+
+/// Admin routes, mounted only when an admin token is configured.
+///
+/// `/dashboard` lives here too. Admin `401`s carry a Basic challenge so browsers
+/// prompt for the admin token. Authenticated requests that change something are
+/// written to the audit log, including ones rejected by the request limits.
+fn admin_routes<S>(
+    config: ApiConfig,
+    cache: Arc<ResponseCache>,
+    errors: Arc<RecentErrors>,
+) -> Router<AppState<S>>
+where
+    S: VideoQueries + StatsQueries + AdminQueries + Clone + Send + Sync + 'static,
+{
+    let Some(admin_auth) = config.admin_auth.clone() else {
+        return Router::new();
     };
 
-    public_routes.merge(api_routes).with_state(state)
+    let routes = Router::new()
+        .route("/admin/backfill", post(trigger_backfill::<S>))
+        .route("/admin/events/{id}/tombstone", post(tombstone_event::<S>))
+        .route("/admin/videos/{id}/hide", post(hide_video::<S>))
+        .route("/admin/videos/{id}/unhide", post(unhide_video::<S>))
+        .route("/admin/reports", get(get_reports::<S>))
+        .route("/admin/reload", post(reload_config))
+        .route("/admin/cache/purge", post(purge_cache))
+        .route("/admin/checkpoints", get(get_checkpoints::<S>))
+        .route("/admin/errors", get(get_recent_errors))
+        .route("/admin/log-level", get(get_log_level).put(set_log_level))
+        .route("/dashboard", get(dashboard));
+
+    with_limits(routes, RouteLimiter::new("admin", config.limits), None)
+        .layer(middleware::from_fn(audit_log))
+        .layer(middleware::from_fn(require_auth))
+        .layer(middleware::from_fn(basic_challenge))
+        .layer(Extension(admin_auth))
+        .layer(Extension(config))
+        .layer(Extension(cache))
+        .layer(Extension(errors))
 }
@@ -0,0 +1,149 @@
+//! Paginated search responses.
+//!
+//! Search results are ordered newest first and paged with a keyset cursor: the
+//! `created_at` and event ID of the last result on the page. Each page also carries an
+//! estimate of the total number of matches, counted with the same predicate but
+//! stopped at [`TOTAL_ESTIMATE_CAP`] so a popular hashtag can't turn into a full scan.
+
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use funnel_clickhouse::{VideoHashtag, VideoStats};
+use serde::Serialize;
+
+/// Highest total a search will count to.
+pub const TOTAL_ESTIMATE_CAP: u64 = 10_000;
+
+/// A page of search results.
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchResponse<T> {
+    pub results: Vec<T>,
+    /// Number of matches across all pages, up to [`TOTAL_ESTIMATE_CAP`]; `null` if the
+    /// count failed.
+    pub total_estimate: Option<u64>,
+    /// Cursor for the next page, or `null` on the last page.
+    pub next_cursor: Option<String>,
+}
+
+impl<T> SearchResponse<T>
+where
+    T: SearchResult,
+{
+    /// Build a page, with a next cursor only if the page is full.
+    pub fn new(results: Vec<T>, total_estimate: Option<u64>, limit: u32) -> Self {
+        let next_cursor = results
+            .last()
+            .filter(|_| results.len() >= limit as usize)
+            .map(|last| last.cursor().to_string());
+        Self {
+            results,
+            total_estimate,
+            next_cursor,
+        }
+    }
+}
+
+/// A search result row that can be paged past.
+pub trait SearchResult {
+    /// Cursor positioned just after this result.
+    fn cursor(&self) -> SearchCursor;
+}
+
+impl SearchResult for VideoHashtag {
+    fn cursor(&self) -> SearchCursor {
+        SearchCursor {
+            created_at: self.created_at,
+            id: self.event_id.clone(),
+        }
+    }
+}
+
+impl SearchResult for VideoStats {
+    fn cursor(&self) -> SearchCursor {
+        SearchCursor {
+            created_at: self.created_at,
+            id: self.id.clone(),
+        }
+    }
+}
+
+/// Position in a search: the creation time and event ID of the last result seen.
+///
+/// Written as `{unix_seconds}:{event_id}`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SearchCursor {
+    pub created_at: DateTime<Utc>,
+    pub id: String,
+}
+
+impl SearchCursor {
+    /// Parse `{unix_seconds}:{event_id}`.
+    pub fn parse(s: &str) -> Option<Self> {
+        let (seconds, id) = s.split_once(':')?;
+        let created_at = DateTime::from_timestamp(seconds.parse().ok()?, 0)?;
+        if id.is_empty() || !id.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        Some(Self {
+            created_at,
+            id: id.to_string(),
+        })
+    }
+}
+
+impl fmt::Display for SearchCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.created_at.timestamp(), self.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn video(id: &str, created_at: i64) -> VideoStats {
+        VideoStats {
+            id: id.to_string(),
+            pubkey: "pubkey1".to_string(),
+            created_at: DateTime::from_timestamp(created_at, 0).unwrap(),
+            kind: 34235,
+            d_tag: String::new(),
+            title: String::new(),
+            thumbnail: String::new(),
+            reactions: 0,
+            comments: 0,
+            reposts: 0,
+            engagement_score: 0,
+        }
+    }
+
+    #[test]
+    fn cursor_round_trips() {
+        let cursor = SearchCursor::parse("1700000000:abc123").unwrap();
+        assert_eq!(cursor.created_at.timestamp(), 1_700_000_000);
+        assert_eq!(cursor.id, "abc123");
+        assert_eq!(cursor.to_string(), "1700000000:abc123");
+    }
+
+    #[test]
+    fn cursor_rejects_malformed_values() {
+        assert!(SearchCursor::parse("").is_none());
+        assert!(SearchCursor::parse("1700000000").is_none());
+        assert!(SearchCursor::parse("1700000000:").is_none());
+        assert!(SearchCursor::parse("1700000000:not-hex").is_none());
+        assert!(SearchCursor::parse("yesterday:abc123").is_none());
+    }
+
+    #[test]
+    fn next_cursor_only_on_full_pages() {
+        let full = SearchResponse::new(vec![video("aa", 200), video("bb", 100)], Some(3), 2);
+        assert_eq!(full.next_cursor.as_deref(), Some("100:bb"));
+
+        let last = SearchResponse::new(vec![video("cc", 50)], Some(3), 2);
+        assert!(last.next_cursor.is_none());
+
+        let empty = SearchResponse::<VideoStats>::new(Vec::new(), Some(0), 2);
+        assert!(empty.next_cursor.is_none());
+    }
+}
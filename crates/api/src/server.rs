@@ -0,0 +1,179 @@
+//! API server startup.
+//!
+//! Reads configuration from the environment, connects to ClickHouse, and serves the
+//! API until SIGTERM/Ctrl+C, draining in-flight requests before returning. With
+//! `CLICKHOUSE_TENANTS` set, every tenant's database is served too (see
+//! [`crate::tenant`]).
+
+use std::collections::HashMap;
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+
+use funnel_clickhouse::{ClickHouseClient, ClickHouseConfig};
+use funnel_observability::PrometheusConfig;
+use funnel_observability::audit::init_audit_log;
+use funnel_observability::heartbeat::{self, Heartbeat};
+
+use crate::probes::self_check;
+use crate::prometheus::metrics_router;
+use crate::shutdown::{shutdown_signal, shutdown_timeout_from_env};
+use crate::tenant::tenant_router;
+use crate::{ApiConfig, AppState, DEFAULT_PUBLIC_URL, create_router};
+
+/// Run the API server until shutdown.
+pub async fn run() -> anyhow::Result<()> {
+    // Write admin actions to rotating files when AUDIT_LOG_DIR is set
+    init_audit_log()?;
+
+    let ch_config = ClickHouseConfig::from_env()?;
+    let bind_addr = env::var("BIND_ADDR").unwrap_or_else(|_| "0.0.0.0:8080".to_string());
+    let public_url = env::var("PUBLIC_URL").unwrap_or_else(|_| DEFAULT_PUBLIC_URL.to_string());
+
+    // Load auth config from environment (optional). A process scoped to one tenant
+    // accepts only that tenant's tokens.
+    let mut api_config = ApiConfig::from_env();
+    if let Some(tenant) = &ch_config.tenant {
+        api_config = api_config.for_tenant(tenant);
+    }
+    let multi_tenant = ch_config.tenant.is_none() && !ch_config.tenants.is_empty();
+    if multi_tenant && api_config.auth.is_none() {
+        anyhow::bail!("CLICKHOUSE_TENANTS requires API authentication to tell tenants apart");
+    }
+    if api_config.auth.is_some() {
+        tracing::info!("API authentication enabled");
+    } else {
+        tracing::warn!("API authentication disabled - set API_TOKEN to enable");
+    }
+    if api_config.admin_auth.is_some() {
+        tracing::info!("Admin API enabled");
+    }
+    if api_config.metrics.auth.is_some() {
+        tracing::info!("Metrics authentication enabled");
+    }
+
+    tracing::info!(
+        clickhouse_url = %ch_config.safe_url(),
+        database = %ch_config.database,
+        tenant = ch_config.tenant.as_deref(),
+        bind_addr = %bind_addr,
+        public_url = %public_url,
+        "Starting API server"
+    );
+
+    // Initialize metrics
+    let metrics_handle = funnel_observability::init_metrics(
+        PrometheusConfig::from_env().with_global_label("service", "funnel-api"),
+    );
+
+    // Connect to ClickHouse
+    let clickhouse = ClickHouseClient::connect(&ch_config).await?;
+
+    let max_lag: Option<u64> = env::var("MAX_REPLICATION_LAG_SECS")
+        .ok()
+        .and_then(|s| s.parse().ok());
+
+    // Re-check dependencies in the background; /readyz reports the latest results
+    let heartbeat = Heartbeat::spawn(heartbeat::DEFAULT_INTERVAL, {
+        let clickhouse = clickhouse.clone();
+        move || {
+            let clickhouse = clickhouse.clone();
+            async move { self_check(&clickhouse, max_lag).await }
+        }
+    });
+
+    if let Some(max_lag) = max_lag {
+        tracing::info!(max_lag_secs = max_lag, "Readiness checks replication lag");
+    }
+    let update_interval = env::var("VIDEO_UPDATES_POLL_MS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .map(Duration::from_millis);
+    let app_state = |clickhouse: ClickHouseClient| {
+        let mut state = AppState::new(clickhouse)
+            .with_public_url(public_url.clone())
+            .with_heartbeat(heartbeat.clone());
+        if let Some(max_lag) = max_lag {
+            state = state.with_max_replication_lag(max_lag);
+        }
+        if let Some(interval) = update_interval {
+            state = state.with_update_interval(interval);
+        }
+        state
+    };
+
+    // Serve /metrics on its own listener when configured, so it can stay on an
+    // internal network while the API port is public.
+    if let Some(metrics_addr) = api_config.metrics.bind_addr.clone() {
+        let metrics_app =
+            metrics_router::<()>(metrics_handle.clone(), api_config.metrics.auth.clone());
+        let metrics_listener = tokio::net::TcpListener::bind(&metrics_addr).await?;
+        tracing::info!("Serving metrics on {}", metrics_addr);
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(metrics_listener, metrics_app).await {
+                tracing::error!(error = %e, "Metrics listener failed");
+            }
+        });
+    }
+
+    let app = if multi_tenant {
+        let mut routers = HashMap::new();
+        for (tenant, database) in &ch_config.tenants {
+            let tenant_clickhouse =
+                ClickHouseClient::connect(&ch_config.for_tenant(tenant)?).await?;
+            tracing::info!(tenant = %tenant, database = %database, "Serving tenant");
+            let router = create_router(
+                app_state(tenant_clickhouse),
+                metrics_handle.clone(),
+                api_config.for_tenant(tenant),
+            );
+            routers.insert(tenant.clone(), router);
+        }
+
+        let auth = [&api_config.auth, &api_config.admin_auth]
+            .into_iter()
+            .flatten()
+            .cloned()
+            .collect();
+        let default = create_router(app_state(clickhouse), metrics_handle, api_config);
+        tenant_router(default, routers, auth)
+    } else {
+        create_router(app_state(clickhouse), metrics_handle, api_config)
+    };
+
+    let listener = tokio::net::TcpListener::bind(&bind_addr).await?;
+    tracing::info!("Listening on {}", bind_addr);
+
+    // Stop accepting connections on SIGTERM/Ctrl+C, then give in-flight requests
+    // until the deadline to finish before exiting anyway.
+    let shutdown_timeout = shutdown_timeout_from_env();
+    let shutdown_started = Arc::new(tokio::sync::Notify::new());
+    let server = axum::serve(listener, app)
+        .with_graceful_shutdown({
+            let shutdown_started = shutdown_started.clone();
+            async move {
+                shutdown_signal().await;
+                shutdown_started.notify_one();
+            }
+        })
+        .into_future();
+
+    tokio::select! {
+        result = server => {
+            result?;
+            tracing::info!("All connections drained");
+        }
+        _ = async {
+            shutdown_started.notified().await;
+            tokio::time::sleep(shutdown_timeout).await;
+        } => {
+            tracing::warn!(
+                timeout_secs = shutdown_timeout.as_secs(),
+                "Shutdown deadline exceeded, dropping in-flight requests"
+            );
+        }
+    }
+
+    tracing::info!("API server stopped");
+    Ok(())
+}
@@ -0,0 +1,116 @@
+//! Multi-tenant routing.
+//!
+//! With `CLICKHOUSE_TENANTS` set, each tenant gets a complete router over its own
+//! database, with its own response cache, circuit breaker, usage tracking, and update
+//! feed. [`tenant_router`] hands each request to the router of the tenant its token
+//! belongs to, and everything else to the default router. Each router validates the
+//! token again and accepts only its own tenant's tokens, so a token can never reach
+//! another tenant's data.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::{Router, extract::Request};
+use tower::ServiceExt;
+
+use crate::auth::{AuthConfig, extract_token};
+
+/// Routers by tenant, and how to tell a request's tenant.
+struct Tenants {
+    default: Router,
+    routers: HashMap<String, Router>,
+    /// Auth configs whose tokens can name a tenant (API and admin).
+    auth: Vec<AuthConfig>,
+}
+
+impl Tenants {
+    /// Router for the tenant of the request's token.
+    fn router(&self, request: &Request) -> &Router {
+        extract_token(request.headers())
+            .and_then(|token| self.auth.iter().find_map(|auth| auth.tenant_of(&token)))
+            .and_then(|tenant| self.routers.get(&tenant))
+            .unwrap_or(&self.default)
+    }
+}
+
+/// Router dispatching each request to its tenant's router in `routers`, or to
+/// `default` for requests without a known tenant (public routes, probes, and tokens
+/// without a tenant).
+pub fn tenant_router(
+    default: Router,
+    routers: HashMap<String, Router>,
+    auth: Vec<AuthConfig>,
+) -> Router {
+    let tenants = Arc::new(Tenants {
+        default,
+        routers,
+        auth,
+    });
+
+    Router::new().fallback(move |request: Request| {
+        let tenants = tenants.clone();
+        async move {
+            let router = tenants.router(&request).clone();
+            match router.oneshot(request).await {
+                Ok(response) => response,
+                Err(infallible) => match infallible {},
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::{Body, to_bytes};
+    use axum::routing::get;
+
+    use super::*;
+
+    fn router(name: &'static str) -> Router {
+        Router::new().route("/api/stats", get(move || async move { name }))
+    }
+
+    async fn served_by(app: &Router, token: Option<&str>) -> String {
+        let mut request = Request::builder().uri("/api/stats");
+        if let Some(token) = token {
+            request = request.header("authorization", format!("Bearer {token}"));
+        }
+        let response = app
+            .clone()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let body = to_bytes(response.into_body(), 1024).await.unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn requests_go_to_their_tokens_tenant() {
+        let path = std::env::temp_dir().join(format!("funnel-tenants-{}", std::process::id()));
+        std::fs::write(&path, "shared-token\nacme-token acme\nbeta-token beta\n").unwrap();
+        // SAFETY: This test runs single-threaded (--test-threads=1)
+        unsafe {
+            std::env::set_var("TENANT_TEST_TOKEN_FILE", &path);
+        }
+        let auth = AuthConfig::from_env_var("TENANT_TEST_TOKEN").unwrap();
+        unsafe {
+            std::env::remove_var("TENANT_TEST_TOKEN_FILE");
+        }
+        let _ = std::fs::remove_file(&path);
+
+        let app = tenant_router(
+            router("default"),
+            HashMap::from([
+                ("acme".to_string(), router("acme")),
+                ("beta".to_string(), router("beta")),
+            ]),
+            vec![auth],
+        );
+
+        assert_eq!(served_by(&app, Some("acme-token")).await, "acme");
+        assert_eq!(served_by(&app, Some("beta-token")).await, "beta");
+        assert_eq!(served_by(&app, Some("shared-token")).await, "default");
+        assert_eq!(served_by(&app, Some("unknown-token")).await, "default");
+        assert_eq!(served_by(&app, None).await, "default");
+    }
+}
@@ -0,0 +1,192 @@
+//! Notifications of newly indexed videos, and the cursors used to follow them.
+//!
+//! [`VideoUpdates`] broadcasts the time the newest video was indexed. A single
+//! background task polls storage for it, started by the first subscriber and stopped
+//! once the last one goes away, so a replica nobody is following never polls. Any
+//! number of waiting requests share that one query.
+//!
+//! Long-poll requests on `GET /api/videos?since_cursor=...&wait=25s` subscribe before
+//! querying, then re-query each time the broadcast changes until videos arrive or the
+//! wait runs out.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use funnel_clickhouse::{IndexedVideo, VideoQueries};
+use tokio::sync::watch;
+
+/// Default interval between checks for newly indexed videos.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Longest a long-poll request may wait, kept below the shutdown drain deadline.
+pub const MAX_WAIT: Duration = Duration::from_secs(25);
+
+/// Time the newest video was indexed, or `None` before the first check.
+pub type LatestIndexed = Option<DateTime<Utc>>;
+
+/// Shared broadcast of the newest video's index time.
+#[derive(Debug)]
+pub struct VideoUpdates {
+    interval: Duration,
+    /// Sender of the running poller; `None` while nobody is subscribed.
+    sender: Arc<Mutex<Option<watch::Sender<LatestIndexed>>>>,
+}
+
+impl Default for VideoUpdates {
+    fn default() -> Self {
+        Self::new(DEFAULT_POLL_INTERVAL)
+    }
+}
+
+impl VideoUpdates {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            sender: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Subscribe to updates, starting the poller if it isn't running.
+    ///
+    /// The receiver starts with the current value marked as seen, so only changes
+    /// after this call wake it.
+    pub fn subscribe<S>(&self, storage: Arc<S>) -> watch::Receiver<LatestIndexed>
+    where
+        S: VideoQueries + Send + Sync + 'static,
+    {
+        let mut slot = self.sender.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(sender) = slot.as_ref() {
+            return sender.subscribe();
+        }
+
+        let (sender, receiver) = watch::channel(None);
+        *slot = Some(sender.clone());
+        tokio::spawn(poll(storage, sender, self.sender.clone(), self.interval));
+        receiver
+    }
+}
+
+/// Publish the newest index time every `interval` until nobody is subscribed.
+async fn poll<S>(
+    storage: Arc<S>,
+    sender: watch::Sender<LatestIndexed>,
+    slot: Arc<Mutex<Option<watch::Sender<LatestIndexed>>>>,
+    interval: Duration,
+) where
+    S: VideoQueries + Send + Sync + 'static,
+{
+    tracing::debug!("Video update poller started");
+    loop {
+        match storage.get_latest_video_indexed_at().await {
+            Ok(latest) => {
+                sender.send_if_modified(|current| {
+                    let changed = *current != latest;
+                    *current = latest;
+                    changed
+                });
+            }
+            Err(e) => tracing::warn!(error = %e, "Failed to check for new videos"),
+        }
+
+        tokio::time::sleep(interval).await;
+
+        // Checked under the lock so a concurrent subscribe either lands on this
+        // sender or starts a new poller, never a stopped one
+        let mut current = slot.lock().unwrap_or_else(|e| e.into_inner());
+        if sender.is_closed() {
+            *current = None;
+            break;
+        }
+    }
+    tracing::debug!("Video update poller stopped");
+}
+
+/// Position in the stream of indexed videos: the index time and event ID of the last
+/// video seen.
+///
+/// Written as `{unix_seconds}:{event_id}`. A bare unix timestamp starts from that
+/// second, including videos indexed during it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VideoCursor {
+    pub indexed_at: DateTime<Utc>,
+    pub id: String,
+}
+
+impl VideoCursor {
+    /// Parse `{unix_seconds}:{event_id}` or a bare unix timestamp.
+    pub fn parse(s: &str) -> Option<Self> {
+        let (seconds, id) = s.split_once(':').unwrap_or((s, ""));
+        let indexed_at = DateTime::from_timestamp(seconds.parse().ok()?, 0)?;
+        if !id.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        Some(Self {
+            indexed_at,
+            id: id.to_string(),
+        })
+    }
+
+    /// Cursor just past `video`.
+    pub fn after(video: &IndexedVideo) -> Self {
+        Self {
+            indexed_at: video.indexed_at,
+            id: video.id.clone(),
+        }
+    }
+}
+
+impl fmt::Display for VideoCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.indexed_at.timestamp(), self.id)
+    }
+}
+
+/// Parse a `wait` value: whole seconds, optionally suffixed `s`, or milliseconds
+/// suffixed `ms`. Values above [`MAX_WAIT`] are capped.
+pub fn parse_wait(value: &str) -> Option<Duration> {
+    let wait = match value.strip_suffix("ms") {
+        Some(millis) => Duration::from_millis(millis.parse().ok()?),
+        None => Duration::from_secs(value.strip_suffix('s').unwrap_or(value).parse().ok()?),
+    };
+    Some(wait.min(MAX_WAIT))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_round_trips() {
+        let cursor = VideoCursor::parse("1700000000:abc123").unwrap();
+        assert_eq!(cursor.indexed_at.timestamp(), 1_700_000_000);
+        assert_eq!(cursor.id, "abc123");
+        assert_eq!(cursor.to_string(), "1700000000:abc123");
+    }
+
+    #[test]
+    fn cursor_accepts_bare_timestamp() {
+        let cursor = VideoCursor::parse("1700000000").unwrap();
+        assert_eq!(cursor.id, "");
+        assert_eq!(cursor.to_string(), "1700000000:");
+    }
+
+    #[test]
+    fn cursor_rejects_malformed_values() {
+        assert!(VideoCursor::parse("").is_none());
+        assert!(VideoCursor::parse("yesterday").is_none());
+        assert!(VideoCursor::parse("1700000000:not-hex").is_none());
+    }
+
+    #[test]
+    fn parse_wait_accepts_units_and_caps() {
+        assert_eq!(parse_wait("25s"), Some(Duration::from_secs(25)));
+        assert_eq!(parse_wait("10"), Some(Duration::from_secs(10)));
+        assert_eq!(parse_wait("500ms"), Some(Duration::from_millis(500)));
+        assert_eq!(parse_wait("3600s"), Some(MAX_WAIT));
+        assert_eq!(parse_wait("soon"), None);
+        assert_eq!(parse_wait("-1"), None);
+    }
+}
@@ -0,0 +1,335 @@
+//! Per-token usage accounting and monthly quotas.
+//!
+//! When API auth is enabled, every `/api/*` request is counted against the token
+//! that made it (identified by [`TokenId`], never the token itself): requests and
+//! response bytes for the current calendar month (UTC). Optional monthly quotas
+//! reject further requests with `429` until the month rolls over.
+//!
+//! Counters live in process memory, so each replica accounts separately and counts
+//! reset on restart. `GET /api/usage` reports the caller's own usage and is never
+//! counted or rejected, so clients can always check where they stand.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use axum::{
+    Extension, Json,
+    body::HttpBody,
+    extract::{Request, State},
+    http::{HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use funnel_observability::api;
+use funnel_proto::ErrorCode;
+use metrics::counter;
+use serde::Serialize;
+
+use crate::auth::TokenId;
+
+/// Path of the usage report, exempt from accounting.
+pub const USAGE_PATH: &str = "/api/usage";
+
+/// Monthly quotas applied to every token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct UsageConfig {
+    /// Requests allowed per token per month. `None` is unlimited.
+    pub monthly_requests: Option<u64>,
+    /// Response bytes allowed per token per month. `None` is unlimited.
+    pub monthly_bytes: Option<u64>,
+}
+
+impl UsageConfig {
+    /// Load from `USAGE_MONTHLY_REQUEST_QUOTA` and `USAGE_MONTHLY_BYTE_QUOTA`.
+    ///
+    /// Unset, unparsable, or zero values leave that quota unlimited.
+    pub fn from_env() -> Self {
+        Self {
+            monthly_requests: env_parse("USAGE_MONTHLY_REQUEST_QUOTA").filter(|n| *n > 0),
+            monthly_bytes: env_parse("USAGE_MONTHLY_BYTE_QUOTA").filter(|n| *n > 0),
+        }
+    }
+
+    /// Set the monthly request quota.
+    pub fn with_monthly_requests(mut self, requests: u64) -> Self {
+        self.monthly_requests = Some(requests);
+        self
+    }
+
+    /// Set the monthly response byte quota.
+    pub fn with_monthly_bytes(mut self, bytes: u64) -> Self {
+        self.monthly_bytes = Some(bytes);
+        self
+    }
+}
+
+/// Usage counted for one token in one month.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Usage {
+    pub requests: u64,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    /// First day of the month being counted.
+    period: Option<NaiveDate>,
+    usage: HashMap<TokenId, Usage>,
+}
+
+/// In-memory usage counters for the current month.
+#[derive(Debug, Default)]
+pub struct UsageTracker {
+    config: UsageConfig,
+    counters: Mutex<Counters>,
+}
+
+impl UsageTracker {
+    pub fn new(config: UsageConfig) -> Self {
+        Self {
+            config,
+            counters: Mutex::new(Counters::default()),
+        }
+    }
+
+    /// Usage for `token` in the month containing `now`.
+    pub fn usage(&self, token: &TokenId, now: DateTime<Utc>) -> Usage {
+        self.lock(now).usage.get(token).copied().unwrap_or_default()
+    }
+
+    /// Count a request, unless the token is already over a quota.
+    ///
+    /// Returns the quota that was exceeded, if any.
+    fn begin(&self, token: &TokenId, now: DateTime<Utc>) -> Result<(), &'static str> {
+        let mut state = self.lock(now);
+        let usage = state.usage.entry(token.clone()).or_default();
+        if self
+            .config
+            .monthly_requests
+            .is_some_and(|quota| usage.requests >= quota)
+        {
+            return Err("requests");
+        }
+        if self
+            .config
+            .monthly_bytes
+            .is_some_and(|quota| usage.bytes >= quota)
+        {
+            return Err("bytes");
+        }
+        usage.requests += 1;
+        Ok(())
+    }
+
+    /// Add response bytes for a counted request.
+    fn add_bytes(&self, token: &TokenId, bytes: u64, now: DateTime<Utc>) {
+        self.lock(now).usage.entry(token.clone()).or_default().bytes += bytes;
+    }
+
+    /// Lock the counters, starting a new period if the month has changed.
+    fn lock(&self, now: DateTime<Utc>) -> MutexGuard<'_, Counters> {
+        let mut state = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        let period = period_start(now);
+        if state.period != Some(period) {
+            state.period = Some(period);
+            state.usage.clear();
+        }
+        state
+    }
+}
+
+/// Middleware counting requests and response bytes per token and enforcing quotas.
+///
+/// Must run inside the auth middleware, which sets the [`TokenId`]. Requests without
+/// one (auth disabled) pass through uncounted.
+pub async fn track_usage(
+    State(tracker): State<Arc<UsageTracker>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(token) = request.extensions().get::<TokenId>().cloned() else {
+        return next.run(request).await;
+    };
+    if request.uri().path() == USAGE_PATH {
+        return next.run(request).await;
+    }
+
+    if let Err(quota) = tracker.begin(&token, Utc::now()) {
+        counter!(api::QUOTA_EXCEEDED, "quota" => quota).increment(1);
+        let mut response = error_response(
+            StatusCode::TOO_MANY_REQUESTS,
+            ErrorCode::ApiRateLimited,
+            "Monthly usage quota exceeded",
+        );
+        if let Ok(value) = HeaderValue::from_str(&seconds_until_next_period(Utc::now()).to_string())
+        {
+            response.headers_mut().insert(header::RETRY_AFTER, value);
+        }
+        return response;
+    }
+
+    let response = next.run(request).await;
+    // Streaming bodies without a known size count as their lower bound
+    let bytes = response.body().size_hint().lower();
+    tracker.add_bytes(&token, bytes, Utc::now());
+    response
+}
+
+/// Remaining allowance, absent when unlimited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct QuotaReport {
+    pub limit: u64,
+    pub remaining: u64,
+}
+
+impl QuotaReport {
+    fn new(limit: Option<u64>, used: u64) -> Option<Self> {
+        limit.map(|limit| Self {
+            limit,
+            remaining: limit.saturating_sub(used),
+        })
+    }
+}
+
+/// Body returned by `GET /api/usage`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UsageReport {
+    /// Identifier of the calling token (a hash prefix, not the token).
+    pub token_id: String,
+    pub period_start: NaiveDate,
+    pub period_end: NaiveDate,
+    pub requests: u64,
+    pub bytes: u64,
+    pub request_quota: Option<QuotaReport>,
+    pub byte_quota: Option<QuotaReport>,
+}
+
+/// Report the calling token's usage for the current month.
+pub async fn get_usage(
+    Extension(tracker): Extension<Arc<UsageTracker>>,
+    token: Option<Extension<TokenId>>,
+) -> Response {
+    counter!(api::REQUESTS, "endpoint" => "usage").increment(1);
+
+    let Some(Extension(token)) = token else {
+        return error_response(
+            StatusCode::NOT_FOUND,
+            ErrorCode::ApiNotFound,
+            "Usage is only tracked when API authentication is enabled",
+        );
+    };
+
+    let now = Utc::now();
+    let usage = tracker.usage(&token, now);
+    let period_start = period_start(now);
+    let report = UsageReport {
+        token_id: token.as_str().to_string(),
+        period_start,
+        period_end: next_period_start(period_start),
+        requests: usage.requests,
+        bytes: usage.bytes,
+        request_quota: QuotaReport::new(tracker.config.monthly_requests, usage.requests),
+        byte_quota: QuotaReport::new(tracker.config.monthly_bytes, usage.bytes),
+    };
+
+    (
+        StatusCode::OK,
+        [(header::CACHE_CONTROL, "private, no-store")],
+        Json(report),
+    )
+        .into_response()
+}
+
+/// First day of the month containing `now`.
+fn period_start(now: DateTime<Utc>) -> NaiveDate {
+    now.date_naive()
+        .with_day(1)
+        .expect("day 1 exists in every month")
+}
+
+/// First day of the following month.
+fn next_period_start(start: NaiveDate) -> NaiveDate {
+    start
+        .checked_add_months(chrono::Months::new(1))
+        .unwrap_or(NaiveDate::MAX)
+}
+
+fn seconds_until_next_period(now: DateTime<Utc>) -> i64 {
+    let next = next_period_start(period_start(now)).and_hms_opt(0, 0, 0);
+    next.map_or(0, |next| (next.and_utc() - now).num_seconds().max(0))
+}
+
+fn error_response(status: StatusCode, code: ErrorCode, message: &str) -> Response {
+    (
+        status,
+        [(header::CACHE_CONTROL, "no-store")],
+        Json(serde_json::json!({ "error": message, "code": code })),
+    )
+        .into_response()
+}
+
+fn env_parse<T>(name: &str) -> Option<T>
+where
+    T: std::str::FromStr,
+{
+    std::env::var(name).ok().and_then(|s| s.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn at(year: i32, month: u32, day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, 12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn begin_enforces_request_quota() {
+        let tracker = UsageTracker::new(UsageConfig::default().with_monthly_requests(2));
+        let token = TokenId::from_token("client-a");
+        let now = at(2024, 3, 10);
+
+        assert!(tracker.begin(&token, now).is_ok());
+        assert!(tracker.begin(&token, now).is_ok());
+        assert_eq!(tracker.begin(&token, now), Err("requests"));
+        assert_eq!(tracker.usage(&token, now).requests, 2);
+
+        // Other tokens have their own allowance
+        assert!(tracker.begin(&TokenId::from_token("client-b"), now).is_ok());
+    }
+
+    #[test]
+    fn begin_enforces_byte_quota() {
+        let tracker = UsageTracker::new(UsageConfig::default().with_monthly_bytes(100));
+        let token = TokenId::from_token("client-a");
+        let now = at(2024, 3, 10);
+
+        assert!(tracker.begin(&token, now).is_ok());
+        tracker.add_bytes(&token, 150, now);
+        assert_eq!(tracker.begin(&token, now), Err("bytes"));
+    }
+
+    #[test]
+    fn usage_resets_each_month() {
+        let tracker = UsageTracker::new(UsageConfig::default().with_monthly_requests(1));
+        let token = TokenId::from_token("client-a");
+
+        assert!(tracker.begin(&token, at(2024, 1, 31)).is_ok());
+        assert!(tracker.begin(&token, at(2024, 1, 31)).is_err());
+        assert!(tracker.begin(&token, at(2024, 2, 1)).is_ok());
+        assert_eq!(tracker.usage(&token, at(2024, 2, 1)).requests, 1);
+    }
+
+    #[test]
+    fn period_bounds_cover_calendar_month() {
+        let start = period_start(at(2024, 12, 15));
+        assert_eq!(start, NaiveDate::from_ymd_opt(2024, 12, 1).unwrap());
+        assert_eq!(
+            next_period_start(start),
+            NaiveDate::from_ymd_opt(2025, 1, 1).unwrap()
+        );
+    }
+}
@@ -0,0 +1,149 @@
+//! `funnel backup` and `funnel restore`: disaster recovery through object storage.
+//!
+//! A backup copies the raw events and every other table to a prefix of an
+//! S3-compatible bucket (`BACKUP_S3_URL`) as Parquet or JSON lines, with a manifest
+//! of row counts and checksums (see [`funnel_clickhouse::backup`]). Restoring applies
+//! the schema, checks every object against the manifest, and only then inserts
+//! anything: the raw events first, then the other tables. Tables that materialized
+//! views fill from the events are restored through a staging table that replaces
+//! what the views derived, so they come back exactly as backed up.
+
+use std::collections::HashMap;
+
+use chrono::Utc;
+use clap::Args;
+use funnel_clickhouse::backup::{BackupFormat, S3Location};
+use funnel_clickhouse::rebuild::projections;
+use funnel_clickhouse::{ClickHouseClient, ClickHouseConfig, Deployment};
+
+#[derive(Args)]
+pub struct BackupArgs {
+    /// Name of the backup, the prefix under `BACKUP_S3_URL` it's written to (the
+    /// current time by default, e.g. `20240115T103000Z`)
+    #[arg(long)]
+    name: Option<String>,
+    /// Object format: `parquet` or `jsonl`
+    #[arg(long, default_value = "parquet")]
+    format: BackupFormat,
+}
+
+#[derive(Args)]
+pub struct RestoreArgs {
+    /// Name of the backup to restore
+    name: String,
+    /// Schema edition to apply first: `cloud` (no projections) or `self-hosted`
+    #[arg(long, env = "CLICKHOUSE_DEPLOYMENT", default_value = "cloud")]
+    deployment: Deployment,
+    /// Restore even if the database already has events
+    #[arg(long)]
+    force: bool,
+}
+
+/// Back up every table to the backup named in `args`.
+pub async fn backup(args: BackupArgs) -> anyhow::Result<()> {
+    let clickhouse = ClickHouseClient::connect(&ClickHouseConfig::from_env()?).await?;
+    let name = args
+        .name
+        .unwrap_or_else(|| Utc::now().format("%Y%m%dT%H%M%SZ").to_string());
+    let location = S3Location::from_env()?.join(&name);
+    tracing::info!(backup = %location.url, format = %args.format, "Starting backup");
+
+    let mut files = Vec::new();
+    for table in clickhouse.get_backup_tables().await? {
+        let has_created_at = clickhouse
+            .get_insertable_columns(&table)
+            .await?
+            .iter()
+            .any(|(column, _)| column == "created_at");
+        let months = if has_created_at {
+            clickhouse
+                .get_backup_partitions(&table)
+                .await?
+                .into_iter()
+                .map(Some)
+                .collect()
+        } else {
+            vec![None]
+        };
+
+        for month in months {
+            let extension = args.format.extension();
+            let path = match month {
+                Some(month) => format!("{table}/{month}.{extension}"),
+                None => format!("{table}.{extension}"),
+            };
+            let file = clickhouse
+                .backup_table(&location, &path, &table, month, args.format)
+                .await?;
+            tracing::info!(%path, rows = file.rows, "Backed up");
+            files.push(file);
+        }
+    }
+
+    clickhouse.write_backup_manifest(&location, &files).await?;
+    let rows: u64 = files.iter().map(|file| file.rows).sum();
+    tracing::info!(%name, files = files.len(), rows, "Backup complete");
+    Ok(())
+}
+
+/// Restore the backup named in `args` into the configured database.
+pub async fn restore(args: RestoreArgs) -> anyhow::Result<()> {
+    let clickhouse = ClickHouseClient::connect(&ClickHouseConfig::from_env()?).await?;
+    let location = S3Location::from_env()?.join(&args.name);
+
+    clickhouse.migrate(args.deployment).await?;
+    if !args.force && clickhouse.get_event_count().await? > 0 {
+        anyhow::bail!(
+            "the database already has events; restore into an empty one, or pass --force to \
+             add the backup to them"
+        );
+    }
+
+    let files = clickhouse.read_backup_manifest(&location).await?;
+    tracing::info!(backup = %location.url, files = files.len(), "Verifying backup");
+    for file in &files {
+        let (rows, checksum) = clickhouse.get_backup_checksum(&location, file).await?;
+        if (rows, checksum) != (file.rows, file.checksum) {
+            anyhow::bail!(
+                "{} doesn't match the manifest ({rows} rows, expected {}); nothing was restored",
+                file.path,
+                file.rows
+            );
+        }
+    }
+
+    let projections: HashMap<_, _> = projections(args.deployment.schema())
+        .into_iter()
+        .map(|projection| (projection.target.clone(), projection))
+        .collect();
+    // The manifest lists events_local first and each table's files together
+    let mut tables: Vec<&str> = Vec::new();
+    for file in &files {
+        if tables.last() != Some(&file.table.as_str()) {
+            tables.push(&file.table);
+        }
+    }
+
+    for table in tables {
+        let projection = projections.get(table);
+        if let Some(projection) = projection {
+            clickhouse.create_rebuild_staging(projection).await?;
+        }
+
+        let into = projection.map_or(table.to_string(), |projection| projection.staging());
+        for file in files.iter().filter(|file| file.table == table) {
+            clickhouse
+                .restore_backup_file(&location, file, &into)
+                .await?;
+            tracing::info!(path = %file.path, rows = file.rows, "Restored");
+        }
+
+        if let Some(projection) = projection {
+            clickhouse.swap_rebuild_staging(projection).await?;
+        }
+    }
+
+    let rows: u64 = files.iter().map(|file| file.rows).sum();
+    tracing::info!(name = %args.name, files = files.len(), rows, "Restore complete");
+    Ok(())
+}
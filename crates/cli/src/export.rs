@@ -0,0 +1,72 @@
+//! `funnel export`: stored events written out as JSON lines.
+//!
+//! Each line is a NIP-01 event, so the file can be replayed with `funnel replay`,
+//! imported into a relay, or fed to anything else that reads Nostr events. Events
+//! come out oldest first, paged by `(created_at, id)` so large exports don't hold a
+//! single long-running query open.
+
+use std::path::PathBuf;
+
+use chrono::{DateTime, Utc};
+use clap::Args;
+use funnel_clickhouse::{ClickHouseClient, ClickHouseConfig};
+use tokio::fs::File;
+use tokio::io::{AsyncWriteExt, BufWriter};
+
+#[derive(Args)]
+pub struct ExportArgs {
+    /// File to write
+    output: PathBuf,
+    /// Only events created at or after this time (RFC 3339)
+    #[arg(long)]
+    since: Option<DateTime<Utc>>,
+    /// Only events created at or before this time (RFC 3339)
+    #[arg(long)]
+    until: Option<DateTime<Utc>>,
+    /// Only these kinds (comma-separated)
+    #[arg(long, value_delimiter = ',')]
+    kinds: Vec<u16>,
+    /// Events fetched per query
+    #[arg(long, default_value_t = 10_000, value_parser = clap::value_parser!(u32).range(1..))]
+    page_size: u32,
+}
+
+/// Write the selected events to `args.output`.
+pub async fn run(args: ExportArgs) -> anyhow::Result<()> {
+    let clickhouse = ClickHouseClient::connect(&ClickHouseConfig::from_env()?).await?;
+    let mut output = BufWriter::new(File::create(&args.output).await?);
+
+    // Event IDs are never empty, so this includes events at exactly `since`
+    let mut after = args.since.map(|since| (since, String::new()));
+    let mut total = 0u64;
+    loop {
+        let page = clickhouse
+            .get_events_after(
+                after
+                    .as_ref()
+                    .map(|(created_at, id)| (*created_at, id.as_str())),
+                args.until,
+                &args.kinds,
+                args.page_size,
+            )
+            .await?;
+
+        for event in &page {
+            output.write_all(event.to_json().as_bytes()).await?;
+            output.write_all(b"\n").await?;
+        }
+        total += page.len() as u64;
+        tracing::info!(total, "Exported page");
+
+        match page.last() {
+            Some(last) if page.len() == args.page_size as usize => {
+                after = Some((last.created_at, last.id.clone()));
+            }
+            _ => break,
+        }
+    }
+
+    output.flush().await?;
+    tracing::info!(total, output = %args.output.display(), "Export complete");
+    Ok(())
+}
@@ -0,0 +1,207 @@
+//! `funnel gen`: synthetic events for load tests and demos.
+//!
+//! Events come from [`Generator`], at `--rate` per second. By default they're
+//! created "now" and paced in real time, like a live relay feed; with `--since` they
+//! are backdated from that time at the same rate and written as fast as possible, to
+//! fill a database with history. They're inserted into ClickHouse, or written as JSON
+//! lines with `--output` so the same stream can be replayed later.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use clap::Args;
+use funnel_clickhouse::{ClickHouseClient, ClickHouseConfig, EventRow};
+use funnel_ingestion::generate::{GenConfig, Generator, Mix};
+use funnel_ingestion::parse_line;
+use tokio::fs::File;
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::time::Instant;
+
+/// Relay recorded as the source of inserted events.
+const SOURCE: &str = "funnel-gen";
+
+/// Events between progress logs.
+const PROGRESS_EVERY: u64 = 10_000;
+
+/// Longest a partial batch waits before it's inserted, so a slow paced stream shows
+/// up promptly.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Args)]
+pub struct GenArgs {
+    /// Events to generate, or 0 to run until interrupted
+    #[arg(long, default_value_t = 10_000)]
+    count: u64,
+    /// Events per second, or 0 for as fast as possible
+    #[arg(long, default_value_t = 50.0)]
+    rate: f64,
+    /// Backdate events from this time (RFC 3339) instead of pacing them in real time
+    #[arg(long)]
+    since: Option<DateTime<Utc>>,
+    /// Write JSON lines to this file instead of inserting into ClickHouse
+    #[arg(long)]
+    output: Option<PathBuf>,
+    /// Accounts that publish videos
+    #[arg(long, default_value_t = 50)]
+    creators: usize,
+    /// Accounts that react, comment, and zap
+    #[arg(long, default_value_t = 1000)]
+    viewers: usize,
+    /// Relative weights of videos, reactions, comments, and zaps
+    #[arg(long, default_value = "1,20,5,2", value_parser = parse_mix)]
+    mix: Mix,
+    /// Share of videos that are shorts (kind 34236)
+    #[arg(long, default_value_t = 0.3)]
+    short_ratio: f64,
+    /// How strongly engagement concentrates on popular videos (1 for uniform)
+    #[arg(long, default_value_t = 3.0)]
+    skew: f64,
+    /// Seed for the accounts and the stream
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Events per insert
+    #[arg(long, env = "BATCH_SIZE", default_value_t = 1000)]
+    batch_size: usize,
+}
+
+impl GenArgs {
+    fn config(&self) -> GenConfig {
+        GenConfig {
+            creators: self.creators,
+            viewers: self.viewers,
+            mix: self.mix,
+            short_ratio: self.short_ratio,
+            skew: self.skew,
+            seed: self.seed,
+        }
+    }
+}
+
+/// `videos,reactions,comments,zaps`, e.g. `1,20,5,2`.
+fn parse_mix(value: &str) -> Result<Mix, String> {
+    let weights = value
+        .split(',')
+        .map(|weight| weight.trim().parse::<u32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    match weights[..] {
+        [videos, reactions, comments, zaps] => Ok(Mix {
+            videos,
+            reactions,
+            comments,
+            zaps,
+        }),
+        _ => Err("expected four weights: videos,reactions,comments,zaps".to_string()),
+    }
+}
+
+/// Where generated events go.
+enum Sink {
+    File(BufWriter<File>),
+    ClickHouse {
+        client: Box<ClickHouseClient>,
+        batch: Vec<EventRow>,
+        batch_size: usize,
+        flushed: Instant,
+    },
+}
+
+impl Sink {
+    async fn write(&mut self, json: &str) -> anyhow::Result<()> {
+        match self {
+            Self::File(output) => {
+                output.write_all(json.as_bytes()).await?;
+                output.write_all(b"\n").await?;
+            }
+            Self::ClickHouse {
+                client,
+                batch,
+                batch_size,
+                flushed,
+            } => {
+                let event = parse_line(json)
+                    .ok_or_else(|| anyhow::anyhow!("generated an unparseable event"))?;
+                batch.push(EventRow::from_parsed(&event, SOURCE));
+                if batch.len() >= *batch_size || flushed.elapsed() >= FLUSH_INTERVAL {
+                    client.insert_events(batch).await?;
+                    batch.clear();
+                    *flushed = Instant::now();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn finish(&mut self) -> anyhow::Result<()> {
+        match self {
+            Self::File(output) => output.flush().await?,
+            Self::ClickHouse { client, batch, .. } => {
+                if !batch.is_empty() {
+                    client.insert_events(batch).await?;
+                    batch.clear();
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Generate `args.count` events into the output file or ClickHouse.
+pub async fn run(args: GenArgs) -> anyhow::Result<()> {
+    let mut generator = Generator::new(args.config());
+    let mut sink = match &args.output {
+        Some(path) => Sink::File(BufWriter::new(File::create(path).await?)),
+        None => Sink::ClickHouse {
+            client: Box::new(ClickHouseClient::connect(&ClickHouseConfig::from_env()?).await?),
+            batch: Vec::with_capacity(args.batch_size.max(1)),
+            batch_size: args.batch_size.max(1),
+            flushed: Instant::now(),
+        },
+    };
+    let interval = if args.rate > 0.0 {
+        Duration::from_secs_f64(1.0 / args.rate)
+    } else {
+        Duration::ZERO
+    };
+    tracing::info!(
+        count = args.count,
+        rate = args.rate,
+        since = ?args.since,
+        output = ?args.output,
+        seed = args.seed,
+        "Generating events"
+    );
+
+    // Stop a paced stream cleanly on Ctrl-C, keeping what's been generated
+    let interrupted = tokio::signal::ctrl_c();
+    tokio::pin!(interrupted);
+
+    let started = Instant::now();
+    let mut generated = 0u64;
+    while args.count == 0 || generated < args.count {
+        let offset = interval.mul_f64(generated as f64);
+        let created_at = match args.since {
+            Some(since) => since + TimeDelta::from_std(offset)?,
+            None => tokio::select! {
+                _ = tokio::time::sleep_until(started + offset) => Utc::now(),
+                _ = &mut interrupted => break,
+            },
+        };
+
+        let event = generator.next_event(created_at);
+        sink.write(&serde_json::to_string(&event)?).await?;
+        generated += 1;
+        if generated.is_multiple_of(PROGRESS_EVERY) {
+            tracing::info!(generated, "Generated events");
+        }
+    }
+
+    sink.finish().await?;
+    tracing::info!(
+        generated,
+        elapsed_secs = started.elapsed().as_secs_f64(),
+        "Generation complete"
+    );
+    Ok(())
+}
@@ -0,0 +1,493 @@
+//! Funnel command-line interface.
+//!
+//! One binary for every service and operation, so a deployment ships a single image:
+//!
+//! - `funnel ingest`: stream new events from the relay (or strfry on stdin, or a NATS
+//!   JetStream stream) into ClickHouse
+//! - `funnel backfill`: page through the relay's history (optionally a time range and
+//!   kinds), then exit
+//! - `funnel sync`: fetch only the events ClickHouse is missing from a time range,
+//!   using negentropy
+//! - `funnel api`: serve the REST API
+//! - `funnel aggregate`: run the aggregation workers
+//! - `funnel migrate`: create the database and apply the schema
+//! - `funnel export`: write stored events to a file as JSON lines
+//! - `funnel replay`: insert events from JSON lines, including gzipped archives
+//! - `funnel gen`: generate synthetic events for load tests and demos
+//! - `funnel rebuild-aggregates`: re-derive the derived tables from stored events
+//! - `funnel backup` / `funnel restore`: copy every table to object storage and back
+//!
+//! Every command reads the same environment variables (`CLICKHOUSE_*`, `RELAY_URL`,
+//! and so on; see the README). Flags only cover what's specific to one command.
+
+mod backup;
+mod export;
+mod generate;
+mod rebuild;
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use clap::{Parser, Subcommand};
+use funnel_clickhouse::{ClickHouseClient, ClickHouseConfig, Deployment};
+use funnel_ingestion::replay::{replay, replay_paths};
+use funnel_ingestion::service::{BackfillOptions, BackfillRange};
+use funnel_ingestion::verify::Verifier;
+use funnel_observability::{init_error_reporting, init_tracing_dev, init_tracing_otel};
+use tokio::io::BufReader;
+
+#[derive(Parser)]
+#[command(
+    name = "funnel",
+    version,
+    about = "Nostr video analytics on ClickHouse"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Stream new events from the relay into ClickHouse
+    Ingest {
+        /// Read `strfry stream` or `strfry router` output (JSON lines) from stdin
+        /// instead of connecting to relays
+        #[arg(long, group = "input")]
+        stdin: bool,
+        /// Consume events from the NATS JetStream stream set by `NATS_URL` and
+        /// `NATS_STREAM` (needs the `nats` feature)
+        #[arg(long, group = "input")]
+        nats: bool,
+        /// Relay recorded as the source of events read from stdin or NATS
+        #[arg(long, default_value = "", requires = "input")]
+        source: String,
+    },
+    /// Page through the relay's event history into ClickHouse, then exit
+    Backfill {
+        /// Only events created at or after this time (RFC 3339, or an age such as
+        /// `30d` for 30 days ago)
+        #[arg(long, env = "BACKFILL_SINCE", value_parser = parse_time)]
+        since: Option<DateTime<Utc>>,
+        /// Only events created at or before this time (RFC 3339, or an age such as
+        /// `12h`)
+        #[arg(long, env = "BACKFILL_UNTIL", value_parser = parse_time)]
+        until: Option<DateTime<Utc>>,
+        /// Only these kinds (comma-separated)
+        #[arg(long, env = "BACKFILL_KINDS", value_delimiter = ',')]
+        kinds: Vec<u16>,
+        /// Save progress to this file and resume from it after an interruption
+        #[arg(long, env = "BACKFILL_STATE_FILE")]
+        state_file: Option<PathBuf>,
+        /// Windows to fetch at once; above 1, `--since` is required and progress
+        /// isn't saved
+        #[arg(
+            long,
+            env = "BACKFILL_CONCURRENCY",
+            default_value_t = 1,
+            value_parser = clap::value_parser!(u16).range(1..)
+        )]
+        concurrency: u16,
+        /// Length of each window fetched concurrently, e.g. `6h`
+        #[arg(long, env = "BACKFILL_WINDOW", default_value = "1d", value_parser = parse_window)]
+        window: TimeDelta,
+    },
+    /// Fetch the events in a time range that ClickHouse is missing, using
+    /// negentropy (NIP-77) reconciliation with each relay
+    Sync {
+        /// Only events created at or after this time (RFC 3339, or an age such as
+        /// `2d`)
+        #[arg(long, env = "SYNC_SINCE", value_parser = parse_time)]
+        since: DateTime<Utc>,
+        /// Only events created at or before this time (default: now)
+        #[arg(long, env = "SYNC_UNTIL", value_parser = parse_time)]
+        until: Option<DateTime<Utc>>,
+        /// Only these kinds (comma-separated)
+        #[arg(long, env = "SYNC_KINDS", value_delimiter = ',')]
+        kinds: Vec<u16>,
+        /// Length of each window reconciled at once, e.g. `6h`
+        #[arg(long, env = "SYNC_WINDOW", default_value = "1d", value_parser = parse_window)]
+        window: TimeDelta,
+    },
+    /// Serve the REST API
+    Api,
+    /// Run the aggregation workers (trending, trust, media checks, publishing)
+    Aggregate,
+    /// Create the database and apply the schema
+    Migrate {
+        /// Schema edition: `cloud` (no projections) or `self-hosted`
+        #[arg(long, env = "CLICKHOUSE_DEPLOYMENT", default_value = "cloud")]
+        deployment: Deployment,
+    },
+    /// Write stored events to a file as JSON lines
+    Export(export::ExportArgs),
+    /// Insert events from JSON lines (raw events or strfry stream output)
+    Replay {
+        /// Files, directories, or glob patterns to read (`.gz` files are
+        /// decompressed), or `-` for stdin
+        #[arg(default_value = "-")]
+        inputs: Vec<PathBuf>,
+        /// Relay recorded as the events' source
+        #[arg(long, default_value = "")]
+        source: String,
+        /// Events per insert
+        #[arg(long, env = "BATCH_SIZE", default_value_t = 1000)]
+        batch_size: usize,
+    },
+    /// Generate synthetic video, reaction, comment, and zap events
+    Gen(generate::GenArgs),
+    /// Re-derive tags, engagement counts, reports, and trending from stored events
+    RebuildAggregates(rebuild::RebuildArgs),
+    /// Back up events and every other table to S3-compatible object storage
+    Backup(backup::BackupArgs),
+    /// Restore a backup from object storage
+    Restore(backup::RestoreArgs),
+}
+
+impl Command {
+    /// Service name for traces and error reports, matching the binaries each
+    /// command replaced so dashboards carry over.
+    fn service(&self) -> &'static str {
+        match self {
+            Self::Ingest { .. } | Self::Backfill { .. } | Self::Sync { .. } => "funnel-ingestion",
+            Self::Api => "funnel-api",
+            Self::Aggregate => "funnel-aggregator",
+            Self::Migrate { .. }
+            | Self::Export(_)
+            | Self::Replay { .. }
+            | Self::Gen(_)
+            | Self::RebuildAggregates(_)
+            | Self::Backup(_)
+            | Self::Restore(_) => "funnel-cli",
+        }
+    }
+}
+
+/// Variables `funnel backfill` reads.
+const BACKFILL_VARS: [&str; 6] = [
+    "BACKFILL_SINCE",
+    "BACKFILL_UNTIL",
+    "BACKFILL_KINDS",
+    "BACKFILL_STATE_FILE",
+    "BACKFILL_CONCURRENCY",
+    "BACKFILL_WINDOW",
+];
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    // Before anything runs, so a leftover `BACKFILL=1` can't start the wrong mode
+    if let Command::Ingest { .. } | Command::Backfill { .. } | Command::Sync { .. } = cli.command {
+        let names = env::vars_os().filter_map(|(name, _)| name.into_string().ok());
+        check_backfill_vars(names).map_err(anyhow::Error::msg)?;
+    }
+
+    // Install rustls crypto provider
+    rustls::crypto::ring::default_provider()
+        .install_default()
+        .expect("Failed to install rustls crypto provider");
+
+    // Report panics and errors when built with `sentry` and SENTRY_DSN is set
+    let _error_reporting = init_error_reporting(cli.command.service());
+
+    // Export traces when an OTLP collector is configured
+    let _otel = match env::var("OTEL_EXPORTER_OTLP_ENDPOINT") {
+        Ok(endpoint) => Some(init_tracing_otel(&endpoint, cli.command.service())?),
+        Err(_) => {
+            init_tracing_dev();
+            None
+        }
+    };
+
+    match cli.command {
+        Command::Ingest {
+            stdin: true,
+            source,
+            ..
+        } => funnel_ingestion::service::run_stdin(&source).await,
+        Command::Ingest {
+            nats: true, source, ..
+        } => funnel_ingestion::service::run_nats(&source).await,
+        Command::Ingest { .. } => funnel_ingestion::service::run_live().await,
+        Command::Backfill {
+            since,
+            until,
+            kinds,
+            state_file,
+            concurrency,
+            window,
+        } => {
+            let range = BackfillRange {
+                since,
+                until,
+                kinds,
+            };
+            let options = BackfillOptions {
+                state_file,
+                concurrency: concurrency.into(),
+                window,
+            };
+            funnel_ingestion::service::run_backfill(range, options).await
+        }
+        Command::Sync {
+            since,
+            until,
+            kinds,
+            window,
+        } => {
+            let range = BackfillRange {
+                since: Some(since),
+                until,
+                kinds,
+            };
+            funnel_ingestion::service::run_sync(range, window).await
+        }
+        Command::Api => funnel_api::server::run().await,
+        Command::Aggregate => funnel_aggregator::service::run().await,
+        Command::Migrate { deployment } => migrate(deployment).await,
+        Command::Export(args) => export::run(args).await,
+        Command::Replay {
+            inputs,
+            source,
+            batch_size,
+        } => replay_files(&inputs, &source, batch_size).await,
+        Command::Gen(args) => generate::run(args).await,
+        Command::RebuildAggregates(args) => rebuild::run(args).await,
+        Command::Backup(args) => backup::backup(args).await,
+        Command::Restore(args) => backup::restore(args).await,
+    }
+}
+
+/// Apply the `deployment` schema to the configured database.
+async fn migrate(deployment: Deployment) -> anyhow::Result<()> {
+    let ch_config = ClickHouseConfig::from_env()?;
+    tracing::info!(
+        clickhouse_url = %ch_config.safe_url(),
+        database = %ch_config.database,
+        %deployment,
+        "Applying schema"
+    );
+
+    let clickhouse = ClickHouseClient::connect(&ch_config).await?;
+    let statements = clickhouse.migrate(deployment).await?;
+    tracing::info!(statements, "Schema applied");
+    Ok(())
+}
+
+/// Fail on `BACKFILL`, which picked backfill mode before `funnel backfill` did and
+/// is no longer read, on a misspelling of it, and on `BACKFILL_` variables no
+/// command reads, rather than quietly running another mode or ignoring an option.
+fn check_backfill_vars(names: impl IntoIterator<Item = String>) -> Result<(), String> {
+    for name in names {
+        let upper = name.to_ascii_uppercase();
+        if BACKFILL_VARS.contains(&upper.as_str()) {
+            continue;
+        }
+
+        if within_one_edit(&upper.replace(['_', '-'], ""), "BACKFILL") {
+            return Err(format!(
+                "{name} is set, but backfill mode is now a command: run `funnel backfill` \
+                 (with --since, --until, or --kinds to narrow it), or unset {name} to \
+                 stream with `funnel ingest`"
+            ));
+        }
+        if let Some((prefix, _)) = upper.split_once('_')
+            && within_one_edit(prefix, "BACKFILL")
+        {
+            return Err(format!(
+                "{name} isn't a backfill setting (expected one of {})",
+                BACKFILL_VARS.join(", ")
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Whether `a` and `b` differ by at most one inserted, removed, or replaced byte.
+fn within_one_edit(a: &str, b: &str) -> bool {
+    let (short, long) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    let (short, long) = (short.as_bytes(), long.as_bytes());
+    if long.len() - short.len() > 1 {
+        return false;
+    }
+
+    let common = short.iter().zip(long).take_while(|(x, y)| x == y).count();
+    if common == short.len() {
+        return true;
+    }
+    let rest = if short.len() == long.len() {
+        common + 1
+    } else {
+        common
+    };
+    short[rest..] == long[common + 1..]
+}
+
+/// An RFC 3339 time, or an age counted back from now: a number followed by `s`,
+/// `m`, `h`, `d`, or `w`, e.g. `30d`.
+fn parse_time(value: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+
+    parse_age(value)
+        .and_then(|age| Utc::now().checked_sub_signed(age))
+        .ok_or_else(|| format!("expected an RFC 3339 time or an age like `30d`, got `{value}`"))
+}
+
+/// A backfill or sync window length, e.g. `6h`; see [`parse_age`].
+fn parse_window(value: &str) -> Result<TimeDelta, String> {
+    parse_age(value)
+        .filter(|window| *window > TimeDelta::zero())
+        .ok_or_else(|| format!("expected a length like `1d`, got `{value}`"))
+}
+
+/// A number followed by `s`, `m`, `h`, `d`, or `w`, e.g. `30d`. `None` unless it's
+/// a valid, non-negative length of time.
+fn parse_age(value: &str) -> Option<TimeDelta> {
+    let unit = value.chars().last()?;
+    let amount: i64 = value[..value.len() - unit.len_utf8()].parse().ok()?;
+    match unit {
+        's' => TimeDelta::try_seconds(amount),
+        'm' => TimeDelta::try_minutes(amount),
+        'h' => TimeDelta::try_hours(amount),
+        'd' => TimeDelta::try_days(amount),
+        'w' => TimeDelta::try_weeks(amount),
+        _ => None,
+    }
+    .filter(|age| *age >= TimeDelta::zero())
+}
+
+/// Insert the events in `inputs` (`-` alone for stdin).
+async fn replay_files(inputs: &[PathBuf], source: &str, batch_size: usize) -> anyhow::Result<()> {
+    let clickhouse = ClickHouseClient::connect(&ClickHouseConfig::from_env()?).await?;
+    let verifier = Verifier::from_env();
+
+    let stats = if inputs == [Path::new("-")] {
+        let stdin = BufReader::new(tokio::io::stdin());
+        replay(&clickhouse, stdin, batch_size, source, verifier).await?
+    } else {
+        replay_paths(&clickhouse, inputs, batch_size, source, verifier).await?
+    };
+
+    tracing::info!(
+        inserted = stats.inserted,
+        skipped = stats.skipped,
+        rejected = stats.rejected,
+        "Replay complete"
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(names: &[&str]) -> Result<(), String> {
+        check_backfill_vars(names.iter().map(|name| name.to_string()))
+    }
+
+    #[test]
+    fn backfill_vars_allow_known_settings() {
+        assert!(
+            check(&[
+                "RELAY_URL",
+                "BACKFILL_SINCE",
+                "BACKFILL_WINDOW",
+                "BACKUP_BUCKET"
+            ])
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn backfill_vars_reject_legacy_mode_and_misspellings() {
+        for name in [
+            "BACKFILL",
+            "backfill",
+            "BACKFIL",
+            "BAKFILL",
+            "BACK_FILL",
+            "BACKFILLS",
+        ] {
+            let error = check(&[name]).unwrap_err();
+            assert!(error.contains("funnel backfill"), "{name}: {error}");
+        }
+    }
+
+    #[test]
+    fn backfill_vars_reject_unknown_settings() {
+        for name in ["BACKFILL_SINCEE", "BACKFIL_KINDS", "BACKFILL_STATEFILE"] {
+            let error = check(&[name]).unwrap_err();
+            assert!(
+                error.contains("isn't a backfill setting"),
+                "{name}: {error}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_time_accepts_rfc3339() {
+        assert_eq!(
+            parse_time("2024-06-01T02:00:00+02:00").unwrap(),
+            "2024-06-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_time_counts_ages_back_from_now() {
+        let before = Utc::now();
+        let time = parse_time("30d").unwrap();
+        let after = Utc::now();
+
+        assert!(time >= before - TimeDelta::days(30));
+        assert!(time <= after - TimeDelta::days(30));
+    }
+
+    #[test]
+    fn parse_age_reads_each_unit() {
+        assert_eq!(parse_age("45s"), Some(TimeDelta::seconds(45)));
+        assert_eq!(parse_age("15m"), Some(TimeDelta::minutes(15)));
+        assert_eq!(parse_age("12h"), Some(TimeDelta::hours(12)));
+        assert_eq!(parse_age("30d"), Some(TimeDelta::days(30)));
+        assert_eq!(parse_age("2w"), Some(TimeDelta::weeks(2)));
+    }
+
+    #[test]
+    fn parse_age_allows_zero_but_not_negative() {
+        assert_eq!(parse_age("0d"), Some(TimeDelta::zero()));
+        assert_eq!(parse_age("-1d"), None);
+        assert!(parse_time("0s").is_ok());
+        assert!(parse_time("-1h").is_err());
+        assert!(parse_window("0h").is_err());
+        assert_eq!(parse_window("6h"), Ok(TimeDelta::hours(6)));
+    }
+
+    #[test]
+    fn parse_age_rejects_garbage() {
+        for value in [
+            "",
+            "d",
+            "30",
+            "30x",
+            "1.5d",
+            "d30",
+            "30 d",
+            "99999999999999999w",
+        ] {
+            assert_eq!(parse_age(value), None, "{value:?}");
+        }
+        assert!(parse_time("yesterday").is_err());
+        assert!(parse_time("2024-06-01").is_err());
+    }
+
+    #[test]
+    fn parse_age_rejects_multibyte_units() {
+        for value in ["é", "30é", "3日", "7🙂"] {
+            assert_eq!(parse_age(value), None, "{value:?}");
+            assert!(parse_time(value).is_err(), "{value:?}");
+        }
+    }
+}
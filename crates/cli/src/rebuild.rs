@@ -0,0 +1,162 @@
+//! `funnel rebuild-aggregates`: re-derive the derived tables from stored events.
+//!
+//! Raw events are the source of truth; everything else is derived from them at
+//! insert time. After a fix to how tags are extracted, or a schema change to a
+//! materialized view, this brings what was derived before in line:
+//!
+//! 1. the materialized columns of `events_local` (video title, URL, and so on, which
+//!    the `videos` view reads) are recomputed;
+//! 2. each materialized view's table (tags and hashtags, engagement counts, reports)
+//!    is rebuilt window by window into a staging table, which then replaces it (see
+//!    [`funnel_clickhouse::rebuild`]);
+//! 3. a fresh trending snapshot is computed from the rebuilt counts.
+//!
+//! Progress is saved after every window, so running the command again after an
+//! interruption resumes each table where it stopped; `--restart` starts over.
+//! Events ingested into a window that has already been rebuilt are missed until the
+//! next rebuild, so pause backfills while it runs; live ingestion only touches the
+//! final window, which is rebuilt last.
+
+use chrono::{DateTime, DurationRound, TimeDelta, Utc};
+use clap::Args;
+use funnel_aggregator::trending::{self, TrendingConfig};
+use funnel_clickhouse::rebuild::{Projection, projections};
+use funnel_clickhouse::{ClickHouseClient, ClickHouseConfig, Deployment, RebuildProgress};
+
+#[derive(Args)]
+pub struct RebuildArgs {
+    /// Schema edition whose views are rebuilt: `cloud` or `self-hosted`
+    #[arg(long, env = "CLICKHOUSE_DEPLOYMENT", default_value = "cloud")]
+    deployment: Deployment,
+    /// Days of events read per query
+    #[arg(long, default_value_t = 30, value_parser = clap::value_parser!(u32).range(1..))]
+    window_days: u32,
+    /// Only rebuild these tables (comma-separated, e.g. `reaction_counts`)
+    #[arg(long, value_delimiter = ',')]
+    tables: Vec<String>,
+    /// Start over instead of resuming an interrupted rebuild
+    #[arg(long)]
+    restart: bool,
+    /// Leave the materialized columns of `events_local` alone
+    #[arg(long)]
+    skip_columns: bool,
+    /// Don't compute a trending snapshot afterwards
+    #[arg(long)]
+    skip_trending: bool,
+}
+
+/// Rebuild the derived tables selected by `args`.
+pub async fn run(args: RebuildArgs) -> anyhow::Result<()> {
+    let clickhouse = ClickHouseClient::connect(&ClickHouseConfig::from_env()?).await?;
+
+    let projections: Vec<Projection> = projections(args.deployment.schema())
+        .into_iter()
+        .filter(|projection| args.tables.is_empty() || args.tables.contains(&projection.target))
+        .collect();
+    if let Some(unknown) = args.tables.iter().find(|table| {
+        !projections
+            .iter()
+            .any(|projection| &projection.target == *table)
+    }) {
+        anyhow::bail!("{unknown} is not a table derived by a materialized view");
+    }
+
+    if !args.skip_columns {
+        tracing::info!("Recomputing materialized columns of events_local");
+        let columns = clickhouse.materialize_event_columns().await?;
+        tracing::info!(?columns, "Materialized columns recomputed");
+    }
+
+    let window = TimeDelta::days(args.window_days.into());
+    let oldest = clickhouse.get_oldest_event_time().await?;
+    for projection in &projections {
+        rebuild(&clickhouse, projection, oldest, window, args.restart).await?;
+    }
+
+    if !args.skip_trending {
+        let videos =
+            trending::run_once(&clickhouse, &TrendingConfig::from_env()?, Utc::now()).await?;
+        tracing::info!(videos, "Trending snapshot computed");
+    }
+
+    tracing::info!(tables = projections.len(), "Rebuild complete");
+    Ok(())
+}
+
+/// Rebuild one table from events created since `oldest`, `window` at a time,
+/// resuming an interrupted rebuild unless `restart` is set.
+async fn rebuild(
+    clickhouse: &ClickHouseClient,
+    projection: &Projection,
+    oldest: Option<DateTime<Utc>>,
+    window: TimeDelta,
+    restart: bool,
+) -> anyhow::Result<()> {
+    let target = projection.target.as_str();
+    let resume_from = match clickhouse.get_rebuild_progress(target).await? {
+        Some(progress)
+            if !restart
+                && progress.status == RebuildProgress::STATUS_RUNNING
+                && clickhouse.rebuild_staging_exists(projection).await? =>
+        {
+            Some(progress.rebuilt_until)
+        }
+        _ => None,
+    };
+
+    let mut since = match resume_from {
+        Some(rebuilt_until) => {
+            tracing::info!(target, %rebuilt_until, "Resuming rebuild");
+            rebuilt_until
+        }
+        None => {
+            tracing::info!(target, "Rebuilding");
+            clickhouse.create_rebuild_staging(projection).await?;
+            let oldest = oldest.unwrap_or_else(Utc::now);
+            let since = oldest.duration_trunc(TimeDelta::days(1)).unwrap_or(oldest);
+            // Supersede the progress of any earlier run, whose staging table is gone
+            clickhouse
+                .record_rebuild_progress(&RebuildProgress::new(
+                    target,
+                    since,
+                    RebuildProgress::STATUS_RUNNING,
+                ))
+                .await?;
+            since
+        }
+    };
+
+    // Bounded windows up to the present, then one open-ended window right before the
+    // swap, so events ingested meanwhile are included
+    loop {
+        let until = since + window;
+        if until >= Utc::now() {
+            clickhouse.rebuild_window(projection, since, None).await?;
+            break;
+        }
+
+        clickhouse
+            .rebuild_window(projection, since, Some(until))
+            .await?;
+        clickhouse
+            .record_rebuild_progress(&RebuildProgress::new(
+                target,
+                until,
+                RebuildProgress::STATUS_RUNNING,
+            ))
+            .await?;
+        tracing::info!(target, rebuilt_until = %until, "Rebuilt window");
+        since = until;
+    }
+
+    clickhouse.swap_rebuild_staging(projection).await?;
+    clickhouse
+        .record_rebuild_progress(&RebuildProgress::new(
+            target,
+            Utc::now(),
+            RebuildProgress::STATUS_DONE,
+        ))
+        .await?;
+    tracing::info!(target, "Rebuilt");
+    Ok(())
+}
@@ -0,0 +1,222 @@
+//! Backups in S3-compatible object storage.
+//!
+//! ClickHouse reads and writes the objects itself through its `s3` table function,
+//! so no data passes through Funnel. A backup is a prefix holding one object per
+//! table, or per month of `created_at` for tables that have one, and a manifest
+//! listing every object with its columns, row count, and checksum. The manifest is
+//! written last, so a backup without one is incomplete.
+//!
+//! Rows are copied as stored, without `FINAL`: duplicates still waiting to be merged
+//! away are restored too, and merge the same way. Tables of aggregate states (like
+//! `video_reports`) can't be written as Parquet and are left out; restoring the raw
+//! events re-derives them.
+
+use std::fmt;
+use std::str::FromStr;
+
+use crate::error::ClickHouseError;
+use crate::queries::BackupFile;
+
+/// Name of the manifest object in a backup.
+pub const MANIFEST: &str = "manifest.jsonl";
+
+/// Structure of the manifest, for reading and writing it.
+pub(crate) const MANIFEST_STRUCTURE: &str = "table String, path String, format String, \
+     columns Array(String), types Array(String), rows UInt64, checksum UInt64";
+
+/// Object format for backed-up tables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackupFormat {
+    Parquet,
+    /// One JSON object per line.
+    JsonLines,
+}
+
+impl BackupFormat {
+    /// ClickHouse's name for the format.
+    pub fn clickhouse_format(self) -> &'static str {
+        match self {
+            Self::Parquet => "Parquet",
+            Self::JsonLines => "JSONEachRow",
+        }
+    }
+
+    /// File extension of objects in this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Parquet => "parquet",
+            Self::JsonLines => "jsonl",
+        }
+    }
+}
+
+impl FromStr for BackupFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "parquet" => Ok(Self::Parquet),
+            "jsonl" => Ok(Self::JsonLines),
+            other => Err(format!(
+                "unknown backup format {other:?} (parquet or jsonl)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for BackupFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.extension())
+    }
+}
+
+/// An S3-compatible bucket prefix, and the credentials ClickHouse uses for it.
+#[derive(Clone)]
+pub struct S3Location {
+    /// `https://` URL of the prefix, e.g. `https://bucket.s3.amazonaws.com/funnel`.
+    pub url: String,
+    /// Access key, or `None` to use the server's own credentials.
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+}
+
+impl S3Location {
+    /// Read the location from `BACKUP_S3_URL`, `BACKUP_S3_ACCESS_KEY_ID`, and
+    /// `BACKUP_S3_SECRET_ACCESS_KEY`.
+    pub fn from_env() -> Result<Self, ClickHouseError> {
+        let url = std::env::var("BACKUP_S3_URL")
+            .map_err(|_| ClickHouseError::Config("BACKUP_S3_URL not set".to_string()))?;
+        let access_key_id = std::env::var("BACKUP_S3_ACCESS_KEY_ID").ok();
+        let secret_access_key = std::env::var("BACKUP_S3_SECRET_ACCESS_KEY").ok();
+        if access_key_id.is_some() != secret_access_key.is_some() {
+            return Err(ClickHouseError::Config(
+                "BACKUP_S3_ACCESS_KEY_ID and BACKUP_S3_SECRET_ACCESS_KEY must be set together"
+                    .to_string(),
+            ));
+        }
+
+        Ok(Self {
+            url,
+            access_key_id,
+            secret_access_key,
+        })
+    }
+
+    /// The location of `path` under this one.
+    pub fn join(&self, path: &str) -> Self {
+        Self {
+            url: format!(
+                "{}/{}",
+                self.url.trim_end_matches('/'),
+                path.trim_start_matches('/')
+            ),
+            ..self.clone()
+        }
+    }
+
+    /// `s3(...)` table function reading or writing this object as `format` with
+    /// `structure`.
+    pub(crate) fn table_function(&self, format: &str, structure: &str) -> String {
+        let credentials = match (&self.access_key_id, &self.secret_access_key) {
+            (Some(key), Some(secret)) => format!("{}, {}, ", quote(key), quote(secret)),
+            _ => String::new(),
+        };
+        format!(
+            "s3({}, {credentials}{}, {})",
+            quote(&self.url),
+            quote(format),
+            quote(structure)
+        )
+    }
+}
+
+impl BackupFile {
+    /// Columns and types as a table function structure, e.g. `` `id` String ``.
+    pub fn structure(&self) -> String {
+        structure(&self.columns, &self.types)
+    }
+
+    /// Column names for a `SELECT` or `INSERT` column list.
+    pub fn column_list(&self) -> String {
+        column_list(&self.columns)
+    }
+}
+
+/// Columns and types as a table function structure.
+pub(crate) fn structure(columns: &[String], types: &[String]) -> String {
+    columns
+        .iter()
+        .zip(types)
+        .map(|(column, ty)| format!("`{}` {ty}", column.replace('`', "")))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Column names for a `SELECT` or `INSERT` column list.
+pub(crate) fn column_list(columns: &[String]) -> String {
+    columns
+        .iter()
+        .map(|column| format!("`{}`", column.replace('`', "")))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// `value` as a single-quoted SQL string literal, with `?` doubled so the client
+/// doesn't take it for a bind parameter.
+fn quote(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('\'', "\\'")
+        .replace('?', "??");
+    format!("'{escaped}'")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn table_function_quotes_arguments_and_includes_credentials() {
+        let location = S3Location {
+            url: "https://bucket.example.com/backups/".to_string(),
+            access_key_id: Some("key".to_string()),
+            secret_access_key: Some("it's secret".to_string()),
+        };
+
+        assert_eq!(
+            location
+                .join("nightly/events_local/202401.parquet")
+                .table_function("Parquet", "`id` String"),
+            "s3('https://bucket.example.com/backups/nightly/events_local/202401.parquet', \
+             'key', 'it\\'s secret', 'Parquet', '`id` String')"
+        );
+
+        let anonymous = S3Location {
+            access_key_id: None,
+            secret_access_key: None,
+            ..location
+        };
+        assert_eq!(
+            anonymous
+                .join(MANIFEST)
+                .table_function("JSONEachRow", "a UInt64"),
+            "s3('https://bucket.example.com/backups/manifest.jsonl', 'JSONEachRow', 'a UInt64')"
+        );
+    }
+
+    #[test]
+    fn manifest_entries_describe_their_columns() {
+        let file = BackupFile {
+            table: "events_local".to_string(),
+            path: "events_local/202401.parquet".to_string(),
+            format: BackupFormat::Parquet.clickhouse_format().to_string(),
+            columns: vec!["id".to_string(), "tags".to_string()],
+            types: vec!["String".to_string(), "Array(Array(String))".to_string()],
+            rows: 2,
+            checksum: 7,
+        };
+
+        assert_eq!(file.structure(), "`id` String, `tags` Array(Array(String))");
+        assert_eq!(file.column_list(), "`id`, `tags`");
+    }
+}
@@ -1,22 +1,68 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
 use clickhouse::Client;
 use url::Url;
 
+use crate::backup::{BackupFormat, MANIFEST, MANIFEST_STRUCTURE, S3Location};
 use crate::error::ClickHouseError;
-use crate::queries::{EventRow, TrendingVideo, VideoHashtag, VideoStats};
+use crate::media::live_media_clause;
+use crate::moderation::{self, DEFAULT_REPORT_THRESHOLD};
+use crate::queries::{
+    BackfillRequest, BackupFile, DuplicateCandidate, EventDeletion, EventRow, EventStamp,
+    FileMetadataEvent, FollowEdge, IndexedVideo, IngestActivity, IngestLatency,
+    IngestionCheckpoint, KindCount, MediaHealth, MediaTarget, MediaVerification, PlaylistEvent,
+    ProfileFetch, PubkeyTrust, RebuildProgress, RejectedEvent, RelayStats, RelaySummary,
+    ReportedVideo, RoutedEventRow, TrendingCandidate, TrendingScore, TrendingVideo, VerifyTarget,
+    VideoDetails, VideoDuplicate, VideoHashtag, VideoModeration, VideoStats,
+};
+use crate::rebuild::Projection;
+use crate::routing::EventRoutes;
+use crate::schema::{self, Deployment};
+use crate::slow_query::{self, DEFAULT_SLOW_QUERY_THRESHOLD};
+use crate::tenant::parse_tenants;
+use crate::trust::trusted_filter;
+
+/// Column list for `VideoDetails` rows selected from the `videos` view.
+const VIDEO_DETAILS_COLUMNS: &str = "id, pubkey, created_at, kind, d_tag, title, thumbnail, video_url, \
+     arrayElement(arrayFilter(t -> t[1] = 'duration', tags), 1)[2] AS duration, content";
+
+/// Column list for `PlaylistEvent` rows selected from `events_local`.
+const PLAYLIST_COLUMNS: &str = "id, pubkey, created_at, d_tag, tags";
 
 /// ClickHouse client wrapper with connection pooling and query methods.
 #[derive(Clone)]
 pub struct ClickHouseClient {
     client: Client,
     database: String,
+    slow_query_threshold: Option<Duration>,
+    report_threshold: Option<u64>,
+    hide_dead_media: bool,
+    routes: EventRoutes,
 }
 
 /// Configuration for connecting to ClickHouse.
+#[derive(Clone)]
 pub struct ClickHouseConfig {
     pub url: String,
     pub database: String,
     pub user: Option<String>,
     pub password: Option<String>,
+    /// Queries slower than this are logged; `None` disables slow-query logging.
+    pub slow_query_threshold: Option<Duration>,
+    /// Videos reported by this many distinct pubkeys are hidden from listings;
+    /// `None` hides only videos an operator hid.
+    pub report_threshold: Option<u64>,
+    /// Hide videos whose video URL keeps failing health checks from listings.
+    pub hide_dead_media: bool,
+    /// Tables events of chosen kinds are copied to, besides `events_local`.
+    pub routes: EventRoutes,
+    /// Database of each tenant, by tenant ID.
+    pub tenants: BTreeMap<String, String>,
+    /// Tenant this config is scoped to; `database` is then the tenant's database.
+    pub tenant: Option<String>,
 }
 
 impl ClickHouseConfig {
@@ -27,18 +73,79 @@ impl ClickHouseConfig {
     /// - `CLICKHOUSE_DATABASE` (optional): Database name, defaults to "nostr"
     /// - `CLICKHOUSE_USER` (optional): Username, defaults to "default"
     /// - `CLICKHOUSE_PASSWORD` (optional): Password
+    /// - `CLICKHOUSE_SLOW_QUERY_MS` (optional): Slow-query log threshold in
+    ///   milliseconds, defaults to 1000; `0` disables it
+    /// - `MODERATION_REPORT_THRESHOLD` (optional): Distinct reporters that hide a
+    ///   video from listings, defaults to 5; `0` disables report-based hiding
+    /// - `MEDIA_HEALTH_HIDE_DEAD` (optional): `true` hides videos with dead media
+    ///   from listings, defaults to `false`
+    /// - `EVENT_ROUTES` (optional): Narrow tables events of chosen kinds are also
+    ///   written to, as `kind=table` pairs separated by commas
+    /// - `CLICKHOUSE_TENANTS` (optional): Tenant databases as `tenant=database`
+    ///   pairs separated by commas
+    /// - `TENANT` (optional): Scope this process to one tenant of
+    ///   `CLICKHOUSE_TENANTS`, using its database instead of `CLICKHOUSE_DATABASE`
     pub fn from_env() -> Result<Self, ClickHouseError> {
         let url = std::env::var("CLICKHOUSE_URL")
             .map_err(|_| ClickHouseError::Config("CLICKHOUSE_URL not set".to_string()))?;
         let database = std::env::var("CLICKHOUSE_DATABASE").unwrap_or_else(|_| "nostr".to_string());
         let user = std::env::var("CLICKHOUSE_USER").ok();
         let password = std::env::var("CLICKHOUSE_PASSWORD").ok();
+        let slow_query_threshold = match std::env::var("CLICKHOUSE_SLOW_QUERY_MS") {
+            Ok(ms) => {
+                let ms: u64 = ms.parse().map_err(|_| {
+                    ClickHouseError::Config(format!("Invalid CLICKHOUSE_SLOW_QUERY_MS: {ms}"))
+                })?;
+                Some(Duration::from_millis(ms)).filter(|threshold| !threshold.is_zero())
+            }
+            Err(_) => Some(DEFAULT_SLOW_QUERY_THRESHOLD),
+        };
+        let report_threshold = match std::env::var("MODERATION_REPORT_THRESHOLD") {
+            Ok(count) => {
+                let count: u64 = count.parse().map_err(|_| {
+                    ClickHouseError::Config(format!("Invalid MODERATION_REPORT_THRESHOLD: {count}"))
+                })?;
+                Some(count).filter(|count| *count > 0)
+            }
+            Err(_) => Some(DEFAULT_REPORT_THRESHOLD),
+        };
+        let hide_dead_media = std::env::var("MEDIA_HEALTH_HIDE_DEAD")
+            .ok()
+            .and_then(|s| s.parse::<bool>().ok())
+            .unwrap_or(false);
+        let routes = EventRoutes::parse(&std::env::var("EVENT_ROUTES").unwrap_or_default())?;
+        let tenants = parse_tenants(&std::env::var("CLICKHOUSE_TENANTS").unwrap_or_default())?;
 
-        Ok(Self {
+        let config = Self {
             url,
             database,
             user,
             password,
+            slow_query_threshold,
+            report_threshold,
+            hide_dead_media,
+            routes,
+            tenants,
+            tenant: None,
+        };
+        match std::env::var("TENANT").ok().filter(|s| !s.is_empty()) {
+            Some(tenant) => config.for_tenant(&tenant),
+            None => Ok(config),
+        }
+    }
+
+    /// This config scoped to `tenant`, connecting to its database.
+    pub fn for_tenant(&self, tenant: &str) -> Result<Self, ClickHouseError> {
+        let database = self.tenants.get(tenant).ok_or_else(|| {
+            ClickHouseError::Config(format!(
+                "Unknown tenant {tenant:?} (not in CLICKHOUSE_TENANTS)"
+            ))
+        })?;
+
+        Ok(Self {
+            database: database.clone(),
+            tenant: Some(tenant.to_string()),
+            ..self.clone()
         })
     }
 
@@ -83,9 +190,22 @@ impl ClickHouseClient {
         Ok(Self {
             client,
             database: config.database.clone(),
+            slow_query_threshold: config.slow_query_threshold,
+            report_threshold: config.report_threshold,
+            hide_dead_media: config.hide_dead_media,
+            routes: config.routes.clone(),
         })
     }
 
+    /// Create a client from configuration and check that the server answers.
+    pub async fn connect(config: &ClickHouseConfig) -> Result<Self, ClickHouseError> {
+        let client = Self::from_config(config)?;
+        client.ping().await?;
+        let version = client.version().await?;
+        tracing::info!(version = %version, "Connected to ClickHouse");
+        Ok(client)
+    }
+
     /// Create a new client connected to the given URL (legacy, for tests).
     #[doc(hidden)]
     pub fn new(url: &str, database: &str) -> Result<Self, ClickHouseError> {
@@ -94,10 +214,42 @@ impl ClickHouseClient {
             database: database.to_string(),
             user: Some("default".to_string()),
             password: None,
+            slow_query_threshold: Some(DEFAULT_SLOW_QUERY_THRESHOLD),
+            report_threshold: Some(DEFAULT_REPORT_THRESHOLD),
+            hide_dead_media: false,
+            routes: EventRoutes::default(),
+            tenants: BTreeMap::new(),
+            tenant: None,
         };
         Self::from_config(&config)
     }
 
+    /// Run `query` in a span named `operation`, logging it with `params()` if it is
+    /// slower than the configured threshold.
+    pub(crate) async fn observe<T, F, P>(
+        &self,
+        operation: &'static str,
+        params: P,
+        query: F,
+    ) -> Result<T, ClickHouseError>
+    where
+        F: Future<Output = Result<T, ClickHouseError>>,
+        P: FnOnce() -> String,
+    {
+        slow_query::observe(self.slow_query_threshold, operation, params, query).await
+    }
+
+    /// `WHERE` condition excluding videos hidden by moderation, or with dead media when
+    /// configured to, matched on `id_column`.
+    fn visible(&self, id_column: &str) -> String {
+        let visible = moderation::visible_clause(id_column, self.report_threshold);
+        if self.hide_dead_media {
+            format!("{visible} AND {}", live_media_clause(id_column))
+        } else {
+            visible
+        }
+    }
+
     /// Test the connection by running a simple query.
     pub async fn ping(&self) -> Result<(), ClickHouseError> {
         self.client.query("SELECT 1").execute().await?;
@@ -111,7 +263,471 @@ impl ClickHouseClient {
         Ok(version)
     }
 
-    /// Insert a batch of events into the events_local table.
+    /// Create the configured database if needed and apply the `deployment` schema to
+    /// it, returning how many statements ran. Old views that became tables are
+    /// dropped first.
+    pub async fn migrate(&self, deployment: Deployment) -> Result<usize, ClickHouseError> {
+        // The database may not exist yet, so it's created from the default one
+        self.client
+            .clone()
+            .with_database("default")
+            .query(&format!(
+                "CREATE DATABASE IF NOT EXISTS `{}`",
+                self.database.replace('`', "")
+            ))
+            .execute()
+            .await?;
+
+        for view in schema::REPLACED_VIEWS {
+            let views: u64 = self
+                .client
+                .query(
+                    "SELECT count() FROM system.tables \
+                     WHERE database = currentDatabase() AND name = ? AND engine = 'View'",
+                )
+                .bind(view)
+                .fetch_one()
+                .await?;
+            if views > 0 {
+                tracing::info!(view, "Dropping view replaced by a table");
+                self.client
+                    .query(&format!("DROP VIEW {view}"))
+                    .execute()
+                    .await?;
+            }
+        }
+
+        let statements = schema::statements(deployment.schema());
+        for statement in &statements {
+            let preview: String = statement.chars().take(70).collect();
+            tracing::info!(statement = %preview, "Applying schema statement");
+            self.client.query(statement).execute().await?;
+        }
+        Ok(statements.len())
+    }
+
+    /// Recompute the materialized columns of `events_local` (video title, URL, and so
+    /// on) for every stored event, waiting until done. Returns the columns recomputed.
+    pub async fn materialize_event_columns(&self) -> Result<Vec<String>, ClickHouseError> {
+        let columns: Vec<String> = self
+            .client
+            .query(
+                "SELECT name FROM system.columns \
+                 WHERE database = currentDatabase() AND table = 'events_local' \
+                   AND default_kind = 'MATERIALIZED' \
+                 ORDER BY position",
+            )
+            .fetch_all()
+            .await?;
+
+        for column in &columns {
+            self.client
+                .query(&format!(
+                    "ALTER TABLE events_local MATERIALIZE COLUMN `{column}`"
+                ))
+                .with_option("mutations_sync", "2")
+                .execute()
+                .await?;
+        }
+        Ok(columns)
+    }
+
+    /// Get the creation time of the oldest stored event, if there are any.
+    pub async fn get_oldest_event_time(&self) -> Result<Option<DateTime<Utc>>, ClickHouseError> {
+        let oldest: Option<i64> = self
+            .client
+            .query("SELECT toInt64(min(created_at)) FROM events_local HAVING count() > 0")
+            .fetch_optional()
+            .await?;
+
+        Ok(oldest.and_then(|timestamp| DateTime::from_timestamp(timestamp, 0)))
+    }
+
+    /// Get how far the last rebuild of `target` got.
+    pub async fn get_rebuild_progress(
+        &self,
+        target: &str,
+    ) -> Result<Option<RebuildProgress>, ClickHouseError> {
+        let result = self
+            .client
+            .query(
+                "SELECT target, rebuilt_until, status, updated_at \
+                 FROM aggregate_rebuilds FINAL WHERE target = ?",
+            )
+            .bind(target)
+            .fetch_optional()
+            .await?;
+
+        Ok(result)
+    }
+
+    /// Record how far a rebuild has got.
+    pub async fn record_rebuild_progress(
+        &self,
+        progress: &RebuildProgress,
+    ) -> Result<(), ClickHouseError> {
+        let mut insert = self.client.insert("aggregate_rebuilds")?;
+        insert.write(progress).await?;
+        insert.end().await?;
+        Ok(())
+    }
+
+    /// Whether a rebuild of `projection` has a staging table to resume into.
+    pub async fn rebuild_staging_exists(
+        &self,
+        projection: &Projection,
+    ) -> Result<bool, ClickHouseError> {
+        let tables: u64 = self
+            .client
+            .query(
+                "SELECT count() FROM system.tables \
+                 WHERE database = currentDatabase() AND name = ?",
+            )
+            .bind(projection.staging())
+            .fetch_one()
+            .await?;
+
+        Ok(tables > 0)
+    }
+
+    /// Start a rebuild of `projection` over from an empty staging table.
+    pub async fn create_rebuild_staging(
+        &self,
+        projection: &Projection,
+    ) -> Result<(), ClickHouseError> {
+        for table in [projection.staging(), projection.window_table()] {
+            self.client
+                .query(&format!("DROP TABLE IF EXISTS {table}"))
+                .execute()
+                .await?;
+            self.client
+                .query(&format!("CREATE TABLE {table} AS {}", projection.target))
+                .execute()
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Add the rows `projection` derives from events created in `[since, until)` (or
+    /// from `since` on) to its staging table.
+    ///
+    /// Rows are written to a scratch table first and attached to the staging table in
+    /// one step, so a window that fails part way leaves nothing behind.
+    pub async fn rebuild_window(
+        &self,
+        projection: &Projection,
+        since: DateTime<Utc>,
+        until: Option<DateTime<Utc>>,
+    ) -> Result<(), ClickHouseError> {
+        let window = projection.window_table();
+        self.client
+            .query(&format!("TRUNCATE TABLE {window}"))
+            .execute()
+            .await?;
+        self.client
+            .query(&format!(
+                "INSERT INTO {window} {}",
+                projection.select_window(since.timestamp(), until.map(|until| until.timestamp()))
+            ))
+            .execute()
+            .await?;
+        self.client
+            .query(&format!(
+                "ALTER TABLE {} ATTACH PARTITION tuple() FROM {window}",
+                projection.staging()
+            ))
+            .execute()
+            .await?;
+        Ok(())
+    }
+
+    /// Replace the contents of `projection`'s table with its staging table in one
+    /// step, and drop the staging tables.
+    pub async fn swap_rebuild_staging(
+        &self,
+        projection: &Projection,
+    ) -> Result<(), ClickHouseError> {
+        self.client
+            .query(&format!(
+                "ALTER TABLE {} REPLACE PARTITION tuple() FROM {}",
+                projection.target,
+                projection.staging()
+            ))
+            .execute()
+            .await?;
+        for table in [projection.staging(), projection.window_table()] {
+            self.client
+                .query(&format!("DROP TABLE IF EXISTS {table}"))
+                .execute()
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Get the tables a backup copies: every MergeTree table except rebuild staging
+    /// tables and tables of aggregate states, `events_local` first.
+    pub async fn get_backup_tables(&self) -> Result<Vec<String>, ClickHouseError> {
+        let tables = self
+            .client
+            .query(
+                "SELECT name FROM system.tables \
+                 WHERE database = currentDatabase() AND engine LIKE '%MergeTree' \
+                   AND NOT endsWith(name, '_rebuild') AND NOT endsWith(name, '_rebuild_window') \
+                   AND name NOT IN ( \
+                       SELECT table FROM system.columns \
+                       WHERE database = currentDatabase() AND type LIKE 'AggregateFunction%' \
+                   ) \
+                 ORDER BY name != 'events_local', name",
+            )
+            .fetch_all()
+            .await?;
+
+        Ok(tables)
+    }
+
+    /// Get the stored columns of `table` that can be inserted into (not materialized,
+    /// aliases, or ephemeral), with their types.
+    pub async fn get_insertable_columns(
+        &self,
+        table: &str,
+    ) -> Result<Vec<(String, String)>, ClickHouseError> {
+        let columns = self
+            .client
+            .query(
+                "SELECT name, type FROM system.columns \
+                 WHERE database = currentDatabase() AND table = ? \
+                   AND default_kind NOT IN ('MATERIALIZED', 'ALIAS', 'EPHEMERAL') \
+                 ORDER BY position",
+            )
+            .bind(table)
+            .fetch_all()
+            .await?;
+
+        Ok(columns)
+    }
+
+    /// Get the months (`YYYYMM`) of `created_at` that `table` has rows for.
+    pub async fn get_backup_partitions(&self, table: &str) -> Result<Vec<u32>, ClickHouseError> {
+        let months = self
+            .client
+            .query(&format!(
+                "SELECT DISTINCT toYYYYMM(created_at) AS month FROM {table} ORDER BY month"
+            ))
+            .fetch_all()
+            .await?;
+
+        Ok(months)
+    }
+
+    /// Write the rows of `table` to `path` under `backup`, only those created in
+    /// `month` (`YYYYMM`) if set, and return the object's manifest entry.
+    pub async fn backup_table(
+        &self,
+        backup: &S3Location,
+        path: &str,
+        table: &str,
+        month: Option<u32>,
+        format: BackupFormat,
+    ) -> Result<BackupFile, ClickHouseError> {
+        let (columns, types): (Vec<String>, Vec<String>) = self
+            .get_insertable_columns(table)
+            .await?
+            .into_iter()
+            .unzip();
+        let mut file = BackupFile {
+            table: table.to_string(),
+            path: path.to_string(),
+            format: format.clickhouse_format().to_string(),
+            columns,
+            types,
+            rows: 0,
+            checksum: 0,
+        };
+
+        let filter = month
+            .map(|month| format!(" WHERE toYYYYMM(created_at) = {month}"))
+            .unwrap_or_default();
+        self.client
+            .query(&format!(
+                "INSERT INTO FUNCTION {} SELECT {} FROM {table}{filter}",
+                backup
+                    .join(path)
+                    .table_function(&file.format, &file.structure()),
+                file.column_list()
+            ))
+            .execute()
+            .await?;
+
+        // Checksum what was written rather than the table, which may have changed since
+        (file.rows, file.checksum) = self.get_backup_checksum(backup, &file).await?;
+        Ok(file)
+    }
+
+    /// Count and checksum the rows of a backup object as stored, to compare with its
+    /// manifest entry.
+    pub async fn get_backup_checksum(
+        &self,
+        backup: &S3Location,
+        file: &BackupFile,
+    ) -> Result<(u64, u64), ClickHouseError> {
+        let result = self
+            .client
+            .query(&format!(
+                "SELECT count(), groupBitXor(cityHash64(*)) FROM {}",
+                backup
+                    .join(&file.path)
+                    .table_function(&file.format, &file.structure())
+            ))
+            .fetch_one()
+            .await?;
+
+        Ok(result)
+    }
+
+    /// Write the manifest listing `files` to `backup`.
+    pub async fn write_backup_manifest(
+        &self,
+        backup: &S3Location,
+        files: &[BackupFile],
+    ) -> Result<(), ClickHouseError> {
+        if files.is_empty() {
+            return Err(ClickHouseError::Config(
+                "a backup manifest needs at least one file".to_string(),
+            ));
+        }
+
+        let values = vec!["(?, ?, ?, ?, ?, ?, ?)"; files.len()].join(", ");
+        let mut query = self.client.query(&format!(
+            "INSERT INTO FUNCTION {} VALUES {values}",
+            backup
+                .join(MANIFEST)
+                .table_function("JSONEachRow", MANIFEST_STRUCTURE)
+        ));
+        for file in files {
+            query = query
+                .bind(&file.table)
+                .bind(&file.path)
+                .bind(&file.format)
+                .bind(&file.columns)
+                .bind(&file.types)
+                .bind(file.rows)
+                .bind(file.checksum);
+        }
+        query.execute().await?;
+        Ok(())
+    }
+
+    /// Read the manifest of `backup`.
+    pub async fn read_backup_manifest(
+        &self,
+        backup: &S3Location,
+    ) -> Result<Vec<BackupFile>, ClickHouseError> {
+        let files = self
+            .client
+            .query(&format!(
+                "SELECT table, path, format, columns, types, rows, checksum FROM {}",
+                backup
+                    .join(MANIFEST)
+                    .table_function("JSONEachRow", MANIFEST_STRUCTURE)
+            ))
+            .fetch_all()
+            .await?;
+
+        Ok(files)
+    }
+
+    /// Insert the rows of a backup object into `table`.
+    pub async fn restore_backup_file(
+        &self,
+        backup: &S3Location,
+        file: &BackupFile,
+        table: &str,
+    ) -> Result<(), ClickHouseError> {
+        let columns = file.column_list();
+        self.client
+            .query(&format!(
+                "INSERT INTO {table} ({columns}) SELECT {columns} FROM {}",
+                backup
+                    .join(&file.path)
+                    .table_function(&file.format, &file.structure())
+            ))
+            .execute()
+            .await?;
+        Ok(())
+    }
+
+    /// Get stored events oldest first, for exports.
+    ///
+    /// `after` is the `(created_at, id)` of the last event of the previous page; only
+    /// later events are returned. Empty `kinds` matches every kind.
+    pub async fn get_events_after(
+        &self,
+        after: Option<(DateTime<Utc>, &str)>,
+        until: Option<DateTime<Utc>>,
+        kinds: &[u16],
+        limit: u32,
+    ) -> Result<Vec<EventRow>, ClickHouseError> {
+        let mut conditions = vec!["1"];
+        if after.is_some() {
+            conditions.push("(created_at, id) > (toDateTime(?), ?)");
+        }
+        if until.is_some() {
+            conditions.push("created_at <= toDateTime(?)");
+        }
+        if !kinds.is_empty() {
+            conditions.push("has(?, kind)");
+        }
+
+        let mut query = self.client.query(&format!(
+            "SELECT id, pubkey, created_at, kind, content, sig, tags, relay_source, ingested_at \
+             FROM events_local FINAL WHERE {} ORDER BY created_at, id LIMIT ?",
+            conditions.join(" AND ")
+        ));
+        if let Some((created_at, id)) = after {
+            query = query.bind(created_at.timestamp()).bind(id);
+        }
+        if let Some(until) = until {
+            query = query.bind(until.timestamp());
+        }
+        if !kinds.is_empty() {
+            query = query.bind(kinds);
+        }
+
+        let results = query.bind(limit).fetch_all().await?;
+        Ok(results)
+    }
+
+    /// Get the ID and creation time of every stored event created in `[since, until]`,
+    /// for negentropy sync. Empty `kinds` matches every kind.
+    pub async fn get_event_stamps(
+        &self,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+        kinds: &[u16],
+    ) -> Result<Vec<EventStamp>, ClickHouseError> {
+        let kind_condition = if kinds.is_empty() {
+            ""
+        } else {
+            " AND has(?, kind)"
+        };
+        let mut query = self
+            .client
+            .query(&format!(
+                "SELECT DISTINCT id, created_at FROM events_local \
+                 WHERE created_at >= toDateTime(?) AND created_at <= toDateTime(?){kind_condition}"
+            ))
+            .bind(since.timestamp())
+            .bind(until.timestamp());
+        if !kinds.is_empty() {
+            query = query.bind(kinds);
+        }
+
+        let results = query.fetch_all().await?;
+        Ok(results)
+    }
+
+    /// Insert a batch of events into the events_local table, and copy the kinds with
+    /// a route to their tables.
     pub async fn insert_events(&self, events: &[EventRow]) -> Result<(), ClickHouseError> {
         if events.is_empty() {
             return Ok(());
@@ -136,6 +752,29 @@ impl ClickHouseClient {
         }
 
         tracing::info!(count = events.len(), "Inserted events batch");
+
+        for (table, rows) in self.routes.route(events) {
+            self.insert_routed(table, &rows).await?;
+        }
+        Ok(())
+    }
+
+    /// Insert events copied to the routed table `table`.
+    async fn insert_routed(
+        &self,
+        table: &str,
+        rows: &[RoutedEventRow],
+    ) -> Result<(), ClickHouseError> {
+        let mut insert = self.client.insert(table)?;
+        for row in rows {
+            insert.write(row).await?;
+        }
+        if let Err(e) = insert.end().await {
+            tracing::error!(table, error = %e, count = rows.len(), "Failed to commit routed events");
+            return Err(e.into());
+        }
+
+        tracing::debug!(table, count = rows.len(), "Inserted routed events");
         Ok(())
     }
 
@@ -162,7 +801,11 @@ impl ClickHouseClient {
     ) -> Result<Vec<VideoStats>, ClickHouseError> {
         let results = self
             .client
-            .query("SELECT * FROM video_stats WHERE pubkey = ? ORDER BY created_at DESC LIMIT ?")
+            .query(&format!(
+                "SELECT * FROM video_stats WHERE pubkey = ? AND {} \
+                 ORDER BY created_at DESC LIMIT ?",
+                self.visible("id")
+            ))
             .bind(pubkey)
             .bind(limit)
             .fetch_all()
@@ -171,14 +814,24 @@ impl ClickHouseClient {
         Ok(results)
     }
 
-    /// Get trending videos.
+    /// Get trending videos from the latest `trending_videos` snapshot, optionally
+    /// only from creators with at least `min_trust`.
     pub async fn get_trending_videos(
         &self,
+        min_trust: Option<f64>,
         limit: u32,
     ) -> Result<Vec<TrendingVideo>, ClickHouseError> {
         let results = self
             .client
-            .query("SELECT * FROM trending_videos LIMIT ?")
+            .query(&format!(
+                "SELECT id, pubkey, created_at, kind, d_tag, title, thumbnail, \
+                        reactions, comments, reposts, engagement_score, trending_score \
+                 FROM trending_videos \
+                 WHERE computed_at = (SELECT max(computed_at) FROM trending_videos) AND {} {} \
+                 ORDER BY trending_score DESC LIMIT ?",
+                self.visible("id"),
+                trusted_filter("pubkey", min_trust)
+            ))
             .bind(limit)
             .fetch_all()
             .await?;
@@ -186,40 +839,76 @@ impl ClickHouseClient {
         Ok(results)
     }
 
-    /// Get recent videos, optionally filtered by kind.
-    pub async fn get_recent_videos(
+    /// Get videos created after `since` with their engagement counts.
+    pub async fn get_trending_candidates(
         &self,
-        kind: Option<u16>,
-        limit: u32,
-    ) -> Result<Vec<VideoStats>, ClickHouseError> {
-        let query = match kind {
-            Some(k) => self
-                .client
-                .query("SELECT * FROM video_stats WHERE kind = ? ORDER BY created_at DESC LIMIT ?")
-                .bind(k)
-                .bind(limit),
-            None => self
-                .client
-                .query("SELECT * FROM video_stats ORDER BY created_at DESC LIMIT ?")
-                .bind(limit),
-        };
+        since: DateTime<Utc>,
+    ) -> Result<Vec<TrendingCandidate>, ClickHouseError> {
+        let results = self
+            .client
+            .query(
+                "SELECT s.id, s.pubkey, s.created_at, s.kind, s.d_tag, s.title, s.thumbnail, \
+                        s.reactions, s.comments, s.reposts, ifNull(z.zap_count, 0) AS zaps \
+                 FROM video_stats AS s \
+                 LEFT JOIN ( \
+                     SELECT target_event_id, sum(zap_count) AS zap_count \
+                     FROM zap_counts GROUP BY target_event_id \
+                 ) AS z ON s.id = z.target_event_id \
+                 WHERE s.created_at > toDateTime(?)",
+            )
+            .bind(since.timestamp())
+            .fetch_all()
+            .await?;
 
-        let results = query.fetch_all().await?;
         Ok(results)
     }
 
-    /// Search videos by hashtag.
-    pub async fn search_by_hashtag(
+    /// Write a `trending_videos` snapshot.
+    pub async fn insert_trending_scores(
         &self,
-        hashtag: &str,
+        scores: &[TrendingScore],
+    ) -> Result<(), ClickHouseError> {
+        if scores.is_empty() {
+            return Ok(());
+        }
+
+        let mut insert = self.client.insert("trending_videos")?;
+        for score in scores {
+            insert.write(score).await?;
+        }
+        insert.end().await?;
+        Ok(())
+    }
+
+    /// Get media URLs of videos created after `created_after` that were last checked
+    /// before `checked_before` (or never), newest videos first.
+    pub async fn get_media_targets(
+        &self,
+        created_after: DateTime<Utc>,
+        checked_before: DateTime<Utc>,
         limit: u32,
-    ) -> Result<Vec<VideoHashtag>, ClickHouseError> {
+    ) -> Result<Vec<MediaTarget>, ClickHouseError> {
         let results = self
             .client
-            .query(
-                "SELECT * FROM video_hashtags WHERE hashtag = ? ORDER BY created_at DESC LIMIT ?",
-            )
-            .bind(hashtag)
+            .query(&format!(
+                "SELECT m.event_id, m.url, m.media_type, h.failures \
+                 FROM ( \
+                     SELECT id AS event_id, created_at, media.1 AS media_type, media.2 AS url \
+                     FROM videos \
+                     ARRAY JOIN [('{video}', video_url), ('{thumbnail}', thumbnail)] AS media \
+                     WHERE created_at > toDateTime(?) AND media.2 != '' \
+                 ) AS m \
+                 LEFT JOIN ( \
+                     SELECT event_id, url, failures, checked_at FROM media_health FINAL \
+                 ) AS h ON m.event_id = h.event_id AND m.url = h.url \
+                 WHERE h.checked_at < fromUnixTimestamp64Milli(?) \
+                 ORDER BY m.created_at DESC \
+                 LIMIT ?",
+                video = MediaHealth::MEDIA_VIDEO,
+                thumbnail = MediaHealth::MEDIA_THUMBNAIL,
+            ))
+            .bind(created_after.timestamp())
+            .bind(checked_before.timestamp_millis())
             .bind(limit)
             .fetch_all()
             .await?;
@@ -227,46 +916,680 @@ impl ClickHouseClient {
         Ok(results)
     }
 
-    /// Full-text search videos by title.
+    /// Get video files of videos created after `created_after` whose `imeta` tag
+    /// declares a SHA-256 and that aren't verified yet, skipping those last checked
+    /// at or after `checked_before`, newest videos first.
     ///
-    /// Uses `hasTokenCaseInsensitive` for word-boundary matching.
-    /// Searches each word in the query independently.
-    pub async fn search_by_text(
+    /// The file URL is the `url` in the same `imeta` tag, or the video's `url` tag.
+    pub async fn get_verify_targets(
         &self,
-        query: &str,
+        created_after: DateTime<Utc>,
+        checked_before: DateTime<Utc>,
         limit: u32,
-    ) -> Result<Vec<VideoStats>, ClickHouseError> {
-        // Split query into tokens and filter empty strings
-        let tokens: Vec<&str> = query.split_whitespace().collect();
+    ) -> Result<Vec<VerifyTarget>, ClickHouseError> {
+        let results = self
+            .client
+            .query(&format!(
+                "SELECT v.event_id, v.url, v.sha256 \
+                 FROM ( \
+                     SELECT id AS event_id, created_at, \
+                            arrayFirst( \
+                                t -> t[1] = 'imeta' \
+                                     AND arrayExists(e -> startsWith(e, 'x '), t), \
+                                tags \
+                            ) AS imeta, \
+                            lower(substring( \
+                                arrayFirst(e -> startsWith(e, 'x '), imeta), 3 \
+                            )) AS sha256, \
+                            substring( \
+                                arrayFirst(e -> startsWith(e, 'url '), imeta), 5 \
+                            ) AS imeta_url, \
+                            if(imeta_url != '', imeta_url, video_url) AS url \
+                     FROM videos \
+                     WHERE created_at > toDateTime(?) \
+                 ) AS v \
+                 LEFT JOIN ( \
+                     SELECT event_id, sha256, status, checked_at FROM media_verification FINAL \
+                 ) AS m ON v.event_id = m.event_id AND v.sha256 = m.sha256 \
+                 WHERE match(v.sha256, '^[0-9a-f]{{64}}$') AND v.url != '' \
+                   AND m.status != '{verified}' \
+                   AND m.checked_at < fromUnixTimestamp64Milli(?) \
+                 ORDER BY v.created_at DESC \
+                 LIMIT ?",
+                verified = MediaVerification::STATUS_VERIFIED,
+            ))
+            .bind(created_after.timestamp())
+            .bind(checked_before.timestamp_millis())
+            .bind(limit)
+            .fetch_all()
+            .await?;
 
-        if tokens.is_empty() {
-            return Ok(vec![]);
+        Ok(results)
+    }
+
+    /// Record hash verifications.
+    pub async fn insert_media_verifications(
+        &self,
+        verifications: &[MediaVerification],
+    ) -> Result<(), ClickHouseError> {
+        if verifications.is_empty() {
+            return Ok(());
         }
 
-        // Build WHERE clause: all tokens must match (AND)
-        let conditions: Vec<String> = tokens
-            .iter()
-            .map(|_| "hasTokenCaseInsensitive(title, ?)".to_string())
-            .collect();
-        let where_clause = conditions.join(" AND ");
+        let mut insert = self.client.insert("media_verification")?;
+        for verification in verifications {
+            insert.write(verification).await?;
+        }
+        insert.end().await?;
+        Ok(())
+    }
 
-        let sql = format!(
-            "SELECT * FROM video_stats WHERE {} ORDER BY created_at DESC LIMIT ?",
-            where_clause
-        );
+    /// Get videos created after `created_after` with their title and the SHA-256
+    /// declared in their `imeta` tag (empty when there is none).
+    pub async fn get_duplicate_candidates(
+        &self,
+        created_after: DateTime<Utc>,
+    ) -> Result<Vec<DuplicateCandidate>, ClickHouseError> {
+        let results = self
+            .client
+            .query(
+                "SELECT id, pubkey, created_at, title, \
+                        lower(substring( \
+                            arrayFirst( \
+                                e -> startsWith(e, 'x '), \
+                                arrayFirst( \
+                                    t -> t[1] = 'imeta' \
+                                         AND arrayExists(e -> startsWith(e, 'x '), t), \
+                                    tags \
+                                ) \
+                            ), 3 \
+                        )) AS sha256 \
+                 FROM videos \
+                 WHERE created_at > toDateTime(?)",
+            )
+            .bind(created_after.timestamp())
+            .fetch_all()
+            .await?;
 
-        let mut query_builder = self.client.query(&sql);
+        Ok(results)
+    }
 
-        // Bind each token
-        for token in &tokens {
-            query_builder = query_builder.bind(*token);
+    /// Write a `video_duplicates` snapshot.
+    pub async fn insert_video_duplicates(
+        &self,
+        duplicates: &[VideoDuplicate],
+    ) -> Result<(), ClickHouseError> {
+        if duplicates.is_empty() {
+            return Ok(());
         }
 
-        // Bind limit
-        query_builder = query_builder.bind(limit);
-
-        let results = query_builder.fetch_all().await?;
-        Ok(results)
+        let mut insert = self.client.insert("video_duplicates")?;
+        for duplicate in duplicates {
+            insert.write(duplicate).await?;
+        }
+        insert.end().await?;
+        Ok(())
+    }
+
+    /// Get authors of videos created after `created_after` whose newest profile
+    /// (kind 0) was stored before `stale_before` (or never), and who weren't fetched
+    /// since then either, newest videos first.
+    pub async fn get_profile_targets(
+        &self,
+        created_after: DateTime<Utc>,
+        stale_before: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<Vec<String>, ClickHouseError> {
+        let results = self
+            .client
+            .query(
+                "SELECT v.pubkey \
+                 FROM ( \
+                     SELECT pubkey, max(created_at) AS last_video FROM videos \
+                     WHERE created_at > toDateTime(?) GROUP BY pubkey \
+                 ) AS v \
+                 LEFT JOIN ( \
+                     SELECT pubkey, max(indexed_at) AS stored_at FROM events_local \
+                     WHERE kind = 0 GROUP BY pubkey \
+                 ) AS p ON v.pubkey = p.pubkey \
+                 LEFT JOIN ( \
+                     SELECT pubkey, fetched_at FROM profile_fetches FINAL \
+                 ) AS f ON v.pubkey = f.pubkey \
+                 WHERE p.stored_at < toDateTime(?) \
+                   AND f.fetched_at < fromUnixTimestamp64Milli(?) \
+                 ORDER BY v.last_video DESC \
+                 LIMIT ?",
+            )
+            .bind(created_after.timestamp())
+            .bind(stale_before.timestamp())
+            .bind(stale_before.timestamp_millis())
+            .bind(limit)
+            .fetch_all()
+            .await?;
+
+        Ok(results)
+    }
+
+    /// Record profile fetches.
+    pub async fn insert_profile_fetches(
+        &self,
+        fetches: &[ProfileFetch],
+    ) -> Result<(), ClickHouseError> {
+        if fetches.is_empty() {
+            return Ok(());
+        }
+
+        let mut insert = self.client.insert("profile_fetches")?;
+        for fetch in fetches {
+            insert.write(fetch).await?;
+        }
+        insert.end().await?;
+        Ok(())
+    }
+
+    /// Record media health checks.
+    pub async fn insert_media_health(&self, checks: &[MediaHealth]) -> Result<(), ClickHouseError> {
+        if checks.is_empty() {
+            return Ok(());
+        }
+
+        let mut insert = self.client.insert("media_health")?;
+        for check in checks {
+            insert.write(check).await?;
+        }
+        insert.end().await?;
+        Ok(())
+    }
+
+    /// Get follows from each pubkey's latest contact list (kind 3).
+    pub async fn get_follow_edges(&self) -> Result<Vec<FollowEdge>, ClickHouseError> {
+        let results = self
+            .client
+            .query(
+                "SELECT DISTINCT pubkey AS follower, tag[2] AS followed \
+                 FROM ( \
+                     SELECT pubkey, argMax(tags, created_at) AS tags \
+                     FROM events_local WHERE kind = 3 GROUP BY pubkey \
+                 ) \
+                 ARRAY JOIN tags AS tag \
+                 WHERE tag[1] = 'p' AND length(tag[2]) = 64 AND tag[2] != pubkey",
+            )
+            .fetch_all()
+            .await?;
+
+        Ok(results)
+    }
+
+    /// Write a `pubkey_trust` snapshot.
+    pub async fn insert_pubkey_trust(&self, scores: &[PubkeyTrust]) -> Result<(), ClickHouseError> {
+        if scores.is_empty() {
+            return Ok(());
+        }
+
+        let mut insert = self.client.insert("pubkey_trust")?;
+        for score in scores {
+            insert.write(score).await?;
+        }
+        insert.end().await?;
+        Ok(())
+    }
+
+    /// Get recent videos, optionally filtered by kind and creator trust.
+    pub async fn get_recent_videos(
+        &self,
+        kind: Option<u16>,
+        min_trust: Option<f64>,
+        limit: u32,
+    ) -> Result<Vec<VideoStats>, ClickHouseError> {
+        let kind_filter = if kind.is_some() { "AND kind = ?" } else { "" };
+        let query = self.client.query(&format!(
+            "SELECT * FROM video_stats WHERE {} {kind_filter} {} \
+             ORDER BY created_at DESC LIMIT ?",
+            self.visible("id"),
+            trusted_filter("pubkey", min_trust)
+        ));
+        let query = match kind {
+            Some(k) => query.bind(k),
+            None => query,
+        };
+
+        let results = query.bind(limit).fetch_all().await?;
+        Ok(results)
+    }
+
+    /// Search videos by hashtag, newest first.
+    ///
+    /// `before` is the `(created_at, event_id)` of the last result of the previous
+    /// page; only older results are returned.
+    pub async fn search_by_hashtag(
+        &self,
+        hashtag: &str,
+        before: Option<(DateTime<Utc>, &str)>,
+        limit: u32,
+    ) -> Result<Vec<VideoHashtag>, ClickHouseError> {
+        let before_filter = if before.is_some() {
+            "AND (created_at, event_id) < (toDateTime(?), ?)"
+        } else {
+            ""
+        };
+        let query = self
+            .client
+            .query(&format!(
+                "SELECT * FROM video_hashtags WHERE hashtag = ? AND {} {before_filter} \
+                 ORDER BY created_at DESC, event_id DESC LIMIT ?",
+                self.visible("event_id")
+            ))
+            .bind(hashtag);
+        let query = match before {
+            Some((created_at, id)) => query.bind(created_at.timestamp()).bind(id),
+            None => query,
+        };
+
+        let results = query.bind(limit).fetch_all().await?;
+        Ok(results)
+    }
+
+    /// Count videos with a hashtag, stopping at `cap`.
+    pub async fn count_by_hashtag(&self, hashtag: &str, cap: u64) -> Result<u64, ClickHouseError> {
+        let count: u64 = self
+            .client
+            .query(&format!(
+                "SELECT count() FROM (SELECT 1 FROM video_hashtags WHERE hashtag = ? AND {} LIMIT ?)",
+                self.visible("event_id")
+            ))
+            .bind(hashtag)
+            .bind(cap)
+            .fetch_one()
+            .await?;
+
+        Ok(count)
+    }
+
+    /// Get video details (including media URL) by event ID.
+    pub async fn get_video_details(
+        &self,
+        event_id: &str,
+    ) -> Result<Option<VideoDetails>, ClickHouseError> {
+        let result = self
+            .client
+            .query(&format!(
+                "SELECT {VIDEO_DETAILS_COLUMNS} FROM videos WHERE id = ? LIMIT 1"
+            ))
+            .bind(event_id)
+            .fetch_optional()
+            .await?;
+
+        Ok(result)
+    }
+
+    /// Get the tombstone for a deleted video, if the event is an indexed video.
+    ///
+    /// Tombstones for events that were never indexed, or aren't videos, are ignored.
+    pub async fn get_video_deletion(
+        &self,
+        event_id: &str,
+    ) -> Result<Option<EventDeletion>, ClickHouseError> {
+        let result = self
+            .client
+            .query(
+                "SELECT event_id, deleted_at, deleted_by, deletion_event_id, reason \
+                 FROM event_deletions \
+                 WHERE event_id = ? \
+                   AND event_id IN (SELECT id FROM events_local WHERE kind IN (34235, 34236)) \
+                 ORDER BY deleted_at DESC LIMIT 1",
+            )
+            .bind(event_id)
+            .fetch_optional()
+            .await?;
+
+        Ok(result)
+    }
+
+    /// Get the latest health check of each of a video's media URLs.
+    pub async fn get_media_health(
+        &self,
+        event_id: &str,
+    ) -> Result<Vec<MediaHealth>, ClickHouseError> {
+        let results = self
+            .client
+            .query(
+                "SELECT event_id, url, media_type, available, status, content_type, \
+                        failures, checked_at \
+                 FROM media_health FINAL \
+                 WHERE event_id = ? \
+                 ORDER BY media_type DESC, url",
+            )
+            .bind(event_id)
+            .fetch_all()
+            .await?;
+
+        Ok(results)
+    }
+
+    /// Get the latest hash verification of a video's file.
+    pub async fn get_media_verification(
+        &self,
+        event_id: &str,
+    ) -> Result<Option<MediaVerification>, ClickHouseError> {
+        let result = self
+            .client
+            .query(
+                "SELECT event_id, sha256, url, status, source, checked_at \
+                 FROM media_verification FINAL \
+                 WHERE event_id = ? \
+                 ORDER BY checked_at DESC \
+                 LIMIT 1",
+            )
+            .bind(event_id)
+            .fetch_optional()
+            .await?;
+
+        Ok(result)
+    }
+
+    /// Get the newest file metadata (kind 1063) event for a video's file, matched by
+    /// the SHA-256 declared in the video's `imeta` tag.
+    pub async fn get_file_metadata(
+        &self,
+        event_id: &str,
+    ) -> Result<Option<FileMetadataEvent>, ClickHouseError> {
+        let result = self
+            .client
+            .query(
+                "WITH ( \
+                     SELECT lower(substring( \
+                         arrayFirst( \
+                             e -> startsWith(e, 'x '), \
+                             arrayFirst( \
+                                 t -> t[1] = 'imeta' \
+                                      AND arrayExists(e -> startsWith(e, 'x '), t), \
+                                 tags \
+                             ) \
+                         ), 3 \
+                     )) \
+                     FROM videos WHERE id = ? LIMIT 1 \
+                 ) AS sha256 \
+                 SELECT id, tags FROM events_local FINAL \
+                 WHERE kind = 1063 AND sha256 != '' \
+                   AND arrayExists(t -> t[1] = 'x' AND lower(t[2]) = sha256, tags) \
+                 ORDER BY created_at DESC LIMIT 1",
+            )
+            .bind(event_id)
+            .fetch_optional()
+            .await?;
+
+        Ok(result)
+    }
+
+    /// Get the latest `video_duplicates` snapshot's rows for a video: what it
+    /// re-uploads, and what re-uploads it.
+    pub async fn get_video_duplicates(
+        &self,
+        event_id: &str,
+    ) -> Result<Vec<VideoDuplicate>, ClickHouseError> {
+        let results = self
+            .client
+            .query(
+                "SELECT computed_at, event_id, duplicate_of, reason \
+                 FROM video_duplicates \
+                 WHERE computed_at = (SELECT max(computed_at) FROM video_duplicates) \
+                   AND (event_id = ? OR duplicate_of = ?) \
+                 ORDER BY event_id",
+            )
+            .bind(event_id)
+            .bind(event_id)
+            .fetch_all()
+            .await?;
+
+        Ok(results)
+    }
+
+    /// Get recent video details (including media URL) for a hashtag, optionally only
+    /// from creators with at least `min_trust`.
+    pub async fn get_video_details_by_hashtag(
+        &self,
+        hashtag: &str,
+        min_trust: Option<f64>,
+        limit: u32,
+    ) -> Result<Vec<VideoDetails>, ClickHouseError> {
+        let results = self
+            .client
+            .query(&format!(
+                "SELECT {VIDEO_DETAILS_COLUMNS} FROM videos \
+                 WHERE id IN (SELECT event_id FROM event_tags_flat WHERE tag_name = 't' AND tag_value_primary = ?) \
+                   AND {} {} \
+                 ORDER BY created_at DESC LIMIT ?",
+                self.visible("id"),
+                trusted_filter("pubkey", min_trust)
+            ))
+            .bind(hashtag)
+            .bind(limit)
+            .fetch_all()
+            .await?;
+
+        Ok(results)
+    }
+
+    /// Get recent video details (including media URL) for an author.
+    pub async fn get_video_details_by_author(
+        &self,
+        pubkey: &str,
+        limit: u32,
+    ) -> Result<Vec<VideoDetails>, ClickHouseError> {
+        let results = self
+            .client
+            .query(&format!(
+                "SELECT {VIDEO_DETAILS_COLUMNS} FROM videos WHERE pubkey = ? AND {} \
+                 ORDER BY created_at DESC LIMIT ?",
+                self.visible("id")
+            ))
+            .bind(pubkey)
+            .bind(limit)
+            .fetch_all()
+            .await?;
+
+        Ok(results)
+    }
+
+    /// Get the latest version of a video set (kind 30005) by author and d-tag.
+    pub async fn get_playlist(
+        &self,
+        pubkey: &str,
+        d_tag: &str,
+    ) -> Result<Option<PlaylistEvent>, ClickHouseError> {
+        let result = self
+            .client
+            .query(&format!(
+                "SELECT {PLAYLIST_COLUMNS} FROM events_local \
+                 WHERE kind = 30005 AND pubkey = ? AND d_tag = ? \
+                   AND id NOT IN (SELECT event_id FROM event_deletions) \
+                 ORDER BY created_at DESC LIMIT 1"
+            ))
+            .bind(pubkey)
+            .bind(d_tag)
+            .fetch_optional()
+            .await?;
+
+        Ok(result)
+    }
+
+    /// Get the latest version of each of an author's video sets, newest first.
+    pub async fn get_playlists_by_author(
+        &self,
+        pubkey: &str,
+        limit: u32,
+    ) -> Result<Vec<PlaylistEvent>, ClickHouseError> {
+        let results = self
+            .client
+            .query(&format!(
+                "SELECT {PLAYLIST_COLUMNS} FROM events_local \
+                 WHERE kind = 30005 AND pubkey = ? \
+                   AND id NOT IN (SELECT event_id FROM event_deletions) \
+                 ORDER BY created_at DESC LIMIT 1 BY d_tag LIMIT ?"
+            ))
+            .bind(pubkey)
+            .bind(limit)
+            .fetch_all()
+            .await?;
+
+        Ok(results)
+    }
+
+    /// Get stats for videos referenced by event ID or by `kind:pubkey:d-tag` address.
+    ///
+    /// Addresses resolve to the latest version of the video. Rows come back in no
+    /// particular order.
+    pub async fn get_videos_by_refs(
+        &self,
+        event_ids: &[String],
+        addresses: &[String],
+    ) -> Result<Vec<VideoStats>, ClickHouseError> {
+        if event_ids.is_empty() && addresses.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let results = self
+            .client
+            .query(&format!(
+                "SELECT * FROM video_stats WHERE has(?, id) AND {visible} \
+                 UNION ALL \
+                 SELECT * FROM ( \
+                     SELECT * FROM video_stats \
+                     WHERE has(?, concat(toString(kind), ':', pubkey, ':', d_tag)) \
+                     ORDER BY created_at DESC LIMIT 1 BY kind, pubkey, d_tag \
+                 ) WHERE {visible}",
+                visible = self.visible("id")
+            ))
+            .bind(event_ids)
+            .bind(addresses)
+            .fetch_all()
+            .await?;
+
+        Ok(results)
+    }
+
+    /// Get videos indexed after the `(indexed_at, id)` cursor, oldest first.
+    ///
+    /// Ordering by index time rather than `created_at` means backfilled and
+    /// back-dated videos still show up once, when they arrive.
+    pub async fn get_videos_indexed_after(
+        &self,
+        indexed_at: DateTime<Utc>,
+        id: &str,
+        kind: Option<u16>,
+        limit: u32,
+    ) -> Result<Vec<IndexedVideo>, ClickHouseError> {
+        let kind_filter = if kind.is_some() { "AND kind = ?" } else { "" };
+        let query = self
+            .client
+            .query(&format!(
+                "SELECT s.id, s.pubkey, s.created_at, s.kind, s.d_tag, s.title, s.thumbnail, \
+                        s.reactions, s.comments, s.reposts, s.engagement_score, v.indexed_at \
+                 FROM video_stats AS s \
+                 INNER JOIN ( \
+                     SELECT id, indexed_at FROM videos \
+                     WHERE (indexed_at, id) > (toDateTime(?), ?) AND {} {kind_filter} \
+                     ORDER BY indexed_at, id LIMIT ? \
+                 ) AS v ON s.id = v.id \
+                 ORDER BY v.indexed_at, s.id",
+                self.visible("id")
+            ))
+            .bind(indexed_at.timestamp())
+            .bind(id);
+        let query = match kind {
+            Some(k) => query.bind(k),
+            None => query,
+        };
+
+        let results = query.bind(limit).fetch_all().await?;
+        Ok(results)
+    }
+
+    /// Get the time the most recent video was indexed, or `None` when there are none.
+    pub async fn get_latest_video_indexed_at(
+        &self,
+    ) -> Result<Option<DateTime<Utc>>, ClickHouseError> {
+        let latest = self
+            .client
+            .query("SELECT toInt64(toUnixTimestamp(max(indexed_at))) FROM videos")
+            .fetch_one::<i64>()
+            .await?;
+
+        Ok(match latest {
+            0 => None,
+            ts => DateTime::from_timestamp(ts, 0),
+        })
+    }
+
+    /// Full-text search videos by title, newest first.
+    ///
+    /// Uses `hasTokenCaseInsensitive` for word-boundary matching.
+    /// Searches each word in the query independently. `before` is the
+    /// `(created_at, id)` of the last result of the previous page.
+    pub async fn search_by_text(
+        &self,
+        query: &str,
+        before: Option<(DateTime<Utc>, &str)>,
+        limit: u32,
+    ) -> Result<Vec<VideoStats>, ClickHouseError> {
+        // Split query into tokens and filter empty strings
+        let tokens: Vec<&str> = query.split_whitespace().collect();
+
+        if tokens.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let before_filter = if before.is_some() {
+            " AND (created_at, id) < (toDateTime(?), ?)"
+        } else {
+            ""
+        };
+        let sql = format!(
+            "SELECT * FROM video_stats WHERE {} AND {}{} ORDER BY created_at DESC, id DESC LIMIT ?",
+            title_match_clause(tokens.len()),
+            self.visible("id"),
+            before_filter
+        );
+
+        let mut query_builder = self.client.query(&sql);
+
+        // Bind each token
+        for token in &tokens {
+            query_builder = query_builder.bind(*token);
+        }
+
+        if let Some((created_at, id)) = before {
+            query_builder = query_builder.bind(created_at.timestamp()).bind(id);
+        }
+
+        // Bind limit
+        query_builder = query_builder.bind(limit);
+
+        let results = query_builder.fetch_all().await?;
+        Ok(results)
+    }
+
+    /// Count videos matching a full-text title search, stopping at `cap`.
+    pub async fn count_by_text(&self, query: &str, cap: u64) -> Result<u64, ClickHouseError> {
+        let tokens: Vec<&str> = query.split_whitespace().collect();
+
+        if tokens.is_empty() {
+            return Ok(0);
+        }
+
+        let sql = format!(
+            "SELECT count() FROM (SELECT 1 FROM video_stats WHERE {} AND {} LIMIT ?)",
+            title_match_clause(tokens.len()),
+            self.visible("id")
+        );
+
+        let mut query_builder = self.client.query(&sql);
+        for token in &tokens {
+            query_builder = query_builder.bind(*token);
+        }
+
+        let count: u64 = query_builder.bind(cap).fetch_one().await?;
+        Ok(count)
     }
 
     /// Get event count.
@@ -291,6 +1614,135 @@ impl ClickHouseClient {
         Ok(count)
     }
 
+    /// Get stored event counts per kind, largest first.
+    pub async fn get_kind_counts(&self) -> Result<Vec<KindCount>, ClickHouseError> {
+        let results = self
+            .client
+            .query(
+                "SELECT kind, count() AS count FROM events_local GROUP BY kind ORDER BY count DESC",
+            )
+            .fetch_all()
+            .await?;
+
+        Ok(results)
+    }
+
+    /// Get recent ingestion activity.
+    ///
+    /// Windows are based on `indexed_at`, so backfilled events count when they are
+    /// inserted rather than when they were created. Future `created_at` values (clock
+    /// skew) are ignored for the newest event.
+    pub async fn get_ingest_activity(&self) -> Result<IngestActivity, ClickHouseError> {
+        let activity = self
+            .client
+            .query(
+                "SELECT \
+                 countIf(indexed_at >= now() - INTERVAL 1 HOUR) AS events_last_hour, \
+                 countIf(indexed_at >= now() - INTERVAL 1 DAY) AS events_last_day, \
+                 (SELECT count() FROM videos WHERE indexed_at >= toStartOfDay(now(), 'UTC')) AS videos_today, \
+                 maxIf(created_at, created_at <= now()) AS latest_event_at \
+                 FROM events_local",
+            )
+            .fetch_one()
+            .await?;
+
+        Ok(activity)
+    }
+
+    /// Get latency percentiles for events inserted in the last hour, per pipeline stage.
+    ///
+    /// Only events with an `ingested_at` (from strfry or the live ingester) count, so
+    /// backfills don't skew them. `indexed_at` has second precision, which limits the
+    /// received→inserted figures; the ingester's `ingestion_event_latency_seconds`
+    /// histogram is exact.
+    pub async fn get_ingest_latency(&self) -> Result<IngestLatency, ClickHouseError> {
+        let latency = self
+            .client
+            .query(
+                "SELECT count() AS events, \
+                 quantiles(0.5, 0.9, 0.99)(greatest(dateDiff('millisecond', created_at, assumeNotNull(ingested_at)), 0) / 1000) AS created_to_received, \
+                 quantiles(0.5, 0.9, 0.99)(greatest(dateDiff('millisecond', assumeNotNull(ingested_at), indexed_at), 0) / 1000) AS received_to_inserted \
+                 FROM events_local \
+                 WHERE ingested_at IS NOT NULL AND indexed_at >= now() - INTERVAL 1 HOUR",
+            )
+            .fetch_one()
+            .await?;
+
+        Ok(latency)
+    }
+
+    /// Check whether the event with `id` can be read back yet.
+    pub async fn has_event(&self, id: &str) -> Result<bool, ClickHouseError> {
+        let count: u64 = self
+            .client
+            .query("SELECT count() FROM events_local WHERE id = ?")
+            .bind(id)
+            .fetch_one()
+            .await?;
+
+        Ok(count > 0)
+    }
+
+    /// Record per-relay counters from the live ingester.
+    pub async fn insert_relay_stats(&self, stats: &[RelayStats]) -> Result<(), ClickHouseError> {
+        if stats.is_empty() {
+            return Ok(());
+        }
+
+        let mut insert = self.client.insert("relay_stats")?;
+        for row in stats {
+            insert.write(row).await?;
+        }
+        insert.end().await?;
+        Ok(())
+    }
+
+    /// Record events the live ingester received but didn't store.
+    pub async fn insert_rejected_events(
+        &self,
+        events: &[RejectedEvent],
+    ) -> Result<(), ClickHouseError> {
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        let mut insert = self.client.insert("events_rejected")?;
+        for row in events {
+            insert.write(row).await?;
+        }
+        insert.end().await?;
+        Ok(())
+    }
+
+    /// Get each relay's counters since `since`, relays contributing the most unique
+    /// videos first.
+    pub async fn get_relay_stats(
+        &self,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<RelaySummary>, ClickHouseError> {
+        let results = self
+            .client
+            .query(
+                "SELECT relay_url, \
+                 sum(events_received) AS events_received, \
+                 sum(unique_events) AS unique_events, \
+                 sum(unique_videos) AS unique_videos, \
+                 sum(duplicates) AS duplicates, \
+                 sum(parse_failures) AS parse_failures, \
+                 argMax(connected, recorded_at) AS connected, \
+                 argMax(uptime_secs, recorded_at) AS uptime_secs, \
+                 max(recorded_at) AS last_recorded_at \
+                 FROM relay_stats WHERE recorded_at >= toDateTime(?) \
+                 GROUP BY relay_url \
+                 ORDER BY unique_videos DESC, unique_events DESC, relay_url",
+            )
+            .bind(since.timestamp())
+            .fetch_all()
+            .await?;
+
+        Ok(results)
+    }
+
     /// Check if the schema is set up.
     pub async fn check_schema(&self) -> Result<bool, ClickHouseError> {
         let count: u64 = self
@@ -303,12 +1755,205 @@ impl ClickHouseClient {
         Ok(count > 0)
     }
 
+    /// Get the worst replication delay (seconds) across replicated tables in the database.
+    ///
+    /// Returns 0 when the database has no replicated tables.
+    pub async fn get_replication_lag(&self) -> Result<u64, ClickHouseError> {
+        let lag: u64 = self
+            .client
+            .query("SELECT toUInt64(max(absolute_delay)) FROM system.replicas WHERE database = ?")
+            .bind(&self.database)
+            .fetch_one()
+            .await?;
+
+        Ok(lag)
+    }
+
     /// Execute a raw DDL statement (for schema setup).
     pub async fn execute_ddl(&self, ddl: &str) -> Result<(), ClickHouseError> {
         self.client.query(ddl).execute().await?;
         Ok(())
     }
 
+    /// Record a tombstone for an event.
+    pub async fn insert_deletion(&self, deletion: &EventDeletion) -> Result<(), ClickHouseError> {
+        let mut insert = self.client.insert("event_deletions")?;
+        insert.write(deletion).await?;
+        insert.end().await?;
+
+        tracing::info!(event_id = %deletion.event_id, deleted_by = %deletion.deleted_by, "Recorded event deletion");
+        Ok(())
+    }
+
+    /// Record tombstones for several events at once.
+    pub async fn insert_deletions(
+        &self,
+        deletions: &[EventDeletion],
+    ) -> Result<(), ClickHouseError> {
+        if deletions.is_empty() {
+            return Ok(());
+        }
+
+        let mut insert = self.client.insert("event_deletions")?;
+        for deletion in deletions {
+            insert.write(deletion).await?;
+        }
+        insert.end().await?;
+        Ok(())
+    }
+
+    /// Get the IDs of the stored events a NIP-09 deletion request deletes: events by
+    /// its author that it names by ID, and versions of its addresses created up to
+    /// the request. Deletion requests themselves can't be deleted.
+    pub async fn get_deletion_targets(
+        &self,
+        request: &funnel_proto::DeletionMeta,
+    ) -> Result<Vec<String>, ClickHouseError> {
+        let mut targets = Vec::new();
+        if !request.event_ids.is_empty() {
+            targets.push("has(?, id)");
+        }
+        if !request.addresses.is_empty() {
+            targets.push("(has(?, (kind, d_tag)) AND created_at <= toDateTime(?))");
+        }
+        if targets.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut query = self.client.query(&format!(
+            "SELECT DISTINCT id FROM events_local \
+             WHERE pubkey = ? AND kind != {} AND ({})",
+            funnel_proto::KIND_DELETION,
+            targets.join(" OR ")
+        ));
+        query = query.bind(&request.pubkey);
+        if !request.event_ids.is_empty() {
+            query = query.bind(&request.event_ids);
+        }
+        if !request.addresses.is_empty() {
+            let addresses: Vec<(u16, &str)> = request
+                .addresses
+                .iter()
+                .map(|address| (address.kind, address.identifier.as_str()))
+                .collect();
+            query = query.bind(addresses).bind(request.created_at.timestamp());
+        }
+
+        let ids = query.fetch_all().await?;
+        Ok(ids)
+    }
+
+    /// Remove events with a lightweight delete. Rows are hidden at once and dropped
+    /// on later merges; tables the materialized views wrote aren't changed.
+    pub async fn delete_events(&self, ids: &[String]) -> Result<(), ClickHouseError> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        self.client
+            .query("DELETE FROM events_local WHERE has(?, id)")
+            .bind(ids)
+            .execute()
+            .await?;
+        Ok(())
+    }
+
+    /// Write a backfill request (new, or a status update for an existing one).
+    pub async fn upsert_backfill_request(
+        &self,
+        request: &BackfillRequest,
+    ) -> Result<(), ClickHouseError> {
+        let mut insert = self.client.insert("backfill_requests")?;
+        insert.write(request).await?;
+        insert.end().await?;
+        Ok(())
+    }
+
+    /// Get backfill requests that have not been picked up yet, oldest first.
+    pub async fn get_pending_backfill_requests(
+        &self,
+    ) -> Result<Vec<BackfillRequest>, ClickHouseError> {
+        let results = self
+            .client
+            .query(
+                "SELECT id, since, until, kinds, status, requested_at, updated_at \
+                 FROM backfill_requests FINAL WHERE status = ? ORDER BY requested_at",
+            )
+            .bind(BackfillRequest::STATUS_PENDING)
+            .fetch_all()
+            .await?;
+
+        Ok(results)
+    }
+
+    /// Record an operator moderation decision for a video.
+    pub async fn upsert_video_moderation(
+        &self,
+        moderation: &VideoModeration,
+    ) -> Result<(), ClickHouseError> {
+        let mut insert = self.client.insert("video_moderation")?;
+        insert.write(moderation).await?;
+        insert.end().await?;
+
+        tracing::info!(
+            event_id = %moderation.event_id,
+            status = %moderation.status,
+            updated_by = %moderation.updated_by,
+            "Recorded video moderation"
+        );
+        Ok(())
+    }
+
+    /// Get reported videos, most distinct reporters first.
+    pub async fn get_reported_videos(
+        &self,
+        limit: u32,
+    ) -> Result<Vec<ReportedVideo>, ClickHouseError> {
+        let results = self
+            .client
+            .query(&format!(
+                "SELECT r.target_event_id AS event_id, v.pubkey, v.title, \
+                        r.reports, r.reporters, r.report_types, r.last_reported_at, \
+                        m.status, NOT ({}) AS hidden \
+                 FROM ( \
+                     SELECT target_event_id, sum(reports) AS reports, \
+                            uniqMerge(reporters) AS reporters, \
+                            groupUniqArray(report_type) AS report_types, \
+                            max(last_reported_at) AS last_reported_at \
+                     FROM video_reports GROUP BY target_event_id \
+                 ) AS r \
+                 INNER JOIN videos AS v ON r.target_event_id = v.id \
+                 LEFT JOIN ( \
+                     SELECT event_id, status FROM video_moderation FINAL \
+                 ) AS m ON r.target_event_id = m.event_id \
+                 ORDER BY r.reporters DESC, r.last_reported_at DESC \
+                 LIMIT ?",
+                self.visible("r.target_event_id")
+            ))
+            .bind(limit)
+            .fetch_all()
+            .await?;
+
+        Ok(results)
+    }
+
+    /// Get ingestion progress grouped by relay source.
+    pub async fn get_ingestion_checkpoints(
+        &self,
+    ) -> Result<Vec<IngestionCheckpoint>, ClickHouseError> {
+        let results = self
+            .client
+            .query(
+                "SELECT relay_source, max(created_at) AS latest_event_at, \
+                 max(indexed_at) AS last_indexed_at, count() AS event_count \
+                 FROM events_local GROUP BY relay_source ORDER BY relay_source",
+            )
+            .fetch_all()
+            .await?;
+
+        Ok(results)
+    }
+
     /// Get the latest event timestamp for catch-up sync.
     ///
     /// Returns the maximum `created_at` timestamp (as Unix timestamp) from the events table,
@@ -331,3 +1976,8 @@ impl ClickHouseClient {
         }
     }
 }
+
+/// `WHERE` condition matching titles that contain all of `tokens` words, one bind each.
+fn title_match_clause(tokens: usize) -> String {
+    vec!["hasTokenCaseInsensitive(title, ?)"; tokens].join(" AND ")
+}
@@ -1,3 +1,4 @@
+use funnel_proto::ErrorCode;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -14,3 +15,74 @@ pub enum ClickHouseError {
     #[error("invalid configuration: {0}")]
     Config(String),
 }
+
+impl ClickHouseError {
+    /// Stable code for this kind of failure.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::Connection(_) => ErrorCode::ClickHouseConnection,
+            Self::Query(_) => ErrorCode::ClickHouseQuery,
+            Self::Serialization(_) => ErrorCode::ClickHouseSerialization,
+            Self::Config(_) => ErrorCode::ClickHouseConfig,
+        }
+    }
+
+    /// Whether retrying the same request could succeed: network failures, timeouts,
+    /// and server errors that come from load or availability rather than the query
+    /// itself. Errors in the query, its data, or the schema are permanent.
+    pub fn is_transient(&self) -> bool {
+        use clickhouse::error::Error;
+
+        match self {
+            Self::Connection(_) => true,
+            Self::Query(Error::Network(_) | Error::TimedOut) => true,
+            // Responses without a server error code come from a proxy or load balancer
+            Self::Query(Error::BadResponse(message)) => {
+                server_error_code(message).is_none_or(|code| TRANSIENT_SERVER_CODES.contains(&code))
+            }
+            Self::Query(_) | Self::Serialization(_) | Self::Config(_) => false,
+        }
+    }
+}
+
+/// ClickHouse server error codes worth retrying: timeouts, network errors, memory
+/// and concurrency limits, too many parts, read-only replicas, and unknown insert
+/// status.
+const TRANSIENT_SERVER_CODES: &[u32] = &[159, 202, 209, 210, 241, 242, 252, 319];
+
+/// The code in a server error message such as `Code: 60. DB::Exception: ...`.
+fn server_error_code(message: &str) -> Option<u32> {
+    let (_, rest) = message.split_once("Code: ")?;
+    let digits = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    rest[..digits].parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bad_response(message: &str) -> ClickHouseError {
+        ClickHouseError::Query(clickhouse::error::Error::BadResponse(message.to_string()))
+    }
+
+    #[test]
+    fn classifies_transient_errors() {
+        assert!(ClickHouseError::Connection("refused".to_string()).is_transient());
+        assert!(ClickHouseError::Query(clickhouse::error::Error::TimedOut).is_transient());
+        assert!(bad_response("Code: 252. DB::Exception: Too many parts (300).").is_transient());
+        assert!(bad_response("<html>502 Bad Gateway</html>").is_transient());
+    }
+
+    #[test]
+    fn classifies_permanent_errors() {
+        assert!(
+            !bad_response("Code: 60. DB::Exception: Table nostr.events doesn't exist.")
+                .is_transient()
+        );
+        assert!(!bad_response("Code: 16. DB::Exception: No such column foo.").is_transient());
+        assert!(!ClickHouseError::Config("bad url".to_string()).is_transient());
+        assert!(!ClickHouseError::Query(clickhouse::error::Error::NotEnoughData).is_transient());
+    }
+}
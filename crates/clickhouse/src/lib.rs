@@ -3,12 +3,34 @@
 //! Provides connection management, query builders, and batch insertion
 //! for Nostr events.
 
+pub mod backup;
 mod client;
 mod error;
+mod media;
+mod moderation;
 pub mod queries;
+pub mod rebuild;
+pub mod routing;
+pub mod schema;
+mod slow_query;
+pub mod tenant;
 pub mod traits;
+mod trust;
 
 pub use self::client::{ClickHouseClient, ClickHouseConfig};
 pub use self::error::ClickHouseError;
-pub use self::queries::{EventRow, TrendingVideo, VideoHashtag, VideoStats};
-pub use self::traits::{EventWriter, StatsQueries, VideoQueries};
+pub use self::media::DEAD_AFTER_FAILURES;
+pub use self::queries::{
+    BackfillRequest, BackupFile, DuplicateCandidate, EventDeletion, EventRow, EventStamp,
+    FileMetadataEvent, FollowEdge, IndexedVideo, IngestActivity, IngestLatency,
+    IngestionCheckpoint, KindCount, MediaHealth, MediaTarget, MediaVerification, PlaylistEvent,
+    ProfileFetch, PubkeyTrust, RebuildProgress, RejectedEvent, RelayStats, RelaySummary,
+    ReportedVideo, RoutedEventRow, TrendingCandidate, TrendingScore, TrendingVideo, VerifyTarget,
+    VideoDetails, VideoDuplicate, VideoHashtag, VideoModeration, VideoStats,
+};
+pub use self::routing::EventRoutes;
+pub use self::schema::Deployment;
+pub use self::traits::{
+    AdminQueries, DuplicateQueries, EventWriter, HealthQueries, MediaQueries, ProfileQueries,
+    StatsQueries, TrendingQueries, TrustQueries, VideoQueries,
+};
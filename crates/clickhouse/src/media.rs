@@ -0,0 +1,21 @@
+//! Dead-media filter for listing queries.
+//!
+//! The aggregator records each check of a video's media URLs in `media_health`.
+//! With `MEDIA_HEALTH_HIDE_DEAD` set, listings leave out videos whose video URL has
+//! failed [`DEAD_AFTER_FAILURES`] checks in a row. Dead thumbnails are only flagged.
+
+use crate::queries::MediaHealth;
+
+/// Consecutive failed checks after which media counts as dead.
+pub const DEAD_AFTER_FAILURES: u32 = 3;
+
+/// `WHERE` condition keeping rows whose `id_column` is not a video with dead media.
+pub(crate) fn live_media_clause(id_column: &str) -> String {
+    format!(
+        "{id_column} NOT IN ( \
+             SELECT event_id FROM media_health FINAL \
+             WHERE media_type = '{}' AND failures >= {DEAD_AFTER_FAILURES} \
+         )",
+        MediaHealth::MEDIA_VIDEO
+    )
+}
@@ -0,0 +1,35 @@
+//! Moderation filter for listing queries.
+//!
+//! A video is hidden from listings (recent, trending, author, search, feeds,
+//! playlists, updates) when an operator hid it through the admin API, or when at
+//! least the report threshold of distinct pubkeys reported it (NIP-56, kind 1984)
+//! and an operator hasn't approved it since. Direct lookups by ID are unaffected.
+
+use crate::queries::VideoModeration;
+
+/// Distinct reporters that hide a video when `MODERATION_REPORT_THRESHOLD` is unset.
+pub(crate) const DEFAULT_REPORT_THRESHOLD: u64 = 5;
+
+/// `WHERE` condition keeping rows whose `id_column` is not a hidden video.
+///
+/// `threshold` is formatted into the SQL rather than bound, so callers' bind order
+/// is unchanged; it is an integer, so this is safe.
+pub(crate) fn visible_clause(id_column: &str, threshold: Option<u64>) -> String {
+    let hidden = format!(
+        "{id_column} NOT IN (SELECT event_id FROM video_moderation FINAL WHERE status = '{}')",
+        VideoModeration::STATUS_HIDDEN
+    );
+    match threshold {
+        Some(threshold) => format!(
+            "{hidden} AND {id_column} NOT IN ( \
+                 SELECT target_event_id FROM video_reports \
+                 WHERE target_event_id NOT IN ( \
+                     SELECT event_id FROM video_moderation FINAL WHERE status = '{}' \
+                 ) \
+                 GROUP BY target_event_id HAVING uniqMerge(reporters) >= {threshold} \
+             )",
+            VideoModeration::STATUS_APPROVED
+        ),
+        None => hidden,
+    }
+}
@@ -14,6 +14,9 @@ pub struct EventRow {
     pub sig: String,
     pub tags: Vec<Vec<String>>,
     pub relay_source: String,
+    /// When a relay or the live ingester received the event, if known.
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis::option")]
+    pub ingested_at: Option<DateTime<Utc>>,
 }
 
 impl EventRow {
@@ -27,6 +30,48 @@ impl EventRow {
             sig: event.sig.clone(),
             tags: event.tags.clone(),
             relay_source: relay_source.to_string(),
+            ingested_at: event.received_at,
+        }
+    }
+
+    /// The event as NIP-01 JSON, the form relays serve it in.
+    pub fn to_json(&self) -> String {
+        serde_json::json!({
+            "id": self.id,
+            "pubkey": self.pubkey,
+            "created_at": self.created_at.timestamp(),
+            "kind": self.kind,
+            "tags": self.tags,
+            "content": self.content,
+            "sig": self.sig,
+        })
+        .to_string()
+    }
+}
+
+/// Row copied to the table an event's kind is routed to (see [`crate::routing`]).
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct RoutedEventRow {
+    pub id: String,
+    pub pubkey: String,
+    #[serde(with = "clickhouse::serde::chrono::datetime")]
+    pub created_at: DateTime<Utc>,
+    pub kind: u16,
+    pub relay_source: String,
+    pub content: String,
+    pub tags: Vec<Vec<String>>,
+}
+
+impl RoutedEventRow {
+    pub fn from_event(event: &EventRow) -> Self {
+        Self {
+            id: event.id.clone(),
+            pubkey: event.pubkey.clone(),
+            created_at: event.created_at,
+            kind: event.kind,
+            relay_source: event.relay_source.clone(),
+            content: event.content.clone(),
+            tags: event.tags.clone(),
         }
     }
 }
@@ -48,6 +93,42 @@ pub struct VideoStats {
     pub engagement_score: u64,
 }
 
+/// Video stats with the time the video was indexed, for following new arrivals.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct IndexedVideo {
+    pub id: String,
+    pub pubkey: String,
+    #[serde(with = "clickhouse::serde::chrono::datetime")]
+    pub created_at: DateTime<Utc>,
+    pub kind: u16,
+    pub d_tag: String,
+    pub title: String,
+    pub thumbnail: String,
+    pub reactions: u64,
+    pub comments: u64,
+    pub reposts: u64,
+    pub engagement_score: u64,
+    #[serde(with = "clickhouse::serde::chrono::datetime")]
+    pub indexed_at: DateTime<Utc>,
+}
+
+/// Video event details from the videos view, including the media URL.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct VideoDetails {
+    pub id: String,
+    pub pubkey: String,
+    #[serde(with = "clickhouse::serde::chrono::datetime")]
+    pub created_at: DateTime<Utc>,
+    pub kind: u16,
+    pub d_tag: String,
+    pub title: String,
+    pub thumbnail: String,
+    pub video_url: String,
+    /// Raw `duration` tag value in seconds (empty when absent).
+    pub duration: String,
+    pub content: String,
+}
+
 /// Trending video with score.
 #[derive(Debug, Clone, Row, Serialize, Deserialize)]
 pub struct TrendingVideo {
@@ -66,6 +147,96 @@ pub struct TrendingVideo {
     pub trending_score: f64,
 }
 
+/// A recent video and its engagement counts, as input to trending scores.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct TrendingCandidate {
+    pub id: String,
+    pub pubkey: String,
+    #[serde(with = "clickhouse::serde::chrono::datetime")]
+    pub created_at: DateTime<Utc>,
+    pub kind: u16,
+    pub d_tag: String,
+    pub title: String,
+    pub thumbnail: String,
+    pub reactions: u64,
+    pub comments: u64,
+    pub reposts: u64,
+    pub zaps: u64,
+}
+
+/// One video's row in a `trending_videos` snapshot.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct TrendingScore {
+    /// When the snapshot was computed; shared by every row in it.
+    #[serde(with = "clickhouse::serde::chrono::datetime")]
+    pub computed_at: DateTime<Utc>,
+    pub id: String,
+    pub pubkey: String,
+    #[serde(with = "clickhouse::serde::chrono::datetime")]
+    pub created_at: DateTime<Utc>,
+    pub kind: u16,
+    pub d_tag: String,
+    pub title: String,
+    pub thumbnail: String,
+    pub reactions: u64,
+    pub comments: u64,
+    pub reposts: u64,
+    pub zaps: u64,
+    pub engagement_score: u64,
+    pub trending_score: f64,
+}
+
+/// One follow in the latest contact list (kind 3) of `follower`.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct FollowEdge {
+    pub follower: String,
+    pub followed: String,
+}
+
+/// One pubkey's row in a `pubkey_trust` snapshot.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct PubkeyTrust {
+    /// When the snapshot was computed; shared by every row in it.
+    #[serde(with = "clickhouse::serde::chrono::datetime")]
+    pub computed_at: DateTime<Utc>,
+    pub pubkey: String,
+    /// Trust score in `(0, 1]`.
+    pub trust: f64,
+    /// Pubkeys that follow this one.
+    pub followers: u64,
+}
+
+/// A recent video compared against the others to find re-uploads.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct DuplicateCandidate {
+    pub id: String,
+    pub pubkey: String,
+    #[serde(with = "clickhouse::serde::chrono::datetime")]
+    pub created_at: DateTime<Utc>,
+    pub title: String,
+    /// SHA-256 from the video's `imeta` tag, lowercased; empty when there is none.
+    pub sha256: String,
+}
+
+/// One re-upload in a `video_duplicates` snapshot.
+#[derive(Debug, Clone, PartialEq, Row, Serialize, Deserialize)]
+pub struct VideoDuplicate {
+    /// When the snapshot was computed; shared by every row in it.
+    #[serde(with = "clickhouse::serde::chrono::datetime")]
+    pub computed_at: DateTime<Utc>,
+    pub event_id: String,
+    /// The earliest video with the same file or title.
+    pub duplicate_of: String,
+    pub reason: String,
+}
+
+impl VideoDuplicate {
+    /// The videos declare the same file SHA-256.
+    pub const REASON_SHA256: &'static str = "sha256";
+    /// The videos' titles match once case and punctuation are ignored.
+    pub const REASON_TITLE: &'static str = "title";
+}
+
 /// Video hashtag mapping.
 #[derive(Debug, Clone, Row, Serialize, Deserialize)]
 pub struct VideoHashtag {
@@ -79,3 +250,418 @@ pub struct VideoHashtag {
     pub thumbnail: String,
     pub d_tag: String,
 }
+
+/// A video set (kind 30005) event, listing the videos of a playlist in its tags.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct PlaylistEvent {
+    pub id: String,
+    pub pubkey: String,
+    #[serde(with = "clickhouse::serde::chrono::datetime")]
+    pub created_at: DateTime<Utc>,
+    pub d_tag: String,
+    pub tags: Vec<Vec<String>>,
+}
+
+/// A file metadata (kind 1063) event, describing a media file in its tags.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct FileMetadataEvent {
+    pub id: String,
+    pub tags: Vec<Vec<String>>,
+}
+
+/// Tombstone marking an event as deleted (by its author or an operator).
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct EventDeletion {
+    pub event_id: String,
+    #[serde(with = "clickhouse::serde::chrono::datetime")]
+    pub deleted_at: DateTime<Utc>,
+    /// Pubkey of the deletion request author, or `admin` for operator tombstones.
+    pub deleted_by: String,
+    /// ID of the kind 5 deletion event, empty for operator tombstones.
+    pub deletion_event_id: String,
+    pub reason: String,
+}
+
+impl EventDeletion {
+    /// Tombstones for the events a NIP-09 deletion request names by ID.
+    ///
+    /// Only insert these for events whose author is the request's pubkey; the
+    /// request doesn't prove that by itself. Addressed targets need their versions
+    /// looked up first and aren't included.
+    pub fn from_request(
+        deletion_event_id: &str,
+        request: &funnel_proto::DeletionMeta,
+    ) -> Vec<Self> {
+        request
+            .event_ids
+            .iter()
+            .map(|event_id| Self::from_target(event_id, deletion_event_id, request))
+            .collect()
+    }
+
+    /// Tombstone for `event_id`, deleted by a NIP-09 deletion request. The event
+    /// should be one the request's author is allowed to delete; see
+    /// `ClickHouseClient::get_deletion_targets`.
+    pub fn from_target(
+        event_id: &str,
+        deletion_event_id: &str,
+        request: &funnel_proto::DeletionMeta,
+    ) -> Self {
+        Self {
+            event_id: event_id.to_string(),
+            deleted_at: request.created_at,
+            deleted_by: request.pubkey.clone(),
+            deletion_event_id: deletion_event_id.to_string(),
+            reason: request.reason.clone(),
+        }
+    }
+}
+
+/// Operator-requested backfill of a time window, consumed by the ingestion service.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct BackfillRequest {
+    pub id: String,
+    #[serde(with = "clickhouse::serde::chrono::datetime")]
+    pub since: DateTime<Utc>,
+    #[serde(with = "clickhouse::serde::chrono::datetime")]
+    pub until: DateTime<Utc>,
+    /// Event kinds to fetch; empty means all kinds.
+    pub kinds: Vec<u16>,
+    pub status: String,
+    #[serde(with = "clickhouse::serde::chrono::datetime")]
+    pub requested_at: DateTime<Utc>,
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    pub updated_at: DateTime<Utc>,
+}
+
+impl BackfillRequest {
+    pub const STATUS_PENDING: &'static str = "pending";
+    pub const STATUS_RUNNING: &'static str = "running";
+    pub const STATUS_DONE: &'static str = "done";
+    pub const STATUS_FAILED: &'static str = "failed";
+
+    /// Create a pending request for the given window.
+    pub fn new(since: DateTime<Utc>, until: DateTime<Utc>, kinds: Vec<u16>) -> Self {
+        let now = Utc::now();
+        Self {
+            id: format!(
+                "{}-{}-{}",
+                now.timestamp_millis(),
+                since.timestamp(),
+                until.timestamp()
+            ),
+            since,
+            until,
+            kinds,
+            status: Self::STATUS_PENDING.to_string(),
+            requested_at: now,
+            updated_at: now,
+        }
+    }
+
+    /// Copy of this request with a new status.
+    pub fn with_status(&self, status: &str) -> Self {
+        Self {
+            status: status.to_string(),
+            updated_at: Utc::now(),
+            ..self.clone()
+        }
+    }
+}
+
+/// A media URL due for a health check.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct MediaTarget {
+    pub event_id: String,
+    pub url: String,
+    /// [`MediaHealth::MEDIA_VIDEO`] or [`MediaHealth::MEDIA_THUMBNAIL`].
+    pub media_type: String,
+    /// Consecutive failed checks so far.
+    pub failures: u32,
+}
+
+/// Result of one health check of a video's media URL.
+///
+/// Each check inserts a new row; the latest by `checked_at` wins.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct MediaHealth {
+    pub event_id: String,
+    pub url: String,
+    pub media_type: String,
+    pub available: bool,
+    /// HTTP status, or 0 when the request failed or was refused.
+    pub status: u16,
+    pub content_type: String,
+    /// Consecutive failed checks, 0 once the media is available again.
+    pub failures: u32,
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    pub checked_at: DateTime<Utc>,
+}
+
+impl MediaHealth {
+    /// The video file (`url` tag).
+    pub const MEDIA_VIDEO: &'static str = "video";
+    /// The thumbnail (`thumb` tag).
+    pub const MEDIA_THUMBNAIL: &'static str = "thumbnail";
+
+    /// Whether the media has failed enough checks in a row to count as dead.
+    pub fn is_dead(&self) -> bool {
+        self.failures >= crate::media::DEAD_AFTER_FAILURES
+    }
+}
+
+/// A video file whose `imeta` tag declares a SHA-256, due for hash verification.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct VerifyTarget {
+    pub event_id: String,
+    pub url: String,
+    /// Declared SHA-256 (lowercase hex).
+    pub sha256: String,
+}
+
+/// Result of checking a video file against the SHA-256 declared in its `imeta` tag.
+///
+/// Each check inserts a new row; the latest by `checked_at` wins.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct MediaVerification {
+    pub event_id: String,
+    pub sha256: String,
+    /// URL from the event.
+    pub url: String,
+    pub status: String,
+    /// URL the matching file was fetched from, when verified.
+    pub source: String,
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    pub checked_at: DateTime<Utc>,
+}
+
+impl MediaVerification {
+    /// A fetched copy hashed to the declared SHA-256.
+    pub const STATUS_VERIFIED: &'static str = "verified";
+    /// The file was fetched, but no copy hashed to the declared SHA-256.
+    pub const STATUS_MISMATCH: &'static str = "mismatch";
+    /// No copy could be fetched.
+    pub const STATUS_UNAVAILABLE: &'static str = "unavailable";
+    /// Every copy was larger than the verifier downloads.
+    pub const STATUS_TOO_LARGE: &'static str = "too_large";
+
+    pub fn is_verified(&self) -> bool {
+        self.status == Self::STATUS_VERIFIED
+    }
+}
+
+/// One relay fetch of a video author's profile (kind 0) and contact list (kind 3).
+///
+/// Each attempt inserts a new row; the latest by `fetched_at` wins.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct ProfileFetch {
+    pub pubkey: String,
+    /// Events the relays returned for the author.
+    pub events: u32,
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    pub fetched_at: DateTime<Utc>,
+}
+
+/// Operator moderation decision for a video.
+///
+/// Each change inserts a new row; the latest by `updated_at` wins.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct VideoModeration {
+    pub event_id: String,
+    pub status: String,
+    pub reason: String,
+    /// Token ID of the operator who made the change.
+    pub updated_by: String,
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    pub updated_at: DateTime<Utc>,
+}
+
+impl VideoModeration {
+    /// Hidden from listings.
+    pub const STATUS_HIDDEN: &'static str = "hidden";
+    /// Listed regardless of reports.
+    pub const STATUS_APPROVED: &'static str = "approved";
+
+    pub fn new(event_id: String, status: &str, reason: String, updated_by: String) -> Self {
+        Self {
+            event_id,
+            status: status.to_string(),
+            reason,
+            updated_by,
+            updated_at: Utc::now(),
+        }
+    }
+}
+
+/// One object of a backup, as listed in its manifest.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct BackupFile {
+    pub table: String,
+    /// Path of the object under the backup's prefix.
+    pub path: String,
+    /// ClickHouse format of the object, e.g. `Parquet`.
+    pub format: String,
+    pub columns: Vec<String>,
+    /// ClickHouse type of each of `columns`.
+    pub types: Vec<String>,
+    pub rows: u64,
+    /// XOR of the rows' `cityHash64`, which doesn't depend on their order.
+    pub checksum: u64,
+}
+
+/// How far a rebuild of one derived table has got.
+///
+/// Each window inserts a new row; the latest by `updated_at` wins.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct RebuildProgress {
+    pub target: String,
+    /// Events created before this are in the staging table.
+    #[serde(with = "clickhouse::serde::chrono::datetime")]
+    pub rebuilt_until: DateTime<Utc>,
+    pub status: String,
+    #[serde(with = "clickhouse::serde::chrono::datetime64::millis")]
+    pub updated_at: DateTime<Utc>,
+}
+
+impl RebuildProgress {
+    pub const STATUS_RUNNING: &'static str = "running";
+    pub const STATUS_DONE: &'static str = "done";
+
+    pub fn new(target: &str, rebuilt_until: DateTime<Utc>, status: &str) -> Self {
+        Self {
+            target: target.to_string(),
+            rebuilt_until,
+            status: status.to_string(),
+            updated_at: Utc::now(),
+        }
+    }
+}
+
+/// Reports (kind 1984) against one video.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct ReportedVideo {
+    pub event_id: String,
+    pub pubkey: String,
+    pub title: String,
+    /// Report events received.
+    pub reports: u64,
+    /// Distinct pubkeys that reported the video (approximate).
+    pub reporters: u64,
+    /// NIP-56 report types, e.g. `spam`, `nudity`.
+    pub report_types: Vec<String>,
+    #[serde(with = "clickhouse::serde::chrono::datetime")]
+    pub last_reported_at: DateTime<Utc>,
+    /// Operator decision, empty when there is none.
+    pub status: String,
+    /// Whether the video is currently hidden from listings.
+    pub hidden: bool,
+}
+
+/// Ingestion progress for one relay source.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct IngestionCheckpoint {
+    pub relay_source: String,
+    /// Newest `created_at` ingested from this source.
+    #[serde(with = "clickhouse::serde::chrono::datetime")]
+    pub latest_event_at: DateTime<Utc>,
+    /// Most recent insert time for this source.
+    #[serde(with = "clickhouse::serde::chrono::datetime")]
+    pub last_indexed_at: DateTime<Utc>,
+    pub event_count: u64,
+}
+
+/// ID and creation time of a stored event, what negentropy sync compares.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct EventStamp {
+    pub id: String,
+    #[serde(with = "clickhouse::serde::chrono::datetime")]
+    pub created_at: DateTime<Utc>,
+}
+
+/// Stored event count for one kind.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct KindCount {
+    pub kind: u16,
+    pub count: u64,
+}
+
+/// Recent ingestion activity across all relay sources.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct IngestActivity {
+    /// Events inserted in the last hour.
+    pub events_last_hour: u64,
+    /// Events inserted in the last 24 hours.
+    pub events_last_day: u64,
+    /// Video events inserted since midnight UTC.
+    pub videos_today: u64,
+    /// Newest `created_at` not in the future (Unix epoch when there are no events).
+    #[serde(with = "clickhouse::serde::chrono::datetime")]
+    pub latest_event_at: DateTime<Utc>,
+}
+
+/// Pipeline latency of recently inserted events that carry a receive time.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct IngestLatency {
+    /// Events inserted in the last hour with a receive time.
+    pub events: u64,
+    /// p50, p90, and p99 seconds from `created_at` to `ingested_at`.
+    pub created_to_received: Vec<f64>,
+    /// p50, p90, and p99 seconds from `ingested_at` to `indexed_at`.
+    pub received_to_inserted: Vec<f64>,
+}
+
+/// What one relay sent the live ingester since its previous row.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct RelayStats {
+    pub relay_url: String,
+    #[serde(with = "clickhouse::serde::chrono::datetime")]
+    pub recorded_at: DateTime<Utc>,
+    /// Every event the relay sent, duplicates included.
+    pub events_received: u64,
+    /// Events no relay had sent before.
+    pub unique_events: u64,
+    /// Unique events that are videos.
+    pub unique_videos: u64,
+    /// Events already received from this relay or another.
+    pub duplicates: u64,
+    /// Unique events that couldn't be parsed.
+    pub parse_failures: u64,
+    pub connected: bool,
+    /// Seconds since the relay (re)connected, 0 while disconnected.
+    pub uptime_secs: u64,
+}
+
+/// An event the live ingester received but didn't store, for `events_rejected`.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct RejectedEvent {
+    /// ID the event claims, which may not be its real one.
+    pub event_id: String,
+    pub pubkey: String,
+    pub kind: u16,
+    pub relay_source: String,
+    #[serde(with = "clickhouse::serde::chrono::datetime")]
+    pub rejected_at: DateTime<Utc>,
+    /// Stable error code for why it was rejected.
+    pub code: String,
+    pub error: String,
+    /// The event JSON as received.
+    pub raw: String,
+}
+
+/// A relay's counters summed over a window of `relay_stats`.
+#[derive(Debug, Clone, Row, Serialize, Deserialize)]
+pub struct RelaySummary {
+    pub relay_url: String,
+    pub events_received: u64,
+    pub unique_events: u64,
+    pub unique_videos: u64,
+    pub duplicates: u64,
+    pub parse_failures: u64,
+    /// Whether the relay was connected at its latest row.
+    pub connected: bool,
+    /// Uptime at its latest row.
+    pub uptime_secs: u64,
+    /// Time of its latest row.
+    #[serde(with = "clickhouse::serde::chrono::datetime")]
+    pub last_recorded_at: DateTime<Utc>,
+}
@@ -0,0 +1,149 @@
+//! Rebuilding derived tables from raw events.
+//!
+//! Materialized views only see events as they're inserted, so a fixed or changed
+//! view leaves its table out of step with `events_local`, which stays the source of
+//! truth. A rebuild re-runs each view's own query (read from the bundled schema)
+//! over the stored events into a staging table, one window of `created_at` at a time,
+//! then swaps the staging table's contents into the live table in one step. Each
+//! window is attached to the staging table whole, and progress is recorded in
+//! `aggregate_rebuilds` after it, so an interrupted rebuild picks up where it stopped.
+//!
+//! Events are read with `FINAL`, so rebuilt counts also drop duplicates that the
+//! views counted when the same event was inserted more than once.
+
+/// A materialized view over `events_local` and the table it writes to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Projection {
+    pub view: String,
+    pub target: String,
+    /// The view's `SELECT`.
+    pub select: String,
+}
+
+impl Projection {
+    /// Table the rebuild writes to before swapping it in.
+    pub fn staging(&self) -> String {
+        format!("{}_rebuild", self.target)
+    }
+
+    /// Scratch table each window is written to before it joins the staging table.
+    pub fn window_table(&self) -> String {
+        format!("{}_rebuild_window", self.target)
+    }
+
+    /// The view's query over events created in `[since, until)`, or from `since` on
+    /// when `until` is `None`.
+    pub fn select_window(&self, since: i64, until: Option<i64>) -> String {
+        let mut window = format!("created_at >= toDateTime({since})");
+        if let Some(until) = until {
+            window.push_str(&format!(" AND created_at < toDateTime({until})"));
+        }
+
+        let select = self
+            .select
+            .replacen("FROM events_local", "FROM events_local FINAL", 1);
+        let group_by = |from: usize| {
+            select[from..]
+                .find(" GROUP BY ")
+                .map_or(select.len(), |i| from + i)
+        };
+        match select.find(" WHERE ") {
+            Some(start) => {
+                let end = group_by(start);
+                format!(
+                    "{} WHERE {window} AND ({}){}",
+                    &select[..start],
+                    &select[start + " WHERE ".len()..end],
+                    &select[end..]
+                )
+            }
+            None => {
+                let end = group_by(0);
+                format!("{} WHERE {window}{}", &select[..end], &select[end..])
+            }
+        }
+    }
+}
+
+/// The materialized views `schema` creates over `events_local`, in schema order.
+pub fn projections(schema: &str) -> Vec<Projection> {
+    crate::schema::statements(schema)
+        .iter()
+        .filter_map(|statement| {
+            let rest = statement.strip_prefix("CREATE MATERIALIZED VIEW IF NOT EXISTS ")?;
+            let (view, rest) = rest.split_once(" TO ")?;
+            let (target, select) = rest.split_once(" AS ")?;
+            select.contains("FROM events_local").then(|| Projection {
+                view: view.trim().to_string(),
+                target: target.trim().to_string(),
+                select: select.trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Deployment;
+
+    fn projection(select: &str) -> Projection {
+        Projection {
+            view: "counts_mv".to_string(),
+            target: "counts".to_string(),
+            select: select.to_string(),
+        }
+    }
+
+    #[test]
+    fn bundled_schemas_have_the_same_projections() {
+        let cloud = projections(Deployment::Cloud.schema());
+        let targets: Vec<_> = cloud.iter().map(|p| p.target.as_str()).collect();
+        assert_eq!(
+            targets,
+            [
+                "event_tags_flat_data",
+                "reaction_counts",
+                "comment_counts",
+                "repost_counts",
+                "zap_counts",
+                "video_reports",
+            ]
+        );
+        assert_eq!(projections(Deployment::SelfHosted.schema()), cloud);
+    }
+
+    #[test]
+    fn select_window_bounds_created_at() {
+        let select = projection(
+            "SELECT tag[2] AS target_event_id FROM events_local ARRAY JOIN tags AS tag \
+             WHERE kind = 7 OR kind = 6",
+        )
+        .select_window(100, Some(200));
+
+        assert_eq!(
+            select,
+            "SELECT tag[2] AS target_event_id FROM events_local FINAL ARRAY JOIN tags AS tag \
+             WHERE created_at >= toDateTime(100) AND created_at < toDateTime(200) \
+             AND (kind = 7 OR kind = 6)"
+        );
+    }
+
+    #[test]
+    fn select_window_keeps_group_by_and_adds_missing_where() {
+        let grouped =
+            projection("SELECT id, count() AS n FROM events_local WHERE kind = 1 GROUP BY id")
+                .select_window(100, None);
+        assert_eq!(
+            grouped,
+            "SELECT id, count() AS n FROM events_local FINAL \
+             WHERE created_at >= toDateTime(100) AND (kind = 1) GROUP BY id"
+        );
+
+        let unfiltered = projection("SELECT id FROM events_local").select_window(100, None);
+        assert_eq!(
+            unfiltered,
+            "SELECT id FROM events_local FINAL WHERE created_at >= toDateTime(100)"
+        );
+    }
+}
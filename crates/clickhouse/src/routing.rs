@@ -0,0 +1,146 @@
+//! Copies of chosen kinds in narrow tables.
+//!
+//! Every event goes into `events_local`, which the API and aggregates read.
+//! Counting reactions or summing zaps there scans the content, tags, and signature
+//! of every event, so `EVENT_ROUTES` also writes chosen kinds to tables holding
+//! only what's needed to aggregate them, such as
+//! `7=reactions,9735=zap_receipts,0=profiles,34237=watch_events` for the tables the
+//! bundled schema creates.
+//!
+//! A routed table is sent the columns of [`RoutedEventRow`]. The bundled ones
+//! declare `content` and `tags` `EPHEMERAL` and derive the columns they keep from
+//! them, so another table for another kind needs no code, only those columns.
+//! Deletions aren't applied to routed copies.
+
+use std::collections::BTreeMap;
+
+use crate::error::ClickHouseError;
+use crate::queries::{EventRow, RoutedEventRow};
+
+/// Tables events of each kind are copied to.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EventRoutes {
+    tables: BTreeMap<u16, String>,
+}
+
+impl EventRoutes {
+    /// Parse a `kind=table` list separated by commas, such as
+    /// `7=reactions,9735=zap_receipts`.
+    ///
+    /// Table names are limited to ASCII letters, digits, and `_`, since they end up
+    /// in the insert statement.
+    pub fn parse(spec: &str) -> Result<Self, ClickHouseError> {
+        let mut tables = BTreeMap::new();
+
+        for entry in spec.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let (kind, table) = entry
+                .split_once('=')
+                .and_then(|(kind, table)| Some((kind.trim().parse::<u16>().ok()?, table.trim())))
+                .filter(|(_, table)| is_valid_table(table))
+                .ok_or_else(|| {
+                    ClickHouseError::Config(format!(
+                        "Invalid EVENT_ROUTES entry {entry:?} (expected kind=table)"
+                    ))
+                })?;
+
+            if tables.insert(kind, table.to_string()).is_some() {
+                return Err(ClickHouseError::Config(format!(
+                    "Kind {kind} routed twice in EVENT_ROUTES"
+                )));
+            }
+        }
+
+        Ok(Self { tables })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    /// Table events of `kind` are copied to, if any.
+    pub fn table(&self, kind: u16) -> Option<&str> {
+        self.tables.get(&kind).map(String::as_str)
+    }
+
+    /// The routed events among `events`, by table.
+    pub fn route(&self, events: &[EventRow]) -> BTreeMap<&str, Vec<RoutedEventRow>> {
+        let mut routed: BTreeMap<&str, Vec<RoutedEventRow>> = BTreeMap::new();
+        for event in events {
+            if let Some(table) = self.table(event.kind) {
+                routed
+                    .entry(table)
+                    .or_default()
+                    .push(RoutedEventRow::from_event(event));
+            }
+        }
+        routed
+    }
+}
+
+fn is_valid_table(table: &str) -> bool {
+    !table.is_empty()
+        && table != "events_local"
+        && table.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+
+    fn event(id: &str, kind: u16) -> EventRow {
+        EventRow {
+            id: id.to_string(),
+            pubkey: "pubkey".to_string(),
+            created_at: Utc::now(),
+            kind,
+            content: "+".to_string(),
+            sig: "sig".to_string(),
+            tags: vec![vec!["e".to_string(), "target".to_string()]],
+            relay_source: "wss://relay.example.com".to_string(),
+            ingested_at: None,
+        }
+    }
+
+    #[test]
+    fn parse_maps_kind_to_table() {
+        let routes = EventRoutes::parse(" 7=reactions, 9735 = zap_receipts ,").unwrap();
+
+        assert_eq!(routes.table(7), Some("reactions"));
+        assert_eq!(routes.table(9735), Some("zap_receipts"));
+        assert_eq!(routes.table(1), None);
+        assert!(EventRoutes::parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn parse_rejects_malformed_entries() {
+        assert!(EventRoutes::parse("reactions").is_err());
+        assert!(EventRoutes::parse("seven=reactions").is_err());
+        assert!(EventRoutes::parse("7=").is_err());
+        assert!(EventRoutes::parse("7=events_local").is_err());
+        assert!(EventRoutes::parse("7=r; DROP TABLE events_local").is_err());
+        assert!(EventRoutes::parse("7=a,7=b").is_err());
+    }
+
+    #[test]
+    fn route_groups_routed_kinds_by_table() {
+        let routes = EventRoutes::parse("7=reactions,9735=zap_receipts").unwrap();
+        let events = [
+            event("a", 7),
+            event("b", 1),
+            event("c", 9735),
+            event("d", 7),
+        ];
+
+        let routed = routes.route(&events);
+
+        let ids = |table: &str| -> Vec<&str> {
+            routed[table].iter().map(|row| row.id.as_str()).collect()
+        };
+        assert_eq!(routed.len(), 2);
+        assert_eq!(ids("reactions"), ["a", "d"]);
+        assert_eq!(ids("zap_receipts"), ["c"]);
+        assert_eq!(routed["reactions"][0].tags, events[0].tags);
+    }
+}
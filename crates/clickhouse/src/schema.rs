@@ -0,0 +1,165 @@
+//! Schema migrations.
+//!
+//! The schema files in `docs/` are compiled in, so a deployed binary can apply them
+//! without a checkout. Every statement is `CREATE ... IF NOT EXISTS`, so applying a
+//! schema again only creates what's missing, except for plain views holding no
+//! data, which may be `CREATE OR REPLACE VIEW` so a changed definition reaches
+//! existing deployments. Views that later became tables are listed in
+//! [`REPLACED_VIEWS`] and dropped before the schema is applied, since `IF NOT EXISTS`
+//! would otherwise keep the old view.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Plain views that later became tables of the same name. A migration drops any of
+/// these still present as a view, so the table can be created in its place.
+pub const REPLACED_VIEWS: &[&str] = &["trending_videos"];
+
+/// Which edition of the schema to apply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Deployment {
+    /// ClickHouse Cloud, which doesn't support projections.
+    Cloud,
+    /// Self-hosted ClickHouse, with projections.
+    SelfHosted,
+}
+
+impl Deployment {
+    /// The schema file for this deployment.
+    pub fn schema(self) -> &'static str {
+        match self {
+            Self::Cloud => include_str!("../../../docs/schema_cloud.sql"),
+            Self::SelfHosted => include_str!("../../../docs/schema_self_hosted.sql"),
+        }
+    }
+}
+
+impl FromStr for Deployment {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cloud" => Ok(Self::Cloud),
+            "self-hosted" | "self_hosted" => Ok(Self::SelfHosted),
+            other => Err(format!(
+                "unknown deployment {other:?} (cloud or self-hosted)"
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Deployment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Cloud => "cloud",
+            Self::SelfHosted => "self-hosted",
+        })
+    }
+}
+
+/// The statements of `schema` to run, one at a time since ClickHouse Cloud doesn't
+/// accept several per request.
+///
+/// Comments are dropped, and so are `CREATE DATABASE` and `USE` (the database is
+/// the configured one) and bare `SELECT`s (diagnostic queries at the end of a file).
+pub fn statements(schema: &str) -> Vec<String> {
+    let mut statements = Vec::new();
+    let mut current = String::new();
+
+    for line in schema.lines() {
+        let line = match line.find("--") {
+            Some(comment) => &line[..comment],
+            None => line,
+        }
+        .trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(line);
+        if let Some(statement) = current.strip_suffix(';') {
+            let statement = statement.trim();
+            let upper = statement.to_ascii_uppercase();
+            if !statement.is_empty()
+                && !upper.starts_with("USE ")
+                && !upper.starts_with("CREATE DATABASE")
+                && !upper.starts_with("SELECT")
+            {
+                statements.push(statement.to_string());
+            }
+            current.clear();
+        }
+    }
+
+    statements
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn statements_skip_comments_and_database_setup() {
+        let schema = "-- header\n\
+                      CREATE DATABASE IF NOT EXISTS nostr;\n\
+                      USE nostr;\n\
+                      CREATE TABLE IF NOT EXISTS t (\n\
+                          id String -- the id\n\
+                      ) ENGINE = MergeTree ORDER BY id;\n\
+                      SELECT count() FROM t;\n";
+
+        assert_eq!(
+            statements(schema),
+            ["CREATE TABLE IF NOT EXISTS t ( id String ) ENGINE = MergeTree ORDER BY id"]
+        );
+    }
+
+    #[test]
+    fn bundled_schemas_only_create_if_missing() {
+        for deployment in [Deployment::Cloud, Deployment::SelfHosted] {
+            let statements = statements(deployment.schema());
+            assert!(!statements.is_empty());
+            for statement in statements {
+                assert!(
+                    statement.contains("IF NOT EXISTS")
+                        || statement.starts_with("CREATE OR REPLACE VIEW "),
+                    "{deployment}: {statement}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn videos_view_is_replaced_to_hide_deletions() {
+        for deployment in [Deployment::Cloud, Deployment::SelfHosted] {
+            let videos = statements(deployment.schema())
+                .into_iter()
+                .find(|s| s.starts_with("CREATE OR REPLACE VIEW videos "))
+                .unwrap_or_else(|| panic!("{deployment}: no videos view"));
+            assert!(videos.contains("event_deletions"), "{deployment}");
+        }
+    }
+
+    #[test]
+    fn replaced_views_are_created_as_tables() {
+        for deployment in [Deployment::Cloud, Deployment::SelfHosted] {
+            let statements = statements(deployment.schema());
+            for view in REPLACED_VIEWS {
+                let table = format!("CREATE TABLE IF NOT EXISTS {view} ");
+                assert!(
+                    statements.iter().any(|s| s.starts_with(&table)),
+                    "{deployment}: no {view} table"
+                );
+                assert!(
+                    !statements
+                        .iter()
+                        .any(|s| s.contains(&format!("VIEW {view} "))),
+                    "{deployment}: {view} is still a view"
+                );
+            }
+        }
+    }
+}
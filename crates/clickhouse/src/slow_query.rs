@@ -0,0 +1,65 @@
+//! Slow-query logging.
+//!
+//! Every query made through the [`traits`](crate::traits) implementations is timed.
+//! Queries slower than the configured threshold are logged at `warn` with their
+//! name, parameters, and duration, and counted in `slow_queries_total`, so a query
+//! that regresses shows up in logs and dashboards before it shows up in user reports.
+//!
+//! Parameters are logged to help reproduce the query, but free text from users
+//! (search terms, hashtags, list names) is replaced with its length.
+
+use std::fmt;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use funnel_observability::query_span;
+use metrics::counter;
+use tracing::Instrument;
+
+use crate::error::ClickHouseError;
+
+/// Threshold used when `CLICKHOUSE_SLOW_QUERY_MS` is unset.
+pub(crate) const DEFAULT_SLOW_QUERY_THRESHOLD: Duration = Duration::from_secs(1);
+
+/// Run `query` in a `clickhouse.query` span named `operation`, logging it if it takes
+/// longer than `threshold`.
+///
+/// `params` is only called for slow queries.
+pub(crate) async fn observe<T, F, P>(
+    threshold: Option<Duration>,
+    operation: &'static str,
+    params: P,
+    query: F,
+) -> Result<T, ClickHouseError>
+where
+    F: Future<Output = Result<T, ClickHouseError>>,
+    P: FnOnce() -> String,
+{
+    let start = Instant::now();
+    let result = query.instrument(query_span(operation)).await;
+    let elapsed = start.elapsed();
+
+    if let Some(threshold) = threshold.filter(|threshold| elapsed >= *threshold) {
+        counter!(funnel_observability::clickhouse::SLOW_QUERIES, "operation" => operation)
+            .increment(1);
+        tracing::warn!(
+            operation,
+            params = %params(),
+            elapsed_ms = elapsed.as_millis() as u64,
+            threshold_ms = threshold.as_millis() as u64,
+            ok = result.is_ok(),
+            "Slow ClickHouse query"
+        );
+    }
+
+    result
+}
+
+/// User-supplied text in a slow-query log line, shown only by length.
+pub(crate) struct Redacted<'a>(pub &'a str);
+
+impl fmt::Display for Redacted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<redacted, {} bytes>", self.0.len())
+    }
+}
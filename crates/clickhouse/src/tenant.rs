@@ -0,0 +1,71 @@
+//! Tenant databases.
+//!
+//! One Funnel deployment can serve several communities, each in its own database on
+//! the same ClickHouse server. `CLICKHOUSE_TENANTS` maps tenant IDs to database
+//! names; a process scoped to a tenant (`TENANT`) reads and writes only its database.
+
+use std::collections::BTreeMap;
+
+use crate::error::ClickHouseError;
+
+/// Parse a `tenant=database` list separated by commas, such as
+/// `acme=funnel_acme,beta=funnel_beta`.
+///
+/// Tenant IDs and database names are limited to ASCII letters, digits, `-`, and
+/// `_`, since they end up in tokens, metric labels, and DDL.
+pub fn parse_tenants(spec: &str) -> Result<BTreeMap<String, String>, ClickHouseError> {
+    let mut tenants = BTreeMap::new();
+
+    for entry in spec.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let (tenant, database) = entry
+            .split_once('=')
+            .map(|(tenant, database)| (tenant.trim(), database.trim()))
+            .filter(|(tenant, database)| is_valid_name(tenant) && is_valid_name(database))
+            .ok_or_else(|| {
+                ClickHouseError::Config(format!(
+                    "Invalid CLICKHOUSE_TENANTS entry {entry:?} (expected tenant=database)"
+                ))
+            })?;
+
+        if tenants
+            .insert(tenant.to_string(), database.to_string())
+            .is_some()
+        {
+            return Err(ClickHouseError::Config(format!(
+                "Tenant {tenant:?} listed twice in CLICKHOUSE_TENANTS"
+            )));
+        }
+    }
+
+    Ok(tenants)
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_tenants_maps_tenant_to_database() {
+        let tenants = parse_tenants(" acme=funnel_acme, beta = funnel_beta ,").unwrap();
+
+        assert_eq!(tenants.len(), 2);
+        assert_eq!(tenants["acme"], "funnel_acme");
+        assert_eq!(tenants["beta"], "funnel_beta");
+        assert!(parse_tenants("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn parse_tenants_rejects_malformed_entries() {
+        assert!(parse_tenants("acme").is_err());
+        assert!(parse_tenants("acme=").is_err());
+        assert!(parse_tenants("acme=db`; DROP").is_err());
+        assert!(parse_tenants("acme=a,acme=b").is_err());
+    }
+}
@@ -4,8 +4,17 @@
 
 use std::future::Future;
 
+use chrono::{DateTime, Utc};
+
 use crate::error::ClickHouseError;
-use crate::queries::{EventRow, TrendingVideo, VideoHashtag, VideoStats};
+use crate::queries::{
+    BackfillRequest, DuplicateCandidate, EventDeletion, EventRow, FileMetadataEvent, FollowEdge,
+    IndexedVideo, IngestActivity, IngestLatency, IngestionCheckpoint, KindCount, MediaHealth,
+    MediaTarget, MediaVerification, PlaylistEvent, ProfileFetch, PubkeyTrust, RelaySummary,
+    ReportedVideo, TrendingCandidate, TrendingScore, TrendingVideo, VerifyTarget, VideoDetails,
+    VideoDuplicate, VideoHashtag, VideoModeration, VideoStats,
+};
+use crate::slow_query::Redacted;
 
 /// Trait for read-only video queries.
 ///
@@ -25,32 +34,140 @@ pub trait VideoQueries: Send + Sync {
         limit: u32,
     ) -> impl Future<Output = Result<Vec<VideoStats>, ClickHouseError>> + Send;
 
-    /// Get trending videos.
+    /// Get trending videos, optionally only from creators with at least `min_trust`.
     fn get_trending_videos(
         &self,
+        min_trust: Option<f64>,
         limit: u32,
     ) -> impl Future<Output = Result<Vec<TrendingVideo>, ClickHouseError>> + Send;
 
-    /// Get recent videos, optionally filtered by kind.
+    /// Get recent videos, optionally filtered by kind and creator trust.
     fn get_recent_videos(
         &self,
         kind: Option<u16>,
+        min_trust: Option<f64>,
         limit: u32,
     ) -> impl Future<Output = Result<Vec<VideoStats>, ClickHouseError>> + Send;
 
-    /// Search videos by hashtag.
+    /// Search videos by hashtag, newest first, after the `(created_at, event_id)` of
+    /// the previous page's last result.
     fn search_by_hashtag(
         &self,
         hashtag: &str,
+        before: Option<(DateTime<Utc>, &str)>,
         limit: u32,
     ) -> impl Future<Output = Result<Vec<VideoHashtag>, ClickHouseError>> + Send;
 
-    /// Full-text search videos by title.
+    /// Count videos with a hashtag, stopping at `cap`.
+    fn count_by_hashtag(
+        &self,
+        hashtag: &str,
+        cap: u64,
+    ) -> impl Future<Output = Result<u64, ClickHouseError>> + Send;
+
+    /// Full-text search videos by title, newest first, after the `(created_at, id)` of
+    /// the previous page's last result.
     fn search_by_text(
         &self,
         query: &str,
+        before: Option<(DateTime<Utc>, &str)>,
         limit: u32,
     ) -> impl Future<Output = Result<Vec<VideoStats>, ClickHouseError>> + Send;
+
+    /// Count videos matching a full-text title search, stopping at `cap`.
+    fn count_by_text(
+        &self,
+        query: &str,
+        cap: u64,
+    ) -> impl Future<Output = Result<u64, ClickHouseError>> + Send;
+
+    /// Get video details (including media URL) by event ID.
+    fn get_video_details(
+        &self,
+        event_id: &str,
+    ) -> impl Future<Output = Result<Option<VideoDetails>, ClickHouseError>> + Send;
+
+    /// Get the tombstone for a deleted video, if any.
+    fn get_video_deletion(
+        &self,
+        event_id: &str,
+    ) -> impl Future<Output = Result<Option<EventDeletion>, ClickHouseError>> + Send;
+
+    /// Get the latest health check of each of a video's media URLs.
+    fn get_media_health(
+        &self,
+        event_id: &str,
+    ) -> impl Future<Output = Result<Vec<MediaHealth>, ClickHouseError>> + Send;
+
+    /// Get the latest hash verification of a video's file.
+    fn get_media_verification(
+        &self,
+        event_id: &str,
+    ) -> impl Future<Output = Result<Option<MediaVerification>, ClickHouseError>> + Send;
+
+    /// Get the newest file metadata (kind 1063) event for a video's file.
+    fn get_file_metadata(
+        &self,
+        event_id: &str,
+    ) -> impl Future<Output = Result<Option<FileMetadataEvent>, ClickHouseError>> + Send;
+
+    /// Get the latest duplicate snapshot's rows for a video: what it re-uploads, and
+    /// what re-uploads it.
+    fn get_video_duplicates(
+        &self,
+        event_id: &str,
+    ) -> impl Future<Output = Result<Vec<VideoDuplicate>, ClickHouseError>> + Send;
+
+    /// Get recent video details (including media URL) for a hashtag, optionally only
+    /// from creators with at least `min_trust`.
+    fn get_video_details_by_hashtag(
+        &self,
+        hashtag: &str,
+        min_trust: Option<f64>,
+        limit: u32,
+    ) -> impl Future<Output = Result<Vec<VideoDetails>, ClickHouseError>> + Send;
+
+    /// Get recent video details (including media URL) for an author.
+    fn get_video_details_by_author(
+        &self,
+        pubkey: &str,
+        limit: u32,
+    ) -> impl Future<Output = Result<Vec<VideoDetails>, ClickHouseError>> + Send;
+
+    /// Get the latest version of a video set by author and d-tag.
+    fn get_playlist(
+        &self,
+        pubkey: &str,
+        d_tag: &str,
+    ) -> impl Future<Output = Result<Option<PlaylistEvent>, ClickHouseError>> + Send;
+
+    /// Get an author's video sets, newest first.
+    fn get_playlists_by_author(
+        &self,
+        pubkey: &str,
+        limit: u32,
+    ) -> impl Future<Output = Result<Vec<PlaylistEvent>, ClickHouseError>> + Send;
+
+    /// Get stats for videos referenced by event ID or `kind:pubkey:d-tag` address.
+    fn get_videos_by_refs(
+        &self,
+        event_ids: &[String],
+        addresses: &[String],
+    ) -> impl Future<Output = Result<Vec<VideoStats>, ClickHouseError>> + Send;
+
+    /// Get videos indexed after the `(indexed_at, id)` cursor, oldest first.
+    fn get_videos_indexed_after(
+        &self,
+        indexed_at: DateTime<Utc>,
+        id: &str,
+        kind: Option<u16>,
+        limit: u32,
+    ) -> impl Future<Output = Result<Vec<IndexedVideo>, ClickHouseError>> + Send;
+
+    /// Get the time the most recent video was indexed, if any.
+    fn get_latest_video_indexed_at(
+        &self,
+    ) -> impl Future<Output = Result<Option<DateTime<Utc>>, ClickHouseError>> + Send;
 }
 
 /// Trait for event insertion operations.
@@ -71,12 +188,185 @@ pub trait StatsQueries: Send + Sync {
 
     /// Get total video count.
     fn get_video_count(&self) -> impl Future<Output = Result<u64, ClickHouseError>> + Send;
+
+    /// Get stored event counts per kind.
+    fn get_kind_counts(
+        &self,
+    ) -> impl Future<Output = Result<Vec<KindCount>, ClickHouseError>> + Send;
+
+    /// Get recent ingestion activity.
+    fn get_ingest_activity(
+        &self,
+    ) -> impl Future<Output = Result<IngestActivity, ClickHouseError>> + Send;
+
+    /// Get recent pipeline latency percentiles.
+    fn get_ingest_latency(
+        &self,
+    ) -> impl Future<Output = Result<IngestLatency, ClickHouseError>> + Send;
+
+    /// Get each relay's ingestion counters since `since`.
+    fn get_relay_stats(
+        &self,
+        since: DateTime<Utc>,
+    ) -> impl Future<Output = Result<Vec<RelaySummary>, ClickHouseError>> + Send;
 }
 
-// Implement traits for ClickHouseClient
+/// Trait for operator actions exposed through the admin API.
+#[allow(dead_code)]
+pub trait AdminQueries: Send + Sync {
+    /// Record a tombstone for an event.
+    fn insert_deletion(
+        &self,
+        deletion: &EventDeletion,
+    ) -> impl Future<Output = Result<(), ClickHouseError>> + Send;
+
+    /// Queue a backfill request for the ingestion service.
+    fn upsert_backfill_request(
+        &self,
+        request: &BackfillRequest,
+    ) -> impl Future<Output = Result<(), ClickHouseError>> + Send;
+
+    /// Get ingestion progress grouped by relay source.
+    fn get_ingestion_checkpoints(
+        &self,
+    ) -> impl Future<Output = Result<Vec<IngestionCheckpoint>, ClickHouseError>> + Send;
+
+    /// Record an operator moderation decision for a video.
+    fn upsert_video_moderation(
+        &self,
+        moderation: &VideoModeration,
+    ) -> impl Future<Output = Result<(), ClickHouseError>> + Send;
+
+    /// Get reported videos, most distinct reporters first.
+    fn get_reported_videos(
+        &self,
+        limit: u32,
+    ) -> impl Future<Output = Result<Vec<ReportedVideo>, ClickHouseError>> + Send;
+}
+
+/// Trait for dependency checks used by readiness probes.
+#[allow(dead_code)]
+pub trait HealthQueries: Send + Sync {
+    /// Check that the database is reachable.
+    fn ping(&self) -> impl Future<Output = Result<(), ClickHouseError>> + Send;
+
+    /// Check that the schema has been applied.
+    fn check_schema(&self) -> impl Future<Output = Result<bool, ClickHouseError>> + Send;
+
+    /// Get the worst replication delay in seconds.
+    fn get_replication_lag(&self) -> impl Future<Output = Result<u64, ClickHouseError>> + Send;
+}
+
+/// Trait for the trending-score worker.
+#[allow(dead_code)]
+pub trait TrendingQueries: Send + Sync {
+    /// Get videos created after `since` with their engagement counts.
+    fn get_trending_candidates(
+        &self,
+        since: DateTime<Utc>,
+    ) -> impl Future<Output = Result<Vec<TrendingCandidate>, ClickHouseError>> + Send;
+
+    /// Write a `trending_videos` snapshot.
+    fn insert_trending_scores(
+        &self,
+        scores: &[TrendingScore],
+    ) -> impl Future<Output = Result<(), ClickHouseError>> + Send;
+}
+
+/// Trait for the media health checker and hash verifier.
+#[allow(dead_code)]
+pub trait MediaQueries: Send + Sync {
+    /// Get media URLs of videos created after `created_after` that were last checked
+    /// before `checked_before` (or never), newest videos first.
+    fn get_media_targets(
+        &self,
+        created_after: DateTime<Utc>,
+        checked_before: DateTime<Utc>,
+        limit: u32,
+    ) -> impl Future<Output = Result<Vec<MediaTarget>, ClickHouseError>> + Send;
+
+    /// Record media health checks.
+    fn insert_media_health(
+        &self,
+        checks: &[MediaHealth],
+    ) -> impl Future<Output = Result<(), ClickHouseError>> + Send;
+
+    /// Get video files with a declared SHA-256 that aren't verified yet, skipping
+    /// those last checked at or after `checked_before`, newest videos first.
+    fn get_verify_targets(
+        &self,
+        created_after: DateTime<Utc>,
+        checked_before: DateTime<Utc>,
+        limit: u32,
+    ) -> impl Future<Output = Result<Vec<VerifyTarget>, ClickHouseError>> + Send;
+
+    /// Record hash verifications.
+    fn insert_media_verifications(
+        &self,
+        verifications: &[MediaVerification],
+    ) -> impl Future<Output = Result<(), ClickHouseError>> + Send;
+}
+
+/// Trait for the web-of-trust worker.
+#[allow(dead_code)]
+pub trait TrustQueries: Send + Sync {
+    /// Get follows from each pubkey's latest contact list (kind 3).
+    fn get_follow_edges(
+        &self,
+    ) -> impl Future<Output = Result<Vec<FollowEdge>, ClickHouseError>> + Send;
+
+    /// Write a `pubkey_trust` snapshot.
+    fn insert_pubkey_trust(
+        &self,
+        scores: &[PubkeyTrust],
+    ) -> impl Future<Output = Result<(), ClickHouseError>> + Send;
+}
+
+/// Trait for the duplicate-video worker.
+#[allow(dead_code)]
+pub trait DuplicateQueries: Send + Sync {
+    /// Get videos created after `created_after`, with their title and file SHA-256.
+    fn get_duplicate_candidates(
+        &self,
+        created_after: DateTime<Utc>,
+    ) -> impl Future<Output = Result<Vec<DuplicateCandidate>, ClickHouseError>> + Send;
+
+    /// Write a `video_duplicates` snapshot.
+    fn insert_video_duplicates(
+        &self,
+        duplicates: &[VideoDuplicate],
+    ) -> impl Future<Output = Result<(), ClickHouseError>> + Send;
+}
+
+/// Trait for the profile enrichment worker.
+#[allow(dead_code)]
+pub trait ProfileQueries: Send + Sync {
+    /// Get authors of videos created after `created_after` whose profile wasn't
+    /// stored, and who weren't fetched, since `stale_before`, newest videos first.
+    fn get_profile_targets(
+        &self,
+        created_after: DateTime<Utc>,
+        stale_before: DateTime<Utc>,
+        limit: u32,
+    ) -> impl Future<Output = Result<Vec<String>, ClickHouseError>> + Send;
+
+    /// Record profile fetches.
+    fn insert_profile_fetches(
+        &self,
+        fetches: &[ProfileFetch],
+    ) -> impl Future<Output = Result<(), ClickHouseError>> + Send;
+}
+
+// Implement traits for ClickHouseClient, running each query in a `clickhouse.query` span
+// and logging it if slow
 impl VideoQueries for crate::ClickHouseClient {
     async fn get_video_stats(&self, event_id: &str) -> Result<Option<VideoStats>, ClickHouseError> {
-        self.get_video_stats(event_id).await
+        self.observe(
+            "get_video_stats",
+            || format!("event_id={event_id}"),
+            self.get_video_stats(event_id),
+        )
+        .await
     }
 
     async fn get_videos_by_author(
@@ -84,50 +374,553 @@ impl VideoQueries for crate::ClickHouseClient {
         pubkey: &str,
         limit: u32,
     ) -> Result<Vec<VideoStats>, ClickHouseError> {
-        self.get_videos_by_author(pubkey, limit).await
+        self.observe(
+            "get_videos_by_author",
+            || format!("pubkey={pubkey}, limit={limit}"),
+            self.get_videos_by_author(pubkey, limit),
+        )
+        .await
     }
 
-    async fn get_trending_videos(&self, limit: u32) -> Result<Vec<TrendingVideo>, ClickHouseError> {
-        self.get_trending_videos(limit).await
+    async fn get_trending_videos(
+        &self,
+        min_trust: Option<f64>,
+        limit: u32,
+    ) -> Result<Vec<TrendingVideo>, ClickHouseError> {
+        self.observe(
+            "get_trending_videos",
+            || format!("min_trust={min_trust:?}, limit={limit}"),
+            self.get_trending_videos(min_trust, limit),
+        )
+        .await
     }
 
     async fn get_recent_videos(
         &self,
         kind: Option<u16>,
+        min_trust: Option<f64>,
         limit: u32,
     ) -> Result<Vec<VideoStats>, ClickHouseError> {
-        self.get_recent_videos(kind, limit).await
+        self.observe(
+            "get_recent_videos",
+            || format!("kind={kind:?}, min_trust={min_trust:?}, limit={limit}"),
+            self.get_recent_videos(kind, min_trust, limit),
+        )
+        .await
     }
 
     async fn search_by_hashtag(
         &self,
         hashtag: &str,
+        before: Option<(DateTime<Utc>, &str)>,
         limit: u32,
     ) -> Result<Vec<VideoHashtag>, ClickHouseError> {
-        self.search_by_hashtag(hashtag, limit).await
+        self.observe(
+            "search_by_hashtag",
+            || {
+                format!(
+                    "hashtag={}, before={before:?}, limit={limit}",
+                    Redacted(hashtag)
+                )
+            },
+            self.search_by_hashtag(hashtag, before, limit),
+        )
+        .await
+    }
+
+    async fn count_by_hashtag(&self, hashtag: &str, cap: u64) -> Result<u64, ClickHouseError> {
+        self.observe(
+            "count_by_hashtag",
+            || format!("hashtag={}, cap={cap}", Redacted(hashtag)),
+            self.count_by_hashtag(hashtag, cap),
+        )
+        .await
     }
 
     async fn search_by_text(
         &self,
         query: &str,
+        before: Option<(DateTime<Utc>, &str)>,
         limit: u32,
     ) -> Result<Vec<VideoStats>, ClickHouseError> {
-        self.search_by_text(query, limit).await
+        self.observe(
+            "search_by_text",
+            || {
+                format!(
+                    "query={}, before={before:?}, limit={limit}",
+                    Redacted(query)
+                )
+            },
+            self.search_by_text(query, before, limit),
+        )
+        .await
+    }
+
+    async fn count_by_text(&self, query: &str, cap: u64) -> Result<u64, ClickHouseError> {
+        self.observe(
+            "count_by_text",
+            || format!("query={}, cap={cap}", Redacted(query)),
+            self.count_by_text(query, cap),
+        )
+        .await
+    }
+
+    async fn get_video_details(
+        &self,
+        event_id: &str,
+    ) -> Result<Option<VideoDetails>, ClickHouseError> {
+        self.observe(
+            "get_video_details",
+            || format!("event_id={event_id}"),
+            self.get_video_details(event_id),
+        )
+        .await
+    }
+
+    async fn get_video_deletion(
+        &self,
+        event_id: &str,
+    ) -> Result<Option<EventDeletion>, ClickHouseError> {
+        self.observe(
+            "get_video_deletion",
+            || format!("event_id={event_id}"),
+            self.get_video_deletion(event_id),
+        )
+        .await
+    }
+
+    async fn get_media_health(&self, event_id: &str) -> Result<Vec<MediaHealth>, ClickHouseError> {
+        self.observe(
+            "get_media_health",
+            || format!("event_id={event_id}"),
+            self.get_media_health(event_id),
+        )
+        .await
+    }
+
+    async fn get_media_verification(
+        &self,
+        event_id: &str,
+    ) -> Result<Option<MediaVerification>, ClickHouseError> {
+        self.observe(
+            "get_media_verification",
+            || format!("event_id={event_id}"),
+            self.get_media_verification(event_id),
+        )
+        .await
+    }
+
+    async fn get_file_metadata(
+        &self,
+        event_id: &str,
+    ) -> Result<Option<FileMetadataEvent>, ClickHouseError> {
+        self.observe(
+            "get_file_metadata",
+            || format!("event_id={event_id}"),
+            self.get_file_metadata(event_id),
+        )
+        .await
+    }
+
+    async fn get_video_duplicates(
+        &self,
+        event_id: &str,
+    ) -> Result<Vec<VideoDuplicate>, ClickHouseError> {
+        self.observe(
+            "get_video_duplicates",
+            || format!("event_id={event_id}"),
+            self.get_video_duplicates(event_id),
+        )
+        .await
+    }
+
+    async fn get_video_details_by_hashtag(
+        &self,
+        hashtag: &str,
+        min_trust: Option<f64>,
+        limit: u32,
+    ) -> Result<Vec<VideoDetails>, ClickHouseError> {
+        self.observe(
+            "get_video_details_by_hashtag",
+            || {
+                format!(
+                    "hashtag={}, min_trust={min_trust:?}, limit={limit}",
+                    Redacted(hashtag)
+                )
+            },
+            self.get_video_details_by_hashtag(hashtag, min_trust, limit),
+        )
+        .await
+    }
+
+    async fn get_video_details_by_author(
+        &self,
+        pubkey: &str,
+        limit: u32,
+    ) -> Result<Vec<VideoDetails>, ClickHouseError> {
+        self.observe(
+            "get_video_details_by_author",
+            || format!("pubkey={pubkey}, limit={limit}"),
+            self.get_video_details_by_author(pubkey, limit),
+        )
+        .await
+    }
+
+    async fn get_playlist(
+        &self,
+        pubkey: &str,
+        d_tag: &str,
+    ) -> Result<Option<PlaylistEvent>, ClickHouseError> {
+        self.observe(
+            "get_playlist",
+            || format!("pubkey={pubkey}, d_tag={}", Redacted(d_tag)),
+            self.get_playlist(pubkey, d_tag),
+        )
+        .await
+    }
+
+    async fn get_playlists_by_author(
+        &self,
+        pubkey: &str,
+        limit: u32,
+    ) -> Result<Vec<PlaylistEvent>, ClickHouseError> {
+        self.observe(
+            "get_playlists_by_author",
+            || format!("pubkey={pubkey}, limit={limit}"),
+            self.get_playlists_by_author(pubkey, limit),
+        )
+        .await
+    }
+
+    async fn get_videos_by_refs(
+        &self,
+        event_ids: &[String],
+        addresses: &[String],
+    ) -> Result<Vec<VideoStats>, ClickHouseError> {
+        self.observe(
+            "get_videos_by_refs",
+            || {
+                format!(
+                    "event_ids={}, addresses={}",
+                    event_ids.len(),
+                    addresses.len()
+                )
+            },
+            self.get_videos_by_refs(event_ids, addresses),
+        )
+        .await
+    }
+
+    async fn get_videos_indexed_after(
+        &self,
+        indexed_at: DateTime<Utc>,
+        id: &str,
+        kind: Option<u16>,
+        limit: u32,
+    ) -> Result<Vec<IndexedVideo>, ClickHouseError> {
+        self.observe(
+            "get_videos_indexed_after",
+            || format!("indexed_at={indexed_at}, id={id}, kind={kind:?}, limit={limit}"),
+            self.get_videos_indexed_after(indexed_at, id, kind, limit),
+        )
+        .await
+    }
+
+    async fn get_latest_video_indexed_at(&self) -> Result<Option<DateTime<Utc>>, ClickHouseError> {
+        self.observe(
+            "get_latest_video_indexed_at",
+            String::new,
+            self.get_latest_video_indexed_at(),
+        )
+        .await
     }
 }
 
 impl EventWriter for crate::ClickHouseClient {
     async fn insert_events(&self, events: &[EventRow]) -> Result<(), ClickHouseError> {
-        self.insert_events(events).await
+        self.observe(
+            "insert_events",
+            || format!("events={}", events.len()),
+            self.insert_events(events),
+        )
+        .await
     }
 }
 
 impl StatsQueries for crate::ClickHouseClient {
     async fn get_event_count(&self) -> Result<u64, ClickHouseError> {
-        self.get_event_count().await
+        self.observe("get_event_count", String::new, self.get_event_count())
+            .await
     }
 
     async fn get_video_count(&self) -> Result<u64, ClickHouseError> {
-        self.get_video_count().await
+        self.observe("get_video_count", String::new, self.get_video_count())
+            .await
+    }
+
+    async fn get_kind_counts(&self) -> Result<Vec<KindCount>, ClickHouseError> {
+        self.observe("get_kind_counts", String::new, self.get_kind_counts())
+            .await
+    }
+
+    async fn get_ingest_activity(&self) -> Result<IngestActivity, ClickHouseError> {
+        self.observe(
+            "get_ingest_activity",
+            String::new,
+            self.get_ingest_activity(),
+        )
+        .await
+    }
+
+    async fn get_ingest_latency(&self) -> Result<IngestLatency, ClickHouseError> {
+        self.observe("get_ingest_latency", String::new, self.get_ingest_latency())
+            .await
+    }
+
+    async fn get_relay_stats(
+        &self,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<RelaySummary>, ClickHouseError> {
+        self.observe(
+            "get_relay_stats",
+            || format!("since={since}"),
+            self.get_relay_stats(since),
+        )
+        .await
+    }
+}
+
+impl AdminQueries for crate::ClickHouseClient {
+    async fn insert_deletion(&self, deletion: &EventDeletion) -> Result<(), ClickHouseError> {
+        self.observe(
+            "insert_deletion",
+            || format!("event_id={}", deletion.event_id),
+            self.insert_deletion(deletion),
+        )
+        .await
+    }
+
+    async fn upsert_backfill_request(
+        &self,
+        request: &BackfillRequest,
+    ) -> Result<(), ClickHouseError> {
+        self.observe(
+            "upsert_backfill_request",
+            || format!("id={}", request.id),
+            self.upsert_backfill_request(request),
+        )
+        .await
+    }
+
+    async fn get_ingestion_checkpoints(&self) -> Result<Vec<IngestionCheckpoint>, ClickHouseError> {
+        self.observe(
+            "get_ingestion_checkpoints",
+            String::new,
+            self.get_ingestion_checkpoints(),
+        )
+        .await
+    }
+
+    async fn upsert_video_moderation(
+        &self,
+        moderation: &VideoModeration,
+    ) -> Result<(), ClickHouseError> {
+        self.observe(
+            "upsert_video_moderation",
+            || {
+                format!(
+                    "event_id={} status={}",
+                    moderation.event_id, moderation.status
+                )
+            },
+            self.upsert_video_moderation(moderation),
+        )
+        .await
+    }
+
+    async fn get_reported_videos(&self, limit: u32) -> Result<Vec<ReportedVideo>, ClickHouseError> {
+        self.observe(
+            "get_reported_videos",
+            || format!("limit={limit}"),
+            self.get_reported_videos(limit),
+        )
+        .await
+    }
+}
+
+impl TrendingQueries for crate::ClickHouseClient {
+    async fn get_trending_candidates(
+        &self,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<TrendingCandidate>, ClickHouseError> {
+        self.observe(
+            "get_trending_candidates",
+            || format!("since={since}"),
+            self.get_trending_candidates(since),
+        )
+        .await
+    }
+
+    async fn insert_trending_scores(
+        &self,
+        scores: &[TrendingScore],
+    ) -> Result<(), ClickHouseError> {
+        self.observe(
+            "insert_trending_scores",
+            || format!("scores={}", scores.len()),
+            self.insert_trending_scores(scores),
+        )
+        .await
+    }
+}
+
+impl HealthQueries for crate::ClickHouseClient {
+    async fn ping(&self) -> Result<(), ClickHouseError> {
+        self.observe("ping", String::new, self.ping()).await
+    }
+
+    async fn check_schema(&self) -> Result<bool, ClickHouseError> {
+        self.observe("check_schema", String::new, self.check_schema())
+            .await
+    }
+
+    async fn get_replication_lag(&self) -> Result<u64, ClickHouseError> {
+        self.observe(
+            "get_replication_lag",
+            String::new,
+            self.get_replication_lag(),
+        )
+        .await
+    }
+}
+
+impl TrustQueries for crate::ClickHouseClient {
+    async fn get_follow_edges(&self) -> Result<Vec<FollowEdge>, ClickHouseError> {
+        self.observe("get_follow_edges", String::new, self.get_follow_edges())
+            .await
+    }
+
+    async fn insert_pubkey_trust(&self, scores: &[PubkeyTrust]) -> Result<(), ClickHouseError> {
+        self.observe(
+            "insert_pubkey_trust",
+            || format!("scores={}", scores.len()),
+            self.insert_pubkey_trust(scores),
+        )
+        .await
+    }
+}
+
+impl DuplicateQueries for crate::ClickHouseClient {
+    async fn get_duplicate_candidates(
+        &self,
+        created_after: DateTime<Utc>,
+    ) -> Result<Vec<DuplicateCandidate>, ClickHouseError> {
+        self.observe(
+            "get_duplicate_candidates",
+            || format!("created_after={created_after}"),
+            self.get_duplicate_candidates(created_after),
+        )
+        .await
+    }
+
+    async fn insert_video_duplicates(
+        &self,
+        duplicates: &[VideoDuplicate],
+    ) -> Result<(), ClickHouseError> {
+        self.observe(
+            "insert_video_duplicates",
+            || format!("duplicates={}", duplicates.len()),
+            self.insert_video_duplicates(duplicates),
+        )
+        .await
+    }
+}
+
+impl ProfileQueries for crate::ClickHouseClient {
+    async fn get_profile_targets(
+        &self,
+        created_after: DateTime<Utc>,
+        stale_before: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<Vec<String>, ClickHouseError> {
+        self.observe(
+            "get_profile_targets",
+            || format!("created_after={created_after}, stale_before={stale_before}, limit={limit}"),
+            self.get_profile_targets(created_after, stale_before, limit),
+        )
+        .await
+    }
+
+    async fn insert_profile_fetches(
+        &self,
+        fetches: &[ProfileFetch],
+    ) -> Result<(), ClickHouseError> {
+        self.observe(
+            "insert_profile_fetches",
+            || format!("fetches={}", fetches.len()),
+            self.insert_profile_fetches(fetches),
+        )
+        .await
+    }
+}
+
+impl MediaQueries for crate::ClickHouseClient {
+    async fn get_media_targets(
+        &self,
+        created_after: DateTime<Utc>,
+        checked_before: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<Vec<MediaTarget>, ClickHouseError> {
+        self.observe(
+            "get_media_targets",
+            || {
+                format!(
+                    "created_after={created_after}, checked_before={checked_before}, \
+                     limit={limit}"
+                )
+            },
+            self.get_media_targets(created_after, checked_before, limit),
+        )
+        .await
+    }
+
+    async fn insert_media_health(&self, checks: &[MediaHealth]) -> Result<(), ClickHouseError> {
+        self.observe(
+            "insert_media_health",
+            || format!("checks={}", checks.len()),
+            self.insert_media_health(checks),
+        )
+        .await
+    }
+
+    async fn get_verify_targets(
+        &self,
+        created_after: DateTime<Utc>,
+        checked_before: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<Vec<VerifyTarget>, ClickHouseError> {
+        self.observe(
+            "get_verify_targets",
+            || {
+                format!(
+                    "created_after={created_after}, checked_before={checked_before}, \
+                     limit={limit}"
+                )
+            },
+            self.get_verify_targets(created_after, checked_before, limit),
+        )
+        .await
+    }
+
+    async fn insert_media_verifications(
+        &self,
+        verifications: &[MediaVerification],
+    ) -> Result<(), ClickHouseError> {
+        self.observe(
+            "insert_media_verifications",
+            || format!("verifications={}", verifications.len()),
+            self.insert_media_verifications(verifications),
+        )
+        .await
     }
 }
@@ -0,0 +1,23 @@
+//! Web-of-trust filter for listing queries.
+//!
+//! Trust scores are computed from follow lists by the aggregator and stored as
+//! snapshots in `pubkey_trust`, in `(0, 1]`. Pubkeys missing from the latest
+//! snapshot have no trust, so any positive `min_trust` drops them.
+
+/// `AND` condition keeping rows whose `pubkey_column` has at least `min_trust`, or
+/// nothing when `min_trust` is `None`.
+///
+/// `min_trust` is formatted into the SQL rather than bound, so callers' bind order
+/// is unchanged; it is a finite float, so this is safe.
+pub(crate) fn trusted_filter(pubkey_column: &str, min_trust: Option<f64>) -> String {
+    match min_trust.filter(|min| min.is_finite() && *min > 0.0) {
+        Some(min) => format!(
+            "AND {pubkey_column} IN ( \
+                 SELECT pubkey FROM pubkey_trust \
+                 WHERE computed_at = (SELECT max(computed_at) FROM pubkey_trust) \
+                   AND trust >= {min} \
+             )"
+        ),
+        None => String::new(),
+    }
+}
@@ -0,0 +1,110 @@
+//! Dropping events live mode has already seen, before they're inserted.
+//!
+//! The relay pool only sends the first copy of each event it receives, but it
+//! forgets them on every reconnect, and live mode then resubscribes from 10 minutes
+//! before the newest stored event, so everything from those 10 minutes arrives again
+//! from every relay. ClickHouse's ReplacingMergeTree collapses the copies, but each
+//! is still inserted and merged. [`DedupCache`] remembers the IDs of the most
+//! recently seen `INGEST_DEDUP_CAPACITY` events (default 100,000) across reconnects
+//! and drops repeats before they're batched; set it to 0 to disable.
+//!
+//! Lookups are counted in `ingestion_dedup_lookups_total` by `result` (`hit` for a
+//! dropped repeat, `miss` for a new event), which gives the hit rate, and the
+//! cache's size is the `ingestion_dedup_entries` gauge.
+
+use std::num::NonZeroUsize;
+
+use funnel_observability::ingestion;
+use lru::LruCache;
+use metrics::{counter, gauge};
+
+/// Number of event IDs remembered unless `INGEST_DEDUP_CAPACITY` is set.
+pub const DEFAULT_CAPACITY: usize = 100_000;
+
+/// The IDs of the most recently seen events, least recently seen evicted first.
+#[derive(Debug)]
+pub struct DedupCache {
+    seen: LruCache<String, ()>,
+}
+
+impl DedupCache {
+    /// A cache remembering up to `capacity` event IDs.
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        gauge!(ingestion::DEDUP_ENTRIES).set(0.0);
+        Self {
+            seen: LruCache::new(capacity),
+        }
+    }
+
+    /// Record `id`, returning whether it's new. A repeat counts as seen again, so
+    /// an event that keeps arriving stays remembered.
+    pub fn insert(&mut self, id: &str) -> bool {
+        let new = self.seen.get(id).is_none();
+        if new {
+            self.seen.put(id.to_string(), ());
+            gauge!(ingestion::DEDUP_ENTRIES).set(self.seen.len() as f64);
+        }
+        let result = if new { "miss" } else { "hit" };
+        counter!(ingestion::DEDUP_LOOKUPS, "result" => result).increment(1);
+        new
+    }
+
+    /// Number of event IDs remembered.
+    pub fn len(&self) -> usize {
+        self.seen.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.seen.is_empty()
+    }
+
+    /// Forget every event, for when batches holding some of them weren't written.
+    pub fn clear(&mut self) {
+        self.seen.clear();
+        gauge!(ingestion::DEDUP_ENTRIES).set(0.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache(capacity: usize) -> DedupCache {
+        DedupCache::new(NonZeroUsize::new(capacity).unwrap())
+    }
+
+    #[test]
+    fn drops_repeats() {
+        let mut dedup = cache(10);
+
+        assert!(dedup.insert("a"));
+        assert!(dedup.insert("b"));
+        assert!(!dedup.insert("a"));
+        assert!(!dedup.insert("b"));
+        assert_eq!(dedup.len(), 2);
+    }
+
+    #[test]
+    fn forgets_least_recently_seen_first() {
+        let mut dedup = cache(2);
+        dedup.insert("a");
+        dedup.insert("b");
+        // Seeing "a" again keeps it, so "b" goes when "c" arrives
+        assert!(!dedup.insert("a"));
+        assert!(dedup.insert("c"));
+
+        assert!(!dedup.insert("a"));
+        assert!(dedup.insert("b"));
+        assert_eq!(dedup.len(), 2);
+    }
+
+    #[test]
+    fn clear_forgets_everything() {
+        let mut dedup = cache(10);
+        dedup.insert("a");
+
+        dedup.clear();
+        assert!(dedup.is_empty());
+        assert!(dedup.insert("a"));
+    }
+}
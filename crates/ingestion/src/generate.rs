@@ -0,0 +1,409 @@
+//! Synthetic event streams for load tests and demos.
+//!
+//! A [`Generator`] invents a population of creators and viewers and emits signed
+//! events among them: videos, and reactions, comments, and zap receipts on those
+//! videos. Engagement follows a power law, so a few videos collect most of it as on a
+//! real relay, and hashtags are drawn the same way. The same seed gives the same
+//! accounts and the same stream of events, except that zap receipts embed a freshly
+//! signed zap request and so get new IDs on every run.
+//!
+//! Events are valid Nostr events, so they go through parsing and storage like relay
+//! data, but zap receipts carry placeholder invoices.
+
+use ::rand::rngs::StdRng;
+use ::rand::{Rng, SeedableRng};
+use chrono::{DateTime, Utc};
+use nostr_sdk::prelude::*;
+
+use funnel_proto::{KIND_VIDEO, KIND_VIDEO_SHORT};
+
+const KIND_ZAP_REQUEST: u16 = 9734;
+const KIND_ZAP_RECEIPT: u16 = 9735;
+
+/// Hashtags videos are tagged with, most popular first.
+const HASHTAGS: [&str; 12] = [
+    "nostr", "music", "gaming", "news", "art", "comedy", "sports", "tech", "travel", "food",
+    "bitcoin", "pets",
+];
+
+const TITLE_WORDS: [&str; 12] = [
+    "Sunset",
+    "Live",
+    "Session",
+    "Morning",
+    "Review",
+    "Highlights",
+    "Tutorial",
+    "City",
+    "Quick",
+    "Weekend",
+    "Behind the Scenes",
+    "First Look",
+];
+
+const COMMENTS: [&str; 6] = [
+    "Love this!",
+    "Great video",
+    "Where was this filmed?",
+    "More like this please",
+    "Haha",
+    "Underrated",
+];
+
+const REACTIONS: [&str; 4] = ["+", "🔥", "❤️", "🤙"];
+
+/// Relative weights of each kind of event.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mix {
+    pub videos: u32,
+    pub reactions: u32,
+    pub comments: u32,
+    pub zaps: u32,
+}
+
+impl Default for Mix {
+    fn default() -> Self {
+        Self {
+            videos: 1,
+            reactions: 20,
+            comments: 5,
+            zaps: 2,
+        }
+    }
+}
+
+impl Mix {
+    fn total(&self) -> u32 {
+        self.videos + self.reactions + self.comments + self.zaps
+    }
+}
+
+/// Shape of the generated population and stream.
+#[derive(Debug, Clone)]
+pub struct GenConfig {
+    /// Accounts that publish videos.
+    pub creators: usize,
+    /// Accounts that react, comment, and zap.
+    pub viewers: usize,
+    /// Relative weights of each kind of event.
+    pub mix: Mix,
+    /// Share of videos that are shorts (kind 34236) rather than kind 34235.
+    pub short_ratio: f64,
+    /// How strongly engagement concentrates on the most popular videos; `1.0` is
+    /// uniform, larger values favour fewer videos.
+    pub skew: f64,
+    /// Seed for the population and the event sequence.
+    pub seed: u64,
+}
+
+impl Default for GenConfig {
+    fn default() -> Self {
+        Self {
+            creators: 50,
+            viewers: 1000,
+            mix: Mix::default(),
+            short_ratio: 0.3,
+            skew: 3.0,
+            seed: 0,
+        }
+    }
+}
+
+/// A generated video that later events engage with.
+#[derive(Debug, Clone)]
+struct Video {
+    id: EventId,
+    author: PublicKey,
+    coordinate: String,
+}
+
+/// Endless source of synthetic events.
+pub struct Generator {
+    config: GenConfig,
+    rng: StdRng,
+    creators: Vec<Keys>,
+    viewers: Vec<Keys>,
+    /// Signs zap receipts, like a Lightning wallet's zapper.
+    zapper: Keys,
+    /// Videos so far, in order of popularity.
+    videos: Vec<Video>,
+}
+
+impl Generator {
+    pub fn new(config: GenConfig) -> Self {
+        let mut rng = StdRng::seed_from_u64(config.seed);
+        let creators = (0..config.creators.max(1))
+            .map(|_| keys(&mut rng))
+            .collect();
+        let viewers = (0..config.viewers.max(1)).map(|_| keys(&mut rng)).collect();
+        let zapper = keys(&mut rng);
+
+        Self {
+            config,
+            rng,
+            creators,
+            viewers,
+            zapper,
+            videos: Vec::new(),
+        }
+    }
+
+    /// The next event, created at `created_at`.
+    ///
+    /// The first event is always a video, so engagement has something to target.
+    pub fn next_event(&mut self, created_at: DateTime<Utc>) -> Event {
+        let created_at = Timestamp::from(created_at.timestamp().max(0) as u64);
+        let mix = self.config.mix;
+        let roll = self.rng.random_range(0..mix.total().max(1));
+
+        if self.videos.is_empty() || roll < mix.videos || mix.total() == mix.videos {
+            self.video(created_at)
+        } else if roll < mix.videos + mix.reactions {
+            self.reaction(created_at)
+        } else if roll < mix.videos + mix.reactions + mix.comments {
+            self.comment(created_at)
+        } else {
+            self.zap(created_at)
+        }
+    }
+
+    fn video(&mut self, created_at: Timestamp) -> Event {
+        let author = self.creators[self.rng.random_range(0..self.creators.len())].clone();
+        let kind = if self
+            .rng
+            .random_bool(self.config.short_ratio.clamp(0.0, 1.0))
+        {
+            KIND_VIDEO_SHORT
+        } else {
+            KIND_VIDEO
+        };
+        let d_tag = format!("{:016x}", self.rng.random::<u64>());
+        let title = format!(
+            "{} {}",
+            TITLE_WORDS[self.rng.random_range(0..TITLE_WORDS.len())],
+            TITLE_WORDS[self.rng.random_range(0..TITLE_WORDS.len())]
+        );
+        let url = format!("https://cdn.example.com/{d_tag}.mp4");
+        let thumb = format!("https://cdn.example.com/{d_tag}.jpg");
+        let duration = self.rng.random_range(5..600u32);
+
+        let mut tags = vec![
+            Tag::identifier(&d_tag),
+            Tag::title(&title),
+            Tag::custom(TagKind::custom("url"), [url.as_str()]),
+            Tag::custom(TagKind::custom("thumb"), [thumb.as_str()]),
+            Tag::custom(
+                TagKind::custom("imeta"),
+                [
+                    format!("url {url}"),
+                    "m video/mp4".to_string(),
+                    format!("image {thumb}"),
+                ],
+            ),
+            Tag::custom(TagKind::custom("duration"), [duration.to_string()]),
+            Tag::custom(TagKind::custom("published_at"), [created_at.to_string()]),
+        ];
+        for _ in 0..self.rng.random_range(0..=3) {
+            let hashtag = HASHTAGS[self.popular(HASHTAGS.len())];
+            tags.push(Tag::hashtag(hashtag));
+        }
+
+        let event = sign(
+            EventBuilder::new(Kind::from(kind), format!("{title} #synthetic")).tags(tags),
+            created_at,
+            &author,
+        );
+        self.videos.push(Video {
+            id: event.id,
+            author: event.pubkey,
+            coordinate: format!("{kind}:{}:{d_tag}", event.pubkey.to_hex()),
+        });
+        event
+    }
+
+    fn reaction(&mut self, created_at: Timestamp) -> Event {
+        let video = self.target();
+        let viewer = self.viewer();
+        let content = REACTIONS[self.rng.random_range(0..REACTIONS.len())];
+        let builder = EventBuilder::new(Kind::Reaction, content).tags(engagement_tags(&video));
+        sign(builder, created_at, &viewer)
+    }
+
+    fn comment(&mut self, created_at: Timestamp) -> Event {
+        let video = self.target();
+        let viewer = self.viewer();
+        let content = COMMENTS[self.rng.random_range(0..COMMENTS.len())];
+        let builder = EventBuilder::new(Kind::TextNote, content).tags(engagement_tags(&video));
+        sign(builder, created_at, &viewer)
+    }
+
+    /// A zap receipt, embedding the viewer's zap request as a wallet would.
+    fn zap(&mut self, created_at: Timestamp) -> Event {
+        let video = self.target();
+        let viewer = self.viewer();
+        let sats = [21u64, 100, 500, 1000, 5000][self.popular(5)];
+        let msats = sats * 1000;
+
+        let request = sign(
+            EventBuilder::new(Kind::from(KIND_ZAP_REQUEST), "").tags([
+                Tag::event(video.id),
+                Tag::public_key(video.author),
+                Tag::custom(TagKind::custom("amount"), [msats.to_string()]),
+            ]),
+            created_at,
+            &viewer,
+        );
+
+        let mut tags = engagement_tags(&video);
+        tags.extend([
+            Tag::custom(TagKind::custom("P"), [viewer.public_key().to_hex()]),
+            Tag::custom(
+                TagKind::custom("bolt11"),
+                [format!("lnbc{sats}0n1synthetic")],
+            ),
+            Tag::description(request.as_json()),
+        ]);
+        sign(
+            EventBuilder::new(Kind::from(KIND_ZAP_RECEIPT), "").tags(tags),
+            created_at,
+            &self.zapper,
+        )
+    }
+
+    /// A video to engage with, favouring the most popular.
+    fn target(&mut self) -> Video {
+        let index = self.popular(self.videos.len());
+        self.videos[index].clone()
+    }
+
+    fn viewer(&mut self) -> Keys {
+        self.viewers[self.rng.random_range(0..self.viewers.len())].clone()
+    }
+
+    /// An index below `len`, power-law distributed so low indexes come up most.
+    fn popular(&mut self, len: usize) -> usize {
+        let u: f64 = self.rng.random();
+        ((u.powf(self.config.skew.max(1.0)) * len as f64) as usize).min(len - 1)
+    }
+}
+
+/// Tags pointing engagement at a video.
+fn engagement_tags(video: &Video) -> Vec<Tag> {
+    vec![
+        Tag::event(video.id),
+        Tag::public_key(video.author),
+        Tag::custom(TagKind::custom("a"), [video.coordinate.as_str()]),
+    ]
+}
+
+/// Keys derived from `rng`, so a seed always produces the same accounts.
+fn keys(rng: &mut StdRng) -> Keys {
+    loop {
+        // Nearly every 32-byte value is a valid secret key
+        if let Ok(secret_key) = SecretKey::from_slice(&rng.random::<[u8; 32]>()) {
+            return Keys::new(secret_key);
+        }
+    }
+}
+
+fn sign(builder: EventBuilder, created_at: Timestamp, keys: &Keys) -> Event {
+    builder
+        .custom_created_at(created_at)
+        .sign_with_keys(keys)
+        .expect("signing with local keys is infallible")
+}
+
+#[cfg(test)]
+mod tests {
+    use funnel_proto::ParsedEvent;
+
+    use super::*;
+
+    fn kinds(generator: &mut Generator, count: usize) -> Vec<u16> {
+        (0..count)
+            .map(|i| {
+                generator
+                    .next_event(DateTime::from_timestamp(1_700_000_000 + i as i64, 0).unwrap())
+                    .kind
+                    .as_u16()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_gives_same_stream() {
+        let config = GenConfig {
+            seed: 7,
+            ..GenConfig::default()
+        };
+        let mut a = Generator::new(config.clone());
+        let mut b = Generator::new(config);
+
+        let created_at = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        for _ in 0..50 {
+            let (x, y) = (a.next_event(created_at), b.next_event(created_at));
+            assert_eq!(x.kind, y.kind);
+            assert_eq!(x.pubkey, y.pubkey);
+            if x.kind != Kind::from(KIND_ZAP_RECEIPT) {
+                assert_eq!(x.id, y.id);
+            }
+        }
+    }
+
+    #[test]
+    fn stream_follows_mix_and_starts_with_a_video() {
+        let mut generator = Generator::new(GenConfig {
+            mix: Mix {
+                videos: 1,
+                reactions: 1,
+                comments: 0,
+                zaps: 1,
+            },
+            ..GenConfig::default()
+        });
+
+        let kinds = kinds(&mut generator, 300);
+        assert!(matches!(kinds[0], KIND_VIDEO | KIND_VIDEO_SHORT));
+        assert!(!kinds.contains(&1));
+        let reactions = kinds.iter().filter(|&&kind| kind == 7).count();
+        let zaps = kinds
+            .iter()
+            .filter(|&&kind| kind == KIND_ZAP_RECEIPT)
+            .count();
+        assert!((60..140).contains(&reactions), "{reactions} reactions");
+        assert!((60..140).contains(&zaps), "{zaps} zaps");
+    }
+
+    #[test]
+    fn events_are_valid_and_engage_with_generated_videos() {
+        let mut generator = Generator::new(GenConfig::default());
+        let mut videos = Vec::new();
+
+        for i in 0..200 {
+            let event =
+                generator.next_event(DateTime::from_timestamp(1_700_000_000 + i, 0).unwrap());
+            assert!(event.verify().is_ok());
+
+            let parsed = ParsedEvent::from_json(&event.as_json()).unwrap();
+            if parsed.is_video() {
+                assert!(parsed.get_tag("title").is_some());
+                assert!(parsed.get_tag("url").is_some());
+                videos.push(parsed.id);
+            } else {
+                let target = parsed.get_tag("e").unwrap();
+                assert!(videos.iter().any(|id| id == target));
+            }
+        }
+    }
+
+    #[test]
+    fn popular_favours_low_indexes() {
+        let mut generator = Generator::new(GenConfig::default());
+        let picks: Vec<usize> = (0..1000).map(|_| generator.popular(10)).collect();
+
+        assert!(picks.iter().all(|&index| index < 10));
+        let top = picks.iter().filter(|&&index| index == 0).count();
+        let bottom = picks.iter().filter(|&&index| index == 9).count();
+        assert!(top > bottom * 3, "top {top}, bottom {bottom}");
+    }
+}
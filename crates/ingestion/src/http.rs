@@ -0,0 +1,154 @@
+//! HTTP endpoints for the live ingester.
+//!
+//! `/metrics` serves the process's Prometheus metrics and `/health` the state of the
+//! stream: whether a relay is connected, how long ago the last batch was flushed, how
+//! far behind the newest batch was, and the latest self-check [`Heartbeat`] report.
+//! `/health` returns 503 until the first round of self-checks has passed and
+//! whenever one fails, so it works as a container health check.
+//!
+//! Served on `INGEST_BIND_ADDR` (default `0.0.0.0:9091`); set it empty to disable.
+
+use std::sync::Arc;
+
+use axum::{
+    Json, Router,
+    extract::State,
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use funnel_observability::heartbeat::{HealthReport, Heartbeat};
+use metrics_exporter_prometheus::PrometheusHandle;
+use serde::Serialize;
+
+use crate::LiveStatus;
+
+/// Address the endpoints are served on unless `INGEST_BIND_ADDR` is set.
+pub const DEFAULT_BIND_ADDR: &str = "0.0.0.0:9091";
+
+/// Read `INGEST_BIND_ADDR`, falling back to [`DEFAULT_BIND_ADDR`]. `None` when it's
+/// set empty, disabling the endpoints.
+pub fn bind_addr_from_env() -> Option<String> {
+    match std::env::var("INGEST_BIND_ADDR") {
+        Ok(addr) if addr.trim().is_empty() => None,
+        Ok(addr) => Some(addr.trim().to_string()),
+        Err(_) => Some(DEFAULT_BIND_ADDR.to_string()),
+    }
+}
+
+/// Body returned by `/health`.
+#[derive(Debug, Clone, Serialize)]
+pub struct HealthResponse {
+    /// `ok`, `unhealthy`, or `starting` before the first self-check.
+    pub status: &'static str,
+    pub relay_connected: bool,
+    /// Seconds since the stream last flushed.
+    pub last_flush_secs: u64,
+    /// Age of the oldest event in the newest batch, in seconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lag_seconds: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub heartbeat: Option<HealthReport>,
+}
+
+#[derive(Clone)]
+struct HealthState {
+    heartbeat: Heartbeat,
+    status: Arc<LiveStatus>,
+}
+
+/// Router serving `/metrics` from `metrics` and `/health` from `heartbeat` and
+/// `status`.
+pub fn router(metrics: PrometheusHandle, heartbeat: Heartbeat, status: Arc<LiveStatus>) -> Router {
+    Router::new()
+        .route(
+            "/metrics",
+            get(move || async move { ([(header::CACHE_CONTROL, "no-store")], metrics.render()) }),
+        )
+        .route("/health", get(health))
+        .with_state(HealthState { heartbeat, status })
+}
+
+/// Serve `app` on `addr` in the background. Binding happens before returning, so a
+/// bad or taken address fails startup.
+pub async fn spawn(addr: &str, app: Router) -> anyhow::Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    tracing::info!("Serving /metrics and /health on {}", addr);
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app).await {
+            tracing::error!(error = %e, "HTTP listener failed");
+        }
+    });
+    Ok(())
+}
+
+async fn health(State(state): State<HealthState>) -> Response {
+    let heartbeat = state.heartbeat.report();
+    let (code, status) = match &heartbeat {
+        Some(report) if report.healthy => (StatusCode::OK, "ok"),
+        Some(_) => (StatusCode::SERVICE_UNAVAILABLE, "unhealthy"),
+        None => (StatusCode::SERVICE_UNAVAILABLE, "starting"),
+    };
+    let response = HealthResponse {
+        status,
+        relay_connected: state.status.relay_connected(),
+        last_flush_secs: state.status.last_flush_age().as_secs(),
+        lag_seconds: state.status.event_lag_secs(),
+        heartbeat,
+    };
+
+    (code, [(header::CACHE_CONTROL, "no-store")], Json(response)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use axum_test::TestServer;
+    use funnel_observability::heartbeat::CheckFailure;
+    use metrics_exporter_prometheus::PrometheusBuilder;
+
+    use super::*;
+
+    fn server(heartbeat: Heartbeat, status: Arc<LiveStatus>) -> TestServer {
+        let metrics = PrometheusBuilder::new().build_recorder().handle();
+        TestServer::new(router(metrics, heartbeat, status)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn health_follows_heartbeat() {
+        let heartbeat = Heartbeat::default();
+        let status = Arc::new(LiveStatus::default());
+        let server = server(heartbeat.clone(), status.clone());
+
+        let response = server.get("/health").await;
+        response.assert_status(StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.json::<serde_json::Value>()["status"], "starting");
+
+        status.record_relay_connected(true);
+        status.record_event_lag(7);
+        heartbeat.record(Vec::new());
+        let response = server.get("/health").await;
+        response.assert_status_ok();
+        assert_eq!(response.header(header::CACHE_CONTROL), "no-store");
+        let body = response.json::<serde_json::Value>();
+        assert_eq!(body["status"], "ok");
+        assert_eq!(body["relay_connected"], true);
+        assert_eq!(body["last_flush_secs"], 0);
+        assert_eq!(body["lag_seconds"], 7);
+
+        heartbeat.record(vec![CheckFailure::new("relay_connected", "reconnecting")]);
+        let response = server.get("/health").await;
+        response.assert_status(StatusCode::SERVICE_UNAVAILABLE);
+        let body = response.json::<serde_json::Value>();
+        assert_eq!(body["status"], "unhealthy");
+        assert_eq!(body["heartbeat"]["failures"][0]["check"], "relay_connected");
+    }
+
+    #[tokio::test]
+    async fn serves_metrics() {
+        let server = server(Heartbeat::default(), Arc::new(LiveStatus::default()));
+
+        let response = server.get("/metrics").await;
+        response.assert_status_ok();
+        assert_eq!(response.header(header::CACHE_CONTROL), "no-store");
+    }
+}
@@ -1,10 +1,33 @@
 //! Funnel Ingestion Library
 //!
-//! Core components for reading Nostr events and batching them for ClickHouse insertion.
-
+//! Core components for reading Nostr events and batching them for ClickHouse insertion,
+//! the relay ingestion service ([`service`]) and its HTTP endpoints ([`http`]), replay of
+//! event files ([`replay`]) with optional signature checks ([`verify`]), durable queues
+//! as an event source ([`source`]), a disk queue for batches ClickHouse can't take
+//! ([`spill`]), saved backfill progress ([`progress`]), and synthetic event streams for
+//! load tests and demos ([`generate`]).
+
+pub mod dedup;
+pub mod generate;
+pub mod http;
+pub mod progress;
+pub mod replay;
+pub mod service;
+pub mod source;
+pub mod spill;
+pub mod verify;
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::time::{Duration, Instant};
 
-use funnel_proto::ParsedEvent;
+use chrono::{DateTime, TimeDelta, Utc};
+use funnel_clickhouse::{ClickHouseError, EventRow, EventWriter};
+use funnel_observability::heartbeat::CheckFailure;
+use funnel_observability::ingestion;
+use funnel_proto::{ParsedEvent, normalize_relay_url};
+use metrics::counter;
 
 /// Configuration for the batch processor.
 #[derive(Debug, Clone)]
@@ -47,17 +70,27 @@ pub enum FlushReason {
 /// Batch processor that accumulates events and determines when to flush.
 ///
 /// This is a pure data structure that doesn't perform I/O. The caller is responsible
-/// for actually flushing the batch to storage.
+/// for actually flushing the batch to storage, usually through an
+/// [`EventWriter`]. Batches hold [`ParsedEvent`]s unless built with
+/// [`with_config`](Self::with_config), which batches anything, such as events paired
+/// with the relay they came from.
 #[derive(Debug)]
-pub struct BatchProcessor {
+pub struct BatchProcessor<T = ParsedEvent> {
     config: BatchConfig,
-    batch: Vec<ParsedEvent>,
+    batch: Vec<T>,
     last_flush: Instant,
 }
 
 impl BatchProcessor {
     /// Create a new batch processor with the given configuration.
     pub fn new(config: BatchConfig) -> Self {
+        Self::with_config(config)
+    }
+}
+
+impl<T> BatchProcessor<T> {
+    /// Create a batch processor of `T` with the given configuration.
+    pub fn with_config(config: BatchConfig) -> Self {
         Self {
             batch: Vec::with_capacity(config.max_batch_size),
             config,
@@ -66,7 +99,7 @@ impl BatchProcessor {
     }
 
     /// Add an event to the batch.
-    pub fn push(&mut self, event: ParsedEvent) {
+    pub fn push(&mut self, event: T) {
         self.batch.push(event);
     }
 
@@ -85,7 +118,7 @@ impl BatchProcessor {
     /// Take the current batch for flushing and reset internal state.
     ///
     /// Returns `None` if the batch is empty.
-    pub fn take_batch(&mut self) -> Option<Vec<ParsedEvent>> {
+    pub fn take_batch(&mut self) -> Option<Vec<T>> {
         if self.batch.is_empty() {
             return None;
         }
@@ -95,7 +128,7 @@ impl BatchProcessor {
     }
 
     /// Force take the batch even if empty (useful for shutdown).
-    pub fn take_batch_force(&mut self) -> Vec<ParsedEvent> {
+    pub fn take_batch_force(&mut self) -> Vec<T> {
         self.last_flush = Instant::now();
         std::mem::take(&mut self.batch)
     }
@@ -111,10 +144,15 @@ impl BatchProcessor {
     }
 
     /// Get the oldest event's timestamp for lag calculation.
-    pub fn oldest_event(&self) -> Option<&ParsedEvent> {
+    pub fn oldest_event(&self) -> Option<&T> {
         self.batch.first()
     }
 
+    /// Events in the batch, oldest first.
+    pub fn events(&self) -> &[T] {
+        &self.batch
+    }
+
     /// Get the configured flush interval.
     pub fn flush_interval(&self) -> Duration {
         self.config.flush_interval
@@ -126,9 +164,356 @@ impl BatchProcessor {
     }
 }
 
+/// Jittered exponential backoff between retries.
+///
+/// Each delay doubles the previous one, up to `max`, and is then jittered down by up
+/// to half so that ingesters restarted together don't retry in lockstep.
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    base: Duration,
+    max: Duration,
+    attempts: u32,
+}
+
+impl Backoff {
+    pub fn new(base: Duration, max: Duration) -> Self {
+        Self {
+            base,
+            max,
+            attempts: 0,
+        }
+    }
+
+    /// Delay before the next retry, counting it as an attempt.
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self
+            .base
+            .saturating_mul(2u32.saturating_pow(self.attempts))
+            .min(self.max);
+        self.attempts = self.attempts.saturating_add(1);
+        delay.mul_f64(rand::random_range(0.5..=1.0))
+    }
+
+    /// Retries since the last reset.
+    pub fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// Start over from `base`, after a success.
+    pub fn reset(&mut self) {
+        self.attempts = 0;
+    }
+}
+
+/// Retry settings for ClickHouse inserts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryConfig {
+    /// Attempts per insert, including the first. `0` and `1` both disable retries.
+    pub attempts: u32,
+    /// Delay before the first retry, doubled for each one after it.
+    pub base_delay: Duration,
+    /// Longest delay between retries.
+    pub max_delay: Duration,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            attempts: 5,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryConfig {
+    /// Load from `INSERT_RETRY_ATTEMPTS`, `INSERT_RETRY_BASE_MS`, and
+    /// `INSERT_RETRY_MAX_MS`.
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let millis = |var: &str| {
+            std::env::var(var)
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .map(Duration::from_millis)
+        };
+        Self {
+            attempts: std::env::var("INSERT_RETRY_ATTEMPTS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(defaults.attempts),
+            base_delay: millis("INSERT_RETRY_BASE_MS").unwrap_or(defaults.base_delay),
+            max_delay: millis("INSERT_RETRY_MAX_MS").unwrap_or(defaults.max_delay),
+        }
+    }
+}
+
+/// An [`EventWriter`] that retries inserts failing with a transient error (see
+/// [`ClickHouseError::is_transient`]), with [`Backoff`] between attempts. Permanent
+/// errors, and the last transient one, are returned.
+#[derive(Debug)]
+pub struct RetryingWriter<'a, W> {
+    writer: &'a W,
+    config: RetryConfig,
+}
+
+impl<'a, W: EventWriter> RetryingWriter<'a, W> {
+    pub fn new(writer: &'a W, config: RetryConfig) -> Self {
+        Self { writer, config }
+    }
+}
+
+impl<W> EventWriter for RetryingWriter<'_, W>
+where
+    W: EventWriter,
+{
+    async fn insert_events(&self, events: &[EventRow]) -> Result<(), ClickHouseError> {
+        let mut backoff = Backoff::new(self.config.base_delay, self.config.max_delay);
+        loop {
+            match self.writer.insert_events(events).await {
+                Ok(()) => return Ok(()),
+                Err(e) if e.is_transient() && backoff.attempts() + 1 < self.config.attempts => {
+                    let delay = backoff.next_delay();
+                    counter!(ingestion::INSERT_RETRIES).increment(1);
+                    tracing::warn!(
+                        code = %e.code(),
+                        error = %e,
+                        attempt = backoff.attempts(),
+                        delay_ms = delay.as_millis() as u64,
+                        "Insert failed, retrying"
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Progress of the live stream, shared with the self-check heartbeat and the
+/// `/health` endpoint.
+#[derive(Debug)]
+pub struct LiveStatus {
+    last_flush: Mutex<Instant>,
+    lagged: AtomicU64,
+    relay_connected: AtomicBool,
+    lag_secs: Mutex<Option<i64>>,
+}
+
+impl Default for LiveStatus {
+    fn default() -> Self {
+        Self {
+            last_flush: Mutex::new(Instant::now()),
+            lagged: AtomicU64::new(0),
+            relay_connected: AtomicBool::new(false),
+            lag_secs: Mutex::new(None),
+        }
+    }
+}
+
+impl LiveStatus {
+    /// Record that everything received so far has been written (including when
+    /// there was nothing to write).
+    pub fn record_flush(&self) {
+        *self.last_flush.lock().unwrap_or_else(|e| e.into_inner()) = Instant::now();
+    }
+
+    /// Time since the last flush.
+    pub fn last_flush_age(&self) -> Duration {
+        self.last_flush
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .elapsed()
+    }
+
+    /// Record whether any relay is connected.
+    pub fn record_relay_connected(&self, connected: bool) {
+        self.relay_connected.store(connected, Ordering::Relaxed);
+    }
+
+    /// Whether a relay was connected at the last check.
+    pub fn relay_connected(&self) -> bool {
+        self.relay_connected.load(Ordering::Relaxed)
+    }
+
+    /// Record the age in seconds of the oldest event in the batch being flushed.
+    pub fn record_event_lag(&self, lag_secs: i64) {
+        *self.lag_secs.lock().unwrap_or_else(|e| e.into_inner()) = Some(lag_secs);
+    }
+
+    /// Age of the oldest event in the latest batch, or `None` before the first.
+    pub fn event_lag_secs(&self) -> Option<i64> {
+        *self.lag_secs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Record `skipped` events dropped because the notification channel lagged.
+    pub fn record_lag(&self, skipped: u64) {
+        self.lagged.fetch_add(skipped, Ordering::Relaxed);
+    }
+
+    /// Check that a flush happened within `max_flush_age` and that no events were
+    /// dropped since the last check.
+    pub fn check(&self, max_flush_age: Duration) -> Vec<CheckFailure> {
+        let mut failures = Vec::new();
+
+        let since_flush = self.last_flush_age();
+        if since_flush > max_flush_age {
+            failures.push(CheckFailure::new(
+                "flush_recent",
+                format!("last flush {}s ago", since_flush.as_secs()),
+            ));
+        }
+
+        let lagged = self.lagged.swap(0, Ordering::Relaxed);
+        if lagged > 0 {
+            failures.push(CheckFailure::new(
+                "channel_not_lagged",
+                format!("{lagged} events dropped since last check"),
+            ));
+        }
+
+        failures
+    }
+}
+
+/// What one relay sent since its counts were last taken.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RelayCounts {
+    /// Every event the relay sent, duplicates included.
+    pub received: u64,
+    /// Events no relay had sent before.
+    pub unique: u64,
+    /// Unique events that are videos.
+    pub unique_videos: u64,
+    /// Unique events that couldn't be parsed.
+    pub parse_failures: u64,
+}
+
+impl RelayCounts {
+    /// Events already received from this relay or another.
+    pub fn duplicates(&self) -> u64 {
+        self.received.saturating_sub(self.unique)
+    }
+}
+
+/// Per-relay counts for the live stream, keyed by relay URL.
+///
+/// The relay pool reports every event a relay sends, and separately the first copy
+/// of each event across all relays; the difference is the relay's duplicates. Also
+/// tracks when each relay last sent an event and how often it has connected, which
+/// taking the counts doesn't reset.
+#[derive(Debug, Default)]
+pub struct RelayCounters {
+    counts: HashMap<String, RelayCounts>,
+    last_event: HashMap<String, Instant>,
+    connections: HashMap<String, usize>,
+}
+
+impl RelayCounters {
+    /// Record an event sent by `relay_url`, whether or not it was new.
+    pub fn record_received(&mut self, relay_url: &str) {
+        self.entry(relay_url).received += 1;
+        self.last_event
+            .insert(relay_url.to_string(), Instant::now());
+    }
+
+    /// When `relay_url` last sent an event, if it has.
+    pub fn last_event(&self, relay_url: &str) -> Option<Instant> {
+        self.last_event.get(relay_url).copied()
+    }
+
+    /// Record that `relay_url` has connected `total` times, returning how many of
+    /// those connections weren't recorded before.
+    pub fn record_connections(&mut self, relay_url: &str, total: usize) -> usize {
+        let previous = self.connections.insert(relay_url.to_string(), total);
+        total.saturating_sub(previous.unwrap_or(0))
+    }
+
+    /// Record the first copy of an event, sent by `relay_url`, and whether it parsed.
+    pub fn record_unique(&mut self, relay_url: &str, parsed: Option<&ParsedEvent>) {
+        let counts = self.entry(relay_url);
+        counts.unique += 1;
+        match parsed {
+            Some(event) if event.is_video() => counts.unique_videos += 1,
+            Some(_) => {}
+            None => counts.parse_failures += 1,
+        }
+    }
+
+    /// Take `relay_url`'s counts, resetting them to zero.
+    pub fn take(&mut self, relay_url: &str) -> RelayCounts {
+        self.counts.remove(relay_url).unwrap_or_default()
+    }
+
+    fn entry(&mut self, relay_url: &str) -> &mut RelayCounts {
+        self.counts.entry(relay_url.to_string()).or_default()
+    }
+}
+
+/// Where each relay's live subscription resumes, from the newest event stored from
+/// it (`relay_source`).
+///
+/// Relay URLs are compared as [`normalize_relay_url`] writes them, so a configured
+/// URL matches the one the relay pool recorded however either is spelled.
+#[derive(Debug, Default)]
+pub struct RelayCheckpoints {
+    latest: HashMap<String, DateTime<Utc>>,
+    newest: Option<DateTime<Utc>>,
+}
+
+impl RelayCheckpoints {
+    /// Checkpoints from `(relay_source, latest_event_at)` pairs. Sources that aren't
+    /// relay URLs, such as the empty source of backfilled events, only count toward
+    /// the newest event overall.
+    pub fn new(checkpoints: impl IntoIterator<Item = (String, DateTime<Utc>)>) -> Self {
+        let mut latest: HashMap<String, DateTime<Utc>> = HashMap::new();
+        let mut newest = None;
+        for (source, at) in checkpoints {
+            newest = newest.max(Some(at));
+            if let Some(url) = normalize_relay_url(&source) {
+                let entry = latest.entry(url).or_insert(at);
+                *entry = (*entry).max(at);
+            }
+        }
+        Self { latest, newest }
+    }
+
+    /// When `relay_url`'s subscription should start: `buffer` before the newest event
+    /// stored from it or, for a relay nothing has been stored from yet, before the
+    /// newest event from any source. `None` when nothing is stored at all.
+    pub fn since(&self, relay_url: &str, buffer: TimeDelta) -> Option<DateTime<Utc>> {
+        normalize_relay_url(relay_url)
+            .and_then(|url| self.latest.get(&url).copied())
+            .or(self.newest)
+            .map(|at| at - buffer)
+    }
+}
+
+/// Split `[since, until]` into consecutive windows of `window`, newest first, for a
+/// backfill fetching several at once. Bounds are inclusive and whole seconds, so
+/// each window ends a second before the next newer one starts. A `window` shorter
+/// than a second is treated as one second.
+pub fn backfill_windows(
+    since: DateTime<Utc>,
+    until: DateTime<Utc>,
+    window: TimeDelta,
+) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+    let window = window.num_seconds().max(1);
+    let since = since.timestamp();
+    let mut end = until.timestamp();
+    let mut windows = Vec::new();
+    while end >= since {
+        let start = end.saturating_sub(window - 1).max(since);
+        windows.extend(DateTime::from_timestamp(start, 0).zip(DateTime::from_timestamp(end, 0)));
+        end = start - 1;
+    }
+    windows
+}
+
 /// Parse a line from strfry stream or raw event JSON.
 ///
-/// Returns `None` if the line cannot be parsed.
+/// Returns `None` if the line cannot be parsed or is a strfry message without an
+/// event.
 pub fn parse_line(line: &str) -> Option<ParsedEvent> {
     use funnel_proto::StrfryMessage;
 
@@ -137,10 +522,9 @@ pub fn parse_line(line: &str) -> Option<ParsedEvent> {
     }
 
     // Try to parse as strfry message first, then as raw event
-    if let Ok(msg) = StrfryMessage::from_json(line) {
-        Some(msg.to_parsed_event())
-    } else {
-        ParsedEvent::from_json(line).ok()
+    match StrfryMessage::from_json(line) {
+        Ok(msg) => msg.to_parsed_event(),
+        Err(_) => ParsedEvent::from_json(line).ok(),
     }
 }
 
@@ -158,6 +542,7 @@ mod tests {
             content: "test".to_string(),
             sig: "test_sig".to_string(),
             tags: vec![],
+            received_at: None,
         }
     }
 
@@ -293,6 +678,21 @@ mod tests {
             assert!(processor.time_since_flush() < Duration::from_millis(20));
         }
 
+        #[test]
+        fn batches_other_item_types() {
+            let config = BatchConfig::new(2, Duration::from_secs(60));
+            let mut processor = BatchProcessor::with_config(config);
+
+            processor.push(("wss://relay.example.com", make_test_event("1", 1)));
+            processor.push(("wss://other.example.com", make_test_event("2", 1)));
+            assert_eq!(processor.should_flush(), FlushReason::BatchFull);
+            assert_eq!(processor.events()[1].0, "wss://other.example.com");
+
+            let batch = processor.take_batch().unwrap();
+            assert_eq!(batch[0].1.id, "1");
+            assert!(processor.is_empty());
+        }
+
         #[test]
         fn take_batch_force_returns_empty_vec() {
             let mut processor = BatchProcessor::new(BatchConfig::default());
@@ -301,6 +701,287 @@ mod tests {
         }
     }
 
+    mod backoff_tests {
+        use super::*;
+
+        #[test]
+        fn delays_double_up_to_max_with_jitter() {
+            let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(10));
+
+            for cap in [1, 2, 4, 8, 10, 10] {
+                let delay = backoff.next_delay();
+                let cap = Duration::from_secs(cap);
+                assert!(delay >= cap / 2 && delay <= cap, "{delay:?} for {cap:?}");
+            }
+            assert_eq!(backoff.attempts(), 6);
+        }
+
+        #[test]
+        fn reset_starts_over() {
+            let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(60));
+            for _ in 0..5 {
+                backoff.next_delay();
+            }
+
+            backoff.reset();
+            assert_eq!(backoff.attempts(), 0);
+            assert!(backoff.next_delay() <= Duration::from_secs(1));
+        }
+    }
+
+    mod retrying_writer_tests {
+        use super::*;
+
+        /// Fails the first `failures` inserts with `error`, then succeeds.
+        struct FlakyWriter {
+            failures: Mutex<u32>,
+            error: fn() -> ClickHouseError,
+            calls: AtomicU64,
+        }
+
+        impl FlakyWriter {
+            fn new(failures: u32, error: fn() -> ClickHouseError) -> Self {
+                Self {
+                    failures: Mutex::new(failures),
+                    error,
+                    calls: AtomicU64::new(0),
+                }
+            }
+        }
+
+        impl EventWriter for FlakyWriter {
+            async fn insert_events(&self, _: &[EventRow]) -> Result<(), ClickHouseError> {
+                self.calls.fetch_add(1, Ordering::Relaxed);
+                let mut failures = self.failures.lock().unwrap();
+                if *failures == 0 {
+                    return Ok(());
+                }
+                *failures -= 1;
+                Err((self.error)())
+            }
+        }
+
+        fn config(attempts: u32) -> RetryConfig {
+            RetryConfig {
+                attempts,
+                base_delay: Duration::from_millis(1),
+                max_delay: Duration::from_millis(2),
+            }
+        }
+
+        fn transient() -> ClickHouseError {
+            ClickHouseError::Connection("refused".to_string())
+        }
+
+        fn permanent() -> ClickHouseError {
+            ClickHouseError::Config("bad schema".to_string())
+        }
+
+        #[tokio::test]
+        async fn retries_transient_errors() {
+            let writer = FlakyWriter::new(2, transient);
+
+            RetryingWriter::new(&writer, config(3))
+                .insert_events(&[])
+                .await
+                .unwrap();
+            assert_eq!(writer.calls.load(Ordering::Relaxed), 3);
+        }
+
+        #[tokio::test]
+        async fn gives_up_after_attempts() {
+            let writer = FlakyWriter::new(5, transient);
+
+            let result = RetryingWriter::new(&writer, config(3))
+                .insert_events(&[])
+                .await;
+            assert!(result.is_err());
+            assert_eq!(writer.calls.load(Ordering::Relaxed), 3);
+        }
+
+        #[tokio::test]
+        async fn does_not_retry_permanent_errors() {
+            let writer = FlakyWriter::new(1, permanent);
+
+            let result = RetryingWriter::new(&writer, config(3))
+                .insert_events(&[])
+                .await;
+            assert!(matches!(result, Err(ClickHouseError::Config(_))));
+            assert_eq!(writer.calls.load(Ordering::Relaxed), 1);
+        }
+    }
+
+    mod live_status_tests {
+        use super::*;
+
+        #[test]
+        fn fresh_status_is_healthy() {
+            let status = LiveStatus::default();
+            assert!(status.check(Duration::from_secs(60)).is_empty());
+        }
+
+        #[test]
+        fn stale_flush_fails_until_next_flush() {
+            let status = LiveStatus::default();
+            sleep(Duration::from_millis(15));
+
+            let failures = status.check(Duration::from_millis(10));
+            assert_eq!(failures.len(), 1);
+            assert_eq!(failures[0].check, "flush_recent");
+
+            status.record_flush();
+            assert!(status.check(Duration::from_millis(10)).is_empty());
+        }
+
+        #[test]
+        fn lag_is_reported_once() {
+            let status = LiveStatus::default();
+            status.record_lag(3);
+            status.record_lag(2);
+
+            let failures = status.check(Duration::from_secs(60));
+            assert_eq!(failures.len(), 1);
+            assert_eq!(failures[0].check, "channel_not_lagged");
+            assert_eq!(failures[0].reason, "5 events dropped since last check");
+
+            assert!(status.check(Duration::from_secs(60)).is_empty());
+        }
+    }
+
+    mod relay_counters_tests {
+        use super::*;
+
+        const RELAY_A: &str = "wss://a.example.com";
+        const RELAY_B: &str = "wss://b.example.com";
+
+        #[test]
+        fn duplicates_are_events_received_that_were_not_new() {
+            let mut counters = RelayCounters::default();
+            let video = make_test_event("v1", 34235);
+            let reaction = make_test_event("r1", 7);
+
+            // A sends both events first; B sends the video again, then an unparseable one
+            for relay in [RELAY_A, RELAY_A, RELAY_B, RELAY_B] {
+                counters.record_received(relay);
+            }
+            counters.record_unique(RELAY_A, Some(&video));
+            counters.record_unique(RELAY_A, Some(&reaction));
+            counters.record_unique(RELAY_B, None);
+
+            let a = counters.take(RELAY_A);
+            assert_eq!(
+                a,
+                RelayCounts {
+                    received: 2,
+                    unique: 2,
+                    unique_videos: 1,
+                    parse_failures: 0,
+                }
+            );
+            assert_eq!(a.duplicates(), 0);
+
+            let b = counters.take(RELAY_B);
+            assert_eq!((b.received, b.unique, b.parse_failures), (2, 1, 1));
+            assert_eq!(b.duplicates(), 1);
+        }
+
+        #[test]
+        fn take_resets_counts() {
+            let mut counters = RelayCounters::default();
+            counters.record_received(RELAY_A);
+
+            assert_eq!(counters.take(RELAY_A).received, 1);
+            assert_eq!(counters.take(RELAY_A), RelayCounts::default());
+            // When it last sent an event outlives its counts
+            assert!(counters.last_event(RELAY_A).is_some());
+            assert!(counters.last_event(RELAY_B).is_none());
+        }
+
+        #[test]
+        fn counts_new_connections() {
+            let mut counters = RelayCounters::default();
+
+            assert_eq!(counters.record_connections(RELAY_A, 1), 1);
+            assert_eq!(counters.record_connections(RELAY_A, 1), 0);
+            assert_eq!(counters.record_connections(RELAY_A, 3), 2);
+            assert_eq!(counters.record_connections(RELAY_B, 2), 2);
+        }
+    }
+
+    mod relay_checkpoints_tests {
+        use super::*;
+
+        fn at(secs: i64) -> DateTime<Utc> {
+            DateTime::from_timestamp(secs, 0).unwrap()
+        }
+
+        #[test]
+        fn each_relay_resumes_from_its_own_events() {
+            let checkpoints = RelayCheckpoints::new([
+                ("wss://relay-a.example.com".to_string(), at(1_000)),
+                ("wss://relay-b.example.com".to_string(), at(5_000)),
+                (String::new(), at(9_000)),
+            ]);
+            let buffer = TimeDelta::seconds(100);
+
+            assert_eq!(
+                checkpoints.since("WSS://relay-a.example.com:443/", buffer),
+                Some(at(900))
+            );
+            assert_eq!(
+                checkpoints.since("wss://relay-b.example.com", buffer),
+                Some(at(4_900))
+            );
+            // A relay without stored events starts from the newest event overall
+            assert_eq!(
+                checkpoints.since("wss://relay-c.example.com", buffer),
+                Some(at(8_900))
+            );
+        }
+
+        #[test]
+        fn nothing_stored_has_no_checkpoint() {
+            let checkpoints = RelayCheckpoints::default();
+            assert_eq!(
+                checkpoints.since("wss://relay.example.com", TimeDelta::zero()),
+                None
+            );
+        }
+    }
+
+    mod backfill_windows_tests {
+        use super::*;
+
+        fn at(secs: i64) -> DateTime<Utc> {
+            DateTime::from_timestamp(secs, 0).unwrap()
+        }
+
+        #[test]
+        fn covers_range_newest_first_without_overlap() {
+            let windows = backfill_windows(at(1_000), at(1_249), TimeDelta::seconds(100));
+            assert_eq!(
+                windows,
+                [
+                    (at(1_150), at(1_249)),
+                    (at(1_050), at(1_149)),
+                    (at(1_000), at(1_049)),
+                ]
+            );
+        }
+
+        #[test]
+        fn handles_short_and_empty_ranges() {
+            let day = TimeDelta::days(1);
+            assert_eq!(
+                backfill_windows(at(500), at(500), day),
+                [(at(500), at(500))]
+            );
+            assert!(backfill_windows(at(600), at(500), day).is_empty());
+            // A sub-second window still makes progress
+            assert_eq!(backfill_windows(at(0), at(1), TimeDelta::zero()).len(), 2);
+        }
+    }
+
     mod parse_line_tests {
         use super::*;
 
@@ -0,0 +1,229 @@
+//! Saved progress of a backfill, so an interrupted one can resume.
+//!
+//! A backfill pages backwards from its `until`, so all it needs to pick up where it
+//! stopped is the `until` of the next page. With a state file configured,
+//! [`BackfillProgress`] is written there every few pages (through a temporary file
+//! renamed into place, so a crash never leaves it half-written) and read back on
+//! the next run of the same backfill. The file is removed once the backfill
+//! completes.
+//!
+//! A run resumes the saved window even when its own `since` or `until` differs, as
+//! it does each time for a relative `--since 30d`. Progress saved for other relays
+//! or kinds is an error rather than silently skipping part of this backfill.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Errors reading or writing a state file.
+#[derive(Debug, Error)]
+pub enum ProgressError {
+    #[error("backfill state file {} I/O failed: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("backfill state file {} is unreadable: {source}", path.display())]
+    Decode {
+        path: PathBuf,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    #[error("failed to encode backfill progress: {0}")]
+    Encode(#[from] serde_json::Error),
+
+    #[error(
+        "backfill state file {} is for a different backfill (relays or kinds); remove it to start over",
+        path.display()
+    )]
+    Mismatch { path: PathBuf },
+}
+
+/// What a backfill covers.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackfillScope {
+    pub relay_urls: Vec<String>,
+    /// Unix seconds.
+    pub since: Option<u64>,
+    /// Unix seconds, where paging started.
+    pub until: Option<u64>,
+    pub kinds: Vec<u16>,
+}
+
+/// How far a backfill has got.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackfillProgress {
+    pub scope: BackfillScope,
+    /// `until` of the next page to fetch (Unix seconds); `None` before the first.
+    pub cursor: Option<u64>,
+    /// Events inserted so far.
+    pub total_events: u64,
+    /// Pages fetched so far.
+    pub pages: u64,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl BackfillProgress {
+    /// Progress of a backfill of `scope` that hasn't started.
+    pub fn new(scope: BackfillScope) -> Self {
+        Self {
+            cursor: scope.until,
+            scope,
+            total_events: 0,
+            pages: 0,
+            updated_at: Utc::now(),
+        }
+    }
+}
+
+/// A file backfill progress is saved to.
+#[derive(Debug, Clone)]
+pub struct ProgressFile {
+    path: PathBuf,
+}
+
+impl ProgressFile {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Progress saved for a backfill of `scope`, or a fresh start when nothing is
+    /// saved. Saved progress keeps its own `since` and `until`.
+    pub async fn resume(&self, scope: BackfillScope) -> Result<BackfillProgress, ProgressError> {
+        let contents = match tokio::fs::read_to_string(&self.path).await {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                return Ok(BackfillProgress::new(scope));
+            }
+            Err(source) => return Err(self.io_error(source)),
+        };
+        let progress: BackfillProgress =
+            serde_json::from_str(&contents).map_err(|source| ProgressError::Decode {
+                path: self.path.clone(),
+                source,
+            })?;
+        if progress.scope.relay_urls != scope.relay_urls || progress.scope.kinds != scope.kinds {
+            return Err(ProgressError::Mismatch {
+                path: self.path.clone(),
+            });
+        }
+        if (progress.scope.since, progress.scope.until) != (scope.since, scope.until) {
+            tracing::warn!(
+                path = %self.path.display(),
+                saved_since = ?progress.scope.since,
+                saved_until = ?progress.scope.until,
+                since = ?scope.since,
+                until = ?scope.until,
+                "Resuming the saved backfill window, not the requested one"
+            );
+        }
+        Ok(progress)
+    }
+
+    /// Save `progress`, replacing what was saved before.
+    pub async fn save(&self, progress: &BackfillProgress) -> Result<(), ProgressError> {
+        let contents = serde_json::to_string_pretty(progress)?;
+        let tmp = self.path.with_extension("tmp");
+        tokio::fs::write(&tmp, contents)
+            .await
+            .map_err(|source| self.io_error(source))?;
+        tokio::fs::rename(&tmp, &self.path)
+            .await
+            .map_err(|source| self.io_error(source))
+    }
+
+    /// Remove the saved progress, once the backfill is complete.
+    pub async fn remove(&self) -> Result<(), ProgressError> {
+        match tokio::fs::remove_file(&self.path).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(source) => Err(self.io_error(source)),
+        }
+    }
+
+    fn io_error(&self, source: io::Error) -> ProgressError {
+        ProgressError::Io {
+            path: self.path.clone(),
+            source,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scope(kinds: Vec<u16>) -> BackfillScope {
+        BackfillScope {
+            relay_urls: vec!["wss://relay.example.com".to_string()],
+            since: Some(1_700_000_000),
+            until: None,
+            kinds,
+        }
+    }
+
+    fn later_scope() -> BackfillScope {
+        BackfillScope {
+            since: Some(1_700_086_400),
+            ..scope(vec![7])
+        }
+    }
+
+    fn temp_file(name: &str) -> ProgressFile {
+        let path = std::env::temp_dir().join(format!(
+            "funnel-progress-{name}-{}.json",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        ProgressFile::new(path)
+    }
+
+    #[tokio::test]
+    async fn resumes_saved_progress_for_same_scope() {
+        let file = temp_file("resume");
+
+        let fresh = file.resume(scope(vec![7])).await.unwrap();
+        assert_eq!(fresh.cursor, None);
+        assert_eq!(fresh.total_events, 0);
+
+        let mut progress = fresh;
+        progress.cursor = Some(1_700_500_000);
+        progress.total_events = 25_000;
+        progress.pages = 5;
+        file.save(&progress).await.unwrap();
+
+        let resumed = file.resume(scope(vec![7])).await.unwrap();
+        assert_eq!(resumed, progress);
+        // A relative `since` a day later still resumes the saved window
+        let resumed = file.resume(later_scope()).await.unwrap();
+        assert_eq!(resumed, progress);
+
+        file.remove().await.unwrap();
+        assert_eq!(file.resume(scope(vec![7])).await.unwrap().pages, 0);
+        // Removing twice is fine
+        file.remove().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn refuses_progress_of_another_backfill() {
+        let file = temp_file("mismatch");
+        file.save(&BackfillProgress::new(scope(vec![7])))
+            .await
+            .unwrap();
+
+        let err = file.resume(scope(vec![9735])).await.unwrap_err();
+        assert!(matches!(err, ProgressError::Mismatch { .. }), "{err}");
+
+        file.remove().await.unwrap();
+    }
+}
@@ -0,0 +1,549 @@
+//! Event replay from files and pipes.
+//!
+//! Reads newline-delimited events, either raw event JSON (as `funnel export` writes
+//! it) or strfry stream lines, and inserts them in batches. ClickHouse deduplicates
+//! by event ID, so replaying events that are already stored is safe.
+//!
+//! [`replay`] reads a file to the end; [`replay_stream`] follows a stream that stays
+//! open, such as `strfry stream` or `strfry router` piped into `funnel ingest --stdin`.
+//! Given a [`Verifier`], both drop events whose ID or signature doesn't check out
+//! before writing them.
+//!
+//! [`replay_paths`] replays archived exports for disaster recovery or to populate a
+//! staging database: files, directories of `.jsonl` and `.jsonl.gz` files, or glob
+//! patterns, one file after another. Files ending in `.gz` are decompressed as they're
+//! read.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use async_compression::tokio::bufread::GzipDecoder;
+use funnel_clickhouse::{ClickHouseError, EventRow, EventWriter};
+use funnel_observability::ingestion;
+use funnel_proto::ParsedEvent;
+use metrics::{counter, histogram};
+use thiserror::Error;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, BufReader};
+
+use crate::verify::Verifier;
+use crate::{BatchConfig, BatchProcessor, FlushReason, parse_line};
+
+/// Errors from a replay.
+#[derive(Debug, Error)]
+pub enum ReplayError {
+    #[error("failed to read events: {0}")]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    ClickHouse(#[from] ClickHouseError),
+    #[error("failed to read {}: {source}", path.display())]
+    File {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    #[error("invalid pattern {pattern}: {source}")]
+    Pattern {
+        pattern: String,
+        #[source]
+        source: glob::PatternError,
+    },
+    #[error("{0} matches no files")]
+    NoMatch(String),
+}
+
+/// Outcome of a replay.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ReplayStats {
+    /// Events written.
+    pub inserted: u64,
+    /// Non-empty lines that weren't a valid event.
+    pub skipped: u64,
+    /// Events dropped because they failed verification.
+    pub rejected: u64,
+}
+
+impl std::ops::AddAssign for ReplayStats {
+    fn add_assign(&mut self, other: Self) {
+        self.inserted += other.inserted;
+        self.skipped += other.skipped;
+        self.rejected += other.rejected;
+    }
+}
+
+/// Replay every file `inputs` name, as [`replay`] does, one after another.
+///
+/// Each input is a file, a directory (its `.jsonl` and `.jsonl.gz` files, by name),
+/// or a glob pattern such as `archive/2024-*.jsonl.gz` (its matches, by name). Files
+/// whose name ends in `.gz` are decompressed.
+pub async fn replay_paths<W>(
+    writer: &W,
+    inputs: &[PathBuf],
+    batch_size: usize,
+    relay_source: &str,
+    verifier: Option<Verifier>,
+) -> Result<ReplayStats, ReplayError>
+where
+    W: EventWriter,
+{
+    let mut total = ReplayStats::default();
+    for path in expand_inputs(inputs)? {
+        let reader = open(&path).await?;
+        let stats = replay(writer, reader, batch_size, relay_source, verifier)
+            .await
+            .map_err(|e| match e {
+                ReplayError::Io(source) => ReplayError::File {
+                    path: path.clone(),
+                    source,
+                },
+                e => e,
+            })?;
+        tracing::info!(
+            path = %path.display(),
+            inserted = stats.inserted,
+            skipped = stats.skipped,
+            rejected = stats.rejected,
+            "Replayed file"
+        );
+        total += stats;
+    }
+    Ok(total)
+}
+
+/// The files `inputs` name; see [`replay_paths`].
+pub fn expand_inputs(inputs: &[PathBuf]) -> Result<Vec<PathBuf>, ReplayError> {
+    let mut paths = Vec::new();
+    for input in inputs {
+        if input.is_dir() {
+            paths.extend(archive_files(input)?);
+        } else if input.exists() || !is_pattern(input) {
+            paths.push(input.clone());
+        } else {
+            let pattern = input.to_string_lossy().into_owned();
+            let matches = glob::glob(&pattern)
+                .map_err(|source| ReplayError::Pattern {
+                    pattern: pattern.clone(),
+                    source,
+                })?
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| ReplayError::File {
+                    path: e.path().to_path_buf(),
+                    source: e.into_error(),
+                })?;
+            if matches.is_empty() {
+                return Err(ReplayError::NoMatch(pattern));
+            }
+            paths.extend(matches.into_iter().filter(|path| !path.is_dir()));
+        }
+    }
+    Ok(paths)
+}
+
+/// The `.jsonl` and `.jsonl.gz` files directly in `dir`, by name.
+fn archive_files(dir: &Path) -> Result<Vec<PathBuf>, ReplayError> {
+    let file_error = |source| ReplayError::File {
+        path: dir.to_path_buf(),
+        source,
+    };
+    let mut paths = Vec::new();
+    for entry in std::fs::read_dir(dir).map_err(file_error)? {
+        let path = entry.map_err(file_error)?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if path.is_file() && (name.ends_with(".jsonl") || name.ends_with(".jsonl.gz")) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+fn is_pattern(path: &Path) -> bool {
+    path.to_string_lossy().contains(['*', '?', '['])
+}
+
+/// A reader of the lines in `path`, decompressed if its name ends in `.gz`.
+pub async fn open(path: &Path) -> Result<Box<dyn AsyncBufRead + Unpin + Send>, ReplayError> {
+    let file = tokio::fs::File::open(path)
+        .await
+        .map_err(|source| ReplayError::File {
+            path: path.to_path_buf(),
+            source,
+        })?;
+    let file = BufReader::new(file);
+    if path.extension().is_some_and(|ext| ext == "gz") {
+        // Archives joined with `cat` are several gzip members in a row
+        let mut decoder = GzipDecoder::new(file);
+        decoder.multiple_members(true);
+        Ok(Box::new(BufReader::new(decoder)))
+    } else {
+        Ok(Box::new(file))
+    }
+}
+
+/// Insert every event read from `reader` in batches of `batch_size`, recording
+/// `relay_source` as their origin, and verifying each batch first with `verifier`
+/// when given.
+pub async fn replay<W, R>(
+    writer: &W,
+    reader: R,
+    batch_size: usize,
+    relay_source: &str,
+    verifier: Option<Verifier>,
+) -> Result<ReplayStats, ReplayError>
+where
+    W: EventWriter,
+    R: AsyncBufRead + Unpin,
+{
+    let batch_size = batch_size.max(1);
+    let mut stats = ReplayStats::default();
+    let mut batch = Vec::with_capacity(batch_size);
+    let mut lines = reader.lines();
+    let mut line_number = 0u64;
+
+    while let Some(line) = lines.next_line().await? {
+        line_number += 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        match parse_line(line) {
+            Some(event) => batch.push(event),
+            None => {
+                stats.skipped += 1;
+                tracing::warn!(line = line_number, "Skipping line that isn't an event");
+            }
+        }
+
+        if batch.len() >= batch_size {
+            let events = std::mem::replace(&mut batch, Vec::with_capacity(batch_size));
+            insert_verified(writer, events, relay_source, verifier, &mut stats).await?;
+        }
+    }
+
+    insert_verified(writer, batch, relay_source, verifier, &mut stats).await?;
+    Ok(stats)
+}
+
+/// Insert `events`, less those that fail verification with `verifier`.
+async fn insert_verified<W>(
+    writer: &W,
+    events: Vec<ParsedEvent>,
+    relay_source: &str,
+    verifier: Option<Verifier>,
+    stats: &mut ReplayStats,
+) -> Result<(), ClickHouseError>
+where
+    W: EventWriter,
+{
+    let events = match verifier {
+        Some(verifier) => {
+            let (verified, rejected) = verifier.verify(events).await;
+            stats.rejected += rejected;
+            verified
+        }
+        None => events,
+    };
+    if events.is_empty() {
+        return Ok(());
+    }
+
+    let rows: Vec<_> = events
+        .iter()
+        .map(|event| EventRow::from_parsed(event, relay_source))
+        .collect();
+    writer.insert_events(&rows).await?;
+    stats.inserted += rows.len() as u64;
+    Ok(())
+}
+
+/// Insert events from `reader` until it closes, as [`replay`] does, except that a
+/// partial batch is also written once it has waited `config.flush_interval`, so
+/// events from a quiet stream don't sit unwritten.
+///
+/// Records the live ingestion metrics (events received and written, batch size,
+/// write latency) as it goes.
+pub async fn replay_stream<W, R>(
+    writer: &W,
+    reader: R,
+    config: BatchConfig,
+    relay_source: &str,
+    verifier: Option<Verifier>,
+) -> Result<ReplayStats, ReplayError>
+where
+    W: EventWriter,
+    R: AsyncBufRead + Unpin,
+{
+    let mut stats = ReplayStats::default();
+    let mut processor = BatchProcessor::new(config);
+    let mut lines = reader.lines();
+    let mut line_number = 0u64;
+
+    loop {
+        // Only wait for the flush interval while there is something to flush
+        let line = if processor.is_empty() {
+            lines.next_line().await?
+        } else {
+            let wait = processor
+                .flush_interval()
+                .saturating_sub(processor.time_since_flush());
+            match tokio::time::timeout(wait, lines.next_line()).await {
+                Ok(line) => line?,
+                Err(_) => {
+                    write_batch(writer, &mut processor, relay_source, verifier, &mut stats).await?;
+                    continue;
+                }
+            }
+        };
+        let Some(line) = line else {
+            break;
+        };
+
+        line_number += 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        match parse_line(line) {
+            Some(event) => {
+                counter!(ingestion::EVENTS_RECEIVED, "kind" => event.kind.to_string()).increment(1);
+                processor.push(event);
+            }
+            None => {
+                stats.skipped += 1;
+                tracing::warn!(line = line_number, "Skipping line that isn't an event");
+            }
+        }
+
+        if processor.should_flush() != FlushReason::None {
+            write_batch(writer, &mut processor, relay_source, verifier, &mut stats).await?;
+        }
+    }
+
+    write_batch(writer, &mut processor, relay_source, verifier, &mut stats).await?;
+    Ok(stats)
+}
+
+/// Write and clear the processor's batch, if it has one, less events that fail
+/// verification with `verifier`.
+pub(crate) async fn write_batch<W>(
+    writer: &W,
+    processor: &mut BatchProcessor,
+    relay_source: &str,
+    verifier: Option<Verifier>,
+    stats: &mut ReplayStats,
+) -> Result<(), ClickHouseError>
+where
+    W: EventWriter,
+{
+    let Some(mut batch) = processor.take_batch() else {
+        return Ok(());
+    };
+    if let Some(verifier) = verifier {
+        let (verified, rejected) = verifier.verify(batch).await;
+        stats.rejected += rejected;
+        batch = verified;
+    }
+    if batch.is_empty() {
+        return Ok(());
+    }
+    let rows: Vec<_> = batch
+        .iter()
+        .map(|event| EventRow::from_parsed(event, relay_source))
+        .collect();
+
+    histogram!(ingestion::BATCH_SIZE).record(rows.len() as f64);
+    let start = Instant::now();
+    writer.insert_events(&rows).await?;
+    histogram!(ingestion::WRITE_LATENCY).record(start.elapsed().as_secs_f64());
+    counter!(ingestion::EVENTS_WRITTEN).increment(rows.len() as u64);
+
+    stats.inserted += rows.len() as u64;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    use async_compression::tokio::write::GzipEncoder;
+    use nostr_sdk::JsonUtil;
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+    use crate::generate::{GenConfig, Generator};
+
+    const EVENT_JSON: &str = r#"{"id":"4376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65","pubkey":"6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93","created_at":1673347337,"kind":1,"tags":[],"content":"Test","sig":"908a15e46fb4d8675bab026fc230a0e3542bfade63da02d542fb78b2a8513fcd0092619a2c8c1221e581946e0191f2af505dfdf8657a414dbca329186f009262"}"#;
+
+    #[derive(Default)]
+    struct MockWriter {
+        batches: Mutex<Vec<Vec<EventRow>>>,
+    }
+
+    impl EventWriter for MockWriter {
+        async fn insert_events(&self, events: &[EventRow]) -> Result<(), ClickHouseError> {
+            self.batches.lock().unwrap().push(events.to_vec());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn replay_batches_events_and_skips_bad_lines() {
+        let input = format!("{EVENT_JSON}\n\nnot json\n{EVENT_JSON}\n{EVENT_JSON}\n");
+        let writer = MockWriter::default();
+
+        let stats = replay(&writer, input.as_bytes(), 2, "archive", None)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            stats,
+            ReplayStats {
+                inserted: 3,
+                skipped: 1,
+                rejected: 0,
+            }
+        );
+        let batches = writer.batches.lock().unwrap();
+        assert_eq!(batches.iter().map(Vec::len).collect::<Vec<_>>(), [2, 1]);
+        assert!(batches[0].iter().all(|row| row.relay_source == "archive"));
+    }
+
+    #[tokio::test]
+    async fn replay_stream_flushes_partial_batches_while_open() {
+        let (mut pipe, reader) = tokio::io::duplex(4096);
+        let writer = MockWriter::default();
+        let config = BatchConfig::new(100, Duration::from_millis(20));
+
+        let feed = async {
+            pipe.write_all(format!("{EVENT_JSON}\nnot json\n").as_bytes())
+                .await
+                .unwrap();
+            // Written after the flush interval, without closing the pipe
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            assert_eq!(writer.batches.lock().unwrap().len(), 1);
+
+            pipe.write_all(format!("{EVENT_JSON}\n").as_bytes())
+                .await
+                .unwrap();
+            drop(pipe);
+        };
+        let (stats, ()) = tokio::join!(
+            replay_stream(&writer, BufReader::new(reader), config, "strfry", None),
+            feed
+        );
+
+        assert_eq!(
+            stats.unwrap(),
+            ReplayStats {
+                inserted: 2,
+                skipped: 1,
+                rejected: 0,
+            }
+        );
+        let batches = writer.batches.lock().unwrap();
+        assert_eq!(batches.iter().map(Vec::len).collect::<Vec<_>>(), [1, 1]);
+        assert_eq!(batches[1][0].relay_source, "strfry");
+    }
+
+    #[tokio::test]
+    async fn replay_drops_events_that_fail_verification() {
+        let mut generator = Generator::new(GenConfig::default());
+        let created_at = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let valid = generator.next_event(created_at).as_json();
+        let forged = generator
+            .next_event(created_at)
+            .as_json()
+            .replace(r#""content":""#, r#""content":"forged "#);
+        let input = format!("{valid}\n{forged}\n");
+        let writer = MockWriter::default();
+        let verifier = Verifier::new(std::num::NonZeroUsize::MIN);
+
+        let stats = replay(&writer, input.as_bytes(), 10, "", Some(verifier))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            stats,
+            ReplayStats {
+                inserted: 1,
+                skipped: 0,
+                rejected: 1,
+            }
+        );
+        assert_eq!(writer.batches.lock().unwrap()[0].len(), 1);
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("funnel-replay-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    async fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzipEncoder::new(Vec::new());
+        encoder.write_all(data).await.unwrap();
+        encoder.shutdown().await.unwrap();
+        encoder.into_inner()
+    }
+
+    #[tokio::test]
+    async fn replays_gzipped_files_directories_and_patterns() {
+        let dir = temp_dir("paths");
+        let lines = format!("{EVENT_JSON}\n{EVENT_JSON}\n");
+        // Two gzip members back to back, as `cat a.gz b.gz` writes them
+        let mut joined = gzip(lines.as_bytes()).await;
+        joined.extend(gzip(format!("{EVENT_JSON}\n").as_bytes()).await);
+        std::fs::write(dir.join("2024-01.jsonl.gz"), joined).unwrap();
+        std::fs::write(dir.join("2024-02.jsonl"), &lines).unwrap();
+        std::fs::write(dir.join("notes.txt"), "not events").unwrap();
+        let writer = MockWriter::default();
+
+        let stats = replay_paths(&writer, std::slice::from_ref(&dir), 10, "archive", None)
+            .await
+            .unwrap();
+        assert_eq!(stats.inserted, 5);
+        assert_eq!(
+            writer
+                .batches
+                .lock()
+                .unwrap()
+                .iter()
+                .map(Vec::len)
+                .collect::<Vec<_>>(),
+            [3, 2]
+        );
+
+        let pattern = dir.join("2024-*.jsonl*");
+        assert_eq!(
+            expand_inputs(&[pattern]).unwrap(),
+            [dir.join("2024-01.jsonl.gz"), dir.join("2024-02.jsonl")]
+        );
+        let missing = dir.join("2023-*.jsonl.gz");
+        assert!(matches!(
+            expand_inputs(&[missing]),
+            Err(ReplayError::NoMatch(_))
+        ));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn replay_reads_exported_events() {
+        let row = EventRow::from_parsed(&parse_line(EVENT_JSON).unwrap(), "");
+        let writer = MockWriter::default();
+
+        replay(&writer, row.to_json().as_bytes(), 10, "", None)
+            .await
+            .unwrap();
+
+        let batches = writer.batches.lock().unwrap();
+        let replayed = &batches[0][0];
+        assert_eq!(replayed.id, row.id);
+        assert_eq!(replayed.created_at, row.created_at);
+        assert_eq!(replayed.sig, row.sig);
+        assert_eq!(replayed.content, "Test");
+    }
+}
@@ -0,0 +1,2054 @@
+//! Relay ingestion.
+//!
+//! Connects to Nostr relays and streams events to ClickHouse, in one of two modes:
+//!
+//! - **Live** ([`run_live`]): subscribes to each relay from the last event stored
+//!   from it, recording the relay that delivered each event in `relay_source`, and
+//!   streams new events. When the stream ends or fails, it reconnects after a
+//!   jittered exponential backoff and resumes from what was last written. Relay
+//!   notifications reach the writer through a bounded queue (`INGEST_QUEUE_CAPACITY`),
+//!   so a slow ClickHouse makes receiving wait rather than grow memory. Events are
+//!   batched by a [`BatchProcessor`] and written once `BATCH_SIZE` have arrived or
+//!   every [`LIVE_FLUSH_INTERVAL`] otherwise. With `INGEST_FLUSH_WORKERS` above 1,
+//!   that many batches are inserted at once, and may be committed out of order.
+//!   With `SPILL_DIR` set, batches ClickHouse rejects are queued on disk and written
+//!   once it recovers (see [`crate::spill`]) instead of ending the stream. Events it
+//!   can't store are recorded with the reason in `events_rejected`. Once a batch
+//!   with deletion requests (kind 5) and every batch before it are written, the
+//!   stored events each one may delete (its author's, named by ID or address) get
+//!   tombstones in `event_deletions`, hiding deleted videos; with
+//!   `INGEST_LIGHTWEIGHT_DELETES=true` the events are removed as well. Also polls
+//!   `backfill_requests` and runs any windows queued through the admin API
+//!   (`POST /admin/backfill`). Every minute it records what each relay sent
+//!   (events, unique events, duplicates, parse failures) and its connection uptime
+//!   in `relay_stats`, served by `GET /api/relays`. Serves `/metrics` and `/health`
+//!   on `INGEST_BIND_ADDR` (see [`crate::http`]).
+//! - **Stdin** ([`run_stdin`]): reads `strfry stream` or `strfry router` output piped
+//!   into stdin instead of connecting to relays, for an ingester running next to
+//!   strfry. Partial batches are written every [`STDIN_FLUSH_INTERVAL`]. With
+//!   `VERIFY_SIGNATURES=true`, events with a bad ID or signature are dropped (see
+//!   [`crate::verify`]).
+//! - **NATS** ([`run_nats`]): consumes events published to a NATS JetStream stream,
+//!   acknowledging each message once its event is written, so nothing is lost when
+//!   ClickHouse or the ingester goes down (see [`crate::source`]). Needs the `nats`
+//!   feature.
+//! - **Backfill** ([`run_backfill`]): paginates through all historical events, then
+//!   returns. With a state file, it saves its position as it goes and resumes from
+//!   there after an interruption. With a concurrency above 1, the range is split
+//!   into windows fetched several at a time instead. With `PUSHGATEWAY_URL` set, it
+//!   pushes its metrics (rows inserted, errors, duration) to a Prometheus
+//!   Pushgateway first, since it exits before a scrape.
+//! - **Sync** ([`run_sync`]): reconciles what's stored for a time range with each
+//!   relay using negentropy (NIP-77) and fetches only the events ClickHouse is
+//!   missing, instead of downloading the whole range again. Pushes its metrics like
+//!   a backfill.
+//!
+//! ClickHouse's ReplacingMergeTree handles deduplication by event ID, so overlapping
+//! relays are fine; live mode also drops events it has recently seen before
+//! inserting them (see [`crate::dedup`]). For a multi-tenant deployment, run one
+//! ingester per tenant with `TENANT` set and `RELAY_URL` (or `RELAY_URL_FILE`)
+//! listing that tenant's relays; its events go to the tenant's database.
+
+use std::collections::{BTreeSet, HashMap};
+use std::env;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use nostr_sdk::prelude::*;
+
+use funnel_clickhouse::{
+    BackfillRequest, ClickHouseClient, ClickHouseConfig, ClickHouseError, EventDeletion, EventRow,
+    EventWriter, RejectedEvent, RelayStats,
+};
+use funnel_observability::heartbeat::{self, CheckFailure, Heartbeat};
+use funnel_observability::push::PushGateway;
+use funnel_observability::{PrometheusConfig, batch, ingestion, warn_throttled};
+use funnel_proto::{DeletionMeta, ErrorCode, ParseError, ParsedEvent};
+use metrics::{counter, gauge, histogram};
+use metrics_exporter_prometheus::PrometheusHandle;
+
+use crate::dedup::{self, DedupCache};
+use crate::http;
+use crate::progress::{BackfillProgress, BackfillScope, ProgressFile};
+use crate::replay::replay_stream;
+#[cfg(feature = "nats")]
+use crate::source::{NatsConfig, consume};
+use crate::spill::{SpillQueue, Written};
+use crate::verify::Verifier;
+use crate::{
+    Backoff, BatchConfig, BatchProcessor, FlushReason, LiveStatus, RelayCheckpoints, RelayCounters,
+    RetryConfig, RetryingWriter, backfill_windows,
+};
+
+const DEFAULT_BATCH_SIZE: usize = 1000;
+const DEFAULT_QUEUE_CAPACITY: usize = 10_000;
+const DEFAULT_FLUSH_WORKERS: usize = 1;
+const PAGINATION_LIMIT: usize = 5000;
+const PAGINATE_INTERVAL_MS: u64 = 500;
+
+/// Pages between saves of backfill progress to the state file.
+const PROGRESS_SAVE_PAGES: u64 = 10;
+
+/// Buffer time to account for backdated events
+const CATCHUP_BUFFER_SECS: u64 = 2 * 24 * 60 * 60; // 2 days
+
+/// Buffer when resubscribing after a reconnect, covering events that arrived while
+/// the stream was down but after the last flushed one
+const RESUME_BUFFER_SECS: u64 = 10 * 60;
+
+/// First and longest wait before reconnecting the live stream
+const RECONNECT_BASE_DELAY: Duration = Duration::from_secs(1);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// How long a live stream must run before its end no longer counts toward backoff
+const RECONNECT_RESET_AFTER: Duration = Duration::from_secs(5 * 60);
+
+/// How often live mode retries ClickHouse while batches are spilled to disk
+const SPILL_RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How long live mode waits before retrying deletion requests it failed to apply
+const DELETION_RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How often live mode checks for queued backfill requests
+const BACKFILL_POLL_INTERVAL_SECS: u64 = 60;
+
+/// Longest the live stream may go without flushing before self-checks fail
+const MAX_FLUSH_AGE: Duration = Duration::from_secs(60);
+
+/// Upper bound on the self-check ClickHouse ping
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often live mode records per-relay counters
+const RELAY_STATS_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often live mode samples how long an inserted event takes to become queryable
+const QUERYABLE_PROBE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// How long a queryable probe polls for its event before giving up
+const QUERYABLE_PROBE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Longest a live event waits in a partial batch
+pub const LIVE_FLUSH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Longest an event read from stdin waits in a partial batch
+pub const STDIN_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Settings shared by live mode and backfills.
+#[derive(Debug, Clone)]
+pub struct IngestConfig {
+    /// Relays events are read from.
+    pub relay_urls: Vec<String>,
+    /// Most events per ClickHouse insert.
+    pub batch_size: usize,
+    /// Notifications live mode holds between the relays and ClickHouse before it
+    /// stops receiving.
+    pub queue_capacity: usize,
+    /// Batches live mode inserts at once.
+    pub flush_workers: usize,
+    /// Event IDs live mode remembers to drop repeats; 0 disables it.
+    pub dedup_capacity: usize,
+    /// Whether live mode also removes the events deletion requests delete, rather
+    /// than only recording tombstones.
+    pub lightweight_deletes: bool,
+    /// Directory live mode spills batches to while ClickHouse is down, if any.
+    pub spill_dir: Option<PathBuf>,
+    /// Retries for failed ClickHouse inserts.
+    pub retry: RetryConfig,
+}
+
+impl IngestConfig {
+    /// Read `RELAY_URL` (one relay, or several separated by commas), `BATCH_SIZE`,
+    /// `INGEST_QUEUE_CAPACITY`, `INGEST_FLUSH_WORKERS`, `INGEST_DEDUP_CAPACITY`,
+    /// `INGEST_LIGHTWEIGHT_DELETES`, `SPILL_DIR`, and the `INSERT_RETRY_*` settings.
+    ///
+    /// Without `RELAY_URL`, relays are read from the file named by `RELAY_URL_FILE`:
+    /// one URL per line, skipping blank lines and `#` comments.
+    pub fn from_env() -> anyhow::Result<Self> {
+        let mut relay_urls = env::var("RELAY_URL")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|url| !url.is_empty())
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        if relay_urls.is_empty()
+            && let Ok(path) = env::var("RELAY_URL_FILE")
+        {
+            relay_urls = read_relay_file(Path::new(&path))?;
+        }
+
+        Ok(Self {
+            relay_urls: if relay_urls.is_empty() {
+                vec!["ws://localhost:7777".to_string()]
+            } else {
+                relay_urls
+            },
+            batch_size: batch_size_from_env(),
+            queue_capacity: env::var("INGEST_QUEUE_CAPACITY")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .filter(|capacity| *capacity > 0)
+                .unwrap_or(DEFAULT_QUEUE_CAPACITY),
+            flush_workers: env::var("INGEST_FLUSH_WORKERS")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .filter(|workers| *workers > 0)
+                .unwrap_or(DEFAULT_FLUSH_WORKERS),
+            dedup_capacity: env::var("INGEST_DEDUP_CAPACITY")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(dedup::DEFAULT_CAPACITY),
+            lightweight_deletes: env::var("INGEST_LIGHTWEIGHT_DELETES")
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(false),
+            spill_dir: env::var_os("SPILL_DIR")
+                .filter(|dir| !dir.is_empty())
+                .map(PathBuf::from),
+            retry: RetryConfig::from_env(),
+        })
+    }
+}
+
+fn batch_size_from_env() -> usize {
+    env::var("BATCH_SIZE")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(DEFAULT_BATCH_SIZE)
+}
+
+fn read_relay_file(path: &Path) -> anyhow::Result<Vec<String>> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| anyhow::anyhow!("failed to read {}: {e}", path.display()))?;
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+/// Stream new events into ClickHouse, reconnecting whenever the stream ends.
+pub async fn run_live() -> anyhow::Result<()> {
+    let config = IngestConfig::from_env()?;
+    let ch_config = ClickHouseConfig::from_env()?;
+    log_start(&config, &ch_config, false);
+
+    let metrics = funnel_observability::init_metrics(
+        PrometheusConfig::from_env().with_global_label("service", "funnel-ingestion"),
+    );
+    let clickhouse = ClickHouseClient::connect(&ch_config).await?;
+
+    tracing::info!("Running in LIVE mode - streaming new events");
+    tokio::spawn(poll_backfill_requests(clickhouse.clone(), config.clone()));
+
+    // Periodically verify the stream is healthy and export `service_healthy`. The
+    // client changes on every reconnect, and there is none while waiting to reconnect.
+    let status = Arc::new(LiveStatus::default());
+    let (client_tx, client_rx) = tokio::sync::watch::channel(None::<Client>);
+    let heartbeat = Heartbeat::spawn(heartbeat::DEFAULT_INTERVAL, {
+        let clickhouse = clickhouse.clone();
+        let status = status.clone();
+        move || {
+            let client = client_rx.borrow().clone();
+            let clickhouse = clickhouse.clone();
+            let status = status.clone();
+            async move { self_check(client.as_ref(), &clickhouse, &status).await }
+        }
+    });
+    if let Some(addr) = http::bind_addr_from_env() {
+        http::spawn(&addr, http::router(metrics, heartbeat, status.clone())).await?;
+    }
+
+    let mut state = LiveState {
+        spill: match &config.spill_dir {
+            Some(dir) => Some(SpillQueue::open(dir, SPILL_RETRY_INTERVAL).await?),
+            None => None,
+        },
+        dedup: NonZeroUsize::new(config.dedup_capacity).map(DedupCache::new),
+        deletions: PendingDeletions::default(),
+    };
+
+    let mut backoff = Backoff::new(RECONNECT_BASE_DELAY, RECONNECT_MAX_DELAY);
+    let mut buffer = CATCHUP_BUFFER_SECS;
+    loop {
+        let client = relay_client(&config.relay_urls).await?;
+        client_tx.send_replace(Some(client.clone()));
+
+        let started = Instant::now();
+        let result = live_stream(&clickhouse, &client, &config, buffer, &status, &mut state).await;
+        client_tx.send_replace(None);
+        client.disconnect().await;
+
+        match result {
+            Ok(()) => tracing::warn!("Live stream ended"),
+            Err(e) => {
+                tracing::warn!(error = %e, "Live stream failed");
+                // Events of the batches that weren't written must not be dropped
+                // when they're replayed
+                if let Some(dedup) = state.dedup.as_mut() {
+                    dedup.clear();
+                }
+            }
+        }
+        if started.elapsed() >= RECONNECT_RESET_AFTER {
+            backoff.reset();
+        }
+        let delay = backoff.next_delay();
+        counter!(ingestion::RECONNECTS).increment(1);
+        tracing::info!(
+            attempt = backoff.attempts(),
+            delay_ms = delay.as_millis() as u64,
+            "Reconnecting"
+        );
+        tokio::time::sleep(delay).await;
+        buffer = RESUME_BUFFER_SECS;
+    }
+}
+
+/// Insert strfry JSON lines from stdin into ClickHouse until stdin closes, recording
+/// `relay_source` as their origin.
+pub async fn run_stdin(relay_source: &str) -> anyhow::Result<()> {
+    let batch_size = batch_size_from_env();
+    let ch_config = ClickHouseConfig::from_env()?;
+    tracing::info!(
+        clickhouse_url = %ch_config.safe_url(),
+        database = %ch_config.database,
+        tenant = ch_config.tenant.as_deref(),
+        batch_size,
+        relay_source,
+        "Starting ingestion service"
+    );
+
+    let _metrics = funnel_observability::init_metrics(
+        PrometheusConfig::from_env().with_global_label("service", "funnel-ingestion"),
+    );
+    let clickhouse = ClickHouseClient::connect(&ch_config).await?;
+
+    tracing::info!("Running in STDIN mode - reading strfry output from stdin");
+    let stdin = tokio::io::BufReader::new(tokio::io::stdin());
+    let config = BatchConfig::new(batch_size.max(1), STDIN_FLUSH_INTERVAL);
+    let writer = RetryingWriter::new(&clickhouse, RetryConfig::from_env());
+    let verifier = Verifier::from_env();
+    let stats = replay_stream(&writer, stdin, config, relay_source, verifier).await?;
+
+    tracing::info!(
+        inserted = stats.inserted,
+        skipped = stats.skipped,
+        rejected = stats.rejected,
+        "Stdin closed"
+    );
+    Ok(())
+}
+
+/// Insert events from the NATS JetStream stream configured by `NATS_*` until the
+/// stream closes, recording `relay_source` as their origin.
+#[cfg(feature = "nats")]
+pub async fn run_nats(relay_source: &str) -> anyhow::Result<()> {
+    let batch_size = batch_size_from_env();
+    let ch_config = ClickHouseConfig::from_env()?;
+    let nats_config = NatsConfig::from_env()?;
+    tracing::info!(
+        clickhouse_url = %ch_config.safe_url(),
+        database = %ch_config.database,
+        tenant = ch_config.tenant.as_deref(),
+        batch_size,
+        relay_source,
+        "Starting ingestion service"
+    );
+
+    let _metrics = funnel_observability::init_metrics(
+        PrometheusConfig::from_env().with_global_label("service", "funnel-ingestion"),
+    );
+    let clickhouse = ClickHouseClient::connect(&ch_config).await?;
+
+    tracing::info!(nats_url = %nats_config.url, "Running in NATS mode");
+    let mut source = crate::source::NatsSource::connect(&nats_config).await?;
+    let config = BatchConfig::new(batch_size.max(1), STDIN_FLUSH_INTERVAL);
+    let writer = RetryingWriter::new(&clickhouse, RetryConfig::from_env());
+    let verifier = Verifier::from_env();
+    let stats = consume(&mut source, &writer, config, relay_source, verifier).await?;
+
+    tracing::info!(
+        inserted = stats.inserted,
+        skipped = stats.skipped,
+        rejected = stats.rejected,
+        "NATS stream closed"
+    );
+    Ok(())
+}
+
+/// NATS mode isn't available in this build.
+#[cfg(not(feature = "nats"))]
+pub async fn run_nats(_relay_source: &str) -> anyhow::Result<()> {
+    anyhow::bail!("NATS support isn't compiled in; build with the funnel-ingestion/nats feature")
+}
+
+/// What a backfill run fetches.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BackfillRange {
+    /// Oldest events to fetch; `None` pages until the relay runs dry.
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+    /// Newest events to fetch; `None` starts from the relay's newest event.
+    pub until: Option<chrono::DateTime<chrono::Utc>>,
+    /// Kinds to fetch; empty fetches every kind.
+    pub kinds: Vec<u16>,
+}
+
+/// How a backfill run pages through its range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackfillOptions {
+    /// Where to save progress so an interrupted run resumes; sequential runs only.
+    pub state_file: Option<PathBuf>,
+    /// Windows fetched at once; 1 pages through the whole range in one pass.
+    pub concurrency: usize,
+    /// Length of each window when `concurrency` is above 1.
+    pub window: chrono::TimeDelta,
+}
+
+impl Default for BackfillOptions {
+    fn default() -> Self {
+        Self {
+            state_file: None,
+            concurrency: 1,
+            window: chrono::TimeDelta::days(1),
+        }
+    }
+}
+
+/// Page through the relay's history in `range` (all of it by default) into
+/// ClickHouse. With a state file, progress is saved there so an interrupted run
+/// resumes where it stopped (see [`crate::progress`]). With a `concurrency` above 1,
+/// the range is split into windows fetched that many at a time instead, which needs
+/// a `since`.
+pub async fn run_backfill(range: BackfillRange, options: BackfillOptions) -> anyhow::Result<()> {
+    if let (Some(since), Some(until)) = (range.since, range.until)
+        && since > until
+    {
+        anyhow::bail!("backfill since ({since}) is after until ({until})");
+    }
+    if options.concurrency > 1 && range.since.is_none() {
+        anyhow::bail!("a concurrent backfill needs a since to split into windows");
+    }
+    let config = IngestConfig::from_env()?;
+    let ch_config = ClickHouseConfig::from_env()?;
+    log_start(&config, &ch_config, true);
+
+    let metrics = funnel_observability::init_metrics(
+        PrometheusConfig::from_env().with_global_label("service", "funnel-ingestion"),
+    );
+    let clickhouse = ClickHouseClient::connect(&ch_config).await?;
+
+    tracing::info!(
+        since = ?range.since,
+        until = ?range.until,
+        kinds = ?range.kinds,
+        "Running in BACKFILL mode - paginating through historical events"
+    );
+    let push_gateway = PushGateway::from_env("funnel-backfill").transpose()?;
+    let start = Instant::now();
+    let result = if options.concurrency > 1 {
+        if let Some(path) = &options.state_file {
+            tracing::warn!(
+                path = %path.display(),
+                "Concurrent backfills don't save progress, ignoring the state file"
+            );
+        }
+        backfill_windowed(
+            &clickhouse,
+            &config,
+            &range,
+            options.concurrency,
+            options.window,
+        )
+        .await
+    } else {
+        let progress_file = options.state_file.map(ProgressFile::new);
+        backfill(&clickhouse, &config, &range, progress_file.as_ref()).await
+    };
+
+    finish_job(&result, start, push_gateway, &metrics).await;
+    result
+}
+
+/// Reconcile the events stored from `range` with each relay using negentropy
+/// (NIP-77), one `window` at a time, and fetch only the events ClickHouse is
+/// missing. Needs a `since`, and relays that support negentropy, such as strfry.
+pub async fn run_sync(range: BackfillRange, window: chrono::TimeDelta) -> anyhow::Result<()> {
+    let Some(since) = range.since else {
+        anyhow::bail!("sync needs a since to split into windows");
+    };
+    let until = range.until.unwrap_or_else(chrono::Utc::now);
+    if since > until {
+        anyhow::bail!("sync since ({since}) is after until ({until})");
+    }
+    let config = IngestConfig::from_env()?;
+    let ch_config = ClickHouseConfig::from_env()?;
+    log_start(&config, &ch_config, true);
+
+    let metrics = funnel_observability::init_metrics(
+        PrometheusConfig::from_env().with_global_label("service", "funnel-ingestion"),
+    );
+    let clickhouse = ClickHouseClient::connect(&ch_config).await?;
+
+    tracing::info!(
+        since = %since,
+        until = %until,
+        kinds = ?range.kinds,
+        "Running in SYNC mode - fetching events missing from ClickHouse"
+    );
+    let push_gateway = PushGateway::from_env("funnel-sync").transpose()?;
+    let start = Instant::now();
+    let result = sync(&clickhouse, &config, since, until, &range.kinds, window).await;
+
+    finish_job(&result, start, push_gateway, &metrics).await;
+    result
+}
+
+/// Record how a one-shot job went and, with `push_gateway`, push its metrics, since
+/// it exits before a scrape.
+async fn finish_job(
+    result: &anyhow::Result<()>,
+    start: Instant,
+    push_gateway: Option<PushGateway>,
+    metrics: &PrometheusHandle,
+) {
+    gauge!(batch::DURATION).set(start.elapsed().as_secs_f64());
+    match result {
+        Ok(()) => gauge!(batch::LAST_SUCCESS).set(chrono::Utc::now().timestamp() as f64),
+        Err(_) => counter!(batch::ERRORS).increment(1),
+    }
+    if let Some(gateway) = push_gateway {
+        match gateway.push(metrics).await {
+            Ok(()) => tracing::info!(url = %gateway.url(), "Pushed job metrics"),
+            Err(e) => {
+                tracing::warn!(url = %gateway.url(), error = %e, "Failed to push metrics")
+            }
+        }
+    }
+}
+
+fn log_start(config: &IngestConfig, ch_config: &ClickHouseConfig, backfill_mode: bool) {
+    tracing::info!(
+        relay_urls = ?config.relay_urls,
+        clickhouse_url = %ch_config.safe_url(),
+        database = %ch_config.database,
+        tenant = ch_config.tenant.as_deref(),
+        batch_size = config.batch_size,
+        backfill_mode = backfill_mode,
+        "Starting ingestion service"
+    );
+}
+
+/// Backfill mode: Paginate backwards through historical events
+///
+/// With no `since`, pages until the relay runs dry. With `since`, stops at the
+/// start of the window. `until` sets where paging starts; empty `kinds` fetches all.
+/// With `progress_file`, resumes from the progress saved there and saves it every
+/// [`PROGRESS_SAVE_PAGES`] pages.
+async fn backfill(
+    clickhouse: &ClickHouseClient,
+    config: &IngestConfig,
+    range: &BackfillRange,
+    progress_file: Option<&ProgressFile>,
+) -> anyhow::Result<()> {
+    let unix_secs = |at: chrono::DateTime<chrono::Utc>| at.timestamp().max(0) as u64;
+    let scope = BackfillScope {
+        relay_urls: config.relay_urls.clone(),
+        since: range.since.map(unix_secs),
+        until: range.until.map(unix_secs),
+        kinds: range.kinds.clone(),
+    };
+    let mut progress = match progress_file {
+        Some(file) => file.resume(scope).await?,
+        None => BackfillProgress::new(scope),
+    };
+    if progress.pages > 0 {
+        tracing::info!(
+            cursor = ?progress.cursor.map(|secs| Timestamp::from(secs).to_human_datetime()),
+            total_events = progress.total_events,
+            pages = progress.pages,
+            "Resuming backfill from saved progress"
+        );
+    }
+
+    let client = relay_client(&config.relay_urls).await?;
+    let writer = RetryingWriter::new(clickhouse, config.retry);
+
+    tracing::info!("Connecting to relays...");
+    client.connect().await;
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    tracing::info!("Connected to relays");
+
+    let since = progress.scope.since.map(Timestamp::from);
+    let mut total_events = progress.total_events;
+    let mut until = progress.cursor.map(Timestamp::from);
+    let mut consecutive_empty = 0;
+    let paginate_interval = Duration::from_millis(PAGINATE_INTERVAL_MS);
+
+    let mut base_filter = Filter::new().limit(PAGINATION_LIMIT);
+    if let Some(ts) = since {
+        base_filter = base_filter.since(ts);
+    }
+    if !progress.scope.kinds.is_empty() {
+        base_filter = base_filter.kinds(progress.scope.kinds.iter().map(|k| Kind::from(*k)));
+    }
+
+    loop {
+        if let Some(file) = progress_file
+            && progress.pages > 0
+            && progress.pages.is_multiple_of(PROGRESS_SAVE_PAGES)
+        {
+            progress.cursor = until.map(|ts| ts.as_secs());
+            progress.total_events = total_events;
+            progress.updated_at = chrono::Utc::now();
+            file.save(&progress).await?;
+        }
+
+        let filter = match until {
+            Some(ts) => base_filter.clone().until(ts),
+            None => base_filter.clone(),
+        };
+
+        tracing::info!(
+            until = ?until.map(|t| t.to_human_datetime()),
+            limit = PAGINATION_LIMIT,
+            total_so_far = total_events,
+            "Fetching batch"
+        );
+
+        let events = match client.fetch_events(filter, Duration::from_secs(60)).await {
+            Ok(events) => events,
+            Err(e) => {
+                counter!(batch::ERRORS).increment(1);
+                tracing::warn!(
+                    code = %ErrorCode::IngestRelayFetch,
+                    error = %e,
+                    "Fetch failed, retrying..."
+                );
+                tokio::time::sleep(Duration::from_secs(2)).await;
+                continue;
+            }
+        };
+        progress.pages += 1;
+
+        let count = events.len();
+
+        if count == 0 {
+            // A bounded window is exhausted as soon as a page comes back empty
+            if since.is_some() {
+                break;
+            }
+            consecutive_empty += 1;
+            if consecutive_empty >= 3 {
+                tracing::info!("No more events after 3 empty batches");
+                break;
+            }
+            tokio::time::sleep(paginate_interval).await;
+            if let Some(ts) = until {
+                until = Some(Timestamp::from(ts.as_secs().saturating_sub(86400 * 7)));
+            }
+            continue;
+        }
+
+        consecutive_empty = 0;
+
+        let oldest_ts = events.iter().map(|e| e.created_at).min().unwrap();
+
+        tracing::info!(
+            count = count,
+            oldest = ?oldest_ts.to_human_datetime(),
+            "Received batch"
+        );
+
+        let inserted = insert_page(&writer, events, config.batch_size, "").await?;
+        total_events += inserted;
+
+        tracing::info!(
+            batch_inserted = inserted,
+            total_events = total_events,
+            "Inserted"
+        );
+
+        if since.is_some_and(|ts| oldest_ts <= ts) {
+            break;
+        }
+
+        until = Some(Timestamp::from(oldest_ts.as_secs().saturating_sub(1)));
+        tokio::time::sleep(paginate_interval).await;
+    }
+
+    tracing::info!(total_events = total_events, "Backfill complete");
+    client.disconnect().await;
+    if let Some(file) = progress_file {
+        file.remove().await?;
+    }
+    Ok(())
+}
+
+/// Concurrent backfill: split `range` into windows of `window` and page through up
+/// to `concurrency` of them at once, over one relay connection.
+///
+/// Pages from every window go through a channel to a single writer, so ClickHouse
+/// sees the same batches as a sequential backfill.
+async fn backfill_windowed(
+    clickhouse: &ClickHouseClient,
+    config: &IngestConfig,
+    range: &BackfillRange,
+    concurrency: usize,
+    window: chrono::TimeDelta,
+) -> anyhow::Result<()> {
+    let Some(since) = range.since else {
+        anyhow::bail!("a concurrent backfill needs a since to split into windows");
+    };
+    let until = range.until.unwrap_or_else(chrono::Utc::now);
+    let windows = backfill_windows(since, until, window);
+    tracing::info!(
+        windows = windows.len(),
+        concurrency = concurrency,
+        "Splitting backfill into windows"
+    );
+
+    let client = relay_client(&config.relay_urls).await?;
+    let writer = RetryingWriter::new(clickhouse, config.retry);
+
+    tracing::info!("Connecting to relays...");
+    client.connect().await;
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    tracing::info!("Connected to relays");
+
+    let mut base_filter = Filter::new().limit(PAGINATION_LIMIT);
+    if !range.kinds.is_empty() {
+        base_filter = base_filter.kinds(range.kinds.iter().map(|k| Kind::from(*k)));
+    }
+
+    let permits = Arc::new(tokio::sync::Semaphore::new(concurrency));
+    let (pages_tx, mut pages_rx) = tokio::sync::mpsc::channel::<Vec<Event>>(concurrency);
+    // Dropping the set aborts the fetchers if the writer fails
+    let mut fetchers = tokio::task::JoinSet::new();
+    for (start, end) in windows {
+        let client = client.clone();
+        let filter = base_filter.clone();
+        let permits = permits.clone();
+        let pages = pages_tx.clone();
+        fetchers.spawn(async move {
+            let _permit = permits
+                .acquire_owned()
+                .await
+                .expect("window semaphore is never closed");
+            fetch_window(&client, filter, start, end, &pages).await;
+        });
+    }
+    drop(pages_tx);
+
+    // The channel closes once every window is done
+    let mut total_events = 0;
+    while let Some(events) = pages_rx.recv().await {
+        total_events += insert_page(&writer, events, config.batch_size, "").await?;
+    }
+    while let Some(result) = fetchers.join_next().await {
+        result?;
+    }
+
+    tracing::info!(total_events = total_events, "Backfill complete");
+    client.disconnect().await;
+    Ok(())
+}
+
+/// Page backwards through `[start, end]`, sending each page to `pages`, until a page
+/// comes back empty or reaches `start`.
+async fn fetch_window(
+    client: &Client,
+    filter: Filter,
+    start: chrono::DateTime<chrono::Utc>,
+    end: chrono::DateTime<chrono::Utc>,
+    pages: &tokio::sync::mpsc::Sender<Vec<Event>>,
+) {
+    let since = Timestamp::from(start.timestamp().max(0) as u64);
+    let mut until = Timestamp::from(end.timestamp().max(0) as u64);
+    let filter = filter.since(since);
+    let paginate_interval = Duration::from_millis(PAGINATE_INTERVAL_MS);
+    let mut count = 0;
+
+    loop {
+        let events = match client
+            .fetch_events(filter.clone().until(until), Duration::from_secs(60))
+            .await
+        {
+            Ok(events) => events,
+            Err(e) => {
+                counter!(batch::ERRORS).increment(1);
+                tracing::warn!(
+                    code = %ErrorCode::IngestRelayFetch,
+                    window_start = %start,
+                    error = %e,
+                    "Fetch failed, retrying..."
+                );
+                tokio::time::sleep(Duration::from_secs(2)).await;
+                continue;
+            }
+        };
+        let Some(oldest_ts) = events.iter().map(|e| e.created_at).min() else {
+            break;
+        };
+        count += events.len();
+        tracing::debug!(
+            window_start = %start,
+            count = events.len(),
+            oldest = ?oldest_ts.to_human_datetime(),
+            "Received batch"
+        );
+
+        if pages.send(events.into_iter().collect()).await.is_err() || oldest_ts <= since {
+            break;
+        }
+        until = Timestamp::from(oldest_ts.as_secs().saturating_sub(1));
+        tokio::time::sleep(paginate_interval).await;
+    }
+
+    tracing::info!(
+        window_start = %start,
+        window_end = %end,
+        events = count,
+        "Window complete"
+    );
+}
+
+/// Convert and insert a page of relay events in chunks of `batch_size`, returning
+/// how many were inserted. ClickHouse handles deduplication.
+async fn insert_page(
+    writer: &impl EventWriter,
+    events: impl IntoIterator<Item = Event>,
+    batch_size: usize,
+    relay_source: &str,
+) -> anyhow::Result<u64> {
+    let batch: Vec<ParsedEvent> = events
+        .into_iter()
+        .filter_map(|e| convert_event(&e).ok())
+        .collect();
+
+    let mut inserted = 0;
+    for chunk in batch.chunks(batch_size) {
+        let rows: Vec<_> = chunk
+            .iter()
+            .map(|e| EventRow::from_parsed(e, relay_source))
+            .collect();
+        writer.insert_events(&rows).await?;
+        counter!(batch::ROWS_INSERTED).increment(rows.len() as u64);
+        inserted += rows.len() as u64;
+    }
+    Ok(inserted)
+}
+
+/// Sync mode: for each window, newest first, compare the IDs stored from it with
+/// each relay's and fetch the events ClickHouse doesn't have.
+///
+/// Reconciliation is a dry run, so nothing is sent to the relays and missing events
+/// go through the same inserts as a backfill, recorded with the relay they came from.
+async fn sync(
+    clickhouse: &ClickHouseClient,
+    config: &IngestConfig,
+    since: chrono::DateTime<chrono::Utc>,
+    until: chrono::DateTime<chrono::Utc>,
+    kinds: &[u16],
+    window: chrono::TimeDelta,
+) -> anyhow::Result<()> {
+    let client = relay_client(&config.relay_urls).await?;
+    let writer = RetryingWriter::new(clickhouse, config.retry);
+
+    tracing::info!("Connecting to relays...");
+    client.connect().await;
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    tracing::info!("Connected to relays");
+
+    let relays = client.relays().await;
+    let mut base_filter = Filter::new();
+    if !kinds.is_empty() {
+        base_filter = base_filter.kinds(kinds.iter().map(|k| Kind::from(*k)));
+    }
+    let opts = SyncOptions::new().dry_run();
+    let unix_secs =
+        |at: chrono::DateTime<chrono::Utc>| Timestamp::from(at.timestamp().max(0) as u64);
+    let mut total_missing = 0;
+    let mut total_events = 0;
+
+    for (start, end) in backfill_windows(since, until, window) {
+        let stored: Vec<(EventId, Timestamp)> = clickhouse
+            .get_event_stamps(start, end, kinds)
+            .await?
+            .into_iter()
+            .filter_map(|stamp| {
+                let id = EventId::from_hex(&stamp.id).ok()?;
+                Some((id, unix_secs(stamp.created_at)))
+            })
+            .collect();
+        let filter = base_filter
+            .clone()
+            .since(unix_secs(start))
+            .until(unix_secs(end));
+
+        for (url, relay) in &relays {
+            let reconciliation = relay
+                .sync_with_items(filter.clone(), stored.clone(), &opts)
+                .await
+                .map_err(|e| anyhow::anyhow!("negentropy sync with {url} failed: {e}"))?;
+            let missing: Vec<EventId> = reconciliation.remote.into_iter().collect();
+            tracing::info!(
+                relay = %url,
+                window_start = %start,
+                window_end = %end,
+                stored = stored.len(),
+                missing = missing.len(),
+                "Reconciled window"
+            );
+
+            for ids in missing.chunks(PAGINATION_LIMIT) {
+                let filter = Filter::new().ids(ids.iter().copied());
+                let events = relay
+                    .fetch_events(filter, Duration::from_secs(60), ReqExitPolicy::ExitOnEOSE)
+                    .await
+                    .map_err(|e| {
+                        anyhow::anyhow!("fetching missing events from {url} failed: {e}")
+                    })?;
+                total_events +=
+                    insert_page(&writer, events, config.batch_size, url.as_str()).await?;
+            }
+            total_missing += missing.len();
+        }
+    }
+
+    tracing::info!(
+        missing = total_missing,
+        total_events = total_events,
+        "Sync complete"
+    );
+    client.disconnect().await;
+    Ok(())
+}
+
+/// Periodically run backfill windows queued through the admin API.
+async fn poll_backfill_requests(clickhouse: ClickHouseClient, config: IngestConfig) {
+    let mut interval = tokio::time::interval(Duration::from_secs(BACKFILL_POLL_INTERVAL_SECS));
+
+    loop {
+        interval.tick().await;
+
+        let requests = match clickhouse.get_pending_backfill_requests().await {
+            Ok(requests) => requests,
+            Err(e) => {
+                tracing::warn!(code = %e.code(), error = %e, "Failed to fetch backfill requests");
+                continue;
+            }
+        };
+
+        for request in requests {
+            if let Err(e) = run_backfill_request(&clickhouse, &config, &request).await {
+                tracing::error!(
+                    code = %ErrorCode::IngestBackfillFailed,
+                    id = %request.id,
+                    error = %e,
+                    "Backfill request failed"
+                );
+                let failed = request.with_status(BackfillRequest::STATUS_FAILED);
+                if let Err(e) = clickhouse.upsert_backfill_request(&failed).await {
+                    tracing::warn!(
+                        code = %e.code(),
+                        id = %request.id,
+                        error = %e,
+                        "Failed to mark backfill request as failed"
+                    );
+                }
+            }
+        }
+    }
+}
+
+async fn run_backfill_request(
+    clickhouse: &ClickHouseClient,
+    config: &IngestConfig,
+    request: &BackfillRequest,
+) -> anyhow::Result<()> {
+    tracing::info!(
+        id = %request.id,
+        since = %request.since,
+        until = %request.until,
+        kinds = ?request.kinds,
+        "Running queued backfill request"
+    );
+
+    clickhouse
+        .upsert_backfill_request(&request.with_status(BackfillRequest::STATUS_RUNNING))
+        .await?;
+
+    let range = BackfillRange {
+        since: Some(request.since),
+        until: Some(request.until),
+        kinds: request.kinds.clone(),
+    };
+    backfill(clickhouse, config, &range, None).await?;
+
+    clickhouse
+        .upsert_backfill_request(&request.with_status(BackfillRequest::STATUS_DONE))
+        .await?;
+    Ok(())
+}
+
+/// An event from the live stream and the relay that delivered it first.
+struct LiveEvent {
+    relay_source: String,
+    event: ParsedEvent,
+}
+
+/// Live mode: Subscribe to each relay from its last stored event, less `buffer_secs`,
+/// and stream new events until the notification channel closes
+///
+/// Notifications pass from the relay pool to the writer through a bounded queue of
+/// `queue_capacity`. While ClickHouse is slow, the queue fills and receiving waits,
+/// so memory stays bounded; if it waits long enough, the pool's own channel lags and
+/// the dropped events are counted (see [`LiveStatus::record_lag`]).
+async fn live_stream(
+    clickhouse: &ClickHouseClient,
+    client: &Client,
+    config: &IngestConfig,
+    buffer_secs: u64,
+    status: &Arc<LiveStatus>,
+    state: &mut LiveState,
+) -> anyhow::Result<()> {
+    // Newest stored event per relay, so each resumes where it left off
+    let checkpoints = RelayCheckpoints::new(
+        clickhouse
+            .get_ingestion_checkpoints()
+            .await?
+            .into_iter()
+            .map(|checkpoint| (checkpoint.relay_source, checkpoint.latest_event_at)),
+    );
+    let buffer = chrono::TimeDelta::seconds(buffer_secs as i64);
+
+    tracing::info!("Connecting to relays...");
+    client.connect().await;
+    tracing::info!("Connected");
+
+    // Listen before subscribing so no stored events are missed
+    let notifications = client.notifications();
+
+    for relay_url in &config.relay_urls {
+        let filter = match checkpoints.since(relay_url, buffer) {
+            Some(since) => {
+                tracing::info!(
+                    %relay_url,
+                    since_with_buffer = %since,
+                    "Subscribing from last known timestamp with buffer"
+                );
+                Filter::new().since(Timestamp::from(since.timestamp().max(0) as u64))
+            }
+            None => {
+                tracing::info!(
+                    %relay_url,
+                    "No existing events, subscribing to new events only (run a backfill for history)"
+                );
+                Filter::new().since(Timestamp::now())
+            }
+        };
+        let output = client
+            .subscribe_to([relay_url.as_str()], filter, None)
+            .await?;
+        tracing::info!(%relay_url, subscription_id = %output.id(), "Subscribed");
+    }
+
+    let (queue_tx, queue_rx) = tokio::sync::mpsc::channel(config.queue_capacity);
+    let receiver = tokio::spawn(receive_notifications(
+        notifications,
+        queue_tx,
+        status.clone(),
+    ));
+    let result = write_stream(clickhouse, client, config, status, state, queue_rx).await;
+    receiver.abort();
+    result
+}
+
+/// Forward relay pool notifications to `queue` until the pool's channel closes or
+/// the writer stops, waiting whenever the queue is full.
+async fn receive_notifications(
+    mut notifications: tokio::sync::broadcast::Receiver<RelayPoolNotification>,
+    queue: tokio::sync::mpsc::Sender<RelayPoolNotification>,
+    status: Arc<LiveStatus>,
+) {
+    loop {
+        match notifications.recv().await {
+            Ok(notification) => {
+                if queue.send(notification).await.is_err() {
+                    return;
+                }
+            }
+            Err(tokio::sync::broadcast::error::RecvError::Lagged(n)) => {
+                warn_throttled!(
+                    code = %ErrorCode::IngestChannelLagged,
+                    skipped = n,
+                    "Channel lagged, some events may be lost"
+                );
+                status.record_lag(n);
+            }
+            Err(tokio::sync::broadcast::error::RecvError::Closed) => {
+                tracing::warn!(code = %ErrorCode::IngestChannelClosed, "Channel closed");
+                return;
+            }
+        }
+    }
+}
+
+/// What live mode keeps across reconnects.
+struct LiveState {
+    spill: Option<SpillQueue>,
+    /// Events recently seen, since reconnects replay the events of the resume buffer
+    dedup: Option<DedupCache>,
+    deletions: PendingDeletions,
+}
+
+/// Take notifications from `queue`, at most a batch at a time, and write their
+/// events to ClickHouse until the queue closes, dropping events the dedup cache has
+/// seen and applying deletion requests once their batches are written.
+async fn write_stream(
+    clickhouse: &ClickHouseClient,
+    client: &Client,
+    config: &IngestConfig,
+    status: &LiveStatus,
+    state: &mut LiveState,
+    mut queue: tokio::sync::mpsc::Receiver<RelayPoolNotification>,
+) -> anyhow::Result<()> {
+    let mut spill = state.spill.as_mut();
+    let mut dedup = state.dedup.as_mut();
+    let deletions = &mut state.deletions;
+    let writer = RetryingWriter::new(clickhouse, config.retry);
+    let batch_size = config.batch_size.max(1);
+    let mut notifications = Vec::with_capacity(batch_size);
+    let mut processor: BatchProcessor<LiveEvent> =
+        BatchProcessor::with_config(BatchConfig::new(batch_size, LIVE_FLUSH_INTERVAL));
+    let mut last_log = Instant::now();
+    let mut events_since_log = 0u64;
+    let mut relay_counters = RelayCounters::default();
+    let mut rejected: Vec<RejectedEvent> = Vec::new();
+    let stream_started = Instant::now();
+    let mut last_relay_stats = Instant::now();
+    let mut last_queryable_probe = Instant::now();
+    let mut flushes = Flushes::new(clickhouse, config.retry, config.flush_workers);
+    deletions.restart();
+
+    tracing::info!(
+        queue_capacity = config.queue_capacity,
+        flush_workers = config.flush_workers,
+        dedup_capacity = config.dedup_capacity,
+        "Streaming events..."
+    );
+
+    loop {
+        // Wait for notifications until the batch is due, or briefly when it's empty
+        // so idle streams still get periodic checks, then take whatever is queued
+        let wait = if processor.is_empty() {
+            LIVE_FLUSH_INTERVAL
+        } else {
+            processor
+                .flush_interval()
+                .saturating_sub(processor.time_since_flush())
+        };
+        let room = batch_size.saturating_sub(processor.len()).max(1);
+        let received = tokio::time::timeout(wait, queue.recv_many(&mut notifications, room)).await;
+        if received == Ok(0) {
+            // The receiver stopped and everything it queued has been taken
+            break;
+        }
+        gauge!(ingestion::QUEUE_DEPTH).set(queue.len() as f64);
+
+        for notification in notifications.drain(..) {
+            if let Some(event) =
+                handle_notification(notification, &mut relay_counters, &mut rejected)
+                && dedup
+                    .as_deref_mut()
+                    .is_none_or(|dedup| dedup.insert(&event.event.id))
+            {
+                processor.push(event);
+                events_since_log += 1;
+            }
+        }
+
+        flushes.reap(spill.as_deref_mut()).await?;
+
+        if processor.should_flush() != FlushReason::None
+            && let Some(batch) = processor.take_batch()
+        {
+            // Now and then, time how long an event takes to show up
+            let probe = (last_queryable_probe.elapsed() >= QUERYABLE_PROBE_INTERVAL)
+                .then(|| batch.last().map(|e| e.event.id.clone()))
+                .flatten();
+            if probe.is_some() {
+                last_queryable_probe = Instant::now();
+            }
+            let requests = deletion_requests(&batch);
+            flush_live(
+                &writer,
+                &mut flushes,
+                batch,
+                probe,
+                spill.as_deref_mut(),
+                status,
+            )
+            .await?;
+            deletions.add(requests, &flushes);
+        } else if processor.is_empty()
+            && let Some(spill) = spill.as_deref_mut().filter(|spill| spill.depth() > 0)
+        {
+            // Nothing new to write, but spilled batches may be drainable
+            spill.write(&writer, &[]).await?;
+        }
+        status.record_flush();
+        record_rejected(clickhouse, &mut rejected).await;
+        deletions
+            .apply(
+                clickhouse,
+                &flushes,
+                spill.as_deref(),
+                config.lightweight_deletes,
+            )
+            .await;
+
+        // Log progress
+        if last_log.elapsed() >= Duration::from_secs(30) {
+            tracing::info!(events_received = events_since_log, "Progress");
+            events_since_log = 0;
+            last_log = Instant::now();
+        }
+
+        if last_relay_stats.elapsed() >= RELAY_STATS_INTERVAL {
+            record_relay_stats(client, clickhouse, &mut relay_counters, stream_started).await;
+            last_relay_stats = Instant::now();
+        }
+    }
+
+    if let Some(batch) = processor.take_batch() {
+        let requests = deletion_requests(&batch);
+        flush_live(
+            &writer,
+            &mut flushes,
+            batch,
+            None,
+            spill.as_deref_mut(),
+            status,
+        )
+        .await?;
+        deletions.add(requests, &flushes);
+    }
+    flushes.finish(spill.as_deref_mut()).await?;
+    deletions
+        .apply(
+            clickhouse,
+            &flushes,
+            spill.as_deref(),
+            config.lightweight_deletes,
+        )
+        .await;
+    Ok(())
+}
+
+/// Write a live `batch`: through `flushes` when there are several workers, or in
+/// place when there's one or spilled batches are waiting to be written first.
+async fn flush_live<W>(
+    writer: &RetryingWriter<'_, W>,
+    flushes: &mut Flushes,
+    batch: Vec<LiveEvent>,
+    probe: Option<String>,
+    mut spill: Option<&mut SpillQueue>,
+    status: &LiveStatus,
+) -> anyhow::Result<()>
+where
+    W: EventWriter,
+{
+    // Lag of the oldest event by created_at, before the write, overall and for
+    // each relay in the batch
+    let now = chrono::Utc::now();
+    if let Some(oldest) = batch.iter().map(|e| e.event.created_at).min() {
+        let lag = now.signed_duration_since(oldest).num_seconds();
+        gauge!(ingestion::LAG).set(lag as f64);
+        status.record_event_lag(lag);
+    }
+    let mut oldest_by_relay: HashMap<&str, chrono::DateTime<chrono::Utc>> = HashMap::new();
+    for e in &batch {
+        oldest_by_relay
+            .entry(e.relay_source.as_str())
+            .and_modify(|oldest| *oldest = (*oldest).min(e.event.created_at))
+            .or_insert(e.event.created_at);
+    }
+    for (relay, oldest) in oldest_by_relay {
+        let lag = now.signed_duration_since(oldest).num_seconds();
+        gauge!(ingestion::RELAY_LAG, "relay" => relay.to_string()).set(lag as f64);
+    }
+
+    let spilling = spill.as_deref().is_some_and(|spill| spill.depth() > 0);
+    if flushes.max > 1 && !spilling {
+        return flushes.submit(batch, probe, spill).await;
+    }
+    flushes.finish(spill.as_deref_mut()).await?;
+    flush_batch(writer, &batch, spill).await?;
+    if let Some(event_id) = probe {
+        tokio::spawn(probe_queryable(flushes.clickhouse.clone(), event_id));
+    }
+    Ok(())
+}
+
+/// Live batches being inserted concurrently, at most `max` at once.
+///
+/// Batches can be committed out of order: a newer batch may land while an older one
+/// is still being retried. If the stream fails in between, it resumes from the
+/// newest stored event less [`RESUME_BUFFER_SECS`] (or [`CATCHUP_BUFFER_SECS`] after
+/// a restart), which covers the batches that were in flight. A failed batch comes
+/// back to the stream, which spills it or ends.
+struct Flushes {
+    max: usize,
+    clickhouse: ClickHouseClient,
+    retry: RetryConfig,
+    /// Batches submitted so far, which numbers the next one.
+    submitted: u64,
+    /// Numbers of the batches being inserted.
+    in_flight: BTreeSet<u64>,
+    tasks: tokio::task::JoinSet<(u64, Result<(), FailedFlush>)>,
+}
+
+/// A batch a flush worker couldn't insert.
+struct FailedFlush {
+    rows: Vec<EventRow>,
+    error: ClickHouseError,
+}
+
+impl Flushes {
+    fn new(clickhouse: &ClickHouseClient, retry: RetryConfig, max: usize) -> Self {
+        gauge!(ingestion::FLUSH_WORKERS).set(max as f64);
+        gauge!(ingestion::FLUSHES_IN_FLIGHT).set(0.0);
+        Self {
+            max,
+            clickhouse: clickhouse.clone(),
+            retry,
+            submitted: 0,
+            in_flight: BTreeSet::new(),
+            tasks: tokio::task::JoinSet::new(),
+        }
+    }
+
+    /// The number of the oldest batch still being inserted, or of the next one when
+    /// none are: every batch before it has been written or spilled.
+    fn settled_before(&self) -> u64 {
+        self.in_flight.first().copied().unwrap_or(self.submitted)
+    }
+
+    /// Start inserting `batch`, first waiting for a worker if all `max` are busy.
+    async fn submit(
+        &mut self,
+        batch: Vec<LiveEvent>,
+        probe: Option<String>,
+        mut spill: Option<&mut SpillQueue>,
+    ) -> anyhow::Result<()> {
+        while self.tasks.len() >= self.max {
+            self.join_next(spill.as_deref_mut()).await?;
+        }
+
+        let clickhouse = self.clickhouse.clone();
+        let retry = self.retry;
+        let number = self.submitted;
+        self.submitted += 1;
+        self.in_flight.insert(number);
+        self.tasks.spawn(async move {
+            let writer = RetryingWriter::new(&clickhouse, retry);
+            let rows = live_rows(&batch);
+            let start = Instant::now();
+            if let Err(error) = writer.insert_events(&rows).await {
+                return (number, Err(FailedFlush { rows, error }));
+            }
+            record_written(&batch, start.elapsed());
+            if let Some(event_id) = probe {
+                tokio::spawn(probe_queryable(clickhouse.clone(), event_id));
+            }
+            (number, Ok(()))
+        });
+        gauge!(ingestion::FLUSHES_IN_FLIGHT).set(self.tasks.len() as f64);
+        Ok(())
+    }
+
+    /// Collect the batches that have finished, without waiting.
+    async fn reap(&mut self, mut spill: Option<&mut SpillQueue>) -> anyhow::Result<()> {
+        while let Some(result) = self.tasks.try_join_next() {
+            self.finished(result, spill.as_deref_mut()).await?;
+        }
+        Ok(())
+    }
+
+    /// Wait for every batch in flight.
+    async fn finish(&mut self, mut spill: Option<&mut SpillQueue>) -> anyhow::Result<()> {
+        while !self.tasks.is_empty() {
+            self.join_next(spill.as_deref_mut()).await?;
+        }
+        Ok(())
+    }
+
+    async fn join_next(&mut self, spill: Option<&mut SpillQueue>) -> anyhow::Result<()> {
+        match self.tasks.join_next().await {
+            Some(result) => self.finished(result, spill).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Spill a batch that failed, or fail the stream without a spill queue.
+    async fn finished(
+        &mut self,
+        result: Result<(u64, Result<(), FailedFlush>), tokio::task::JoinError>,
+        spill: Option<&mut SpillQueue>,
+    ) -> anyhow::Result<()> {
+        gauge!(ingestion::FLUSHES_IN_FLIGHT).set(self.tasks.len() as f64);
+        let (number, result) = result?;
+        self.in_flight.remove(&number);
+        match (result, spill) {
+            (Ok(()), _) => Ok(()),
+            (Err(failed), Some(spill)) => {
+                spill.spill_failed(failed.error, &failed.rows).await?;
+                Ok(())
+            }
+            (Err(failed), None) => Err(failed.error.into()),
+        }
+    }
+}
+
+/// A client for `relay_urls`, not yet connected.
+async fn relay_client(relay_urls: &[String]) -> anyhow::Result<Client> {
+    let client = Client::builder().build();
+    for relay_url in relay_urls {
+        client.add_relay(relay_url).await?;
+    }
+    Ok(client)
+}
+
+/// Self-checks for live mode: a relay is connected, batches are being flushed,
+/// ClickHouse is reachable, and the notification channel hasn't dropped events.
+///
+/// `client` is `None` while the stream is waiting to reconnect.
+async fn self_check(
+    client: Option<&Client>,
+    clickhouse: &ClickHouseClient,
+    status: &LiveStatus,
+) -> Vec<CheckFailure> {
+    let mut failures = status.check(MAX_FLUSH_AGE);
+
+    let connected = match client {
+        Some(client) => {
+            let connected = client
+                .relays()
+                .await
+                .values()
+                .any(|relay| relay.is_connected());
+            if !connected {
+                failures.push(CheckFailure::new("relay_connected", "no relay connected"));
+            }
+            connected
+        }
+        None => {
+            failures.push(CheckFailure::new("relay_connected", "reconnecting"));
+            false
+        }
+    };
+    status.record_relay_connected(connected);
+
+    match tokio::time::timeout(CHECK_TIMEOUT, clickhouse.ping()).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => failures.push(CheckFailure::new("clickhouse_reachable", e.to_string())),
+        Err(_) => failures.push(CheckFailure::new("clickhouse_reachable", "timed out")),
+    }
+
+    failures
+}
+
+/// Write what each relay sent since the last call to `relay_stats`, with its
+/// connection state, and update each relay's connection and last-event metrics. A
+/// failed insert is logged and its counts dropped rather than ending the stream.
+async fn record_relay_stats(
+    client: &Client,
+    clickhouse: &ClickHouseClient,
+    counters: &mut RelayCounters,
+    stream_started: Instant,
+) {
+    let now = chrono::Utc::now();
+    let stats: Vec<RelayStats> = client
+        .relays()
+        .await
+        .iter()
+        .map(|(url, relay)| {
+            let relay_url = url.as_str().to_string();
+            let connections = counters.record_connections(&relay_url, relay.stats().success());
+            counter!(ingestion::RELAY_CONNECTIONS, "relay" => relay_url.clone())
+                .increment(connections as u64);
+            let last_event = counters.last_event(&relay_url).unwrap_or(stream_started);
+            gauge!(ingestion::RELAY_LAST_EVENT_AGE, "relay" => relay_url.clone())
+                .set(last_event.elapsed().as_secs_f64());
+
+            let counts = counters.take(&relay_url);
+            let connected = relay.is_connected();
+            let uptime_secs = if connected {
+                (now.timestamp().max(0) as u64)
+                    .saturating_sub(relay.stats().connected_at().as_secs())
+            } else {
+                0
+            };
+            RelayStats {
+                relay_url,
+                recorded_at: now,
+                events_received: counts.received,
+                unique_events: counts.unique,
+                unique_videos: counts.unique_videos,
+                duplicates: counts.duplicates(),
+                parse_failures: counts.parse_failures,
+                connected,
+                uptime_secs,
+            }
+        })
+        .collect();
+
+    if let Err(e) = clickhouse.insert_relay_stats(&stats).await {
+        warn_throttled!(code = %e.code(), error = %e, "Failed to record relay stats");
+    }
+}
+
+/// The event to write for `notification`, if any, counting it for its relay and
+/// stamping it with the time it was received and the relay it came from. Events that
+/// have already expired (NIP-40) are dropped; events that can't be parsed are added
+/// to `rejected`.
+///
+/// The pool sends every event a relay delivers as a message, and only the first
+/// copy of each event as an `Event` notification.
+fn handle_notification(
+    notification: RelayPoolNotification,
+    counters: &mut RelayCounters,
+    rejected: &mut Vec<RejectedEvent>,
+) -> Option<LiveEvent> {
+    match notification {
+        RelayPoolNotification::Event {
+            relay_url, event, ..
+        } => {
+            counter!(ingestion::EVENTS_RECEIVED, "kind" => event.kind.as_u16().to_string())
+                .increment(1);
+            let parsed = convert_event(&event)
+                .map(|parsed| parsed.with_received_at(chrono::Utc::now()))
+                .inspect_err(|e| {
+                    warn_throttled!(
+                        code = %e.code(),
+                        event_id = %event.id,
+                        kind = event.kind.as_u16(),
+                        error = %e,
+                        "Skipping unparseable event"
+                    );
+                    rejected.push(reject(&event, relay_url.as_str(), e.code(), e));
+                })
+                .ok();
+            counters.record_unique(relay_url.as_str(), parsed.as_ref());
+            parsed
+                .filter(|parsed| {
+                    let expired = parsed.received_at.is_some_and(|at| parsed.is_expired(at));
+                    if expired {
+                        tracing::debug!(event_id = %parsed.id, "Skipping expired event");
+                    }
+                    !expired
+                })
+                .map(|event| LiveEvent {
+                    relay_source: relay_url.as_str().to_string(),
+                    event,
+                })
+        }
+        RelayPoolNotification::Message { relay_url, message } => {
+            match message {
+                RelayMessage::Event { .. } => {
+                    counter!(ingestion::RELAY_EVENTS_RECEIVED, "relay" => relay_url.as_str().to_string())
+                        .increment(1);
+                    counters.record_received(relay_url.as_str());
+                }
+                RelayMessage::EndOfStoredEvents(_) => {
+                    tracing::info!("EOSE received - now streaming live events")
+                }
+                _ => {}
+            }
+            None
+        }
+        RelayPoolNotification::Shutdown => {
+            tracing::warn!(code = %ErrorCode::IngestChannelClosed, "Relay pool shutdown");
+            None
+        }
+    }
+}
+
+/// A record of `event`, from `relay_source`, rejected with `code` because of `error`.
+fn reject(
+    event: &Event,
+    relay_source: &str,
+    code: ErrorCode,
+    error: &impl std::fmt::Display,
+) -> RejectedEvent {
+    counter!(ingestion::EVENTS_REJECTED, "code" => code.to_string()).increment(1);
+    RejectedEvent {
+        event_id: event.id.to_hex(),
+        pubkey: event.pubkey.to_hex(),
+        kind: event.kind.as_u16(),
+        relay_source: relay_source.to_string(),
+        rejected_at: chrono::Utc::now(),
+        code: code.to_string(),
+        error: error.to_string(),
+        raw: event.as_json(),
+    }
+}
+
+/// Write and clear the rejected events collected so far. A failed insert is logged
+/// and the records dropped rather than ending the stream.
+async fn record_rejected(clickhouse: &ClickHouseClient, rejected: &mut Vec<RejectedEvent>) {
+    if rejected.is_empty() {
+        return;
+    }
+    if let Err(e) = clickhouse.insert_rejected_events(rejected).await {
+        warn_throttled!(code = %e.code(), error = %e, "Failed to record rejected events");
+    }
+    rejected.clear();
+}
+
+/// The deletion requests in `batch`, with the ID of the event making each.
+fn deletion_requests(batch: &[LiveEvent]) -> Vec<(String, DeletionMeta)> {
+    batch
+        .iter()
+        .filter_map(|e| DeletionMeta::from_event(&e.event).map(|r| (e.event.id.clone(), r)))
+        .collect()
+}
+
+/// Deletion requests from live batches, held until they can find their targets.
+///
+/// Batches may be committed out of order with several flush workers, and spilled
+/// ones are written later, so a request waits until the batch it came in and every
+/// batch before it have been written and nothing is spilled: a target that came
+/// with the request, or before it, is stored by then. Requests that fail to apply
+/// are kept and retried after [`DELETION_RETRY_INTERVAL`]. They're kept across
+/// reconnects too, but only in memory, so any still waiting when the ingester
+/// stops are lost.
+#[derive(Default)]
+struct PendingDeletions {
+    requests: Vec<PendingDeletion>,
+    retry_at: Option<Instant>,
+}
+
+struct PendingDeletion {
+    /// The number of batches submitted to [`Flushes`] that must settle first.
+    after: u64,
+    deletion_event_id: String,
+    request: DeletionMeta,
+}
+
+impl PendingDeletions {
+    /// Hold `requests` until every batch submitted to `flushes` so far has settled.
+    fn add(&mut self, requests: Vec<(String, DeletionMeta)>, flushes: &Flushes) {
+        let after = flushes.submitted;
+        self.requests
+            .extend(
+                requests
+                    .into_iter()
+                    .map(|(deletion_event_id, request)| PendingDeletion {
+                        after,
+                        deletion_event_id,
+                        request,
+                    }),
+            );
+    }
+
+    /// Release requests from an earlier stream, whose batches have all settled.
+    fn restart(&mut self) {
+        for pending in &mut self.requests {
+            pending.after = 0;
+        }
+    }
+
+    /// Take the requests whose batches have been written, unless batches are spilled.
+    fn take_ready(
+        &mut self,
+        flushes: &Flushes,
+        spill: Option<&SpillQueue>,
+    ) -> Vec<PendingDeletion> {
+        if spill.is_some_and(|spill| spill.depth() > 0) {
+            return Vec::new();
+        }
+        let settled = flushes.settled_before();
+        let (ready, waiting) = std::mem::take(&mut self.requests)
+            .into_iter()
+            .partition(|pending| pending.after <= settled);
+        self.requests = waiting;
+        ready
+    }
+
+    /// Apply the requests that are ready. A failure is logged and the requests kept
+    /// for a retry rather than ending the stream.
+    async fn apply(
+        &mut self,
+        clickhouse: &ClickHouseClient,
+        flushes: &Flushes,
+        spill: Option<&SpillQueue>,
+        lightweight: bool,
+    ) {
+        if self.retry_at.is_some_and(|at| Instant::now() < at) {
+            return;
+        }
+        let ready = self.take_ready(flushes, spill);
+        if ready.is_empty() {
+            return;
+        }
+        match apply_deletion_requests(clickhouse, &ready, lightweight).await {
+            Ok(()) => self.retry_at = None,
+            Err(e) => {
+                warn_throttled!(
+                    code = %e.code(),
+                    error = %e,
+                    requests = ready.len(),
+                    "Failed to apply deletion requests, will retry"
+                );
+                self.retry_at = Some(Instant::now() + DELETION_RETRY_INTERVAL);
+                self.requests.splice(0..0, ready);
+            }
+        }
+    }
+}
+
+/// Record tombstones for the events each of `requests` deletes, also removing them
+/// with `lightweight`.
+async fn apply_deletion_requests(
+    clickhouse: &ClickHouseClient,
+    requests: &[PendingDeletion],
+    lightweight: bool,
+) -> Result<(), ClickHouseError> {
+    let mut tombstones = Vec::new();
+    for PendingDeletion {
+        deletion_event_id,
+        request,
+        ..
+    } in requests
+    {
+        // Only the author's own events, which the request can't show by itself
+        let targets = clickhouse.get_deletion_targets(request).await?;
+        tracing::debug!(
+            %deletion_event_id,
+            pubkey = %request.pubkey,
+            targets = targets.len(),
+            "Applying deletion request"
+        );
+        tombstones.extend(
+            targets
+                .iter()
+                .map(|id| EventDeletion::from_target(id, deletion_event_id, request)),
+        );
+    }
+    if tombstones.is_empty() {
+        return Ok(());
+    }
+
+    clickhouse.insert_deletions(&tombstones).await?;
+    counter!(ingestion::EVENTS_DELETED).increment(tombstones.len() as u64);
+    if lightweight {
+        let ids: Vec<String> = tombstones.into_iter().map(|t| t.event_id).collect();
+        clickhouse.delete_events(&ids).await?;
+    }
+    Ok(())
+}
+
+fn convert_event(event: &Event) -> Result<ParsedEvent, ParseError> {
+    ParsedEvent::from_json(&event.as_json())
+}
+
+/// Write `batch` to ClickHouse, or to `spill` if ClickHouse fails.
+async fn flush_batch(
+    writer: &impl EventWriter,
+    batch: &[LiveEvent],
+    spill: Option<&mut SpillQueue>,
+) -> anyhow::Result<()> {
+    if batch.is_empty() {
+        return Ok(());
+    }
+
+    let start = Instant::now();
+    let rows = live_rows(batch);
+
+    let written = match spill {
+        Some(spill) => spill.write(writer, &rows).await?,
+        None => {
+            writer.insert_events(&rows).await?;
+            Written::Inserted
+        }
+    };
+    if written == Written::Spilled {
+        tracing::debug!(count = batch.len(), "Spilled");
+        return Ok(());
+    }
+
+    record_written(batch, start.elapsed());
+    Ok(())
+}
+
+/// Rows to insert for `batch`, recording its size.
+fn live_rows(batch: &[LiveEvent]) -> Vec<EventRow> {
+    histogram!(ingestion::BATCH_SIZE).record(batch.len() as f64);
+    batch
+        .iter()
+        .map(|e| EventRow::from_parsed(&e.event, &e.relay_source))
+        .collect()
+}
+
+/// Record `batch` as inserted, `duration` after it was started.
+fn record_written(batch: &[LiveEvent], duration: Duration) {
+    histogram!(ingestion::WRITE_LATENCY).record(duration.as_secs_f64());
+    counter!(ingestion::EVENTS_WRITTEN).increment(batch.len() as u64);
+    record_event_latency(batch.iter().map(|e| &e.event), chrono::Utc::now());
+
+    tracing::debug!(
+        count = batch.len(),
+        duration_ms = duration.as_millis(),
+        "Flushed"
+    );
+}
+
+/// Record how long each event in a just-inserted batch took to reach the relay and
+/// then ClickHouse. Events without a receive time (backfilled history) are skipped.
+fn record_event_latency<'a>(
+    batch: impl IntoIterator<Item = &'a ParsedEvent>,
+    inserted_at: chrono::DateTime<chrono::Utc>,
+) {
+    for event in batch {
+        let Some(received_at) = event.received_at else {
+            continue;
+        };
+        let created_to_received = received_at.signed_duration_since(event.created_at);
+        let received_to_inserted = inserted_at.signed_duration_since(received_at);
+        histogram!(ingestion::EVENT_LATENCY, "stage" => "created_to_received")
+            .record(seconds(created_to_received));
+        histogram!(ingestion::EVENT_LATENCY, "stage" => "received_to_inserted")
+            .record(seconds(received_to_inserted));
+    }
+}
+
+/// `duration` in seconds, treating clock skew that makes it negative as zero.
+fn seconds(duration: chrono::TimeDelta) -> f64 {
+    duration.num_milliseconds().max(0) as f64 / 1000.0
+}
+
+/// Poll until the just-inserted `event_id` can be read back, recording how long that
+/// took. Gives up quietly after [`QUERYABLE_PROBE_TIMEOUT`].
+async fn probe_queryable(clickhouse: ClickHouseClient, event_id: String) {
+    let start = Instant::now();
+    while start.elapsed() < QUERYABLE_PROBE_TIMEOUT {
+        match clickhouse.has_event(&event_id).await {
+            Ok(true) => {
+                histogram!(ingestion::EVENT_LATENCY, "stage" => "inserted_to_queryable")
+                    .record(start.elapsed().as_secs_f64());
+                return;
+            }
+            Ok(false) => {}
+            Err(e) => {
+                warn_throttled!(code = %e.code(), error = %e, "Queryable probe failed");
+                return;
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    tracing::debug!(%event_id, "Inserted event not queryable before the probe timed out");
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use super::*;
+
+    const EVENT_JSON: &str = r#"{"id":"4376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65","pubkey":"6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93","created_at":1673347337,"kind":1,"tags":[["t","nostr"]],"content":"Test","sig":"908a15e46fb4d8675bab026fc230a0e3542bfade63da02d542fb78b2a8513fcd0092619a2c8c1221e581946e0191f2af505dfdf8657a414dbca329186f009262"}"#;
+
+    #[derive(Default)]
+    struct MockWriter {
+        down: AtomicBool,
+        batches: Mutex<Vec<Vec<EventRow>>>,
+    }
+
+    impl EventWriter for MockWriter {
+        async fn insert_events(&self, events: &[EventRow]) -> Result<(), ClickHouseError> {
+            if self.down.load(Ordering::Relaxed) {
+                return Err(ClickHouseError::Connection("refused".to_string()));
+            }
+            self.batches.lock().unwrap().push(events.to_vec());
+            Ok(())
+        }
+    }
+
+    fn no_retries() -> RetryConfig {
+        RetryConfig {
+            attempts: 1,
+            ..RetryConfig::default()
+        }
+    }
+
+    fn flushes(max: usize) -> Flushes {
+        let clickhouse = ClickHouseClient::new("http://localhost:8123", "test").unwrap();
+        Flushes::new(&clickhouse, no_retries(), max)
+    }
+
+    fn live_batch(sources: &[&str]) -> Vec<LiveEvent> {
+        let event = ParsedEvent::from_json(EVENT_JSON)
+            .unwrap()
+            .with_received_at(chrono::Utc::now());
+        sources
+            .iter()
+            .map(|source| LiveEvent {
+                relay_source: source.to_string(),
+                event: event.clone(),
+            })
+            .collect()
+    }
+
+    fn sources(writer: &MockWriter) -> Vec<Vec<String>> {
+        writer
+            .batches
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|batch| batch.iter().map(|row| row.relay_source.clone()).collect())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn live_batches_are_written_with_their_relays() {
+        let writer = MockWriter::default();
+        let status = LiveStatus::default();
+        let batch = live_batch(&["wss://a.example.com", "wss://b.example.com"]);
+
+        let retrying = RetryingWriter::new(&writer, no_retries());
+        flush_live(&retrying, &mut flushes(1), batch, None, None, &status)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            sources(&writer),
+            [["wss://a.example.com", "wss://b.example.com"]]
+        );
+        assert!(writer.batches.lock().unwrap()[0][0].ingested_at.is_some());
+        assert!(status.event_lag_secs().is_some());
+    }
+
+    #[tokio::test]
+    async fn failed_live_batches_spill_and_are_written_first() {
+        let dir = std::env::temp_dir().join(format!("funnel-live-spill-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut spill = SpillQueue::open(&dir, Duration::ZERO).await.unwrap();
+        let writer = MockWriter::default();
+        let retrying = RetryingWriter::new(&writer, no_retries());
+        let status = LiveStatus::default();
+        let mut flushes = flushes(1);
+
+        writer.down.store(true, Ordering::Relaxed);
+        let batch = live_batch(&["wss://a.example.com"]);
+        flush_live(
+            &retrying,
+            &mut flushes,
+            batch,
+            None,
+            Some(&mut spill),
+            &status,
+        )
+        .await
+        .unwrap();
+        assert_eq!(spill.depth(), 1);
+
+        writer.down.store(false, Ordering::Relaxed);
+        let batch = live_batch(&["wss://b.example.com"]);
+        flush_live(
+            &retrying,
+            &mut flushes,
+            batch,
+            None,
+            Some(&mut spill),
+            &status,
+        )
+        .await
+        .unwrap();
+        assert_eq!(spill.depth(), 0);
+        assert_eq!(
+            sources(&writer),
+            [["wss://a.example.com"], ["wss://b.example.com"]]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn deletions_wait_for_their_batch_with_several_workers() {
+        let dir =
+            std::env::temp_dir().join(format!("funnel-live-deletions-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut spill = SpillQueue::open(&dir, Duration::ZERO).await.unwrap();
+        let writer = MockWriter::default();
+        let retrying = RetryingWriter::new(&writer, no_retries());
+        let mut flushes = flushes(2);
+        let mut deletions = PendingDeletions::default();
+
+        // The target and a request deleting it come in the same batch
+        let mut batch = live_batch(&["wss://a.example.com"]);
+        let target = batch[0].event.id.clone();
+        let mut request = batch[0].event.clone();
+        request.id = "ab".repeat(32);
+        request.kind = 5;
+        request.tags = vec![vec!["e".to_string(), target.clone()]];
+        batch.push(LiveEvent {
+            relay_source: "wss://a.example.com".to_string(),
+            event: request,
+        });
+
+        let requests = deletion_requests(&batch);
+        flush_live(
+            &retrying,
+            &mut flushes,
+            batch,
+            None,
+            Some(&mut spill),
+            &LiveStatus::default(),
+        )
+        .await
+        .unwrap();
+        deletions.add(requests, &flushes);
+        // Still being inserted by a worker
+        assert!(deletions.take_ready(&flushes, Some(&spill)).is_empty());
+
+        // The workers' ClickHouse isn't running, so the batch is spilled
+        flushes.finish(Some(&mut spill)).await.unwrap();
+        assert_eq!(spill.depth(), 1);
+        assert!(deletions.take_ready(&flushes, Some(&spill)).is_empty());
+
+        spill.write(&retrying, &[]).await.unwrap();
+        assert_eq!(spill.depth(), 0);
+        assert_eq!(writer.batches.lock().unwrap()[0].len(), 2);
+        let ready = deletions.take_ready(&flushes, Some(&spill));
+        assert_eq!(ready.len(), 1);
+        assert_eq!(ready[0].request.event_ids, [target]);
+        assert!(deletions.requests.is_empty());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn failed_live_batch_without_spill_ends_the_stream() {
+        let writer = MockWriter::default();
+        writer.down.store(true, Ordering::Relaxed);
+        let retrying = RetryingWriter::new(&writer, no_retries());
+
+        let batch = live_batch(&["wss://a.example.com"]);
+        let result = flush_live(
+            &retrying,
+            &mut flushes(1),
+            batch,
+            None,
+            None,
+            &LiveStatus::default(),
+        )
+        .await;
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,324 @@
+//! Durable queues as an event source.
+//!
+//! Instead of subscribing to relays directly, relays (or anything else) can publish
+//! events into a durable queue and `funnel ingest --nats` consumes them. Each
+//! message is acknowledged only after the batch holding its event is in ClickHouse,
+//! so delivery is at least once: messages from a failed insert or a crashed
+//! ingester are delivered again, and ClickHouse deduplicates the events by ID.
+//! Messages that aren't an event are acknowledged and counted as skipped, so they
+//! aren't redelivered forever.
+//!
+//! [`EventSource`] is the queue; [`consume`] batches its messages the way stdin
+//! mode does. The NATS JetStream implementation, [`NatsSource`], needs the `nats`
+//! feature.
+
+#[cfg(feature = "nats")]
+mod nats;
+
+use std::future::Future;
+
+use funnel_clickhouse::{ClickHouseError, EventWriter};
+use funnel_observability::{ingestion, warn_throttled};
+use metrics::counter;
+use thiserror::Error;
+
+use crate::replay::{ReplayStats, write_batch};
+use crate::verify::Verifier;
+use crate::{BatchConfig, BatchProcessor, FlushReason, parse_line};
+
+#[cfg(feature = "nats")]
+pub use self::nats::NatsSource;
+
+/// Consumer name used unless `NATS_CONSUMER` is set.
+pub const DEFAULT_NATS_CONSUMER: &str = "funnel-ingestion";
+
+/// Errors consuming from an event source.
+#[derive(Debug, Error)]
+pub enum SourceError {
+    #[error("event source failed: {0}")]
+    Queue(#[source] Box<dyn std::error::Error + Send + Sync>),
+    #[error(transparent)]
+    ClickHouse(#[from] ClickHouseError),
+}
+
+impl SourceError {
+    pub fn queue(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Self {
+        Self::Queue(error.into())
+    }
+}
+
+/// A message from an event source.
+#[derive(Debug)]
+pub struct Delivery<A> {
+    /// Event JSON, or a strfry stream line.
+    pub payload: Vec<u8>,
+    /// Acknowledges the message once its event is stored.
+    pub ack: A,
+}
+
+/// A durable queue of events, consumed with explicit acknowledgements.
+pub trait EventSource {
+    type Ack: Send;
+
+    /// The next message, or `None` once the source has closed. Must be cancel safe:
+    /// a call dropped before it completes loses no message.
+    fn next(
+        &mut self,
+    ) -> impl Future<Output = Result<Option<Delivery<Self::Ack>>, SourceError>> + Send;
+
+    /// Acknowledge messages whose events are stored, so they aren't delivered again.
+    fn ack(&mut self, acks: Vec<Self::Ack>)
+    -> impl Future<Output = Result<(), SourceError>> + Send;
+}
+
+/// Connection settings for the NATS JetStream source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NatsConfig {
+    pub url: String,
+    /// Stream events are published to.
+    pub stream: String,
+    /// Durable consumer whose position is kept between runs.
+    pub consumer: String,
+    /// Only subjects matching this filter, if set.
+    pub subject: Option<String>,
+}
+
+impl NatsConfig {
+    /// Read `NATS_URL` and `NATS_STREAM` (both required), `NATS_CONSUMER` (default
+    /// [`DEFAULT_NATS_CONSUMER`]), and `NATS_SUBJECT`.
+    pub fn from_env() -> anyhow::Result<Self> {
+        let var = |name: &str| {
+            std::env::var(name)
+                .ok()
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        Ok(Self {
+            url: var("NATS_URL").ok_or_else(|| anyhow::anyhow!("NATS_URL is not set"))?,
+            stream: var("NATS_STREAM").ok_or_else(|| anyhow::anyhow!("NATS_STREAM is not set"))?,
+            consumer: var("NATS_CONSUMER").unwrap_or_else(|| DEFAULT_NATS_CONSUMER.to_string()),
+            subject: var("NATS_SUBJECT"),
+        })
+    }
+}
+
+/// Insert events from `source` until it closes, recording `relay_source` as their
+/// origin and verifying each batch with `verifier` when given.
+///
+/// Batches like [`replay_stream`](crate::replay::replay_stream), writing a partial
+/// batch once it has waited `config.flush_interval`. Messages are acknowledged after
+/// their batch is written; a failed insert returns the error with the batch's
+/// messages unacknowledged.
+pub async fn consume<S, W>(
+    source: &mut S,
+    writer: &W,
+    config: BatchConfig,
+    relay_source: &str,
+    verifier: Option<Verifier>,
+) -> Result<ReplayStats, SourceError>
+where
+    S: EventSource,
+    W: EventWriter,
+{
+    let mut stats = ReplayStats::default();
+    let mut processor = BatchProcessor::new(config);
+    let mut acks = Vec::new();
+
+    loop {
+        // Only wait for the flush interval while there is something to acknowledge
+        let delivery = if acks.is_empty() {
+            source.next().await?
+        } else {
+            let wait = processor
+                .flush_interval()
+                .saturating_sub(processor.time_since_flush());
+            match tokio::time::timeout(wait, source.next()).await {
+                Ok(delivery) => delivery?,
+                Err(_) => {
+                    flush(
+                        source,
+                        writer,
+                        &mut processor,
+                        &mut acks,
+                        relay_source,
+                        verifier,
+                        &mut stats,
+                    )
+                    .await?;
+                    continue;
+                }
+            }
+        };
+        let Some(delivery) = delivery else {
+            break;
+        };
+
+        match std::str::from_utf8(&delivery.payload)
+            .ok()
+            .and_then(|line| parse_line(line.trim()))
+        {
+            Some(event) => {
+                counter!(ingestion::EVENTS_RECEIVED, "kind" => event.kind.to_string()).increment(1);
+                processor.push(event);
+            }
+            None => {
+                stats.skipped += 1;
+                warn_throttled!("Skipping message that isn't an event");
+            }
+        }
+        acks.push(delivery.ack);
+
+        if processor.should_flush() != FlushReason::None {
+            flush(
+                source,
+                writer,
+                &mut processor,
+                &mut acks,
+                relay_source,
+                verifier,
+                &mut stats,
+            )
+            .await?;
+        }
+    }
+
+    flush(
+        source,
+        writer,
+        &mut processor,
+        &mut acks,
+        relay_source,
+        verifier,
+        &mut stats,
+    )
+    .await?;
+    Ok(stats)
+}
+
+/// Write the processor's batch, if it has one, then acknowledge `acks`.
+async fn flush<S, W>(
+    source: &mut S,
+    writer: &W,
+    processor: &mut BatchProcessor,
+    acks: &mut Vec<S::Ack>,
+    relay_source: &str,
+    verifier: Option<Verifier>,
+    stats: &mut ReplayStats,
+) -> Result<(), SourceError>
+where
+    S: EventSource,
+    W: EventWriter,
+{
+    write_batch(writer, processor, relay_source, verifier, stats).await?;
+    if !acks.is_empty() {
+        source.ack(std::mem::take(acks)).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    use funnel_clickhouse::EventRow;
+
+    use super::*;
+
+    const EVENT_JSON: &str = r#"{"id":"4376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65","pubkey":"6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93","created_at":1673347337,"kind":1,"tags":[],"content":"Test","sig":"908a15e46fb4d8675bab026fc230a0e3542bfade63da02d542fb78b2a8513fcd0092619a2c8c1221e581946e0191f2af505dfdf8657a414dbca329186f009262"}"#;
+
+    /// Messages numbered in order, acknowledged by number.
+    #[derive(Default)]
+    struct MockSource {
+        messages: VecDeque<&'static str>,
+        delivered: u32,
+        acked: Vec<u32>,
+    }
+
+    impl MockSource {
+        fn new(messages: &[&'static str]) -> Self {
+            Self {
+                messages: messages.iter().copied().collect(),
+                ..Self::default()
+            }
+        }
+    }
+
+    impl EventSource for MockSource {
+        type Ack = u32;
+
+        async fn next(&mut self) -> Result<Option<Delivery<u32>>, SourceError> {
+            let Some(message) = self.messages.pop_front() else {
+                return Ok(None);
+            };
+            self.delivered += 1;
+            Ok(Some(Delivery {
+                payload: message.as_bytes().to_vec(),
+                ack: self.delivered,
+            }))
+        }
+
+        async fn ack(&mut self, acks: Vec<u32>) -> Result<(), SourceError> {
+            self.acked.extend(acks);
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct MockWriter {
+        down: AtomicBool,
+        batches: Mutex<Vec<Vec<EventRow>>>,
+    }
+
+    impl EventWriter for MockWriter {
+        async fn insert_events(&self, events: &[EventRow]) -> Result<(), ClickHouseError> {
+            if self.down.load(Ordering::Relaxed) {
+                return Err(ClickHouseError::Connection("refused".to_string()));
+            }
+            self.batches.lock().unwrap().push(events.to_vec());
+            Ok(())
+        }
+    }
+
+    fn config(batch_size: usize) -> BatchConfig {
+        BatchConfig::new(batch_size, Duration::from_secs(60))
+    }
+
+    #[tokio::test]
+    async fn acknowledges_messages_once_written() {
+        let mut source = MockSource::new(&[EVENT_JSON, "not json", EVENT_JSON, EVENT_JSON]);
+        let writer = MockWriter::default();
+
+        let stats = consume(&mut source, &writer, config(2), "nats", None)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            stats,
+            ReplayStats {
+                inserted: 3,
+                skipped: 1,
+                rejected: 0,
+            }
+        );
+        let batches = writer.batches.lock().unwrap();
+        assert_eq!(batches.iter().map(Vec::len).collect::<Vec<_>>(), [2, 1]);
+        assert_eq!(batches[0][0].relay_source, "nats");
+        // The skipped message is acknowledged with its batch
+        assert_eq!(source.acked, [1, 2, 3, 4]);
+    }
+
+    #[tokio::test]
+    async fn failed_insert_leaves_messages_unacknowledged() {
+        let mut source = MockSource::new(&[EVENT_JSON, EVENT_JSON, EVENT_JSON]);
+        let writer = MockWriter::default();
+        writer.down.store(true, Ordering::Relaxed);
+
+        let result = consume(&mut source, &writer, config(2), "nats", None).await;
+
+        assert!(matches!(result, Err(SourceError::ClickHouse(_))));
+        assert!(source.acked.is_empty());
+    }
+}
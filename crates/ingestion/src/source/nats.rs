@@ -0,0 +1,78 @@
+//! NATS JetStream event source.
+//!
+//! Reads from a durable pull consumer on `NATS_STREAM`, created if it doesn't
+//! exist, with explicit acknowledgements. The consumer keeps its position on the
+//! server, so a restarted ingester carries on from the first unacknowledged message.
+
+use async_nats::jetstream::{
+    self,
+    consumer::{AckPolicy, pull},
+    message::Acker,
+};
+use futures::StreamExt;
+
+use super::{Delivery, EventSource, NatsConfig, SourceError};
+
+/// Messages from a JetStream pull consumer.
+pub struct NatsSource {
+    messages: pull::Stream,
+}
+
+impl NatsSource {
+    /// Connect to `config.url` and start pulling from the stream's consumer.
+    pub async fn connect(config: &NatsConfig) -> Result<Self, SourceError> {
+        let client = async_nats::connect(&config.url)
+            .await
+            .map_err(SourceError::queue)?;
+        let stream = jetstream::new(client)
+            .get_stream(&config.stream)
+            .await
+            .map_err(SourceError::queue)?;
+        let consumer = stream
+            .get_or_create_consumer(
+                &config.consumer,
+                pull::Config {
+                    durable_name: Some(config.consumer.clone()),
+                    ack_policy: AckPolicy::Explicit,
+                    filter_subject: config.subject.clone().unwrap_or_default(),
+                    ..Default::default()
+                },
+            )
+            .await
+            .map_err(SourceError::queue)?;
+        let messages = consumer.messages().await.map_err(SourceError::queue)?;
+
+        tracing::info!(
+            stream = %config.stream,
+            consumer = %config.consumer,
+            subject = config.subject.as_deref(),
+            "Consuming from NATS JetStream"
+        );
+        Ok(Self { messages })
+    }
+}
+
+impl EventSource for NatsSource {
+    type Ack = Acker;
+
+    async fn next(&mut self) -> Result<Option<Delivery<Acker>>, SourceError> {
+        match self.messages.next().await {
+            Some(Ok(message)) => {
+                let (message, ack) = message.split();
+                Ok(Some(Delivery {
+                    payload: message.payload.to_vec(),
+                    ack,
+                }))
+            }
+            Some(Err(e)) => Err(SourceError::queue(e)),
+            None => Ok(None),
+        }
+    }
+
+    async fn ack(&mut self, acks: Vec<Acker>) -> Result<(), SourceError> {
+        for ack in acks {
+            ack.ack().await.map_err(SourceError::queue)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,374 @@
+//! Disk spill queue for batches ClickHouse can't take.
+//!
+//! When an insert fails, live ingestion writes the batch to a segment file in
+//! `SPILL_DIR` (one JSONL file of [`EventRow`]s per batch) instead of dropping it,
+//! and keeps streaming. While segments are queued, new batches go straight to disk
+//! until the retry interval has passed; then the queue is drained oldest first,
+//! ahead of the new batch, deleting each segment once it's written. Segments left
+//! behind by a previous run are picked up on startup.
+
+use std::collections::VecDeque;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use funnel_clickhouse::{ClickHouseError, EventRow, EventWriter};
+use funnel_observability::ingestion;
+use metrics::{counter, gauge};
+use thiserror::Error;
+
+const SEGMENT_EXTENSION: &str = "jsonl";
+
+/// Errors from the spill queue.
+#[derive(Debug, Error)]
+pub enum SpillError {
+    #[error("spill queue I/O failed on {}: {source}", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("failed to encode spilled event: {0}")]
+    Encode(#[from] serde_json::Error),
+
+    #[error(transparent)]
+    ClickHouse(#[from] ClickHouseError),
+}
+
+/// Where a batch written through [`SpillQueue::write`] ended up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Written {
+    Inserted,
+    Spilled,
+}
+
+/// Queue of batches waiting on disk for ClickHouse to come back.
+#[derive(Debug)]
+pub struct SpillQueue {
+    dir: PathBuf,
+    segments: VecDeque<PathBuf>,
+    next_id: u64,
+    retry_interval: Duration,
+    retry_at: Option<Instant>,
+}
+
+impl SpillQueue {
+    /// Open the queue in `dir`, creating it if needed, with any segments already
+    /// there queued first. While segments are queued, ClickHouse is retried at most
+    /// once every `retry_interval`.
+    pub async fn open(
+        dir: impl Into<PathBuf>,
+        retry_interval: Duration,
+    ) -> Result<Self, SpillError> {
+        let dir = dir.into();
+        tokio::fs::create_dir_all(&dir)
+            .await
+            .map_err(io_error(&dir))?;
+
+        let mut ids = Vec::new();
+        let mut entries = tokio::fs::read_dir(&dir).await.map_err(io_error(&dir))?;
+        while let Some(entry) = entries.next_entry().await.map_err(io_error(&dir))? {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == SEGMENT_EXTENSION)
+                && let Some(id) = path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .and_then(|stem| stem.parse::<u64>().ok())
+            {
+                ids.push(id);
+            }
+        }
+        ids.sort_unstable();
+
+        let queue = Self {
+            next_id: ids.last().map_or(0, |id| id + 1),
+            segments: ids.iter().map(|id| segment_path(&dir, *id)).collect(),
+            dir,
+            retry_interval,
+            retry_at: None,
+        };
+        if !queue.segments.is_empty() {
+            tracing::info!(
+                dir = %queue.dir.display(),
+                segments = queue.segments.len(),
+                "Found spilled batches, will drain them first"
+            );
+        }
+        queue.record_depth();
+        Ok(queue)
+    }
+
+    /// Number of batches waiting on disk.
+    pub fn depth(&self) -> usize {
+        self.segments.len()
+    }
+
+    /// Write `rows` to ClickHouse through the queue: drain queued segments first,
+    /// then insert `rows`, spilling them to disk instead if ClickHouse fails or is
+    /// still waiting out the retry interval. Empty `rows` only drains.
+    ///
+    /// Only a failure to spill is an error.
+    pub async fn write<W>(&mut self, writer: &W, rows: &[EventRow]) -> Result<Written, SpillError>
+    where
+        W: EventWriter,
+    {
+        if self.retry_at.is_some_and(|at| Instant::now() < at) {
+            return self.spill(rows).await;
+        }
+
+        match self.drain(writer).await {
+            Ok(()) => {}
+            Err(SpillError::ClickHouse(e)) => return self.retry_later(e, rows).await,
+            Err(e) => return Err(e),
+        }
+        if rows.is_empty() {
+            return Ok(Written::Inserted);
+        }
+        match writer.insert_events(rows).await {
+            Ok(()) => Ok(Written::Inserted),
+            Err(e) => self.retry_later(e, rows).await,
+        }
+    }
+
+    /// Spill `rows` after inserting them failed with `error` outside the queue, as
+    /// [`write`](Self::write) does when its own insert fails: ClickHouse isn't tried
+    /// again until the retry interval has passed.
+    pub async fn spill_failed(
+        &mut self,
+        error: ClickHouseError,
+        rows: &[EventRow],
+    ) -> Result<Written, SpillError> {
+        self.retry_later(error, rows).await
+    }
+
+    /// Insert and delete queued segments, oldest first.
+    async fn drain<W>(&mut self, writer: &W) -> Result<(), SpillError>
+    where
+        W: EventWriter,
+    {
+        while let Some(path) = self.segments.front() {
+            let contents = tokio::fs::read_to_string(path)
+                .await
+                .map_err(io_error(path))?;
+            let mut rows = Vec::new();
+            for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+                match serde_json::from_str::<EventRow>(line) {
+                    Ok(row) => rows.push(row),
+                    Err(e) => tracing::warn!(
+                        segment = %path.display(),
+                        error = %e,
+                        "Skipping unreadable spilled event"
+                    ),
+                }
+            }
+
+            if !rows.is_empty() {
+                writer.insert_events(&rows).await?;
+                counter!(ingestion::EVENTS_WRITTEN).increment(rows.len() as u64);
+            }
+            tokio::fs::remove_file(path).await.map_err(io_error(path))?;
+            tracing::info!(
+                segment = %path.display(),
+                events = rows.len(),
+                "Drained spilled batch"
+            );
+            self.segments.pop_front();
+            self.record_depth();
+        }
+        self.retry_at = None;
+        Ok(())
+    }
+
+    async fn retry_later(
+        &mut self,
+        error: ClickHouseError,
+        rows: &[EventRow],
+    ) -> Result<Written, SpillError> {
+        tracing::warn!(
+            code = %error.code(),
+            error = %error,
+            retry_in_secs = self.retry_interval.as_secs(),
+            "ClickHouse insert failed, spilling to disk"
+        );
+        self.retry_at = Some(Instant::now() + self.retry_interval);
+        self.spill(rows).await
+    }
+
+    /// Write `rows` to a new segment. The segment is written under a temporary name
+    /// and renamed into place, so a crash never leaves a partial segment queued.
+    async fn spill(&mut self, rows: &[EventRow]) -> Result<Written, SpillError> {
+        if rows.is_empty() {
+            return Ok(Written::Spilled);
+        }
+
+        let mut contents = String::new();
+        for row in rows {
+            contents.push_str(&serde_json::to_string(row)?);
+            contents.push('\n');
+        }
+        let path = segment_path(&self.dir, self.next_id);
+        let tmp = path.with_extension("tmp");
+        tokio::fs::write(&tmp, contents)
+            .await
+            .map_err(io_error(&tmp))?;
+        tokio::fs::rename(&tmp, &path)
+            .await
+            .map_err(io_error(&path))?;
+
+        self.next_id += 1;
+        self.segments.push_back(path);
+        counter!(ingestion::EVENTS_SPILLED).increment(rows.len() as u64);
+        self.record_depth();
+        Ok(Written::Spilled)
+    }
+
+    fn record_depth(&self) {
+        gauge!(ingestion::SPILL_SEGMENTS).set(self.segments.len() as f64);
+    }
+}
+
+fn segment_path(dir: &Path, id: u64) -> PathBuf {
+    dir.join(format!("{id:020}.{SEGMENT_EXTENSION}"))
+}
+
+fn io_error(path: &Path) -> impl FnOnce(io::Error) -> SpillError {
+    let path = path.to_path_buf();
+    move |source| SpillError::Io { path, source }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use funnel_proto::ParsedEvent;
+
+    use super::*;
+
+    const EVENT_JSON: &str = r#"{"id":"4376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65","pubkey":"6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93","created_at":1673347337,"kind":1,"tags":[["t","nostr"]],"content":"Test","sig":"908a15e46fb4d8675bab026fc230a0e3542bfade63da02d542fb78b2a8513fcd0092619a2c8c1221e581946e0191f2af505dfdf8657a414dbca329186f009262"}"#;
+
+    #[derive(Default)]
+    struct MockWriter {
+        down: AtomicBool,
+        batches: Mutex<Vec<Vec<EventRow>>>,
+    }
+
+    impl EventWriter for MockWriter {
+        async fn insert_events(&self, events: &[EventRow]) -> Result<(), ClickHouseError> {
+            if self.down.load(Ordering::Relaxed) {
+                return Err(ClickHouseError::Connection("refused".to_string()));
+            }
+            self.batches.lock().unwrap().push(events.to_vec());
+            Ok(())
+        }
+    }
+
+    fn rows(source: &str) -> Vec<EventRow> {
+        let event = ParsedEvent::from_json(EVENT_JSON).unwrap();
+        vec![EventRow::from_parsed(&event, source)]
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("funnel-spill-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn sources(writer: &MockWriter) -> Vec<String> {
+        writer
+            .batches
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|batch| batch[0].relay_source.clone())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn spills_during_outage_and_drains_in_order() {
+        let dir = temp_dir("outage");
+        let writer = MockWriter::default();
+        let mut queue = SpillQueue::open(&dir, Duration::ZERO).await.unwrap();
+
+        writer.down.store(true, Ordering::Relaxed);
+        assert_eq!(
+            queue.write(&writer, &rows("a")).await.unwrap(),
+            Written::Spilled
+        );
+        assert_eq!(
+            queue.write(&writer, &rows("b")).await.unwrap(),
+            Written::Spilled
+        );
+        assert_eq!(queue.depth(), 2);
+
+        writer.down.store(false, Ordering::Relaxed);
+        assert_eq!(
+            queue.write(&writer, &rows("c")).await.unwrap(),
+            Written::Inserted
+        );
+        assert_eq!(queue.depth(), 0);
+        assert_eq!(sources(&writer), ["a", "b", "c"]);
+
+        let spilled = &writer.batches.lock().unwrap()[0][0];
+        let original = &rows("a")[0];
+        assert_eq!(spilled.id, original.id);
+        assert_eq!(spilled.created_at, original.created_at);
+        assert_eq!(spilled.tags, original.tags);
+        assert_eq!(spilled.ingested_at, original.ingested_at);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn spills_batches_that_failed_elsewhere() {
+        let dir = temp_dir("failed");
+        let writer = MockWriter::default();
+        let mut queue = SpillQueue::open(&dir, Duration::ZERO).await.unwrap();
+
+        let error = ClickHouseError::Connection("refused".to_string());
+        assert_eq!(
+            queue.spill_failed(error, &rows("a")).await.unwrap(),
+            Written::Spilled
+        );
+        assert_eq!(queue.depth(), 1);
+
+        assert_eq!(
+            queue.write(&writer, &rows("b")).await.unwrap(),
+            Written::Inserted
+        );
+        assert_eq!(sources(&writer), ["a", "b"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn waits_out_retry_interval_and_resumes_after_restart() {
+        let dir = temp_dir("restart");
+        let writer = MockWriter::default();
+        let mut queue = SpillQueue::open(&dir, Duration::from_secs(3600))
+            .await
+            .unwrap();
+
+        writer.down.store(true, Ordering::Relaxed);
+        queue.write(&writer, &rows("a")).await.unwrap();
+        writer.down.store(false, Ordering::Relaxed);
+        // Still inside the retry interval, so ClickHouse isn't tried
+        assert_eq!(
+            queue.write(&writer, &rows("b")).await.unwrap(),
+            Written::Spilled
+        );
+        assert!(writer.batches.lock().unwrap().is_empty());
+        drop(queue);
+
+        let mut queue = SpillQueue::open(&dir, Duration::from_secs(3600))
+            .await
+            .unwrap();
+        assert_eq!(queue.depth(), 2);
+        assert_eq!(queue.write(&writer, &[]).await.unwrap(), Written::Inserted);
+        assert_eq!(sources(&writer), ["a", "b"]);
+        assert_eq!(queue.depth(), 0);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
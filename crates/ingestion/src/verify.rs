@@ -0,0 +1,155 @@
+//! Signature verification for events that didn't come through a relay connection.
+//!
+//! Events from relay subscriptions are verified by the relay pool before they reach
+//! the live stream, but events piped in (`funnel ingest --stdin`) or replayed from a
+//! file are only parsed, so anyone who can write to the pipe or the file can forge
+//! reactions, zaps, or views from any pubkey. With `VERIFY_SIGNATURES=true`, each
+//! batch is checked before it's written: the event ID is recomputed and the
+//! signature checked, spread across `VERIFY_WORKERS` blocking tasks since Schnorr
+//! verification is CPU-bound. Events that fail are dropped and counted in
+//! `ingestion_events_rejected_total` by error code.
+
+use std::num::NonZeroUsize;
+
+use funnel_observability::{ingestion, warn_throttled};
+use funnel_proto::{ParsedEvent, VerifyError};
+use metrics::counter;
+
+/// Verifies event batches on Tokio's blocking pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Verifier {
+    workers: NonZeroUsize,
+}
+
+impl Verifier {
+    /// A verifier splitting each batch across `workers` tasks.
+    pub fn new(workers: NonZeroUsize) -> Self {
+        Self { workers }
+    }
+
+    /// Load from `VERIFY_SIGNATURES` and `VERIFY_WORKERS` (default: the number of
+    /// CPUs). `None` unless verification is enabled.
+    pub fn from_env() -> Option<Self> {
+        let enabled = std::env::var("VERIFY_SIGNATURES")
+            .ok()
+            .and_then(|s| s.parse::<bool>().ok())
+            .unwrap_or(false);
+        if !enabled {
+            return None;
+        }
+
+        let workers = std::env::var("VERIFY_WORKERS")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .or_else(|| std::thread::available_parallelism().ok())
+            .unwrap_or(NonZeroUsize::MIN);
+        Some(Self::new(workers))
+    }
+
+    /// `events` with every event that fails verification dropped, keeping the order
+    /// of the rest, and the number dropped.
+    pub async fn verify(&self, events: Vec<ParsedEvent>) -> (Vec<ParsedEvent>, u64) {
+        if events.is_empty() {
+            return (events, 0);
+        }
+
+        let chunk_size = events.len().div_ceil(self.workers.get());
+        let mut events = events.into_iter();
+        let mut tasks = Vec::new();
+        loop {
+            let chunk: Vec<_> = events.by_ref().take(chunk_size).collect();
+            if chunk.is_empty() {
+                break;
+            }
+            tasks.push(tokio::task::spawn_blocking(move || {
+                chunk
+                    .into_iter()
+                    .map(|event| {
+                        let result = event.verify();
+                        (event, result)
+                    })
+                    .collect::<Vec<_>>()
+            }));
+        }
+
+        let mut verified = Vec::new();
+        let mut rejected = 0;
+        for task in tasks {
+            let results = task.await.expect("signature verification task panicked");
+            for (event, result) in results {
+                match result {
+                    Ok(()) => verified.push(event),
+                    Err(e) => {
+                        rejected += 1;
+                        record_rejection(&event, &e);
+                    }
+                }
+            }
+        }
+        (verified, rejected)
+    }
+}
+
+fn record_rejection(event: &ParsedEvent, error: &VerifyError) {
+    counter!(ingestion::EVENTS_REJECTED, "code" => error.code().to_string()).increment(1);
+    warn_throttled!(
+        code = %error.code(),
+        event_id = %event.id,
+        pubkey = %event.pubkey,
+        kind = event.kind,
+        error = %error,
+        "Dropping event that failed verification"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+    use nostr_sdk::JsonUtil;
+
+    use super::*;
+    use crate::generate::{GenConfig, Generator};
+
+    fn events(count: i64) -> Vec<ParsedEvent> {
+        let mut generator = Generator::new(GenConfig::default());
+        (0..count)
+            .map(|i| {
+                let event =
+                    generator.next_event(DateTime::from_timestamp(1_700_000_000 + i, 0).unwrap());
+                ParsedEvent::from_json(&event.as_json()).unwrap()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn drops_forged_events_and_keeps_order() {
+        let mut events = events(10);
+        let ids: Vec<_> = events.iter().map(|event| event.id.clone()).collect();
+        // Content changed after signing, and a signature from another event
+        events[2].content.push_str(" (edited)");
+        events[7].sig = events[8].sig.clone();
+
+        let verifier = Verifier::new(NonZeroUsize::new(3).unwrap());
+        let (verified, rejected) = verifier.verify(events).await;
+
+        assert_eq!(rejected, 2);
+        let kept: Vec<_> = verified.iter().map(|event| event.id.as_str()).collect();
+        let expected: Vec<_> = ids
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != 2 && *i != 7)
+            .map(|(_, id)| id.as_str())
+            .collect();
+        assert_eq!(kept, expected);
+    }
+
+    #[tokio::test]
+    async fn handles_more_workers_than_events() {
+        let verifier = Verifier::new(NonZeroUsize::new(16).unwrap());
+
+        let (verified, rejected) = verifier.verify(events(3)).await;
+        assert_eq!((verified.len(), rejected), (3, 0));
+        let (verified, rejected) = verifier.verify(Vec::new()).await;
+        assert_eq!((verified.len(), rejected), (0, 0));
+    }
+}
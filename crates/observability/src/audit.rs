@@ -0,0 +1,119 @@
+//! Audit log stream.
+//!
+//! Privileged actions are recorded with [`audit`] as one JSON object per line, kept
+//! apart from the diagnostic log so they aren't lost to `RUST_LOG` changes and can
+//! be retained on their own schedule. With `AUDIT_LOG_DIR` set, records go to files
+//! in that directory rotated daily (`audit.YYYY-MM-DD.log`), keeping
+//! `AUDIT_LOG_RETENTION_DAYS` days (default 90); otherwise they are written to
+//! stdout alongside the regular log, tagged `"target": "audit"`.
+
+use std::io::Write;
+use std::sync::{Mutex, OnceLock};
+
+use chrono::{SecondsFormat, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use tracing_appender::rolling::{InitError, RollingFileAppender, Rotation};
+
+/// Value of the `target` field on every audit record.
+pub const TARGET: &str = "audit";
+
+/// Days of audit files kept when `AUDIT_LOG_RETENTION_DAYS` is unset.
+pub const DEFAULT_RETENTION_DAYS: usize = 90;
+
+static FILE: OnceLock<Mutex<RollingFileAppender>> = OnceLock::new();
+
+/// Send audit records to rotating files when `AUDIT_LOG_DIR` is set.
+///
+/// Without it (or before this is called) records go to stdout.
+pub fn init_audit_log() -> Result<(), InitError> {
+    let Some(dir) = std::env::var_os("AUDIT_LOG_DIR").filter(|dir| !dir.is_empty()) else {
+        return Ok(());
+    };
+    let retention = std::env::var("AUDIT_LOG_RETENTION_DAYS")
+        .ok()
+        .and_then(|days| days.parse().ok())
+        .unwrap_or(DEFAULT_RETENTION_DAYS);
+
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(TARGET)
+        .filename_suffix("log")
+        .max_log_files(retention)
+        .build(&dir)?;
+    // Only the first call configures the stream
+    let _ = FILE.set(Mutex::new(appender));
+    Ok(())
+}
+
+/// Write `record` to the audit stream, stamped with the current time.
+///
+/// `record` must serialize to a JSON object.
+pub fn audit<T>(record: &T)
+where
+    T: Serialize,
+{
+    let Some(line) = audit_line(record) else {
+        tracing::warn!("Dropped audit record that isn't a JSON object");
+        return;
+    };
+
+    match FILE.get() {
+        Some(file) => {
+            let mut file = file.lock().unwrap_or_else(|e| e.into_inner());
+            if let Err(e) = writeln!(file, "{line}") {
+                tracing::error!(error = %e, "Failed to write audit record");
+            }
+        }
+        None => println!("{line}"),
+    }
+}
+
+/// `record` as a JSON line with `timestamp` and `target` added.
+fn audit_line<T>(record: &T) -> Option<String>
+where
+    T: Serialize,
+{
+    let Ok(Value::Object(mut fields)) = serde_json::to_value(record) else {
+        return None;
+    };
+    fields.insert(
+        "timestamp".to_string(),
+        Utc::now()
+            .to_rfc3339_opts(SecondsFormat::Millis, true)
+            .into(),
+    );
+    fields.insert("target".to_string(), TARGET.into());
+    Some(Value::Object(fields).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Record {
+        route: &'static str,
+        status: u16,
+    }
+
+    #[test]
+    fn audit_line_adds_timestamp_and_target() {
+        let line = audit_line(&Record {
+            route: "/admin/reload",
+            status: 200,
+        })
+        .unwrap();
+        let value: Value = serde_json::from_str(&line).unwrap();
+
+        assert_eq!(value["target"], "audit");
+        assert_eq!(value["route"], "/admin/reload");
+        assert_eq!(value["status"], 200);
+        assert!(value["timestamp"].as_str().unwrap().ends_with('Z'));
+    }
+
+    #[test]
+    fn audit_line_rejects_non_objects() {
+        assert!(audit_line(&"not an object").is_none());
+    }
+}
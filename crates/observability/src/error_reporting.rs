@@ -0,0 +1,75 @@
+//! Error reporting to Sentry-compatible backends.
+//!
+//! Built with the `sentry` feature and run with `SENTRY_DSN` set, panics and
+//! `error!` events are reported along with the fields of the spans they happened in
+//! (request method, path and ID, ClickHouse query name); lower-level events ride
+//! along as breadcrumbs. Without the feature these are no-ops, so callers don't need
+//! their own feature gates.
+
+#[cfg(feature = "sentry")]
+mod enabled {
+    use tracing::Subscriber;
+    use tracing_subscriber::Layer;
+    use tracing_subscriber::registry::LookupSpan;
+
+    /// Flushes pending reports when dropped. Hold it for the life of the process.
+    pub type ErrorReportingGuard = sentry::ClientInitGuard;
+
+    /// Start reporting to the backend in `SENTRY_DSN`, if set.
+    ///
+    /// Reports are tagged with `service_name` and its version, and with
+    /// `SENTRY_ENVIRONMENT` when set.
+    pub fn init_error_reporting(service_name: &str) -> Option<ErrorReportingGuard> {
+        let dsn = std::env::var("SENTRY_DSN")
+            .ok()
+            .filter(|dsn| !dsn.is_empty())?;
+        let guard = sentry::init((
+            dsn,
+            sentry::ClientOptions {
+                release: Some(format!("{service_name}@{}", env!("CARGO_PKG_VERSION")).into()),
+                environment: std::env::var("SENTRY_ENVIRONMENT").ok().map(Into::into),
+                attach_stacktrace: true,
+                ..Default::default()
+            },
+        ));
+        sentry::configure_scope(|scope| scope.set_tag("service", service_name));
+        Some(guard)
+    }
+
+    /// Layer forwarding events to the reporting backend; idle until it is initialized.
+    pub(crate) fn layer<S>() -> impl Layer<S>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        sentry_tracing::layer().enable_span_attributes()
+    }
+}
+
+#[cfg(not(feature = "sentry"))]
+mod enabled {
+    use tracing::Subscriber;
+    use tracing_subscriber::Layer;
+    use tracing_subscriber::layer::Identity;
+
+    /// Placeholder guard; error reporting needs the `sentry` feature.
+    #[derive(Debug)]
+    pub struct ErrorReportingGuard;
+
+    /// Does nothing without the `sentry` feature.
+    pub fn init_error_reporting(_service_name: &str) -> Option<ErrorReportingGuard> {
+        if std::env::var_os("SENTRY_DSN").is_some() {
+            eprintln!("SENTRY_DSN is set but this build lacks the `sentry` feature");
+        }
+        None
+    }
+
+    pub(crate) fn layer<S>() -> impl Layer<S>
+    where
+        S: Subscriber,
+    {
+        Identity::new()
+    }
+}
+
+pub(crate) use self::enabled::layer;
+pub use self::enabled::{ErrorReportingGuard, init_error_reporting};
@@ -0,0 +1,131 @@
+//! Periodic self-checks.
+//!
+//! Each service spawns a [`Heartbeat`] with a function that checks its own critical
+//! invariants (dependencies reachable, work making progress) and returns whatever is
+//! wrong. The outcome is exported as the `service_healthy` gauge, logged when it
+//! changes, and kept as a [`HealthReport`] for health endpoints to serve.
+
+use std::future::Future;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use metrics::gauge;
+use serde::Serialize;
+use tokio::time::MissedTickBehavior;
+
+use crate::service;
+
+/// How often services run their self-checks by default.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// A self-check that did not pass.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CheckFailure {
+    /// Name of the invariant, e.g. `relay_connected`.
+    pub check: &'static str,
+    /// What was wrong, for operators.
+    pub reason: String,
+}
+
+impl CheckFailure {
+    pub fn new(check: &'static str, reason: impl Into<String>) -> Self {
+        Self {
+            check,
+            reason: reason.into(),
+        }
+    }
+}
+
+/// Outcome of the latest round of self-checks.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HealthReport {
+    pub healthy: bool,
+    pub failures: Vec<CheckFailure>,
+    pub checked_at: DateTime<Utc>,
+}
+
+/// Handle to the latest self-check results; clones share them.
+#[derive(Debug, Clone, Default)]
+pub struct Heartbeat {
+    report: Arc<RwLock<Option<HealthReport>>>,
+}
+
+impl Heartbeat {
+    /// Run `check` every `interval` on the Tokio runtime, recording each result.
+    pub fn spawn<F, Fut>(interval: Duration, mut check: F) -> Self
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = Vec<CheckFailure>> + Send,
+    {
+        let heartbeat = Self::default();
+        let recorder = heartbeat.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                recorder.record(check().await);
+            }
+        });
+        heartbeat
+    }
+
+    /// Latest results, or `None` before the first round has finished.
+    pub fn report(&self) -> Option<HealthReport> {
+        self.report
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Record a round of self-checks, logging when the set of failures changes.
+    pub fn record(&self, failures: Vec<CheckFailure>) {
+        let healthy = failures.is_empty();
+        gauge!(service::HEALTHY).set(if healthy { 1.0 } else { 0.0 });
+
+        let mut report = self.report.write().unwrap_or_else(|e| e.into_inner());
+        let changed = report
+            .as_ref()
+            .is_none_or(|previous| previous.failures != failures);
+        if changed {
+            if healthy {
+                tracing::info!("Self-checks passing");
+            }
+            for failure in &failures {
+                tracing::warn!(
+                    check = failure.check,
+                    reason = %failure.reason,
+                    "Self-check failing"
+                );
+            }
+        }
+
+        *report = Some(HealthReport {
+            healthy,
+            failures,
+            checked_at: Utc::now(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_follows_latest_round() {
+        let heartbeat = Heartbeat::default();
+        assert!(heartbeat.report().is_none());
+
+        heartbeat.record(vec![CheckFailure::new("clickhouse", "timed out")]);
+        let report = heartbeat.clone().report().unwrap();
+        assert!(!report.healthy);
+        assert_eq!(report.failures[0].check, "clickhouse");
+
+        heartbeat.record(Vec::new());
+        let report = heartbeat.report().unwrap();
+        assert!(report.healthy);
+        assert!(report.failures.is_empty());
+    }
+}
@@ -1,34 +1,157 @@
 //! Observability setup for Funnel services.
 //!
-//! Provides tracing subscriber configuration and Prometheus metrics export.
+//! Provides tracing subscriber configuration, OpenTelemetry trace export, error
+//! reporting, Prometheus metrics export (scraped, or pushed by batch jobs), self-check
+//! heartbeats, and the audit log.
 
-use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
-use tracing_subscriber::{EnvFilter, layer::SubscriberExt, util::SubscriberInitExt};
+pub mod audit;
+mod error_reporting;
+pub mod heartbeat;
+mod log_filter;
+mod otel;
+pub mod push;
+pub mod throttle;
+
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use tracing_subscriber::{
+    EnvFilter, Registry, layer::SubscriberExt, reload, util::SubscriberInitExt,
+};
+
+pub use self::error_reporting::{ErrorReportingGuard, init_error_reporting};
+pub use self::log_filter::{LogFilterError, log_filter, set_log_filter};
+pub use self::otel::{
+    OtelGuard, http_request_span, init_tracing_otel, query_span, trace_context_headers,
+};
+
+// Used by `warn_throttled!` so callers don't need their own `tracing` import
+#[doc(hidden)]
+pub use tracing as __tracing;
+
+/// Filter from `RUST_LOG`, defaulting to `info`, changeable with [`set_log_filter`].
+fn env_filter() -> reload::Layer<EnvFilter, Registry> {
+    log_filter::reloadable(
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+    )
+}
 
 /// Initialize tracing with JSON output and env filter.
 pub fn init_tracing() {
-    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
-
     tracing_subscriber::registry()
-        .with(filter)
+        .with(env_filter())
         .with(tracing_subscriber::fmt::layer().json())
+        .with(error_reporting::layer())
         .init();
 }
 
 /// Initialize tracing with human-readable output (for development).
 pub fn init_tracing_dev() {
-    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
-
     tracing_subscriber::registry()
-        .with(filter)
+        .with(env_filter())
         .with(tracing_subscriber::fmt::layer())
+        .with(error_reporting::layer())
         .init();
 }
 
+/// Buckets for request and query latencies, in seconds.
+pub const LATENCY_BUCKETS: &[f64] = &[
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// Buckets for ClickHouse batch write latency, in seconds.
+pub const WRITE_LATENCY_BUCKETS: &[f64] = &[
+    0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0,
+];
+
+/// Buckets for batch sizes, in events.
+pub const BATCH_SIZE_BUCKETS: &[f64] = &[
+    1.0, 10.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0, 5000.0, 10000.0,
+];
+
+/// Buckets for per-event pipeline latency, in seconds.
+pub const EVENT_LATENCY_BUCKETS: &[f64] = &[
+    0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 300.0, 900.0, 3600.0,
+];
+
+/// Prometheus exporter configuration.
+///
+/// The default gives each histogram family in this crate buckets sized for what it
+/// measures; metrics without configured buckets are exported as summaries.
+#[derive(Debug, Clone)]
+pub struct PrometheusConfig {
+    /// Histogram buckets by full metric name.
+    pub buckets: Vec<(String, Vec<f64>)>,
+    /// Labels added to every metric.
+    pub global_labels: Vec<(String, String)>,
+}
+
+impl Default for PrometheusConfig {
+    fn default() -> Self {
+        Self {
+            buckets: Vec::new(),
+            global_labels: Vec::new(),
+        }
+        .with_buckets(api::REQUEST_DURATION, LATENCY_BUCKETS)
+        .with_buckets(api::QUERY_DURATION, LATENCY_BUCKETS)
+        .with_buckets(ingestion::WRITE_LATENCY, WRITE_LATENCY_BUCKETS)
+        .with_buckets(ingestion::BATCH_SIZE, BATCH_SIZE_BUCKETS)
+        .with_buckets(ingestion::EVENT_LATENCY, EVENT_LATENCY_BUCKETS)
+        .with_buckets(aggregator::RUN_DURATION, WRITE_LATENCY_BUCKETS)
+    }
+}
+
+impl PrometheusConfig {
+    /// Default configuration plus global labels from `METRICS_GLOBAL_LABELS`
+    /// (`key=value` pairs separated by commas, e.g. `environment=prod,instance=api-1`).
+    pub fn from_env() -> Self {
+        let labels = std::env::var("METRICS_GLOBAL_LABELS").unwrap_or_default();
+        parse_labels(&labels)
+            .into_iter()
+            .fold(Self::default(), |config, (key, value)| {
+                config.with_global_label(key, value)
+            })
+    }
+
+    /// Use `buckets` for the histogram named `metric`, replacing any set before.
+    pub fn with_buckets(mut self, metric: impl Into<String>, buckets: &[f64]) -> Self {
+        let metric = metric.into();
+        self.buckets.retain(|(name, _)| *name != metric);
+        self.buckets.push((metric, buckets.to_vec()));
+        self
+    }
+
+    /// Add `key=value` to every metric, replacing any value set before.
+    pub fn with_global_label(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        let key = key.into();
+        self.global_labels.retain(|(name, _)| *name != key);
+        self.global_labels.push((key, value.into()));
+        self
+    }
+}
+
+/// Parse comma-separated `key=value` pairs, skipping malformed entries.
+fn parse_labels(s: &str) -> Vec<(String, String)> {
+    s.split(',')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, value)| (key.trim(), value.trim()))
+        .filter(|(key, _)| !key.is_empty())
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
 /// Initialize Prometheus metrics exporter.
 /// Returns a handle that can render metrics in Prometheus format.
-pub fn init_metrics() -> PrometheusHandle {
-    PrometheusBuilder::new()
+pub fn init_metrics(config: PrometheusConfig) -> PrometheusHandle {
+    let mut builder = PrometheusBuilder::new();
+    for (metric, buckets) in &config.buckets {
+        builder = builder
+            .set_buckets_for_metric(Matcher::Full(metric.clone()), buckets)
+            .expect("histogram buckets must not be empty");
+    }
+    for (key, value) in config.global_labels {
+        builder = builder.add_global_label(key, value);
+    }
+
+    builder
         .install_recorder()
         .expect("failed to install Prometheus recorder")
 }
@@ -46,7 +169,46 @@ pub mod ingestion {
     pub const EVENTS_WRITTEN: &str = "ingestion_events_written_total";
     pub const BATCH_SIZE: &str = "ingestion_batch_size";
     pub const WRITE_LATENCY: &str = "ingestion_clickhouse_write_latency_seconds";
+    /// Time since the newest batch's oldest event was created, which includes any
+    /// backdating by its author; see [`EVENT_LATENCY`] for the pipeline's own delay.
     pub const LAG: &str = "ingestion_lag_seconds";
+    /// Per-event latency by `stage`: `created_to_received`, `received_to_inserted`,
+    /// or `inserted_to_queryable` (sampled).
+    pub const EVENT_LATENCY: &str = "ingestion_event_latency_seconds";
+    /// Times live mode restarted its relay connection after the stream ended.
+    pub const RECONNECTS: &str = "ingestion_reconnects_total";
+    /// Events each relay sent live mode, duplicates included, by `relay`.
+    pub const RELAY_EVENTS_RECEIVED: &str = "ingestion_relay_events_received_total";
+    /// Time since the oldest event of the newest batch was created, by the `relay`
+    /// that delivered it.
+    pub const RELAY_LAG: &str = "ingestion_relay_lag_seconds";
+    /// Connections live mode made to each relay, by `relay`; each after the first is
+    /// a reconnect. Sampled every minute.
+    pub const RELAY_CONNECTIONS: &str = "ingestion_relay_connections_total";
+    /// Time since each relay last sent an event (or since the stream started, if it
+    /// hasn't), by `relay`. Sampled every minute.
+    pub const RELAY_LAST_EVENT_AGE: &str = "ingestion_relay_last_event_age_seconds";
+    /// ClickHouse inserts retried after a transient failure.
+    pub const INSERT_RETRIES: &str = "ingestion_insert_retries_total";
+    /// Events received but not stored, by error `code`; see `events_rejected`.
+    pub const EVENTS_REJECTED: &str = "ingestion_events_rejected_total";
+    /// Events written to the disk spill queue because ClickHouse was unavailable.
+    pub const EVENTS_SPILLED: &str = "ingestion_events_spilled_total";
+    /// Batches waiting in the disk spill queue.
+    pub const SPILL_SEGMENTS: &str = "ingestion_spill_segments";
+    /// Relay notifications received by live mode but not yet taken by the writer.
+    pub const QUEUE_DEPTH: &str = "ingestion_queue_depth";
+    /// Live batches being inserted right now.
+    pub const FLUSHES_IN_FLIGHT: &str = "ingestion_flushes_in_flight";
+    /// Most live batches inserted at once (`INGEST_FLUSH_WORKERS`).
+    pub const FLUSH_WORKERS: &str = "ingestion_flush_workers";
+    /// Live events checked against the recently seen IDs, by `result`: `hit` (a
+    /// repeat, dropped) or `miss`.
+    pub const DEDUP_LOOKUPS: &str = "ingestion_dedup_lookups_total";
+    /// Event IDs live mode remembers, up to `INGEST_DEDUP_CAPACITY`.
+    pub const DEDUP_ENTRIES: &str = "ingestion_dedup_entries";
+    /// Events tombstoned by NIP-09 deletion requests live mode received.
+    pub const EVENTS_DELETED: &str = "ingestion_events_deleted_total";
 }
 
 /// Metric names for the API service.
@@ -54,4 +216,84 @@ pub mod api {
     pub const REQUESTS: &str = "api_requests_total";
     pub const REQUEST_DURATION: &str = "api_request_duration_seconds";
     pub const QUERY_DURATION: &str = "api_clickhouse_query_duration_seconds";
+    pub const CIRCUIT_BREAKER_OPEN: &str = "api_circuit_breaker_open";
+    pub const CIRCUIT_BREAKER_REJECTIONS: &str = "api_circuit_breaker_rejections_total";
+    pub const REQUEST_TIMEOUTS: &str = "api_request_timeouts_total";
+    pub const REQUESTS_REJECTED: &str = "api_requests_rejected_total";
+    pub const CACHE_REQUESTS: &str = "api_cache_requests_total";
+    pub const CACHE_ENTRIES: &str = "api_cache_entries";
+    pub const CACHE_ERRORS: &str = "api_cache_errors_total";
+    pub const QUOTA_EXCEEDED: &str = "api_quota_exceeded_total";
+}
+
+/// Metric names for the aggregator service.
+pub mod aggregator {
+    pub const RUNS: &str = "aggregator_runs_total";
+    pub const RUN_DURATION: &str = "aggregator_run_duration_seconds";
+    pub const TRENDING_VIDEOS: &str = "aggregator_trending_videos";
+    pub const TRUSTED_PUBKEYS: &str = "aggregator_trusted_pubkeys";
+    pub const DUPLICATE_VIDEOS: &str = "aggregator_duplicate_videos";
+    pub const MEDIA_CHECKED: &str = "aggregator_media_checked";
+    pub const MEDIA_CHECKS: &str = "aggregator_media_checks_total";
+    pub const MEDIA_VERIFIED: &str = "aggregator_media_verified";
+    pub const MEDIA_VERIFICATIONS: &str = "aggregator_media_verifications_total";
+    pub const PROFILES_FETCHED: &str = "aggregator_profiles_fetched";
+    pub const PROFILE_EVENTS: &str = "aggregator_profile_events_total";
+    pub const PUBLISHED_VIDEOS: &str = "aggregator_published_videos";
+    pub const DVM_JOBS: &str = "aggregator_dvm_jobs_total";
+}
+
+/// Metric names shared by every service.
+pub mod service {
+    pub const HEALTHY: &str = "service_healthy";
+}
+
+/// Metric names for batch jobs, such as backfills, reported through [`push`](crate::push).
+pub mod batch {
+    pub const ROWS_INSERTED: &str = "batch_rows_inserted_total";
+    pub const ERRORS: &str = "batch_errors_total";
+    pub const DURATION: &str = "batch_duration_seconds";
+    pub const LAST_SUCCESS: &str = "batch_last_success_timestamp_seconds";
+}
+
+/// Metric names for ClickHouse queries, shared by both services.
+pub mod clickhouse {
+    pub const SLOW_QUERIES: &str = "slow_queries_total";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_labels_skips_malformed_pairs() {
+        assert_eq!(
+            parse_labels("environment=prod, instance = api-1,broken,=x"),
+            [
+                ("environment".to_string(), "prod".to_string()),
+                ("instance".to_string(), "api-1".to_string()),
+            ]
+        );
+        assert!(parse_labels("").is_empty());
+    }
+
+    #[test]
+    fn later_settings_replace_earlier_ones() {
+        let config = PrometheusConfig::default()
+            .with_buckets(api::QUERY_DURATION, &[1.0])
+            .with_global_label("service", "a")
+            .with_global_label("service", "b");
+
+        let buckets: Vec<_> = config
+            .buckets
+            .iter()
+            .filter(|(name, _)| name == api::QUERY_DURATION)
+            .collect();
+        assert_eq!(buckets.len(), 1);
+        assert_eq!(buckets[0].1, [1.0]);
+        assert_eq!(
+            config.global_labels,
+            [("service".to_string(), "b".to_string())]
+        );
+    }
 }
@@ -0,0 +1,69 @@
+//! Runtime changes to the log filter.
+//!
+//! The `init_tracing*` functions install their `RUST_LOG` filter behind a reload
+//! layer and keep its handle here, so the filter can be replaced while the process
+//! runs (e.g. from an admin endpoint) instead of restarting with a new `RUST_LOG`.
+
+use std::sync::OnceLock;
+
+use thiserror::Error;
+use tracing_subscriber::filter::ParseError;
+use tracing_subscriber::{EnvFilter, Registry, reload};
+
+static HANDLE: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// Errors from changing the log filter.
+#[derive(Debug, Error)]
+pub enum LogFilterError {
+    #[error("invalid filter: {0}")]
+    Invalid(#[from] ParseError),
+
+    #[error("tracing was not initialized by funnel-observability")]
+    NotInitialized,
+
+    #[error("failed to reload filter: {0}")]
+    Reload(#[from] reload::Error),
+}
+
+/// Wrap `filter` in a reload layer and keep its handle for [`set_log_filter`].
+pub(crate) fn reloadable(filter: EnvFilter) -> reload::Layer<EnvFilter, Registry> {
+    let (layer, handle) = reload::Layer::new(filter);
+    // Only one subscriber is installed per process; a second init keeps the first handle
+    let _ = HANDLE.set(handle);
+    layer
+}
+
+/// Current filter directives, or `None` if tracing wasn't initialized here.
+pub fn log_filter() -> Option<String> {
+    HANDLE.get()?.with_current(|filter| filter.to_string()).ok()
+}
+
+/// Replace the log filter with `directives`, in `RUST_LOG` syntax.
+pub fn set_log_filter(directives: &str) -> Result<(), LogFilterError> {
+    let filter = EnvFilter::try_new(directives)?;
+    let handle = HANDLE.get().ok_or(LogFilterError::NotInitialized)?;
+    handle.reload(filter)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+
+    #[test]
+    fn reload_replaces_filter() {
+        let layer = reloadable(EnvFilter::new("info"));
+        let _guard = tracing::subscriber::set_default(Registry::default().with(layer));
+
+        assert!(matches!(
+            set_log_filter("funnel=loud"),
+            Err(LogFilterError::Invalid(_))
+        ));
+        assert_eq!(log_filter().as_deref(), Some("info"));
+
+        set_log_filter("funnel_api=debug").unwrap();
+        assert_eq!(log_filter().as_deref(), Some("funnel_api=debug"));
+    }
+}
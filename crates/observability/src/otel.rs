@@ -0,0 +1,112 @@
+//! OpenTelemetry trace export over OTLP.
+//!
+//! Spans recorded through `tracing` are exported to an OTLP collector (Jaeger, Tempo,
+//! ...) alongside the usual JSON log output. The helpers here name spans after the
+//! OpenTelemetry semantic conventions, so an API request and the ClickHouse queries
+//! it runs show up as one trace.
+//!
+//! Trace context crosses process boundaries as W3C `traceparent`/`tracestate`
+//! headers: incoming requests that carry one join the caller's trace, and outbound
+//! requests can carry the current span's context onward.
+
+use http::{HeaderMap, Request};
+use opentelemetry::trace::{TraceError, TracerProvider as _};
+use opentelemetry::{KeyValue, global};
+use opentelemetry_http::{HeaderExtractor, HeaderInjector};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::runtime;
+use opentelemetry_sdk::trace::TracerProvider;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+
+use crate::{env_filter, error_reporting};
+
+/// Flushes buffered spans to the collector when dropped.
+///
+/// Keep it alive for the life of the process; spans still queued when it drops are
+/// exported before shutdown.
+#[must_use = "dropping the guard stops trace export"]
+pub struct OtelGuard {
+    provider: TracerProvider,
+}
+
+impl Drop for OtelGuard {
+    fn drop(&mut self) {
+        if let Err(e) = self.provider.shutdown() {
+            eprintln!("Failed to flush traces: {e}");
+        }
+    }
+}
+
+/// Initialize tracing with JSON output and OTLP trace export to `endpoint`
+/// (e.g. `http://tempo:4317`).
+///
+/// Must be called from within a Tokio runtime, which runs the batch exporter.
+pub fn init_tracing_otel(endpoint: &str, service_name: &str) -> Result<OtelGuard, TraceError> {
+    let exporter = SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(endpoint)
+        .build()?;
+    let provider = TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_resource(Resource::new([KeyValue::new(
+            "service.name",
+            service_name.to_string(),
+        )]))
+        .build();
+    let tracer = provider.tracer(service_name.to_string());
+    global::set_tracer_provider(provider.clone());
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    tracing_subscriber::registry()
+        .with(env_filter())
+        .with(tracing_subscriber::fmt::layer().json())
+        .with(tracing_opentelemetry::layer().with_tracer(tracer))
+        .with(error_reporting::layer())
+        .init();
+
+    Ok(OtelGuard { provider })
+}
+
+/// Span for an incoming HTTP request, joined to the caller's trace when the request
+/// carries a `traceparent` header.
+pub fn http_request_span<B>(request: &Request<B>) -> tracing::Span {
+    let span = tracing::info_span!(
+        "http.request",
+        otel.kind = "server",
+        http.request.method = %request.method(),
+        url.path = request.uri().path(),
+        request_id = request
+            .headers()
+            .get("x-request-id")
+            .and_then(|v| v.to_str().ok()),
+    );
+    let parent =
+        global::get_text_map_propagator(|p| p.extract(&HeaderExtractor(request.headers())));
+    span.set_parent(parent);
+    span
+}
+
+/// Headers carrying the current span's trace context, for outbound requests.
+///
+/// Empty when trace export isn't enabled.
+pub fn trace_context_headers() -> HeaderMap {
+    let context = tracing::Span::current().context();
+    let mut headers = HeaderMap::new();
+    global::get_text_map_propagator(|p| {
+        p.inject_context(&context, &mut HeaderInjector(&mut headers))
+    });
+    headers
+}
+
+/// Span for a ClickHouse query, named after the query method.
+pub fn query_span(operation: &'static str) -> tracing::Span {
+    tracing::info_span!(
+        "clickhouse.query",
+        otel.kind = "client",
+        db.system = "clickhouse",
+        db.operation = operation,
+    )
+}
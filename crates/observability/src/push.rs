@@ -0,0 +1,93 @@
+//! Push-gateway export for batch jobs.
+//!
+//! Backfills and other one-off runs exit before Prometheus gets a chance to scrape
+//! them. With `PUSHGATEWAY_URL` set, a job pushes everything it recorded to a
+//! Prometheus Pushgateway when it finishes, under `/metrics/job/<job>`, replacing
+//! that job's previous push. The [`batch`](crate::batch) metrics describe the run.
+
+use std::time::Duration;
+
+use metrics_exporter_prometheus::PrometheusHandle;
+use reqwest::Url;
+use reqwest::header::CONTENT_TYPE;
+use thiserror::Error;
+
+/// Upper bound on a push, so a dead gateway can't hold up a job's exit.
+const PUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Errors from pushing metrics.
+#[derive(Debug, Error)]
+pub enum PushError {
+    #[error("invalid push gateway URL: {0}")]
+    InvalidUrl(String),
+
+    #[error("failed to push metrics: {0}")]
+    Http(#[from] reqwest::Error),
+}
+
+/// Where a batch job pushes its metrics.
+#[derive(Debug, Clone)]
+pub struct PushGateway {
+    client: reqwest::Client,
+    url: Url,
+}
+
+impl PushGateway {
+    /// Push gateway at `base_url` (e.g. `http://pushgateway:9091`), grouping this
+    /// process's metrics under `job`.
+    pub fn new(base_url: &str, job: &str) -> Result<Self, PushError> {
+        let mut url =
+            Url::parse(base_url).map_err(|e| PushError::InvalidUrl(format!("{base_url}: {e}")))?;
+        url.path_segments_mut()
+            .map_err(|()| PushError::InvalidUrl(base_url.to_string()))?
+            .pop_if_empty()
+            .extend(["metrics", "job", job]);
+
+        let client = reqwest::Client::builder().timeout(PUSH_TIMEOUT).build()?;
+        Ok(Self { client, url })
+    }
+
+    /// Push gateway from `PUSHGATEWAY_URL`, or `None` when it is unset.
+    pub fn from_env(job: &str) -> Option<Result<Self, PushError>> {
+        let url = std::env::var("PUSHGATEWAY_URL")
+            .ok()
+            .filter(|url| !url.is_empty())?;
+        Some(Self::new(&url, job))
+    }
+
+    /// URL metrics are pushed to.
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// Replace this job's metrics on the gateway with everything in `handle`.
+    pub async fn push(&self, handle: &PrometheusHandle) -> Result<(), PushError> {
+        self.client
+            .put(self.url.clone())
+            .header(CONTENT_TYPE, "text/plain; version=0.0.4")
+            .body(handle.render())
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn url_groups_by_job() {
+        let gateway = PushGateway::new("http://pushgateway:9091/", "funnel-backfill").unwrap();
+        assert_eq!(
+            gateway.url().as_str(),
+            "http://pushgateway:9091/metrics/job/funnel-backfill"
+        );
+
+        let gateway = PushGateway::new("http://gw/prefix", "a b").unwrap();
+        assert_eq!(gateway.url().as_str(), "http://gw/prefix/metrics/job/a%20b");
+
+        assert!(PushGateway::new("not a url", "job").is_err());
+    }
+}
@@ -0,0 +1,124 @@
+//! Rate limiting for repeated log lines.
+//!
+//! [`warn_throttled!`](crate::warn_throttled) logs a warning at most N times per
+//! minute from each call site. Once the minute is up, the next warning from that
+//! site is preceded by a summary of how many were dropped, so a misbehaving relay
+//! shows up as a handful of lines and a count rather than gigabytes of repeats.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Warnings logged per call site per window by default.
+pub const DEFAULT_LIMIT: u32 = 10;
+
+/// Length of a throttling window.
+pub const WINDOW: Duration = Duration::from_secs(60);
+
+/// Counter deciding which occurrences of a repeated log line get through.
+#[derive(Debug)]
+pub struct Throttle {
+    limit: u32,
+    window: Duration,
+    state: Mutex<Option<Window>>,
+}
+
+#[derive(Debug)]
+struct Window {
+    start: Instant,
+    logged: u32,
+    suppressed: u64,
+}
+
+impl Throttle {
+    pub const fn new(limit: u32, window: Duration) -> Self {
+        Self {
+            limit,
+            window,
+            state: Mutex::new(None),
+        }
+    }
+
+    /// Record an occurrence.
+    ///
+    /// Returns `None` if it should be dropped. Otherwise returns how many were
+    /// dropped in the window that just ended (usually zero), to report before it.
+    pub fn admit(&self) -> Option<u64> {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        match state.as_mut() {
+            Some(window) if now.duration_since(window.start) < self.window => {
+                if window.logged < self.limit {
+                    window.logged += 1;
+                    Some(0)
+                } else {
+                    window.suppressed += 1;
+                    None
+                }
+            }
+            _ => {
+                let previous = state.replace(Window {
+                    start: now,
+                    logged: 1,
+                    suppressed: 0,
+                });
+                Some(previous.map_or(0, |window| window.suppressed))
+            }
+        }
+    }
+}
+
+/// Log a warning like `tracing::warn!`, but at most
+/// [`DEFAULT_LIMIT`](crate::throttle::DEFAULT_LIMIT) times a minute from this call
+/// site. Pass `limit: N,` first to change the cap.
+///
+/// ```ignore
+/// warn_throttled!(error = %e, "Failed to parse event");
+/// warn_throttled!(limit: 1, skipped = n, "Channel lagged");
+/// ```
+#[macro_export]
+macro_rules! warn_throttled {
+    (limit: $limit:expr, $($arg:tt)+) => {{
+        static THROTTLE: $crate::throttle::Throttle =
+            $crate::throttle::Throttle::new($limit, $crate::throttle::WINDOW);
+        if let Some(suppressed) = THROTTLE.admit() {
+            if suppressed > 0 {
+                $crate::__tracing::warn!(
+                    suppressed,
+                    "Suppressed repeated warnings from {}:{}",
+                    file!(),
+                    line!()
+                );
+            }
+            $crate::__tracing::warn!($($arg)+);
+        }
+    }};
+    ($($arg:tt)+) => {
+        $crate::warn_throttled!(limit: $crate::throttle::DEFAULT_LIMIT, $($arg)+)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn admits_up_to_limit_then_reports_suppressed() {
+        let throttle = Throttle::new(2, Duration::from_millis(50));
+        assert_eq!(throttle.admit(), Some(0));
+        assert_eq!(throttle.admit(), Some(0));
+        assert_eq!(throttle.admit(), None);
+        assert_eq!(throttle.admit(), None);
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(throttle.admit(), Some(2));
+        assert_eq!(throttle.admit(), Some(0));
+    }
+
+    #[test]
+    fn macro_compiles_with_and_without_limit() {
+        for n in 0..3 {
+            warn_throttled!(n, "Repeated warning");
+            warn_throttled!(limit: 1, n, "Rarer warning");
+        }
+    }
+}
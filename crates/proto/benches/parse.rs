@@ -0,0 +1,58 @@
+//! Compares owned and borrowed event parsing.
+//!
+//! Run with `cargo bench -p funnel-proto`. Each case parses the same batch of event
+//! JSON lines and reports the time per event; the "filter" cases keep only video
+//! events, as ingestion does when it drops unwanted kinds.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use funnel_proto::{ParsedEvent, ParsedEventRef};
+
+const EVENTS: usize = 20_000;
+const ROUNDS: u32 = 5;
+
+fn lines() -> Vec<String> {
+    (0..EVENTS)
+        .map(|i| {
+            let kind = if i.is_multiple_of(10) { 34235 } else { 7 };
+            format!(
+                r#"{{"id":"{i:064x}","pubkey":"6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93","created_at":{},"kind":{kind},"tags":[["d","video-{i}"],["title","Video number {i}"],["t","nostr"],["t","bitcoin"],["e","{i:064x}","wss://relay.example.com","root"]],"content":"Check out my video! It is number {i}.","sig":"908a15e46fb4d8675bab026fc230a0e3542bfade63da02d542fb78b2a8513fcd0092619a2c8c1221e581946e0191f2af505dfdf8657a414dbca329186f009262"}}"#,
+                1_700_000_000 + i
+            )
+        })
+        .collect()
+}
+
+/// Best time of `ROUNDS` runs of `parse` over `lines`.
+fn bench(name: &str, lines: &[String], parse: impl Fn(&str) -> usize) {
+    let mut best = Duration::MAX;
+    for _ in 0..ROUNDS {
+        let start = Instant::now();
+        let kept: usize = lines.iter().map(|line| parse(black_box(line))).sum();
+        best = best.min(start.elapsed());
+        black_box(kept);
+    }
+    let per_event = best.as_nanos() as f64 / lines.len() as f64;
+    println!("{name:<24} {per_event:>8.0} ns/event");
+}
+
+fn main() {
+    let lines = lines();
+
+    bench("owned", &lines, |line| {
+        ParsedEvent::from_json(line).map_or(0, |event| event.tags.len())
+    });
+    bench("borrowed", &lines, |line| {
+        ParsedEventRef::from_json(line).map_or(0, |event| event.tags.len())
+    });
+    bench("owned, filter", &lines, |line| {
+        ParsedEvent::from_json(line).map_or(0, |event| usize::from(event.is_video()))
+    });
+    bench("borrowed, filter", &lines, |line| {
+        ParsedEventRef::from_json(line)
+            .ok()
+            .filter(ParsedEventRef::is_video)
+            .map_or(0, |event| usize::from(!event.into_owned().id.is_empty()))
+    });
+}
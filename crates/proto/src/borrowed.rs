@@ -0,0 +1,143 @@
+//! Borrowed event parsing for high-throughput paths.
+//!
+//! [`ParsedEvent::from_json`] goes through `nostr::Event` and copies every field
+//! into an owned `String`. [`ParsedEventRef`] deserializes straight from the JSON
+//! line instead, borrowing each string that has no escapes, so a consumer that
+//! looks at an event and drops it (filtering by kind, counting) allocates little.
+//! [`ParsedEventRef::into_owned`] converts it when the event must be kept.
+
+use std::borrow::Cow;
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::{KIND_VIDEO, KIND_VIDEO_SHORT, ParseError, ParsedEvent, is_hex_id};
+
+/// A Nostr event borrowing its strings from the JSON it was parsed from.
+///
+/// Only the shape of the ID, pubkey, and signature is checked; like
+/// [`ParsedEvent::from_json`], parsing doesn't verify the signature.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ParsedEventRef<'a> {
+    #[serde(borrow)]
+    pub id: Cow<'a, str>,
+    #[serde(borrow)]
+    pub pubkey: Cow<'a, str>,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub created_at: DateTime<Utc>,
+    pub kind: u16,
+    #[serde(borrow)]
+    pub content: Cow<'a, str>,
+    #[serde(borrow)]
+    pub sig: Cow<'a, str>,
+    #[serde(borrow)]
+    pub tags: Vec<Vec<Cow<'a, str>>>,
+}
+
+impl<'a> ParsedEventRef<'a> {
+    /// Parse from a JSON string, borrowing from it.
+    pub fn from_json(json: &'a str) -> Result<Self, ParseError> {
+        let event: Self = serde_json::from_str(json)?;
+        if !is_hex_id(&event.id) {
+            return Err(ParseError::InvalidEvent(
+                "id is not 32-byte hex".to_string(),
+            ));
+        }
+        if !is_hex_id(&event.pubkey) {
+            return Err(ParseError::InvalidEvent(
+                "pubkey is not 32-byte hex".to_string(),
+            ));
+        }
+        if event.sig.len() != 128 || !event.sig.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(ParseError::InvalidEvent(
+                "sig is not 64-byte hex".to_string(),
+            ));
+        }
+        Ok(event)
+    }
+
+    /// Check if this is a video event.
+    pub fn is_video(&self) -> bool {
+        self.kind == KIND_VIDEO || self.kind == KIND_VIDEO_SHORT
+    }
+
+    /// Extract the first tag value for a given name.
+    pub fn get_tag(&self, name: &str) -> Option<&str> {
+        self.tags
+            .iter()
+            .find(|t| t.first().map(|s| s.as_ref()) == Some(name))
+            .and_then(|t| t.get(1).map(|s| s.as_ref()))
+    }
+
+    /// Copy into an owned [`ParsedEvent`], with hex fields lowercased as
+    /// [`ParsedEvent::from_event`] writes them.
+    pub fn into_owned(self) -> ParsedEvent {
+        ParsedEvent {
+            id: self.id.to_ascii_lowercase(),
+            pubkey: self.pubkey.to_ascii_lowercase(),
+            created_at: self.created_at,
+            kind: self.kind,
+            content: self.content.into_owned(),
+            sig: self.sig.to_ascii_lowercase(),
+            tags: self
+                .tags
+                .into_iter()
+                .map(|t| t.into_iter().map(Cow::into_owned).collect())
+                .collect(),
+            received_at: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EVENT_JSON: &str = r#"{
+        "id": "a376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65",
+        "pubkey": "6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93",
+        "created_at": 1700000000,
+        "kind": 34235,
+        "tags": [["d", "my-video-id"], ["title", "Line one\nline two"]],
+        "content": "Check out my video!",
+        "sig": "908a15e46fb4d8675bab026fc230a0e3542bfade63da02d542fb78b2a8513fcd0092619a2c8c1221e581946e0191f2af505dfdf8657a414dbca329186f009262"
+    }"#;
+
+    #[test]
+    fn from_json_borrows_unescaped_strings() {
+        let event = ParsedEventRef::from_json(EVENT_JSON).unwrap();
+
+        assert!(matches!(event.id, Cow::Borrowed(_)));
+        assert!(matches!(event.content, Cow::Borrowed(_)));
+        assert!(matches!(event.tags[1][1], Cow::Owned(_)));
+        assert_eq!(event.get_tag("title"), Some("Line one\nline two"));
+        assert!(event.is_video());
+    }
+
+    #[test]
+    fn into_owned_matches_owned_parsing() {
+        let borrowed = ParsedEventRef::from_json(EVENT_JSON).unwrap().into_owned();
+        let owned = ParsedEvent::from_json(EVENT_JSON).unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&borrowed).unwrap(),
+            serde_json::to_value(&owned).unwrap()
+        );
+    }
+
+    #[test]
+    fn from_json_rejects_malformed_fields() {
+        for (field, value) in [("id", "abc"), ("pubkey", "not-hex"), ("sig", "908a15e4")] {
+            let mut event: serde_json::Value = serde_json::from_str(EVENT_JSON).unwrap();
+            event[field] = value.into();
+            let json = event.to_string();
+
+            let err = ParsedEventRef::from_json(&json).unwrap_err();
+            assert!(matches!(err, ParseError::InvalidEvent(_)), "{field}: {err}");
+        }
+        assert!(matches!(
+            ParsedEventRef::from_json("not json"),
+            Err(ParseError::InvalidJson(_))
+        ));
+    }
+}
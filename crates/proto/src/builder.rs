@@ -0,0 +1,242 @@
+//! Building signed video events.
+//!
+//! [`VideoEventBuilder`] writes a [`VideoMeta`] out as the tags
+//! [`VideoMeta::from_event`] reads back, so tests and publishers don't assemble
+//! tag arrays by hand.
+
+use chrono::{DateTime, Utc};
+use nostr::{EventBuilder, Keys};
+use thiserror::Error;
+
+use crate::{
+    ErrorCode, Event, KIND_VIDEO, KIND_VIDEO_REGULAR, KIND_VIDEO_SHORT, KIND_VIDEO_SHORT_REGULAR,
+    Kind, LANGUAGE_NAMESPACE, Tag, Timestamp, VideoMeta,
+};
+
+/// Errors from building a video event.
+#[derive(Debug, Error)]
+pub enum BuildError {
+    #[error("kind {0} is not a video kind")]
+    UnsupportedKind(u16),
+
+    #[error("addressable video event needs a non-empty d tag")]
+    MissingIdentifier,
+
+    #[error("invalid tag: {0}")]
+    InvalidTag(String),
+
+    #[error("failed to sign event: {0}")]
+    Sign(String),
+}
+
+impl BuildError {
+    /// Stable code for this kind of failure.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::UnsupportedKind(_) | Self::Sign(_) => ErrorCode::ProtoInvalidEvent,
+            Self::MissingIdentifier => ErrorCode::ProtoMissingTag,
+            Self::InvalidTag(_) => ErrorCode::ProtoInvalidTag,
+        }
+    }
+}
+
+/// Builder for a signed NIP-71 video event.
+///
+/// Addressable kinds (34235, 34236) get a `d` tag from [`VideoMeta::d_tag`]; regular
+/// kinds (21, 22) don't have one. The video URL is written both as a `url` tag and
+/// in an `imeta` tag, which NIP-71 clients read.
+#[derive(Debug, Clone)]
+pub struct VideoEventBuilder {
+    kind: u16,
+    meta: VideoMeta,
+    content: String,
+    created_at: Option<DateTime<Utc>>,
+    extra_tags: Vec<Vec<String>>,
+}
+
+impl VideoEventBuilder {
+    /// Builder for a video event of `kind` (one of 34235, 34236, 21, 22).
+    pub fn new(kind: u16, meta: VideoMeta) -> Self {
+        Self {
+            kind,
+            meta,
+            content: String::new(),
+            created_at: None,
+            extra_tags: Vec::new(),
+        }
+    }
+
+    /// Event content, usually the video's description.
+    pub fn content(mut self, content: impl Into<String>) -> Self {
+        self.content = content.into();
+        self
+    }
+
+    /// Creation time; defaults to the time of signing.
+    pub fn created_at(mut self, created_at: DateTime<Utc>) -> Self {
+        self.created_at = Some(created_at);
+        self
+    }
+
+    /// Add a tag beyond those derived from the metadata, e.g. `["p", <pubkey>]`.
+    pub fn tag<S>(mut self, tag: impl IntoIterator<Item = S>) -> Self
+    where
+        S: Into<String>,
+    {
+        self.extra_tags
+            .push(tag.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Tag arrays the event will carry, in order.
+    pub fn tags(&self) -> Result<Vec<Vec<String>>, BuildError> {
+        let addressable = match self.kind {
+            KIND_VIDEO | KIND_VIDEO_SHORT => true,
+            KIND_VIDEO_REGULAR | KIND_VIDEO_SHORT_REGULAR => false,
+            kind => return Err(BuildError::UnsupportedKind(kind)),
+        };
+        let meta = &self.meta;
+        let mut tags: Vec<Vec<String>> = Vec::new();
+        let mut push = |tag: &[&str]| tags.push(tag.iter().map(|s| s.to_string()).collect());
+
+        if addressable {
+            if meta.d_tag.is_empty() {
+                return Err(BuildError::MissingIdentifier);
+            }
+            push(&["d", &meta.d_tag]);
+        }
+        if let Some(title) = &meta.title {
+            push(&["title", title]);
+        }
+        if let Some(thumbnail) = &meta.thumbnail {
+            push(&["thumb", thumbnail]);
+        }
+        if let Some(url) = &meta.video_url {
+            push(&["url", url]);
+        }
+        for hashtag in &meta.hashtags {
+            push(&["t", hashtag]);
+        }
+        if let Some(secs) = meta.duration_secs {
+            push(&["duration", &secs.to_string()]);
+        }
+        if let Some(published_at) = meta.published_at {
+            push(&["published_at", &published_at.timestamp().to_string()]);
+        }
+        if let Some(alt) = &meta.alt {
+            push(&["alt", alt]);
+        }
+        match meta.content_warning.as_deref() {
+            Some("") => push(&["content-warning"]),
+            Some(reason) => push(&["content-warning", reason]),
+            None => {}
+        }
+        if let Some(language) = &meta.language {
+            push(&["L", LANGUAGE_NAMESPACE]);
+            push(&["l", language, LANGUAGE_NAMESPACE]);
+        }
+        if let Some(geohash) = &meta.geohash {
+            push(&["g", geohash]);
+        }
+        if let Some(url) = &meta.video_url {
+            let url = format!("url {url}");
+            match &meta.thumbnail {
+                Some(thumbnail) => push(&["imeta", &url, &format!("image {thumbnail}")]),
+                None => push(&["imeta", &url]),
+            }
+        }
+        tags.extend(self.extra_tags.iter().cloned());
+
+        Ok(tags)
+    }
+
+    /// Build the event and sign it with `keys`.
+    pub fn sign(self, keys: &Keys) -> Result<Event, BuildError> {
+        let tags = self
+            .tags()?
+            .into_iter()
+            .map(|tag| Tag::parse(&tag).map_err(|e| BuildError::InvalidTag(e.to_string())))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut builder = EventBuilder::new(Kind::from(self.kind), self.content).tags(tags);
+        if let Some(created_at) = self.created_at {
+            builder =
+                builder.custom_created_at(Timestamp::from(created_at.timestamp().max(0) as u64));
+        }
+        builder
+            .sign_with_keys(keys)
+            .map_err(|e| BuildError::Sign(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ParsedEvent;
+
+    fn meta() -> VideoMeta {
+        VideoMeta {
+            d_tag: "my-video".to_string(),
+            title: Some("My Video".to_string()),
+            thumbnail: Some("https://example.com/thumb.jpg".to_string()),
+            video_url: Some("https://example.com/video.mp4".to_string()),
+            hashtags: vec!["nostr".to_string(), "bitcoin".to_string()],
+            duration_secs: Some(12.5),
+            published_at: DateTime::from_timestamp(1_690_000_000, 0),
+            alt: Some("A cat".to_string()),
+            content_warning: Some(String::new()),
+            language: Some("en".to_string()),
+            geohash: Some("u4pruy".to_string()),
+        }
+    }
+
+    #[test]
+    fn signed_event_round_trips_through_video_meta() {
+        let keys = Keys::generate();
+        let created_at = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let event = VideoEventBuilder::new(KIND_VIDEO_SHORT, meta())
+            .content("Check out my video!")
+            .created_at(created_at)
+            .tag(["client", "funnel"])
+            .sign(&keys)
+            .unwrap();
+
+        let parsed = ParsedEvent::from_event(&event);
+        assert!(parsed.verify().is_ok());
+        assert_eq!(parsed.kind, KIND_VIDEO_SHORT);
+        assert_eq!(parsed.pubkey, keys.public_key().to_hex());
+        assert_eq!(parsed.created_at, created_at);
+        assert_eq!(parsed.content, "Check out my video!");
+        assert_eq!(parsed.get_tag("client"), Some("funnel"));
+        assert_eq!(VideoMeta::from_event(&parsed), Some(meta()));
+    }
+
+    #[test]
+    fn regular_kinds_have_no_d_tag() {
+        let tags = VideoEventBuilder::new(KIND_VIDEO_REGULAR, meta())
+            .tags()
+            .unwrap();
+
+        assert!(!tags.iter().any(|tag| tag[0] == "d"));
+        assert!(tags.contains(&vec![
+            "imeta".to_string(),
+            "url https://example.com/video.mp4".to_string(),
+            "image https://example.com/thumb.jpg".to_string(),
+        ]));
+    }
+
+    #[test]
+    fn rejects_bad_kind_and_missing_identifier() {
+        let err = VideoEventBuilder::new(1, meta()).tags().unwrap_err();
+        assert!(matches!(err, BuildError::UnsupportedKind(1)));
+
+        let meta = VideoMeta {
+            d_tag: String::new(),
+            ..meta()
+        };
+        let err = VideoEventBuilder::new(KIND_VIDEO, meta)
+            .sign(&Keys::generate())
+            .unwrap_err();
+        assert_eq!(err.code(), ErrorCode::ProtoMissingTag);
+    }
+}
@@ -0,0 +1,123 @@
+//! Stable error codes shared by every Funnel crate.
+//!
+//! Each failure that gets logged or returned to a client carries one of these codes
+//! (`FNL-<AREA>-<NNN>`) alongside its free-text message, so dashboards, alerts, and
+//! support can group failures by code. Codes are never reused or renumbered; add new
+//! variants at the end of their area.
+
+use std::fmt;
+
+use serde::{Serialize, Serializer};
+
+/// Error code identifying a class of failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    /// Event JSON could not be deserialized.
+    ProtoInvalidJson,
+    /// Event JSON was well-formed but not a valid Nostr event.
+    ProtoInvalidEvent,
+    /// Event lacked a tag its kind requires.
+    ProtoMissingTag,
+    /// Event ID didn't match the hash of its contents.
+    ProtoInvalidId,
+    /// Event signature didn't verify against its pubkey.
+    ProtoInvalidSignature,
+    /// A tag didn't have the shape its name requires.
+    ProtoInvalidTag,
+    /// Reading an event stream failed.
+    ProtoReadFailed,
+
+    /// Could not connect to ClickHouse.
+    ClickHouseConnection,
+    /// A ClickHouse query or insert failed.
+    ClickHouseQuery,
+    /// A row could not be serialized or deserialized.
+    ClickHouseSerialization,
+    /// ClickHouse connection settings are invalid.
+    ClickHouseConfig,
+
+    /// Fetching events from the relay failed.
+    IngestRelayFetch,
+    /// The relay notification channel lagged and dropped events.
+    IngestChannelLagged,
+    /// The relay notification channel closed.
+    IngestChannelClosed,
+    /// A queued backfill request failed.
+    IngestBackfillFailed,
+
+    /// A query parameter or request body was invalid.
+    ApiInvalidParameter,
+    /// An event ID, pubkey, or other identifier in the path was malformed.
+    ApiInvalidIdentifier,
+    /// The requested resource doesn't exist.
+    ApiNotFound,
+    /// The requested resource was deleted.
+    ApiGone,
+    /// Credentials were missing or invalid.
+    ApiUnauthorized,
+    /// Credentials were valid but lack the required scope.
+    ApiForbidden,
+    /// The caller exceeded a rate limit or quota.
+    ApiRateLimited,
+    /// The request body was too large.
+    ApiPayloadTooLarge,
+    /// The request took too long to handle.
+    ApiTimeout,
+    /// The service or feature is temporarily unavailable.
+    ApiUnavailable,
+    /// An unexpected internal failure not attributable to a dependency.
+    ApiInternal,
+
+    /// An aggregation run failed.
+    AggregatorRunFailed,
+}
+
+impl ErrorCode {
+    /// The code as written in logs and responses, e.g. `FNL-CH-002`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::ProtoInvalidJson => "FNL-PROTO-001",
+            Self::ProtoInvalidEvent => "FNL-PROTO-002",
+            Self::ProtoMissingTag => "FNL-PROTO-003",
+            Self::ProtoInvalidId => "FNL-PROTO-004",
+            Self::ProtoInvalidSignature => "FNL-PROTO-005",
+            Self::ProtoInvalidTag => "FNL-PROTO-006",
+            Self::ProtoReadFailed => "FNL-PROTO-007",
+            Self::ClickHouseConnection => "FNL-CH-001",
+            Self::ClickHouseQuery => "FNL-CH-002",
+            Self::ClickHouseSerialization => "FNL-CH-003",
+            Self::ClickHouseConfig => "FNL-CH-004",
+            Self::IngestRelayFetch => "FNL-INGEST-001",
+            Self::IngestChannelLagged => "FNL-INGEST-002",
+            Self::IngestChannelClosed => "FNL-INGEST-003",
+            Self::IngestBackfillFailed => "FNL-INGEST-004",
+            Self::ApiInvalidParameter => "FNL-API-001",
+            Self::ApiInvalidIdentifier => "FNL-API-002",
+            Self::ApiNotFound => "FNL-API-003",
+            Self::ApiGone => "FNL-API-004",
+            Self::ApiUnauthorized => "FNL-API-005",
+            Self::ApiForbidden => "FNL-API-006",
+            Self::ApiRateLimited => "FNL-API-007",
+            Self::ApiPayloadTooLarge => "FNL-API-008",
+            Self::ApiTimeout => "FNL-API-009",
+            Self::ApiUnavailable => "FNL-API-010",
+            Self::ApiInternal => "FNL-API-011",
+            Self::AggregatorRunFailed => "FNL-AGG-001",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for ErrorCode {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
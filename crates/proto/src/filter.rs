@@ -0,0 +1,152 @@
+//! Matching events against a filter in process.
+//!
+//! [`ProtoFilter`] mirrors the fields of a NIP-01 relay filter, so ingestion can
+//! drop events it doesn't want before they reach ClickHouse, whichever relay or
+//! stream they came from.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::ParsedEvent;
+
+/// Conditions an event must meet, like a NIP-01 filter.
+///
+/// Every set condition must hold; an empty list or unset bound matches any event,
+/// so the default filter matches everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProtoFilter {
+    /// Kinds the event may have.
+    pub kinds: Vec<u16>,
+    /// Hex pubkeys the event's author may have.
+    pub authors: Vec<String>,
+    /// Earliest `created_at`, inclusive.
+    pub since: Option<DateTime<Utc>>,
+    /// Latest `created_at`, inclusive.
+    pub until: Option<DateTime<Utc>>,
+    /// Per tag name, values of which the event must have at least one tag.
+    pub tags: BTreeMap<String, Vec<String>>,
+}
+
+impl ProtoFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also require one of `kinds`.
+    pub fn kinds(mut self, kinds: impl IntoIterator<Item = u16>) -> Self {
+        self.kinds.extend(kinds);
+        self
+    }
+
+    /// Also require one of `authors` (hex pubkeys).
+    pub fn authors<S>(mut self, authors: impl IntoIterator<Item = S>) -> Self
+    where
+        S: Into<String>,
+    {
+        self.authors.extend(authors.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn since(mut self, since: DateTime<Utc>) -> Self {
+        self.since = Some(since);
+        self
+    }
+
+    pub fn until(mut self, until: DateTime<Utc>) -> Self {
+        self.until = Some(until);
+        self
+    }
+
+    /// Also require a `name` tag with one of `values`, e.g. `tag("t", ["nostr"])`.
+    pub fn tag<S>(mut self, name: impl Into<String>, values: impl IntoIterator<Item = S>) -> Self
+    where
+        S: Into<String>,
+    {
+        self.tags
+            .entry(name.into())
+            .or_default()
+            .extend(values.into_iter().map(Into::into));
+        self
+    }
+
+    /// Whether `event` meets every condition of the filter.
+    pub fn matches(&self, event: &ParsedEvent) -> bool {
+        (self.kinds.is_empty() || self.kinds.contains(&event.kind))
+            && (self.authors.is_empty()
+                || self
+                    .authors
+                    .iter()
+                    .any(|author| author.eq_ignore_ascii_case(&event.pubkey)))
+            && self.since.is_none_or(|since| event.created_at >= since)
+            && self.until.is_none_or(|until| event.created_at <= until)
+            && self.tags.iter().all(|(name, values)| {
+                event.get_tags(name).iter().any(|tag| {
+                    tag.get(1)
+                        .is_some_and(|value| values.iter().any(|v| v == value))
+                })
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUBKEY: &str = "6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93";
+
+    fn event() -> ParsedEvent {
+        ParsedEvent {
+            id: "f376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65".to_string(),
+            pubkey: PUBKEY.to_string(),
+            created_at: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+            kind: 34235,
+            content: String::new(),
+            sig: String::new(),
+            tags: vec![
+                vec!["d".to_string(), "my-video".to_string()],
+                vec!["t".to_string(), "nostr".to_string()],
+                vec!["t".to_string(), "bitcoin".to_string()],
+            ],
+            received_at: None,
+        }
+    }
+
+    #[test]
+    fn empty_filter_matches_everything() {
+        assert!(ProtoFilter::new().matches(&event()));
+    }
+
+    #[test]
+    fn matches_when_every_condition_holds() {
+        let at = event().created_at;
+        let filter = ProtoFilter::new()
+            .kinds([34235, 34236])
+            .authors([PUBKEY.to_uppercase()])
+            .since(at)
+            .until(at)
+            .tag("t", ["music", "bitcoin"])
+            .tag("d", ["my-video"]);
+
+        assert!(filter.matches(&event()));
+    }
+
+    #[test]
+    fn rejects_when_any_condition_fails() {
+        let at = event().created_at;
+        let filters = [
+            ProtoFilter::new().kinds([7]),
+            ProtoFilter::new()
+                .authors(["bf2376e17ba4ec269d10fcc996a4746b451152be9031fa48e74553dde5526bce"]),
+            ProtoFilter::new().since(at + chrono::Duration::seconds(1)),
+            ProtoFilter::new().until(at - chrono::Duration::seconds(1)),
+            ProtoFilter::new().tag("t", ["music"]),
+            ProtoFilter::new().tag("t", ["nostr"]).tag("e", [PUBKEY]),
+        ];
+
+        for filter in filters {
+            assert!(!filter.matches(&event()), "{filter:?}");
+        }
+    }
+}
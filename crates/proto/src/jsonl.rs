@@ -0,0 +1,297 @@
+//! Reading JSONL event streams.
+//!
+//! `strfry stream`, `strfry export`, and event archives are JSONL: one event per
+//! line, either a bare Nostr event or a strfry message wrapping one.
+//! [`JsonlReader`] (and [`AsyncJsonlReader`] with the `tokio` feature) split such a
+//! stream into [`ParsedEvent`]s, counting lines and lines that failed to parse so
+//! callers can report on both. strfry messages that carry no event, such as
+//! `DELETE`, are skipped.
+
+use std::io::{self, BufRead};
+use std::ops::ControlFlow;
+
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::{ErrorCode, Event, ParseError, ParsedEvent, StrfryMessage};
+
+/// Errors from reading a JSONL event stream.
+#[derive(Debug, Error)]
+pub enum JsonlError {
+    #[error("failed to read line {line}: {source}")]
+    Io {
+        line: u64,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("line {line}: {source}")]
+    Parse {
+        line: u64,
+        #[source]
+        source: ParseError,
+    },
+}
+
+impl JsonlError {
+    /// Line number (1-based) the error occurred on.
+    pub fn line(&self) -> u64 {
+        match self {
+            Self::Io { line, .. } | Self::Parse { line, .. } => *line,
+        }
+    }
+
+    /// Stable code for this kind of failure.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::Io { .. } => ErrorCode::ProtoReadFailed,
+            Self::Parse { source, .. } => source.code(),
+        }
+    }
+}
+
+/// A line of a JSONL stream.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Line {
+    Strfry(StrfryMessage),
+    Event(Box<Event>),
+}
+
+/// Line buffer and counters shared by the sync and async readers.
+#[derive(Debug, Default)]
+struct LineState {
+    buf: Vec<u8>,
+    lines: u64,
+    parse_errors: u64,
+    done: bool,
+}
+
+impl LineState {
+    /// Handle the result of reading a line into `buf`: continue on a blank line,
+    /// otherwise break with the next item (`None` at the end of the stream).
+    ///
+    /// A read error ends the stream, as the reader's position is unknown after it.
+    fn on_read(
+        &mut self,
+        read: io::Result<usize>,
+    ) -> ControlFlow<Option<Result<ParsedEvent, JsonlError>>> {
+        match read {
+            Ok(0) => {
+                self.done = true;
+                return ControlFlow::Break(None);
+            }
+            Ok(_) => self.lines += 1,
+            Err(source) => {
+                self.done = true;
+                return ControlFlow::Break(Some(Err(JsonlError::Io {
+                    line: self.lines + 1,
+                    source,
+                })));
+            }
+        }
+
+        if self.buf.trim_ascii().is_empty() {
+            return ControlFlow::Continue(());
+        }
+        let result = match serde_json::from_slice(&self.buf) {
+            Ok(Line::Strfry(message)) => match message.to_parsed_event() {
+                Some(event) => Ok(event),
+                None => return ControlFlow::Continue(()),
+            },
+            Ok(Line::Event(event)) => Ok(ParsedEvent::from_event(&event)),
+            Err(err) => {
+                self.parse_errors += 1;
+                Err(JsonlError::Parse {
+                    line: self.lines,
+                    source: err.into(),
+                })
+            }
+        };
+        ControlFlow::Break(Some(result))
+    }
+}
+
+/// Iterator over the events of a JSONL stream.
+///
+/// Blank lines and strfry messages without an event are skipped. A line that fails
+/// to parse yields an error and reading continues with the next line; a read error
+/// ends the iteration.
+#[derive(Debug)]
+pub struct JsonlReader<R> {
+    reader: R,
+    state: LineState,
+}
+
+impl<R> JsonlReader<R>
+where
+    R: BufRead,
+{
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            state: LineState::default(),
+        }
+    }
+}
+
+impl<R> JsonlReader<R> {
+    /// Number of lines read so far, including blank and malformed ones.
+    pub fn line_number(&self) -> u64 {
+        self.state.lines
+    }
+
+    /// Number of lines that failed to parse so far.
+    pub fn parse_errors(&self) -> u64 {
+        self.state.parse_errors
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R> Iterator for JsonlReader<R>
+where
+    R: BufRead,
+{
+    type Item = Result<ParsedEvent, JsonlError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.state.done {
+            self.state.buf.clear();
+            let read = self.reader.read_until(b'\n', &mut self.state.buf);
+            if let ControlFlow::Break(item) = self.state.on_read(read) {
+                return item;
+            }
+        }
+        None
+    }
+}
+
+/// Async counterpart of [`JsonlReader`], over tokio's `AsyncBufRead`.
+#[cfg(feature = "tokio")]
+#[derive(Debug)]
+pub struct AsyncJsonlReader<R> {
+    reader: R,
+    state: LineState,
+}
+
+#[cfg(feature = "tokio")]
+impl<R> AsyncJsonlReader<R>
+where
+    R: tokio::io::AsyncBufRead + Unpin,
+{
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            state: LineState::default(),
+        }
+    }
+
+    /// Read the next event, as [`JsonlReader`]'s `next` does.
+    pub async fn next_event(&mut self) -> Option<Result<ParsedEvent, JsonlError>> {
+        use tokio::io::AsyncBufReadExt;
+
+        while !self.state.done {
+            self.state.buf.clear();
+            let read = self.reader.read_until(b'\n', &mut self.state.buf).await;
+            if let ControlFlow::Break(item) = self.state.on_read(read) {
+                return item;
+            }
+        }
+        None
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<R> AsyncJsonlReader<R> {
+    /// Number of lines read so far, including blank and malformed ones.
+    pub fn line_number(&self) -> u64 {
+        self.state.lines
+    }
+
+    /// Number of lines that failed to parse so far.
+    pub fn parse_errors(&self) -> u64 {
+        self.state.parse_errors
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EVENT: &str = r#"{"id":"a376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65","pubkey":"6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93","created_at":1700000000,"kind":34235,"tags":[["d","my-video-id"]],"content":"","sig":"908a15e46fb4d8675bab026fc230a0e3542bfade63da02d542fb78b2a8513fcd0092619a2c8c1221e581946e0191f2af505dfdf8657a414dbca329186f009262"}"#;
+
+    fn stream() -> String {
+        format!(
+            "{EVENT}\n\n{{\"type\":\"new\",\"event\":{EVENT},\"receivedAt\":1700000005}}\r\nnot json\n{EVENT}"
+        )
+    }
+
+    #[test]
+    fn reads_bare_events_and_strfry_messages() {
+        let input = stream();
+        let mut reader = JsonlReader::new(input.as_bytes());
+
+        let first = reader.next().unwrap().unwrap();
+        assert_eq!(first.kind, 34235);
+        assert_eq!(first.received_at, None);
+
+        let second = reader.next().unwrap().unwrap();
+        assert_eq!(second.id, first.id);
+        assert_eq!(
+            second.received_at,
+            chrono::DateTime::from_timestamp(1_700_000_005, 0)
+        );
+        assert_eq!(reader.line_number(), 3);
+
+        let err = reader.next().unwrap().unwrap_err();
+        assert_eq!(err.line(), 4);
+        assert_eq!(err.code(), ErrorCode::ProtoInvalidJson);
+
+        assert!(reader.next().unwrap().is_ok());
+        assert!(reader.next().is_none());
+        assert_eq!(reader.line_number(), 5);
+        assert_eq!(reader.parse_errors(), 1);
+    }
+
+    #[test]
+    fn read_error_ends_iteration() {
+        struct Failing;
+
+        impl io::Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other("disk gone"))
+            }
+        }
+
+        let mut reader = JsonlReader::new(io::BufReader::new(Failing));
+        let err = reader.next().unwrap().unwrap_err();
+        assert_eq!(err.line(), 1);
+        assert_eq!(err.code(), ErrorCode::ProtoReadFailed);
+        assert!(reader.next().is_none());
+        assert_eq!(reader.parse_errors(), 0);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn async_reader_matches_sync_reader() {
+        let input = stream();
+        let mut reader = AsyncJsonlReader::new(input.as_bytes());
+
+        let mut events = 0;
+        while let Some(result) = reader.next_event().await {
+            if result.is_ok() {
+                events += 1;
+            }
+        }
+        assert_eq!(events, 3);
+        assert_eq!(reader.line_number(), 5);
+        assert_eq!(reader.parse_errors(), 1);
+    }
+}
@@ -1,18 +1,92 @@
 //! Nostr protocol types and video event parsing for Funnel.
 //!
 //! This crate wraps the `nostr` crate and provides video-specific event types
-//! for kinds 34235 (normal videos) and 34236 (short videos) per NIP-71.
+//! for kinds 34235 (normal videos) and 34236 (short videos) per NIP-71, video sets
+//! (kind 30005) per NIP-51, and live streams (kind 30311) per NIP-53. It also
+//! parses the events around videos: profiles (kind 0), deletion requests (kind 5)
+//! per NIP-09, reactions (kind 7) per NIP-25, reposts (kinds 6 and 16) per NIP-18,
+//! comments (kind 1111) per NIP-22, file metadata (kind 1063) per NIP-94, and zap
+//! receipts (kind 9735) per NIP-57, as well as Funnel's own watch sessions (kind
+//! 34237).
+//!
+//! [`JsonlReader`] reads events from JSONL streams such as `strfry stream` output
+//! and archives; with the `tokio` feature, [`AsyncJsonlReader`] does the same over
+//! async readers. [`ProtoFilter`] matches events against a NIP-01 style filter in
+//! process, and [`VideoEventBuilder`] builds signed video events.
+//! [`ParsedEventRef`] parses JSON without copying strings, for hot paths, and
+//! [`LatestRevisions`] keeps only the latest version of replaceable events.
+
+use std::fmt;
+use std::str::FromStr;
 
 use chrono::{DateTime, Utc};
+use nostr::ToBech32;
+use nostr::nips::nip01::Coordinate;
+use nostr::nips::nip19::{FromBech32, Nip19, Nip19Event};
+use nostr::secp256k1::schnorr::Signature;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-pub use nostr::{Event, EventId, Kind, PublicKey, Tag, Timestamp};
+mod borrowed;
+mod builder;
+mod error_code;
+mod filter;
+mod jsonl;
+mod replaceable;
+mod tags;
+
+pub use self::borrowed::ParsedEventRef;
+pub use self::builder::{BuildError, VideoEventBuilder};
+pub use self::error_code::ErrorCode;
+pub use self::filter::ProtoFilter;
+#[cfg(feature = "tokio")]
+pub use self::jsonl::AsyncJsonlReader;
+pub use self::jsonl::{JsonlError, JsonlReader};
+pub use self::replaceable::{LatestRevisions, latest_revisions, supersedes};
+pub use self::tags::{EventRef, Imeta, Mention, ParsedTag};
+pub use nostr::{Event, EventId, Keys, Kind, PublicKey, Tag, Timestamp};
+
+/// Profile metadata kind per NIP-01.
+pub const KIND_METADATA: u16 = 0;
 
 /// Video event kinds per NIP-71.
 pub const KIND_VIDEO: u16 = 34235;
 pub const KIND_VIDEO_SHORT: u16 = 34236;
 
+/// Regular (non-addressable) video event kinds per NIP-71. Only
+/// [`VideoEventBuilder`] produces these; ingestion indexes the addressable kinds.
+pub const KIND_VIDEO_REGULAR: u16 = 21;
+pub const KIND_VIDEO_SHORT_REGULAR: u16 = 22;
+
+/// Video set (curated playlist) kind per NIP-51.
+pub const KIND_VIDEO_SET: u16 = 30005;
+
+/// Live stream kind per NIP-53.
+pub const KIND_LIVE_STREAM: u16 = 30311;
+
+/// Watch session kind, Funnel's own: how long a viewer watched a video. See
+/// [`WatchMeta`].
+pub const KIND_WATCH_SESSION: u16 = 34237;
+
+/// Deletion request kind per NIP-09.
+pub const KIND_DELETION: u16 = 5;
+
+/// Reaction kind per NIP-25.
+pub const KIND_REACTION: u16 = 7;
+
+/// Repost kinds per NIP-18: of text notes, and of any other kind.
+pub const KIND_REPOST: u16 = 6;
+pub const KIND_GENERIC_REPOST: u16 = 16;
+
+/// Comment kind per NIP-22.
+pub const KIND_COMMENT: u16 = 1111;
+
+/// File metadata kind per NIP-94.
+pub const KIND_FILE_METADATA: u16 = 1063;
+
+/// Zap receipt kind per NIP-57.
+pub const KIND_ZAP_RECEIPT: u16 = 9735;
+
 /// Errors that can occur when parsing events.
 #[derive(Debug, Error)]
 pub enum ParseError {
@@ -24,6 +98,57 @@ pub enum ParseError {
 
     #[error("missing required tag: {0}")]
     MissingTag(String),
+
+    #[error("invalid tag: {0}")]
+    InvalidTag(String),
+}
+
+impl ParseError {
+    /// Stable code for this kind of failure.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::InvalidJson(_) => ErrorCode::ProtoInvalidJson,
+            Self::InvalidEvent(_) => ErrorCode::ProtoInvalidEvent,
+            Self::MissingTag(_) => ErrorCode::ProtoMissingTag,
+            Self::InvalidTag(_) => ErrorCode::ProtoInvalidTag,
+        }
+    }
+}
+
+/// Errors from verifying an event's ID and signature.
+#[derive(Debug, Error)]
+pub enum VerifyError {
+    #[error("malformed event {0}")]
+    Malformed(&'static str),
+
+    #[error("event ID does not match its contents")]
+    InvalidId,
+
+    #[error("event signature is invalid")]
+    InvalidSignature,
+}
+
+impl VerifyError {
+    /// Stable code for this kind of failure.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::Malformed(_) => ErrorCode::ProtoInvalidEvent,
+            Self::InvalidId => ErrorCode::ProtoInvalidId,
+            Self::InvalidSignature => ErrorCode::ProtoInvalidSignature,
+        }
+    }
+}
+
+/// Check that `event`'s ID is the hash of its contents and that its signature is
+/// valid for that ID and its pubkey.
+fn verify_event(event: &Event) -> Result<(), VerifyError> {
+    if !event.verify_id() {
+        return Err(VerifyError::InvalidId);
+    }
+    if !event.verify_signature() {
+        return Err(VerifyError::InvalidSignature);
+    }
+    Ok(())
 }
 
 /// A parsed Nostr event with extracted fields for ClickHouse insertion.
@@ -36,6 +161,10 @@ pub struct ParsedEvent {
     pub content: String,
     pub sig: String,
     pub tags: Vec<Vec<String>>,
+    /// When a relay (strfry's `receivedAt`) or the live ingester received the event;
+    /// `None` when unknown, as for backfilled history.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub received_at: Option<DateTime<Utc>>,
 }
 
 impl ParsedEvent {
@@ -54,20 +183,103 @@ impl ParsedEvent {
                 .iter()
                 .map(|t| t.as_slice().iter().map(|s| s.to_string()).collect())
                 .collect(),
+            received_at: None,
         }
     }
 
+    /// The event, received at `received_at`.
+    pub fn with_received_at(mut self, received_at: DateTime<Utc>) -> Self {
+        self.received_at = Some(received_at);
+        self
+    }
+
     /// Parse from JSON string.
     pub fn from_json(json: &str) -> Result<Self, ParseError> {
         let event: Event = serde_json::from_str(json)?;
         Ok(Self::from_event(&event))
     }
 
+    /// Recompute the event ID and check the signature, so forged events can be
+    /// dropped before they are stored.
+    pub fn verify(&self) -> Result<(), VerifyError> {
+        let tags = self
+            .tags
+            .iter()
+            .map(Tag::parse)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| VerifyError::Malformed("tags"))?;
+        let event = Event::new(
+            EventId::from_hex(&self.id).map_err(|_| VerifyError::Malformed("id"))?,
+            PublicKey::from_hex(&self.pubkey).map_err(|_| VerifyError::Malformed("pubkey"))?,
+            Timestamp::from(self.created_at.timestamp().max(0) as u64),
+            Kind::from(self.kind),
+            tags,
+            self.content.clone(),
+            Signature::from_str(&self.sig).map_err(|_| VerifyError::Malformed("signature"))?,
+        );
+        verify_event(&event)
+    }
+
     /// Check if this is a video event.
     pub fn is_video(&self) -> bool {
         self.kind == KIND_VIDEO || self.kind == KIND_VIDEO_SHORT
     }
 
+    /// When the event expires (`expiration` tag, NIP-40), if it does.
+    pub fn expiration(&self) -> Option<DateTime<Utc>> {
+        self.get_tag("expiration")
+            .and_then(|s| s.trim().parse::<i64>().ok())
+            .and_then(|secs| DateTime::from_timestamp(secs, 0))
+    }
+
+    /// Whether the event has expired as of `now` and should no longer be served.
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expiration()
+            .is_some_and(|expiration| expiration <= now)
+    }
+
+    /// Proof-of-work difficulty per NIP-13: the number of leading zero bits of the
+    /// event ID. 0 if the ID isn't hex.
+    pub fn pow_difficulty(&self) -> u32 {
+        let mut bits = 0;
+        for c in self.id.chars() {
+            let Some(nibble) = c.to_digit(16) else {
+                return 0;
+            };
+            if nibble != 0 {
+                return bits + nibble.leading_zeros() - 28;
+            }
+            bits += 4;
+        }
+        bits
+    }
+
+    /// Check if this is a replaceable event, kinds 0, 3, and 10000-19999 per NIP-01,
+    /// of which only the latest per kind and pubkey is kept.
+    pub fn is_replaceable(&self) -> bool {
+        matches!(self.kind, 0 | 3 | 10000..20000)
+    }
+
+    /// Check if this is a parameterized replaceable (addressable) event, kinds
+    /// 30000-39999 per NIP-01.
+    pub fn is_addressable(&self) -> bool {
+        (30000..40000).contains(&self.kind)
+    }
+
+    /// Address other events reference this one by (`a` tags), for addressable kinds.
+    /// A missing `d` tag counts as an empty identifier, as NIP-01 specifies.
+    pub fn coordinate(&self) -> Option<EventAddress> {
+        if !self.is_addressable() {
+            return None;
+        }
+
+        Some(EventAddress {
+            kind: self.kind,
+            pubkey: self.pubkey.to_ascii_lowercase(),
+            identifier: self.get_tag("d").unwrap_or_default().to_string(),
+        })
+    }
+
     /// Extract a tag value by name (first occurrence).
     pub fn get_tag(&self, name: &str) -> Option<&str> {
         self.tags
@@ -76,6 +288,55 @@ impl ParsedEvent {
             .and_then(|t| t.get(1).map(|s| s.as_str()))
     }
 
+    /// All tags in their typed form, failing on the first malformed one.
+    pub fn parsed_tags(&self) -> Result<Vec<ParsedTag>, ParseError> {
+        self.tags.iter().map(|tag| ParsedTag::parse(tag)).collect()
+    }
+
+    /// Pubkeys referenced in `p` tags, in tag order, skipping malformed tags.
+    pub fn mentions(&self) -> Vec<Mention> {
+        self.get_tags("p")
+            .into_iter()
+            .filter_map(|tag| match ParsedTag::parse(tag) {
+                Ok(ParsedTag::PubkeyRef { pubkey, relay }) => Some(Mention { pubkey, relay }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Events referenced in `e` tags, in tag order, skipping malformed tags.
+    pub fn event_refs(&self) -> Vec<EventRef> {
+        self.get_tags("e")
+            .into_iter()
+            .filter_map(|tag| match ParsedTag::parse(tag) {
+                Ok(ParsedTag::EventRef { id, relay, marker }) => {
+                    Some(EventRef { id, relay, marker })
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Relays the event's tags point to: hints in `e` and `a` tags and `r` tags (as
+    /// in NIP-65 relay lists), normalized with [`normalize_relay_url`], deduplicated,
+    /// in tag order. URLs that aren't websocket URLs are skipped.
+    pub fn relay_hints(&self) -> Vec<String> {
+        let mut relays: Vec<String> = Vec::new();
+        for tag in &self.tags {
+            let url = match tag.first().map(|s| s.as_str()) {
+                Some("e" | "a") => tag.get(2),
+                Some("r") => tag.get(1),
+                _ => None,
+            };
+            if let Some(url) = url.and_then(|url| normalize_relay_url(url))
+                && !relays.contains(&url)
+            {
+                relays.push(url);
+            }
+        }
+        relays
+    }
+
     /// Extract all tag values for a given name.
     pub fn get_tags(&self, name: &str) -> Vec<&[String]> {
         self.tags
@@ -87,13 +348,28 @@ impl ParsedEvent {
 }
 
 /// Video metadata extracted from a video event.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct VideoMeta {
     pub d_tag: String,
     pub title: Option<String>,
     pub thumbnail: Option<String>,
     pub video_url: Option<String>,
     pub hashtags: Vec<String>,
+    /// Length of the video in seconds (`duration` tag).
+    pub duration_secs: Option<f64>,
+    /// When the video was first published (`published_at` tag), which stays the same
+    /// across edits while `created_at` changes.
+    pub published_at: Option<DateTime<Utc>>,
+    /// Accessibility description (`alt` tag).
+    pub alt: Option<String>,
+    /// Reason given in a NIP-36 `content-warning` tag; empty when the tag has no
+    /// reason, `None` without the tag.
+    pub content_warning: Option<String>,
+    /// ISO 639-1 language code from a NIP-32 `l` label in the `ISO-639-1`
+    /// namespace, lowercased.
+    pub language: Option<String>,
+    /// Most precise geohash among the `g` tags, lowercased.
+    pub geohash: Option<String>,
 }
 
 impl VideoMeta {
@@ -118,307 +394,2415 @@ impl VideoMeta {
                 .iter()
                 .filter_map(|t| t.get(1).map(|s| s.to_string()))
                 .collect(),
+            duration_secs: event
+                .get_tag("duration")
+                .and_then(|s| s.trim().parse::<f64>().ok())
+                .filter(|secs| secs.is_finite() && *secs >= 0.0),
+            published_at: event
+                .get_tag("published_at")
+                .and_then(|s| s.trim().parse::<i64>().ok())
+                .and_then(|secs| DateTime::from_timestamp(secs, 0)),
+            alt: event.get_tag("alt").map(|s| s.to_string()),
+            content_warning: event
+                .get_tags("content-warning")
+                .first()
+                .map(|t| t.get(1).cloned().unwrap_or_default()),
+            language: event
+                .get_tags("l")
+                .iter()
+                .filter(|t| t.get(2).map(|s| s.as_str()) == Some(LANGUAGE_NAMESPACE))
+                .filter_map(|t| t.get(1))
+                .find(|code| code.len() == 2 && code.bytes().all(|b| b.is_ascii_alphabetic()))
+                .map(|code| code.to_ascii_lowercase()),
+            geohash: event
+                .get_tags("g")
+                .iter()
+                .filter_map(|t| t.get(1))
+                .map(|hash| hash.to_ascii_lowercase())
+                .filter(|hash| is_geohash(hash))
+                .max_by_key(|hash| hash.len()),
         })
     }
 }
 
-/// strfry stream message format (JSONL from `strfry stream`).
-#[derive(Debug, Clone, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct StrfryMessage {
-    #[serde(rename = "type")]
-    pub msg_type: String,
-    pub event: Event,
-    pub received_at: Option<f64>,
-    pub source_type: Option<String>,
-    pub source_info: Option<String>,
+/// NIP-32 label namespace of two-letter language codes.
+pub const LANGUAGE_NAMESPACE: &str = "ISO-639-1";
+
+/// Whether `s` is a lowercase geohash of 1 to 12 characters.
+fn is_geohash(s: &str) -> bool {
+    (1..=12).contains(&s.len())
+        && s.bytes()
+            .all(|b| b"0123456789bcdefghjkmnpqrstuvwxyz".contains(&b))
 }
 
-impl StrfryMessage {
-    /// Parse from JSON line.
-    pub fn from_json(json: &str) -> Result<Self, ParseError> {
-        Ok(serde_json::from_str(json)?)
+/// Profile fields from a metadata (kind 0) event's content.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProfileMeta {
+    pub name: Option<String>,
+    pub display_name: Option<String>,
+    pub picture: Option<String>,
+    pub nip05: Option<String>,
+    pub about: Option<String>,
+    /// Lightning address.
+    pub lud16: Option<String>,
+}
+
+impl ProfileMeta {
+    /// Extract profile metadata from a parsed event.
+    ///
+    /// Returns `None` for other kinds and when the content isn't a JSON object.
+    /// Fields that are missing, blank, or not strings are `None`, so one bad field
+    /// doesn't lose the rest of the profile.
+    pub fn from_event(event: &ParsedEvent) -> Option<Self> {
+        if event.kind != KIND_METADATA {
+            return None;
+        }
+
+        let content: serde_json::Map<String, serde_json::Value> =
+            serde_json::from_str(&event.content).ok()?;
+        let field = |name: &str| {
+            content
+                .get(name)
+                .and_then(|value| value.as_str())
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+
+        Some(Self {
+            name: field("name"),
+            // Older clients write `displayName`
+            display_name: field("display_name").or_else(|| field("displayName")),
+            picture: field("picture"),
+            nip05: field("nip05"),
+            about: field("about"),
+            lud16: field("lud16"),
+        })
     }
 
-    /// Convert to ParsedEvent.
-    pub fn to_parsed_event(&self) -> ParsedEvent {
-        ParsedEvent::from_event(&self.event)
+    /// Name to show for the profile: the display name, falling back to the name.
+    pub fn best_name(&self) -> Option<&str> {
+        self.display_name.as_deref().or(self.name.as_deref())
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Targets of a deletion request (kind 5) event.
+///
+/// A request may only delete its author's own events. Addresses are checked here
+/// since they name their author; event IDs don't, so whoever applies the request
+/// must check that each target event has the request's pubkey.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeletionMeta {
+    /// Author of the request, as lowercase hex.
+    pub pubkey: String,
+    /// When the request was made; addressed events up to this time are deleted.
+    pub created_at: DateTime<Utc>,
+    /// IDs of the events to delete (`e` tags), as lowercase hex.
+    pub event_ids: Vec<String>,
+    /// Addressable events to delete (`a` tags), all versions up to `created_at`.
+    pub addresses: Vec<EventAddress>,
+    /// Kinds of the events to delete (`k` tags).
+    pub kinds: Vec<u16>,
+    /// Reason given in the content; may be empty.
+    pub reason: String,
+}
 
-    #[test]
-    fn test_video_kind_constants() {
-        assert_eq!(KIND_VIDEO, 34235);
-        assert_eq!(KIND_VIDEO_SHORT, 34236);
+impl DeletionMeta {
+    /// Extract deletion targets from a parsed event.
+    ///
+    /// Returns `None` for other kinds and for requests with no valid target. `e`
+    /// tags that aren't hex IDs and `a` tags addressing another author's events are
+    /// skipped.
+    pub fn from_event(event: &ParsedEvent) -> Option<Self> {
+        if event.kind != KIND_DELETION {
+            return None;
+        }
+
+        let pubkey = event.pubkey.to_ascii_lowercase();
+        let values = |name: &str| {
+            event
+                .get_tags(name)
+                .into_iter()
+                .filter_map(|t| t.get(1).map(|s| s.as_str()))
+                .collect::<Vec<_>>()
+        };
+
+        let event_ids: Vec<String> = values("e")
+            .into_iter()
+            .filter(|id| is_hex_id(id))
+            .map(|id| id.to_ascii_lowercase())
+            .collect();
+        let addresses: Vec<EventAddress> = values("a")
+            .into_iter()
+            .filter_map(EventAddress::from_coordinate)
+            .filter(|address| address.pubkey == pubkey)
+            .collect();
+        if event_ids.is_empty() && addresses.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            pubkey,
+            created_at: event.created_at,
+            event_ids,
+            addresses,
+            kinds: values("k")
+                .into_iter()
+                .filter_map(|k| k.trim().parse().ok())
+                .collect(),
+            reason: event.content.clone(),
+        })
     }
 
-    // Sample valid Nostr event JSON (kind 1 text note)
-    const VALID_EVENT_JSON: &str = r#"{
-        "id": "4376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65",
-        "pubkey": "6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93",
-        "created_at": 1673347337,
-        "kind": 1,
-        "tags": [
-            ["e", "3da979448d9ba263864c4d6f14984c423a3838364ec255f03c7904b1ae77f206"],
-            ["p", "bf2376e17ba4ec269d10fcc996a4746b451152be9031fa48e74553dde5526bce"]
-        ],
-        "content": "Hello, Nostr!",
-        "sig": "908a15e46fb4d8675bab026fc230a0e3542bfade63da02d542fb78b2a8513fcd0092619a2c8c1221e581946e0191f2af505dfdf8657a414dbca329186f009262"
-    }"#;
+    /// Whether the request deletes a video, going by its `k` tags or addresses.
+    pub fn targets_video(&self) -> bool {
+        self.kinds
+            .iter()
+            .chain(self.addresses.iter().map(|a| &a.kind))
+            .any(|kind| *kind == KIND_VIDEO || *kind == KIND_VIDEO_SHORT)
+    }
+}
 
-    // Sample video event JSON (kind 34235)
-    // Uses the same valid pubkey/sig from VALID_EVENT_JSON for simplicity
-    const VIDEO_EVENT_JSON: &str = r#"{
-        "id": "a376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65",
-        "pubkey": "6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93",
-        "created_at": 1700000000,
-        "kind": 34235,
-        "tags": [
-            ["d", "my-video-id"],
-            ["title", "My Cool Video"],
-            ["thumb", "https://example.com/thumb.jpg"],
-            ["url", "https://example.com/video.mp4"],
-            ["t", "nostr"],
-            ["t", "bitcoin"],
-            ["p", "bf2376e17ba4ec269d10fcc996a4746b451152be9031fa48e74553dde5526bce"]
-        ],
-        "content": "Check out my video!",
-        "sig": "908a15e46fb4d8675bab026fc230a0e3542bfade63da02d542fb78b2a8513fcd0092619a2c8c1221e581946e0191f2af505dfdf8657a414dbca329186f009262"
-    }"#;
+/// Target and content of a reaction (kind 7) event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReactionMeta {
+    /// ID of the reacted-to event (last `e` tag), as lowercase hex.
+    pub event_id: String,
+    /// Address of the reacted-to event when it is addressable (last `a` tag).
+    pub address: Option<EventAddress>,
+    /// Author of the reacted-to event (last `p` tag), as lowercase hex.
+    pub author: Option<String>,
+    /// `+` (like, also used for empty content), `-` (dislike), or an emoji or
+    /// `:shortcode:`.
+    pub content: String,
+}
 
-    // Sample short video event JSON (kind 34236)
-    const SHORT_VIDEO_EVENT_JSON: &str = r#"{
-        "id": "b376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65",
-        "pubkey": "6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93",
-        "created_at": 1700000001,
-        "kind": 34236,
-        "tags": [
-            ["d", "my-short-id"],
-            ["title", "Short clip"]
-        ],
-        "content": "",
-        "sig": "908a15e46fb4d8675bab026fc230a0e3542bfade63da02d542fb78b2a8513fcd0092619a2c8c1221e581946e0191f2af505dfdf8657a414dbca329186f009262"
-    }"#;
+impl ReactionMeta {
+    /// Extract reaction metadata from a parsed event.
+    ///
+    /// Returns `None` for other kinds and for reactions without a valid `e` tag.
+    /// Per NIP-25 the target is in the last tag of each name, since clients may keep
+    /// the tags of the thread being reacted in.
+    pub fn from_event(event: &ParsedEvent) -> Option<Self> {
+        if event.kind != KIND_REACTION {
+            return None;
+        }
 
-    // Sample strfry stream message
-    const STRFRY_MESSAGE_JSON: &str = r#"{
-        "type": "EVENT",
-        "event": {
-            "id": "4376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65",
-            "pubkey": "6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93",
-            "created_at": 1673347337,
-            "kind": 1,
-            "tags": [],
-            "content": "Test",
-            "sig": "908a15e46fb4d8675bab026fc230a0e3542bfade63da02d542fb78b2a8513fcd0092619a2c8c1221e581946e0191f2af505dfdf8657a414dbca329186f009262"
-        },
-        "receivedAt": 1673347338.123,
-        "sourceType": "IP4",
-        "sourceInfo": "192.168.1.1"
-    }"#;
+        let last = |name: &str| {
+            event
+                .get_tags(name)
+                .last()
+                .and_then(|t| t.get(1))
+                .map(|s| s.as_str())
+        };
 
-    mod parsed_event_tests {
-        use super::*;
+        let event_id = last("e").filter(|id| is_hex_id(id))?.to_ascii_lowercase();
+        let content = match event.content.trim() {
+            "" => "+".to_string(),
+            content => content.to_string(),
+        };
 
-        #[test]
-        fn from_json_valid_event() {
-            let event = ParsedEvent::from_json(VALID_EVENT_JSON).unwrap();
+        Some(Self {
+            event_id,
+            address: last("a").and_then(EventAddress::from_coordinate),
+            author: last("p")
+                .filter(|pubkey| is_hex_id(pubkey))
+                .map(|pubkey| pubkey.to_ascii_lowercase()),
+            content,
+        })
+    }
 
-            assert_eq!(
-                event.id,
-                "4376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65"
-            );
-            assert_eq!(
-                event.pubkey,
-                "6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93"
-            );
-            assert_eq!(event.kind, 1);
-            assert_eq!(event.content, "Hello, Nostr!");
-            assert_eq!(event.tags.len(), 2);
-        }
+    /// Whether this is a like (`+`).
+    pub fn is_like(&self) -> bool {
+        self.content == "+"
+    }
 
-        #[test]
-        fn from_json_video_event() {
-            let event = ParsedEvent::from_json(VIDEO_EVENT_JSON).unwrap();
+    /// Whether this is a dislike (`-`).
+    pub fn is_dislike(&self) -> bool {
+        self.content == "-"
+    }
+}
 
-            assert_eq!(event.kind, KIND_VIDEO);
-            assert!(event.is_video());
-            assert_eq!(event.tags.len(), 7);
+/// Root and parent of a comment (kind 1111) event.
+///
+/// NIP-22 comments point at the root of the thread with uppercase tags (`E`, `A`,
+/// `K`) and at the item they reply to with lowercase ones (`e`, `a`, `k`). For a
+/// top-level comment both point at the root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommentMeta {
+    /// ID of the root event (`E` tag), as lowercase hex.
+    pub root_event_id: Option<String>,
+    /// Address of the root event when it is addressable (`A` tag).
+    pub root_address: Option<EventAddress>,
+    /// Kind of the root event (`K` tag).
+    pub root_kind: Option<u16>,
+    /// ID of the parent event (`e` tag), as lowercase hex.
+    pub parent_event_id: Option<String>,
+    /// Address of the parent event when it is addressable (`a` tag).
+    pub parent_address: Option<EventAddress>,
+    /// Kind of the parent event (`k` tag).
+    pub parent_kind: Option<u16>,
+}
+
+impl CommentMeta {
+    /// Extract comment metadata from a parsed event.
+    ///
+    /// Returns `None` for other kinds and for comments with neither a valid `E` nor
+    /// `A` tag, which can't be placed in a thread.
+    pub fn from_event(event: &ParsedEvent) -> Option<Self> {
+        if event.kind != KIND_COMMENT {
+            return None;
         }
 
-        #[test]
-        fn from_json_short_video_event() {
-            let event = ParsedEvent::from_json(SHORT_VIDEO_EVENT_JSON).unwrap();
+        let event_id = |name: &str| {
+            event
+                .get_tag(name)
+                .filter(|id| is_hex_id(id))
+                .map(|id| id.to_ascii_lowercase())
+        };
+        let address = |name: &str| event.get_tag(name).and_then(EventAddress::from_coordinate);
+        let kind = |name: &str| event.get_tag(name).and_then(|k| k.trim().parse().ok());
 
-            assert_eq!(event.kind, KIND_VIDEO_SHORT);
-            assert!(event.is_video());
-        }
+        let meta = Self {
+            root_event_id: event_id("E"),
+            root_address: address("A"),
+            root_kind: kind("K"),
+            parent_event_id: event_id("e"),
+            parent_address: address("a"),
+            parent_kind: kind("k"),
+        };
+        (meta.root_event_id.is_some() || meta.root_address.is_some()).then_some(meta)
+    }
 
-        #[test]
-        fn from_json_invalid_json() {
-            let result = ParsedEvent::from_json("not json");
-            assert!(result.is_err());
-        }
+    /// Whether the thread's root is a video, by its `K` tag or its address.
+    pub fn is_on_video(&self) -> bool {
+        let kind = self
+            .root_kind
+            .or_else(|| self.root_address.as_ref().map(|a| a.kind));
+        matches!(kind, Some(KIND_VIDEO | KIND_VIDEO_SHORT))
+    }
 
-        #[test]
-        fn from_json_missing_fields() {
-            let result = ParsedEvent::from_json(r#"{"id": "abc"}"#);
-            assert!(result.is_err());
-        }
+    /// Whether this replies to another comment rather than to the root.
+    pub fn is_reply(&self) -> bool {
+        self.parent_kind == Some(KIND_COMMENT)
+    }
+}
 
-        #[test]
-        fn is_video_returns_true_for_video_kinds() {
-            let video = ParsedEvent::from_json(VIDEO_EVENT_JSON).unwrap();
-            let short = ParsedEvent::from_json(SHORT_VIDEO_EVENT_JSON).unwrap();
+/// Target of a repost (kind 6 or 16) event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepostMeta {
+    /// ID of the reposted event, as lowercase hex.
+    pub event_id: String,
+    /// Kind of the reposted event.
+    pub kind: Option<u16>,
+    /// Author of the reposted event, as lowercase hex.
+    pub author: Option<String>,
+    /// Address of the reposted event when it is addressable (`a` tag).
+    pub address: Option<EventAddress>,
+}
 
-            assert!(video.is_video());
-            assert!(short.is_video());
+impl RepostMeta {
+    /// Extract repost metadata from a parsed event.
+    ///
+    /// The target comes from the `e`, `k`, and `p` tags, with anything missing taken
+    /// from the reposted event when it is embedded as JSON in the content. A kind 6
+    /// repost is of a text note. Returns `None` for other kinds and for reposts whose
+    /// target ID is in neither place.
+    pub fn from_event(event: &ParsedEvent) -> Option<Self> {
+        if event.kind != KIND_REPOST && event.kind != KIND_GENERIC_REPOST {
+            return None;
         }
 
-        #[test]
-        fn is_video_returns_false_for_other_kinds() {
-            let event = ParsedEvent::from_json(VALID_EVENT_JSON).unwrap();
-            assert!(!event.is_video());
-        }
+        let embedded = serde_json::from_str::<Event>(&event.content).ok();
+        let event_id = event
+            .get_tag("e")
+            .filter(|id| is_hex_id(id))
+            .map(|id| id.to_ascii_lowercase())
+            .or_else(|| embedded.as_ref().map(|e| e.id.to_hex()))?;
+        let kind = match event.kind {
+            KIND_REPOST => Some(1),
+            _ => event.get_tag("k").and_then(|k| k.trim().parse().ok()),
+        };
 
-        #[test]
-        fn get_tag_returns_first_matching_tag() {
-            let event = ParsedEvent::from_json(VIDEO_EVENT_JSON).unwrap();
+        Some(Self {
+            event_id,
+            kind: kind.or_else(|| embedded.as_ref().map(|e| e.kind.as_u16())),
+            author: event
+                .get_tag("p")
+                .filter(|pubkey| is_hex_id(pubkey))
+                .map(|pubkey| pubkey.to_ascii_lowercase())
+                .or_else(|| embedded.as_ref().map(|e| e.pubkey.to_hex())),
+            address: event.get_tag("a").and_then(EventAddress::from_coordinate),
+        })
+    }
+}
+
+/// Metadata of a media file, from a file metadata (kind 1063) event or an `imeta`
+/// tag, which share their field names.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileMeta {
+    pub url: Option<String>,
+    /// MIME type (`m`).
+    pub mime_type: Option<String>,
+    /// SHA-256 of the file (`x`), as lowercase hex.
+    pub sha256: Option<String>,
+    /// SHA-256 of the file before the server transformed it (`ox`), as lowercase hex.
+    pub original_sha256: Option<String>,
+    /// Size in bytes.
+    pub size: Option<u64>,
+    /// Dimensions as `<width>x<height>`.
+    pub dim: Option<String>,
+    /// BlurHash placeholder to show while the file loads.
+    pub blurhash: Option<String>,
+    pub thumb: Option<String>,
+    pub alt: Option<String>,
+}
+
+impl FileMeta {
+    /// Extract file metadata from a parsed event; `None` for other kinds.
+    pub fn from_event(event: &ParsedEvent) -> Option<Self> {
+        (event.kind == KIND_FILE_METADATA).then(|| Self::from_tags(&event.tags))
+    }
+
+    /// Extract file metadata from the tags of a file metadata event.
+    pub fn from_tags(tags: &[Vec<String>]) -> Self {
+        Self::from_fields(|name| {
+            tags.iter()
+                .find(|t| t.first().map(|s| s.as_str()) == Some(name))
+                .and_then(|t| t.get(1).map(|s| s.as_str()))
+        })
+    }
+
+    /// Extract file metadata from the entries of an `imeta` tag.
+    pub fn from_imeta(imeta: &Imeta) -> Self {
+        Self::from_fields(|key| imeta.get(key))
+    }
+
+    fn from_fields<'a>(field: impl Fn(&str) -> Option<&'a str>) -> Self {
+        let text = |name: &str| {
+            field(name)
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+        let hash = |name: &str| {
+            field(name)
+                .filter(|hash| is_hex_id(hash))
+                .map(|hash| hash.to_ascii_lowercase())
+        };
+
+        Self {
+            url: text("url"),
+            mime_type: text("m"),
+            sha256: hash("x"),
+            original_sha256: hash("ox"),
+            size: field("size").and_then(|size| size.trim().parse().ok()),
+            dim: text("dim"),
+            blurhash: text("blurhash"),
+            thumb: text("thumb"),
+            alt: text("alt"),
+        }
+    }
+}
+
+/// Target and amount of a zap receipt (kind 9735) event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZapMeta {
+    /// ID of the zapped event (`e` tag), as lowercase hex; `None` for profile zaps.
+    pub event_id: Option<String>,
+    /// Address of the zapped event when it is addressable (`a` tag).
+    pub address: Option<EventAddress>,
+    /// Pubkey that received the zap (`p` tag), as lowercase hex.
+    pub recipient: String,
+    /// Pubkey that sent the zap (`P` tag), as lowercase hex.
+    pub sender: Option<String>,
+    /// Lightning invoice that was paid (`bolt11` tag).
+    pub bolt11: String,
+    /// Amount of the invoice in millisats; `None` when the invoice has no amount or
+    /// can't be read.
+    pub amount_msats: Option<u64>,
+}
+
+impl ZapMeta {
+    /// Extract zap metadata from a parsed event.
+    ///
+    /// Returns `None` for other kinds and for receipts without a valid `p` tag or a
+    /// `bolt11` tag.
+    pub fn from_event(event: &ParsedEvent) -> Option<Self> {
+        if event.kind != KIND_ZAP_RECEIPT {
+            return None;
+        }
+
+        let pubkey = |name: &str| {
+            event
+                .get_tag(name)
+                .filter(|pubkey| is_hex_id(pubkey))
+                .map(|pubkey| pubkey.to_ascii_lowercase())
+        };
+        let bolt11 = event.get_tag("bolt11")?.trim().to_string();
+
+        Some(Self {
+            event_id: event
+                .get_tag("e")
+                .filter(|id| is_hex_id(id))
+                .map(|id| id.to_ascii_lowercase()),
+            address: event.get_tag("a").and_then(EventAddress::from_coordinate),
+            recipient: pubkey("p")?,
+            sender: pubkey("P"),
+            amount_msats: bolt11_amount_msats(&bolt11),
+            bolt11,
+        })
+    }
+}
+
+/// Amount of a BOLT 11 invoice in millisats, from its human-readable part
+/// (`ln` + currency + amount + multiplier, e.g. `lnbc2500u`).
+///
+/// Returns `None` for invoices without an amount and for malformed ones. Only the
+/// amount is read; the invoice's signature isn't checked.
+pub fn bolt11_amount_msats(invoice: &str) -> Option<u64> {
+    let invoice = invoice.trim().to_ascii_lowercase();
+    let invoice = invoice.strip_prefix("lightning:").unwrap_or(&invoice);
+    // The human-readable part ends at the last `1`, the bech32 separator
+    let hrp = invoice.get(..invoice.rfind('1')?)?.strip_prefix("ln")?;
+    let amount = hrp.trim_start_matches(|c: char| c.is_ascii_lowercase());
+    if amount.is_empty() {
+        return None;
+    }
+
+    let (digits, multiplier) = match amount.char_indices().last()? {
+        (i, c) if c.is_ascii_lowercase() => (&amount[..i], Some(c)),
+        _ => (amount, None),
+    };
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let value: u64 = digits.parse().ok()?;
+
+    // Millisats per unit: 1 BTC is 10^11 msat
+    match multiplier {
+        None => value.checked_mul(100_000_000_000),
+        Some('m') => value.checked_mul(100_000_000),
+        Some('u') => value.checked_mul(100_000),
+        Some('n') => value.checked_mul(100),
+        // A pico-bitcoin is a tenth of a millisat, so only multiples of 10 are valid
+        Some('p') if value.is_multiple_of(10) => Some(value / 10),
+        _ => None,
+    }
+}
+
+/// A viewer's watch session on a video, from a watch session (kind 34237) event.
+///
+/// The event is addressable, with the session ID as its `d` tag, so a client can
+/// republish it as the viewer keeps watching and only the latest total counts:
+///
+/// ```json
+/// {
+///   "kind": 34237,
+///   "tags": [
+///     ["d", "<session id>"],
+///     ["a", "34235:<video author pubkey>:<video d tag>"],
+///     ["watched", "42.5"],
+///     ["e", "<video event id>"]
+///   ]
+/// }
+/// ```
+///
+/// `watched` is the total seconds watched in the session. The `e` tag, naming the
+/// version of the video that was watched, is optional.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchMeta {
+    pub session_id: String,
+    /// Coordinate of the watched video.
+    pub video: EventAddress,
+    /// Seconds watched in the session.
+    pub watched_secs: f64,
+    /// ID of the watched version of the video, as lowercase hex.
+    pub video_event_id: Option<String>,
+}
+
+impl WatchMeta {
+    /// Extract a watch session from a parsed event.
+    ///
+    /// Returns `None` for other kinds, and for sessions without a `d` tag, an `a`
+    /// tag addressing a video, or a finite, non-negative `watched` value.
+    pub fn from_event(event: &ParsedEvent) -> Option<Self> {
+        if event.kind != KIND_WATCH_SESSION {
+            return None;
+        }
+
+        let session_id = event.get_tag("d").filter(|d| !d.is_empty())?.to_string();
+        let video = event
+            .get_tag("a")
+            .and_then(EventAddress::from_coordinate)
+            .filter(|address| matches!(address.kind, KIND_VIDEO | KIND_VIDEO_SHORT))?;
+        let watched_secs = event
+            .get_tag("watched")
+            .and_then(|s| s.trim().parse::<f64>().ok())
+            .filter(|secs| secs.is_finite() && *secs >= 0.0)?;
+
+        Some(Self {
+            session_id,
+            video,
+            watched_secs,
+            video_event_id: event
+                .get_tag("e")
+                .filter(|id| is_hex_id(id))
+                .map(|id| id.to_ascii_lowercase()),
+        })
+    }
+}
+
+/// State of a live stream per its `status` tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LiveStatus {
+    Planned,
+    Live,
+    Ended,
+}
+
+impl LiveStatus {
+    /// Parse a `status` tag value; `None` for anything else.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "planned" => Some(Self::Planned),
+            "live" => Some(Self::Live),
+            "ended" => Some(Self::Ended),
+            _ => None,
+        }
+    }
+}
+
+/// A pubkey taking part in a live stream (`p` tag).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Participant {
+    /// Lowercase hex pubkey.
+    pub pubkey: String,
+    pub relay: Option<String>,
+    /// Role such as `Host`, `Speaker`, or `Participant`.
+    pub role: Option<String>,
+}
+
+/// Metadata of a live stream (kind 30311) event.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LiveStreamMeta {
+    pub d_tag: String,
+    pub title: Option<String>,
+    pub summary: Option<String>,
+    pub image: Option<String>,
+    /// URL the stream is watched at (`streaming` tag).
+    pub streaming_url: Option<String>,
+    /// URL of the recording once the stream has ended (`recording` tag).
+    pub recording_url: Option<String>,
+    pub starts: Option<DateTime<Utc>>,
+    pub ends: Option<DateTime<Utc>>,
+    /// `None` when the tag is missing or not a known status.
+    pub status: Option<LiveStatus>,
+    pub current_participants: Option<u64>,
+    pub total_participants: Option<u64>,
+    /// Participants in tag order; `p` tags without a hex pubkey are skipped.
+    pub participants: Vec<Participant>,
+    pub hashtags: Vec<String>,
+}
+
+impl LiveStreamMeta {
+    /// Extract live stream metadata from a parsed event.
+    ///
+    /// Returns `None` for other kinds and for streams without a `d` tag.
+    pub fn from_event(event: &ParsedEvent) -> Option<Self> {
+        if event.kind != KIND_LIVE_STREAM {
+            return None;
+        }
+
+        let tag = |name: &str| event.get_tag(name).map(|s| s.to_string());
+        let timestamp = |name: &str| {
+            event
+                .get_tag(name)
+                .and_then(|s| s.trim().parse::<i64>().ok())
+                .and_then(|secs| DateTime::from_timestamp(secs, 0))
+        };
+        let count = |name: &str| event.get_tag(name).and_then(|s| s.trim().parse().ok());
+
+        Some(Self {
+            d_tag: event.get_tag("d")?.to_string(),
+            title: tag("title"),
+            summary: tag("summary"),
+            image: tag("image"),
+            streaming_url: tag("streaming"),
+            recording_url: tag("recording"),
+            starts: timestamp("starts"),
+            ends: timestamp("ends"),
+            status: event.get_tag("status").and_then(LiveStatus::parse),
+            current_participants: count("current_participants"),
+            total_participants: count("total_participants"),
+            participants: event
+                .get_tags("p")
+                .iter()
+                .filter_map(|t| {
+                    let pubkey = t.get(1).filter(|pubkey| is_hex_id(pubkey))?;
+                    let optional = |i: usize| t.get(i).filter(|s| !s.is_empty()).cloned();
+                    Some(Participant {
+                        pubkey: pubkey.to_ascii_lowercase(),
+                        relay: optional(2),
+                        role: optional(3),
+                    })
+                })
+                .collect(),
+            hashtags: event
+                .get_tags("t")
+                .iter()
+                .filter_map(|t| t.get(1).map(|s| s.to_string()))
+                .collect(),
+        })
+    }
+}
+
+/// Address of a parameterized replaceable event, as used in `a` tags and `naddr`s.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EventAddress {
+    pub kind: u16,
+    pub pubkey: String,
+    pub identifier: String,
+}
+
+impl EventAddress {
+    /// Parse a `<kind>:<pubkey>:<d-tag>` coordinate, an `naddr`, or a `nostr:` URI.
+    pub fn parse(s: &str) -> Option<Self> {
+        if let Some(address) = Self::from_coordinate(s) {
+            return Some(address);
+        }
+
+        let coordinate = Coordinate::parse(s).ok()?;
+        Some(Self {
+            kind: coordinate.kind.as_u16(),
+            pubkey: coordinate.public_key.to_hex(),
+            identifier: coordinate.identifier,
+        })
+    }
+
+    /// Parse a `<kind>:<pubkey>:<d-tag>` coordinate. The d-tag may contain colons.
+    fn from_coordinate(s: &str) -> Option<Self> {
+        let mut parts = s.splitn(3, ':');
+        let kind = parts.next()?.parse().ok()?;
+        let pubkey = PublicKey::from_hex(parts.next()?).ok()?;
+        let identifier = parts.next()?;
+        Some(Self {
+            kind,
+            pubkey: pubkey.to_hex(),
+            identifier: identifier.to_string(),
+        })
+    }
+
+    /// Encode as an `naddr` without relay hints.
+    pub fn to_naddr(&self) -> Option<String> {
+        let mut coordinate = Coordinate::new(
+            Kind::from(self.kind),
+            PublicKey::from_hex(&self.pubkey).ok()?,
+        );
+        coordinate.identifier = self.identifier.clone();
+        coordinate.to_bech32().ok()
+    }
+}
+
+impl fmt::Display for EventAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.kind, self.pubkey, self.identifier)
+    }
+}
+
+/// A video listed in a video set.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum VideoRef {
+    /// A video addressed by kind, author, and d-tag (`a` tag), resolving to its
+    /// latest version.
+    Address(EventAddress),
+    /// A specific video event (`e` tag).
+    Event(String),
+}
+
+/// Metadata and entries of a NIP-51 video set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VideoSet {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image: Option<String>,
+    /// Listed videos in tag order.
+    pub videos: Vec<VideoRef>,
+}
+
+impl VideoSet {
+    /// Extract a video set from a parsed event; `None` for other kinds.
+    pub fn from_event(event: &ParsedEvent) -> Option<Self> {
+        (event.kind == KIND_VIDEO_SET).then(|| Self::from_tags(&event.tags))
+    }
+
+    /// Extract a video set from its event tags.
+    ///
+    /// `a` tags must address a video kind and `e` tags must hold a hex event ID;
+    /// anything else is skipped.
+    pub fn from_tags(tags: &[Vec<String>]) -> Self {
+        let value = |name: &str| {
+            tags.iter()
+                .find(|t| t.first().map(|s| s.as_str()) == Some(name))
+                .and_then(|t| t.get(1).cloned())
+        };
+
+        let videos = tags
+            .iter()
+            .filter_map(|tag| match (tag.first()?.as_str(), tag.get(1)?) {
+                ("a", value) => EventAddress::from_coordinate(value)
+                    .filter(|a| a.kind == KIND_VIDEO || a.kind == KIND_VIDEO_SHORT)
+                    .map(VideoRef::Address),
+                ("e", value) if is_hex_id(value) => {
+                    Some(VideoRef::Event(value.to_ascii_lowercase()))
+                }
+                _ => None,
+            })
+            .collect();
+
+        Self {
+            // Older clients name sets with a `name` tag
+            title: value("title").or_else(|| value("name")),
+            description: value("description"),
+            image: value("image"),
+            videos,
+        }
+    }
+}
+
+/// Whether `s` is a 32-byte hex event ID or pubkey.
+fn is_hex_id(s: &str) -> bool {
+    s.len() == 64 && s.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Normalize an event ID given as hex (any case), `note`, `nevent`, or a `nostr:` URI
+/// to lowercase hex.
+pub fn normalize_event_id(s: &str) -> Option<String> {
+    if is_hex_id(s) {
+        return Some(s.to_ascii_lowercase());
+    }
+
+    match Nip19::from_bech32(s.strip_prefix("nostr:").unwrap_or(s)).ok()? {
+        Nip19::EventId(id) => Some(id.to_hex()),
+        Nip19::Event(event) => Some(event.event_id.to_hex()),
+        _ => None,
+    }
+}
+
+/// Normalize a pubkey given as hex (any case), `npub`, `nprofile`, or a `nostr:` URI
+/// to lowercase hex.
+pub fn normalize_pubkey(s: &str) -> Option<String> {
+    if is_hex_id(s) {
+        return Some(s.to_ascii_lowercase());
+    }
+
+    match Nip19::from_bech32(s.strip_prefix("nostr:").unwrap_or(s)).ok()? {
+        Nip19::Pubkey(pubkey) => Some(pubkey.to_hex()),
+        Nip19::Profile(profile) => Some(profile.public_key.to_hex()),
+        _ => None,
+    }
+}
+
+/// Normalize a relay URL: `ws`/`wss` scheme and host lowercased, default port and
+/// trailing slash dropped. `None` if it isn't a websocket URL with a plain host.
+pub fn normalize_relay_url(url: &str) -> Option<String> {
+    let (scheme, rest) = url.trim().split_once("://")?;
+    let scheme = scheme.to_ascii_lowercase();
+    let default_port = match scheme.as_str() {
+        "ws" => ":80",
+        "wss" => ":443",
+        _ => return None,
+    };
+
+    let rest = rest.split('#').next().unwrap_or_default();
+    let (host, path) = rest.split_at(rest.find(['/', '?']).unwrap_or(rest.len()));
+    let host = host.to_ascii_lowercase();
+    let host = host.strip_suffix(default_port).unwrap_or(&host);
+    if host.is_empty()
+        || !host
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b".-:[]".contains(&b))
+        || path.bytes().any(|b| b.is_ascii_whitespace())
+    {
+        return None;
+    }
+
+    Some(format!("{scheme}://{host}{}", path.trim_end_matches('/')))
+}
+
+/// Encode a hex pubkey as an `npub`.
+pub fn encode_npub(pubkey: &str) -> Option<String> {
+    PublicKey::from_hex(pubkey).ok()?.to_bech32().ok()
+}
+
+/// Encode a hex event ID as a `note`.
+pub fn encode_note(event_id: &str) -> Option<String> {
+    EventId::from_hex(event_id).ok()?.to_bech32().ok()
+}
+
+/// Encode a hex event ID as an `nevent`, with its author and relay hints when known.
+pub fn encode_nevent(event_id: &str, author: Option<&str>, relays: &[String]) -> Option<String> {
+    let mut nevent = Nip19Event::new(EventId::from_hex(event_id).ok()?, relays);
+    if let Some(author) = author {
+        nevent = nevent.author(PublicKey::from_hex(author).ok()?);
+    }
+    nevent.to_bech32().ok()
+}
+
+/// A line of strfry's JSONL output (`strfry stream`, router and plugin feeds),
+/// by its `type`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type")]
+pub enum StrfryMessage {
+    /// An event strfry stored (`EVENT`, or `new` in plugin input).
+    #[serde(rename = "EVENT", alias = "new")]
+    Event(Box<StrfryEvent>),
+
+    /// strfry deleted an event.
+    #[serde(rename = "DELETE")]
+    Delete {
+        /// ID of the deleted event.
+        #[serde(default)]
+        id: String,
+    },
+
+    /// A relay's answer to an event it was sent, as in a NIP-01 `OK`.
+    #[serde(rename = "OK")]
+    Ok {
+        #[serde(default)]
+        id: String,
+        #[serde(default)]
+        accepted: bool,
+        #[serde(default)]
+        message: String,
+    },
+
+    /// A message type this version doesn't know.
+    #[serde(other)]
+    Other,
+}
+
+/// An event in a strfry `EVENT` message, with where and when strfry got it.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StrfryEvent {
+    pub event: Event,
+    pub received_at: Option<f64>,
+    pub source_type: Option<String>,
+    pub source_info: Option<String>,
+}
+
+impl StrfryMessage {
+    /// Parse from JSON line.
+    pub fn from_json(json: &str) -> Result<Self, ParseError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// The event this message carries, if it's an `EVENT`.
+    pub fn event(&self) -> Option<&StrfryEvent> {
+        match self {
+            Self::Event(event) => Some(event),
+            _ => None,
+        }
+    }
+
+    /// Convert the carried event to a ParsedEvent; `None` for other message types.
+    pub fn to_parsed_event(&self) -> Option<ParsedEvent> {
+        self.event().map(StrfryEvent::to_parsed_event)
+    }
+}
+
+impl StrfryEvent {
+    /// Recompute the event ID and check the signature.
+    pub fn verify(&self) -> Result<(), VerifyError> {
+        verify_event(&self.event)
+    }
+
+    /// Convert to ParsedEvent, keeping the time strfry received the event.
+    pub fn to_parsed_event(&self) -> ParsedEvent {
+        let event = ParsedEvent::from_event(&self.event);
+        match self
+            .received_at
+            .and_then(|secs| DateTime::from_timestamp_millis((secs * 1000.0).round() as i64))
+        {
+            Some(received_at) => event.with_received_at(received_at),
+            None => event,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_video_kind_constants() {
+        assert_eq!(KIND_VIDEO, 34235);
+        assert_eq!(KIND_VIDEO_SHORT, 34236);
+    }
+
+    // Sample valid Nostr event JSON (kind 1 text note)
+    const VALID_EVENT_JSON: &str = r#"{
+        "id": "4376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65",
+        "pubkey": "6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93",
+        "created_at": 1673347337,
+        "kind": 1,
+        "tags": [
+            ["e", "3da979448d9ba263864c4d6f14984c423a3838364ec255f03c7904b1ae77f206"],
+            ["p", "bf2376e17ba4ec269d10fcc996a4746b451152be9031fa48e74553dde5526bce"]
+        ],
+        "content": "Hello, Nostr!",
+        "sig": "908a15e46fb4d8675bab026fc230a0e3542bfade63da02d542fb78b2a8513fcd0092619a2c8c1221e581946e0191f2af505dfdf8657a414dbca329186f009262"
+    }"#;
+
+    // Sample video event JSON (kind 34235)
+    // Uses the same valid pubkey/sig from VALID_EVENT_JSON for simplicity
+    const VIDEO_EVENT_JSON: &str = r#"{
+        "id": "a376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65",
+        "pubkey": "6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93",
+        "created_at": 1700000000,
+        "kind": 34235,
+        "tags": [
+            ["d", "my-video-id"],
+            ["title", "My Cool Video"],
+            ["thumb", "https://example.com/thumb.jpg"],
+            ["url", "https://example.com/video.mp4"],
+            ["t", "nostr"],
+            ["t", "bitcoin"],
+            ["p", "bf2376e17ba4ec269d10fcc996a4746b451152be9031fa48e74553dde5526bce"]
+        ],
+        "content": "Check out my video!",
+        "sig": "908a15e46fb4d8675bab026fc230a0e3542bfade63da02d542fb78b2a8513fcd0092619a2c8c1221e581946e0191f2af505dfdf8657a414dbca329186f009262"
+    }"#;
+
+    // Sample short video event JSON (kind 34236)
+    const SHORT_VIDEO_EVENT_JSON: &str = r#"{
+        "id": "b376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65",
+        "pubkey": "6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93",
+        "created_at": 1700000001,
+        "kind": 34236,
+        "tags": [
+            ["d", "my-short-id"],
+            ["title", "Short clip"]
+        ],
+        "content": "",
+        "sig": "908a15e46fb4d8675bab026fc230a0e3542bfade63da02d542fb78b2a8513fcd0092619a2c8c1221e581946e0191f2af505dfdf8657a414dbca329186f009262"
+    }"#;
+
+    // Sample strfry stream message
+    const STRFRY_MESSAGE_JSON: &str = r#"{
+        "type": "EVENT",
+        "event": {
+            "id": "4376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65",
+            "pubkey": "6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93",
+            "created_at": 1673347337,
+            "kind": 1,
+            "tags": [],
+            "content": "Test",
+            "sig": "908a15e46fb4d8675bab026fc230a0e3542bfade63da02d542fb78b2a8513fcd0092619a2c8c1221e581946e0191f2af505dfdf8657a414dbca329186f009262"
+        },
+        "receivedAt": 1673347338.123,
+        "sourceType": "IP4",
+        "sourceInfo": "192.168.1.1"
+    }"#;
+
+    mod parsed_event_tests {
+        use super::*;
+
+        #[test]
+        fn from_json_valid_event() {
+            let event = ParsedEvent::from_json(VALID_EVENT_JSON).unwrap();
+
+            assert_eq!(
+                event.id,
+                "4376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65"
+            );
+            assert_eq!(
+                event.pubkey,
+                "6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93"
+            );
+            assert_eq!(event.kind, 1);
+            assert_eq!(event.content, "Hello, Nostr!");
+            assert_eq!(event.tags.len(), 2);
+        }
+
+        #[test]
+        fn from_json_video_event() {
+            let event = ParsedEvent::from_json(VIDEO_EVENT_JSON).unwrap();
+
+            assert_eq!(event.kind, KIND_VIDEO);
+            assert!(event.is_video());
+            assert_eq!(event.tags.len(), 7);
+        }
+
+        #[test]
+        fn from_json_short_video_event() {
+            let event = ParsedEvent::from_json(SHORT_VIDEO_EVENT_JSON).unwrap();
+
+            assert_eq!(event.kind, KIND_VIDEO_SHORT);
+            assert!(event.is_video());
+        }
+
+        #[test]
+        fn from_json_invalid_json() {
+            let result = ParsedEvent::from_json("not json");
+            assert_eq!(result.unwrap_err().code().as_str(), "FNL-PROTO-001");
+        }
+
+        #[test]
+        fn from_json_missing_fields() {
+            let result = ParsedEvent::from_json(r#"{"id": "abc"}"#);
+            assert!(result.is_err());
+        }
+
+        #[test]
+        fn is_video_returns_true_for_video_kinds() {
+            let video = ParsedEvent::from_json(VIDEO_EVENT_JSON).unwrap();
+            let short = ParsedEvent::from_json(SHORT_VIDEO_EVENT_JSON).unwrap();
+
+            assert!(video.is_video());
+            assert!(short.is_video());
+        }
+
+        #[test]
+        fn is_video_returns_false_for_other_kinds() {
+            let event = ParsedEvent::from_json(VALID_EVENT_JSON).unwrap();
+            assert!(!event.is_video());
+        }
+
+        #[test]
+        fn get_tag_returns_first_matching_tag() {
+            let event = ParsedEvent::from_json(VIDEO_EVENT_JSON).unwrap();
+
+            assert_eq!(event.get_tag("d"), Some("my-video-id"));
+            assert_eq!(event.get_tag("title"), Some("My Cool Video"));
+            assert_eq!(
+                event.get_tag("thumb"),
+                Some("https://example.com/thumb.jpg")
+            );
+            assert_eq!(event.get_tag("url"), Some("https://example.com/video.mp4"));
+        }
+
+        #[test]
+        fn pow_difficulty_counts_leading_zero_bits() {
+            let mut event = ParsedEvent::from_json(VIDEO_EVENT_JSON).unwrap();
+            assert_eq!(event.pow_difficulty(), 0);
+
+            // Example from NIP-13: 0x000000000e9d... has 36 leading zero bits
+            event.id =
+                "000000000e9d97a1ab09fc381030b346cdd7a142ad57e6df0b46dc9bef6c7e2d".to_string();
+            assert_eq!(event.pow_difficulty(), 36);
+            event.id = "002f".to_string() + &"f".repeat(60);
+            assert_eq!(event.pow_difficulty(), 10);
+            event.id = "0".repeat(64);
+            assert_eq!(event.pow_difficulty(), 256);
+            event.id = "00zz".to_string();
+            assert_eq!(event.pow_difficulty(), 0);
+        }
+
+        #[test]
+        fn is_expired_compares_expiration_tag() {
+            let mut event = ParsedEvent::from_json(VIDEO_EVENT_JSON).unwrap();
+            let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+            assert_eq!(event.expiration(), None);
+            assert!(!event.is_expired(now));
+
+            event
+                .tags
+                .push(vec!["expiration".to_string(), "1700000000".to_string()]);
+            assert_eq!(event.expiration(), Some(now));
+            assert!(event.is_expired(now));
+            assert!(!event.is_expired(now - chrono::TimeDelta::seconds(1)));
+
+            event.tags.last_mut().unwrap()[1] = "soon".to_string();
+            assert_eq!(event.expiration(), None);
+            assert!(!event.is_expired(now));
+        }
+
+        #[test]
+        fn coordinate_addresses_addressable_kinds() {
+            let video = ParsedEvent::from_json(VIDEO_EVENT_JSON).unwrap();
+            let coordinate = video.coordinate().unwrap();
 
-            assert_eq!(event.get_tag("d"), Some("my-video-id"));
-            assert_eq!(event.get_tag("title"), Some("My Cool Video"));
             assert_eq!(
-                event.get_tag("thumb"),
-                Some("https://example.com/thumb.jpg")
+                coordinate.to_string(),
+                "34235:6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93:my-video-id"
+            );
+            assert_eq!(
+                EventAddress::parse(&coordinate.to_string()),
+                Some(coordinate)
+            );
+
+            let mut set = ParsedEvent::from_json(VALID_EVENT_JSON).unwrap();
+            assert_eq!(set.coordinate(), None);
+            set.kind = KIND_VIDEO_SET;
+            set.tags.clear();
+            assert_eq!(set.coordinate().unwrap().identifier, "");
+        }
+
+        #[test]
+        fn get_tag_returns_none_for_missing_tag() {
+            let event = ParsedEvent::from_json(VALID_EVENT_JSON).unwrap();
+            assert_eq!(event.get_tag("nonexistent"), None);
+        }
+
+        #[test]
+        fn get_tags_returns_all_matching_tags() {
+            let event = ParsedEvent::from_json(VIDEO_EVENT_JSON).unwrap();
+
+            let t_tags = event.get_tags("t");
+            assert_eq!(t_tags.len(), 2);
+            assert_eq!(t_tags[0], &["t", "nostr"]);
+            assert_eq!(t_tags[1], &["t", "bitcoin"]);
+        }
+
+        #[test]
+        fn parsed_tags_reads_all_tags() {
+            let event = ParsedEvent::from_json(VIDEO_EVENT_JSON).unwrap();
+            let tags = event.parsed_tags().unwrap();
+
+            assert_eq!(tags.len(), 7);
+            assert_eq!(tags[0], ParsedTag::D("my-video-id".to_string()));
+            assert_eq!(tags[4], ParsedTag::Hashtag("nostr".to_string()));
+            assert!(matches!(tags[6], ParsedTag::PubkeyRef { .. }));
+
+            let mut event = event;
+            event
+                .tags
+                .push(vec!["e".to_string(), "not-an-id".to_string()]);
+            assert!(matches!(
+                event.parsed_tags(),
+                Err(ParseError::InvalidTag(_))
+            ));
+        }
+
+        #[test]
+        fn mentions_and_event_refs_skip_malformed_tags() {
+            let mut event = ParsedEvent::from_json(VIDEO_EVENT_JSON).unwrap();
+            let id = "a376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65";
+            for tag in [
+                &["p", "not-a-pubkey"][..],
+                &["e", id, "wss://relay.example.com", "root"],
+                &["e", "not-an-id"],
+                &["e", &id.to_uppercase()],
+            ] {
+                event.tags.push(tag.iter().map(|s| s.to_string()).collect());
+            }
+
+            assert_eq!(
+                event.mentions(),
+                vec![Mention {
+                    pubkey: "bf2376e17ba4ec269d10fcc996a4746b451152be9031fa48e74553dde5526bce"
+                        .to_string(),
+                    relay: None,
+                }]
+            );
+            assert_eq!(
+                event.event_refs(),
+                vec![
+                    EventRef {
+                        id: id.to_string(),
+                        relay: Some("wss://relay.example.com".to_string()),
+                        marker: Some("root".to_string()),
+                    },
+                    EventRef {
+                        id: id.to_string(),
+                        relay: None,
+                        marker: None,
+                    },
+                ]
+            );
+        }
+
+        #[test]
+        fn get_tags_returns_empty_for_no_matches() {
+            let event = ParsedEvent::from_json(VALID_EVENT_JSON).unwrap();
+            let tags = event.get_tags("nonexistent");
+            assert!(tags.is_empty());
+        }
+
+        #[test]
+        fn created_at_is_parsed_correctly() {
+            let event = ParsedEvent::from_json(VALID_EVENT_JSON).unwrap();
+            assert_eq!(event.created_at.timestamp(), 1673347337);
+        }
+
+        fn signed(content: &str) -> ParsedEvent {
+            let event = nostr::EventBuilder::new(Kind::from(KIND_VIDEO), content)
+                .tags([Tag::identifier("my-video-id"), Tag::hashtag("nostr")])
+                .sign_with_keys(&nostr::Keys::generate())
+                .unwrap();
+            ParsedEvent::from_event(&event)
+        }
+
+        #[test]
+        fn verify_accepts_signed_event() {
+            assert!(signed("Check out my video!").verify().is_ok());
+        }
+
+        #[test]
+        fn verify_rejects_tampered_event() {
+            let mut event = signed("Check out my video!");
+            event.content = "Check out my other video!".to_string();
+            assert_eq!(event.verify().unwrap_err().code().as_str(), "FNL-PROTO-004");
+
+            let mut event = signed("Check out my video!");
+            event.sig = signed("Something else").sig;
+            assert_eq!(event.verify().unwrap_err().code().as_str(), "FNL-PROTO-005");
+
+            let mut event = signed("Check out my video!");
+            event.sig = "not-a-signature".to_string();
+            assert!(matches!(
+                event.verify(),
+                Err(VerifyError::Malformed("signature"))
+            ));
+        }
+    }
+
+    mod video_meta_tests {
+        use super::*;
+
+        #[test]
+        fn from_event_extracts_all_fields() {
+            let event = ParsedEvent::from_json(VIDEO_EVENT_JSON).unwrap();
+            let meta = VideoMeta::from_event(&event).unwrap();
+
+            assert_eq!(meta.d_tag, "my-video-id");
+            assert_eq!(meta.title, Some("My Cool Video".to_string()));
+            assert_eq!(
+                meta.thumbnail,
+                Some("https://example.com/thumb.jpg".to_string())
+            );
+            assert_eq!(
+                meta.video_url,
+                Some("https://example.com/video.mp4".to_string())
+            );
+            assert_eq!(meta.hashtags, vec!["nostr", "bitcoin"]);
+        }
+
+        #[test]
+        fn from_event_handles_minimal_video() {
+            let event = ParsedEvent::from_json(SHORT_VIDEO_EVENT_JSON).unwrap();
+            let meta = VideoMeta::from_event(&event).unwrap();
+
+            assert_eq!(meta.d_tag, "my-short-id");
+            assert_eq!(meta.title, Some("Short clip".to_string()));
+            assert_eq!(meta.thumbnail, None);
+            assert_eq!(meta.video_url, None);
+            assert!(meta.hashtags.is_empty());
+            assert_eq!(meta.duration_secs, None);
+            assert_eq!(meta.published_at, None);
+            assert_eq!(meta.alt, None);
+            assert_eq!(meta.content_warning, None);
+            assert_eq!(meta.language, None);
+            assert_eq!(meta.geohash, None);
+        }
+
+        #[test]
+        fn from_event_extracts_language_and_geohash() {
+            let json = r#"{
+                "id": "e376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65",
+                "pubkey": "6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93",
+                "created_at": 1700000000,
+                "kind": 34235,
+                "tags": [
+                    ["d", "test"],
+                    ["L", "ISO-639-1"],
+                    ["l", "english", "ISO-639-1"],
+                    ["l", "music", "genre"],
+                    ["l", "EN", "ISO-639-1"],
+                    ["g", "u4pr"],
+                    ["g", "u4pruy"],
+                    ["g", "not a geohash"]
+                ],
+                "content": "",
+                "sig": "908a15e46fb4d8675bab026fc230a0e3542bfade63da02d542fb78b2a8513fcd0092619a2c8c1221e581946e0191f2af505dfdf8657a414dbca329186f009262"
+            }"#;
+
+            let event = ParsedEvent::from_json(json).unwrap();
+            let meta = VideoMeta::from_event(&event).unwrap();
+            assert_eq!(meta.language.as_deref(), Some("en"));
+            assert_eq!(meta.geohash.as_deref(), Some("u4pruy"));
+        }
+
+        #[test]
+        fn from_event_extracts_optional_tags() {
+            let json = r#"{
+                "id": "e376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65",
+                "pubkey": "6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93",
+                "created_at": 1700000000,
+                "kind": 34235,
+                "tags": [
+                    ["d", "test"],
+                    ["duration", "29.5"],
+                    ["published_at", "1699999000"],
+                    ["alt", "A cat chasing a laser pointer"],
+                    ["content-warning", "flashing lights"]
+                ],
+                "content": "",
+                "sig": "908a15e46fb4d8675bab026fc230a0e3542bfade63da02d542fb78b2a8513fcd0092619a2c8c1221e581946e0191f2af505dfdf8657a414dbca329186f009262"
+            }"#;
+
+            let event = ParsedEvent::from_json(json).unwrap();
+            let meta = VideoMeta::from_event(&event).unwrap();
+            assert_eq!(meta.duration_secs, Some(29.5));
+            assert_eq!(
+                meta.published_at,
+                DateTime::from_timestamp(1_699_999_000, 0)
+            );
+            assert_eq!(meta.alt.as_deref(), Some("A cat chasing a laser pointer"));
+            assert_eq!(meta.content_warning.as_deref(), Some("flashing lights"));
+        }
+
+        #[test]
+        fn from_event_handles_malformed_optional_tags() {
+            let json = r#"{
+                "id": "e376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65",
+                "pubkey": "6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93",
+                "created_at": 1700000000,
+                "kind": 34235,
+                "tags": [
+                    ["d", "test"],
+                    ["duration", "-3"],
+                    ["published_at", "yesterday"],
+                    ["content-warning"]
+                ],
+                "content": "",
+                "sig": "908a15e46fb4d8675bab026fc230a0e3542bfade63da02d542fb78b2a8513fcd0092619a2c8c1221e581946e0191f2af505dfdf8657a414dbca329186f009262"
+            }"#;
+
+            let event = ParsedEvent::from_json(json).unwrap();
+            let meta = VideoMeta::from_event(&event).unwrap();
+            assert_eq!(meta.duration_secs, None);
+            assert_eq!(meta.published_at, None);
+            // A content warning without a reason still marks the video
+            assert_eq!(meta.content_warning.as_deref(), Some(""));
+        }
+
+        #[test]
+        fn from_event_returns_none_for_non_video() {
+            let event = ParsedEvent::from_json(VALID_EVENT_JSON).unwrap();
+            let meta = VideoMeta::from_event(&event);
+            assert!(meta.is_none());
+        }
+
+        #[test]
+        fn from_event_returns_none_without_d_tag() {
+            // Video event without d tag
+            let json = r#"{
+                "id": "c376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65",
+                "pubkey": "6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93",
+                "created_at": 1700000000,
+                "kind": 34235,
+                "tags": [["title", "No D Tag"]],
+                "content": "",
+                "sig": "908a15e46fb4d8675bab026fc230a0e3542bfade63da02d542fb78b2a8513fcd0092619a2c8c1221e581946e0191f2af505dfdf8657a414dbca329186f009262"
+            }"#;
+
+            let event = ParsedEvent::from_json(json).unwrap();
+            let meta = VideoMeta::from_event(&event);
+            assert!(meta.is_none());
+        }
+
+        #[test]
+        fn from_event_uses_thumbnail_fallback() {
+            // Video with "thumbnail" instead of "thumb"
+            let json = r#"{
+                "id": "d376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65",
+                "pubkey": "6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93",
+                "created_at": 1700000000,
+                "kind": 34235,
+                "tags": [
+                    ["d", "test"],
+                    ["thumbnail", "https://example.com/alt-thumb.jpg"]
+                ],
+                "content": "",
+                "sig": "908a15e46fb4d8675bab026fc230a0e3542bfade63da02d542fb78b2a8513fcd0092619a2c8c1221e581946e0191f2af505dfdf8657a414dbca329186f009262"
+            }"#;
+
+            let event = ParsedEvent::from_json(json).unwrap();
+            let meta = VideoMeta::from_event(&event).unwrap();
+            assert_eq!(
+                meta.thumbnail,
+                Some("https://example.com/alt-thumb.jpg".to_string())
+            );
+        }
+    }
+
+    mod profile_meta_tests {
+        use super::*;
+
+        fn profile(kind: u16, content: &str) -> ParsedEvent {
+            ParsedEvent {
+                id: "f376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65".to_string(),
+                pubkey: "6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93"
+                    .to_string(),
+                created_at: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+                kind,
+                content: content.to_string(),
+                sig: String::new(),
+                tags: Vec::new(),
+                received_at: None,
+            }
+        }
+
+        #[test]
+        fn from_event_extracts_fields() {
+            let event = profile(
+                KIND_METADATA,
+                r#"{
+                    "name": "alice",
+                    "display_name": "Alice ",
+                    "picture": "https://example.com/alice.jpg",
+                    "nip05": "alice@example.com",
+                    "about": "Films cats",
+                    "lud16": "alice@wallet.example.com",
+                    "website": "https://example.com"
+                }"#,
+            );
+
+            let meta = ProfileMeta::from_event(&event).unwrap();
+            assert_eq!(
+                meta,
+                ProfileMeta {
+                    name: Some("alice".to_string()),
+                    display_name: Some("Alice".to_string()),
+                    picture: Some("https://example.com/alice.jpg".to_string()),
+                    nip05: Some("alice@example.com".to_string()),
+                    about: Some("Films cats".to_string()),
+                    lud16: Some("alice@wallet.example.com".to_string()),
+                }
+            );
+            assert_eq!(meta.best_name(), Some("Alice"));
+        }
+
+        #[test]
+        fn from_event_tolerates_odd_fields() {
+            let event = profile(
+                KIND_METADATA,
+                r#"{"name": "bob", "displayName": "Bob", "picture": 42, "about": "  "}"#,
+            );
+
+            let meta = ProfileMeta::from_event(&event).unwrap();
+            assert_eq!(meta.display_name.as_deref(), Some("Bob"));
+            assert_eq!(meta.picture, None);
+            assert_eq!(meta.about, None);
+
+            let meta = ProfileMeta::from_event(&profile(KIND_METADATA, r#"{"name": "carol"}"#));
+            assert_eq!(meta.unwrap().best_name(), Some("carol"));
+        }
+
+        #[test]
+        fn from_event_rejects_other_kinds_and_bad_content() {
+            assert!(ProfileMeta::from_event(&profile(1, r#"{"name": "alice"}"#)).is_none());
+            assert!(ProfileMeta::from_event(&profile(KIND_METADATA, "not json")).is_none());
+            assert!(ProfileMeta::from_event(&profile(KIND_METADATA, r#"["alice"]"#)).is_none());
+        }
+    }
+
+    mod deletion_meta_tests {
+        use super::*;
+
+        const AUTHOR: &str = "6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93";
+        const OTHER: &str = "bf2376e17ba4ec269d10fcc996a4746b451152be9031fa48e74553dde5526bce";
+        const VIDEO_ID: &str = "a376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65";
+
+        fn deletion(kind: u16, tags: &[&[&str]]) -> ParsedEvent {
+            ParsedEvent {
+                id: "f376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65".to_string(),
+                pubkey: AUTHOR.to_string(),
+                created_at: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+                kind,
+                content: "posted by mistake".to_string(),
+                sig: String::new(),
+                tags: tags
+                    .iter()
+                    .map(|t| t.iter().map(|s| s.to_string()).collect())
+                    .collect(),
+                received_at: None,
+            }
+        }
+
+        #[test]
+        fn from_event_extracts_targets() {
+            let own = format!("34235:{AUTHOR}:my-video-id");
+            let others = format!("34235:{OTHER}:their-video");
+            let event = deletion(
+                KIND_DELETION,
+                &[
+                    &["e", &VIDEO_ID.to_uppercase()],
+                    &["e", "not-an-id"],
+                    &["a", &own],
+                    &["a", &others],
+                    &["k", "34235"],
+                ],
+            );
+
+            let meta = DeletionMeta::from_event(&event).unwrap();
+            assert_eq!(meta.pubkey, AUTHOR);
+            assert_eq!(meta.created_at, event.created_at);
+            assert_eq!(meta.event_ids, vec![VIDEO_ID.to_string()]);
+            assert_eq!(meta.addresses, vec![EventAddress::parse(&own).unwrap()]);
+            assert_eq!(meta.kinds, vec![KIND_VIDEO]);
+            assert_eq!(meta.reason, "posted by mistake");
+            assert!(meta.targets_video());
+        }
+
+        #[test]
+        fn from_event_without_video_kinds() {
+            let meta = DeletionMeta::from_event(&deletion(
+                KIND_DELETION,
+                &[&["e", VIDEO_ID], &["k", "1"]],
+            ))
+            .unwrap();
+            assert!(meta.addresses.is_empty());
+            assert!(!meta.targets_video());
+        }
+
+        #[test]
+        fn from_event_rejects_other_kinds_and_no_targets() {
+            assert!(DeletionMeta::from_event(&deletion(1, &[&["e", VIDEO_ID]])).is_none());
+            assert!(DeletionMeta::from_event(&deletion(KIND_DELETION, &[])).is_none());
+            let others = format!("34235:{OTHER}:their-video");
+            assert!(
+                DeletionMeta::from_event(&deletion(KIND_DELETION, &[&["a", &others]])).is_none()
+            );
+        }
+    }
+
+    mod reaction_meta_tests {
+        use super::*;
+
+        const TARGET_ID: &str = "a376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65";
+        const THREAD_ID: &str = "3da979448d9ba263864c4d6f14984c423a3838364ec255f03c7904b1ae77f206";
+        const AUTHOR: &str = "6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93";
+
+        fn reaction(kind: u16, content: &str, tags: &[&[&str]]) -> ParsedEvent {
+            ParsedEvent {
+                id: "f376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65".to_string(),
+                pubkey: "bf2376e17ba4ec269d10fcc996a4746b451152be9031fa48e74553dde5526bce"
+                    .to_string(),
+                created_at: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+                kind,
+                content: content.to_string(),
+                sig: String::new(),
+                tags: tags
+                    .iter()
+                    .map(|t| t.iter().map(|s| s.to_string()).collect())
+                    .collect(),
+                received_at: None,
+            }
+        }
+
+        #[test]
+        fn from_event_uses_last_tags() {
+            let address = format!("34235:{AUTHOR}:my-video-id");
+            let event = reaction(
+                KIND_REACTION,
+                "🔥",
+                &[
+                    &["e", THREAD_ID],
+                    &[
+                        "p",
+                        "bf2376e17ba4ec269d10fcc996a4746b451152be9031fa48e74553dde5526bce",
+                    ],
+                    &["e", &TARGET_ID.to_uppercase()],
+                    &["a", &address],
+                    &["p", AUTHOR],
+                ],
+            );
+
+            let meta = ReactionMeta::from_event(&event).unwrap();
+            assert_eq!(meta.event_id, TARGET_ID);
+            assert_eq!(meta.address, EventAddress::parse(&address));
+            assert_eq!(meta.author.as_deref(), Some(AUTHOR));
+            assert_eq!(meta.content, "🔥");
+            assert!(!meta.is_like() && !meta.is_dislike());
+        }
+
+        #[test]
+        fn from_event_treats_empty_content_as_like() {
+            let like = reaction(KIND_REACTION, "", &[&["e", TARGET_ID]]);
+            let dislike = reaction(KIND_REACTION, "-", &[&["e", TARGET_ID]]);
+
+            let like = ReactionMeta::from_event(&like).unwrap();
+            assert!(like.is_like());
+            assert_eq!(like.address, None);
+            assert_eq!(like.author, None);
+            assert!(ReactionMeta::from_event(&dislike).unwrap().is_dislike());
+        }
+
+        #[test]
+        fn from_event_rejects_other_kinds_and_missing_target() {
+            assert!(ReactionMeta::from_event(&reaction(1, "+", &[&["e", TARGET_ID]])).is_none());
+            assert!(ReactionMeta::from_event(&reaction(KIND_REACTION, "+", &[])).is_none());
+            assert!(
+                ReactionMeta::from_event(&reaction(KIND_REACTION, "+", &[&["e", "not-an-id"]]))
+                    .is_none()
+            );
+        }
+    }
+
+    mod comment_meta_tests {
+        use super::*;
+
+        const VIDEO_ID: &str = "a376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65";
+        const COMMENT_ID: &str = "c376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65";
+        const AUTHOR: &str = "6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93";
+
+        fn comment(kind: u16, tags: &[&[&str]]) -> ParsedEvent {
+            ParsedEvent {
+                id: "f376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65".to_string(),
+                pubkey: "bf2376e17ba4ec269d10fcc996a4746b451152be9031fa48e74553dde5526bce"
+                    .to_string(),
+                created_at: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+                kind,
+                content: "Great video!".to_string(),
+                sig: String::new(),
+                tags: tags
+                    .iter()
+                    .map(|t| t.iter().map(|s| s.to_string()).collect())
+                    .collect(),
+                received_at: None,
+            }
+        }
+
+        #[test]
+        fn from_event_extracts_top_level_comment_on_video() {
+            let address = format!("34235:{AUTHOR}:my-video-id");
+            let event = comment(
+                KIND_COMMENT,
+                &[
+                    &["A", &address],
+                    &["E", &VIDEO_ID.to_uppercase()],
+                    &["K", "34235"],
+                    &["P", AUTHOR],
+                    &["a", &address],
+                    &["e", VIDEO_ID],
+                    &["k", "34235"],
+                    &["p", AUTHOR],
+                ],
             );
-            assert_eq!(event.get_tag("url"), Some("https://example.com/video.mp4"));
+
+            let meta = CommentMeta::from_event(&event).unwrap();
+            assert_eq!(meta.root_event_id.as_deref(), Some(VIDEO_ID));
+            assert_eq!(meta.root_address, EventAddress::parse(&address));
+            assert_eq!(meta.root_kind, Some(KIND_VIDEO));
+            assert_eq!(meta.parent_event_id.as_deref(), Some(VIDEO_ID));
+            assert_eq!(meta.parent_address, meta.root_address);
+            assert_eq!(meta.parent_kind, Some(KIND_VIDEO));
+            assert!(meta.is_on_video());
+            assert!(!meta.is_reply());
         }
 
         #[test]
-        fn get_tag_returns_none_for_missing_tag() {
-            let event = ParsedEvent::from_json(VALID_EVENT_JSON).unwrap();
-            assert_eq!(event.get_tag("nonexistent"), None);
+        fn from_event_extracts_reply_to_comment() {
+            let event = comment(
+                KIND_COMMENT,
+                &[
+                    &["E", VIDEO_ID],
+                    &["K", "34236"],
+                    &["e", COMMENT_ID],
+                    &["k", "1111"],
+                ],
+            );
+
+            let meta = CommentMeta::from_event(&event).unwrap();
+            assert_eq!(meta.root_event_id.as_deref(), Some(VIDEO_ID));
+            assert_eq!(meta.root_address, None);
+            assert_eq!(meta.parent_event_id.as_deref(), Some(COMMENT_ID));
+            assert!(meta.is_on_video());
+            assert!(meta.is_reply());
         }
 
         #[test]
-        fn get_tags_returns_all_matching_tags() {
-            let event = ParsedEvent::from_json(VIDEO_EVENT_JSON).unwrap();
+        fn from_event_rejects_other_kinds_and_missing_root() {
+            assert!(CommentMeta::from_event(&comment(1, &[&["E", VIDEO_ID]])).is_none());
+            // Lowercase tags alone don't say what the thread is about
+            assert!(CommentMeta::from_event(&comment(KIND_COMMENT, &[&["e", VIDEO_ID]])).is_none());
+            assert!(
+                CommentMeta::from_event(&comment(KIND_COMMENT, &[&["E", "not-an-id"]])).is_none()
+            );
+        }
+    }
 
-            let t_tags = event.get_tags("t");
-            assert_eq!(t_tags.len(), 2);
-            assert_eq!(t_tags[0], &["t", "nostr"]);
-            assert_eq!(t_tags[1], &["t", "bitcoin"]);
+    mod repost_meta_tests {
+        use super::*;
+
+        const TARGET_ID: &str = "a376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65";
+        const AUTHOR: &str = "6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93";
+
+        fn repost(kind: u16, content: &str, tags: &[&[&str]]) -> ParsedEvent {
+            ParsedEvent {
+                id: "f376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65".to_string(),
+                pubkey: "bf2376e17ba4ec269d10fcc996a4746b451152be9031fa48e74553dde5526bce"
+                    .to_string(),
+                created_at: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+                kind,
+                content: content.to_string(),
+                sig: String::new(),
+                tags: tags
+                    .iter()
+                    .map(|t| t.iter().map(|s| s.to_string()).collect())
+                    .collect(),
+                received_at: None,
+            }
         }
 
         #[test]
-        fn get_tags_returns_empty_for_no_matches() {
-            let event = ParsedEvent::from_json(VALID_EVENT_JSON).unwrap();
-            let tags = event.get_tags("nonexistent");
-            assert!(tags.is_empty());
+        fn from_event_reads_tags() {
+            let address = format!("34235:{AUTHOR}:my-video-id");
+            let event = repost(
+                KIND_GENERIC_REPOST,
+                "",
+                &[
+                    &["e", &TARGET_ID.to_uppercase(), "wss://relay.example.com"],
+                    &["k", "34235"],
+                    &["p", AUTHOR],
+                    &["a", &address],
+                ],
+            );
+
+            let meta = RepostMeta::from_event(&event).unwrap();
+            assert_eq!(meta.event_id, TARGET_ID);
+            assert_eq!(meta.kind, Some(KIND_VIDEO));
+            assert_eq!(meta.author.as_deref(), Some(AUTHOR));
+            assert_eq!(meta.address, EventAddress::parse(&address));
         }
 
         #[test]
-        fn created_at_is_parsed_correctly() {
-            let event = ParsedEvent::from_json(VALID_EVENT_JSON).unwrap();
-            assert_eq!(event.created_at.timestamp(), 1673347337);
+        fn from_event_falls_back_to_embedded_event() {
+            let event = repost(KIND_GENERIC_REPOST, VIDEO_EVENT_JSON, &[]);
+
+            let meta = RepostMeta::from_event(&event).unwrap();
+            assert_eq!(meta.event_id, TARGET_ID);
+            assert_eq!(meta.kind, Some(KIND_VIDEO));
+            assert_eq!(meta.author.as_deref(), Some(AUTHOR));
+            assert_eq!(meta.address, None);
+        }
+
+        #[test]
+        fn from_event_treats_kind_6_as_note_repost() {
+            let meta =
+                RepostMeta::from_event(&repost(KIND_REPOST, "", &[&["e", TARGET_ID]])).unwrap();
+            assert_eq!(meta.kind, Some(1));
+            assert_eq!(meta.author, None);
+        }
+
+        #[test]
+        fn from_event_rejects_other_kinds_and_missing_target() {
+            assert!(RepostMeta::from_event(&repost(1, "", &[&["e", TARGET_ID]])).is_none());
+            assert!(RepostMeta::from_event(&repost(KIND_REPOST, "not json", &[])).is_none());
+            assert!(
+                RepostMeta::from_event(&repost(KIND_REPOST, "", &[&["e", "not-an-id"]])).is_none()
+            );
         }
     }
 
-    mod video_meta_tests {
+    mod file_meta_tests {
         use super::*;
 
+        const SHA256: &str = "f1d2d2f924e986ac86fdf7b36c94bcdf32beec15a6a3e5d5e4e0c8f0f8b4a2c1";
+
+        fn file(kind: u16, tags: &[&[&str]]) -> ParsedEvent {
+            ParsedEvent {
+                id: "f376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65".to_string(),
+                pubkey: "6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93"
+                    .to_string(),
+                created_at: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+                kind,
+                content: "My upload".to_string(),
+                sig: String::new(),
+                tags: tags
+                    .iter()
+                    .map(|t| t.iter().map(|s| s.to_string()).collect())
+                    .collect(),
+                received_at: None,
+            }
+        }
+
         #[test]
         fn from_event_extracts_all_fields() {
-            let event = ParsedEvent::from_json(VIDEO_EVENT_JSON).unwrap();
-            let meta = VideoMeta::from_event(&event).unwrap();
+            let upper = SHA256.to_uppercase();
+            let event = file(
+                KIND_FILE_METADATA,
+                &[
+                    &["url", "https://cdn.example.com/video.mp4"],
+                    &["m", "video/mp4"],
+                    &["x", &upper],
+                    &["ox", SHA256],
+                    &["size", "1048576"],
+                    &["dim", "1920x1080"],
+                    &["blurhash", "LEHV6nWB2yk8pyo0adR*.7kCMdnj"],
+                    &["thumb", "https://cdn.example.com/thumb.jpg"],
+                    &["alt", "A cat"],
+                ],
+            );
 
-            assert_eq!(meta.d_tag, "my-video-id");
-            assert_eq!(meta.title, Some("My Cool Video".to_string()));
             assert_eq!(
-                meta.thumbnail,
-                Some("https://example.com/thumb.jpg".to_string())
+                FileMeta::from_event(&event).unwrap(),
+                FileMeta {
+                    url: Some("https://cdn.example.com/video.mp4".to_string()),
+                    mime_type: Some("video/mp4".to_string()),
+                    sha256: Some(SHA256.to_string()),
+                    original_sha256: Some(SHA256.to_string()),
+                    size: Some(1_048_576),
+                    dim: Some("1920x1080".to_string()),
+                    blurhash: Some("LEHV6nWB2yk8pyo0adR*.7kCMdnj".to_string()),
+                    thumb: Some("https://cdn.example.com/thumb.jpg".to_string()),
+                    alt: Some("A cat".to_string()),
+                }
             );
+        }
+
+        #[test]
+        fn from_event_skips_malformed_fields_and_other_kinds() {
+            let tags: &[&[&str]] = &[&["x", "not-a-hash"], &["size", "big"], &["m", " "]];
             assert_eq!(
-                meta.video_url,
-                Some("https://example.com/video.mp4".to_string())
+                FileMeta::from_event(&file(KIND_FILE_METADATA, tags)),
+                Some(FileMeta::default())
             );
-            assert_eq!(meta.hashtags, vec!["nostr", "bitcoin"]);
+            assert_eq!(FileMeta::from_event(&file(KIND_VIDEO, tags)), None);
         }
 
         #[test]
-        fn from_event_handles_minimal_video() {
-            let event = ParsedEvent::from_json(SHORT_VIDEO_EVENT_JSON).unwrap();
-            let meta = VideoMeta::from_event(&event).unwrap();
+        fn from_imeta_reads_same_fields() {
+            let tag: Vec<String> = [
+                "imeta",
+                "url https://cdn.example.com/video.mp4",
+                &format!("x {SHA256}"),
+                "size 2048",
+                "blurhash LEHV6nWB2yk8pyo0adR*.7kCMdnj",
+            ]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+            let ParsedTag::Imeta(imeta) = ParsedTag::parse(&tag).unwrap() else {
+                panic!("expected imeta");
+            };
 
-            assert_eq!(meta.d_tag, "my-short-id");
-            assert_eq!(meta.title, Some("Short clip".to_string()));
-            assert_eq!(meta.thumbnail, None);
-            assert_eq!(meta.video_url, None);
-            assert!(meta.hashtags.is_empty());
+            let meta = FileMeta::from_imeta(&imeta);
+            assert_eq!(meta.sha256.as_deref(), Some(SHA256));
+            assert_eq!(meta.size, Some(2048));
+            assert_eq!(
+                meta.blurhash.as_deref(),
+                Some("LEHV6nWB2yk8pyo0adR*.7kCMdnj")
+            );
+        }
+    }
+
+    mod zap_meta_tests {
+        use super::*;
+
+        const VIDEO_ID: &str = "a376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65";
+        const RECIPIENT: &str = "6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93";
+        const SENDER: &str = "bf2376e17ba4ec269d10fcc996a4746b451152be9031fa48e74553dde5526bce";
+        const BOLT11: &str = "lnbc2500u1pvjluezpp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdq5xysxxatsyp3k7enxv4jsxqzpuaztrnwngzn3kdzw5hydlzf03qdgm2hdq27cqv3agm2awhz5se903vruatfhq77w3ls4evs3ch9zw97j25emudupq63nyw24cg27h2rspfj9srp";
+
+        fn receipt(kind: u16, tags: &[&[&str]]) -> ParsedEvent {
+            ParsedEvent {
+                id: "f376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65".to_string(),
+                pubkey: "9e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93"
+                    .to_string(),
+                created_at: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+                kind,
+                content: String::new(),
+                sig: String::new(),
+                tags: tags
+                    .iter()
+                    .map(|t| t.iter().map(|s| s.to_string()).collect())
+                    .collect(),
+                received_at: None,
+            }
         }
 
         #[test]
-        fn from_event_returns_none_for_non_video() {
-            let event = ParsedEvent::from_json(VALID_EVENT_JSON).unwrap();
-            let meta = VideoMeta::from_event(&event);
-            assert!(meta.is_none());
+        fn from_event_extracts_target_and_amount() {
+            let address = format!("34235:{RECIPIENT}:my-video-id");
+            let event = receipt(
+                KIND_ZAP_RECEIPT,
+                &[
+                    &["p", &RECIPIENT.to_uppercase()],
+                    &["P", SENDER],
+                    &["e", VIDEO_ID],
+                    &["a", &address],
+                    &["bolt11", BOLT11],
+                    &["description", "{}"],
+                ],
+            );
+
+            let meta = ZapMeta::from_event(&event).unwrap();
+            assert_eq!(meta.event_id.as_deref(), Some(VIDEO_ID));
+            assert_eq!(meta.address, EventAddress::parse(&address));
+            assert_eq!(meta.recipient, RECIPIENT);
+            assert_eq!(meta.sender.as_deref(), Some(SENDER));
+            assert_eq!(meta.bolt11, BOLT11);
+            assert_eq!(meta.amount_msats, Some(250_000_000));
         }
 
         #[test]
-        fn from_event_returns_none_without_d_tag() {
-            // Video event without d tag
-            let json = r#"{
-                "id": "c376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65",
-                "pubkey": "6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93",
-                "created_at": 1700000000,
-                "kind": 34235,
-                "tags": [["title", "No D Tag"]],
-                "content": "",
-                "sig": "908a15e46fb4d8675bab026fc230a0e3542bfade63da02d542fb78b2a8513fcd0092619a2c8c1221e581946e0191f2af505dfdf8657a414dbca329186f009262"
-            }"#;
+        fn from_event_rejects_other_kinds_and_missing_tags() {
+            let tags: &[&[&str]] = &[&["p", RECIPIENT], &["bolt11", BOLT11]];
+            assert!(ZapMeta::from_event(&receipt(KIND_ZAP_RECEIPT, tags)).is_some());
+            assert!(ZapMeta::from_event(&receipt(9734, tags)).is_none());
+            assert!(
+                ZapMeta::from_event(&receipt(KIND_ZAP_RECEIPT, &[&["p", RECIPIENT]])).is_none()
+            );
+            assert!(
+                ZapMeta::from_event(&receipt(KIND_ZAP_RECEIPT, &[&["bolt11", BOLT11]])).is_none()
+            );
+        }
 
-            let event = ParsedEvent::from_json(json).unwrap();
-            let meta = VideoMeta::from_event(&event);
-            assert!(meta.is_none());
+        #[test]
+        fn bolt11_amount_reads_multipliers() {
+            assert_eq!(bolt11_amount_msats("lnbc1pvjluez"), None);
+            assert_eq!(
+                bolt11_amount_msats("lnbc21pvjluez"),
+                Some(2 * 100_000_000_000)
+            );
+            assert_eq!(bolt11_amount_msats("lnbc20m1pvjluez"), Some(2_000_000_000));
+            assert_eq!(bolt11_amount_msats("LNBC2500U1PVJLUEZ"), Some(250_000_000));
+            assert_eq!(bolt11_amount_msats("lntb10n1pvjluez"), Some(1_000));
+            assert_eq!(bolt11_amount_msats("lnbcrt10p1pvjluez"), Some(1));
+            assert_eq!(
+                bolt11_amount_msats("lightning:lnbc1u1pvjluez"),
+                Some(100_000)
+            );
         }
 
         #[test]
-        fn from_event_uses_thumbnail_fallback() {
-            // Video with "thumbnail" instead of "thumb"
-            let json = r#"{
-                "id": "d376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65",
-                "pubkey": "6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93",
-                "created_at": 1700000000,
-                "kind": 34235,
-                "tags": [
-                    ["d", "test"],
-                    ["thumbnail", "https://example.com/alt-thumb.jpg"]
+        fn bolt11_amount_rejects_malformed_invoices() {
+            assert_eq!(bolt11_amount_msats(""), None);
+            assert_eq!(bolt11_amount_msats("not an invoice"), None);
+            assert_eq!(bolt11_amount_msats("lnbc15p1pvjluez"), None);
+            assert_eq!(bolt11_amount_msats("lnbc10x1pvjluez"), None);
+            assert_eq!(bolt11_amount_msats("lnbcu1pvjluez"), None);
+            assert_eq!(bolt11_amount_msats("lnbc99999999999999999991pvjluez"), None);
+        }
+    }
+
+    mod watch_meta_tests {
+        use super::*;
+
+        const AUTHOR: &str = "6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93";
+        const VIDEO_ID: &str = "a376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65";
+
+        fn session(kind: u16, tags: &[&[&str]]) -> ParsedEvent {
+            ParsedEvent {
+                id: "f376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65".to_string(),
+                pubkey: "bf2376e17ba4ec269d10fcc996a4746b451152be9031fa48e74553dde5526bce"
+                    .to_string(),
+                created_at: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+                kind,
+                content: String::new(),
+                sig: String::new(),
+                tags: tags
+                    .iter()
+                    .map(|t| t.iter().map(|s| s.to_string()).collect())
+                    .collect(),
+                received_at: None,
+            }
+        }
+
+        #[test]
+        fn from_event_extracts_session() {
+            let address = format!("34235:{AUTHOR}:my-video-id");
+            let event = session(
+                KIND_WATCH_SESSION,
+                &[
+                    &["d", "session-1"],
+                    &["a", &address],
+                    &["watched", "42.5"],
+                    &["e", &VIDEO_ID.to_uppercase()],
                 ],
-                "content": "",
-                "sig": "908a15e46fb4d8675bab026fc230a0e3542bfade63da02d542fb78b2a8513fcd0092619a2c8c1221e581946e0191f2af505dfdf8657a414dbca329186f009262"
-            }"#;
+            );
 
-            let event = ParsedEvent::from_json(json).unwrap();
-            let meta = VideoMeta::from_event(&event).unwrap();
+            let meta = WatchMeta::from_event(&event).unwrap();
+            assert_eq!(meta.session_id, "session-1");
+            assert_eq!(meta.video.to_string(), address);
+            assert_eq!(meta.watched_secs, 42.5);
+            assert_eq!(meta.video_event_id.as_deref(), Some(VIDEO_ID));
+        }
+
+        #[test]
+        fn from_event_rejects_incomplete_sessions() {
+            let video = format!("34235:{AUTHOR}:my-video-id");
+            let set = format!("30005:{AUTHOR}:my-set");
+            let cases: [&[&[&str]]; 5] = [
+                &[&["a", &video], &["watched", "10"]],
+                &[&["d", "s"], &["watched", "10"]],
+                &[&["d", "s"], &["a", &set], &["watched", "10"]],
+                &[&["d", "s"], &["a", &video], &["watched", "-1"]],
+                &[&["d", "s"], &["a", &video], &["watched", "NaN"]],
+            ];
+
+            for tags in cases {
+                assert_eq!(
+                    WatchMeta::from_event(&session(KIND_WATCH_SESSION, tags)),
+                    None,
+                    "{tags:?}"
+                );
+            }
+            let tags: &[&[&str]] = &[&["d", "s"], &["a", &video], &["watched", "10"]];
+            assert!(WatchMeta::from_event(&session(KIND_WATCH_SESSION, tags)).is_some());
+            assert_eq!(WatchMeta::from_event(&session(KIND_VIDEO, tags)), None);
+        }
+    }
+
+    mod live_stream_meta_tests {
+        use super::*;
+
+        const HOST: &str = "6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93";
+        const GUEST: &str = "bf2376e17ba4ec269d10fcc996a4746b451152be9031fa48e74553dde5526bce";
+
+        fn stream(kind: u16, tags: &[&[&str]]) -> ParsedEvent {
+            ParsedEvent {
+                id: "f376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65".to_string(),
+                pubkey: HOST.to_string(),
+                created_at: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+                kind,
+                content: String::new(),
+                sig: String::new(),
+                tags: tags
+                    .iter()
+                    .map(|t| t.iter().map(|s| s.to_string()).collect())
+                    .collect(),
+                received_at: None,
+            }
+        }
+
+        #[test]
+        fn from_event_extracts_all_fields() {
+            let event = stream(
+                KIND_LIVE_STREAM,
+                &[
+                    &["d", "weekly-show"],
+                    &["title", "Weekly Show"],
+                    &["summary", "News and chat"],
+                    &["image", "https://example.com/cover.jpg"],
+                    &["streaming", "https://example.com/live.m3u8"],
+                    &["starts", "1700000000"],
+                    &["ends", "1700003600"],
+                    &["status", "live"],
+                    &["current_participants", "12"],
+                    &["total_participants", "40"],
+                    &["p", HOST, "wss://relay.example.com", "Host"],
+                    &["p", &GUEST.to_uppercase(), "", "Speaker", "proof"],
+                    &["p", "not-a-pubkey", "", "Speaker"],
+                    &["t", "news"],
+                ],
+            );
+
+            let meta = LiveStreamMeta::from_event(&event).unwrap();
+            assert_eq!(meta.d_tag, "weekly-show");
+            assert_eq!(meta.title.as_deref(), Some("Weekly Show"));
+            assert_eq!(meta.summary.as_deref(), Some("News and chat"));
+            assert_eq!(meta.image.as_deref(), Some("https://example.com/cover.jpg"));
             assert_eq!(
-                meta.thumbnail,
-                Some("https://example.com/alt-thumb.jpg".to_string())
+                meta.streaming_url.as_deref(),
+                Some("https://example.com/live.m3u8")
+            );
+            assert_eq!(meta.recording_url, None);
+            assert_eq!(meta.starts, DateTime::from_timestamp(1_700_000_000, 0));
+            assert_eq!(meta.ends, DateTime::from_timestamp(1_700_003_600, 0));
+            assert_eq!(meta.status, Some(LiveStatus::Live));
+            assert_eq!(meta.current_participants, Some(12));
+            assert_eq!(meta.total_participants, Some(40));
+            assert_eq!(
+                meta.participants,
+                vec![
+                    Participant {
+                        pubkey: HOST.to_string(),
+                        relay: Some("wss://relay.example.com".to_string()),
+                        role: Some("Host".to_string()),
+                    },
+                    Participant {
+                        pubkey: GUEST.to_string(),
+                        relay: None,
+                        role: Some("Speaker".to_string()),
+                    },
+                ]
+            );
+            assert_eq!(meta.hashtags, vec!["news"]);
+        }
+
+        #[test]
+        fn from_event_handles_minimal_stream() {
+            let meta = LiveStreamMeta::from_event(&stream(
+                KIND_LIVE_STREAM,
+                &[
+                    &["d", "show"],
+                    &["status", "paused"],
+                    &["starts", "tomorrow"],
+                ],
+            ))
+            .unwrap();
+
+            assert_eq!(meta.status, None);
+            assert_eq!(meta.starts, None);
+            assert!(meta.participants.is_empty());
+        }
+
+        #[test]
+        fn from_event_rejects_other_kinds_and_missing_d_tag() {
+            assert!(LiveStreamMeta::from_event(&stream(KIND_VIDEO, &[&["d", "show"]])).is_none());
+            assert!(
+                LiveStreamMeta::from_event(&stream(KIND_LIVE_STREAM, &[&["title", "Show"]]))
+                    .is_none()
+            );
+        }
+    }
+
+    mod video_set_tests {
+        use super::*;
+
+        const PUBKEY: &str = "6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93";
+
+        fn tag(values: &[&str]) -> Vec<String> {
+            values.iter().map(|s| s.to_string()).collect()
+        }
+
+        #[test]
+        fn event_address_parses_coordinate_with_colons() {
+            let address = EventAddress::parse(&format!("30005:{PUBKEY}:faves:2024")).unwrap();
+
+            assert_eq!(address.kind, KIND_VIDEO_SET);
+            assert_eq!(address.pubkey, PUBKEY);
+            assert_eq!(address.identifier, "faves:2024");
+        }
+
+        #[test]
+        fn event_address_round_trips_through_naddr() {
+            let address = EventAddress {
+                kind: KIND_VIDEO_SET,
+                pubkey: PUBKEY.to_string(),
+                identifier: "faves".to_string(),
+            };
+
+            let naddr = address.to_naddr().unwrap();
+            assert!(naddr.starts_with("naddr1"));
+            assert_eq!(EventAddress::parse(&naddr), Some(address.clone()));
+            assert_eq!(
+                EventAddress::parse(&format!("nostr:{naddr}")),
+                Some(address)
+            );
+        }
+
+        #[test]
+        fn event_address_rejects_garbage() {
+            assert_eq!(EventAddress::parse("not-an-address"), None);
+            assert_eq!(EventAddress::parse("30005:not-a-pubkey:faves"), None);
+        }
+
+        #[test]
+        fn video_set_lists_videos_in_tag_order() {
+            let event_id = "a376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65";
+            let tags = vec![
+                tag(&["d", "faves"]),
+                tag(&["title", "Favourites"]),
+                tag(&["image", "https://example.com/cover.jpg"]),
+                tag(&["e", event_id]),
+                tag(&["a", &format!("34235:{PUBKEY}:my-video-id")]),
+                // Not a video kind
+                tag(&["a", &format!("30023:{PUBKEY}:article")]),
+                tag(&["e", "not-an-id"]),
+            ];
+
+            let set = VideoSet::from_tags(&tags);
+            assert_eq!(set.title.as_deref(), Some("Favourites"));
+            assert_eq!(set.image.as_deref(), Some("https://example.com/cover.jpg"));
+            assert_eq!(set.description, None);
+            assert_eq!(
+                set.videos,
+                vec![
+                    VideoRef::Event(event_id.to_string()),
+                    VideoRef::Address(EventAddress {
+                        kind: KIND_VIDEO,
+                        pubkey: PUBKEY.to_string(),
+                        identifier: "my-video-id".to_string(),
+                    }),
+                ]
             );
         }
+
+        #[test]
+        fn video_set_from_event_checks_kind() {
+            let mut event = ParsedEvent::from_json(VIDEO_EVENT_JSON).unwrap();
+            assert_eq!(VideoSet::from_event(&event), None);
+
+            event.kind = KIND_VIDEO_SET;
+            event.tags = vec![
+                tag(&["d", "faves"]),
+                tag(&["title", "Favourites"]),
+                tag(&["a", &format!("34235:{PUBKEY}:my-video-id")]),
+            ];
+            let set = VideoSet::from_event(&event).unwrap();
+            assert_eq!(set.title.as_deref(), Some("Favourites"));
+            assert_eq!(set.videos.len(), 1);
+        }
+
+        #[test]
+        fn video_set_falls_back_to_name_tag() {
+            let set = VideoSet::from_tags(&[tag(&["name", "Old style"])]);
+            assert_eq!(set.title.as_deref(), Some("Old style"));
+            assert!(set.videos.is_empty());
+        }
+    }
+
+    mod relay_url_tests {
+        use super::*;
+
+        #[test]
+        fn normalize_relay_url_canonicalizes() {
+            for (url, expected) in [
+                ("wss://relay.example.com", "wss://relay.example.com"),
+                (" WSS://Relay.Example.COM/ ", "wss://relay.example.com"),
+                ("wss://relay.example.com:443/", "wss://relay.example.com"),
+                ("ws://localhost:7777", "ws://localhost:7777"),
+                ("ws://127.0.0.1:80", "ws://127.0.0.1"),
+                (
+                    "wss://relay.example.com/nostr/#frag",
+                    "wss://relay.example.com/nostr",
+                ),
+            ] {
+                assert_eq!(normalize_relay_url(url).as_deref(), Some(expected), "{url}");
+            }
+        }
+
+        #[test]
+        fn normalize_relay_url_rejects_non_relays() {
+            for url in [
+                "",
+                "relay.example.com",
+                "https://relay.example.com",
+                "wss://",
+                "wss://user@relay.example.com",
+                "wss://relay example.com",
+            ] {
+                assert_eq!(normalize_relay_url(url), None, "{url}");
+            }
+        }
+
+        #[test]
+        fn relay_hints_collects_e_a_and_r_tags() {
+            let mut event = ParsedEvent::from_json(VIDEO_EVENT_JSON).unwrap();
+            let id = "a376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65";
+            for tag in [
+                &["e", id, "wss://one.example.com/"][..],
+                &["e", id, ""],
+                &["a", "34235:abc:d", "wss://two.example.com"],
+                &["r", "WSS://one.example.com"],
+                &["r", "https://example.com/article"],
+                &["p", id, "wss://three.example.com"],
+            ] {
+                event.tags.push(tag.iter().map(|s| s.to_string()).collect());
+            }
+
+            assert_eq!(
+                event.relay_hints(),
+                vec!["wss://one.example.com", "wss://two.example.com"]
+            );
+        }
+    }
+
+    mod identifier_tests {
+        use nostr::nips::nip19::Nip19Profile;
+
+        use super::*;
+
+        const EVENT_ID: &str = "a376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65";
+        const PUBKEY: &str = "6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93";
+
+        #[test]
+        fn normalize_event_id_accepts_hex_and_bech32() {
+            let id = EventId::from_hex(EVENT_ID).unwrap();
+            let note = id.to_bech32().unwrap();
+            let nevent = Nip19Event::new(id, Vec::<String>::new())
+                .to_bech32()
+                .unwrap();
+
+            let expected = Some(EVENT_ID.to_string());
+            assert_eq!(normalize_event_id(EVENT_ID), expected);
+            assert_eq!(normalize_event_id(&EVENT_ID.to_uppercase()), expected);
+            assert_eq!(normalize_event_id(&note), expected);
+            assert_eq!(normalize_event_id(&format!("nostr:{nevent}")), expected);
+        }
+
+        #[test]
+        fn normalize_pubkey_accepts_hex_and_bech32() {
+            let pubkey = PublicKey::from_hex(PUBKEY).unwrap();
+            let npub = pubkey.to_bech32().unwrap();
+            let nprofile = Nip19Profile::new(pubkey, Vec::<String>::new())
+                .unwrap()
+                .to_bech32()
+                .unwrap();
+
+            let expected = Some(PUBKEY.to_string());
+            assert_eq!(normalize_pubkey(&PUBKEY.to_uppercase()), expected);
+            assert_eq!(normalize_pubkey(&npub), expected);
+            assert_eq!(normalize_pubkey(&nprofile), expected);
+        }
+
+        #[test]
+        fn encode_round_trips_through_normalize() {
+            let npub = encode_npub(PUBKEY).unwrap();
+            let note = encode_note(EVENT_ID).unwrap();
+            let nevent = encode_nevent(
+                EVENT_ID,
+                Some(PUBKEY),
+                &["wss://relay.example.com".to_string()],
+            )
+            .unwrap();
+
+            assert!(npub.starts_with("npub1"));
+            assert!(note.starts_with("note1"));
+            assert!(nevent.starts_with("nevent1"));
+            assert_eq!(normalize_pubkey(&npub).as_deref(), Some(PUBKEY));
+            assert_eq!(normalize_event_id(&note).as_deref(), Some(EVENT_ID));
+            assert_eq!(normalize_event_id(&nevent).as_deref(), Some(EVENT_ID));
+            match Nip19::from_bech32(&nevent).unwrap() {
+                Nip19::Event(event) => {
+                    assert_eq!(event.author.map(|a| a.to_hex()).as_deref(), Some(PUBKEY));
+                    assert_eq!(event.relays.len(), 1);
+                }
+                other => panic!("expected nevent, got {other:?}"),
+            }
+        }
+
+        #[test]
+        fn encode_rejects_invalid_hex() {
+            assert_eq!(encode_npub("not-a-pubkey"), None);
+            assert_eq!(encode_note(&EVENT_ID[..63]), None);
+            assert_eq!(encode_nevent(EVENT_ID, Some("not-a-pubkey"), &[]), None);
+        }
+
+        #[test]
+        fn normalize_rejects_garbage_and_wrong_entity() {
+            let npub = PublicKey::from_hex(PUBKEY).unwrap().to_bech32().unwrap();
+
+            assert_eq!(normalize_event_id("video123"), None);
+            assert_eq!(normalize_event_id(&EVENT_ID[..63]), None);
+            assert_eq!(normalize_event_id(&npub), None);
+            assert_eq!(normalize_pubkey(&format!("{PUBKEY}00")), None);
+            assert_eq!(normalize_pubkey("npub1garbage"), None);
+        }
     }
 
     mod strfry_message_tests {
         use super::*;
 
+        fn event_message(json: &str) -> StrfryEvent {
+            match StrfryMessage::from_json(json).unwrap() {
+                StrfryMessage::Event(event) => *event,
+                other => panic!("expected an event message, got {other:?}"),
+            }
+        }
+
         #[test]
         fn from_json_valid_message() {
-            let msg = StrfryMessage::from_json(STRFRY_MESSAGE_JSON).unwrap();
+            let msg = event_message(STRFRY_MESSAGE_JSON);
 
-            assert_eq!(msg.msg_type, "EVENT");
             assert_eq!(
                 msg.event.id.to_hex(),
                 "4376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65"
@@ -434,10 +2818,21 @@ mod tests {
             assert!(result.is_err());
         }
 
+        #[test]
+        fn verify_rejects_forged_id() {
+            // The fixture's ID isn't the hash of its contents
+            let msg = event_message(STRFRY_MESSAGE_JSON);
+            assert!(matches!(msg.verify(), Err(VerifyError::InvalidId)));
+            assert!(matches!(
+                msg.to_parsed_event().verify(),
+                Err(VerifyError::InvalidId)
+            ));
+        }
+
         #[test]
         fn to_parsed_event_converts_correctly() {
             let msg = StrfryMessage::from_json(STRFRY_MESSAGE_JSON).unwrap();
-            let event = msg.to_parsed_event();
+            let event = msg.to_parsed_event().unwrap();
 
             assert_eq!(
                 event.id,
@@ -445,6 +2840,10 @@ mod tests {
             );
             assert_eq!(event.kind, 1);
             assert_eq!(event.content, "Test");
+            assert_eq!(
+                event.received_at.map(|at| at.timestamp_millis()),
+                Some(1673347338123)
+            );
         }
 
         #[test]
@@ -462,10 +2861,38 @@ mod tests {
                 }
             }"#;
 
-            let msg = StrfryMessage::from_json(json).unwrap();
+            let msg = event_message(json);
             assert!(msg.received_at.is_none());
             assert!(msg.source_type.is_none());
             assert!(msg.source_info.is_none());
+            assert!(msg.to_parsed_event().received_at.is_none());
+        }
+
+        #[test]
+        fn from_json_reads_plugin_new_messages() {
+            let json = STRFRY_MESSAGE_JSON.replace(r#""type": "EVENT""#, r#""type": "new""#);
+            assert!(StrfryMessage::from_json(&json).unwrap().event().is_some());
+        }
+
+        #[test]
+        fn from_json_reads_non_event_messages() {
+            let id = "4376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65";
+
+            let msg = StrfryMessage::from_json(&format!(r#"{{"type":"DELETE","id":"{id}"}}"#));
+            assert!(matches!(msg, Ok(StrfryMessage::Delete { id: ref deleted }) if deleted == id));
+
+            let msg = StrfryMessage::from_json(&format!(
+                r#"{{"type":"OK","id":"{id}","accepted":false,"message":"blocked: spam"}}"#
+            ))
+            .unwrap();
+            assert!(matches!(
+                msg,
+                StrfryMessage::Ok { accepted: false, ref message, .. } if message == "blocked: spam"
+            ));
+            assert!(msg.to_parsed_event().is_none());
+
+            let msg = StrfryMessage::from_json(r#"{"type":"HEARTBEAT","at":1}"#).unwrap();
+            assert!(matches!(msg, StrfryMessage::Other));
         }
     }
 }
@@ -0,0 +1,178 @@
+//! "Latest version wins" for replaceable events.
+//!
+//! NIP-01 keeps one version of each replaceable event (kinds 0, 3, and
+//! 10000-19999, per kind and pubkey) and of each addressable event (kinds
+//! 30000-39999, per kind, pubkey, and `d` tag): the one with the newest
+//! `created_at`, or on a tie the lowest ID. [`LatestRevisions`] applies that rule
+//! to a stream of events, so backfills and the API agree on which video version
+//! is current.
+
+use std::collections::HashMap;
+
+use crate::{EventAddress, ParsedEvent};
+
+/// Whether `candidate` replaces `current`, two versions of the same replaceable
+/// event: it's newer, or as old with a lower ID.
+pub fn supersedes(candidate: &ParsedEvent, current: &ParsedEvent) -> bool {
+    candidate.created_at > current.created_at
+        || (candidate.created_at == current.created_at && candidate.id < current.id)
+}
+
+/// Events with older versions of replaceable events dropped.
+///
+/// Other events are all kept. Events keep the order in which each was first seen;
+/// a newer version takes the place of the one it replaces.
+#[derive(Debug, Clone, Default)]
+pub struct LatestRevisions {
+    events: Vec<ParsedEvent>,
+    slots: HashMap<EventAddress, usize>,
+}
+
+impl LatestRevisions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an event, returning whether it was kept: it isn't replaceable, or it's
+    /// the latest version seen so far.
+    pub fn insert(&mut self, event: ParsedEvent) -> bool {
+        let Some(address) = replaceable_address(&event) else {
+            self.events.push(event);
+            return true;
+        };
+
+        match self.slots.get(&address) {
+            Some(&slot) if !supersedes(&event, &self.events[slot]) => false,
+            Some(&slot) => {
+                self.events[slot] = event;
+                true
+            }
+            None => {
+                self.slots.insert(address, self.events.len());
+                self.events.push(event);
+                true
+            }
+        }
+    }
+
+    /// Number of events kept.
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn into_events(self) -> Vec<ParsedEvent> {
+        self.events
+    }
+}
+
+impl Extend<ParsedEvent> for LatestRevisions {
+    fn extend<I>(&mut self, events: I)
+    where
+        I: IntoIterator<Item = ParsedEvent>,
+    {
+        for event in events {
+            self.insert(event);
+        }
+    }
+}
+
+impl FromIterator<ParsedEvent> for LatestRevisions {
+    fn from_iter<I>(events: I) -> Self
+    where
+        I: IntoIterator<Item = ParsedEvent>,
+    {
+        let mut latest = Self::new();
+        latest.extend(events);
+        latest
+    }
+}
+
+/// `events` with older versions of replaceable events dropped; see
+/// [`LatestRevisions`].
+pub fn latest_revisions(events: impl IntoIterator<Item = ParsedEvent>) -> Vec<ParsedEvent> {
+    events
+        .into_iter()
+        .collect::<LatestRevisions>()
+        .into_events()
+}
+
+/// Key versions of a replaceable event share: its coordinate, with an empty
+/// identifier for replaceable (non-addressable) kinds.
+fn replaceable_address(event: &ParsedEvent) -> Option<EventAddress> {
+    if event.is_replaceable() {
+        Some(EventAddress {
+            kind: event.kind,
+            pubkey: event.pubkey.to_ascii_lowercase(),
+            identifier: String::new(),
+        })
+    } else {
+        event.coordinate()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use super::*;
+
+    const PUBKEY: &str = "6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93";
+
+    fn event(id: &str, kind: u16, d: &str, created_at: i64) -> ParsedEvent {
+        ParsedEvent {
+            id: id.repeat(64),
+            pubkey: PUBKEY.to_string(),
+            created_at: DateTime::from_timestamp(created_at, 0).unwrap(),
+            kind,
+            content: String::new(),
+            sig: String::new(),
+            tags: vec![vec!["d".to_string(), d.to_string()]],
+            received_at: None,
+        }
+    }
+
+    fn ids(events: &[ParsedEvent]) -> Vec<&str> {
+        events.iter().map(|event| &event.id[..1]).collect()
+    }
+
+    #[test]
+    fn keeps_latest_version_per_address() {
+        let events = latest_revisions([
+            event("a", 34235, "video", 100),
+            event("b", 34235, "other", 100),
+            event("c", 34235, "video", 200),
+            event("d", 34235, "video", 150),
+            event("e", 34236, "video", 50),
+        ]);
+
+        assert_eq!(ids(&events), ["c", "b", "e"]);
+    }
+
+    #[test]
+    fn breaks_ties_by_lowest_id() {
+        let mut latest = LatestRevisions::new();
+        assert!(latest.insert(event("b", 34235, "video", 100)));
+        assert!(latest.insert(event("a", 34235, "video", 100)));
+        assert!(!latest.insert(event("c", 34235, "video", 100)));
+        // The same version again doesn't replace itself
+        assert!(!latest.insert(event("a", 34235, "video", 100)));
+
+        assert_eq!(ids(&latest.into_events()), ["a"]);
+    }
+
+    #[test]
+    fn replaceable_kinds_ignore_d_tag_and_others_are_all_kept() {
+        let events = latest_revisions([
+            event("a", 0, "x", 100),
+            event("b", 0, "y", 200),
+            event("c", 7, "video", 100),
+            event("d", 7, "video", 200),
+        ]);
+
+        assert_eq!(ids(&events), ["b", "c", "d"]);
+    }
+}
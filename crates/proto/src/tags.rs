@@ -0,0 +1,256 @@
+//! Typed view of event tags.
+//!
+//! Events keep their tags as raw string arrays, which is what ClickHouse stores.
+//! [`ParsedTag`] reads the tags Funnel cares about into typed values, checking
+//! their shape so a malformed `e`, `p`, or `a` tag is caught once, at parse time,
+//! instead of by every consumer that looks it up.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{EventAddress, ParseError, is_hex_id};
+
+/// A tag read into its typed form.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParsedTag {
+    /// Identifier of an addressable event (`d`); empty when the tag has no value.
+    D(String),
+    /// Title (`title`).
+    Title(String),
+    /// Media URL (`url`).
+    Url(String),
+    /// Thumbnail URL (`thumb`, or the older `thumbnail`).
+    Thumb(String),
+    /// Media metadata (`imeta`, NIP-92).
+    Imeta(Imeta),
+    /// Hashtag (`t`).
+    Hashtag(String),
+    /// Reference to a pubkey (`p`).
+    PubkeyRef {
+        /// Lowercase hex pubkey.
+        pubkey: String,
+        relay: Option<String>,
+    },
+    /// Reference to an event (`e`).
+    EventRef {
+        /// Lowercase hex event ID.
+        id: String,
+        relay: Option<String>,
+        /// NIP-10 marker such as `root` or `reply`.
+        marker: Option<String>,
+    },
+    /// Reference to an addressable event (`a`).
+    AddressRef {
+        address: EventAddress,
+        relay: Option<String>,
+    },
+    /// Any other tag, kept as is.
+    Unknown(Vec<String>),
+}
+
+/// A pubkey an event references in a `p` tag.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Mention {
+    /// Lowercase hex pubkey.
+    pub pubkey: String,
+    /// Relay hint.
+    pub relay: Option<String>,
+}
+
+/// An event an event references in an `e` tag.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventRef {
+    /// Lowercase hex event ID.
+    pub id: String,
+    /// Relay hint.
+    pub relay: Option<String>,
+    /// NIP-10 marker such as `root` or `reply`.
+    pub marker: Option<String>,
+}
+
+/// Entries of an `imeta` tag, each a `<key> <value>` pair, in tag order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Imeta {
+    pub fields: Vec<(String, String)>,
+}
+
+impl Imeta {
+    /// Value of the first entry with `key`.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// URL of the media (`url`).
+    pub fn url(&self) -> Option<&str> {
+        self.get("url")
+    }
+
+    /// SHA-256 of the media file (`x`).
+    pub fn sha256(&self) -> Option<&str> {
+        self.get("x")
+    }
+
+    /// MIME type of the media (`m`).
+    pub fn mime_type(&self) -> Option<&str> {
+        self.get("m")
+    }
+}
+
+impl ParsedTag {
+    /// Read a raw tag array.
+    ///
+    /// Tags of known names must have the shape their NIP requires (a value, a hex
+    /// ID or pubkey, a `kind:pubkey:d` address, `key value` imeta entries), or this
+    /// returns [`ParseError::InvalidTag`]. Tags of other names are
+    /// [`ParsedTag::Unknown`].
+    pub fn parse(tag: &[String]) -> Result<Self, ParseError> {
+        let Some(name) = tag.first() else {
+            return Err(invalid(tag, "empty tag"));
+        };
+        let value = || {
+            tag.get(1)
+                .cloned()
+                .ok_or_else(|| invalid(tag, "missing value"))
+        };
+        let relay = || tag.get(2).filter(|relay| !relay.is_empty()).cloned();
+
+        Ok(match name.as_str() {
+            "d" => Self::D(tag.get(1).cloned().unwrap_or_default()),
+            "title" => Self::Title(value()?),
+            "url" => Self::Url(value()?),
+            "thumb" | "thumbnail" => Self::Thumb(value()?),
+            "t" => Self::Hashtag(value()?),
+            "imeta" => Self::Imeta(Imeta {
+                fields: tag[1..]
+                    .iter()
+                    .map(|entry| {
+                        entry
+                            .split_once(' ')
+                            .map(|(k, v)| (k.to_string(), v.to_string()))
+                            .ok_or_else(|| invalid(tag, "imeta entry without a value"))
+                    })
+                    .collect::<Result<_, _>>()?,
+            }),
+            "p" => Self::PubkeyRef {
+                pubkey: hex(tag, value()?)?,
+                relay: relay(),
+            },
+            "e" => Self::EventRef {
+                id: hex(tag, value()?)?,
+                relay: relay(),
+                marker: tag.get(3).filter(|marker| !marker.is_empty()).cloned(),
+            },
+            "a" => Self::AddressRef {
+                address: EventAddress::from_coordinate(&value()?)
+                    .ok_or_else(|| invalid(tag, "invalid address"))?,
+                relay: relay(),
+            },
+            _ => Self::Unknown(tag.to_vec()),
+        })
+    }
+}
+
+/// `value` lowercased if it's a 32-byte hex ID or pubkey.
+fn hex(tag: &[String], value: String) -> Result<String, ParseError> {
+    if is_hex_id(&value) {
+        Ok(value.to_ascii_lowercase())
+    } else {
+        Err(invalid(tag, "not a hex ID"))
+    }
+}
+
+fn invalid(tag: &[String], reason: &str) -> ParseError {
+    ParseError::InvalidTag(format!("{}: {reason}", tag.join(",")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: &str = "a376c65d2f232afbe9b882a35baa4f6fe8667c4e684749af565f981833ed6a65";
+    const PUBKEY: &str = "6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93";
+
+    fn parse(values: &[&str]) -> Result<ParsedTag, ParseError> {
+        let tag: Vec<String> = values.iter().map(|s| s.to_string()).collect();
+        ParsedTag::parse(&tag)
+    }
+
+    #[test]
+    fn parse_reads_known_tags() {
+        assert_eq!(
+            parse(&["d", "my-video"]).unwrap(),
+            ParsedTag::D("my-video".to_string())
+        );
+        assert_eq!(parse(&["d"]).unwrap(), ParsedTag::D(String::new()));
+        assert_eq!(
+            parse(&["thumbnail", "https://example.com/t.jpg"]).unwrap(),
+            ParsedTag::Thumb("https://example.com/t.jpg".to_string())
+        );
+        assert_eq!(
+            parse(&["e", &ID.to_uppercase(), "wss://relay.example.com", "root"]).unwrap(),
+            ParsedTag::EventRef {
+                id: ID.to_string(),
+                relay: Some("wss://relay.example.com".to_string()),
+                marker: Some("root".to_string()),
+            }
+        );
+        assert_eq!(
+            parse(&["p", PUBKEY, ""]).unwrap(),
+            ParsedTag::PubkeyRef {
+                pubkey: PUBKEY.to_string(),
+                relay: None,
+            }
+        );
+        assert_eq!(
+            parse(&["a", &format!("34235:{PUBKEY}:my-video")]).unwrap(),
+            ParsedTag::AddressRef {
+                address: EventAddress {
+                    kind: 34235,
+                    pubkey: PUBKEY.to_string(),
+                    identifier: "my-video".to_string(),
+                },
+                relay: None,
+            }
+        );
+        assert_eq!(
+            parse(&["client", "funnel"]).unwrap(),
+            ParsedTag::Unknown(vec!["client".to_string(), "funnel".to_string()])
+        );
+    }
+
+    #[test]
+    fn parse_reads_imeta_entries() {
+        let Ok(ParsedTag::Imeta(imeta)) = parse(&[
+            "imeta",
+            "url https://example.com/video.mp4",
+            "m video/mp4",
+            "x abc123",
+            "alt A video with spaces",
+        ]) else {
+            panic!("expected imeta");
+        };
+
+        assert_eq!(imeta.url(), Some("https://example.com/video.mp4"));
+        assert_eq!(imeta.mime_type(), Some("video/mp4"));
+        assert_eq!(imeta.sha256(), Some("abc123"));
+        assert_eq!(imeta.get("alt"), Some("A video with spaces"));
+        assert_eq!(imeta.get("dim"), None);
+    }
+
+    #[test]
+    fn parse_rejects_malformed_tags() {
+        for tag in [
+            &[][..],
+            &["title"],
+            &["e", "not-an-id"],
+            &["p", &PUBKEY[..63]],
+            &["a", "34235:not-a-pubkey:my-video"],
+            &["imeta", "url"],
+        ] {
+            let err = parse(tag).unwrap_err();
+            assert_eq!(err.code().as_str(), "FNL-PROTO-006", "{tag:?}");
+        }
+    }
+}
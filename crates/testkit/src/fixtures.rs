@@ -0,0 +1,92 @@
+//! Events for end-to-end tests.
+//!
+//! Fixtures are stored rows, not signed Nostr events: ClickHouse doesn't check
+//! signatures, so IDs only need to be unique and well-formed. Engagement fixtures
+//! reference their target with an `e` tag, as the engagement views expect.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use chrono::{DateTime, Duration, Utc};
+use funnel_clickhouse::EventRow;
+use funnel_proto::KIND_VIDEO;
+
+/// A valid public key, for fixtures whose pubkey ends up in `npub`s or `naddr`s.
+pub const PUBKEY: &str = "6e468422dfb74a5738702a8823b9b28168abab8655faacb6853cd0ee15deee93";
+
+/// A second valid public key, for engagement from someone other than the creator.
+pub const OTHER_PUBKEY: &str = "3bf0c63fcb93463407af97a5e5ee64fa883d107ef9e558472c4eb9aaaefa459d";
+
+/// Relay recorded as the source of fixtures.
+pub const RELAY: &str = "wss://relay.test";
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// A unique 64-character hex event ID.
+pub fn event_id() -> String {
+    format!("{:064x}", NEXT_ID.fetch_add(1, Ordering::Relaxed))
+}
+
+/// An event of `kind` with a fresh ID.
+pub fn event(
+    kind: u16,
+    pubkey: &str,
+    created_at: DateTime<Utc>,
+    tags: Vec<Vec<String>>,
+    content: &str,
+) -> EventRow {
+    EventRow {
+        id: event_id(),
+        pubkey: pubkey.to_string(),
+        created_at,
+        kind,
+        content: content.to_string(),
+        sig: "0".repeat(128),
+        tags,
+        relay_source: RELAY.to_string(),
+        ingested_at: None,
+    }
+}
+
+/// A video (kind 34235) with a title, video URL, and thumbnail, plus a `t` tag for
+/// each of `hashtags`.
+pub fn video(pubkey: &str, title: &str, created_at: DateTime<Utc>, hashtags: &[&str]) -> EventRow {
+    let d_tag = format!("video-{}", NEXT_ID.fetch_add(1, Ordering::Relaxed));
+    let mut tags = vec![
+        tag(&["d", &d_tag]),
+        tag(&["title", title]),
+        tag(&["url", &format!("https://cdn.example.com/{d_tag}.mp4")]),
+        tag(&["thumb", &format!("https://cdn.example.com/{d_tag}.jpg")]),
+    ];
+    tags.extend(hashtags.iter().map(|hashtag| tag(&["t", hashtag])));
+    event(KIND_VIDEO, pubkey, created_at, tags, "")
+}
+
+/// A reaction (kind 7) to `target`, a minute after it.
+pub fn reaction(target: &EventRow, pubkey: &str) -> EventRow {
+    engagement(7, target, pubkey, "+")
+}
+
+/// A comment (kind 1) replying to `target`, a minute after it.
+pub fn comment(target: &EventRow, pubkey: &str, content: &str) -> EventRow {
+    engagement(1, target, pubkey, content)
+}
+
+/// A repost (kind 6) of `target`, a minute after it.
+pub fn repost(target: &EventRow, pubkey: &str) -> EventRow {
+    engagement(6, target, pubkey, "")
+}
+
+fn engagement(kind: u16, target: &EventRow, pubkey: &str, content: &str) -> EventRow {
+    event(
+        kind,
+        pubkey,
+        target.created_at + Duration::minutes(1),
+        vec![tag(&["e", &target.id]), tag(&["p", &target.pubkey])],
+        content,
+    )
+}
+
+/// A tag from its values.
+pub fn tag(values: &[&str]) -> Vec<String> {
+    values.iter().map(|value| value.to_string()).collect()
+}
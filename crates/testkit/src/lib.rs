@@ -0,0 +1,91 @@
+//! Test harness for end-to-end tests against a real ClickHouse.
+//!
+//! [`TestClickHouse::start`] runs ClickHouse in a container, applies the schema the
+//! way `funnel migrate` does, and hands back a connected client, so tests can insert
+//! events and check what the queries and the API make of them. [`fixtures`] builds
+//! the events. The container is removed when the [`TestClickHouse`] is dropped.
+//!
+//! Starting a container needs Docker, so tests using the harness are `#[ignore]`d
+//! and run with:
+//!
+//! ```sh
+//! cargo test -p funnel-testkit -- --ignored
+//! ```
+
+pub mod fixtures;
+
+use funnel_clickhouse::{ClickHouseClient, ClickHouseConfig, ClickHouseError, Deployment};
+use testcontainers::runners::AsyncRunner;
+use testcontainers::{ContainerAsync, ImageExt, TestcontainersError};
+use testcontainers_modules::clickhouse::ClickHouse;
+use thiserror::Error;
+
+/// ClickHouse server version the tests run against.
+pub const CLICKHOUSE_TAG: &str = "24.8";
+
+/// Database the schema is applied to.
+pub const DATABASE: &str = "funnel_test";
+
+/// HTTP interface port inside the container.
+const HTTP_PORT: u16 = 8123;
+
+/// Errors starting the harness.
+#[derive(Debug, Error)]
+pub enum TestKitError {
+    #[error("failed to start ClickHouse container: {0}")]
+    Container(#[from] TestcontainersError),
+    #[error(transparent)]
+    ClickHouse(#[from] ClickHouseError),
+}
+
+/// A ClickHouse container with the Funnel schema applied.
+pub struct TestClickHouse {
+    client: ClickHouseClient,
+    config: ClickHouseConfig,
+    _container: ContainerAsync<ClickHouse>,
+}
+
+impl TestClickHouse {
+    /// Start a container and apply the self-hosted schema to [`DATABASE`].
+    pub async fn start() -> Result<Self, TestKitError> {
+        let container = ClickHouse::default()
+            .with_tag(CLICKHOUSE_TAG)
+            // Let the passwordless default user connect over the network
+            .with_env_var("CLICKHOUSE_SKIP_USER_SETUP", "1")
+            .start()
+            .await?;
+        let host = container.get_host().await?;
+        let port = container.get_host_port_ipv4(HTTP_PORT).await?;
+
+        let config = ClickHouseConfig {
+            url: format!("http://{host}:{port}"),
+            database: DATABASE.to_string(),
+            user: Some("default".to_string()),
+            password: None,
+            slow_query_threshold: None,
+            report_threshold: None,
+            hide_dead_media: false,
+            routes: Default::default(),
+            tenants: Default::default(),
+            tenant: None,
+        };
+        let client = ClickHouseClient::connect(&config).await?;
+        client.migrate(Deployment::SelfHosted).await?;
+
+        Ok(Self {
+            client,
+            config,
+            _container: container,
+        })
+    }
+
+    /// Client connected to [`DATABASE`].
+    pub fn client(&self) -> &ClickHouseClient {
+        &self.client
+    }
+
+    /// Configuration the client was built from, for code that connects on its own.
+    pub fn config(&self) -> &ClickHouseConfig {
+        &self.config
+    }
+}
@@ -0,0 +1,215 @@
+//! Insert → query → API flows against a real ClickHouse.
+//!
+//! Each test starts its own container; they need Docker and run with
+//! `cargo test -p funnel-testkit -- --ignored`.
+
+use axum_test::TestServer;
+use chrono::{Duration, Utc};
+use funnel_api::{ApiConfig, AppState, create_router};
+use funnel_clickhouse::rebuild::projections;
+use funnel_clickhouse::{
+    ClickHouseClient, ClickHouseConfig, Deployment, EventDeletion, EventRoutes,
+};
+use funnel_testkit::TestClickHouse;
+use funnel_testkit::fixtures::{self, OTHER_PUBKEY, PUBKEY};
+use metrics_exporter_prometheus::PrometheusBuilder;
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn migrations_can_be_reapplied() {
+    let clickhouse = TestClickHouse::start().await.unwrap();
+
+    assert!(clickhouse.client().check_schema().await.unwrap());
+    clickhouse
+        .client()
+        .migrate(Deployment::SelfHosted)
+        .await
+        .unwrap();
+    assert_eq!(clickhouse.client().get_event_count().await.unwrap(), 0);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn migrations_replace_the_old_trending_view() {
+    let clickhouse = TestClickHouse::start().await.unwrap();
+    let client = clickhouse.client();
+    client
+        .execute_ddl("DROP TABLE trending_videos")
+        .await
+        .unwrap();
+    client
+        .execute_ddl("CREATE VIEW trending_videos AS SELECT id FROM videos")
+        .await
+        .unwrap();
+
+    client.migrate(Deployment::SelfHosted).await.unwrap();
+
+    // Inserting fails unless trending_videos is a table again
+    client
+        .execute_ddl("INSERT INTO trending_videos (computed_at, id) VALUES (now(), 'video')")
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn inserted_videos_and_engagement_are_queryable() {
+    let clickhouse = TestClickHouse::start().await.unwrap();
+    let client = clickhouse.client();
+
+    let now = Utc::now();
+    let older = fixtures::video(PUBKEY, "Older video", now - Duration::hours(2), &[]);
+    let newer = fixtures::video(PUBKEY, "Newer video", now - Duration::hours(1), &[]);
+    client
+        .insert_events(&[
+            older.clone(),
+            newer.clone(),
+            fixtures::reaction(&newer, OTHER_PUBKEY),
+            fixtures::reaction(&newer, PUBKEY),
+            fixtures::comment(&newer, OTHER_PUBKEY, "Nice"),
+            fixtures::repost(&older, OTHER_PUBKEY),
+        ])
+        .await
+        .unwrap();
+
+    let recent = client.get_recent_videos(None, None, 10).await.unwrap();
+    let titles: Vec<_> = recent.iter().map(|video| video.title.as_str()).collect();
+    assert_eq!(titles, ["Newer video", "Older video"]);
+
+    let stats = client.get_video_stats(&newer.id).await.unwrap().unwrap();
+    assert_eq!(stats.reactions, 2);
+    assert_eq!(stats.comments, 1);
+    assert_eq!(stats.reposts, 0);
+    assert_eq!(client.get_video_count().await.unwrap(), 2);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn deleted_videos_leave_listings() {
+    let clickhouse = TestClickHouse::start().await.unwrap();
+    let client = clickhouse.client();
+
+    let kept = fixtures::video(PUBKEY, "Kept", Utc::now() - Duration::hours(2), &[]);
+    let deleted = fixtures::video(PUBKEY, "Deleted", Utc::now() - Duration::hours(1), &[]);
+    client
+        .insert_events(&[kept.clone(), deleted.clone()])
+        .await
+        .unwrap();
+    client
+        .insert_deletion(&EventDeletion {
+            event_id: deleted.id.clone(),
+            deleted_at: Utc::now(),
+            deleted_by: "admin".to_string(),
+            deletion_event_id: String::new(),
+            reason: "test".to_string(),
+        })
+        .await
+        .unwrap();
+
+    let recent = client.get_recent_videos(None, None, 10).await.unwrap();
+    assert_eq!(recent.len(), 1);
+    assert_eq!(recent[0].id, kept.id);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn routed_kinds_are_copied_to_narrow_tables() {
+    let clickhouse = TestClickHouse::start().await.unwrap();
+    let config = ClickHouseConfig {
+        routes: EventRoutes::parse("7=reactions").unwrap(),
+        ..clickhouse.config().clone()
+    };
+    let client = ClickHouseClient::from_config(&config).unwrap();
+
+    let video = fixtures::video(PUBKEY, "Routed", Utc::now(), &[]);
+    let reaction = fixtures::reaction(&video, OTHER_PUBKEY);
+    client
+        .insert_events(&[video.clone(), reaction.clone()])
+        .await
+        .unwrap();
+
+    // events_local still has every event
+    assert_eq!(client.get_event_count().await.unwrap(), 2);
+    let reactions: Vec<(String, String, String)> = clickhouse::Client::default()
+        .with_url(&config.url)
+        .with_database(&config.database)
+        .query("SELECT id, target_event_id, reaction FROM reactions")
+        .fetch_all()
+        .await
+        .unwrap();
+    assert_eq!(reactions, [(reaction.id, video.id, "+".to_string())]);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn api_serves_inserted_videos() {
+    let clickhouse = TestClickHouse::start().await.unwrap();
+    let video = fixtures::video(PUBKEY, "Served video", Utc::now(), &["funnel"]);
+    clickhouse
+        .client()
+        .insert_events(&[video.clone(), fixtures::reaction(&video, OTHER_PUBKEY)])
+        .await
+        .unwrap();
+
+    let handle = PrometheusBuilder::new().build_recorder().handle();
+    let app = create_router(
+        AppState::new(clickhouse.client().clone()),
+        handle,
+        ApiConfig::default(),
+    );
+    let server = TestServer::new(app).unwrap();
+
+    let response = server.get("/api/videos").await;
+    response.assert_status_ok();
+    let videos: Vec<serde_json::Value> = response.json();
+    assert_eq!(videos.len(), 1);
+    assert_eq!(videos[0]["title"], "Served video");
+
+    let response = server.get(&format!("/api/videos/{}/stats", video.id)).await;
+    response.assert_status_ok();
+    let stats: serde_json::Value = response.json();
+    assert_eq!(stats["id"], video.id);
+    assert_eq!(stats["reactions"], 1);
+}
+
+#[tokio::test]
+#[ignore = "needs Docker"]
+async fn rebuild_rederives_engagement_counts() {
+    let clickhouse = TestClickHouse::start().await.unwrap();
+    let client = clickhouse.client();
+
+    let video = fixtures::video(PUBKEY, "Rebuilt", Utc::now() - Duration::days(40), &[]);
+    let reaction = fixtures::reaction(&video, OTHER_PUBKEY);
+    // Inserted twice: the view counts both, the rebuild only one
+    client
+        .insert_events(&[video.clone(), reaction.clone()])
+        .await
+        .unwrap();
+    client.insert_events(&[reaction]).await.unwrap();
+    let stats = client.get_video_stats(&video.id).await.unwrap().unwrap();
+    assert_eq!(stats.reactions, 2);
+
+    let projection = projections(Deployment::SelfHosted.schema())
+        .into_iter()
+        .find(|projection| projection.target == "reaction_counts")
+        .unwrap();
+    client.create_rebuild_staging(&projection).await.unwrap();
+    let split = Utc::now() - Duration::days(30);
+    client
+        .rebuild_window(
+            &projection,
+            video.created_at - Duration::days(1),
+            Some(split),
+        )
+        .await
+        .unwrap();
+    client
+        .rebuild_window(&projection, split, None)
+        .await
+        .unwrap();
+    client.swap_rebuild_staging(&projection).await.unwrap();
+
+    let stats = client.get_video_stats(&video.id).await.unwrap().unwrap();
+    assert_eq!(stats.reactions, 1);
+    assert!(!client.rebuild_staging_exists(&projection).await.unwrap());
+}
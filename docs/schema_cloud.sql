@@ -33,7 +33,6 @@ USE nostr;
 -- =============================================================================
 
 -- -- Views (must drop before tables they depend on)
--- DROP VIEW IF EXISTS trending_videos;
 -- DROP VIEW IF EXISTS video_stats;
 -- DROP VIEW IF EXISTS video_hashtags;
 -- DROP VIEW IF EXISTS popular_video_hashtags;
@@ -52,12 +51,32 @@ USE nostr;
 -- DROP TABLE IF EXISTS reaction_counts_mv;
 -- DROP TABLE IF EXISTS comment_counts_mv;
 -- DROP TABLE IF EXISTS repost_counts_mv;
+-- DROP TABLE IF EXISTS zap_counts_mv;
+-- DROP TABLE IF EXISTS video_reports_mv;
 
 -- -- Base tables
 -- DROP TABLE IF EXISTS event_tags_flat_data;
 -- DROP TABLE IF EXISTS reaction_counts;
 -- DROP TABLE IF EXISTS comment_counts;
 -- DROP TABLE IF EXISTS repost_counts;
+-- DROP TABLE IF EXISTS zap_counts;
+-- DROP TABLE IF EXISTS video_reports;
+-- DROP TABLE IF EXISTS video_moderation;
+-- DROP TABLE IF EXISTS aggregate_rebuilds;
+-- DROP TABLE IF EXISTS relay_stats;
+-- DROP TABLE IF EXISTS events_rejected;
+-- DROP TABLE IF EXISTS trending_videos;
+-- DROP TABLE IF EXISTS pubkey_trust;
+-- DROP TABLE IF EXISTS video_duplicates;
+-- DROP TABLE IF EXISTS media_health;
+-- DROP TABLE IF EXISTS media_verification;
+-- DROP TABLE IF EXISTS profile_fetches;
+-- DROP TABLE IF EXISTS backfill_requests;
+-- DROP TABLE IF EXISTS watch_events;
+-- DROP TABLE IF EXISTS profiles;
+-- DROP TABLE IF EXISTS zap_receipts;
+-- DROP TABLE IF EXISTS reactions;
+-- DROP TABLE IF EXISTS event_deletions;
 -- DROP TABLE IF EXISTS events_local;
 
 -- =============================================================================
@@ -82,6 +101,7 @@ CREATE TABLE IF NOT EXISTS events_local (
     -- Metadata fields
     indexed_at DateTime DEFAULT now(),
     relay_source String DEFAULT '',
+    ingested_at Nullable(DateTime64(3)), -- When a relay or the live ingester received it; NULL for backfills
 
     -- Materialized columns for video events (computed at insert time)
     d_tag String MATERIALIZED arrayElement(arrayFilter(t -> t[1] = 'd', tags), 1)[2],
@@ -98,6 +118,9 @@ CREATE TABLE IF NOT EXISTS events_local (
 ORDER BY (id)
 SETTINGS index_granularity = 8192;
 
+-- Added after the first release; brings existing tables up to date.
+ALTER TABLE events_local ADD COLUMN IF NOT EXISTS ingested_at Nullable(DateTime64(3)) AFTER relay_source;
+
 -- =============================================================================
 -- TAG MATERIALIZED VIEW
 -- =============================================================================
@@ -143,12 +166,182 @@ FROM events_local
 ARRAY JOIN tags AS tag
 WHERE length(tag) >= 1;
 
+-- =============================================================================
+-- ROUTED EVENT TABLES (EVENT_ROUTES)
+-- =============================================================================
+
+-- Narrow copies of chosen kinds for cheap aggregation, written besides events_local
+-- when EVENT_ROUTES routes a kind to them (e.g. 7=reactions,9735=zap_receipts,
+-- 0=profiles,34237=watch_events). The ingester sends each event's id, pubkey,
+-- created_at, kind, relay_source, content, and tags; content and tags are
+-- EPHEMERAL, so only the columns derived from them are stored. Deletion requests
+-- aren't applied to these copies.
+
+-- Reactions (kind 7), one row per reaction. Per NIP-25 the target is the last tag
+-- of each name.
+CREATE TABLE IF NOT EXISTS reactions (
+    id String,
+    pubkey String,
+    created_at DateTime,
+    kind UInt16,
+    relay_source String,
+    content String EPHEMERAL '',
+    tags Array(Array(String)) EPHEMERAL [],
+    target_event_id String DEFAULT arrayElement(arrayFilter(t -> t[1] = 'e', tags), -1)[2],
+    target_pubkey String DEFAULT arrayElement(arrayFilter(t -> t[1] = 'p', tags), -1)[2],
+    target_address String DEFAULT arrayElement(arrayFilter(t -> t[1] = 'a', tags), -1)[2],
+    reaction LowCardinality(String) DEFAULT if(trimBoth(content) = '', '+', trimBoth(content))
+) ENGINE = ReplacingMergeTree
+ORDER BY (target_event_id, id);
+
+-- Zap receipts (kind 9735), one row per receipt. amount_msats is the amount the
+-- zap request asked for, 0 when it doesn't say; bolt11 holds the paid invoice.
+CREATE TABLE IF NOT EXISTS zap_receipts (
+    id String,
+    pubkey String,                -- Lightning service that issued the receipt
+    created_at DateTime,
+    kind UInt16,
+    relay_source String,
+    content String EPHEMERAL '',
+    tags Array(Array(String)) EPHEMERAL [],
+    recipient String DEFAULT arrayElement(arrayFilter(t -> t[1] = 'p', tags), 1)[2],
+    sender String DEFAULT arrayElement(arrayFilter(t -> t[1] = 'P', tags), 1)[2],
+    target_event_id String DEFAULT arrayElement(arrayFilter(t -> t[1] = 'e', tags), 1)[2],
+    target_address String DEFAULT arrayElement(arrayFilter(t -> t[1] = 'a', tags), 1)[2],
+    bolt11 String DEFAULT arrayElement(arrayFilter(t -> t[1] = 'bolt11', tags), 1)[2],
+    amount_msats UInt64 DEFAULT toUInt64OrZero(arrayElement(arrayFilter(
+        t -> t[1] = 'amount',
+        JSONExtract(arrayElement(arrayFilter(t -> t[1] = 'description', tags), 1)[2], 'tags', 'Array(Array(String))')
+    ), 1)[2])
+) ENGINE = ReplacingMergeTree
+ORDER BY (recipient, id);
+
+-- Profiles (kind 0), the latest per pubkey once merged (read with FINAL).
+CREATE TABLE IF NOT EXISTS profiles (
+    id String,
+    pubkey String,
+    created_at DateTime,
+    kind UInt16,
+    relay_source String,
+    content String EPHEMERAL '',
+    tags Array(Array(String)) EPHEMERAL [],
+    name String DEFAULT JSONExtractString(content, 'name'),
+    display_name String DEFAULT if(JSONExtractString(content, 'display_name') != '',
+        JSONExtractString(content, 'display_name'), JSONExtractString(content, 'displayName')),
+    picture String DEFAULT JSONExtractString(content, 'picture'),
+    nip05 String DEFAULT JSONExtractString(content, 'nip05'),
+    lud16 String DEFAULT JSONExtractString(content, 'lud16')
+) ENGINE = ReplacingMergeTree(created_at)
+ORDER BY (pubkey);
+
+-- Watch sessions (kind 34237), the latest total per session once merged (read
+-- with FINAL).
+CREATE TABLE IF NOT EXISTS watch_events (
+    id String,
+    pubkey String,                -- Viewer
+    created_at DateTime,
+    kind UInt16,
+    relay_source String,
+    content String EPHEMERAL '',
+    tags Array(Array(String)) EPHEMERAL [],
+    session_id String DEFAULT arrayElement(arrayFilter(t -> t[1] = 'd', tags), 1)[2],
+    video_address String DEFAULT arrayElement(arrayFilter(t -> t[1] = 'a', tags), 1)[2],
+    video_event_id String DEFAULT arrayElement(arrayFilter(t -> t[1] = 'e', tags), 1)[2],
+    watched_secs Float64 DEFAULT toFloat64OrZero(arrayElement(arrayFilter(t -> t[1] = 'watched', tags), 1)[2])
+) ENGINE = ReplacingMergeTree(created_at)
+ORDER BY (pubkey, session_id);
+
+-- =============================================================================
+-- OPERATIONS TABLES
+-- =============================================================================
+
+-- Tombstones for deleted events (operator tombstones via the admin API).
+-- Video views exclude any event listed here.
+CREATE TABLE IF NOT EXISTS event_deletions (
+    event_id String,
+    deleted_at DateTime,
+    deleted_by String,            -- Deletion request author pubkey, or 'admin'
+    deletion_event_id String,     -- Kind 5 event ID, empty for operator tombstones
+    reason String
+) ENGINE = ReplacingMergeTree(deleted_at)
+ORDER BY (event_id);
+
+-- Backfill windows queued through the admin API and run by the ingestion service.
+-- Each status change inserts a new row; FINAL keeps the latest by updated_at.
+CREATE TABLE IF NOT EXISTS backfill_requests (
+    id String,
+    since DateTime,
+    until DateTime,
+    kinds Array(UInt16),          -- Empty means all kinds
+    status LowCardinality(String), -- pending, running, done, failed
+    requested_at DateTime,
+    updated_at DateTime64(3)
+) ENGINE = ReplacingMergeTree(updated_at)
+ORDER BY (id);
+
+-- Operator moderation decisions made through the admin API.
+-- Each change inserts a new row; FINAL keeps the latest by updated_at.
+-- 'hidden' removes a video from listings; 'approved' keeps it listed despite reports.
+CREATE TABLE IF NOT EXISTS video_moderation (
+    event_id String,
+    status LowCardinality(String), -- hidden, approved
+    reason String,
+    updated_by String,            -- Admin token ID
+    updated_at DateTime64(3)
+) ENGINE = ReplacingMergeTree(updated_at)
+ORDER BY (event_id);
+
+-- Progress of `funnel rebuild-aggregates`, per rebuilt table. Each window inserts a
+-- new row; FINAL keeps the latest by updated_at.
+CREATE TABLE IF NOT EXISTS aggregate_rebuilds (
+    target String,                -- Table being rebuilt
+    rebuilt_until DateTime,       -- Events created before this are in the staging table
+    status LowCardinality(String), -- running, done
+    updated_at DateTime64(3)
+) ENGINE = ReplacingMergeTree(updated_at)
+ORDER BY (target);
+
+-- Per-relay counters from the live ingester, one row per relay every minute. Counts
+-- cover the minute since the previous row, so sum them over a window; connected and
+-- uptime_secs are the state at recorded_at. Served by GET /api/relays.
+CREATE TABLE IF NOT EXISTS relay_stats (
+    relay_url String,
+    recorded_at DateTime,
+    events_received UInt64,       -- Every event the relay sent, duplicates included
+    unique_events UInt64,         -- Events no relay had sent before
+    unique_videos UInt64,         -- Unique events of kinds 34235 and 34236
+    duplicates UInt64,            -- Events already received from this or another relay
+    parse_failures UInt64,        -- Unique events that couldn't be parsed
+    connected Bool,
+    uptime_secs UInt64            -- Seconds since the relay (re)connected, 0 when down
+) ENGINE = MergeTree()
+ORDER BY (relay_url, recorded_at)
+TTL recorded_at + INTERVAL 30 DAY;
+
+-- Events the live ingester received but didn't store, with why: code is the stable
+-- error code (e.g. an unparseable event or an invalid signature) and raw the event
+-- JSON as received, for debugging the client that published it. Kept for 14 days.
+CREATE TABLE IF NOT EXISTS events_rejected (
+    event_id String,              -- ID the event claims, which may not be its real one
+    pubkey String,
+    kind UInt16,
+    relay_source String,          -- Relay that delivered the event
+    rejected_at DateTime,
+    code String,                  -- Error code, e.g. FNL-PROTO-002
+    error String,
+    raw String                    -- Event JSON as received
+) ENGINE = MergeTree()
+ORDER BY (rejected_at, relay_source)
+TTL rejected_at + INTERVAL 14 DAY;
+
 -- =============================================================================
 -- VIDEO-SPECIFIC VIEWS (Kinds 34235, 34236)
 -- =============================================================================
 
--- Video events view using materialized columns (no runtime tag extraction)
-CREATE VIEW IF NOT EXISTS videos AS
+-- Video events view using materialized columns (no runtime tag extraction).
+-- Replaced on every migration so existing deployments get the deletion filter;
+-- video_stats reads through it.
+CREATE OR REPLACE VIEW videos AS
 SELECT
     id,
     pubkey,
@@ -163,7 +356,8 @@ SELECT
     thumbnail,
     video_url
 FROM events_local
-WHERE kind IN (34235, 34236);
+WHERE kind IN (34235, 34236)
+  AND id NOT IN (SELECT event_id FROM event_deletions);
 
 -- =============================================================================
 -- ENGAGEMENT METRICS (Materialized Views with SummingMergeTree)
@@ -225,6 +419,55 @@ WHERE kind IN (6, 16)
     AND tag[1] = 'e'
     AND length(tag) >= 2;
 
+-- Zap counts table
+CREATE TABLE IF NOT EXISTS zap_counts (
+    target_event_id String,
+    zap_count UInt64
+) ENGINE = SummingMergeTree()
+ORDER BY (target_event_id);
+
+-- Zap counts MV (kind 9735 = zap receipt)
+CREATE MATERIALIZED VIEW IF NOT EXISTS zap_counts_mv TO zap_counts
+AS SELECT
+    tag[2] AS target_event_id,
+    toUInt64(1) AS zap_count
+FROM events_local
+ARRAY JOIN tags AS tag
+WHERE kind = 9735
+    AND tag[1] = 'e'
+    AND length(tag) >= 2;
+
+-- =============================================================================
+-- MODERATION (NIP-56 reports)
+-- =============================================================================
+
+-- Reports per target event and report type. Listings hide videos reported by
+-- MODERATION_REPORT_THRESHOLD distinct pubkeys unless an operator approved them.
+-- NOTE: reporters is an aggregate state; read it with uniqMerge().
+CREATE TABLE IF NOT EXISTS video_reports (
+    target_event_id String,
+    report_type LowCardinality(String),
+    reports SimpleAggregateFunction(sum, UInt64),
+    reporters AggregateFunction(uniq, String),
+    last_reported_at SimpleAggregateFunction(max, DateTime)
+) ENGINE = AggregatingMergeTree()
+ORDER BY (target_event_id, report_type);
+
+-- Video reports MV (kind 1984 = report; type is the 'e' tag's third element)
+CREATE MATERIALIZED VIEW IF NOT EXISTS video_reports_mv TO video_reports
+AS SELECT
+    tag[2] AS target_event_id,
+    if(length(tag) >= 3 AND tag[3] != '', tag[3], 'other') AS report_type,
+    count() AS reports,
+    uniqState(pubkey) AS reporters,
+    max(created_at) AS last_reported_at
+FROM events_local
+ARRAY JOIN tags AS tag
+WHERE kind = 1984
+    AND tag[1] = 'e'
+    AND length(tag) >= 2
+GROUP BY target_event_id, report_type;
+
 -- =============================================================================
 -- VIDEO ANALYTICS VIEWS
 -- =============================================================================
@@ -263,15 +506,91 @@ LEFT JOIN (
     GROUP BY target_event_id
 ) rp ON v.id = rp.target_event_id;
 
--- Trending videos (recent videos with high engagement)
--- Uses 30-day window with time decay (newer content ranks higher)
-CREATE VIEW IF NOT EXISTS trending_videos AS
-SELECT
-    *,
-    engagement_score * exp(-dateDiff('hour', created_at, now()) / 168.0) AS trending_score
-FROM video_stats
-WHERE created_at > now() - INTERVAL 30 DAY
-ORDER BY trending_score DESC;
+-- Trending scores, computed by funnel-aggregator (see its TRENDING_* settings).
+-- Each run inserts a full snapshot stamped with computed_at; readers use the
+-- latest one. Older snapshots expire after a day.
+-- This used to be a view; `funnel migrate` drops it before creating the table.
+CREATE TABLE IF NOT EXISTS trending_videos (
+    computed_at DateTime,
+    id String,
+    pubkey String,
+    created_at DateTime,
+    kind UInt16,
+    d_tag String,
+    title String,
+    thumbnail String,
+    reactions UInt64,
+    comments UInt64,
+    reposts UInt64,
+    zaps UInt64,
+    engagement_score UInt64,
+    trending_score Float64
+) ENGINE = MergeTree()
+ORDER BY (computed_at, id)
+TTL computed_at + INTERVAL 1 DAY;
+
+-- Web-of-trust scores, computed by funnel-aggregator from contact lists (kind 3;
+-- see its WOT_* settings). Snapshots work like trending_videos. Pubkeys without
+-- trust are left out, and min_trust filters treat them as 0.
+CREATE TABLE IF NOT EXISTS pubkey_trust (
+    computed_at DateTime,
+    pubkey String,
+    trust Float64,                -- (0, 1], highest-trust pubkey is 1
+    followers UInt64
+) ENGINE = MergeTree()
+ORDER BY (computed_at, pubkey)
+TTL computed_at + INTERVAL 1 DAY;
+
+-- Re-uploads found by funnel-aggregator (see its DUPLICATES_* settings): videos whose
+-- file SHA-256 (from imeta) or title, ignoring case and punctuation, matches an
+-- earlier video by another pubkey. Snapshots work like trending_videos.
+CREATE TABLE IF NOT EXISTS video_duplicates (
+    computed_at DateTime,
+    event_id String,
+    duplicate_of String,          -- The earliest video with the same file or title
+    reason LowCardinality(String) -- sha256, title
+) ENGINE = MergeTree()
+ORDER BY (computed_at, event_id)
+TTL computed_at + INTERVAL 1 DAY;
+
+-- Media URL health checks, recorded by funnel-aggregator (see its MEDIA_CHECK_*
+-- settings). Each check inserts a new row; FINAL keeps the latest by checked_at.
+-- With MEDIA_HEALTH_HIDE_DEAD=true, listings leave out videos whose video URL has
+-- failed 3 checks in a row.
+CREATE TABLE IF NOT EXISTS media_health (
+    event_id String,
+    url String,
+    media_type LowCardinality(String), -- video, thumbnail
+    available Bool,
+    status UInt16,                -- HTTP status, 0 when the request failed
+    content_type String,
+    failures UInt32,              -- Consecutive failed checks
+    checked_at DateTime64(3)
+) ENGINE = ReplacingMergeTree(checked_at)
+ORDER BY (event_id, url);
+
+-- Video files checked against the SHA-256 in their imeta tag by funnel-aggregator
+-- (see its MEDIA_VERIFY_* settings). Each check inserts a new row; FINAL keeps the
+-- latest by checked_at.
+CREATE TABLE IF NOT EXISTS media_verification (
+    event_id String,
+    sha256 String,                -- Declared SHA-256 (lowercase hex)
+    url String,                   -- URL from the event
+    status LowCardinality(String), -- verified, mismatch, unavailable, too_large
+    source String,                -- URL the matching file was fetched from
+    checked_at DateTime64(3)
+) ENGINE = ReplacingMergeTree(checked_at)
+ORDER BY (event_id, sha256);
+
+-- Relay fetches of video authors' profiles (kind 0) and contact lists (kind 3) by
+-- funnel-aggregator (see its PROFILE_* settings). Each attempt inserts a new row;
+-- FINAL keeps the latest by fetched_at.
+CREATE TABLE IF NOT EXISTS profile_fetches (
+    pubkey String,
+    events UInt32,                -- Events the relays returned
+    fetched_at DateTime64(3)
+) ENGINE = ReplacingMergeTree(fetched_at)
+ORDER BY pubkey;
 
 -- Videos by hashtag
 CREATE VIEW IF NOT EXISTS video_hashtags AS